@@ -0,0 +1,35 @@
+/* The C ABI in src/ffi.rs, for embedding the viewer: link against the
+ * liblab4_gpc shared library that `cargo build --release` leaves in
+ * target/release. The comments there say what each function needs of its
+ * pointers. A panic inside comes back as the function's failure, after
+ * which the renderer is only good for lab4_gpc_destroy. */
+#ifndef LAB4_GPC_H
+#define LAB4_GPC_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+typedef struct Lab4GpcRenderer Lab4GpcRenderer;
+
+/* Frames drawn width by height, shaded on `threads` threads (0 counts as 1),
+ * with depth reversed as --reverse-z has it; null for a size of nothing or a
+ * renderer that couldn't be made */
+Lab4GpcRenderer *lab4_gpc_create(uint32_t width, uint32_t height, uint32_t threads,
+                                 bool reversed_z);
+
+/* Moons from a --scene file from the next frame on; false if it didn't read */
+bool lab4_gpc_load_scene(Lab4GpcRenderer *renderer, const char *path);
+
+/* Draws the next frame and fills width * height * 4 bytes of RGBA with the
+ * one before it, scaled to fit, then waits out the rest of the frame */
+bool lab4_gpc_render_rgba(Lab4GpcRenderer *renderer, uint8_t *buffer, uint32_t width,
+                          uint32_t height);
+
+/* The same, but fills the buffer with the frame just drawn and returns as
+ * soon as it's done */
+bool lab4_gpc_render_rgba_now(Lab4GpcRenderer *renderer, uint8_t *buffer, uint32_t width,
+                              uint32_t height);
+
+void lab4_gpc_destroy(Lab4GpcRenderer *renderer);
+
+#endif
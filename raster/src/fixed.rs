@@ -0,0 +1,89 @@
+use core::ops::{Add, Mul, Sub};
+
+// Signed 16.16 fixed point: 16 integer bits and 16 fraction bits in an i32,
+// for hardware where float math is slow or emulated. Products and quotients
+// go through an i64 so they stay exact before rounding back down.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    pub const FRAC_BITS: u32 = 16;
+    pub const ONE: Fixed = Fixed(1 << Self::FRAC_BITS);
+
+    pub const fn from_int(value: i32) -> Self {
+        Fixed(value << Self::FRAC_BITS)
+    }
+
+    // Rounds to the nearest representable value
+    pub fn from_f32(value: f32) -> Self {
+        let scaled = value * Self::ONE.0 as f32;
+        Fixed(if scaled < 0.0 {
+            (scaled - 0.5) as i32
+        } else {
+            (scaled + 0.5) as i32
+        })
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / Self::ONE.0 as f32
+    }
+
+    pub const fn floor(self) -> i32 {
+        self.0 >> Self::FRAC_BITS
+    }
+
+    // `self / divisor`, or None when the divisor is zero
+    pub fn checked_div(self, divisor: Fixed) -> Option<Fixed> {
+        if divisor.0 == 0 {
+            return None;
+        }
+        Some(Fixed(
+            (((self.0 as i64) << Self::FRAC_BITS) / divisor.0 as i64) as i32,
+        ))
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_add(other.0))
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * other.0 as i64) >> Self::FRAC_BITS) as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_round_trips_and_multiplies() {
+        assert_eq!(Fixed::from_f32(1.5), Fixed(0x18000));
+        assert_eq!(Fixed::from_f32(-2.25).to_f32(), -2.25);
+        assert_eq!(
+            (Fixed::from_f32(1.5) * Fixed::from_f32(-2.0)).to_f32(),
+            -3.0
+        );
+        assert_eq!(Fixed::from_f32(-0.5).floor(), -1);
+        assert_eq!(Fixed::ONE.checked_div(Fixed(0)), None);
+        assert_eq!(
+            Fixed::from_int(3).checked_div(Fixed::from_int(4)),
+            Some(Fixed::from_f32(0.75))
+        );
+    }
+}
@@ -0,0 +1,16 @@
+// Core of the software rasterizer: screen-space math, triangle traversal and
+// targets to draw them into. It only needs `core` and `alloc`, so it builds
+// for embedded targets with no operating system, e.g. to drive a small LCD.
+#![no_std]
+
+extern crate alloc;
+
+mod fixed;
+mod math;
+mod target;
+mod triangle;
+
+pub use fixed::Fixed;
+pub use math::{ceil, edge_function, floor, Point};
+pub use target::{Image, Scissor, Target};
+pub use triangle::{rasterize, rasterize_f32, rasterize_fixed, rasterize_subpixel, Quad};
@@ -0,0 +1,39 @@
+// A screen-space vertex: pixel coordinates plus depth
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Point {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Point { x, y, z }
+    }
+}
+
+// Twice the signed area of the triangle abc, positive when c lies to one side
+// of the line from a to b and negative on the other
+pub fn edge_function(a: &Point, b: &Point, c: &Point) -> f32 {
+    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
+}
+
+// `core` has no float rounding without std, but pixel coordinates only ever
+// need it as integers. Out-of-range values saturate like `as` does.
+pub fn floor(value: f32) -> i32 {
+    let truncated = value as i32;
+    if (truncated as f32) > value {
+        truncated - 1
+    } else {
+        truncated
+    }
+}
+
+pub fn ceil(value: f32) -> i32 {
+    let truncated = value as i32;
+    if (truncated as f32) < value {
+        truncated + 1
+    } else {
+        truncated
+    }
+}
@@ -0,0 +1,142 @@
+use crate::math::Point;
+use crate::triangle::rasterize;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Pixel rectangle that drawing is clipped to, max bounds exclusive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scissor {
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+}
+
+impl Scissor {
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.min_x && x < self.max_x && y >= self.min_y && y < self.max_y
+    }
+}
+
+// Somewhere triangles are drawn: pixels with a color each and a depth test
+// in front of them. `Image` is the plain one; a renderer with more buffers
+// behind its pixels implements it over those.
+pub trait Target {
+    type Color;
+
+    // Pixels drawing is limited to
+    fn scissor(&self) -> Scissor;
+
+    // Whether a sample at `depth` would be written at (x, y), without
+    // writing it
+    fn passes(&self, x: usize, y: usize, depth: f32) -> bool;
+
+    // Writes `color` if it passes; returns whether it did
+    fn write(&mut self, x: usize, y: usize, depth: f32, color: Self::Color) -> bool;
+
+    // Depth-tested triangle with `shade` picking each pixel's color from its
+    // barycentric weights, only for pixels that pass; enough for flat or
+    // Gouraud shading on a device with no room for the full fragment
+    // pipeline
+    fn fill_triangle(
+        &mut self,
+        a: Point,
+        b: Point,
+        c: Point,
+        mut shade: impl FnMut((f32, f32, f32)) -> Self::Color,
+    ) {
+        let scissor = self.scissor();
+        rasterize(a, b, c, &scissor, |quad| {
+            for lane in 0..4 {
+                if !quad.covered[lane] {
+                    continue;
+                }
+                let (w1, w2, w3) = quad.weights[lane];
+                let (x, y) = quad.lane_position(lane);
+                let (x, y) = (x as usize, y as usize);
+                let depth = a.z * w1 + b.z * w2 + c.z * w3;
+                if self.passes(x, y, depth) {
+                    let color = shade((w1, w2, w3));
+                    self.write(x, y, depth, color);
+                }
+            }
+        });
+    }
+}
+
+// Colors and depths for a `width` x `height` image, row by row from the top,
+// where nearer samples win
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub color: Vec<u32>,
+    pub depth: Vec<f32>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            color: vec![0; width * height],
+            depth: vec![f32::INFINITY; width * height],
+        }
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.color.fill(color);
+        self.depth.fill(f32::INFINITY);
+    }
+}
+
+impl Target for Image {
+    type Color = u32;
+
+    fn scissor(&self) -> Scissor {
+        Scissor {
+            min_x: 0,
+            min_y: 0,
+            max_x: self.width,
+            max_y: self.height,
+        }
+    }
+
+    fn passes(&self, x: usize, y: usize, depth: f32) -> bool {
+        depth < self.depth[y * self.width + x]
+    }
+
+    fn write(&mut self, x: usize, y: usize, depth: f32, color: u32) -> bool {
+        if !self.passes(x, y, depth) {
+            return false;
+        }
+        let index = y * self.width + x;
+        self.depth[index] = depth;
+        self.color[index] = color;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearer_triangles_win() {
+        let mut image = Image::new(8, 8);
+        image.clear(0);
+        let far = [(0.0, 0.0), (8.0, 0.0), (0.0, 8.0)].map(|(x, y)| Point::new(x, y, 0.8));
+        let near = [(0.0, 0.0), (8.0, 0.0), (0.0, 8.0)].map(|(x, y)| Point::new(x, y, 0.2));
+        image.fill_triangle(near[0], near[1], near[2], |_| 2);
+        let mut shaded = 0;
+        image.fill_triangle(far[0], far[1], far[2], |_| {
+            shaded += 1;
+            1
+        });
+        // Hidden everywhere, so never shaded
+        assert_eq!(shaded, 0);
+        assert_eq!(image.color[0], 2);
+        assert_eq!(image.depth[0], 0.2);
+        // Past the diagonal nothing was drawn
+        assert_eq!(image.color[63], 0);
+    }
+}
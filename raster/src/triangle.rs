@@ -0,0 +1,415 @@
+use crate::fixed::Fixed;
+use crate::math::{ceil, edge_function, floor, Point};
+use crate::target::Scissor;
+
+// A 2x2 block of pixels with its top-left corner at (x, y), lanes in the
+// order top-left, top-right, bottom-left, bottom-right. Every lane carries
+// barycentric weights, even ones outside the triangle, so covered pixels can
+// take finite differences against their neighbours.
+#[derive(Clone, Copy, Debug)]
+pub struct Quad {
+    pub x: i32,
+    pub y: i32,
+    pub weights: [(f32, f32, f32); 4],
+    // Inside the triangle and the scissor
+    pub covered: [bool; 4],
+}
+
+impl Quad {
+    pub fn lane_position(&self, lane: usize) -> (i32, i32) {
+        (self.x + (lane % 2) as i32, self.y + (lane / 2) as i32)
+    }
+}
+
+// Walks the triangle's bounding box, clipped to the scissor, in quads aligned
+// to even pixels like a GPU, and hands every quad that covers at least one
+// pixel to `visit`. Coverage and weights come from `rasterize_fixed` when the
+// `fixed` feature is on and from `rasterize_subpixel` otherwise.
+pub fn rasterize(a: Point, b: Point, c: Point, scissor: &Scissor, visit: impl FnMut(&Quad)) {
+    #[cfg(feature = "fixed")]
+    rasterize_fixed(a, b, c, scissor, visit);
+    #[cfg(not(feature = "fixed"))]
+    rasterize_subpixel(a, b, c, scissor, visit);
+}
+
+// Fraction bits of the 28.4 coordinates both integer paths snap to
+const SUBPIXEL_BITS: u32 = 4;
+// Kept well inside an i32 so edge function products can't overflow an i64
+const SUBPIXEL_LIMIT: f32 = (1 << 30) as f32;
+
+// Float edge functions round differently at every position, so an edge that
+// moves a fraction of a pixel can pick up or drop pixels all along its
+// length, and two triangles sharing it don't always agree on which of them
+// a pixel on it belongs to. Here vertices are snapped to a sixteenth of a
+// pixel and the edge functions worked out exactly in integers, so coverage
+// only changes where the edge has really crossed a pixel center. Weights are
+// still handed on as floats.
+pub fn rasterize_subpixel(
+    a: Point,
+    b: Point,
+    c: Point,
+    scissor: &Scissor,
+    visit: impl FnMut(&Quad),
+) {
+    let scale = (1 << SUBPIXEL_BITS) as f32;
+    let snap =
+        |value: f32| floor((value * scale + 0.5).clamp(-SUBPIXEL_LIMIT, SUBPIXEL_LIMIT)) as i64;
+    let [a, b, c] = [a, b, c].map(|p| (snap(p.x), snap(p.y)));
+    rasterize_snapped([a, b, c], scissor, |e, area| e as f32 / area as f32, visit);
+}
+
+pub fn rasterize_f32(a: Point, b: Point, c: Point, scissor: &Scissor, visit: impl FnMut(&Quad)) {
+    let bounds = Bounds {
+        min_x: floor(a.x.min(b.x).min(c.x)),
+        min_y: floor(a.y.min(b.y).min(c.y)),
+        max_x: ceil(a.x.max(b.x).max(c.x)),
+        max_y: ceil(a.y.max(b.y).max(c.y)),
+    };
+
+    let area = edge_function(&a, &b, &c);
+    let sample = |x: i32, y: i32| {
+        let p = Point::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
+        let weights = (
+            edge_function(&b, &c, &p) / area,
+            edge_function(&c, &a, &p) / area,
+            edge_function(&a, &b, &p) / area,
+        );
+        let (w1, w2, w3) = weights;
+        let inside =
+            (0.0..=1.0).contains(&w1) && (0.0..=1.0).contains(&w2) && (0.0..=1.0).contains(&w3);
+        (weights, inside)
+    };
+
+    walk(bounds.clip(scissor), sample, visit);
+}
+
+// The same coverage without float math past the inputs: vertices go through
+// 16.16 fixed point on their way to the 28.4 grid, and the weights are
+// divided out in 16.16 too. Vertices must stay within the 16.16 range, about
+// 32767 pixels either way; the scissor takes care of the rest.
+pub fn rasterize_fixed(a: Point, b: Point, c: Point, scissor: &Scissor, visit: impl FnMut(&Quad)) {
+    const SHIFT: u32 = Fixed::FRAC_BITS - SUBPIXEL_BITS;
+    let snap = |value: f32| (Fixed::from_f32(value).0 as i64 + (1 << (SHIFT - 1))) >> SHIFT;
+    let [a, b, c] = [a, b, c].map(|p| (snap(p.x), snap(p.y)));
+    let weight = |e: i64, area: i64| {
+        let weight = (e << Fixed::FRAC_BITS) / area;
+        Fixed(weight.clamp(i32::MIN as i64, i32::MAX as i64) as i32).to_f32()
+    };
+    rasterize_snapped([a, b, c], scissor, weight, visit);
+}
+
+// Coverage of a triangle already snapped to 28.4, with edge functions worked
+// out exactly in integers. A center right on an edge goes to the triangle
+// that edge is a top or left edge of, so it's drawn once. `weight` turns an
+// edge function and the triangle's doubled area, both positive inside, into
+// a barycentric weight.
+fn rasterize_snapped(
+    [a, mut b, mut c]: [(i64, i64); 3],
+    scissor: &Scissor,
+    weight: impl Fn(i64, i64) -> f32,
+    visit: impl FnMut(&Quad),
+) {
+    let edge = |a: (i64, i64), b: (i64, i64), c: (i64, i64)| {
+        (c.0 - a.0) * (b.1 - a.1) - (c.1 - a.1) * (b.0 - a.0)
+    };
+    let mut area = edge(a, b, c);
+    if area == 0 {
+        return;
+    }
+    // Wound the other way every edge function comes out negated, which the
+    // fill rule would get backwards, so it's turned around here and the
+    // weights put back in order after
+    let flipped = area < 0;
+    if flipped {
+        core::mem::swap(&mut b, &mut c);
+        area = -area;
+    }
+
+    let pixel = |value: i64| (value >> SUBPIXEL_BITS) as i32;
+    let ceil_pixel = |value: i64| ((value + (1 << SUBPIXEL_BITS) - 1) >> SUBPIXEL_BITS) as i32;
+    let bounds = Bounds {
+        min_x: pixel(a.0.min(b.0).min(c.0)),
+        min_y: pixel(a.1.min(b.1).min(c.1)),
+        max_x: ceil_pixel(a.0.max(b.0).max(c.0)),
+        max_y: ceil_pixel(a.1.max(b.1).max(c.1)),
+    };
+
+    // With y pointing down and the inside on the positive side, an edge
+    // heading down has the inside to its right and is a left edge, and a
+    // flat one heading left has it below and is a top edge
+    let top_left = |from: (i64, i64), to: (i64, i64)| {
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        dy > 0 || (dy == 0 && dx < 0)
+    };
+    let owned = [top_left(b, c), top_left(c, a), top_left(a, b)];
+    let half = 1 << (SUBPIXEL_BITS - 1);
+    let sample = |x: i32, y: i32| {
+        let p = (
+            ((x as i64) << SUBPIXEL_BITS) + half,
+            ((y as i64) << SUBPIXEL_BITS) + half,
+        );
+        let edges = [edge(b, c, p), edge(c, a, p), edge(a, b, p)];
+        let inside = edges
+            .iter()
+            .zip(owned)
+            .all(|(&e, owned)| e > 0 || (e == 0 && owned));
+        let [w1, mut w2, mut w3] = edges.map(|e| weight(e, area));
+        if flipped {
+            core::mem::swap(&mut w2, &mut w3);
+        }
+        ((w1, w2, w3), inside)
+    };
+
+    walk(bounds.clip(scissor), sample, visit);
+}
+
+// Inclusive pixel bounds
+struct Bounds {
+    min_x: i32,
+    min_y: i32,
+    max_x: i32,
+    max_y: i32,
+}
+
+impl Bounds {
+    fn clip(self, scissor: &Scissor) -> Bounds {
+        Bounds {
+            min_x: self.min_x.max(scissor.min_x as i32),
+            min_y: self.min_y.max(scissor.min_y as i32),
+            max_x: self.max_x.min(scissor.max_x as i32 - 1),
+            max_y: self.max_y.min(scissor.max_y as i32 - 1),
+        }
+    }
+}
+
+// `sample` gives a pixel's weights and whether its center is inside
+fn walk(
+    bounds: Bounds,
+    sample: impl Fn(i32, i32) -> ((f32, f32, f32), bool),
+    mut visit: impl FnMut(&Quad),
+) {
+    let Bounds {
+        min_x,
+        min_y,
+        max_x,
+        max_y,
+    } = bounds;
+    for quad_y in (min_y & !1..=max_y).step_by(2) {
+        for quad_x in (min_x & !1..=max_x).step_by(2) {
+            let lanes =
+                [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| sample(quad_x + dx, quad_y + dy));
+            if !lanes.iter().any(|&(_, inside)| inside) {
+                continue;
+            }
+
+            let quad = Quad {
+                x: quad_x,
+                y: quad_y,
+                weights: lanes.map(|(weights, _)| weights),
+                covered: [0, 1, 2, 3].map(|lane: usize| {
+                    let (x, y) = (quad_x + (lane % 2) as i32, quad_y + (lane / 2) as i32);
+                    lanes[lane].1 && (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+                }),
+            };
+            visit(&quad);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::BTreeMap;
+
+    type Coverage = BTreeMap<(i32, i32), (f32, f32, f32)>;
+    type Rasterizer = fn(Point, Point, Point, &Scissor, &mut dyn FnMut(&Quad));
+
+    fn covered(rasterize: Rasterizer, points: [Point; 3], scissor: &Scissor) -> Coverage {
+        let mut pixels = BTreeMap::new();
+        rasterize(points[0], points[1], points[2], scissor, &mut |quad| {
+            for lane in 0..4 {
+                if quad.covered[lane] {
+                    pixels.insert(quad.lane_position(lane), quad.weights[lane]);
+                }
+            }
+        });
+        pixels
+    }
+
+    fn float(a: Point, b: Point, c: Point, scissor: &Scissor, visit: &mut dyn FnMut(&Quad)) {
+        rasterize_f32(a, b, c, scissor, visit);
+    }
+
+    fn fixed(a: Point, b: Point, c: Point, scissor: &Scissor, visit: &mut dyn FnMut(&Quad)) {
+        rasterize_fixed(a, b, c, scissor, visit);
+    }
+
+    fn subpixel(a: Point, b: Point, c: Point, scissor: &Scissor, visit: &mut dyn FnMut(&Quad)) {
+        rasterize_subpixel(a, b, c, scissor, visit);
+    }
+
+    // Small deterministic generator so the test needs no dependencies
+    fn random_points(seed: &mut u32, count: usize) -> alloc::vec::Vec<Point> {
+        let mut next = || {
+            *seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (*seed >> 8) as f32 / (1 << 24) as f32
+        };
+        (0..count)
+            .map(|_| Point::new(next() * 180.0 - 10.0, next() * 140.0 - 10.0, next()))
+            .collect()
+    }
+
+    fn assert_matches_float(rasterize: Rasterizer, points: &[Point]) {
+        let scissor = Scissor {
+            min_x: 0,
+            min_y: 0,
+            max_x: 160,
+            max_y: 120,
+        };
+        let (mut total, mut mismatched) = (0, 0);
+        for triangle in points.chunks_exact(3) {
+            let points = [triangle[0], triangle[1], triangle[2]];
+            let expected = covered(float, points, &scissor);
+            let actual = covered(rasterize, points, &scissor);
+
+            total += expected.len();
+            mismatched += expected.keys().filter(|p| !actual.contains_key(p)).count();
+            mismatched += actual.keys().filter(|p| !expected.contains_key(p)).count();
+            for (pixel, weights) in &expected {
+                if let Some(fixed_weights) = actual.get(pixel) {
+                    let error = [
+                        weights.0 - fixed_weights.0,
+                        weights.1 - fixed_weights.1,
+                        weights.2 - fixed_weights.2,
+                    ]
+                    .map(f32::abs);
+                    assert!(
+                        error.iter().all(|&e| e < 0.01),
+                        "weights at {:?}: {:?} vs {:?}",
+                        pixel,
+                        weights,
+                        fixed_weights
+                    );
+                }
+            }
+        }
+
+        // Only pixels whose centers sit within rounding of an edge may flip
+        assert!(total > 10_000);
+        assert!(
+            mismatched * 1000 < total,
+            "{} of {} pixels differ",
+            mismatched,
+            total
+        );
+    }
+
+    // On the sixteenth-pixel grid already, so the snapping paths and the
+    // float one see the same triangles
+    fn random_snapped_points(seed: &mut u32, count: usize) -> alloc::vec::Vec<Point> {
+        random_points(seed, count)
+            .into_iter()
+            .map(|p| {
+                Point::new(
+                    floor(p.x * 16.0) as f32 / 16.0,
+                    floor(p.y * 16.0) as f32 / 16.0,
+                    p.z,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fixed_point_matches_float_within_tolerance() {
+        let mut seed = 7;
+        assert_matches_float(fixed, &random_snapped_points(&mut seed, 300));
+    }
+
+    #[test]
+    fn subpixel_matches_float_within_tolerance() {
+        let mut seed = 7;
+        assert_matches_float(subpixel, &random_snapped_points(&mut seed, 300));
+    }
+
+    // Both integer paths agree on every pixel, off the grid too
+    #[test]
+    fn fixed_point_covers_what_subpixel_does() {
+        let scissor = Scissor {
+            min_x: 0,
+            min_y: 0,
+            max_x: 160,
+            max_y: 120,
+        };
+        let mut seed = 11;
+        for triangle in random_points(&mut seed, 300).chunks_exact(3) {
+            let points = [triangle[0], triangle[1], triangle[2]];
+            let expected = covered(subpixel, points, &scissor);
+            let actual = covered(fixed, points, &scissor);
+            assert!(expected.keys().eq(actual.keys()), "{:?}", points);
+        }
+    }
+
+    // A fan of triangles around an off-grid center, wound both ways, covers
+    // every pixel inside it exactly once
+    fn assert_covers_shared_edges_once(rasterize: Rasterizer) {
+        let scissor = Scissor {
+            min_x: 0,
+            min_y: 0,
+            max_x: 96,
+            max_y: 96,
+        };
+        let center = Point::new(48.3, 47.7, 0.0);
+        let rim = [
+            Point::new(10.25, 48.0, 0.0),
+            Point::new(20.0, 12.5, 0.0),
+            Point::new(48.3, 6.0, 0.0),
+            Point::new(84.0, 20.0, 0.0),
+            Point::new(90.5, 47.7, 0.0),
+            Point::new(70.0, 88.0, 0.0),
+            Point::new(48.0, 90.0, 0.0),
+            Point::new(14.0, 80.125, 0.0),
+        ];
+        let mut counts = BTreeMap::new();
+        for index in 0..rim.len() {
+            let (from, to) = (rim[index], rim[(index + 1) % rim.len()]);
+            let points = if index % 2 == 0 {
+                [center, from, to]
+            } else {
+                [center, to, from]
+            };
+            for pixel in covered(rasterize, points, &scissor).into_keys() {
+                *counts.entry(pixel).or_insert(0) += 1;
+            }
+        }
+        assert!(counts.len() > 4000);
+        assert!(counts.values().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn subpixel_covers_shared_edges_once() {
+        assert_covers_shared_edges_once(subpixel);
+    }
+
+    #[test]
+    fn fixed_point_covers_shared_edges_once() {
+        assert_covers_shared_edges_once(fixed);
+    }
+
+    #[test]
+    fn fixed_point_skips_degenerate_triangles() {
+        let scissor = Scissor {
+            min_x: 0,
+            min_y: 0,
+            max_x: 64,
+            max_y: 64,
+        };
+        let line = [
+            Point::new(1.0, 1.0, 0.0),
+            Point::new(30.0, 30.0, 0.0),
+            Point::new(60.0, 60.0, 0.0),
+        ];
+        assert!(covered(fixed, line, &scissor).is_empty());
+        assert!(covered(subpixel, line, &scissor).is_empty());
+        assert!(covered(float, line, &scissor).is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::camera::Camera;
+use crate::framebuffer::Framebuffer;
+use nalgebra_glm::{Mat4, Vec2, Vec3};
+
+// Frames past which each new one only counts for this share of the average,
+// so the picture still settles if something slowly changes under it
+const MAX_FRAMES: u32 = 64;
+
+// Averages frame after frame of a still scene into one, each drawn shifted
+// by a different fraction of a pixel, so the edges come out antialiased and
+// the noise in the shading evens out, for screenshots. It starts over
+// whenever the camera moves, the window changes size or a key or the mouse
+// is pressed, since that could have changed what's in the picture.
+pub struct Accumulation {
+    pub enabled: bool,
+    history: Vec<Vec3>,
+    frames: u32,
+    // The camera and framebuffer the frames so far were drawn with
+    pose: Option<([Vec3; 3], usize, usize)>,
+}
+
+impl Accumulation {
+    pub fn new() -> Self {
+        Accumulation {
+            enabled: false,
+            history: Vec::new(),
+            frames: 0,
+            pose: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.frames = 0;
+    }
+
+    // Frames averaged into the picture so far
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    // Called before the frame is drawn. `disturbed` is whether anything was
+    // pressed this frame.
+    pub fn start_frame(&mut self, camera: &Camera, framebuffer: &Framebuffer, disturbed: bool) {
+        let pose = (
+            [camera.eye, camera.center, camera.up],
+            framebuffer.width,
+            framebuffer.height,
+        );
+        if disturbed || self.pose != Some(pose) {
+            self.frames = 0;
+        }
+        self.pose = Some(pose);
+    }
+
+    // Where in its pixel this frame is drawn, from -0.5 to 0.5 each way:
+    // the middle for the first frame, then points from the Halton sequence
+    // in bases 2 and 3, which spread evenly whenever they stop
+    pub fn jitter(&self) -> Vec2 {
+        if !self.enabled || self.frames == 0 {
+            return Vec2::zeros();
+        }
+        Vec2::new(halton(self.frames, 2), halton(self.frames, 3)).add_scalar(-0.5)
+    }
+
+    // Blends the finished frame in with the ones before it and leaves the
+    // average in its place
+    pub fn accumulate(&mut self, framebuffer: &mut Framebuffer) {
+        if !self.enabled {
+            return;
+        }
+        if self.frames == 0 {
+            self.history.clone_from(&framebuffer.hdr);
+        } else {
+            let weight = 1.0 / (self.frames.min(MAX_FRAMES) + 1) as f32;
+            for (average, &radiance) in self.history.iter_mut().zip(&framebuffer.hdr) {
+                *average += (radiance - *average) * weight;
+            }
+            framebuffer.hdr.copy_from_slice(&self.history);
+        }
+        self.frames += 1;
+    }
+}
+
+// `projection` moved across the screen by `offset` pixels of a viewport
+// `width` x `height`
+pub fn jitter_projection(projection: Mat4, offset: Vec2, width: f32, height: f32) -> Mat4 {
+    let mut jittered = projection;
+    // Added in before the divide by w, which is -z, so the shift is the same
+    // at every depth
+    jittered[(0, 2)] -= offset.x * 2.0 / width;
+    jittered[(1, 2)] += offset.y * 2.0 / height;
+    jittered
+}
+
+// The `index`th number in the van der Corput sequence in `base`, in (0, 1)
+fn halton(index: u32, base: u32) -> f32 {
+    let (mut index, mut fraction, mut result) = (index, 1.0, 0.0);
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
@@ -0,0 +1,102 @@
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+
+// Radiance the sun gives the air for scattering, against the 1.0 it gives a
+// white surface facing it
+const SUN_INTENSITY: f32 = 12.0;
+
+// A thin shell of air over a planet, of uniform density, lit by the sun. Its
+// coefficients are per planet radius of path, so the same shell looks the
+// same on a planet of any size.
+#[derive(Clone, Copy)]
+pub struct Atmosphere {
+    // Thickness of the shell, in planet radii
+    pub height: f32,
+    // Rayleigh scattering by the gas itself, strongest at short wavelengths,
+    // so the shell is blue seen through at a slant
+    pub rayleigh: Vec3,
+    // Mie scattering by haze and dust, the same for every color
+    pub mie: f32,
+    // How much of what haze scatters keeps going forward, from 0 for evenly
+    // all around up to 1 for straight on
+    pub mie_forward: f32,
+}
+
+impl Atmosphere {
+    // Coefficients a few times stronger than Earth's over a shell forty times
+    // thicker, so the blue rim still shows at a few hundred pixels across
+    pub const TERRAN: Atmosphere = Atmosphere {
+        height: 0.03,
+        rayleigh: Vec3::new(1.2, 2.8, 6.9),
+        mie: 0.5,
+        mie_forward: 0.76,
+    };
+    // Deep, hazy and much less blue, so the bands fade into a pale limb
+    pub const GAS_GIANT: Atmosphere = Atmosphere {
+        height: 0.06,
+        rayleigh: Vec3::new(0.25, 0.35, 0.55),
+        mie: 1.8,
+        mie_forward: 0.6,
+    };
+    pub const ICE_GIANT: Atmosphere = Atmosphere {
+        height: 0.06,
+        rayleigh: Vec3::new(0.4, 1.1, 1.8),
+        mie: 1.0,
+        mie_forward: 0.6,
+    };
+
+    // What gets to the eye from a point on the surface whose own radiance
+    // is `surface`: that light thinned by the air on its way out, plus the
+    // sunlight the air scatters toward the eye along the same path, both
+    // from a single scattering with the sunlight reddened by how much air it
+    // crossed to get there. `normal` points out of the planet there, and
+    // `view_dir` and `light_dir` toward the eye and the sun, whose light is
+    // `sun_color` before it reaches the air.
+    pub fn scatter(
+        &self,
+        surface: Vec3,
+        normal: &Vec3,
+        view_dir: &Vec3,
+        light_dir: &Vec3,
+        sun_color: &Vec3,
+    ) -> Vec3 {
+        let extinction = self.rayleigh.add_scalar(self.mie);
+        let view_path = self.path(normal.dot(view_dir));
+        let through = extinction.map(|coefficient| (-coefficient * view_path).exp());
+
+        // Past the terminator the planet itself blocks the sun, though the
+        // air a little above still catches it for a while
+        let sun_height = normal.dot(light_dir);
+        let daylight = ((sun_height + 0.12) / 0.15).clamp(0.0, 1.0);
+        let sun_path = self.path(sun_height.max(0.0));
+        let sunlight = extinction.map(|coefficient| (-coefficient * sun_path).exp());
+
+        // Between the sunlight's way in and the way out toward the eye
+        let cosine = -light_dir.dot(view_dir);
+        let rayleigh_phase = 3.0 / (16.0 * PI) * (1.0 + cosine * cosine);
+        let g = self.mie_forward;
+        let mie_phase =
+            (1.0 - g * g) / (4.0 * PI * (1.0 + g * g - 2.0 * g * cosine).max(1e-4).powf(1.5));
+        let scattered = (self.rayleigh * rayleigh_phase).add_scalar(self.mie * mie_phase);
+        let glow = scattered
+            .component_div(&extinction)
+            .component_mul(&(Vec3::repeat(1.0) - through))
+            .component_mul(&sunlight)
+            .component_mul(sun_color)
+            * (SUN_INTENSITY * daylight);
+
+        // The surface is lit by the same reddened sunlight, but only by day;
+        // the night side's own light never crossed the air
+        let tint = Vec3::repeat(1.0).lerp(&sunlight, daylight);
+        surface.component_mul(&tint).component_mul(&through) + glow
+    }
+
+    // How far a ray leaving the surface at `cosine` from straight up
+    // travels before it leaves the shell, in planet radii: a little over
+    // the height looking up and the most at the horizon
+    fn path(&self, cosine: f32) -> f32 {
+        let cosine = cosine.max(0.0);
+        let h = self.height;
+        (cosine * cosine + 2.0 * h + h * h).sqrt() - cosine
+    }
+}
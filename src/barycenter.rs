@@ -0,0 +1,126 @@
+use crate::font::GLYPH_HEIGHT;
+use crate::framebuffer::Framebuffer;
+use crate::satellite::Satellite;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+use std::borrow::Cow;
+
+const MARKER_RADIUS: f32 = 5.0;
+
+// A body and everything orbiting it, balanced at `barycenter`
+pub struct System {
+    pub primary: Cow<'static, str>,
+    pub barycenter: Vec3,
+}
+
+// Every body among `primary` and its `satellites` that has moons of its own,
+// with the point its system balances on. Inner systems come before the ones
+// they orbit in, so the last is the whole of it. `positions` are where each
+// satellite is, in the order `satellite::attach` adds them.
+pub fn systems(
+    primary: Cow<'static, str>,
+    center: Vec3,
+    mass: f32,
+    satellites: &[Satellite],
+    positions: &[Vec3],
+) -> Vec<System> {
+    let mut systems = Vec::new();
+    let mut positions = positions.iter().copied();
+    collect(
+        primary,
+        center,
+        mass,
+        satellites,
+        &mut positions,
+        &mut systems,
+    );
+    systems
+}
+
+// Adds the systems under `primary` and returns its own mass-weighted
+// position and mass, moons included
+fn collect(
+    primary: Cow<'static, str>,
+    center: Vec3,
+    mass: f32,
+    satellites: &[Satellite],
+    positions: &mut impl Iterator<Item = Vec3>,
+    systems: &mut Vec<System>,
+) -> (Vec3, f32) {
+    let (mut moment, mut total) = (center * mass, mass);
+    for satellite in satellites {
+        let Some(position) = positions.next() else {
+            break;
+        };
+        let (satellite_moment, satellite_mass) = collect(
+            satellite.name.clone(),
+            position,
+            satellite.mass,
+            &satellite.satellites,
+            positions,
+            systems,
+        );
+        moment += satellite_moment;
+        total += satellite_mass;
+    }
+    if !satellites.is_empty() && total > 0.0 {
+        systems.push(System {
+            primary,
+            barycenter: moment / total,
+        });
+    }
+    (moment, total)
+}
+
+// A crosshair circle at `position` with `label` beside it, in the current
+// color, drawn over whatever is there since a barycenter is usually inside
+// a body
+pub fn draw_marker(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    position: Vec3,
+    label: &str,
+) {
+    let screen = uniforms.viewport_matrix
+        * uniforms.projection_matrix
+        * uniforms.view_matrix
+        * Vec4::new(position.x, position.y, position.z, 1.0);
+    if screen.w <= 0.0 {
+        return;
+    }
+    let (x, y) = (screen.x / screen.w, screen.y / screen.w);
+    let scissor = framebuffer.scissor();
+    if x < scissor.min_x as f32
+        || y < scissor.min_y as f32
+        || x >= scissor.max_x as f32
+        || y >= scissor.max_y as f32
+    {
+        return;
+    }
+
+    let steps = 32;
+    for step in 0..steps {
+        let angle = step as f32 / steps as f32 * std::f32::consts::TAU;
+        let (px, py) = (
+            x + angle.cos() * MARKER_RADIUS,
+            y + angle.sin() * MARKER_RADIUS,
+        );
+        if px >= 0.0 && py >= 0.0 {
+            framebuffer.set_pixel(px as usize, py as usize);
+        }
+    }
+    let reach = MARKER_RADIUS as i32 + 2;
+    for offset in -reach..=reach {
+        let (px, py) = (x as i32 + offset, y as i32 + offset);
+        if px >= 0 {
+            framebuffer.set_pixel(px as usize, y as usize);
+        }
+        if py >= 0 {
+            framebuffer.set_pixel(x as usize, py as usize);
+        }
+    }
+
+    let text_x = (x + MARKER_RADIUS + 4.0) as i32;
+    let text_y = (y - GLYPH_HEIGHT as f32 / 2.0) as i32;
+    framebuffer.draw_text(text_x, text_y, label, 1);
+}
@@ -0,0 +1,332 @@
+use crate::cubemap::{Cubemap, REFLECTION_SIZE};
+use crate::dither::Dither;
+use crate::error;
+use crate::framebuffer::Framebuffer;
+use crate::generator::GeneratedPlanet;
+use crate::instance::Instance;
+use crate::light::Star;
+use crate::locale::Locale;
+use crate::material::Material;
+use crate::noise::NoiseRegistry;
+use crate::obj::Obj;
+use crate::procedural::{asteroid, asteroid_belt, uv_sphere};
+use crate::profiler::{self, Stage, Stopwatch, NAMES, STAGES};
+use crate::projection::Projection;
+use crate::satellite::{self, SatelliteMesh};
+use crate::season::Orbit;
+use crate::shaders::{AuroraParams, GasGiantParams, OceanParams, ShaderType, ShadingModel};
+use crate::simulation::belt_rotation;
+use crate::sky::{Backdrop, Sky, NEBULAE};
+use crate::tectonics::Continents;
+use crate::tonemap::ToneMapping;
+use crate::transform::{SceneGraph, Transform};
+use crate::vertex::Vertex;
+use crate::weather::Weather;
+use crate::{
+    create_perspective_matrix, create_view_matrix, create_viewport_matrix, fill_light,
+    render_instanced, satellites_of, sun_direction, DepthMode, Uniforms, Viewport, PLANET_SPIN,
+};
+use nalgebra_glm::{Mat4, Vec3};
+use std::fmt::Display;
+use std::time::Instant;
+
+// Frames rendered before the timing starts, so the caches are warm and the
+// first frame's allocations don't count
+const WARMUP_FRAMES: u32 = 5;
+
+// What each scene puts in front of the pipeline
+#[derive(Clone, Copy)]
+enum Scene {
+    // The planet's own mesh
+    Sphere,
+    // Far more triangles than pixels, so vertex work and assembly dominate
+    HighPoly,
+    // The gas giant with its moons and the belt, a few thousand draws
+    System,
+    // The sky behind the planet seen from far off, so the sky and clearing
+    // the framebuffer are most of the frame
+    Backdrop,
+}
+
+const SCENES: [Scene; 4] = [
+    Scene::Sphere,
+    Scene::HighPoly,
+    Scene::System,
+    Scene::Backdrop,
+];
+
+impl Scene {
+    fn name(self) -> &'static str {
+        match self {
+            Scene::Sphere => "bench.sphere",
+            Scene::HighPoly => "bench.high_poly",
+            Scene::System => "bench.system",
+            Scene::Backdrop => "bench.backdrop",
+        }
+    }
+}
+
+// One call to the renderer
+struct Draw<'a> {
+    vertex_array: &'a [Vertex],
+    instances: Vec<Instance>,
+    shader: ShaderType,
+}
+
+// The meshes every scene is drawn from, loaded once
+struct Meshes {
+    planet: Vec<Vertex>,
+    planet_radius: f32,
+    high_poly: Vec<Vertex>,
+    moon: Vec<Vertex>,
+    asteroid: Vec<Vertex>,
+    belt_rock: Vec<Vertex>,
+    // Each rock's place in the belt and its seed
+    belt: Vec<(Mat4, u32)>,
+}
+
+impl Meshes {
+    fn load() -> error::Result<Self> {
+        let planet = Obj::load("assets/models/sphere.obj")?;
+        let moon = Obj::load("assets/models/moon.obj")?;
+        let planet_radius = planet.bounding_radius();
+        let belt = asteroid_belt(2000, 2.6, 3.4, 0.25, 42)
+            .iter()
+            .map(|rock| {
+                let local = Transform::from_euler(rock.position, rock.scale, rock.rotation);
+                (local.matrix(), rock.seed)
+            })
+            .collect();
+        Ok(Meshes {
+            planet: planet.get_vertex_array(),
+            planet_radius,
+            high_poly: uv_sphere(planet_radius, 512, 256),
+            moon: moon.get_vertex_array(),
+            asteroid: asteroid(7, 3),
+            belt_rock: asteroid(11, 1),
+            belt,
+        })
+    }
+
+    // What `scene` draws at `time`, and where it's seen from
+    fn draws(&self, scene: Scene, time: u32) -> (Vec<Draw<'_>>, Vec3) {
+        let spin = Transform::new(Vec3::zeros(), 1.0, PLANET_SPIN.orientation(time)).matrix();
+        let planet = |vertex_array, shader| Draw {
+            vertex_array,
+            instances: vec![Instance::new(spin)],
+            shader,
+        };
+        let close = Vec3::new(0.0, 0.0, self.planet_radius * 3.0);
+        match scene {
+            Scene::Sphere => (vec![planet(&self.planet, ShaderType::RockyPlanet)], close),
+            Scene::HighPoly => (
+                vec![planet(&self.high_poly, ShaderType::RockyPlanet)],
+                close,
+            ),
+            Scene::System => {
+                let mut graph = SceneGraph::default();
+                let system = graph.add(None, Transform::at(Vec3::zeros()));
+                let shader = ShaderType::GasGiant;
+                let satellites = satellite::attach(&mut graph, satellites_of(shader), system, time);
+                let belt = graph.add(
+                    Some(system),
+                    Transform::from_euler(Vec3::zeros(), 1.0, belt_rotation(time)),
+                );
+                graph.update();
+
+                let mut draws = vec![planet(&self.planet, shader)];
+                for (index, &(satellite, node)) in satellites.iter().enumerate() {
+                    let vertex_array = match satellite.mesh {
+                        SatelliteMesh::Moon => &self.moon,
+                        SatelliteMesh::Asteroid => &self.asteroid,
+                    };
+                    draws.push(Draw {
+                        vertex_array,
+                        instances: vec![Instance {
+                            seed: index as u32 * 13,
+                            ..Instance::new(graph.world(node))
+                        }],
+                        shader: ShaderType::Moon,
+                    });
+                }
+                let belt_matrix = graph.world(belt);
+                draws.push(Draw {
+                    vertex_array: &self.belt_rock,
+                    instances: self
+                        .belt
+                        .iter()
+                        .map(|&(local, seed)| Instance {
+                            seed,
+                            material: Some(Material::ROCK),
+                            ..Instance::new(belt_matrix * local)
+                        })
+                        .collect(),
+                    shader: ShaderType::Moon,
+                });
+                // High over the belt, to take in all of it
+                (draws, Vec3::new(0.0, 3.0, 6.0))
+            }
+            Scene::Backdrop => (
+                vec![planet(&self.planet, ShaderType::RockyPlanet)],
+                Vec3::new(0.0, 0.0, self.planet_radius * 40.0),
+            ),
+        }
+    }
+}
+
+// Renders each scene for `frames` frames at `width` x `height` without a
+// window and prints how long every stage of the pipeline took, so a change
+// to the pipeline can be timed against the one before it. Times are CPU
+// time summed over the render threads, as on the profiler overlay, but for
+// clearing the framebuffer, which is timed on its own. `threads` and
+// `reversed_z` are as --threads and --reverse-z have them for the window.
+pub fn run(
+    frames: u32,
+    (width, height): (usize, usize),
+    threads: usize,
+    reversed_z: bool,
+    locale: &Locale,
+) -> error::Result<()> {
+    let meshes = Meshes::load()?;
+    let continents = Continents::generate(5);
+    let noises = NoiseRegistry::new();
+    let generated = GeneratedPlanet::from_seed(0);
+    let sky = Sky::bake(&NEBULAE[0]);
+    let reflections = Cubemap::from_sky(&sky, REFLECTION_SIZE);
+    let weather = Weather::new(5);
+    let orbit = Orbit {
+        axial_tilt: PLANET_SPIN.axial_tilt,
+        ..Orbit::default()
+    };
+    let viewport = Viewport::full(width as f32, height as f32);
+    let mut framebuffer = Framebuffer::new(width, height);
+    let tone_mapping = ToneMapping::default();
+
+    profiler::set_enabled(true);
+    for scene in SCENES {
+        let mut samples: Vec<[f32; STAGES]> = Vec::with_capacity(frames as usize);
+        let mut frame_times = Vec::with_capacity(frames as usize);
+        let mut clear_times = Vec::with_capacity(frames as usize);
+        let mut backdrop = Backdrop::new();
+        let mut triangles = 0;
+        for time in 0..WARMUP_FRAMES + frames {
+            let (draws, eye) = meshes.draws(scene, time);
+            let sun = sun_direction(&orbit, time);
+            let uniforms = Uniforms {
+                model_matrix: Mat4::identity(),
+                view_matrix: create_view_matrix(eye, Vec3::zeros(), Vec3::y()),
+                projection_matrix: create_perspective_matrix(
+                    &Projection::default(),
+                    viewport.width,
+                    viewport.height,
+                    reversed_z,
+                ),
+                viewport_matrix: create_viewport_matrix(&viewport, reversed_z),
+                time,
+                noise: noises.get(draws[0].shader),
+                aurora: AuroraParams::default(),
+                gas_giant: GasGiantParams::default(),
+                ocean: OceanParams::default(),
+                shading: ShadingModel::Phong,
+                light_dir: sun,
+                star: Star::YellowDwarf,
+                lights: &[fill_light(sun)],
+                light_space_matrix: Mat4::identity(),
+                shadow_map: None,
+                companion: None,
+                extra_octaves: 0,
+                ambient: sky.ambient,
+                environment: Some(&reflections),
+                particle_stride: 1,
+                weather: weather.state(),
+                clouds: None,
+                surface: None,
+                eye,
+                season: orbit.season(time),
+                continents: &continents,
+                #[cfg(feature = "physics")]
+                impacts: &[],
+                planet: &generated,
+                debug_view: None,
+                threads,
+                reversed_z,
+            };
+
+            let start = Instant::now();
+            framebuffer.clear();
+            let clear_ms = start.elapsed().as_secs_f32() * 1000.0;
+            if let Scene::Backdrop = scene {
+                sky.draw(&mut framebuffer, &uniforms, &mut backdrop);
+            }
+            for draw in &draws {
+                let uniforms = Uniforms {
+                    noise: noises.get(draw.shader),
+                    ..uniforms
+                };
+                render_instanced(
+                    &mut framebuffer,
+                    &uniforms,
+                    draw.vertex_array,
+                    &draw.instances,
+                    &draw.shader,
+                    DepthMode::ZBuffer,
+                );
+            }
+            let mut presenting = Stopwatch::new();
+            presenting.time(|| framebuffer.resolve(&tone_mapping, Dither::Off));
+            profiler::add(Stage::Present, presenting.elapsed());
+            let frame_ms = start.elapsed().as_secs_f32() * 1000.0;
+
+            let spent = profiler::take();
+            if time >= WARMUP_FRAMES {
+                samples.push(spent);
+                frame_times.push(frame_ms);
+                clear_times.push(clear_ms);
+            }
+            triangles = draws
+                .iter()
+                .map(|draw| draw.vertex_array.len() / 3 * draw.instances.len())
+                .sum();
+        }
+
+        println!(
+            "{}",
+            locale.format(
+                "bench.scene",
+                &[&locale.get(scene.name()), &triangles, &frames]
+            )
+        );
+        let columns = ["bench.mean", "bench.std_dev", "bench.min", "bench.max"];
+        print_row(
+            locale.get("bench.stage"),
+            columns.map(|key| locale.get(key)),
+        );
+        for (stage, name) in NAMES.iter().enumerate() {
+            let times: Vec<f32> = samples.iter().map(|sample| sample[stage]).collect();
+            print_row(locale.get(name), statistics(&times));
+        }
+        print_row(locale.get("bench.clear"), statistics(&clear_times));
+        print_row(locale.get("bench.frame"), statistics(&frame_times));
+        println!();
+    }
+    profiler::set_enabled(false);
+    Ok(())
+}
+
+// Mean, standard deviation, fastest and slowest of `times`, in milliseconds
+fn statistics(times: &[f32]) -> [String; 4] {
+    let count = times.len().max(1) as f32;
+    let mean = times.iter().sum::<f32>() / count;
+    let variance = times.iter().map(|t| (t - mean).powi(2)).sum::<f32>() / count;
+    let min = times.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = times.iter().copied().fold(0.0, f32::max);
+    [mean, variance.sqrt(), min, max].map(|ms| format!("{:.3}", ms))
+}
+
+fn print_row(name: &str, cells: [impl Display; 4]) {
+    let [mean, std_dev, min, max] = cells;
+    println!(
+        "  {:<20} {:>10} {:>10} {:>10} {:>10}",
+        name, mean, std_dev, min, max
+    );
+}
@@ -0,0 +1,152 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::OnceLock;
+
+const SIZE: usize = 64;
+const SIGMA: f32 = 1.5;
+const RADIUS: i32 = 6;
+
+// Tileable threshold map whose values are spread evenly in space, so noise
+// drawn from it looks like fine even grain instead of the clumps of white noise
+pub struct BlueNoise {
+    size: usize,
+    values: Vec<f32>,
+}
+
+// Shared tile for shaders and post effects, built on first use
+pub fn blue_noise() -> &'static BlueNoise {
+    static TILE: OnceLock<BlueNoise> = OnceLock::new();
+    TILE.get_or_init(|| BlueNoise::generate(SIZE, 7))
+}
+
+impl BlueNoise {
+    // Void-and-cluster (Ulichney 1993): every pixel gets a rank by repeatedly
+    // filling the emptiest spot of a toroidal Gaussian energy field
+    pub fn generate(size: usize, seed: u64) -> Self {
+        let count = size * size;
+        let mut field = EnergyField::new(size);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        // Random initial pattern, relaxed by moving the tightest cluster into
+        // the largest void until that no longer changes anything
+        let initial = count / 10;
+        while field.ones < initial {
+            let pixel = rng.gen_range(0..count);
+            if !field.points[pixel] {
+                field.toggle(pixel);
+            }
+        }
+        loop {
+            let cluster = field.tightest_cluster();
+            field.toggle(cluster);
+            let void = field.largest_void();
+            field.toggle(void);
+            if void == cluster {
+                break;
+            }
+        }
+
+        let mut ranks = vec![0; count];
+        let mut removal = EnergyField::new(size);
+        for pixel in 0..count {
+            if field.points[pixel] {
+                removal.toggle(pixel);
+            }
+        }
+        while removal.ones > 0 {
+            let cluster = removal.tightest_cluster();
+            removal.toggle(cluster);
+            ranks[cluster] = removal.ones;
+        }
+
+        while field.ones < count {
+            let void = field.largest_void();
+            ranks[void] = field.ones;
+            field.toggle(void);
+        }
+
+        BlueNoise {
+            size,
+            values: ranks
+                .into_iter()
+                .map(|rank| (rank as f32 + 0.5) / count as f32)
+                .collect(),
+        }
+    }
+
+    // Threshold in (0, 1), repeating every `size` pixels
+    pub fn sample(&self, x: usize, y: usize) -> f32 {
+        self.values[(y % self.size) * self.size + x % self.size]
+    }
+
+    // Same tile shifted by the golden ratio each frame, so temporal effects get
+    // a fresh, still evenly spread value per pixel
+    pub fn sample_animated(&self, x: usize, y: usize, frame: u32) -> f32 {
+        (self.sample(x, y) + frame as f32 * 0.618_034).fract()
+    }
+}
+
+struct EnergyField {
+    size: usize,
+    points: Vec<bool>,
+    energy: Vec<f32>,
+    ones: usize,
+    kernel: Vec<(i32, i32, f32)>,
+}
+
+impl EnergyField {
+    fn new(size: usize) -> Self {
+        let mut kernel = Vec::new();
+        for dy in -RADIUS..=RADIUS {
+            for dx in -RADIUS..=RADIUS {
+                let distance_squared = (dx * dx + dy * dy) as f32;
+                kernel.push((dx, dy, (-distance_squared / (2.0 * SIGMA * SIGMA)).exp()));
+            }
+        }
+        EnergyField {
+            size,
+            points: vec![false; size * size],
+            energy: vec![0.0; size * size],
+            ones: 0,
+            kernel,
+        }
+    }
+
+    fn toggle(&mut self, pixel: usize) {
+        let sign = if self.points[pixel] { -1.0 } else { 1.0 };
+        self.points[pixel] = !self.points[pixel];
+        if sign > 0.0 {
+            self.ones += 1;
+        } else {
+            self.ones -= 1;
+        }
+
+        let size = self.size as i32;
+        let (x, y) = ((pixel % self.size) as i32, (pixel / self.size) as i32);
+        for &(dx, dy, weight) in &self.kernel {
+            let nx = (x + dx).rem_euclid(size);
+            let ny = (y + dy).rem_euclid(size);
+            self.energy[(ny * size + nx) as usize] += sign * weight;
+        }
+    }
+
+    fn tightest_cluster(&self) -> usize {
+        self.extreme(true, |a, b| a > b)
+    }
+
+    fn largest_void(&self) -> usize {
+        self.extreme(false, |a, b| a < b)
+    }
+
+    fn extreme(&self, point: bool, better: impl Fn(f32, f32) -> bool) -> usize {
+        let mut best: Option<usize> = None;
+        for pixel in 0..self.points.len() {
+            if self.points[pixel] == point
+                && best.is_none_or(|b| better(self.energy[pixel], self.energy[b]))
+            {
+                best = Some(pixel);
+            }
+        }
+        best.unwrap_or(0)
+    }
+}
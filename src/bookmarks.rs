@@ -0,0 +1,91 @@
+use crate::camera::Camera;
+use crate::error::{self, Error};
+use nalgebra_glm::Vec3;
+use std::fs;
+use std::path::PathBuf;
+
+pub const SLOTS: usize = 9;
+
+#[derive(Clone, Copy)]
+struct Pose {
+    eye: Vec3,
+    center: Vec3,
+    up: Vec3,
+}
+
+// Camera poses in numbered slots 1 to 9, kept in a text file between runs with
+// one `slot eye center up` line per filled slot. Floats are written in their
+// shortest exact form, so a recalled view matches the saved one bit for bit.
+pub struct Bookmarks {
+    path: PathBuf,
+    slots: [Option<Pose>; SLOTS],
+}
+
+impl Bookmarks {
+    // A missing file just means no bookmarks yet; lines that don't parse are
+    // skipped
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let mut slots = [None; SLOTS];
+        for line in fs::read_to_string(&path).unwrap_or_default().lines() {
+            if let Some((slot, pose)) = parse_line(line) {
+                slots[slot - 1] = Some(pose);
+            }
+        }
+        Bookmarks { path, slots }
+    }
+
+    pub fn store(&mut self, slot: usize, camera: &Camera) -> error::Result<()> {
+        self.slots[slot - 1] = Some(Pose {
+            eye: camera.eye,
+            center: camera.center,
+            up: camera.up,
+        });
+
+        let mut contents = String::new();
+        for (index, pose) in self.slots.iter().enumerate() {
+            if let Some(pose) = pose {
+                let values = [pose.eye, pose.center, pose.up]
+                    .iter()
+                    .flat_map(|v| [v.x, v.y, v.z])
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                contents += &format!("{} {}\n", index + 1, values);
+            }
+        }
+        fs::write(&self.path, contents).map_err(Error::io(self.path.display().to_string()))
+    }
+
+    // Starts the camera flying over to the slot's pose. Returns false, leaving
+    // the camera alone, if the slot is empty.
+    pub fn recall(&self, slot: usize, camera: &mut Camera) -> bool {
+        let Some(pose) = self.slots[slot - 1] else {
+            return false;
+        };
+        camera.fly_to(pose.eye, pose.center, pose.up);
+        true
+    }
+}
+
+fn parse_line(line: &str) -> Option<(usize, Pose)> {
+    let mut fields = line.split_whitespace();
+    let slot: usize = fields.next()?.parse().ok()?;
+    if !(1..=SLOTS).contains(&slot) {
+        return None;
+    }
+
+    let values: Vec<f32> = fields.map(str::parse).collect::<Result<_, _>>().ok()?;
+    if values.len() != 9 {
+        return None;
+    }
+    let vector = |i: usize| Vec3::new(values[i], values[i + 1], values[i + 2]);
+    Some((
+        slot,
+        Pose {
+            eye: vector(0),
+            center: vector(3),
+            up: vector(6),
+        },
+    ))
+}
@@ -0,0 +1,120 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// What the frame has drawn so far, added to by every render thread
+pub static TRIANGLES: AtomicUsize = AtomicUsize::new(0);
+pub static FRAGMENTS: AtomicUsize = AtomicUsize::new(0);
+
+// Load, as a fraction of the budget, under which a frame counts as calm
+const CALM_LOAD: f32 = 0.6;
+// Calm frames in a row before one cut is given back
+const CALM_FRAMES: u32 = 30;
+
+// What's given up at one step of the budget
+#[derive(Clone, Copy)]
+pub struct Cuts {
+    // Scales a body's projected radius before its LOD is picked, so below
+    // one every mesh comes out coarser than its size on screen calls for
+    pub detail: f32,
+    // Belt rocks and other small bodies with a smaller radius on screen, in
+    // pixels, aren't drawn
+    pub min_pixels: f32,
+    // Only one particle in this many is drawn
+    pub particle_stride: usize,
+}
+
+// In the order they're taken: coarser meshes first, since they're the
+// hardest to tell apart, then the bodies too far away to make out, then the
+// particles. Each step keeps the cuts of the ones before.
+const STEPS: [Cuts; 7] = [
+    Cuts {
+        detail: 1.0,
+        min_pixels: 0.0,
+        particle_stride: 1,
+    },
+    Cuts {
+        detail: 0.7,
+        min_pixels: 0.0,
+        particle_stride: 1,
+    },
+    Cuts {
+        detail: 0.5,
+        min_pixels: 0.0,
+        particle_stride: 1,
+    },
+    Cuts {
+        detail: 0.5,
+        min_pixels: 1.5,
+        particle_stride: 1,
+    },
+    Cuts {
+        detail: 0.35,
+        min_pixels: 3.0,
+        particle_stride: 1,
+    },
+    Cuts {
+        detail: 0.35,
+        min_pixels: 3.0,
+        particle_stride: 2,
+    },
+    Cuts {
+        detail: 0.25,
+        min_pixels: 6.0,
+        particle_stride: 4,
+    },
+];
+
+// Hard limits on the triangles and fragments one frame may draw, for
+// keeping the viewer responsive on slow machines. A frame over either limit
+// takes the next step of cuts for the frames after it, and once frames
+// have stayed well under for a while the last step is given back. No
+// limits means no cuts.
+pub struct FrameBudget {
+    pub triangles: Option<usize>,
+    pub fragments: Option<usize>,
+    step: usize,
+    calm_frames: u32,
+}
+
+impl FrameBudget {
+    pub fn new(triangles: Option<usize>, fragments: Option<usize>) -> Self {
+        FrameBudget {
+            triangles,
+            fragments,
+            step: 0,
+            calm_frames: 0,
+        }
+    }
+
+    pub fn cuts(&self) -> Cuts {
+        STEPS[self.step]
+    }
+
+    // How many steps of cuts are being taken, and how many there are
+    pub fn step(&self) -> (usize, usize) {
+        (self.step, STEPS.len() - 1)
+    }
+
+    // Reads and resets the counts the frame ran up, and decides the cuts
+    // for the next one
+    pub fn update(&mut self) {
+        let triangles = TRIANGLES.swap(0, Ordering::Relaxed);
+        let fragments = FRAGMENTS.swap(0, Ordering::Relaxed);
+        let share = |drawn: usize, limit: Option<usize>| {
+            limit.map_or(0.0, |limit| drawn as f32 / limit.max(1) as f32)
+        };
+        let load = share(triangles, self.triangles).max(share(fragments, self.fragments));
+
+        if load > 1.0 {
+            self.step = (self.step + 1).min(STEPS.len() - 1);
+            self.calm_frames = 0;
+        } else if load < CALM_LOAD && self.step > 0 {
+            self.calm_frames += 1;
+            if self.calm_frames >= CALM_FRAMES {
+                self.step -= 1;
+                self.calm_frames = 0;
+            }
+        } else {
+            self.calm_frames = 0;
+        }
+    }
+}
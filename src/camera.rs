@@ -1,11 +1,58 @@
-use nalgebra_glm::{rotate_vec3, Vec3};
+use nalgebra_glm::{
+    mat3_to_quat, quat_angle_axis, quat_rotate_vec3, quat_slerp, rotate_vec3, Mat3, Quat, Vec3,
+};
 use std::f32::consts::PI;
 
+// Frames a `fly_to` takes
+const FLIGHT_FRAMES: u32 = 45;
+// Closest `zoom` brings the eye to the center, so it never lands on it
+const MIN_DISTANCE: f32 = 0.5;
+// How close to straight up or down the view can turn, in radians short of
+// vertical. Any closer and there's no telling which way is right.
+const PITCH_MARGIN: f32 = 0.1;
+// Room kept between the eye and a body's bounding sphere, a little more than
+// the near plane so the surface in front of the eye isn't clipped away
+const COLLISION_MARGIN: f32 = 0.15;
+// Times the eye is pushed out per move, for spheres that overlap, where
+// leaving one can put it in the other
+const COLLISION_PASSES: usize = 4;
+// Turning or zooming slower than this a frame has come to a stop
+const RESTING_SPEED: f32 = 1e-5;
+
 pub struct Camera {
     pub eye: Vec3,
     pub center: Vec3,
     pub up: Vec3,
     pub has_changed: bool,
+    // Share of its turning and zooming the camera keeps from one frame to
+    // the next once `push` stops, from 0 for stopping dead to just under 1
+    // for drifting a long way
+    pub damping: f32,
+    flight: Option<Flight>,
+    // Bounding spheres of the bodies the eye stays out of, center and radius
+    obstacles: Vec<(Vec3, f32)>,
+    motion: Motion,
+}
+
+// How far the camera turns and zooms each frame on its own, in what `orbit`
+// and `zoom` take
+#[derive(Clone, Copy, Default)]
+struct Motion {
+    yaw: f32,
+    pitch: f32,
+    zoom: f32,
+}
+
+// Where the camera is heading and where it set off from. The view turns
+// along the shortest arc between the two attitudes while the pivot and the
+// distance to it are eased in a straight line, so the eye swings around
+// instead of cutting through whatever it's looking at.
+struct Flight {
+    from: (Quat, Vec3, f32),
+    to: (Quat, Vec3, f32),
+    eye: Vec3,
+    up: Vec3,
+    frame: u32,
 }
 
 impl Camera {
@@ -15,36 +62,162 @@ impl Camera {
             center,
             up,
             has_changed: true,
+            damping: 0.0,
+            flight: None,
+            obstacles: Vec::new(),
+            motion: Motion::default(),
         }
     }
 
-    pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
-        let radius_vector = self.eye - self.center;
-        let radius = radius_vector.magnitude();
+    // Speeds the camera up toward turning and zooming by these amounts a
+    // frame, which it gets to after a few frames of pushing and coasts down
+    // from after the last, by `damping`. Held down every frame it settles on
+    // exactly them, so the damping changes how the camera starts and stops
+    // but not how fast it goes.
+    pub fn push(&mut self, yaw: f32, pitch: f32, zoom: f32) {
+        let share = 1.0 - self.damping;
+        self.motion.yaw += yaw * share;
+        self.motion.pitch += pitch * share;
+        self.motion.zoom += zoom * share;
+    }
+
+    // Turns and zooms by what `push` built up, then lets it die down
+    fn coast(&mut self) {
+        let Motion { yaw, pitch, zoom } = self.motion;
+        if yaw.abs().max(pitch.abs()).max(zoom.abs()) < RESTING_SPEED {
+            self.motion = Motion::default();
+            return;
+        }
+        self.orbit(yaw, pitch);
+        self.zoom(zoom);
+        self.motion = Motion {
+            yaw: yaw * self.damping,
+            pitch: pitch * self.damping,
+            zoom: zoom * self.damping,
+        };
+    }
 
-        let current_yaw = radius_vector.z.atan2(radius_vector.x);
+    // Where the bodies are now. An eye they've moved into is pushed back
+    // out right away, as it is after every move.
+    pub fn set_obstacles(&mut self, obstacles: impl IntoIterator<Item = (Vec3, f32)>) {
+        self.obstacles.clear();
+        self.obstacles.extend(obstacles);
+        self.keep_outside();
+    }
 
-        let radius_xz =
-            (radius_vector.x * radius_vector.x + radius_vector.z * radius_vector.z).sqrt();
-        let current_pitch = (-radius_vector.y).atan2(radius_xz);
+    // Pushes the eye straight out of any sphere it's in, to COLLISION_MARGIN
+    // past its surface. Only the part of a move that goes into a body is
+    // undone, so the eye slides along it instead of stopping dead.
+    fn keep_outside(&mut self) {
+        for _ in 0..COLLISION_PASSES {
+            let mut moved = false;
+            for &(center, radius) in &self.obstacles {
+                let offset = self.eye - center;
+                let clearance = radius + COLLISION_MARGIN;
+                let distance = offset.magnitude();
+                if distance >= clearance {
+                    continue;
+                }
+                // Right at the middle there's no way out that's nearer than
+                // another, so it backs out the way it's looking from
+                let outward = if distance > f32::EPSILON {
+                    offset / distance
+                } else {
+                    (self.eye - self.center)
+                        .try_normalize(f32::EPSILON)
+                        .unwrap_or(Vec3::z())
+                };
+                self.eye = center + outward * clearance;
+                moved = true;
+            }
+            if !moved {
+                return;
+            }
+            self.has_changed = true;
+        }
+    }
 
-        let new_yaw = (current_yaw + delta_yaw) % (2.0 * PI);
-        let new_pitch = (current_pitch + delta_pitch).clamp(-PI / 2.0 + 0.1, PI / 2.0 - 0.1);
+    // Orientation of the view: the camera's right, up and backward axes as
+    // a rotation from +X, +Y and +Z
+    pub fn attitude(&self) -> Quat {
+        let back = (self.eye - self.center).normalize();
+        let right = self.up.cross(&back).normalize();
+        let up = back.cross(&right);
+        mat3_to_quat(&Mat3::from_columns(&[right, up, back]))
+    }
 
-        let new_eye = self.center
-            + Vec3::new(
-                radius * new_yaw.cos() * new_pitch.cos(),
-                -radius * new_pitch.sin(),
-                radius * new_yaw.sin() * new_pitch.cos(),
-            );
+    // Eases over to a new pose over the next FLIGHT_FRAMES calls to `update`
+    pub fn fly_to(&mut self, eye: Vec3, center: Vec3, up: Vec3) {
+        let distance = (self.eye - self.center).magnitude();
+        let from = (self.attitude(), self.center, distance);
+        let target = Camera::new(eye, center, up);
+        let to = (target.attitude(), center, (eye - center).magnitude());
+        self.flight = Some(Flight {
+            from,
+            to,
+            eye,
+            up,
+            frame: 0,
+        });
+        // Drifting on would cut the flight short
+        self.motion = Motion::default();
+    }
+
+    // Moves by what `push` built up, or else advances a flight started by
+    // `fly_to`, if there is one
+    pub fn update(&mut self) {
+        self.coast();
+        let Some(flight) = self.flight.as_mut() else {
+            return;
+        };
+        flight.frame += 1;
+        if flight.frame >= FLIGHT_FRAMES {
+            // Lands exactly on the requested pose
+            self.eye = flight.eye;
+            self.center = flight.to.1;
+            self.up = flight.up;
+            self.flight = None;
+        } else {
+            let t = flight.frame as f32 / FLIGHT_FRAMES as f32;
+            let t = t * t * (3.0 - 2.0 * t);
+            let attitude = quat_slerp(&flight.from.0, &flight.to.0, t);
+            let distance = flight.from.2 + (flight.to.2 - flight.from.2) * t;
+            self.center = flight.from.1.lerp(&flight.to.1, t);
+            self.eye = self.center + quat_rotate_vec3(&attitude, &Vec3::z()) * distance;
+            self.up = quat_rotate_vec3(&attitude, &Vec3::y());
+        }
+        self.keep_outside();
+        self.has_changed = true;
+    }
+
+    // Swings the eye around the center: yaw about the world's Y axis, pitch
+    // about the camera's horizontal axis, both as one rotation. Pitch stops
+    // short of straight up or down, where yaw would lose its meaning.
+    pub fn orbit(&mut self, delta_yaw: f32, delta_pitch: f32) {
+        let offset = self.eye - self.center;
+        let radius = offset.magnitude();
+
+        let limit = PI / 2.0 - PITCH_MARGIN;
+        let pitch = (-offset.y / radius).clamp(-1.0, 1.0).asin();
+        let delta_pitch = (pitch + delta_pitch).clamp(-limit, limit) - pitch;
+
+        let horizontal = Vec3::y().cross(&offset).normalize();
+        let rotation =
+            quat_angle_axis(-delta_yaw, &Vec3::y()) * quat_angle_axis(delta_pitch, &horizontal);
 
-        self.eye = new_eye;
+        self.eye = self.center + quat_rotate_vec3(&rotation, &offset);
+        self.keep_outside();
+        self.flight = None;
         self.has_changed = true;
     }
 
+    // Moves the eye `delta` toward the center, stopping MIN_DISTANCE short
     pub fn zoom(&mut self, delta: f32) {
-        let direction = (self.center - self.eye).normalize();
-        self.eye += direction * delta;
+        let offset = self.eye - self.center;
+        let distance = (offset.magnitude() - delta).max(MIN_DISTANCE);
+        self.eye = self.center + offset.normalize() * distance;
+        self.keep_outside();
+        self.flight = None;
         self.has_changed = true;
     }
 
@@ -57,11 +230,267 @@ impl Camera {
 
         let rotated = rotate_vec3(&radius_vector, angle_x, &Vec3::new(0.0, 1.0, 0.0));
 
+        // Tilting is skipped rather than turn the view too close to vertical
         let right = rotated.cross(&self.up).normalize();
-        let final_rotated = rotate_vec3(&rotated, angle_y, &right);
+        let tilted = rotate_vec3(&rotated, angle_y, &right);
+        let max_height = (PI / 2.0 - PITCH_MARGIN).sin() * radius;
+        let final_rotated = if tilted.y.abs() <= max_height || tilted.y.abs() < rotated.y.abs() {
+            tilted
+        } else {
+            rotated
+        };
 
         self.center = self.eye + final_rotated.normalize() * radius;
+        self.flight = None;
         self.has_changed = true;
     }
 
+    // Carries the eye along as the pivot moves to `center`, so the view of
+    // it stays the same. A flight is left to land first.
+    pub fn follow(&mut self, center: Vec3) {
+        if self.flight.is_some() || center == self.center {
+            return;
+        }
+        self.eye += center - self.center;
+        self.center = center;
+        self.keep_outside();
+        self.has_changed = true;
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::look_at;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const SEEDS: u64 = 32;
+    const STEPS: usize = 400;
+
+    fn start() -> Camera {
+        Camera::new(Vec3::new(0.0, 0.0, 5.0), Vec3::zeros(), Vec3::y())
+    }
+
+    // One random control, about as large as a frame of held keys or larger
+    fn random_input(camera: &mut Camera, rng: &mut StdRng) {
+        match rng.gen_range(0..3) {
+            0 => camera.orbit(rng.gen_range(-0.3..0.3), rng.gen_range(-0.3..0.3)),
+            1 => camera.zoom(rng.gen_range(-1.5..1.5)),
+            _ => camera.move_center(Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                0.0,
+            )),
+        }
+    }
+
+    // Somewhere a bookmark could have been saved: upright, not looking
+    // straight up or down
+    fn random_pose(rng: &mut StdRng) -> (Vec3, Vec3) {
+        let center = Vec3::new(
+            rng.gen_range(-5.0..5.0),
+            rng.gen_range(-5.0..5.0),
+            rng.gen_range(-5.0..5.0),
+        );
+        let yaw = rng.gen_range(0.0..2.0 * PI);
+        let pitch = rng.gen_range(-1.2..1.2f32);
+        let direction = Vec3::new(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        );
+        (center + direction * rng.gen_range(1.0..20.0), center)
+    }
+
+    fn check(camera: &Camera, seed: u64, step: usize) {
+        let context = format!("seed {} step {}", seed, step);
+        let finite = |v: &Vec3| v.iter().all(|c| c.is_finite());
+        assert!(
+            finite(&camera.eye) && finite(&camera.center) && finite(&camera.up),
+            "{}: eye {:?} center {:?} up {:?}",
+            context,
+            camera.eye,
+            camera.center,
+            camera.up
+        );
+        assert!(
+            (camera.up.magnitude() - 1.0).abs() < 1e-3,
+            "{}: up {:?}",
+            context,
+            camera.up
+        );
+        let distance = (camera.eye - camera.center).magnitude();
+        assert!(
+            distance >= MIN_DISTANCE * 0.999,
+            "{}: distance {}",
+            context,
+            distance
+        );
+        let view = look_at(&camera.eye, &camera.center, &camera.up);
+        assert!(
+            view.iter().all(|c| c.is_finite()),
+            "{}: view {:?}",
+            context,
+            view
+        );
+    }
+
+    #[test]
+    fn random_controls_keep_the_camera_valid() {
+        let max_height = (PI / 2.0 - PITCH_MARGIN).sin() + 1e-4;
+        for seed in 0..SEEDS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut camera = start();
+            for step in 0..STEPS {
+                random_input(&mut camera, &mut rng);
+                check(&camera, seed, step);
+
+                let offset = camera.eye - camera.center;
+                let height = offset.y.abs() / offset.magnitude();
+                assert!(
+                    height <= max_height,
+                    "seed {} step {}: pitched to {}",
+                    seed,
+                    step,
+                    height
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn flights_stay_valid_when_interrupted() {
+        for seed in 0..SEEDS {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut camera = start();
+            for step in 0..STEPS {
+                if rng.gen_bool(0.05) {
+                    let (eye, center) = random_pose(&mut rng);
+                    camera.fly_to(eye, center, Vec3::y());
+                } else if rng.gen_bool(0.2) {
+                    random_input(&mut camera, &mut rng);
+                }
+                camera.update();
+                check(&camera, seed, step);
+            }
+        }
+    }
+
+    #[test]
+    fn flights_land_on_their_target() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut camera = start();
+        for _ in 0..SEEDS {
+            let (eye, center) = random_pose(&mut rng);
+            camera.fly_to(eye, center, Vec3::y());
+            for _ in 0..FLIGHT_FRAMES {
+                camera.update();
+            }
+            assert_eq!(
+                (camera.eye, camera.center, camera.up),
+                (eye, center, Vec3::y())
+            );
+        }
+    }
+
+    #[test]
+    fn zoom_stops_at_the_minimum_distance() {
+        let mut camera = start();
+        for _ in 0..100 {
+            camera.zoom(0.3);
+        }
+        let distance = (camera.eye - camera.center).magnitude();
+        assert!(
+            (distance - MIN_DISTANCE).abs() < 1e-4,
+            "distance {}",
+            distance
+        );
+
+        // One step far past the center doesn't flip the eye through it
+        let mut camera = start();
+        camera.zoom(50.0);
+        assert!(camera.eye.z > 0.0, "eye {:?}", camera.eye);
+    }
+
+    #[test]
+    fn pushes_ease_in_and_coast_to_a_stop() {
+        let yaw = |camera: &Camera| camera.eye.x.atan2(camera.eye.z);
+        // Without damping a push is one step of `orbit`
+        let mut steady = start();
+        steady.push(0.1, 0.0, 0.0);
+        steady.update();
+        let mut stepped = start();
+        stepped.orbit(0.1, 0.0);
+        assert!((yaw(&steady) - yaw(&stepped)).abs() < 1e-5);
+
+        let mut camera = start();
+        camera.damping = 0.8;
+        let mut turned = Vec::new();
+        for frame in 0..90 {
+            if frame < 30 {
+                camera.push(-0.02, 0.0, 0.0);
+            }
+            let before = yaw(&camera);
+            camera.update();
+            turned.push(yaw(&camera) - before);
+        }
+        // Starts slow, reaches the pushed speed, and keeps going a while
+        // after the push ends before it stops
+        assert!(turned[0] < turned[29] * 0.5, "turned {:?}", turned);
+        assert!((turned[29] - 0.02).abs() < 1e-3, "turned {:?}", turned);
+        assert!(turned[32] > 0.005, "turned {:?}", turned);
+        assert_eq!(turned[89], 0.0, "turned {:?}", turned);
+    }
+
+    #[test]
+    fn the_eye_stays_out_of_bodies() {
+        let clearance = 1.0 + COLLISION_MARGIN;
+        // Zooming into the body it orbits stops at its surface
+        let mut camera = start();
+        camera.set_obstacles([(Vec3::zeros(), 1.0)]);
+        for _ in 0..100 {
+            camera.zoom(0.3);
+        }
+        let distance = camera.eye.magnitude();
+        assert!((distance - clearance).abs() < 1e-4, "distance {}", distance);
+
+        // Orbiting a point beside a body slides around it, and the rest of
+        // the swing still happens
+        let mut camera = Camera::new(Vec3::new(0.0, 0.0, 3.0), Vec3::z() * 1.5, Vec3::y());
+        camera.set_obstacles([(Vec3::zeros(), 1.0)]);
+        for _ in 0..40 {
+            camera.orbit(0.1, 0.0);
+            let distance = camera.eye.magnitude();
+            assert!(distance >= clearance - 1e-4, "distance {}", distance);
+        }
+        assert!(camera.eye.z < 1.5, "eye {:?}", camera.eye);
+
+        // A body that moves onto the eye pushes it back out
+        let mut camera = start();
+        camera.set_obstacles([(Vec3::new(0.0, 0.0, 4.5), 1.0)]);
+        let distance = (camera.eye - Vec3::new(0.0, 0.0, 4.5)).magnitude();
+        assert!((distance - clearance).abs() < 1e-4, "distance {}", distance);
+    }
+
+    #[test]
+    fn orbits_and_zooms_around_a_followed_body() {
+        let mut camera = start();
+        let body = |frame: usize| Vec3::new((frame as f32 * 0.05).cos(), 0.0, 0.0) * 3.0;
+        camera.follow(body(0));
+        let mut distance = (camera.eye - camera.center).magnitude();
+        for frame in 1..60 {
+            camera.follow(body(frame));
+            camera.orbit(0.05, 0.01);
+            if frame % 10 == 0 {
+                camera.zoom(0.5);
+                distance -= 0.5;
+            }
+            assert!((camera.center - body(frame)).magnitude() < 1e-5);
+            let now = (camera.eye - camera.center).magnitude();
+            let drift = (now - distance).abs();
+            assert!(drift < 1e-3, "{} away, not {}", now, distance);
+        }
+    }
 }
@@ -0,0 +1,238 @@
+use crate::vertex::Vertex;
+use nalgebra_glm::{Mat4, Vec4};
+#[cfg(feature = "diagnostics")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// The near plane keeps w well away from zero for a perspective projection,
+// but not for every projection matrix, and a corner cut right on it can come
+// out a rounding error short. Dividing by anything smaller throws the corner
+// far enough off screen to drag a sliver across all of it.
+const MIN_W: f32 = 1e-5;
+
+// The sides of the view volume in clip space, -w <= x, y, z <= w, and one
+// more holding w to at least MIN_W. A vertex is on the inside of each where
+// its dot product with the plane's normal is at least the offset. With
+// reversed Z, z = w is the near plane and the volume ends at z = 0 rather
+// than at z = -w.
+const PLANES: [(Vec4, f32); 7] = [
+    (Vec4::new(1.0, 0.0, 0.0, 1.0), 0.0),
+    (Vec4::new(-1.0, 0.0, 0.0, 1.0), 0.0),
+    (Vec4::new(0.0, 1.0, 0.0, 1.0), 0.0),
+    (Vec4::new(0.0, -1.0, 0.0, 1.0), 0.0),
+    (Vec4::new(0.0, 0.0, 1.0, 1.0), 0.0),
+    (Vec4::new(0.0, 0.0, -1.0, 1.0), 0.0),
+    (Vec4::new(0.0, 0.0, 0.0, 1.0), MIN_W),
+];
+
+// Triangles thrown away this frame for a coordinate that isn't finite, going
+// in or after the divide, as opposed to those the view volume cuts away. It
+// should stay at zero; with diagnostics on, the HUD shows it when it
+// doesn't.
+#[cfg(feature = "diagnostics")]
+pub static REJECTED: AtomicUsize = AtomicUsize::new(0);
+
+fn reject() {
+    #[cfg(feature = "diagnostics")]
+    REJECTED.fetch_add(1, Ordering::Relaxed);
+}
+
+// Cuts a triangle of clip-space vertices down to the part inside the view
+// volume and hands `emit` a fan of triangles covering it, or nothing if none
+// of it is inside. Most triangles are wholly in and go through as they are.
+// Whatever's left is in front of the near plane with w at least MIN_W at
+// every corner, so the divide after this is safe. A triangle with a NaN or
+// infinite corner would pass every test, since comparisons with NaN all
+// fail, so it's rejected before any.
+pub fn clip_triangle(triangle: &[Vertex], reversed_z: bool, mut emit: impl FnMut([Vertex; 3])) {
+    if !triangle[..3]
+        .iter()
+        .all(|vertex| vertex.clip_position.iter().all(|c| c.is_finite()))
+    {
+        reject();
+        return;
+    }
+    let mut planes = PLANES;
+    if reversed_z {
+        planes[4].0.w = 0.0;
+    }
+    let distances =
+        |vertex: &Vertex| planes.map(|(normal, offset)| normal.dot(&vertex.clip_position) - offset);
+    let corners = [0, 1, 2].map(|index| distances(&triangle[index]));
+    let mut straddles = false;
+    for plane in 0..planes.len() {
+        let outside = corners.iter().filter(|d| d[plane] < 0.0).count();
+        if outside == 3 {
+            return;
+        }
+        straddles |= outside > 0;
+    }
+    if !straddles {
+        emit([0, 1, 2].map(|index| triangle[index].clone()));
+        return;
+    }
+
+    // Sutherland-Hodgman, one plane at a time
+    let mut polygon = triangle[..3].to_vec();
+    for (normal, offset) in planes {
+        let mut kept = Vec::with_capacity(polygon.len() + 1);
+        for (index, current) in polygon.iter().enumerate() {
+            let next = &polygon[(index + 1) % polygon.len()];
+            let (from, to) = (
+                normal.dot(&current.clip_position) - offset,
+                normal.dot(&next.clip_position) - offset,
+            );
+            if from >= 0.0 {
+                kept.push(current.clone());
+            }
+            if (from >= 0.0) != (to >= 0.0) {
+                kept.push(current.lerp(next, from / (from - to)));
+            }
+        }
+        polygon = kept;
+        if polygon.len() < 3 {
+            return;
+        }
+    }
+    for index in 1..polygon.len() - 1 {
+        emit([
+            polygon[0].clone(),
+            polygon[index].clone(),
+            polygon[index + 1].clone(),
+        ]);
+    }
+}
+
+// The perspective divide and the viewport transform, from clip space to a
+// framebuffer pixel and the depth the viewport maps to, for each corner of a
+// triangle `clip_triangle` handed out. False, and the triangle rejected, if
+// any corner still comes out of it non-finite.
+pub fn to_screen(triangle: &mut [Vertex; 3], viewport_matrix: &Mat4) -> bool {
+    for vertex in triangle.iter_mut() {
+        let ndc = vertex.clip_position.xyz() / vertex.clip_position.w;
+        vertex.screen_position = (viewport_matrix * ndc.push(1.0)).xyz();
+        if !vertex.screen_position.iter().all(|c| c.is_finite()) {
+            reject();
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::{Vec2, Vec3};
+
+    // A corner at (x, y, z, 1) in clip space, with texture coordinates that
+    // are a linear function of it, so any corner cut from its edges must
+    // carry `expected_tex_coords` of where it ended up
+    fn corner(x: f32, y: f32, z: f32) -> Vertex {
+        let mut vertex = Vertex::new(Vec3::new(x, y, z), Vec3::z(), Vec2::zeros());
+        vertex.clip_position = Vec4::new(x, y, z, 1.0);
+        vertex.tex_coords = expected_tex_coords(&vertex);
+        vertex
+    }
+
+    fn expected_tex_coords(vertex: &Vertex) -> Vec2 {
+        let clip = vertex.clip_position;
+        Vec2::new(clip.x + clip.z, clip.y - 2.0 * clip.z)
+    }
+
+    fn clipped(triangle: [Vertex; 3]) -> Vec<[Vertex; 3]> {
+        let mut triangles = Vec::new();
+        clip_triangle(&triangle, false, |triangle| triangles.push(triangle));
+        triangles
+    }
+
+    // In front of the near plane, z >= -w, and carrying what it was cut from
+    fn assert_cut_properly(triangles: &[[Vertex; 3]]) {
+        for vertex in triangles.iter().flatten() {
+            let clip = vertex.clip_position;
+            assert!(
+                clip.z + clip.w >= -1e-6,
+                "{:?} is behind the near plane",
+                clip
+            );
+            assert!(
+                (vertex.tex_coords - expected_tex_coords(vertex)).norm() < 1e-5,
+                "{:?} at {:?}",
+                vertex.tex_coords,
+                clip
+            );
+        }
+    }
+
+    #[test]
+    fn a_triangle_inside_goes_through_as_it_is() {
+        let triangle = [
+            corner(-0.5, -0.5, 0.2),
+            corner(0.5, -0.5, 0.4),
+            corner(0.0, 0.5, -0.3),
+        ];
+        let triangles = clipped(triangle.clone());
+        assert_eq!(triangles.len(), 1);
+        for (kept, original) in triangles[0].iter().zip(&triangle) {
+            assert_eq!(kept.clip_position, original.clip_position);
+            assert_eq!(kept.tex_coords, original.tex_coords);
+        }
+    }
+
+    #[test]
+    fn a_triangle_outside_is_dropped() {
+        // Behind the near plane at every corner
+        assert!(clipped([
+            corner(-0.5, -0.5, -2.0),
+            corner(0.5, -0.5, -3.0),
+            corner(0.0, 0.5, -1.5),
+        ])
+        .is_empty());
+        // Off to one side, each corner in front of the near plane
+        assert!(clipped([
+            corner(1.5, -0.5, 0.0),
+            corner(2.5, -0.5, 0.0),
+            corner(2.0, 0.5, 0.0),
+        ])
+        .is_empty());
+    }
+
+    #[test]
+    fn one_corner_in_front_leaves_one_triangle() {
+        let triangles = clipped([
+            corner(0.0, 0.0, 0.0),
+            corner(0.6, 0.0, -3.0),
+            corner(0.0, 0.6, -3.0),
+        ]);
+        assert_eq!(triangles.len(), 1);
+        assert_cut_properly(&triangles);
+        // A third of the way down each edge, where it crosses z = -1
+        let [kept, b, c] = &triangles[0];
+        assert_eq!(kept.clip_position, Vec4::new(0.0, 0.0, 0.0, 1.0));
+        assert!((b.clip_position - Vec4::new(0.2, 0.0, -1.0, 1.0)).norm() < 1e-6);
+        assert!((c.clip_position - Vec4::new(0.0, 0.2, -1.0, 1.0)).norm() < 1e-6);
+        assert!((b.tex_coords - Vec2::new(-0.8, 2.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn two_corners_in_front_leave_two_triangles() {
+        let triangles = clipped([
+            corner(-0.5, 0.0, 0.0),
+            corner(0.5, 0.0, 0.0),
+            corner(0.0, 0.5, -3.0),
+        ]);
+        assert_eq!(triangles.len(), 2);
+        assert_cut_properly(&triangles);
+        // The cut edge runs between the two corners on the near plane
+        let on_plane: Vec<Vec4> = triangles
+            .iter()
+            .flatten()
+            .map(|vertex| vertex.clip_position)
+            .filter(|clip| (clip.z + clip.w).abs() < 1e-6)
+            .collect();
+        for expected in [
+            Vec4::new(-1.0 / 3.0, 1.0 / 6.0, -1.0, 1.0),
+            Vec4::new(1.0 / 3.0, 1.0 / 6.0, -1.0, 1.0),
+        ] {
+            assert!(on_plane.iter().any(|clip| (clip - expected).norm() < 1e-6));
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use nalgebra_glm::Vec3;
 use std::fmt;
 
 #[derive(Debug, Clone, Copy)]
@@ -16,10 +17,38 @@ impl Color {
         Color { r: 0, g: 0, b: 0 }
     }
 
-    pub fn to_hex(&self) -> u32 {
+    // Channels as floats, 1.0 being full brightness. Out of range values and
+    // NaN saturate instead of wrapping.
+    pub fn from_f32(r: f32, g: f32, b: f32) -> Self {
+        let channel = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+        Color {
+            r: channel(r),
+            g: channel(g),
+            b: channel(b),
+        }
+    }
+
+    pub fn from_vec3(rgb: Vec3) -> Self {
+        Color::from_f32(rgb.x, rgb.y, rgb.z)
+    }
+
+    pub const fn from_hex(hex: u32) -> Self {
+        Color {
+            r: ((hex >> 16) & 0xFF) as u8,
+            g: ((hex >> 8) & 0xFF) as u8,
+            b: (hex & 0xFF) as u8,
+        }
+    }
+
+    pub fn to_hex(self) -> u32 {
         ((self.r as u32) << 16) | ((self.g as u32) << 8) | (self.b as u32)
     }
 
+    // Channels as floats, 1.0 being full brightness
+    pub fn to_vec3(self) -> Vec3 {
+        Vec3::new(self.r as f32, self.g as f32, self.b as f32) / 255.0
+    }
+
     // Linear interpolation between two colors
     pub fn lerp(&self, other: &Color, t: f32) -> Self {
         let t = t.clamp(0.0, 1.0);
@@ -39,7 +68,7 @@ impl Color {
     }
 }
 
-use std::ops::Add;
+use std::ops::{Add, AddAssign, Mul, MulAssign, Sub};
 
 impl Add for Color {
     type Output = Color;
@@ -53,7 +82,23 @@ impl Add for Color {
     }
 }
 
-use std::ops::Mul;
+impl AddAssign for Color {
+    fn add_assign(&mut self, other: Color) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, other: Color) -> Color {
+        Color {
+            r: self.r.saturating_sub(other.r),
+            g: self.g.saturating_sub(other.g),
+            b: self.b.saturating_sub(other.b),
+        }
+    }
+}
 
 impl Mul<f32> for Color {
     type Output = Color;
@@ -67,6 +112,26 @@ impl Mul<f32> for Color {
     }
 }
 
+impl MulAssign<f32> for Color {
+    fn mul_assign(&mut self, scalar: f32) {
+        *self = *self * scalar;
+    }
+}
+
+// Channel-wise product, e.g. tinting a surface by the color of its light
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, other: Color) -> Color {
+        let channel = |a: u8, b: u8| ((a as u16 * b as u16 + 127) / 255) as u8;
+        Color {
+            r: channel(self.r, other.r),
+            g: channel(self.g, other.g),
+            b: channel(self.b, other.b),
+        }
+    }
+}
+
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "Color(r: {}, g: {}, b: {})", self.r, self.g, self.b)
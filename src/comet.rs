@@ -0,0 +1,101 @@
+use crate::framebuffer::Framebuffer;
+use crate::kepler::KeplerOrbit;
+#[cfg(feature = "particles")]
+use crate::particles::{Blend, ColorRamp, ParticleStyle, Particles};
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+#[cfg(feature = "particles")]
+use rand::rngs::StdRng;
+#[cfg(feature = "particles")]
+use rand::{Rng, SeedableRng};
+
+// Particles per frame at the orbit's mean distance. Swinging in close the
+// comet outgasses more, with the inverse square of its distance.
+#[cfg(feature = "particles")]
+const EMIT_RATE: f32 = 4.0;
+#[cfg(feature = "particles")]
+const MAX_EMIT: f32 = 24.0;
+// Push away from the sun on every particle, world units per frame²
+#[cfg(feature = "particles")]
+const SOLAR_WIND: f32 = 0.0012;
+
+// Faint blue glow that fades out over each particle's life
+#[cfg(feature = "particles")]
+const TAIL: ParticleStyle = ParticleStyle {
+    lifetime: (60, 140),
+    color: ColorRamp {
+        start: Vec3::new(0.35, 0.55, 0.8),
+        end: Vec3::new(0.0, 0.0, 0.0),
+        falloff: 2.0,
+    },
+    blend: Blend::Additive,
+    size: 0.0,
+};
+
+// A small icy body on an elongated orbit, trailing gas and dust. The tail is
+// a stream of particles let go at the nucleus with some of its orbital speed
+// and blown away from the sun, so it points away from it and bends a little
+// behind the comet's path. Without particles it's only the nucleus.
+#[derive(Clone)]
+pub struct Comet {
+    pub orbit: KeplerOrbit,
+    #[cfg(feature = "particles")]
+    tail: Particles,
+    #[cfg(feature = "particles")]
+    rng: StdRng,
+}
+
+impl Comet {
+    #[cfg_attr(not(feature = "particles"), allow(unused_variables))]
+    pub fn new(orbit: KeplerOrbit, seed: u64) -> Self {
+        Comet {
+            orbit,
+            #[cfg(feature = "particles")]
+            tail: Particles::new(TAIL, seed + 1),
+            #[cfg(feature = "particles")]
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn nucleus(&self, center: Vec3, time: u32) -> Vec3 {
+        center + self.orbit.position(time)
+    }
+
+    // Ages and moves the tail, then emits this frame's particles.
+    // `light_dir` points toward the sun.
+    #[cfg(feature = "particles")]
+    pub fn update(&mut self, center: Vec3, time: u32, light_dir: Vec3) {
+        let away = -light_dir.normalize();
+        self.tail.update(|_| away * SOLAR_WIND, |_| true);
+
+        let nucleus = self.nucleus(center, time);
+        let velocity = nucleus - self.nucleus(center, time.saturating_sub(1));
+        let distance = (nucleus - center).magnitude().max(0.01);
+        let activity = (self.orbit.semi_major_axis / distance).powi(2);
+        let count = self.tail.due((EMIT_RATE * activity).min(MAX_EMIT));
+        for _ in 0..count {
+            let jitter = Vec3::new(
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+            ) * 0.002;
+            let inherited = velocity * self.rng.gen_range(0.1..0.3);
+            self.tail.spawn(nucleus, inherited + jitter);
+        }
+    }
+
+    #[cfg(not(feature = "particles"))]
+    pub fn update(&mut self, _center: Vec3, _time: u32, _light_dir: Vec3) {}
+
+    pub fn clear(&mut self) {
+        #[cfg(feature = "particles")]
+        self.tail.clear();
+    }
+
+    // Hidden behind anything nearer but never hiding what's behind it
+    #[cfg_attr(not(feature = "particles"), allow(unused_variables))]
+    pub fn draw_tail(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        #[cfg(feature = "particles")]
+        self.tail.draw(framebuffer, uniforms);
+    }
+}
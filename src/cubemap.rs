@@ -0,0 +1,243 @@
+use crate::color::Color;
+use crate::error::{self, Error};
+use crate::framebuffer::Framebuffer;
+use crate::png;
+use crate::sh::ShIrradiance;
+use crate::sky::{Backdrop, Sky, AMBIENT_STRENGTH};
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+use std::f32::consts::FRAC_PI_2;
+use std::fs;
+
+// Faces of the map the bodies' reflections are looked up in when there's no
+// skybox
+pub const REFLECTION_SIZE: usize = 64;
+
+// Largest faces the light the map sheds on the bodies is worked out from;
+// nine coefficients don't need more
+const IRRADIANCE_SIZE: usize = 16;
+
+// File names of the faces in a skybox directory, in the order of `faces`
+const FACE_NAMES: [&str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+// Radiance all around a point stored as the six square faces of a cube, +X,
+// -X, +Y, -Y, +Z and -Z, each seen from the center with its top row toward
+// +Y, or toward -Z and +Z for the top and bottom faces. A direction is
+// looked up on the face it points through, so every texel covers about the
+// same solid angle and the poles don't bunch up like they do in a
+// latitude-longitude map.
+//
+// Smaller copies of the faces, each half the size of the one before, are
+// kept for blurred lookups that cover many texels of the full-size faces.
+pub struct Cubemap {
+    levels: Vec<Level>,
+}
+
+// The six faces at one size
+struct Level {
+    size: usize,
+    faces: [Vec<Vec3>; 6],
+}
+
+impl Level {
+    // Bilinear lookup on `face`, clamped at its edges
+    fn sample(&self, face: usize, u: f32, v: f32) -> Vec3 {
+        let last = (self.size - 1) as f32;
+        let x = (u * self.size as f32 - 0.5).clamp(0.0, last);
+        let y = (v * self.size as f32 - 0.5).clamp(0.0, last);
+        let (x0, y0) = (x as usize, y as usize);
+        let (x1, y1) = ((x0 + 1).min(self.size - 1), (y0 + 1).min(self.size - 1));
+        let (fx, fy) = (x.fract(), y.fract());
+
+        let texel = |x: usize, y: usize| self.faces[face][y * self.size + x];
+        let top = texel(x0, y0).lerp(&texel(x1, y0), fx);
+        let bottom = texel(x0, y1).lerp(&texel(x1, y1), fx);
+        top.lerp(&bottom, fy)
+    }
+
+    // Half the size, every texel the average of the two by two it covers.
+    // An odd last row and column are left out.
+    fn halved(&self) -> Level {
+        let size = (self.size / 2).max(1);
+        let faces = std::array::from_fn(|face| {
+            let texel = |x: usize, y: usize| {
+                self.faces[face][y.min(self.size - 1) * self.size + x.min(self.size - 1)]
+            };
+            let mut texels = Vec::with_capacity(size * size);
+            for y in 0..size {
+                for x in 0..size {
+                    let (x, y) = (2 * x, 2 * y);
+                    let sum = texel(x, y) + texel(x + 1, y) + texel(x, y + 1) + texel(x + 1, y + 1);
+                    texels.push(sum / 4.0);
+                }
+            }
+            texels
+        });
+        Level { size, faces }
+    }
+}
+
+impl Cubemap {
+    // The sky's latitude-longitude map resampled onto `size` by `size` faces
+    pub fn from_sky(sky: &Sky, size: usize) -> Self {
+        let faces = std::array::from_fn(|face| {
+            let mut texels = Vec::with_capacity(size * size);
+            for y in 0..size {
+                for x in 0..size {
+                    let u = (x as f32 + 0.5) / size as f32;
+                    let v = (y as f32 + 0.5) / size as f32;
+                    texels.push(sky.radiance(&face_direction(face, u, v)));
+                }
+            }
+            texels
+        });
+        Cubemap::with_levels(Level { size, faces })
+    }
+
+    // Reads px.png, nx.png, py.png, ny.png, pz.png and nz.png out of `dir`.
+    // The faces have to be square, all the same size, and written the way
+    // png::encode writes them.
+    pub fn load(dir: &str) -> error::Result<Self> {
+        let mut size = None;
+        let mut faces: [Vec<Vec3>; 6] = Default::default();
+        for (face, name) in faces.iter_mut().zip(FACE_NAMES) {
+            let path = format!("{}/{}.png", dir, name);
+            let bytes = fs::read(&path).map_err(Error::io(&path))?;
+            let image_error = |message: &str| Error::Image {
+                path: path.clone(),
+                message: message.to_string(),
+            };
+            let (width, height, pixels) =
+                png::decode(&bytes).ok_or_else(|| image_error("not a PNG this can read"))?;
+            if width != height || size.is_some_and(|size| size != width) {
+                return Err(image_error("faces must be square and the same size"));
+            }
+            size = Some(width);
+            *face = pixels
+                .iter()
+                .map(|&pixel| Color::from_hex(pixel).to_vec3())
+                .collect();
+        }
+        Ok(Cubemap::with_levels(Level {
+            size: size.unwrap_or_default(),
+            faces,
+        }))
+    }
+
+    // `full` and its halvings down to a single texel
+    fn with_levels(full: Level) -> Self {
+        let mut levels = vec![full];
+        while let Some(last) = levels.last().filter(|level| level.size > 1) {
+            let next = last.halved();
+            levels.push(next);
+        }
+        Cubemap { levels }
+    }
+
+    // The light the faces shed on the bodies around them, as bright as the
+    // nebula's
+    pub fn irradiance(&self) -> ShIrradiance {
+        let level = self
+            .levels
+            .iter()
+            .find(|level| level.size <= IRRADIANCE_SIZE)
+            .unwrap_or(&self.levels[0]);
+        let size = level.size;
+        let texels = (0..6).flat_map(|face| (0..size * size).map(move |index| (face, index)));
+        ShIrradiance::project(texels.map(|(face, index)| {
+            let u = ((index % size) as f32 + 0.5) / size as f32;
+            let v = ((index / size) as f32 + 0.5) / size as f32;
+            // Texels toward a face's corners are farther off and seen at a
+            // slant, so they cover less of the sphere
+            let (right, down) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+            let solid_angle =
+                4.0 / (size * size) as f32 / (1.0 + right * right + down * down).powf(1.5);
+            (
+                face_direction(face, u, v),
+                level.faces[face][index] * AMBIENT_STRENGTH,
+                solid_angle,
+            )
+        }))
+    }
+
+    // Bilinear lookup on the face `direction` points through, clamped at the
+    // face's edges
+    pub fn sample(&self, direction: &Vec3) -> Vec3 {
+        let (face, u, v) = face_coordinates(direction);
+        self.levels[0].sample(face, u, v)
+    }
+
+    // The average around `direction` over a cone about `spread` radians
+    // across, read from the two copies of the faces whose texels come
+    // closest to that size and blended between them
+    pub fn sample_blurred(&self, direction: &Vec3, spread: f32) -> Vec3 {
+        let (face, u, v) = face_coordinates(direction);
+        // A face spans a right angle, so this is about a texel's width at
+        // full size
+        let texel = FRAC_PI_2 / self.levels[0].size as f32;
+        let lod = (spread / texel)
+            .max(1.0)
+            .log2()
+            .min((self.levels.len() - 1) as f32);
+        let level = lod as usize;
+        let near = self.levels[level].sample(face, u, v);
+        match self.levels.get(level + 1) {
+            Some(far) => near.lerp(&far.sample(face, u, v), lod.fract()),
+            None => near,
+        }
+    }
+
+    // Fills the current scissor rectangle with the cube as seen by the
+    // camera in `uniforms`, as a skybox behind everything else, or with what
+    // `backdrop` kept of it if that hasn't moved
+    pub fn draw(
+        &self,
+        framebuffer: &mut Framebuffer,
+        uniforms: &Uniforms,
+        backdrop: &mut Backdrop,
+    ) {
+        backdrop.draw(framebuffer, uniforms, |direction| self.sample(direction));
+    }
+}
+
+// Which face `direction` points through, and where on it, with (0, 0) at
+// the face's top-left corner
+fn face_coordinates(direction: &Vec3) -> (usize, f32, f32) {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    // The face's right and down directions, and the distance along its axis
+    let (face, right, down, along) = if ax >= ay && ax >= az {
+        if x > 0.0 {
+            (0, -z, -y, ax)
+        } else {
+            (1, z, -y, ax)
+        }
+    } else if ay >= az {
+        if y > 0.0 {
+            (2, x, z, ay)
+        } else {
+            (3, x, -z, ay)
+        }
+    } else if z > 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    let along = along.max(f32::MIN_POSITIVE);
+    let (u, v) = (right / along, down / along);
+    (face, u * 0.5 + 0.5, v * 0.5 + 0.5)
+}
+
+// Unit direction through (u, v) on `face`, the inverse of `face_coordinates`
+pub fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    let (right, down) = (u * 2.0 - 1.0, v * 2.0 - 1.0);
+    let direction = match face {
+        0 => Vec3::new(1.0, -down, -right),
+        1 => Vec3::new(-1.0, -down, right),
+        2 => Vec3::new(right, 1.0, down),
+        3 => Vec3::new(right, -1.0, -down),
+        4 => Vec3::new(right, -down, 1.0),
+        _ => Vec3::new(-right, -down, -1.0),
+    };
+    direction.normalize()
+}
@@ -0,0 +1,443 @@
+use nalgebra_glm::Vec3;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+// Symmetric 4x4 error quadric stored as its upper triangle
+#[derive(Clone, Copy)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn zero() -> Self {
+        Quadric([0.0; 10])
+    }
+
+    // Plane n·p + d = 0, scaled by `weight` (the triangle area)
+    fn from_plane(normal: Vec3, d: f32, weight: f32) -> Self {
+        let [a, b, c, d] = [normal.x, normal.y, normal.z, d].map(f64::from);
+        let w = f64::from(weight);
+        Quadric([
+            a * a * w,
+            a * b * w,
+            a * c * w,
+            a * d * w,
+            b * b * w,
+            b * c * w,
+            b * d * w,
+            c * c * w,
+            c * d * w,
+            d * d * w,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (value, other) in self.0.iter_mut().zip(other.0.iter()) {
+            *value += other;
+        }
+    }
+
+    fn error(&self, point: Vec3) -> f64 {
+        let q = &self.0;
+        let [x, y, z] = [point.x, point.y, point.z].map(f64::from);
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+struct Candidate {
+    cost: f64,
+    keep: usize,
+    remove: usize,
+    position: Vec3,
+    versions: (u32, u32),
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Reversed so the BinaryHeap pops the cheapest collapse first
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Decimator<A> {
+    positions: Vec<Vec3>,
+    corners: Vec<(usize, A)>,
+    face_alive: Vec<bool>,
+    vertex_faces: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    versions: Vec<u32>,
+    removed: Vec<bool>,
+}
+
+impl<A: Copy> Decimator<A> {
+    fn face(&self, face: usize) -> [usize; 3] {
+        [0, 1, 2].map(|i| self.corners[face * 3 + i].0)
+    }
+
+    fn candidate(&self, a: usize, b: usize) -> Candidate {
+        let mut quadric = self.quadrics[a];
+        quadric.add(&self.quadrics[b]);
+
+        let midpoint = (self.positions[a] + self.positions[b]) * 0.5;
+        let (position, cost) = [self.positions[a], self.positions[b], midpoint]
+            .into_iter()
+            .map(|p| (p, quadric.error(p)))
+            .min_by(|x, y| x.1.total_cmp(&y.1))
+            .unwrap();
+
+        Candidate {
+            cost,
+            keep: a,
+            remove: b,
+            position,
+            versions: (self.versions[a], self.versions[b]),
+        }
+    }
+
+    // Every vertex sharing a surviving face with `vertex`
+    fn neighbours(&self, vertex: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = self.vertex_faces[vertex]
+            .iter()
+            .filter(|&&face| self.face_alive[face])
+            .flat_map(|&face| self.face(face))
+            .filter(|&other| other != vertex)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    // The link condition: the two ends may only share the neighbours across
+    // the faces on their edge, or else collapsing it pinches the surface
+    // together where another vertex touches both. Nor may a face moved over
+    // to `keep` land on one it already has, which is all that's left of a
+    // tetrahedron.
+    fn keeps_manifold(&self, keep: usize, remove: usize) -> bool {
+        let around_keep = self.neighbours(keep);
+        let shared = self
+            .neighbours(remove)
+            .into_iter()
+            .filter(|vertex| around_keep.binary_search(vertex).is_ok())
+            .count();
+        let across = self.vertex_faces[keep]
+            .iter()
+            .filter(|&&face| self.face_alive[face] && self.face(face).contains(&remove))
+            .count();
+        if shared != across {
+            return false;
+        }
+        let sorted = |mut face: [usize; 3]| {
+            face.sort_unstable();
+            face
+        };
+        let kept: Vec<[usize; 3]> = self.vertex_faces[keep]
+            .iter()
+            .filter(|&&face| self.face_alive[face])
+            .map(|&face| sorted(self.face(face)))
+            .collect();
+        self.vertex_faces[remove]
+            .iter()
+            .filter(|&&face| self.face_alive[face] && !self.face(face).contains(&keep))
+            .map(|&face| sorted(self.face(face).map(|i| if i == remove { keep } else { i })))
+            .all(|moved| !kept.contains(&moved))
+    }
+
+    // Rejects collapses that would flip a surviving triangle over
+    fn flips(&self, keep: usize, remove: usize, position: Vec3) -> bool {
+        for vertex in [keep, remove] {
+            for &face in &self.vertex_faces[vertex] {
+                let indices = self.face(face);
+                if !self.face_alive[face] || (indices.contains(&keep) && indices.contains(&remove))
+                {
+                    continue;
+                }
+
+                let old = indices.map(|i| self.positions[i]);
+                let new = indices.map(|i| {
+                    if i == vertex {
+                        position
+                    } else {
+                        self.positions[i]
+                    }
+                });
+                let old_normal = (old[1] - old[0]).cross(&(old[2] - old[0]));
+                let new_normal = (new[1] - new[0]).cross(&(new[2] - new[0]));
+                if old_normal.dot(&new_normal) <= 0.0 {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn collapse(&mut self, keep: usize, remove: usize, position: Vec3) -> usize {
+        self.positions[keep] = position;
+        let removed_quadric = self.quadrics[remove];
+        self.quadrics[keep].add(&removed_quadric);
+        self.removed[remove] = true;
+        self.versions[keep] += 1;
+
+        let mut killed = 0;
+        for face in std::mem::take(&mut self.vertex_faces[remove]) {
+            if !self.face_alive[face] {
+                continue;
+            }
+            if self.face(face).contains(&keep) {
+                self.face_alive[face] = false;
+                killed += 1;
+                continue;
+            }
+            for corner in &mut self.corners[face * 3..face * 3 + 3] {
+                if corner.0 == remove {
+                    corner.0 = keep;
+                }
+            }
+            self.vertex_faces[keep].push(face);
+        }
+
+        let alive = &self.face_alive;
+        self.vertex_faces[keep].retain(|&face| alive[face]);
+        killed
+    }
+}
+
+// Garland-Heckbert quadric error edge collapse over a welded triangle list.
+// Whatever else each corner carries, such as its texcoord, stays attached to
+// its face corner.
+pub fn decimate<A: Copy>(
+    positions: Vec<Vec3>,
+    corners: Vec<(usize, A)>,
+    target_triangles: usize,
+) -> (Vec<Vec3>, Vec<(usize, A)>) {
+    let face_count = corners.len() / 3;
+    let mut decimator = Decimator {
+        quadrics: vec![Quadric::zero(); positions.len()],
+        vertex_faces: vec![Vec::new(); positions.len()],
+        versions: vec![0; positions.len()],
+        removed: vec![false; positions.len()],
+        face_alive: vec![true; face_count],
+        positions,
+        corners,
+    };
+
+    let mut edges = HashSet::new();
+    for face in 0..face_count {
+        let [a, b, c] = decimator.face(face);
+        let [pa, pb, pc] = [a, b, c].map(|i| decimator.positions[i]);
+        let cross = (pb - pa).cross(&(pc - pa));
+        let area = cross.magnitude() * 0.5;
+        if area > f32::EPSILON {
+            let normal = cross.normalize();
+            let quadric = Quadric::from_plane(normal, -normal.dot(&pa), area);
+            for vertex in [a, b, c] {
+                decimator.quadrics[vertex].add(&quadric);
+            }
+        }
+        for vertex in [a, b, c] {
+            decimator.vertex_faces[vertex].push(face);
+        }
+        for (x, y) in [(a, b), (b, c), (c, a)] {
+            edges.insert((x.min(y), x.max(y)));
+        }
+    }
+
+    let mut heap: BinaryHeap<Candidate> = edges
+        .into_iter()
+        .map(|(a, b)| decimator.candidate(a, b))
+        .collect();
+
+    let mut alive = face_count;
+    while alive > target_triangles {
+        let Some(candidate) = heap.pop() else {
+            break;
+        };
+        let (keep, remove) = (candidate.keep, candidate.remove);
+        if decimator.removed[keep]
+            || decimator.removed[remove]
+            || candidate.versions != (decimator.versions[keep], decimator.versions[remove])
+            || !decimator.keeps_manifold(keep, remove)
+            || decimator.flips(keep, remove, candidate.position)
+        {
+            continue;
+        }
+
+        alive -= decimator.collapse(keep, remove, candidate.position);
+
+        for neighbour in decimator.neighbours(keep) {
+            heap.push(decimator.candidate(keep, neighbour));
+        }
+    }
+
+    // Compact the surviving vertices and faces
+    let mut remap = vec![usize::MAX; decimator.positions.len()];
+    let mut positions = Vec::new();
+    let mut corners = Vec::with_capacity(alive * 3);
+    for face in 0..face_count {
+        if !decimator.face_alive[face] {
+            continue;
+        }
+        for &(vertex, attributes) in &decimator.corners[face * 3..face * 3 + 3] {
+            if remap[vertex] == usize::MAX {
+                remap[vertex] = positions.len();
+                positions.push(decimator.positions[vertex]);
+            }
+            corners.push((remap[vertex], attributes));
+        }
+    }
+
+    (positions, corners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::halfedge::HalfEdgeMesh;
+    use nalgebra_glm::Vec2;
+    use std::collections::HashMap;
+
+    // A flat square of `n` by `n` cells in the XY plane, two triangles each,
+    // every corner tagged with the face it belongs to
+    fn grid(n: usize) -> (Vec<Vec3>, Vec<(usize, usize)>) {
+        let mut positions = Vec::new();
+        for y in 0..=n {
+            for x in 0..=n {
+                positions.push(Vec3::new(x as f32, y as f32, 0.0));
+            }
+        }
+        let mut corners = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let corner = y * (n + 1) + x;
+                let [a, b, c, d] = [corner, corner + 1, corner + n + 2, corner + n + 1];
+                for triangle in [[a, b, c], [a, c, d]] {
+                    let face = corners.len() / 3;
+                    corners.extend(triangle.map(|vertex| (vertex, face)));
+                }
+            }
+        }
+        (positions, corners)
+    }
+
+    // A closed, smoothly curved surface: a tetrahedron Loop-subdivided three
+    // times into 256 faces
+    fn blob() -> (Vec<Vec3>, Vec<(usize, ())>) {
+        let positions = vec![
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+        ];
+        let corners: Vec<(usize, Vec2)> = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]]
+            .iter()
+            .flatten()
+            .map(|&vertex| (vertex, Vec2::zeros()))
+            .collect();
+        let mut mesh = HalfEdgeMesh::from_triangles(positions, &corners);
+        for _ in 0..3 {
+            mesh = mesh.loop_subdivide();
+        }
+        let corners = mesh
+            .half_edges
+            .iter()
+            .map(|half_edge| (half_edge.origin, ()))
+            .collect();
+        (mesh.positions, corners)
+    }
+
+    // Every face has three different corners, and every edge is shared by
+    // exactly two faces running it opposite ways: a closed 2-manifold, whose
+    // vertices, edges and faces then add up to 2 like a sphere's
+    fn assert_closed_manifold<A>(positions: &[Vec3], corners: &[(usize, A)]) {
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for triangle in corners.chunks(3) {
+            let [a, b, c] = [triangle[0].0, triangle[1].0, triangle[2].0];
+            assert!(
+                a != b && b != c && c != a,
+                "degenerate face {:?}",
+                [a, b, c]
+            );
+            for edge in [(a, b), (b, c), (c, a)] {
+                *edges.entry(edge).or_default() += 1;
+            }
+        }
+        for (&(a, b), &count) in &edges {
+            assert_eq!(count, 1, "edge {:?} is used {} times", (a, b), count);
+            assert!(
+                edges.contains_key(&(b, a)),
+                "edge {:?} is on a boundary",
+                (a, b)
+            );
+        }
+        let faces = corners.len() / 3;
+        assert_eq!(positions.len() + faces, edges.len() / 2 + 2);
+    }
+
+    #[test]
+    fn quadric_error_is_weighted_squared_distance() {
+        let quadric = Quadric::from_plane(Vec3::z(), -1.0, 3.0);
+        assert_eq!(quadric.error(Vec3::new(5.0, -2.0, 1.0)), 0.0);
+        assert!((quadric.error(Vec3::new(0.5, 0.5, 3.0)) - 12.0).abs() < 1e-9);
+
+        let mut sum = quadric;
+        sum.add(&Quadric::from_plane(Vec3::x(), 0.0, 1.0));
+        assert!((sum.error(Vec3::new(2.0, 0.0, 3.0)) - 16.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn flat_surfaces_stay_flat() {
+        let (positions, corners) = grid(8);
+        let (positions, corners) = decimate(positions, corners, 20);
+        assert!(corners.len() / 3 <= 20, "{} faces", corners.len() / 3);
+        assert!(positions.iter().all(|position| position.z == 0.0));
+        // Nothing folded over
+        for triangle in corners.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i].0]);
+            assert!((b - a).cross(&(c - a)).z > 0.0);
+        }
+    }
+
+    #[test]
+    fn closed_surfaces_stay_closed() {
+        let (positions, corners) = blob();
+        assert_closed_manifold(&positions, &corners);
+        for target in [128, 32, 8, 0] {
+            let (positions, corners) = decimate(positions.clone(), corners.clone(), target);
+            assert_closed_manifold(&positions, &corners);
+            assert!(corners.len() / 3 >= 4, "{} faces", corners.len() / 3);
+        }
+    }
+
+    #[test]
+    fn corners_keep_their_own_attributes() {
+        let (positions, corners) = grid(6);
+        let (_, corners) = decimate(positions, corners, 12);
+        for triangle in corners.chunks(3) {
+            assert!(
+                triangle.iter().all(|&(_, face)| face == triangle[0].1),
+                "{:?}",
+                triangle
+            );
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use nalgebra_glm::Vec3;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+// With the `diagnostics` feature on, vertices, depths and colors are checked
+// on their way through the pipeline, since a single NaN otherwise comes out
+// as a quietly black pixel (or a missing triangle) with nothing to say where
+// it came from. Pixels whose radiance isn't finite are painted this color
+// when the framebuffer is resolved.
+pub const MAGENTA: u32 = 0xFF00FF;
+
+// Different sources reported before the rest are dropped
+const MAX_REPORTS: usize = 20;
+
+// Sources already reported, so a broken shader logs each triangle once
+// instead of once per pixel per frame
+static REPORTED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+
+pub fn finite(v: &Vec3) -> bool {
+    v.iter().all(|c| c.is_finite())
+}
+
+// Logs `detail` the first time a non-finite value comes from `source`, e.g.
+// "GasGiant instance 0 triangle 12"
+pub fn report(source: String, detail: impl FnOnce() -> String) {
+    let mut reported = REPORTED.lock().unwrap_or_else(|err| err.into_inner());
+    let reported = reported.get_or_insert_with(HashSet::new);
+    if reported.len() > MAX_REPORTS || reported.contains(&source) {
+        return;
+    }
+    if reported.len() == MAX_REPORTS {
+        eprintln!("Non-finite values: too many sources, not reporting any more");
+    } else {
+        eprintln!("Non-finite value from {}: {}", source, detail());
+    }
+    reported.insert(source);
+}
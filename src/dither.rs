@@ -0,0 +1,57 @@
+use crate::blue_noise::blue_noise;
+use crate::color::Color;
+use nalgebra_glm::{Vec2, Vec3};
+
+const BAYER_4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+const BAYER_8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// Dithering used when a shader's floating point output is quantized
+// to 8 bits per channel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Dither {
+    Off,
+    Bayer4,
+    Bayer8,
+    BlueNoise,
+}
+
+impl Dither {
+    // Fraction of one 8-bit step added before truncating, in [0, 1)
+    fn threshold(self, x: usize, y: usize) -> f32 {
+        match self {
+            Dither::Off => 0.0,
+            Dither::Bayer4 => (BAYER_4[y % 4][x % 4] as f32 + 0.5) / 16.0,
+            Dither::Bayer8 => (BAYER_8[y % 8][x % 8] as f32 + 0.5) / 64.0,
+            Dither::BlueNoise => blue_noise().sample(x, y),
+        }
+    }
+
+    // Converts an RGB color in [0, 1] to a Color. Off truncates like a plain
+    // cast; the other modes spread the rounding error over the pattern so
+    // smooth gradients average out to the exact value instead of banding.
+    pub fn quantize(self, rgb: Vec3, position: Vec2) -> Color {
+        let offset = self.threshold(position.x.max(0.0) as usize, position.y.max(0.0) as usize);
+        // Rounding after shifting by offset - 0.5 steps is the same as
+        // truncating after adding offset
+        Color::from_vec3(rgb + Vec3::repeat((offset - 0.5) / 255.0))
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Dither::Off => Dither::Bayer4,
+            Dither::Bayer4 => Dither::Bayer8,
+            Dither::Bayer8 => Dither::BlueNoise,
+            Dither::BlueNoise => Dither::Off,
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use nalgebra_glm::{Mat3, Vec3};
+use std::borrow::Cow;
+
+// Wavelengths in nanometers the blue, green and red channels stand for, and
+// how far past either end light still shows as the channel it's nearest
+const BLUE: f32 = 465.0;
+const GREEN: f32 = 550.0;
+const RED: f32 = 610.0;
+const FADE: f32 = 100.0;
+// Kept short of the speed of light, where the shift runs off to infinity
+const MAX_BETA: f32 = 0.95;
+// How much of a new frame's speed goes into the smoothed one, so a camera
+// jump doesn't flash every body for a frame
+const SMOOTHING: f32 = 0.2;
+
+// How much of the light emitted at `wavelength` each channel picks up, as
+// (red, green, blue)
+fn channels(wavelength: f32) -> Vec3 {
+    if wavelength < BLUE {
+        Vec3::new(0.0, 0.0, (1.0 - (BLUE - wavelength) / FADE).max(0.0))
+    } else if wavelength < GREEN {
+        let t = (wavelength - BLUE) / (GREEN - BLUE);
+        Vec3::new(0.0, t, 1.0 - t)
+    } else if wavelength < RED {
+        let t = (wavelength - GREEN) / (RED - GREEN);
+        Vec3::new(t, 1.0 - t, 0.0)
+    } else {
+        Vec3::new((1.0 - (wavelength - RED) / FADE).max(0.0), 0.0, 0.0)
+    }
+}
+
+// The color change seen from a body moving at `beta` times the speed of
+// light away from the eye, negative toward it. The light reaching each
+// channel left the body at a wavelength stretched or squeezed by the Doppler
+// factor, so it's made up of whichever channels sat there.
+pub fn shift(beta: f32) -> Mat3 {
+    let beta = beta.clamp(-MAX_BETA, MAX_BETA);
+    let stretch = ((1.0 + beta) / (1.0 - beta)).sqrt();
+    let row = |wavelength: f32| channels(wavelength / stretch).transpose();
+    Mat3::from_rows(&[row(RED), row(GREEN), row(BLUE)])
+}
+
+// Each body's speed along the line from the eye, which is all a Doppler
+// shift sees, taken from how its distance changes frame to frame. It's
+// measured against the light speed flashes travel at and multiplied by
+// `exaggeration`, since at any speed a body here could believably have the
+// tint would be far too faint to see.
+pub struct Doppler {
+    pub exaggeration: f32,
+    // Distance last frame and smoothed speed, in world units per frame
+    bodies: Vec<(Cow<'static, str>, f32, f32)>,
+}
+
+impl Doppler {
+    pub fn new(exaggeration: f32) -> Self {
+        Doppler {
+            exaggeration,
+            bodies: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, eye: Vec3, bodies: impl Iterator<Item = (Cow<'static, str>, Vec3)>) {
+        let previous = std::mem::take(&mut self.bodies);
+        for (name, center) in bodies {
+            let distance = (center - eye).magnitude();
+            let speed = match previous.iter().find(|(body, _, _)| *body == name) {
+                Some(&(_, last, speed)) => speed + (distance - last - speed) * SMOOTHING,
+                None => 0.0,
+            };
+            self.bodies.push((name, distance, speed));
+        }
+    }
+
+    // The tint for `body` with light crossing `light_speed` world units a
+    // frame, or None for a body that wasn't in the last update
+    pub fn tint(&self, body: &str, light_speed: f32) -> Option<Mat3> {
+        let &(_, _, speed) = self.bodies.iter().find(|(name, _, _)| name == body)?;
+        Some(shift(
+            speed / light_speed.max(f32::EPSILON) * self.exaggeration,
+        ))
+    }
+
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+    }
+}
@@ -0,0 +1,169 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{DepthState, DepthTest, Framebuffer};
+use crate::{depth_planes, Uniforms};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+// Past the comet at its farthest
+const RADIUS: f32 = 9.0;
+// World units between the distance rings
+const RING_SPACING: f32 = 1.0;
+// Degrees between the labeled spokes, and between the ticks around the rim
+const SPOKE_DEGREES: f32 = 30.0;
+const TICK_DEGREES: f32 = 10.0;
+const TICK_LENGTH: f32 = 0.4;
+// HDR radiance added over what's behind: a faint tint for the disk, brighter
+// for its markings, and the drop lines from bodies above and below it
+const FILL: Vec3 = Vec3::new(0.006, 0.012, 0.024);
+const MARKINGS: Vec3 = Vec3::new(0.06, 0.14, 0.3);
+const ABOVE: Vec3 = Vec3::new(0.35, 0.3, 0.08);
+const BELOW: Vec3 = Vec3::new(0.08, 0.3, 0.3);
+const LABEL_COLOR: u32 = 0x7090C0;
+
+// The reference plane the orbits' elements are measured from, the XZ plane
+// through `center`, as a see-through disk with a ring every RING_SPACING and
+// spokes marking the angle from +X toward +Z. Each of `bodies` gets a line
+// straight down or up to the plane, so how far an inclined orbit has taken
+// it out of the plane can be read off the foot.
+pub fn draw(framebuffer: &mut Framebuffer, uniforms: &Uniforms, center: Vec3, bodies: &[Vec3]) {
+    let previous = framebuffer.depth_state();
+    framebuffer.set_depth_state(DepthState::TRANSLUCENT);
+    draw_disk(framebuffer, uniforms, center);
+    for body in bodies {
+        draw_drop_line(framebuffer, uniforms, center, body);
+    }
+    framebuffer.set_depth_state(previous);
+    draw_degrees(framebuffer, uniforms, center);
+}
+
+fn draw_disk(framebuffer: &mut Framebuffer, uniforms: &Uniforms, center: Vec3) {
+    let transform = screen_transform(uniforms);
+    let Some(inverse) = transform.try_inverse() else {
+        return;
+    };
+    let (near_depth, far_depth) = depth_planes(&uniforms.viewport_matrix, uniforms.reversed_z);
+    // Pixels per world unit at a distance of one
+    let focal = uniforms.projection_matrix[(1, 1)] * uniforms.viewport_matrix[(1, 1)].abs();
+
+    let scissor = framebuffer.scissor();
+    for y in scissor.min_y..scissor.max_y {
+        for x in scissor.min_x..scissor.max_x {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let near = inverse * Vec4::new(px, py, near_depth, 1.0);
+            let far = inverse * Vec4::new(px, py, far_depth, 1.0);
+            let (near, far) = (near.xyz() / near.w, far.xyz() / far.w);
+            let ray = far - near;
+            let along = (center.y - near.y) / ray.y;
+            if !(0.0..=1.0).contains(&along) {
+                continue;
+            }
+            let hit = near + ray * along;
+            let (offset_x, offset_z) = (hit.x - center.x, hit.z - center.z);
+            let radius = offset_x.hypot(offset_z);
+            if radius > RADIUS {
+                continue;
+            }
+
+            // A line is a pixel wide wherever it is, fading out toward the
+            // edges of that pixel
+            let pixel = (hit - uniforms.eye).magnitude() / focal;
+            let coverage = |distance: f32| (1.0 - distance / pixel).max(0.0);
+            let nearest =
+                |value: f32, spacing: f32| (value - (value / spacing).round() * spacing).abs();
+            let angle = offset_z.atan2(offset_x);
+            let ring = coverage(nearest(radius, RING_SPACING));
+            let spoke = if radius >= RING_SPACING {
+                coverage(nearest(angle, SPOKE_DEGREES.to_radians()) * radius)
+            } else {
+                0.0
+            };
+            let tick = if radius >= RADIUS - TICK_LENGTH {
+                coverage(nearest(angle, TICK_DEGREES.to_radians()) * radius)
+            } else {
+                0.0
+            };
+            // Seen edge on the lines crowd together into shimmer, so they
+            // give way to the tint
+            let slope = ray.y.abs() / ray.magnitude();
+            let markings = ring.max(spoke).max(tick) * (slope * 4.0).min(1.0);
+
+            let Some((_, _, depth)) = project(&transform, &hit) else {
+                continue;
+            };
+            framebuffer.add_radiance(x, y, depth, FILL + MARKINGS * markings);
+        }
+    }
+}
+
+// From `body` to the point on the plane under or over it, ending in a small
+// cross
+fn draw_drop_line(framebuffer: &mut Framebuffer, uniforms: &Uniforms, center: Vec3, body: &Vec3) {
+    let transform = screen_transform(uniforms);
+    let foot = Vec3::new(body.x, center.y, body.z);
+    let color = if body.y >= center.y { ABOVE } else { BELOW };
+    let (Some(top), Some(bottom)) = (project(&transform, body), project(&transform, &foot)) else {
+        return;
+    };
+
+    // A sample per pixel along the line, each projected on its own so depth
+    // follows the perspective
+    let length = (top.0 - bottom.0).abs().max((top.1 - bottom.1).abs());
+    let steps = (length.ceil() as usize).max(1);
+    for step in 0..=steps {
+        let point = body.lerp(&foot, step as f32 / steps as f32);
+        match project(&transform, &point) {
+            Some((x, y, depth)) if x >= 0.0 && y >= 0.0 => {
+                framebuffer.add_radiance(x as usize, y as usize, depth, color);
+            }
+            _ => {}
+        }
+    }
+    if bottom.0 < 0.0 || bottom.1 < 0.0 {
+        return;
+    }
+    let (x, y, depth) = (bottom.0 as usize, bottom.1 as usize, bottom.2);
+    for offset in 1..=2 {
+        framebuffer.add_radiance(x + offset, y, depth, color);
+        framebuffer.add_radiance(x.wrapping_sub(offset), y, depth, color);
+        framebuffer.add_radiance(x, y + offset, depth, color);
+        framebuffer.add_radiance(x, y.wrapping_sub(offset), depth, color);
+    }
+}
+
+// The angle of each spoke just past the rim, hidden behind anything solid
+fn draw_degrees(framebuffer: &mut Framebuffer, uniforms: &Uniforms, center: Vec3) {
+    let transform = screen_transform(uniforms);
+    framebuffer.set_current_color(LABEL_COLOR);
+    for degrees in (0..360).step_by(SPOKE_DEGREES as usize) {
+        let angle = (degrees as f32).to_radians();
+        let at = center + Vec3::new(angle.cos(), 0.0, angle.sin()) * (RADIUS + 0.4);
+        let Some((x, y, depth)) = project(&transform, &at) else {
+            continue;
+        };
+        if x < 0.0 || y < 0.0 {
+            continue;
+        }
+        if !framebuffer.passes_depth(x as usize, y as usize, depth, DepthTest::Less) {
+            continue;
+        }
+        let text = format!("{}°", degrees);
+        let left = x - text_width(&text, 1) as f32 / 2.0;
+        let top = y - GLYPH_HEIGHT as f32 / 2.0;
+        framebuffer.draw_text(left.round() as i32, top.round() as i32, &text, 1);
+    }
+}
+
+fn screen_transform(uniforms: &Uniforms) -> Mat4 {
+    uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix
+}
+
+// Screen position and depth of a world point, or None behind the eye
+fn project(transform: &Mat4, point: &Vec3) -> Option<(f32, f32, f32)> {
+    let screen = transform * Vec4::new(point.x, point.y, point.z, 1.0);
+    (screen.w > 0.0).then(|| {
+        (
+            screen.x / screen.w,
+            screen.y / screen.w,
+            screen.z / screen.w,
+        )
+    })
+}
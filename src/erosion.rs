@@ -0,0 +1,177 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f32::consts::{PI, TAU};
+
+// How deep a river cuts per pass, per unit of sqrt(drainage area) and slope
+const STREAM_POWER: f32 = 0.001;
+
+// A cell waiting in the flood queue, ordered so the lowest pops first
+struct Pending {
+    level: f32,
+    index: usize,
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.level.total_cmp(&self.level)
+    }
+}
+
+// Routes rain over a latitude-longitude heightmap that wraps in longitude and
+// lets the rivers cut into it. Each pass floods inward from the sea so every
+// land cell drains somewhere, even out of a basin, accumulates the water along
+// those paths, then lowers each cell by stream power: the more water passes
+// through and the steeper it falls, the deeper the valley.
+//
+// Returns the drainage area of every cell, measured in equator-sized cells
+// upstream of it including itself, and 0 in the sea.
+pub fn erode(
+    elevation: &mut [f32],
+    width: usize,
+    height: usize,
+    sea_level: f32,
+    passes: usize,
+) -> Vec<f32> {
+    let mut flow = vec![0.0_f32; elevation.len()];
+    for _ in 0..passes {
+        let (order, downstream) = drainage(elevation, width, height, sea_level);
+
+        // Upstream cells leave the queue after the ones they drain into, so
+        // walking it backwards hands each cell's water on exactly once
+        flow.iter_mut().for_each(|water| *water = 0.0);
+        for &index in order.iter().rev() {
+            if elevation[index] <= sea_level {
+                continue;
+            }
+            flow[index] += cell_area(index, width, height);
+            if let Some(next) = downstream[index] {
+                flow[next] += flow[index];
+            }
+        }
+
+        // Carve downstream first, and never below the next cell down, so the
+        // river keeps running downhill
+        for &index in &order {
+            let Some(next) = downstream[index] else {
+                continue;
+            };
+            if elevation[index] <= sea_level {
+                continue;
+            }
+            let drop = elevation[index] - elevation[next];
+            let slope = drop.max(0.0) / cell_distance(index, next, width, height);
+            let carved = elevation[index] - STREAM_POWER * flow[index].sqrt() * slope;
+            elevation[index] = carved.max(elevation[next].min(elevation[index]));
+        }
+    }
+
+    for (water, &level) in flow.iter_mut().zip(elevation.iter()) {
+        if level <= sea_level {
+            *water = 0.0;
+        }
+    }
+    flow
+}
+
+// Priority flood: the order cells are reached in rising from the sea, and for
+// each land cell the neighbour it was reached from, which is where it drains
+fn drainage(
+    elevation: &[f32],
+    width: usize,
+    height: usize,
+    sea_level: f32,
+) -> (Vec<usize>, Vec<Option<usize>>) {
+    let mut downstream = vec![None; elevation.len()];
+    let mut visited = vec![false; elevation.len()];
+    let mut order = Vec::with_capacity(elevation.len());
+    let mut queue = BinaryHeap::new();
+
+    for (index, &level) in elevation.iter().enumerate() {
+        if level <= sea_level {
+            visited[index] = true;
+            queue.push(Pending { level, index });
+        }
+    }
+    // A world with no sea still needs somewhere for the water to go
+    if queue.is_empty() {
+        if let Some(index) =
+            (0..elevation.len()).min_by(|&a, &b| elevation[a].total_cmp(&elevation[b]))
+        {
+            visited[index] = true;
+            queue.push(Pending {
+                level: elevation[index],
+                index,
+            });
+        }
+    }
+
+    while let Some(Pending { level, index }) = queue.pop() {
+        order.push(index);
+        for next in neighbours(index, width, height) {
+            if visited[next] {
+                continue;
+            }
+            visited[next] = true;
+            downstream[next] = Some(index);
+            // Basins fill up to their spill point, plus a sliver so the water
+            // still has a direction across the lake. The sliver is jittered
+            // per cell, or the paths across flat ground come out ruler-straight.
+            let jitter = (next as u32).wrapping_mul(2654435761) as f32 / u32::MAX as f32;
+            queue.push(Pending {
+                level: elevation[next].max(level + 1e-6 * (1.0 + jitter)),
+                index: next,
+            });
+        }
+    }
+
+    (order, downstream)
+}
+
+fn neighbours(index: usize, width: usize, height: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = ((index % width) as i32, (index / width) as i32);
+    (-1..=1)
+        .flat_map(move |dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(move |&(dx, dy)| (dx, dy) != (0, 0) && (0..height as i32).contains(&(y + dy)))
+        .map(move |(dx, dy)| {
+            let nx = (x + dx).rem_euclid(width as i32) as usize;
+            (y + dy) as usize * width + nx
+        })
+}
+
+// Cells shrink toward the poles; one on the equator counts as 1
+fn cell_area(index: usize, width: usize, height: usize) -> f32 {
+    let colatitude = ((index / width) as f32 + 0.5) / height as f32 * PI;
+    colatitude.sin()
+}
+
+// Angle between two neighbouring cell centers. Columns crowd together toward
+// the poles, so east-west steps shrink with the sine of the colatitude.
+fn cell_distance(a: usize, b: usize, width: usize, height: usize) -> f32 {
+    let row = (a / width).min(b / width);
+    let colatitude = (row as f32 + 0.5) / height as f32 * PI;
+    let dx = if a % width == b % width {
+        0.0
+    } else {
+        TAU / width as f32 * colatitude.sin()
+    };
+    let dy = if a / width == b / width {
+        0.0
+    } else {
+        PI / height as f32
+    };
+    (dx * dx + dy * dy).sqrt().max(1e-4)
+}
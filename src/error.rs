@@ -0,0 +1,71 @@
+use crate::obj::ObjError;
+use std::fmt;
+use std::io;
+
+// What can go wrong reading the files the viewer is given or writing the
+// ones it makes, each with the path it happened on, so a message says which
+// file to look at without the caller having to add it
+#[derive(Debug)]
+pub enum Error {
+    // A model that couldn't be read or didn't parse
+    Model {
+        path: String,
+        source: ObjError,
+    },
+    // An image that couldn't be decoded or isn't the shape it has to be
+    Image {
+        path: String,
+        message: String,
+    },
+    // A scene file line that doesn't describe a body or a system
+    Scene {
+        path: String,
+        line: usize,
+        message: String,
+    },
+    // Any other file that couldn't be read or written
+    Io {
+        path: String,
+        source: io::Error,
+    },
+    // The port the frame server was to listen on, which it couldn't have
+    Server {
+        port: u16,
+        source: io::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: impl Into<String>) -> impl FnOnce(io::Error) -> Error {
+        let path = path.into();
+        move |source| Error::Io { path, source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Model { path, source } => write!(f, "{}: {}", path, source),
+            Error::Image { path, message } => write!(f, "{}: {}", path, message),
+            Error::Scene {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", path, line, message),
+            Error::Io { path, source } => write!(f, "{}: {}", path, source),
+            Error::Server { port, source } => write!(f, "port {}: {}", port, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Model { source, .. } => Some(source),
+            Error::Io { source, .. } | Error::Server { source, .. } => Some(source),
+            Error::Image { .. } | Error::Scene { .. } => None,
+        }
+    }
+}
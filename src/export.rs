@@ -0,0 +1,64 @@
+use crate::error::{self, Error};
+use crate::kitbash::spacecraft;
+use crate::obj;
+use crate::procedural::{asteroid, uv_sphere};
+use crate::vertex::Vertex;
+use std::fs;
+
+// A mesh the program builds instead of loading, as named on the command line
+#[derive(Clone, Copy)]
+pub enum Model {
+    Sphere,
+    Asteroid { seed: u64, subdivisions: u32 },
+    Spacecraft { seed: u64 },
+}
+
+impl Model {
+    // Parses `sphere`, `asteroid`, `asteroid:seed[:subdivisions]` or
+    // `spacecraft[:seed]`. Left out, the seeds are the ones the window shows.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut parts = spec.split(':');
+        let name = parts.next().unwrap_or_default();
+        let mut number = |default: u64| match parts.next() {
+            Some(text) => text
+                .parse::<u64>()
+                .map_err(|_| format!("'{}' is not a whole number", text)),
+            None => Ok(default),
+        };
+        let model = match name {
+            "sphere" => Model::Sphere,
+            "asteroid" => Model::Asteroid {
+                seed: number(7)?,
+                subdivisions: number(3)? as u32,
+            },
+            "spacecraft" => Model::Spacecraft { seed: number(9)? },
+            _ => {
+                return Err(format!(
+                    "unknown model '{}', expected sphere, asteroid or spacecraft",
+                    name
+                ))
+            }
+        };
+        match parts.next() {
+            Some(extra) => Err(format!("unexpected '{}' in '{}'", extra, spec)),
+            None => Ok(model),
+        }
+    }
+
+    fn vertices(self) -> Vec<Vertex> {
+        match self {
+            // The UV sphere the window draws, at unit radius
+            Model::Sphere => uv_sphere(1.0, 64, 32),
+            Model::Asteroid { seed, subdivisions } => asteroid(seed, subdivisions),
+            Model::Spacecraft { seed } => spacecraft(seed),
+        }
+    }
+}
+
+// Builds `model` and saves it to `path` as an OBJ file, to be looked at in
+// Blender or used somewhere else. Returns how many triangles it has.
+pub fn save(model: Model, path: &str) -> error::Result<usize> {
+    let vertices = model.vertices();
+    fs::write(path, obj::encode(&vertices)).map_err(Error::io(path))?;
+    Ok(vertices.len() / 3)
+}
@@ -0,0 +1,122 @@
+use crate::framebuffer::Framebuffer;
+use nalgebra_glm::Vec3;
+
+// Average luminance, on the log scale, exposure brings the metered pixels to
+const KEY: f32 = 0.18;
+// Keeps black pixels from sending the log average to zero
+const BLACK: f32 = 1e-3;
+// Meters every STRIDE-th pixel of every STRIDE-th row
+const STRIDE: usize = 4;
+// Fraction of the sampled pixels bodies have to cover to be metered on their
+// own; below it the whole frame, sky included, is metered
+const MIN_COVERAGE: f32 = 0.005;
+
+// How bright sunlight is where the camera is. Sunlight falls off with the
+// square of the distance from the sun, so further out the eye has to open up
+// more to see the same scene, and with no sun at all it only has starlight
+// and nebulae to go on. Each preset bounds how far the exposure can follow
+// the meter in either direction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExposurePreset {
+    InnerSystem,
+    OuterSystem,
+    DeepSpace,
+}
+
+const PRESETS: [ExposurePreset; 3] = [
+    ExposurePreset::InnerSystem,
+    ExposurePreset::OuterSystem,
+    ExposurePreset::DeepSpace,
+];
+
+impl ExposurePreset {
+    pub fn parse(name: &str) -> Option<Self> {
+        PRESETS.into_iter().find(|preset| preset.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExposurePreset::InnerSystem => "inner",
+            ExposurePreset::OuterSystem => "outer",
+            ExposurePreset::DeepSpace => "deep",
+        }
+    }
+
+    // Lowest and highest exposure the meter can set
+    fn range(&self) -> (f32, f32) {
+        match self {
+            ExposurePreset::InnerSystem => (0.25, 1.5),
+            ExposurePreset::OuterSystem => (0.5, 6.0),
+            ExposurePreset::DeepSpace => (1.0, 16.0),
+        }
+    }
+
+    // Manual, then each preset in order, then back to manual
+    pub fn cycle(preset: Option<ExposurePreset>) -> Option<ExposurePreset> {
+        match preset {
+            None => Some(ExposurePreset::InnerSystem),
+            Some(ExposurePreset::InnerSystem) => Some(ExposurePreset::OuterSystem),
+            Some(ExposurePreset::OuterSystem) => Some(ExposurePreset::DeepSpace),
+            Some(ExposurePreset::DeepSpace) => None,
+        }
+    }
+}
+
+// Automatic exposure that eases toward what the meter asks for instead of
+// jumping to it, the way eyes take a moment to adjust going into or out of
+// the dark. The meter favours the bodies over the sky around them, so a
+// planet keeps its brightness as it shrinks into the distance.
+pub struct EyeAdaptation {
+    // None leaves the exposure to the keyboard
+    pub preset: Option<ExposurePreset>,
+    // Seconds to cover about two thirds of the way to a new exposure
+    pub time_constant: f32,
+}
+
+impl EyeAdaptation {
+    // Moves `exposure` toward the preset's exposure for the HDR scene in
+    // `framebuffer`, `elapsed` seconds after the last update
+    pub fn update(&self, exposure: &mut f32, framebuffer: &Framebuffer, elapsed: f32) {
+        let Some(preset) = self.preset else {
+            return;
+        };
+        let (min, max) = preset.range();
+        let target = (KEY / meter(framebuffer)).clamp(min, max);
+        let blend = if self.time_constant > 0.0 {
+            1.0 - (-elapsed / self.time_constant).exp()
+        } else {
+            1.0
+        };
+        // Eased on the log scale so opening up by some factor takes as long
+        // as closing down by it
+        *exposure = (exposure.ln() + (target.ln() - exposure.ln()) * blend).exp();
+    }
+}
+
+// Log average luminance of the sampled pixels that show a body, or of all of
+// them when bodies hardly cover any
+fn meter(framebuffer: &Framebuffer) -> f32 {
+    let mut all = (0.0, 0);
+    let mut bodies = (0.0, 0);
+    for y in (0..framebuffer.height).step_by(STRIDE) {
+        for x in (0..framebuffer.width).step_by(STRIDE) {
+            let index = y * framebuffer.width + x;
+            let radiance = framebuffer.hdr[index];
+            let log = (radiance.dot(&Vec3::new(0.2126, 0.7152, 0.0722)) + BLACK).ln();
+            // One bad pixel would otherwise stick the exposure at NaN
+            if !log.is_finite() {
+                continue;
+            }
+            all = (all.0 + log, all.1 + 1);
+            if framebuffer.zbuffer[index].is_finite() {
+                bodies = (bodies.0 + log, bodies.1 + 1);
+            }
+        }
+    }
+    let (sum, count) = if bodies.1 as f32 >= all.1 as f32 * MIN_COVERAGE {
+        bodies
+    } else {
+        all
+    };
+    (sum / count.max(1) as f32).exp()
+}
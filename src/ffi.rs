@@ -0,0 +1,245 @@
+// A C ABI for embedding the viewer in programs that aren't written in Rust,
+// declared for C in include/lab4_gpc.h. `lab4_gpc_create` makes one, drawing
+// at the size it's given, and `lab4_gpc_destroy` is the end of it; in
+// between, `lab4_gpc_load_scene` takes the moons from a --scene file, and
+// `lab4_gpc_render_rgba` draws the next frame with no keys held and fills a
+// buffer with one. As in the window, that's a frame behind what was just
+// drawn, and the call waits out the rest of the frame for --fps; with
+// `lab4_gpc_render_rgba_now` it's the frame just drawn, as soon as it's done.
+//
+// A panic stops at the boundary, said on stderr, and comes back as the
+// function's failure instead of unwinding into C. The renderer it happened in
+// may be halfway through a frame, so it's only good for destroying after.
+//
+// Each function says what it needs of the pointers it's given, in place of
+// the `# Safety` doc comments clippy looks for.
+#![allow(clippy::missing_safety_doc)]
+
+use crate::input::InputFrame;
+use crate::locale::Locale;
+use crate::options::Options;
+use crate::viewer::Viewer;
+use std::ffi::{c_char, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+// Runs `body`, or gives back `failed` if it panics
+fn guard<T>(failed: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(failed)
+}
+
+// A viewer whose frames are drawn `width` by `height`, shaded on `threads`
+// threads (0 counts as 1) and with depth reversed as --reverse-z has it, or
+// null for a size of nothing or one that couldn't be made, with why on
+// stderr. Models and locales are looked for under assets/ in the working
+// directory, with a plain sphere for a model that isn't there.
+#[no_mangle]
+pub extern "C" fn lab4_gpc_create(
+    width: u32,
+    height: u32,
+    threads: u32,
+    reversed_z: bool,
+) -> *mut Viewer {
+    if width == 0 || height == 0 {
+        return std::ptr::null_mut();
+    }
+    guard(std::ptr::null_mut(), || {
+        let size = (width as usize, height as usize);
+        let options = Options {
+            render_size: Some(size),
+            threads: (threads as usize).max(1),
+            reversed_z,
+            ..Options::default()
+        };
+        match Viewer::new(options, Locale::english(), size, Vec::new()) {
+            Ok(viewer) => Box::into_raw(Box::new(viewer)),
+            Err(err) => {
+                eprintln!("{}", err);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+// Whether the scene file at `path` was read, for `renderer`'s moon systems
+// from the next frame on. `renderer` is one from `lab4_gpc_create` that
+// hasn't been destroyed, and `path` a nul-terminated UTF-8 string; either
+// being null is a failure to read it.
+#[no_mangle]
+pub unsafe extern "C" fn lab4_gpc_load_scene(renderer: *mut Viewer, path: *const c_char) -> bool {
+    let Some(viewer) = renderer.as_mut() else {
+        return false;
+    };
+    if path.is_null() {
+        return false;
+    }
+    let Ok(path) = CStr::from_ptr(path).to_str() else {
+        return false;
+    };
+    guard(false, || match viewer.load_scene(path) {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("{}", err);
+            false
+        }
+    })
+}
+
+// Moves `renderer` on a frame and fills `buffer`, `width` by `height` pixels
+// of red, green, blue and alpha bytes row by row, with the frame before,
+// scaled to fit. `renderer` is one from `lab4_gpc_create` that hasn't been
+// destroyed, and `buffer` has room for `width * height * 4` bytes. False,
+// with the buffer untouched, when either is null or the size is nothing.
+#[no_mangle]
+pub unsafe extern "C" fn lab4_gpc_render_rgba(
+    renderer: *mut Viewer,
+    buffer: *mut u8,
+    width: u32,
+    height: u32,
+) -> bool {
+    render(renderer, buffer, (width, height), false)
+}
+
+// Like `lab4_gpc_render_rgba`, with the same needs, but what fills `buffer`
+// is the frame just drawn, and it comes back as soon as that's done
+#[no_mangle]
+pub unsafe extern "C" fn lab4_gpc_render_rgba_now(
+    renderer: *mut Viewer,
+    buffer: *mut u8,
+    width: u32,
+    height: u32,
+) -> bool {
+    render(renderer, buffer, (width, height), true)
+}
+
+unsafe fn render(
+    renderer: *mut Viewer,
+    buffer: *mut u8,
+    (width, height): (u32, u32),
+    now: bool,
+) -> bool {
+    let Some(viewer) = renderer.as_mut() else {
+        return false;
+    };
+    if buffer.is_null() || width == 0 || height == 0 {
+        return false;
+    }
+    let pixels = width as usize * height as usize;
+    let buffer = slice::from_raw_parts_mut(buffer, pixels * 4);
+    guard(false, || {
+        viewer.set_window_size((width as usize, height as usize));
+        let mut frame = if now {
+            viewer.frame_now(&InputFrame::default())
+        } else {
+            viewer.frame(&InputFrame::default())
+        };
+        // The frame before was scaled before the size changed, so the next
+        // one is at the new size
+        if frame.len() != pixels {
+            frame = viewer.frame(&InputFrame::default());
+        }
+        for (rgba, &pixel) in buffer.chunks_exact_mut(4).zip(frame) {
+            let [_, red, green, blue] = pixel.to_be_bytes();
+            rgba.copy_from_slice(&[red, green, blue, 0xFF]);
+        }
+        // There's no status line to show them on
+        for message in viewer.take_messages() {
+            eprintln!("{}", message);
+        }
+        true
+    })
+}
+
+// Frees `renderer`, one from `lab4_gpc_create` that hasn't been destroyed
+// already, or does nothing for null
+#[no_mangle]
+pub unsafe extern "C" fn lab4_gpc_destroy(renderer: *mut Viewer) {
+    if !renderer.is_null() {
+        guard((), || drop(Box::from_raw(renderer)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::ptr;
+
+    #[test]
+    fn renders_into_the_buffer_it_is_given() {
+        let renderer = lab4_gpc_create(80, 60, 2, false);
+        assert!(!renderer.is_null());
+        let mut buffer = vec![0u8; 100 * 50 * 4];
+        unsafe {
+            assert!(lab4_gpc_render_rgba(renderer, buffer.as_mut_ptr(), 100, 50));
+            assert!(lab4_gpc_render_rgba(renderer, buffer.as_mut_ptr(), 100, 50));
+            // A smaller buffer than before still gets a frame its size
+            assert!(lab4_gpc_render_rgba(renderer, buffer.as_mut_ptr(), 20, 10));
+            lab4_gpc_destroy(renderer);
+        }
+        let drawn = &buffer[..20 * 10 * 4];
+        assert!(drawn.chunks(4).all(|rgba| rgba[3] == 0xFF));
+        assert!(drawn.chunks(4).any(|rgba| rgba[..3] != [0, 0, 0]));
+    }
+
+    #[test]
+    fn renders_the_frame_just_drawn_when_asked_to() {
+        let renderer = lab4_gpc_create(80, 60, 1, true);
+        assert!(!renderer.is_null());
+        let mut buffer = vec![0u8; 40 * 30 * 4];
+        unsafe {
+            // The very first frame, which the other way has nothing to show
+            // for yet
+            assert!(lab4_gpc_render_rgba_now(
+                renderer,
+                buffer.as_mut_ptr(),
+                40,
+                30
+            ));
+            lab4_gpc_destroy(renderer);
+        }
+        assert!(buffer.chunks(4).all(|rgba| rgba[3] == 0xFF));
+        assert!(buffer.chunks(4).any(|rgba| rgba[..3] != [0, 0, 0]));
+    }
+
+    #[test]
+    fn loads_scene_files_that_read() {
+        let renderer = lab4_gpc_create(40, 30, 1, false);
+        let scene = CString::new("assets/three_body.txt").unwrap();
+        let missing = CString::new("assets/no_such_scene.txt").unwrap();
+        unsafe {
+            assert!(lab4_gpc_load_scene(renderer, scene.as_ptr()));
+            assert!(!lab4_gpc_load_scene(renderer, missing.as_ptr()));
+            assert!(!lab4_gpc_load_scene(renderer, ptr::null()));
+            assert!(!lab4_gpc_load_scene(ptr::null_mut(), scene.as_ptr()));
+            lab4_gpc_destroy(renderer);
+        }
+    }
+
+    #[test]
+    fn refuses_what_it_cannot_draw() {
+        assert!(lab4_gpc_create(0, 60, 1, false).is_null());
+        let renderer = lab4_gpc_create(20, 10, 0, false);
+        let mut buffer = [7u8; 4];
+        unsafe {
+            assert!(!lab4_gpc_render_rgba(renderer, ptr::null_mut(), 20, 10));
+            assert!(!lab4_gpc_render_rgba(renderer, buffer.as_mut_ptr(), 0, 1));
+            assert!(!lab4_gpc_render_rgba(
+                ptr::null_mut(),
+                buffer.as_mut_ptr(),
+                1,
+                1
+            ));
+            assert!(!lab4_gpc_render_rgba_now(renderer, ptr::null_mut(), 20, 10));
+            lab4_gpc_destroy(renderer);
+            lab4_gpc_destroy(ptr::null_mut());
+        }
+        assert_eq!(buffer, [7; 4]);
+    }
+
+    #[test]
+    fn panics_come_back_as_failures() {
+        assert!(!guard(false, || -> bool { panic!("a panic for the test") }));
+        assert_eq!(guard(0, || 3), 3);
+    }
+}
@@ -0,0 +1,51 @@
+use crate::framebuffer::Framebuffer;
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+
+// Distance haze over the bodies, worked out from the depth buffer once a
+// view's bodies are in. It starts at the distance the view is focused on so
+// whatever it's looking at stays clear, and thickens exponentially beyond,
+// so the far side of the belt and the outer moons sink into the haze and the
+// eye can tell how far back they are. The sky is left alone: it's already as
+// far as anything gets.
+pub struct Fog {
+    pub enabled: bool,
+    // HDR radiance everything fades toward
+    pub color: Vec3,
+    // Fraction of the remaining contrast lost per unit of distance, roughly
+    pub density: f32,
+}
+
+impl Fog {
+    // `focus` is how far from the eye the view's subject is
+    pub fn apply(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms, focus: f32) {
+        if !self.enabled {
+            return;
+        }
+        let scissor = framebuffer.scissor();
+        for y in scissor.min_y..scissor.max_y {
+            for x in scissor.min_x..scissor.max_x {
+                let index = y * framebuffer.width + x;
+                let depth = framebuffer.zbuffer[index];
+                if !depth.is_finite() {
+                    continue;
+                }
+                let beyond = (view_distance(depth, uniforms) - focus).max(0.0);
+                let haze = 1.0 - (-self.density * beyond).exp();
+                let radiance = framebuffer.hdr[index];
+                framebuffer.hdr[index] = radiance.lerp(&self.color, haze);
+            }
+        }
+    }
+}
+
+// Undoes the viewport and projection matrices on a stored depth to get back
+// how far in front of the eye it is, along the view direction
+pub fn view_distance(depth: f32, uniforms: &Uniforms) -> f32 {
+    let viewport = &uniforms.viewport_matrix;
+    let projection = &uniforms.projection_matrix;
+    let ndc = (depth - viewport[(2, 3)]) / viewport[(2, 2)];
+    // NDC z is (a * z + b) / -z for view-space z, which is negative in front
+    // of the eye
+    projection[(2, 3)] / (ndc + projection[(2, 2)])
+}
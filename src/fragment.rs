@@ -1,25 +1,182 @@
 
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Mat3, Vec2, Vec3};
 use crate::color::Color;
+use crate::material::Material;
 
 pub struct Fragment {
-    pub position: Vec2,
     pub color: Color,
-    pub depth: f32,
+    // World-space surface normal interpolated from the vertices, unit length
     pub normal: Vec3,
-    pub intensity: f32,
     pub vertex_position: Vec3,
+    // Interpolated like vertex_position, but after the model transform
+    pub world_position: Vec3,
+    // Interpolated from the vertices' like world_position, perspective-correct
+    pub tex_coords: Vec2,
+    pub instance_seed: u32,
+    pub material: Material,
+    // Screen-space derivatives of vertex_position, one pixel right and down
+    pub dpdx: Vec3,
+    pub dpdy: Vec3,
 }
 
 impl Fragment {
-    pub fn new(x: f32, y: f32, color: Color, depth: f32, normal: Vec3, intensity: f32, vertex_position: Vec3,) -> Self {
+    pub fn new(color: Color, normal: Vec3, vertex_position: Vec3) -> Self {
         Fragment {
-            position: Vec2::new(x, y),
             color,
-            depth,
             normal,
-            intensity,
-            vertex_position
+            vertex_position,
+            world_position: Vec3::zeros(),
+            tex_coords: Vec2::zeros(),
+            instance_seed: 0,
+            material: Material::default(),
+            dpdx: Vec3::zeros(),
+            dpdy: Vec3::zeros(),
         }
     }
-}
\ No newline at end of file
+
+    // Object-space size of this pixel, the larger of its two sides
+    pub fn footprint(&self) -> f32 {
+        self.dpdx.magnitude().max(self.dpdy.magnitude())
+    }
+
+    // Unit vector from this fragment toward the camera
+    pub fn view_dir(&self, eye: &Vec3) -> Vec3 {
+        (eye - self.world_position).normalize()
+    }
+}
+
+// Fragments handed to the shaders this many at a time
+pub const CHUNK: usize = 64;
+
+// One Vec3 per fragment in a batch, held as three arrays of its components
+// so a loop over the batch works on each component in one contiguous run
+#[derive(Clone, Copy)]
+pub struct Vec3s {
+    pub x: [f32; CHUNK],
+    pub y: [f32; CHUNK],
+    pub z: [f32; CHUNK],
+}
+
+impl Vec3s {
+    pub const ZERO: Vec3s = Vec3s {
+        x: [0.0; CHUNK],
+        y: [0.0; CHUNK],
+        z: [0.0; CHUNK],
+    };
+
+    pub fn get(&self, index: usize) -> Vec3 {
+        Vec3::new(self.x[index], self.y[index], self.z[index])
+    }
+
+    pub fn set(&mut self, index: usize, value: Vec3) {
+        self.x[index] = value.x;
+        self.y[index] = value.y;
+        self.z[index] = value.z;
+    }
+
+    // Blends the three corners' values by each fragment's weights, for the
+    // first `len` fragments
+    pub fn interpolate(&mut self, corners: [Vec3; 3], weights: &[[f32; CHUNK]; 3], len: usize) {
+        let [w1, w2, w3] = weights.each_ref().map(|w| &w[..len]);
+        let columns = [&mut self.x, &mut self.y, &mut self.z];
+        for (axis, column) in columns.into_iter().enumerate() {
+            let [a, b, c] = corners.map(|corner| corner[axis]);
+            let lanes = column[..len].iter_mut().zip(w1).zip(w2).zip(w3);
+            for (((value, w1), w2), w3) in lanes {
+                *value = a * w1 + b * w2 + c * w3;
+            }
+        }
+    }
+
+    pub fn normalize(&mut self, len: usize) {
+        let (x, y, z) = (&mut self.x[..len], &mut self.y[..len], &mut self.z[..len]);
+        for ((x, y), z) in x.iter_mut().zip(y.iter_mut()).zip(z.iter_mut()) {
+            let length = (*x * *x + *y * *y + *z * *z).sqrt();
+            *x /= length;
+            *y /= length;
+            *z /= length;
+        }
+    }
+
+    // Multiplies the first `len` values by `matrix`
+    pub fn transform(&mut self, matrix: &Mat3, len: usize) {
+        let (x, y, z) = (&mut self.x[..len], &mut self.y[..len], &mut self.z[..len]);
+        for ((x, y), z) in x.iter_mut().zip(y.iter_mut()).zip(z.iter_mut()) {
+            let row = |r: usize| matrix[(r, 0)] * *x + matrix[(r, 1)] * *y + matrix[(r, 2)] * *z;
+            (*x, *y, *z) = (row(0), row(1), row(2));
+        }
+    }
+}
+
+// Up to CHUNK fragments of one triangle, laid out as a structure of arrays:
+// each attribute has an array of its own, indexed by fragment, rather than
+// every fragment being a struct of them. The rasterizer fills in where each
+// fragment is and its weights, then interpolates every attribute over the
+// whole batch in tight loops the compiler can vectorize, and the shaded
+// radiance goes back into `shaded` the same way. A batch is reused from one
+// triangle to the next.
+pub struct FragmentBatch {
+    pub len: usize,
+    pub x: [usize; CHUNK],
+    pub y: [usize; CHUNK],
+    // Each corner's weight, on screen and perspective-correct
+    pub screen_weights: [[f32; CHUNK]; 3],
+    pub weights: [[f32; CHUNK]; 3],
+    pub depth: [f32; CHUNK],
+    pub color: [Color; CHUNK],
+    pub normal: Vec3s,
+    pub vertex_position: Vec3s,
+    pub world_position: Vec3s,
+    pub tex_coords: [[f32; CHUNK]; 2],
+    pub dpdx: Vec3s,
+    pub dpdy: Vec3s,
+    // Interpolated from the vertices' radiance, shown as is under Gouraud
+    // shading instead of shading the fragment
+    pub radiance: Vec3s,
+    // What the shaders made of each fragment
+    pub shaded: Vec3s,
+}
+
+impl FragmentBatch {
+    // Boxed, being a few kilobytes
+    pub fn new() -> Box<Self> {
+        Box::new(FragmentBatch {
+            len: 0,
+            x: [0; CHUNK],
+            y: [0; CHUNK],
+            screen_weights: [[0.0; CHUNK]; 3],
+            weights: [[0.0; CHUNK]; 3],
+            depth: [0.0; CHUNK],
+            color: [Color::black(); CHUNK],
+            normal: Vec3s::ZERO,
+            vertex_position: Vec3s::ZERO,
+            world_position: Vec3s::ZERO,
+            tex_coords: [[0.0; CHUNK]; 2],
+            dpdx: Vec3s::ZERO,
+            dpdy: Vec3s::ZERO,
+            radiance: Vec3s::ZERO,
+            shaded: Vec3s::ZERO,
+        })
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == CHUNK
+    }
+
+    // The fragment at `index` put back together for the shaders
+    pub fn fragment(&self, index: usize, instance_seed: u32, material: Material) -> Fragment {
+        Fragment {
+            world_position: self.world_position.get(index),
+            tex_coords: Vec2::new(self.tex_coords[0][index], self.tex_coords[1][index]),
+            instance_seed,
+            material,
+            dpdx: self.dpdx.get(index),
+            dpdy: self.dpdy.get(index),
+            ..Fragment::new(
+                self.color[index],
+                self.normal.get(index),
+                self.vertex_position.get(index),
+            )
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use crate::png;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// How long a client waits for the next frame before giving up on it, e.g.
+// while the window is minimized
+const FRAME_TIMEOUT: Duration = Duration::from_secs(5);
+
+const INDEX_PAGE: &str = "<!DOCTYPE html>\n<html><head><title>LAB4-GPC</title></head>\
+<body style=\"margin:0;background:#000\">\
+<img src=\"/stream\" style=\"display:block;margin:auto;max-width:100%\">\
+</body></html>\n";
+
+#[derive(Default)]
+struct Frame {
+    number: u64,
+    width: usize,
+    height: usize,
+    pixels: Arc<Vec<u32>>,
+}
+
+#[derive(Default)]
+struct Shared {
+    frame: Mutex<Frame>,
+    new_frame: Condvar,
+    clients: AtomicUsize,
+}
+
+// Serves the presented frames over HTTP so the simulation can be watched from
+// a browser: `/` is a page showing the live stream, `/stream` the stream
+// itself as `multipart/x-mixed-replace` (motion JPEG style, but with PNG
+// parts) and `/frame.png` a single snapshot. Each connection gets its own
+// thread, which does the encoding, so the render loop only copies the frame,
+// and only while someone is watching.
+pub struct FrameServer {
+    shared: Arc<Shared>,
+}
+
+impl FrameServer {
+    pub fn start(port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let shared = Arc::new(Shared::default());
+
+        let accepting = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let shared = Arc::clone(&accepting);
+                thread::spawn(move || {
+                    // A viewer closing the tab ends its connection with an
+                    // error, which is nothing to report
+                    let _ = serve(stream, &shared);
+                });
+            }
+        });
+
+        Ok(FrameServer { shared })
+    }
+
+    pub fn publish(&self, pixels: &[u32], width: usize, height: usize) {
+        if self.shared.clients.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut frame = self.shared.frame.lock().unwrap();
+        frame.number += 1;
+        frame.width = width;
+        frame.height = height;
+        frame.pixels = Arc::new(pixels.to_vec());
+        drop(frame);
+        self.shared.new_frame.notify_all();
+    }
+}
+
+// Keeps the watcher count right however the connection ends
+struct ClientGuard<'a>(&'a AtomicUsize);
+
+impl<'a> ClientGuard<'a> {
+    fn new(clients: &'a AtomicUsize) -> Self {
+        clients.fetch_add(1, Ordering::Relaxed);
+        ClientGuard(clients)
+    }
+}
+
+impl Drop for ClientGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn serve(mut stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Skip the headers; nothing in them changes the response
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+    if method != "GET" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"GET only\n",
+        );
+    }
+
+    match path {
+        "/" => respond(&mut stream, "200 OK", "text/html", INDEX_PAGE.as_bytes()),
+        "/frame.png" => {
+            let _client = ClientGuard::new(&shared.clients);
+            match next_frame(shared, 0) {
+                Some((_, png)) => respond(&mut stream, "200 OK", "image/png", &png),
+                None => respond(
+                    &mut stream,
+                    "503 Service Unavailable",
+                    "text/plain",
+                    b"No frame\n",
+                ),
+            }
+        }
+        "/stream" => {
+            let _client = ClientGuard::new(&shared.clients);
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: multipart/x-mixed-replace; boundary=frame\r\n\
+                 Cache-Control: no-cache\r\nConnection: close\r\n\r\n"
+            )?;
+            let mut last = 0;
+            while let Some((number, png)) = next_frame(shared, last) {
+                last = number;
+                write!(
+                    stream,
+                    "--frame\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n",
+                    png.len()
+                )?;
+                stream.write_all(&png)?;
+                stream.write_all(b"\r\n")?;
+            }
+            Ok(())
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", b"Not found\n"),
+    }
+}
+
+// Waits for a frame newer than `last` and encodes it, or None on timeout
+fn next_frame(shared: &Shared, last: u64) -> Option<(u64, Vec<u8>)> {
+    let frame = shared.frame.lock().unwrap();
+    let (frame, _) = shared
+        .new_frame
+        .wait_timeout_while(frame, FRAME_TIMEOUT, |frame| frame.number <= last)
+        .unwrap();
+    if frame.number <= last {
+        return None;
+    }
+    let (number, width, height) = (frame.number, frame.width, frame.height);
+    let pixels = Arc::clone(&frame.pixels);
+    drop(frame);
+    Some((number, png::encode(width, height, &pixels)))
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
@@ -1,11 +1,162 @@
+use crate::color::Color;
+#[cfg(feature = "diagnostics")]
+use crate::diagnostics;
+use crate::dither::Dither;
+use crate::font::{glyph, GLYPH_SPACING, GLYPH_WIDTH};
+use crate::sprite::SpriteAtlas;
+use crate::texture::Texture;
+use crate::tonemap::ToneMapping;
+use nalgebra_glm::{Vec2, Vec3};
+pub use raster::Scissor;
+use raster::Target;
+
+// A block of pixels, in framebuffer coordinates
+#[derive(Clone, Copy)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+// How a sample's depth is compared against what's stored before it's drawn
+#[derive(Clone, Copy, PartialEq)]
+pub enum DepthTest {
+    Less,
+    LessEqual,
+    Always,
+}
+
+impl DepthTest {
+    pub fn passes(self, depth: f32, stored: f32) -> bool {
+        match self {
+            DepthTest::Less => depth < stored,
+            DepthTest::LessEqual => depth <= stored,
+            DepthTest::Always => true,
+        }
+    }
+}
+
+// The depth test plus whether samples that pass store their depth.
+// Translucent passes test without writing, so they're hidden behind solid
+// surfaces but don't hide anything drawn after them.
+#[derive(Clone, Copy, PartialEq)]
+pub struct DepthState {
+    pub test: DepthTest,
+    pub write: bool,
+}
+
+impl DepthState {
+    pub const OPAQUE: DepthState = DepthState {
+        test: DepthTest::Less,
+        write: true,
+    };
+    pub const TRANSLUCENT: DepthState = DepthState {
+        test: DepthTest::Less,
+        write: false,
+    };
+    // Drawn over everything, leaving depth alone
+    pub const OVERLAY: DepthState = DepthState {
+        test: DepthTest::Always,
+        write: false,
+    };
+}
+
+// How a sample is compared against the stencil value stored under it
+#[derive(Clone, Copy, PartialEq)]
+pub enum StencilTest {
+    Always,
+    NotEqual(u8),
+}
+
+impl StencilTest {
+    pub fn passes(self, stored: u8) -> bool {
+        match self {
+            StencilTest::Always => true,
+            StencilTest::NotEqual(value) => stored != value,
+        }
+    }
+}
+
+// The stencil test plus the value, if any, that samples passing it leave in
+// the stencil buffer. Samples also have to pass the depth test to be drawn.
+#[derive(Clone, Copy, PartialEq)]
+pub struct StencilState {
+    pub test: StencilTest,
+    pub write: Option<u8>,
+}
+
+impl StencilState {
+    pub const DISABLED: StencilState = StencilState {
+        test: StencilTest::Always,
+        write: None,
+    };
+}
+
+// Columns `start..end` of one row that were written since the last clear,
+// empty while `start >= end`
+#[derive(Clone, Copy)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+const CLEAN: Span = Span {
+    start: usize::MAX,
+    end: 0,
+};
+
+impl Span {
+    fn mark(&mut self, x: usize) {
+        self.start = self.start.min(x);
+        self.end = self.end.max(x + 1);
+    }
+
+    fn range(&self) -> Option<std::ops::Range<usize>> {
+        (self.start < self.end).then_some(self.start..self.end)
+    }
+}
+
+// A sample of a see-through surface held back until the opaque ones are in
+#[derive(Clone, Copy)]
+struct TranslucentSample {
+    x: usize,
+    y: usize,
+    depth: f32,
+    radiance: Vec3,
+    alpha: f32,
+}
 
 pub struct Framebuffer {
     pub width: usize,
     pub height: usize,
     pub buffer: Vec<u32>,
     pub zbuffer: Vec<f32>,
+    // Shaded scene radiance, turned into `buffer` by `resolve`. Pixels drawn
+    // straight into `buffer` afterwards (text, outlines) clear their `hdr_mask`
+    // entry so resolving doesn't paint over them.
+    pub hdr: Vec<Vec3>,
+    hdr_mask: Vec<bool>,
+    // Whether `resolve` has run since the last clear, so `buffer` holds this
+    // frame's picture
+    resolved: bool,
+    // Cleared to 0, and only written along with a sample's radiance
+    stencil: Vec<u8>,
+    // How many of the rasterizer's samples were written to each pixel since
+    // the last clear, counted only while `counting_overdraw` is on
+    overdraw: Vec<u16>,
+    counting_overdraw: bool,
+    // What `clear` has to undo, per row: depth written by `point`, and HDR,
+    // mask or stencil entries written by anything. The sky fills every color
+    // row but a planet only touches the depth of the rows it covers.
+    depth_dirty: Vec<Span>,
+    color_dirty: Vec<Span>,
+    depth_state: DepthState,
+    stencil_state: StencilState,
     background_color: u32,
     current_color: u32,
+    scissor: Scissor,
+    translucent: Vec<TranslucentSample>,
 }
 
 impl Framebuffer {
@@ -15,32 +166,482 @@ impl Framebuffer {
             height,
             buffer: vec![0; width * height],
             zbuffer: vec![f32::INFINITY; width * height],
+            hdr: vec![Vec3::zeros(); width * height],
+            hdr_mask: vec![true; width * height],
+            resolved: false,
+            stencil: vec![0; width * height],
+            overdraw: vec![0; width * height],
+            counting_overdraw: false,
+            depth_dirty: vec![CLEAN; height],
+            color_dirty: vec![CLEAN; height],
+            depth_state: DepthState::OPAQUE,
+            stencil_state: StencilState::DISABLED,
             background_color: 0x000000,
-            current_color: 0xFFFFFF
+            current_color: 0xFFFFFF,
+            scissor: Scissor {
+                min_x: 0,
+                min_y: 0,
+                max_x: width,
+                max_y: height,
+            },
+            translucent: Vec::new(),
         }
     }
 
+    // Only resets the spans written since the last clear. `buffer` is left
+    // as it is, since with every mask entry set again `resolve` rewrites all
+    // of it.
     pub fn clear(&mut self) {
-        for pixel in self.buffer.iter_mut() {
-            *pixel = self.background_color;
+        let background = Color::from_hex(self.background_color).to_vec3();
+        for (y, span) in self.depth_dirty.iter_mut().enumerate() {
+            if let Some(columns) = span.range() {
+                let row = y * self.width;
+                self.zbuffer[row + columns.start..row + columns.end].fill(f32::INFINITY);
+            }
+            *span = CLEAN;
+        }
+        for (y, span) in self.color_dirty.iter_mut().enumerate() {
+            if let Some(columns) = span.range() {
+                let row = y * self.width;
+                self.hdr[row + columns.start..row + columns.end].fill(background);
+                self.hdr_mask[row + columns.start..row + columns.end].fill(true);
+                self.stencil[row + columns.start..row + columns.end].fill(0);
+                self.overdraw[row + columns.start..row + columns.end].fill(0);
+            }
+            *span = CLEAN;
+        }
+        self.translucent.clear();
+        self.resolved = false;
+    }
+
+    // Writes a shaded sample into the HDR buffer under the current depth
+    // state
+    pub fn point(&mut self, x: usize, y: usize, depth: f32, radiance: Vec3) {
+        self.store(x, y, depth, self.depth_state, |hdr| *hdr = radiance);
+    }
+
+    // Like `point` but added onto what's there, for glowing particles
+    pub fn add_radiance(&mut self, x: usize, y: usize, depth: f32, radiance: Vec3) {
+        self.store(x, y, depth, self.depth_state, |hdr| *hdr += radiance);
+    }
+
+    // Like `point` but without touching or testing the depth buffer
+    pub fn set_radiance(&mut self, x: usize, y: usize, radiance: Vec3) {
+        self.store(x, y, 0.0, DepthState::OVERLAY, |hdr| *hdr = radiance);
+    }
+
+    // Holds back a sample that lets `1 - alpha` of what's behind it through,
+    // to be laid over the picture by `composite_translucent`. One already
+    // behind what's in the depth buffer is dropped now.
+    pub fn queue_translucent(
+        &mut self,
+        x: usize,
+        y: usize,
+        depth: f32,
+        radiance: Vec3,
+        alpha: f32,
+    ) {
+        if self.in_scissor(x, y) && depth < self.zbuffer[y * self.width + x] {
+            self.translucent.push(TranslucentSample {
+                x,
+                y,
+                depth,
+                radiance,
+                alpha: alpha.clamp(0.0, 1.0),
+            });
+        }
+    }
+
+    // Blends the queued samples in, farthest first, so each one covers those
+    // behind it whatever order they were drawn in. Sorting the samples
+    // themselves rather than the triangles means surfaces that cross each
+    // other come out right without having to be split up. Called once the
+    // opaque geometry is in; it still hides the samples behind it, and they
+    // don't write depth.
+    pub fn composite_translucent(&mut self) {
+        let mut queue = std::mem::take(&mut self.translucent);
+        // Stable, so samples at the same depth go on in the order drawn
+        queue.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+        for sample in &queue {
+            self.store(
+                sample.x,
+                sample.y,
+                sample.depth,
+                DepthState::TRANSLUCENT,
+                |hdr| *hdr = hdr.lerp(&sample.radiance, sample.alpha),
+            );
         }
-        for depth in self.zbuffer.iter_mut() {
-            *depth = f32::INFINITY;
+        queue.clear();
+        self.translucent = queue;
+    }
+
+    fn store(
+        &mut self,
+        x: usize,
+        y: usize,
+        depth: f32,
+        state: DepthState,
+        blend: impl FnOnce(&mut Vec3),
+    ) -> bool {
+        if !self.passes_tests(x, y, depth, state) {
+            return false;
+        }
+        let index = y * self.width + x;
+        blend(&mut self.hdr[index]);
+        self.hdr_mask[index] = true;
+        self.color_dirty[y].mark(x);
+        if let Some(value) = self.stencil_state.write {
+            self.stencil[index] = value;
+        }
+        if state.write {
+            self.zbuffer[index] = depth;
+            self.depth_dirty[y].mark(x);
+        }
+        true
+    }
+
+    // The scissor, stencil and `state`'s depth test, as every HDR write
+    // takes them
+    fn passes_tests(&self, x: usize, y: usize, depth: f32, state: DepthState) -> bool {
+        if !self.in_scissor(x, y) {
+            return false;
         }
+        let index = y * self.width + x;
+        self.stencil_state.test.passes(self.stencil[index])
+            && state.test.passes(depth, self.zbuffer[index])
     }
 
-    pub fn point(&mut self, x: usize, y: usize, depth: f32) {
-        if x < self.width && y < self.height {
+    // How `point` and `add_radiance` test and write depth from now on, and
+    // the rasterizer through `row_bands`
+    pub fn set_depth_state(&mut self, state: DepthState) {
+        self.depth_state = state;
+    }
+
+    pub fn depth_state(&self) -> DepthState {
+        self.depth_state
+    }
+
+    // How every HDR write tests and marks the stencil buffer from now on,
+    // including through `row_bands`
+    pub fn set_stencil_state(&mut self, state: StencilState) {
+        self.stencil_state = state;
+    }
+
+    // Whether a sample at `depth` would pass `test` against what's stored at
+    // (x, y), without drawing anything. Outside the framebuffer nothing does.
+    pub fn passes_depth(&self, x: usize, y: usize, depth: f32, test: DepthTest) -> bool {
+        x < self.width && y < self.height && test.passes(depth, self.zbuffer[y * self.width + x])
+    }
+
+    // Writes the current color without touching or testing the depth buffer
+    pub fn set_pixel(&mut self, x: usize, y: usize) {
+        if self.in_scissor(x, y) {
             let index = y * self.width + x;
+            self.buffer[index] = self.current_color;
+            self.hdr_mask[index] = false;
+            self.color_dirty[y].mark(x);
+        }
+    }
 
-            if self.zbuffer[index] > depth {
-                self.buffer[index] = self.current_color;
-                self.zbuffer[index] = depth;
+    // Tone maps and quantizes every HDR pixel into `buffer`. Anything drawn
+    // directly into `buffer` since the last clear is left alone.
+    pub fn resolve(&mut self, tone_mapping: &ToneMapping, dither: Dither) {
+        self.resolved = true;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = y * self.width + x;
+                if self.hdr_mask[index] {
+                    #[cfg(feature = "diagnostics")]
+                    if !diagnostics::finite(&self.hdr[index]) {
+                        self.buffer[index] = diagnostics::MAGENTA;
+                        continue;
+                    }
+                    let mapped = tone_mapping.apply(self.hdr[index]);
+                    self.buffer[index] = dither
+                        .quantize(mapped, Vec2::new(x as f32, y as f32))
+                        .to_hex();
+                }
+            }
+        }
+    }
+
+    // Blits `text` in the current color with its top-left corner at (x, y),
+    // each font pixel drawn as a `scale` x `scale` block. Clipped to the
+    // framebuffer and ignores depth.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, scale: usize) {
+        let step = ((GLYPH_WIDTH + GLYPH_SPACING) * scale) as i32;
+        for (index, c) in text.chars().enumerate() {
+            let origin_x = x + index as i32 * step;
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let px = origin_x + (column * scale + dx) as i32;
+                            let py = y + (row * scale + dy) as i32;
+                            if px >= 0 && py >= 0 {
+                                self.set_pixel(px as usize, py as usize);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Stretches `texture` over a screen rectangle with its own filter,
+    // ignoring depth. Clipped by the scissor like any other drawing.
+    pub fn draw_texture(
+        &mut self,
+        texture: &Texture,
+        x: usize,
+        y: usize,
+        width: usize,
+        height: usize,
+    ) {
+        let footprint =
+            (texture.width as f32 / width as f32).max(texture.height as f32 / height as f32);
+        for row in 0..height {
+            let v = (row as f32 + 0.5) / height as f32;
+            for column in 0..width {
+                let u = (column as f32 + 0.5) / width as f32;
+                if self.in_scissor(x + column, y + row) {
+                    let index = (y + row) * self.width + x + column;
+                    self.buffer[index] = texture.sample(u, v, footprint).to_hex();
+                    self.hdr_mask[index] = false;
+                    self.color_dirty[y + row].mark(x + column);
+                }
+            }
+        }
+    }
+
+    // Fills `rect` with the current color, ignoring depth
+    pub fn fill_rect(&mut self, rect: Rect) {
+        for y in rect.y..rect.y + rect.height {
+            for x in rect.x..rect.x + rect.width {
+                self.set_pixel(x, y);
+            }
+        }
+    }
+
+    // Draws the `from` region of `atlas` with its top-left corner at (x, y),
+    // `scale` times its size, each texel's color multiplied by the current
+    // color and laid over what's there by its alpha. Shrunk, each pixel
+    // averages the texels it covers. With a `depth`, it's hidden wherever
+    // something nearer has been drawn. Before `resolve` it's blended into
+    // the radiance of the scene's pixels, to be tone mapped along with them,
+    // and otherwise over the picture in `buffer`.
+    pub fn draw_sprite(
+        &mut self,
+        atlas: &SpriteAtlas,
+        from: Rect,
+        x: i32,
+        y: i32,
+        scale: f32,
+        depth: Option<f32>,
+    ) {
+        let tint = Color::from_hex(self.current_color).to_vec3();
+        let width = (from.width as f32 * scale).round() as i32;
+        let height = (from.height as f32 * scale).round() as i32;
+        // Texels of `from` along one side under pixels `start..start + 1`
+        let covered = |start: i32, limit: usize| {
+            let first = (start as f32 / scale) as usize;
+            let last = (((start + 1) as f32 / scale).ceil() as usize).clamp(first + 1, limit);
+            first.min(limit - 1)..last
+        };
+        for row in 0..height {
+            for column in 0..width {
+                let (px, py) = (x + column, y + row);
+                if px < 0 || py < 0 || !self.in_scissor(px as usize, py as usize) {
+                    continue;
+                }
+                let (px, py) = (px as usize, py as usize);
+                let index = py * self.width + px;
+                if depth.is_some_and(|depth| !DepthTest::Less.passes(depth, self.zbuffer[index])) {
+                    continue;
+                }
+
+                let (mut color, mut alpha, mut count) = (Vec3::zeros(), 0.0, 0.0);
+                for ty in covered(row, from.height) {
+                    for tx in covered(column, from.width) {
+                        let texel = atlas.texel(from.x + tx, from.y + ty);
+                        let opacity = (texel >> 24) as f32 / 255.0;
+                        color += Color::from_hex(texel).to_vec3() * opacity;
+                        alpha += opacity;
+                        count += 1.0;
+                    }
+                }
+                if alpha <= 0.0 {
+                    continue;
+                }
+                let color = (color / alpha).component_mul(&tint);
+                let alpha = alpha / count;
+                if self.hdr_mask[index] && !self.resolved {
+                    self.hdr[index] = self.hdr[index].lerp(&color, alpha);
+                } else {
+                    let under = Color::from_hex(self.buffer[index]).to_vec3();
+                    self.buffer[index] = Color::from_vec3(under.lerp(&color, alpha)).to_hex();
+                    self.hdr_mask[index] = false;
+                }
+                self.color_dirty[py].mark(px);
+            }
+        }
+    }
+
+    // Copies the resolved pixels in `from` out of another framebuffer with
+    // their top-left corner at (x, y), one to one. Pixels equal to
+    // `color_key` are skipped, leaving what's underneath.
+    pub fn blit(
+        &mut self,
+        source: &Framebuffer,
+        from: Rect,
+        x: usize,
+        y: usize,
+        color_key: Option<u32>,
+    ) {
+        let from = source.clip(from);
+        for row in 0..from.height {
+            let start = (from.y + row) * source.width + from.x;
+            for (column, &color) in source.buffer[start..start + from.width].iter().enumerate() {
+                self.put(x + column, y + row, color, color_key);
             }
         }
     }
 
+    // Like `blit` but within this framebuffer. The source is read before
+    // anything is written, so the two rectangles may overlap.
+    pub fn copy_region(&mut self, from: Rect, x: usize, y: usize, color_key: Option<u32>) {
+        let from = self.clip(from);
+        let pixels: Vec<u32> = (0..from.height)
+            .flat_map(|row| {
+                let start = (from.y + row) * self.width + from.x;
+                self.buffer[start..start + from.width].to_vec()
+            })
+            .collect();
+        for (row, colors) in pixels.chunks_exact(from.width.max(1)).enumerate() {
+            for (column, &color) in colors.iter().enumerate() {
+                self.put(x + column, y + row, color, color_key);
+            }
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, color: u32, color_key: Option<u32>) {
+        if Some(color) != color_key && self.in_scissor(x, y) {
+            let index = y * self.width + x;
+            self.buffer[index] = color;
+            self.hdr_mask[index] = false;
+            self.color_dirty[y].mark(x);
+        }
+    }
+
+    // `rect` trimmed to the framebuffer
+    fn clip(&self, rect: Rect) -> Rect {
+        let x = rect.x.min(self.width);
+        let y = rect.y.min(self.height);
+        Rect {
+            x,
+            y,
+            width: rect.width.min(self.width - x),
+            height: rect.height.min(self.height - y),
+        }
+    }
+
+    // Splits the rows between `count` owners, top to bottom, so each can
+    // shade its own strip without locking. Strips start on even rows to keep
+    // the rasterizer's 2x2 quads whole, and each one's scissor is the current
+    // scissor cut down to its rows.
+    pub fn row_bands(&mut self, count: usize) -> Vec<RowBand<'_>> {
+        let rows = self
+            .height
+            .div_ceil(count.max(1))
+            .next_multiple_of(2)
+            .max(2);
+        let pixels = rows * self.width;
+        let scissor = self.scissor;
+        let depth_state = self.depth_state;
+        let stencil_state = self.stencil_state;
+        let counting_overdraw = self.counting_overdraw;
+        self.hdr
+            .chunks_mut(pixels)
+            .zip(self.zbuffer.chunks_mut(pixels))
+            .zip(self.hdr_mask.chunks_mut(pixels))
+            .zip(self.stencil.chunks_mut(pixels))
+            .zip(self.overdraw.chunks_mut(pixels))
+            .zip(self.depth_dirty.chunks_mut(rows))
+            .zip(self.color_dirty.chunks_mut(rows))
+            .enumerate()
+            .map(
+                |(
+                    index,
+                    ((((((hdr, zbuffer), hdr_mask), stencil), overdraw), depth_dirty), color_dirty),
+                )| {
+                    let min_y = index * rows;
+                    RowBand {
+                        scissor: Scissor {
+                            min_y: scissor.min_y.clamp(min_y, min_y + rows),
+                            max_y: scissor.max_y.clamp(min_y, min_y + rows),
+                            ..scissor
+                        },
+                        width: self.width,
+                        min_y,
+                        depth_state,
+                        stencil_state,
+                        hdr,
+                        zbuffer,
+                        hdr_mask,
+                        stencil,
+                        overdraw: counting_overdraw.then_some(overdraw),
+                        depth_dirty,
+                        color_dirty,
+                    }
+                },
+            )
+            .collect()
+    }
+
+    // Restricts `point`, `set_pixel` and the rasterizer to a rectangle,
+    // clamped to the framebuffer. `clear` still covers everything.
+    pub fn set_scissor(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        self.scissor = Scissor {
+            min_x: x.min(self.width),
+            min_y: y.min(self.height),
+            max_x: (x + width).min(self.width),
+            max_y: (y + height).min(self.height),
+        };
+    }
+
+    pub fn reset_scissor(&mut self) {
+        self.set_scissor(0, 0, self.width, self.height);
+    }
+
+    pub fn scissor(&self) -> Scissor {
+        self.scissor
+    }
+
+    fn in_scissor(&self, x: usize, y: usize) -> bool {
+        self.scissor.contains(x, y)
+    }
+
+    // Pixels that haven't been written still hold the old background, so
+    // changing it makes the next clear cover everything
+    // Whether the rasterizer counts its samples in `overdraw` from now on
+    pub fn count_overdraw(&mut self, on: bool) {
+        self.counting_overdraw = on;
+    }
+
+    pub fn overdraw(&self, x: usize, y: usize) -> u16 {
+        self.overdraw[y * self.width + x]
+    }
+
     pub fn set_background_color(&mut self, color: u32) {
+        if color != self.background_color {
+            self.color_dirty.fill(Span {
+                start: 0,
+                end: self.width,
+            });
+        }
         self.background_color = color;
     }
 
@@ -48,3 +649,252 @@ impl Framebuffer {
         self.current_color = color;
     }
 }
+
+// Radiance into the HDR buffer, through the scissor, the stencil state and
+// the depth state, as `point` writes it
+impl Target for Framebuffer {
+    type Color = Vec3;
+
+    fn scissor(&self) -> Scissor {
+        self.scissor
+    }
+
+    fn passes(&self, x: usize, y: usize, depth: f32) -> bool {
+        self.passes_tests(x, y, depth, self.depth_state)
+    }
+
+    fn write(&mut self, x: usize, y: usize, depth: f32, radiance: Vec3) -> bool {
+        self.store(x, y, depth, self.depth_state, |hdr| *hdr = radiance)
+    }
+}
+
+// A strip of whole rows borrowed out of a framebuffer by `row_bands`, with
+// the HDR writes of `Framebuffer` limited to its own scissor and its depth
+// and stencil states as of the borrow
+pub struct RowBand<'a> {
+    pub scissor: Scissor,
+    width: usize,
+    min_y: usize,
+    depth_state: DepthState,
+    stencil_state: StencilState,
+    hdr: &'a mut [Vec3],
+    zbuffer: &'a mut [f32],
+    hdr_mask: &'a mut [bool],
+    stencil: &'a mut [u8],
+    // Only lent while the framebuffer is counting overdraw
+    overdraw: Option<&'a mut [u16]>,
+    depth_dirty: &'a mut [Span],
+    color_dirty: &'a mut [Span],
+}
+
+impl RowBand<'_> {
+    pub fn point(&mut self, x: usize, y: usize, depth: f32, radiance: Vec3) {
+        self.store(x, y, depth, self.depth_state, radiance);
+    }
+
+    pub fn set_radiance(&mut self, x: usize, y: usize, radiance: Vec3) {
+        self.store(x, y, 0.0, DepthState::OVERLAY, radiance);
+    }
+
+    fn store(&mut self, x: usize, y: usize, depth: f32, state: DepthState, radiance: Vec3) {
+        if !self.scissor.contains(x, y) {
+            return;
+        }
+        let row = y - self.min_y;
+        let index = row * self.width + x;
+        let stencil = self.stencil_state;
+        if stencil.test.passes(self.stencil[index]) && state.test.passes(depth, self.zbuffer[index])
+        {
+            self.hdr[index] = radiance;
+            self.hdr_mask[index] = true;
+            self.color_dirty[row].mark(x);
+            if let Some(overdraw) = self.overdraw.as_mut() {
+                overdraw[index] = overdraw[index].saturating_add(1);
+            }
+            if let Some(value) = stencil.write {
+                self.stencil[index] = value;
+            }
+            if state.write {
+                self.zbuffer[index] = depth;
+                self.depth_dirty[row].mark(x);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raster::Point;
+
+    const WIDTH: usize = 64;
+    const HEIGHT: usize = 48;
+
+    type Path = (&'static str, fn(&mut Framebuffer));
+
+    // Every way a frame writes to the framebuffer, each on its own so one
+    // that covers the whole screen, like the sky, doesn't hide what another
+    // leaves behind
+    const PATHS: [Path; 10] = [
+        ("sky", |framebuffer| {
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    framebuffer.set_radiance(x, y, Vec3::new(0.0, 0.0, 0.1));
+                }
+            }
+        }),
+        ("point", |framebuffer| {
+            for x in 10..30 {
+                framebuffer.point(x, 12, 0.5, Vec3::new(1.0, 0.5, 0.2));
+            }
+        }),
+        ("fill_triangle", |framebuffer| {
+            framebuffer.fill_triangle(
+                Point::new(4.0, 20.0, 0.3),
+                Point::new(30.0, 22.0, 0.3),
+                Point::new(12.0, 40.0, 0.6),
+                |(w1, _, _)| Vec3::repeat(w1),
+            );
+        }),
+        ("add_radiance", |framebuffer| {
+            for x in 10..30 {
+                framebuffer.add_radiance(x, 13, 0.4, Vec3::repeat(2.0));
+            }
+        }),
+        ("stencil", |framebuffer| {
+            framebuffer.set_stencil_state(StencilState {
+                test: StencilTest::Always,
+                write: Some(1),
+            });
+            framebuffer.point(7, 9, 0.5, Vec3::repeat(1.0));
+            framebuffer.set_stencil_state(StencilState::DISABLED);
+        }),
+        ("row_bands", |framebuffer| {
+            framebuffer.count_overdraw(true);
+            framebuffer.set_scissor(5, 3, 50, 40);
+            let mut bands = framebuffer.row_bands(4);
+            assert!(bands.len() > 1);
+            std::thread::scope(|scope| {
+                for band in &mut bands {
+                    scope.spawn(move || {
+                        for y in band.scissor.min_y..band.scissor.max_y {
+                            for x in 20..40 {
+                                let depth = 0.3 + x as f32 / 100.0;
+                                band.point(x, y, depth, Vec3::new(0.2, 0.9, 0.1));
+                            }
+                            band.set_radiance(45, y, Vec3::repeat(5.0));
+                        }
+                    });
+                }
+            });
+            framebuffer.reset_scissor();
+            framebuffer.count_overdraw(false);
+        }),
+        ("translucent", |framebuffer| {
+            for x in 0..WIDTH {
+                framebuffer.queue_translucent(x, 20, 0.2, Vec3::new(0.3, 0.3, 1.0), 0.5);
+            }
+            framebuffer.composite_translucent();
+            // Queued and never composited
+            framebuffer.queue_translucent(1, 1, 0.1, Vec3::repeat(1.0), 0.5);
+        }),
+        ("text", |framebuffer| {
+            framebuffer.resolve(&ToneMapping::default(), Dither::Off);
+            framebuffer.set_current_color(0xFF00FF);
+            framebuffer.draw_text(2, 2, "CLEAR", 1);
+        }),
+        ("fill_rect", |framebuffer| {
+            framebuffer.fill_rect(Rect {
+                x: 50,
+                y: 40,
+                width: 8,
+                height: 5,
+            });
+        }),
+        ("copy_region", |framebuffer| {
+            let from = Rect {
+                x: 0,
+                y: 0,
+                width: 20,
+                height: 10,
+            };
+            framebuffer.copy_region(from, 40, 30, None);
+        }),
+    ];
+
+    fn assert_fresh(framebuffer: &mut Framebuffer, after: &str) {
+        let mut fresh = Framebuffer::new(WIDTH, HEIGHT);
+        assert!(framebuffer.zbuffer == fresh.zbuffer, "depth after {after}");
+        assert!(framebuffer.hdr == fresh.hdr, "HDR after {after}");
+        assert!(framebuffer.hdr_mask == fresh.hdr_mask, "mask after {after}");
+        assert!(
+            framebuffer.stencil == fresh.stencil,
+            "stencil after {after}"
+        );
+        assert!(
+            framebuffer.overdraw == fresh.overdraw,
+            "overdraw after {after}"
+        );
+        assert!(framebuffer.translucent.is_empty(), "queue after {after}");
+        assert!(!framebuffer.resolved, "resolved after {after}");
+        // `buffer` is only rewritten by resolving, which then covers it all
+        framebuffer.resolve(&ToneMapping::default(), Dither::Off);
+        fresh.resolve(&ToneMapping::default(), Dither::Off);
+        assert!(framebuffer.buffer == fresh.buffer, "picture after {after}");
+        framebuffer.clear();
+    }
+
+    #[test]
+    fn clear_undoes_each_kind_of_drawing() {
+        let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+        for (name, draw) in PATHS {
+            draw(&mut framebuffer);
+            framebuffer.clear();
+            assert_fresh(&mut framebuffer, name);
+        }
+        for (_, draw) in PATHS {
+            draw(&mut framebuffer);
+        }
+        framebuffer.clear();
+        assert_fresh(&mut framebuffer, "everything");
+    }
+
+    #[test]
+    fn clear_after_a_new_background_covers_everything() {
+        let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+        framebuffer.point(3, 4, 0.5, Vec3::repeat(1.0));
+        framebuffer.clear();
+        framebuffer.set_background_color(0x336699);
+        framebuffer.clear();
+        let background = Color::from_hex(0x336699).to_vec3();
+        assert!(framebuffer.hdr.iter().all(|hdr| *hdr == background));
+        assert!(framebuffer
+            .zbuffer
+            .iter()
+            .all(|&depth| depth == f32::INFINITY));
+    }
+
+    #[test]
+    fn triangles_go_through_the_scissor_and_stencil() {
+        let mut framebuffer = Framebuffer::new(WIDTH, HEIGHT);
+        framebuffer.clear();
+        framebuffer.set_stencil_state(StencilState {
+            test: StencilTest::Always,
+            write: Some(1),
+        });
+        framebuffer.point(2, 2, 0.5, Vec3::repeat(0.25));
+        framebuffer.set_stencil_state(StencilState {
+            test: StencilTest::NotEqual(1),
+            write: None,
+        });
+        framebuffer.set_scissor(0, 0, 8, 8);
+        let corners = [(0.0, 0.0), (WIDTH as f32, 0.0), (0.0, HEIGHT as f32)];
+        let [a, b, c] = corners.map(|(x, y)| Point::new(x, y, 0.1));
+        framebuffer.fill_triangle(a, b, c, |_| Vec3::repeat(1.0));
+        let hdr = |x: usize, y: usize| framebuffer.hdr[y * WIDTH + x];
+        assert_eq!(hdr(2, 2), Vec3::repeat(0.25));
+        assert_eq!(hdr(3, 2), Vec3::repeat(1.0));
+        assert!((framebuffer.zbuffer[2 * WIDTH + 3] - 0.1).abs() < 1e-4);
+        assert_eq!(framebuffer.zbuffer[2 * WIDTH + 9], f32::INFINITY);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::input::InputFrame;
+use minifb::{Window, WindowOptions};
+
+// Where the viewer's finished frames are shown and its input comes from. The
+// loop in main.rs hands what `poll` read to `Viewer::frame`, which draws a
+// frame and gives back one scaled to `size` for `show`.
+pub trait Frontend {
+    // False once it's been closed, which ends the viewer
+    fn is_open(&self) -> bool;
+    // Pixels across and down, which can change from one frame to the next
+    fn size(&self) -> (usize, usize);
+    // `frame` is `size` pixels, 0xRRGGBB, row by row
+    fn show(&mut self, frame: &[u32]);
+    fn poll(&mut self) -> InputFrame;
+    // A line for the user to read, like a file saved or one that couldn't
+    // be, kept out of the frames
+    fn status(&mut self, message: &str);
+    // F11: over the whole screen and back, where there's a screen to cover
+    fn toggle_fullscreen(&mut self) {}
+}
+
+// A minifb window, decorated where it was last put or borderless on top of
+// everything in the screen's top-left corner, which is as close to
+// fullscreen as minifb gets
+pub struct WindowFrontend {
+    window: Window,
+    title: String,
+    windowed_size: (usize, usize),
+    fullscreen_size: (usize, usize),
+    fullscreen: bool,
+    windowed_position: (isize, isize),
+}
+
+impl WindowFrontend {
+    pub fn open(title: &str, size: (usize, usize), fullscreen_size: (usize, usize)) -> Self {
+        let windowed_position = (500, 500);
+        let mut window = open_window(title, size, windowed_position, false);
+        window.update();
+        WindowFrontend {
+            window,
+            title: title.to_string(),
+            windowed_size: size,
+            fullscreen_size,
+            fullscreen: false,
+            windowed_position,
+        }
+    }
+}
+
+fn open_window(
+    title: &str,
+    (width, height): (usize, usize),
+    position: (isize, isize),
+    fullscreen: bool,
+) -> Window {
+    let options = WindowOptions {
+        borderless: fullscreen,
+        title: !fullscreen,
+        topmost: fullscreen,
+        ..WindowOptions::default()
+    };
+    let mut window = Window::new(title, width, height, options).expect("Failed to open window");
+    let (x, y) = if fullscreen { (0, 0) } else { position };
+    window.set_position(x, y);
+    window
+}
+
+impl Frontend for WindowFrontend {
+    fn is_open(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn size(&self) -> (usize, usize) {
+        if self.fullscreen {
+            self.fullscreen_size
+        } else {
+            self.windowed_size
+        }
+    }
+
+    fn show(&mut self, frame: &[u32]) {
+        let (width, height) = self.size();
+        self.window
+            .update_with_buffer(frame, width, height)
+            .unwrap();
+    }
+
+    fn poll(&mut self) -> InputFrame {
+        InputFrame::poll(&self.window)
+    }
+
+    fn status(&mut self, message: &str) {
+        println!("{}", message);
+    }
+
+    fn toggle_fullscreen(&mut self) {
+        self.fullscreen = !self.fullscreen;
+        if self.fullscreen {
+            self.windowed_position = self.window.get_position();
+        }
+        self.window = open_window(
+            &self.title,
+            self.size(),
+            self.windowed_position,
+            self.fullscreen,
+        );
+    }
+}
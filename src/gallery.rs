@@ -0,0 +1,292 @@
+use crate::color::Color;
+use crate::error::{self, Error};
+use crate::font::{GLYPH_HEIGHT, GLYPH_SPACING, GLYPH_WIDTH};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::input::InputFrame;
+use crate::locale::Locale;
+use crate::png;
+use minifb::Key;
+use nalgebra_glm::Vec3;
+use std::fs;
+use std::path::Path;
+
+pub const SCREENSHOT_DIR: &str = "screenshots";
+
+const COLUMNS: usize = 4;
+// Pixels around the edge of the screen and between thumbnails
+const MARGIN: usize = 8;
+// Room for one line of text, under each thumbnail and for the title
+const LINE: usize = GLYPH_HEIGHT + 4;
+const BACKGROUND: u32 = 0x101018;
+const HIGHLIGHT: u32 = 0xFFD700;
+
+// Saves the resolved frame as the next numbered PNG in SCREENSHOT_DIR and
+// returns where it went
+pub fn save_screenshot(framebuffer: &Framebuffer) -> error::Result<String> {
+    fs::create_dir_all(SCREENSHOT_DIR).map_err(Error::io(SCREENSHOT_DIR))?;
+    let path = (1..)
+        .map(|number| format!("{}/screenshot-{:04}.png", SCREENSHOT_DIR, number))
+        .find(|path| !Path::new(path).exists())
+        .unwrap_or_default();
+    let pixels = png::encode(framebuffer.width, framebuffer.height, &framebuffer.buffer);
+    fs::write(&path, pixels).map_err(Error::io(&path))?;
+    Ok(path)
+}
+
+struct Shot {
+    name: String,
+    image: Framebuffer,
+}
+
+// The screenshots taken so far, as a grid of thumbnails to move around with
+// the arrow keys and open one at a time over the whole screen. The files
+// are read when it opens so new captures show up, and the scaled copies
+// are made again whenever the screen changes size.
+pub struct Gallery {
+    open: bool,
+    shots: Vec<Shot>,
+    selected: usize,
+    full_view: bool,
+    // Row of thumbnails at the top of the screen, scrolled to keep the
+    // selection in sight
+    first_row: usize,
+    // Scaled to fit cells of this size
+    thumbnails: Vec<Framebuffer>,
+    cell: (usize, usize),
+    // The selected shot scaled to fit the screen, which one it is and the
+    // size it was fitted to
+    full: Option<(usize, (usize, usize), Framebuffer)>,
+}
+
+impl Gallery {
+    pub fn new() -> Self {
+        Gallery {
+            open: false,
+            shots: Vec::new(),
+            selected: 0,
+            full_view: false,
+            first_row: 0,
+            thumbnails: Vec::new(),
+            cell: (0, 0),
+            full: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // Opens on the newest screenshot, or closes
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.thumbnails.clear();
+        self.full = None;
+        self.full_view = false;
+        if !self.open {
+            self.shots.clear();
+            return;
+        }
+        self.shots = load_shots();
+        self.selected = self.shots.len().saturating_sub(1);
+    }
+
+    pub fn handle_input(&mut self, input: &InputFrame) {
+        let last = self.shots.len().saturating_sub(1);
+        if input.is_key_pressed(Key::Left) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if input.is_key_pressed(Key::Right) {
+            self.selected = (self.selected + 1).min(last);
+        }
+        if input.is_key_pressed(Key::Up) && !self.full_view {
+            self.selected = self.selected.saturating_sub(COLUMNS);
+        }
+        if input.is_key_pressed(Key::Down) && !self.full_view {
+            self.selected = (self.selected + COLUMNS).min(last);
+        }
+        if input.is_key_pressed(Key::Enter) && !self.shots.is_empty() {
+            self.full_view = !self.full_view;
+        }
+        if input.is_key_pressed(Key::Backspace) {
+            if self.full_view {
+                self.full_view = false;
+            } else {
+                self.toggle();
+            }
+        }
+    }
+
+    pub fn draw(&mut self, framebuffer: &mut Framebuffer, locale: &Locale) {
+        framebuffer.reset_scissor();
+        framebuffer.set_current_color(BACKGROUND);
+        framebuffer.fill_rect(Rect {
+            x: 0,
+            y: 0,
+            width: framebuffer.width,
+            height: framebuffer.height,
+        });
+        framebuffer.set_current_color(0xFFFFFF);
+
+        if self.shots.is_empty() {
+            let text = locale.format("gallery.empty", &[&SCREENSHOT_DIR]);
+            framebuffer.draw_text(MARGIN as i32, MARGIN as i32, &text, 1);
+        } else if self.full_view {
+            self.draw_full_view(framebuffer);
+        } else {
+            self.draw_grid(framebuffer, locale);
+        }
+    }
+
+    fn draw_grid(&mut self, framebuffer: &mut Framebuffer, locale: &Locale) {
+        let cell_width = framebuffer.width.saturating_sub(MARGIN * (COLUMNS + 1)) / COLUMNS;
+        let cell_height = cell_width * framebuffer.height / framebuffer.width.max(1);
+        if cell_width == 0 || cell_height == 0 {
+            return;
+        }
+        if self.thumbnails.len() != self.shots.len() || self.cell != (cell_width, cell_height) {
+            self.cell = (cell_width, cell_height);
+            self.thumbnails = self
+                .shots
+                .iter()
+                .map(|shot| fitted(&shot.image, cell_width, cell_height))
+                .collect();
+        }
+
+        let title = locale.format("gallery.title", &[&(self.selected + 1), &self.shots.len()]);
+        framebuffer.draw_text(MARGIN as i32, MARGIN as i32, &title, 1);
+
+        let top = MARGIN + LINE;
+        let bottom = framebuffer.height.saturating_sub(LINE + MARGIN);
+        let row_height = cell_height + LINE + MARGIN;
+        let rows = (bottom.saturating_sub(top) / row_height).max(1);
+        let selected_row = self.selected / COLUMNS;
+        if selected_row < self.first_row {
+            self.first_row = selected_row;
+        } else if selected_row >= self.first_row + rows {
+            self.first_row = selected_row + 1 - rows;
+        }
+
+        let first = self.first_row * COLUMNS;
+        let visible = self.shots.iter().zip(&self.thumbnails).enumerate();
+        for (index, (shot, thumbnail)) in visible.skip(first).take(rows * COLUMNS) {
+            let column = index % COLUMNS;
+            let row = index / COLUMNS - self.first_row;
+            let x = MARGIN + column * (cell_width + MARGIN);
+            let y = top + row * row_height;
+            let thumbnail_x = x + (cell_width - thumbnail.width) / 2;
+            let thumbnail_y = y + (cell_height - thumbnail.height) / 2;
+
+            if index == self.selected {
+                framebuffer.set_current_color(HIGHLIGHT);
+                framebuffer.fill_rect(Rect {
+                    x: thumbnail_x - 2,
+                    y: thumbnail_y - 2,
+                    width: thumbnail.width + 4,
+                    height: thumbnail.height + 4,
+                });
+            }
+            let whole = Rect {
+                x: 0,
+                y: 0,
+                width: thumbnail.width,
+                height: thumbnail.height,
+            };
+            framebuffer.blit(thumbnail, whole, thumbnail_x, thumbnail_y, None);
+
+            let fits = cell_width / (GLYPH_WIDTH + GLYPH_SPACING);
+            let caption: String = shot.name.chars().take(fits).collect();
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(x as i32, (y + cell_height + 3) as i32, &caption, 1);
+        }
+
+        let help = locale.get("gallery.help");
+        framebuffer.draw_text(MARGIN as i32, (bottom + 3) as i32, help, 1);
+    }
+
+    fn draw_full_view(&mut self, framebuffer: &mut Framebuffer) {
+        let (width, height) = (framebuffer.width, framebuffer.height - LINE);
+        let wanted = (self.selected, (width, height));
+        if self
+            .full
+            .as_ref()
+            .is_none_or(|(index, size, _)| (*index, *size) != wanted)
+        {
+            let image = fitted(&self.shots[self.selected].image, width, height);
+            self.full = Some((self.selected, (width, height), image));
+        }
+        let Some((_, _, image)) = &self.full else {
+            return;
+        };
+        let whole = Rect {
+            x: 0,
+            y: 0,
+            width: image.width,
+            height: image.height,
+        };
+        let x = (width - image.width) / 2;
+        let y = (height - image.height) / 2;
+        framebuffer.blit(image, whole, x, y, None);
+
+        let name = &self.shots[self.selected].name;
+        framebuffer.draw_text(MARGIN as i32, (height + 2) as i32, name, 1);
+    }
+}
+
+// Every screenshot that can be read back, oldest first. png::decode only
+// reads files like the ones save_screenshot writes, so others are left out.
+fn load_shots() -> Vec<Shot> {
+    let Ok(entries) = fs::read_dir(SCREENSHOT_DIR) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| {
+            let decoded = fs::read(path).ok().and_then(|bytes| png::decode(&bytes));
+            let (width, height, pixels) = decoded?;
+            let mut image = Framebuffer::new(width, height);
+            image.buffer = pixels;
+            let name = path.file_stem()?.to_string_lossy().into_owned();
+            Some(Shot { name, image })
+        })
+        .collect()
+}
+
+// `image` scaled to the largest size that fits the box without changing its
+// shape. Each pixel averages the ones it covers, so thumbnails don't shimmer.
+fn fitted(image: &Framebuffer, max_width: usize, max_height: usize) -> Framebuffer {
+    let scale =
+        (max_width as f32 / image.width as f32).min(max_height as f32 / image.height as f32);
+    let width = ((image.width as f32 * scale) as usize).clamp(1, max_width.max(1));
+    let height = ((image.height as f32 * scale) as usize).clamp(1, max_height.max(1));
+
+    let mut scaled = Framebuffer::new(width, height);
+    for y in 0..height {
+        let (y0, y1) = span(y, height, image.height);
+        for x in 0..width {
+            let (x0, x1) = span(x, width, image.width);
+            let mut sum = Vec3::zeros();
+            for source_y in y0..y1 {
+                let row = source_y * image.width;
+                for &pixel in &image.buffer[row + x0..row + x1] {
+                    sum += Color::from_hex(pixel).to_vec3();
+                }
+            }
+            let count = ((y1 - y0) * (x1 - x0)) as f32;
+            scaled.buffer[y * width + x] = Color::from_vec3(sum / count).to_hex();
+        }
+    }
+    scaled
+}
+
+// Source pixels, at least one, that pixel `index` of `scaled` covers
+fn span(index: usize, scaled: usize, source: usize) -> (usize, usize) {
+    let start = index * source / scaled;
+    let end = ((index + 1) * source / scaled).max(start + 1);
+    (start, end.min(source))
+}
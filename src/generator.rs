@@ -0,0 +1,91 @@
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use nalgebra_glm::Vec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlanetKind {
+    Banded,
+    Rocky,
+}
+
+// Each gives the surface a different grain, from soft gradient blobs to
+// blockier value noise
+const NOISE_TYPES: [NoiseType; 5] = [
+    NoiseType::OpenSimplex2,
+    NoiseType::OpenSimplex2S,
+    NoiseType::Perlin,
+    NoiseType::ValueCubic,
+    NoiseType::Value,
+];
+
+// Everything the generated-planet shader needs, all derived from one seed so
+// the same seed always rebuilds the same planet
+pub struct GeneratedPlanet {
+    pub seed: u64,
+    pub kind: PlanetKind,
+    // Dark to light, indexed by band position or elevation
+    pub palette: [Vec3; 4],
+    // Bands per unit of height on banded planets
+    pub band_frequency: f32,
+    // How much of the surface storm ovals cover, 0 to 1
+    pub storm_probability: f32,
+    // Weight of the fine noise octaves against the broad shapes, 0 to 1
+    pub roughness: f32,
+    pub noise: FastNoiseLite,
+}
+
+impl GeneratedPlanet {
+    pub fn from_seed(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let kind = if rng.gen_bool(0.5) {
+            PlanetKind::Banded
+        } else {
+            PlanetKind::Rocky
+        };
+
+        // Neighbouring hues around a random one, getting lighter and less
+        // saturated toward the top of the palette
+        let hue = rng.gen_range(0.0..1.0);
+        let spread = rng.gen_range(0.02..0.12);
+        let saturation = rng.gen_range(0.3..0.85);
+        let palette = [0.0, 1.0, 2.0, 3.0].map(|i: f32| {
+            let t = i / 3.0;
+            hsv_to_rgb(
+                hue + (t - 0.5) * spread * 2.0,
+                saturation * (1.0 - 0.4 * t),
+                0.2 + 0.7 * t,
+            )
+        });
+
+        let mut noise = FastNoiseLite::with_seed(rng.gen());
+        noise.set_noise_type(Some(NOISE_TYPES[rng.gen_range(0..NOISE_TYPES.len())]));
+
+        GeneratedPlanet {
+            seed,
+            kind,
+            palette,
+            band_frequency: rng.gen_range(15.0..60.0),
+            storm_probability: rng.gen_range(0.0..1.0),
+            roughness: rng.gen_range(0.1..1.0),
+            noise,
+        }
+    }
+
+    // Piecewise-linear walk along the palette, `t` from 0 to 1
+    pub fn color_at(&self, t: f32) -> Vec3 {
+        let scaled = t.clamp(0.0, 1.0) * (self.palette.len() - 1) as f32;
+        let index = (scaled as usize).min(self.palette.len() - 2);
+        self.palette[index].lerp(&self.palette[index + 1], scaled - index as f32)
+    }
+}
+
+// Hue wraps around at 1; saturation and value from 0 to 1
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> Vec3 {
+    let channel = |offset: f32| {
+        let k = (hue.rem_euclid(1.0) * 6.0 + offset) % 6.0;
+        value - value * saturation * (k.min(4.0 - k).clamp(0.0, 1.0))
+    };
+    Vec3::new(channel(5.0), channel(3.0), channel(1.0))
+}
@@ -0,0 +1,263 @@
+use crate::cubemap::{Cubemap, REFLECTION_SIZE};
+use crate::dither::Dither;
+use crate::error::{self, Error};
+use crate::framebuffer::Framebuffer;
+use crate::generator::GeneratedPlanet;
+use crate::light::Star;
+use crate::locale::{snake_case, Locale};
+use crate::menu::SHADERS;
+use crate::noise::NoiseRegistry;
+use crate::obj::Obj;
+use crate::png;
+use crate::projection::Projection;
+use crate::season::Orbit;
+use crate::shaders::{AuroraParams, GasGiantParams, OceanParams, ShaderType, ShadingModel};
+use crate::sky::{Sky, NEBULAE};
+use crate::tectonics::Continents;
+use crate::tonemap::ToneMapping;
+use crate::transform::Transform;
+use crate::vertex::Vertex;
+use crate::weather::Weather;
+use crate::{
+    create_perspective_matrix, create_view_matrix, create_viewport_matrix, fill_light, render,
+    sun_direction, DepthMode, Uniforms, Viewport, PLANET_SPIN,
+};
+use nalgebra_glm::{Mat4, Vec3};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+#[cfg(not(feature = "fixed"))]
+pub const GOLDEN_DIR: &str = "assets/golden";
+// The fixed-point rasterizer rounds its weights to 16.16, which is enough to
+// move a band edge or a filtered octave by a pixel here and there, so it's
+// held to images of its own
+#[cfg(feature = "fixed")]
+pub const GOLDEN_DIR: &str = "assets/golden/fixed";
+pub const DIFF_DIR: &str = "golden-diff";
+// Pixels per side of each image
+const SIZE: usize = 64;
+// Far enough into the day for the bands and clouds to have moved off their
+// starting places, and fixed so every run draws the same frame
+const TIME: u32 = 240;
+// Largest difference in any channel a pixel may have from its golden one and
+// still match, for rounding that comes out a step apart
+pub const TOLERANCE: u8 = 2;
+
+// How a rendered image differs from its golden one
+pub struct Difference {
+    // Pixels off by more than TOLERANCE in some channel
+    pub pixels: usize,
+    // Largest difference in any channel of any pixel
+    pub largest: u8,
+    // The golden image darkened, with the pixels that don't match in red,
+    // brighter the more they're off
+    pub image: Vec<u32>,
+}
+
+// Everything the shaders read besides the fragment, built the same way on
+// every run: the same seeds, camera, sun and time
+pub struct Scene {
+    vertex_array: Vec<Vertex>,
+    eye: Vec3,
+    continents: Continents,
+    generated: GeneratedPlanet,
+    noises: NoiseRegistry,
+    sky: Sky,
+    reflections: Cubemap,
+    weather: Weather,
+    orbit: Orbit,
+}
+
+impl Scene {
+    pub fn load() -> error::Result<Self> {
+        let obj = Obj::load("assets/models/sphere.obj")?;
+        let sky = Sky::bake(&NEBULAE[0]);
+        let reflections = Cubemap::from_sky(&sky, REFLECTION_SIZE);
+        Ok(Scene {
+            vertex_array: obj.get_vertex_array(),
+            // A little off the sun's side, so the terminator crosses the disc
+            eye: Vec3::new(1.0, 0.8, 2.4).normalize() * obj.bounding_radius() * 3.0,
+            continents: Continents::generate(5),
+            generated: GeneratedPlanet::from_seed(0),
+            noises: NoiseRegistry::new(),
+            sky,
+            reflections,
+            weather: Weather::new(5),
+            orbit: Orbit {
+                axial_tilt: PLANET_SPIN.axial_tilt,
+                ..Orbit::default()
+            },
+        })
+    }
+
+    // The planet drawn with `shader`, as 0xRRGGBB pixels row by row. Depth
+    // is laid out the usual way and it's all shaded on one thread, as the
+    // golden images were, whatever --reverse-z and --threads say.
+    pub fn render(&self, shader: ShaderType) -> Vec<u32> {
+        let viewport = Viewport::full(SIZE as f32, SIZE as f32);
+        let sun = sun_direction(&self.orbit, TIME);
+        let uniforms = Uniforms {
+            model_matrix: Transform::new(Vec3::zeros(), 1.0, PLANET_SPIN.orientation(TIME))
+                .matrix(),
+            view_matrix: create_view_matrix(self.eye, Vec3::zeros(), Vec3::y()),
+            projection_matrix: create_perspective_matrix(
+                &Projection::default(),
+                viewport.width,
+                viewport.height,
+                false,
+            ),
+            viewport_matrix: create_viewport_matrix(&viewport, false),
+            time: TIME,
+            noise: self.noises.get(shader),
+            aurora: AuroraParams::default(),
+            gas_giant: GasGiantParams::default(),
+            ocean: OceanParams::default(),
+            shading: ShadingModel::Phong,
+            light_dir: sun,
+            star: Star::YellowDwarf,
+            lights: &[fill_light(sun)],
+            light_space_matrix: Mat4::identity(),
+            shadow_map: None,
+            companion: None,
+            extra_octaves: 0,
+            ambient: self.sky.ambient,
+            environment: Some(&self.reflections),
+            particle_stride: 1,
+            weather: self.weather.state(),
+            clouds: None,
+            surface: None,
+            eye: self.eye,
+            season: self.orbit.season(TIME),
+            continents: &self.continents,
+            #[cfg(feature = "physics")]
+            impacts: &[],
+            planet: &self.generated,
+            debug_view: None,
+            threads: 1,
+            reversed_z: false,
+        };
+        let mut framebuffer = Framebuffer::new(SIZE, SIZE);
+        framebuffer.clear();
+        render(
+            &mut framebuffer,
+            &uniforms,
+            &self.vertex_array,
+            &shader,
+            DepthMode::ZBuffer,
+        );
+        framebuffer.resolve(&ToneMapping::default(), Dither::Off);
+        framebuffer.buffer
+    }
+}
+
+// "gas_giant" for ShaderType::GasGiant, as in the locale's keys
+fn key(shader: ShaderType) -> String {
+    snake_case(&format!("{:?}", shader))
+}
+
+pub fn golden_path(shader: ShaderType) -> PathBuf {
+    PathBuf::from(GOLDEN_DIR).join(format!("{}.png", key(shader)))
+}
+
+// The stored image for `shader`, or None if there's none yet or it isn't the
+// size the harness renders at
+pub fn load(shader: ShaderType) -> error::Result<Option<Vec<u32>>> {
+    let path = golden_path(shader);
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::io(path.display().to_string())(err)),
+    };
+    Ok(png::decode(&bytes)
+        .filter(|&(width, height, _)| width == SIZE && height == SIZE)
+        .map(|(_, _, pixels)| pixels))
+}
+
+pub fn compare(golden: &[u32], actual: &[u32]) -> Difference {
+    let mut pixels = 0;
+    let mut largest = 0;
+    let image = golden
+        .iter()
+        .zip(actual)
+        .map(|(&expected, &got)| {
+            let off = [16, 8, 0]
+                .map(|shift| ((expected >> shift) as u8).abs_diff((got >> shift) as u8))
+                .into_iter()
+                .max()
+                .unwrap_or(0);
+            largest = largest.max(off);
+            if off > TOLERANCE {
+                pixels += 1;
+                (off.max(0x40) as u32) << 16
+            } else {
+                (expected >> 2) & 0x3F3F3F
+            }
+        })
+        .collect();
+    Difference {
+        pixels,
+        largest,
+        image,
+    }
+}
+
+// Renders every shader the viewer can pick and checks it against its golden
+// image in GOLDEN_DIR, printing how each came out and saving an image of the
+// pixels that differ into DIFF_DIR for each one that doesn't match. With
+// `update`, the renders are saved as the new golden images instead. Returns
+// whether every shader matched.
+pub fn run(update: bool, locale: &Locale) -> error::Result<bool> {
+    let scene = Scene::load()?;
+    if update {
+        fs::create_dir_all(GOLDEN_DIR).map_err(Error::io(GOLDEN_DIR))?;
+    }
+    let mut passed = true;
+    for shader in SHADERS {
+        let name_key = format!("shader.{}", key(shader));
+        let name = locale.get(&name_key);
+        let actual = scene.render(shader);
+        if update {
+            let path = golden_path(shader);
+            fs::write(&path, png::encode(SIZE, SIZE, &actual))
+                .map_err(Error::io(path.display().to_string()))?;
+            println!(
+                "{}",
+                locale.format("golden.updated", &[&name, &path.display()])
+            );
+            continue;
+        }
+        let Some(golden) = load(shader)? else {
+            passed = false;
+            println!("{}", locale.format("golden.missing", &[&name]));
+            continue;
+        };
+        let difference = compare(&golden, &actual);
+        if difference.pixels == 0 {
+            println!(
+                "{}",
+                locale.format("golden.matched", &[&name, &difference.largest])
+            );
+            continue;
+        }
+        passed = false;
+        fs::create_dir_all(DIFF_DIR).map_err(Error::io(DIFF_DIR))?;
+        let path = PathBuf::from(DIFF_DIR).join(format!("{}.png", key(shader)));
+        fs::write(&path, png::encode(SIZE, SIZE, &difference.image))
+            .map_err(Error::io(path.display().to_string()))?;
+        println!(
+            "{}",
+            locale.format(
+                "golden.differs",
+                &[
+                    &name,
+                    &difference.pixels,
+                    &(SIZE * SIZE),
+                    &difference.largest,
+                    &path.display()
+                ]
+            )
+        );
+    }
+    Ok(passed)
+}
@@ -0,0 +1,261 @@
+use nalgebra_glm::{Vec2, Vec3};
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+// Triangle-only half-edge mesh. Face f owns half-edges 3f, 3f + 1 and 3f + 2,
+// so `next` and the face index are implicit.
+pub struct HalfEdgeMesh {
+    pub positions: Vec<Vec3>,
+    pub half_edges: Vec<HalfEdge>,
+}
+
+pub struct HalfEdge {
+    pub origin: usize,
+    pub twin: Option<usize>,
+    // Texture coordinate of `origin` as seen from this face, so UV seams survive
+    pub texcoord: Vec2,
+}
+
+fn next(half_edge: usize) -> usize {
+    half_edge - half_edge % 3 + (half_edge + 1) % 3
+}
+
+impl HalfEdgeMesh {
+    // `corners` lists (position index, texcoord) per triangle corner
+    pub fn from_triangles(positions: Vec<Vec3>, corners: &[(usize, Vec2)]) -> Self {
+        let mut half_edges: Vec<HalfEdge> = corners
+            .iter()
+            .map(|&(origin, texcoord)| HalfEdge {
+                origin,
+                twin: None,
+                texcoord,
+            })
+            .collect();
+
+        let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+        for index in 0..half_edges.len() {
+            let from = half_edges[index].origin;
+            let to = half_edges[next(index)].origin;
+            if let Some(&twin) = edges.get(&(to, from)) {
+                if half_edges[twin].twin.is_none() {
+                    half_edges[twin].twin = Some(index);
+                    half_edges[index].twin = Some(twin);
+                    continue;
+                }
+            }
+            edges.insert((from, to), index);
+        }
+
+        HalfEdgeMesh {
+            positions,
+            half_edges,
+        }
+    }
+
+    fn destination(&self, half_edge: usize) -> usize {
+        self.half_edges[next(half_edge)].origin
+    }
+
+    // Vertex opposite to the half-edge within its own triangle
+    fn opposite(&self, half_edge: usize) -> usize {
+        self.half_edges[next(next(half_edge))].origin
+    }
+
+    // Loop subdivision: every triangle splits into four, edge points are
+    // weighted 3/8-3/8-1/8-1/8 and original vertices relax toward their ring
+    pub fn loop_subdivide(&self) -> HalfEdgeMesh {
+        let vertex_count = self.positions.len();
+        let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+        let mut boundary: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+
+        for (index, half_edge) in self.half_edges.iter().enumerate() {
+            let (from, to) = (half_edge.origin, self.destination(index));
+            if !neighbours[from].contains(&to) {
+                neighbours[from].push(to);
+            }
+            if !neighbours[to].contains(&from) {
+                neighbours[to].push(from);
+            }
+            if half_edge.twin.is_none() {
+                boundary[from].push(to);
+                boundary[to].push(from);
+            }
+        }
+
+        let mut positions: Vec<Vec3> = (0..vertex_count)
+            .map(|vertex| {
+                let position = self.positions[vertex];
+                if boundary[vertex].len() >= 2 {
+                    let (a, b) = (boundary[vertex][0], boundary[vertex][1]);
+                    return position * 0.75 + (self.positions[a] + self.positions[b]) * 0.125;
+                }
+
+                let n = neighbours[vertex].len();
+                if n == 0 {
+                    return position;
+                }
+                let beta = if n == 3 {
+                    3.0 / 16.0
+                } else {
+                    let cosine = 0.375 + 0.25 * (2.0 * PI / n as f32).cos();
+                    (0.625 - cosine * cosine) / n as f32
+                };
+                let ring: Vec3 = neighbours[vertex].iter().map(|&v| self.positions[v]).sum();
+                position * (1.0 - n as f32 * beta) + ring * beta
+            })
+            .collect();
+
+        let mut edge_points = vec![0; self.half_edges.len()];
+        for index in 0..self.half_edges.len() {
+            let twin = self.half_edges[index].twin;
+            if let Some(twin) = twin {
+                if twin < index {
+                    edge_points[index] = edge_points[twin];
+                    continue;
+                }
+            }
+
+            let a = self.positions[self.half_edges[index].origin];
+            let b = self.positions[self.destination(index)];
+            let point = match twin {
+                Some(twin) => {
+                    let c = self.positions[self.opposite(index)];
+                    let d = self.positions[self.opposite(twin)];
+                    (a + b) * 0.375 + (c + d) * 0.125
+                }
+                None => (a + b) * 0.5,
+            };
+            edge_points[index] = positions.len();
+            positions.push(point);
+        }
+
+        let mut corners = Vec::with_capacity(self.half_edges.len() * 4);
+        for face in 0..self.half_edges.len() / 3 {
+            let [h0, h1, h2] = [face * 3, face * 3 + 1, face * 3 + 2];
+            let [a, b, c] = [h0, h1, h2].map(|h| self.half_edges[h].origin);
+            let [ua, ub, uc] = [h0, h1, h2].map(|h| self.half_edges[h].texcoord);
+            let (ab, bc, ca) = (edge_points[h0], edge_points[h1], edge_points[h2]);
+            let (uab, ubc, uca) = ((ua + ub) * 0.5, (ub + uc) * 0.5, (uc + ua) * 0.5);
+
+            corners.extend([(a, ua), (ab, uab), (ca, uca)]);
+            corners.extend([(b, ub), (bc, ubc), (ab, uab)]);
+            corners.extend([(c, uc), (ca, uca), (bc, ubc)]);
+            corners.extend([(ab, uab), (bc, ubc), (ca, uca)]);
+        }
+
+        HalfEdgeMesh::from_triangles(positions, &corners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A closed tetrahedron, every face wound outward
+    fn tetrahedron() -> HalfEdgeMesh {
+        let positions = vec![
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(1.0, -1.0, -1.0),
+            Vec3::new(-1.0, 1.0, -1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+        ];
+        let faces = [[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]];
+        let corners: Vec<(usize, Vec2)> = faces
+            .iter()
+            .flatten()
+            .map(|&vertex| (vertex, Vec2::zeros()))
+            .collect();
+        HalfEdgeMesh::from_triangles(positions, &corners)
+    }
+
+    fn assert_close(actual: Vec3, expected: Vec3) {
+        assert!(
+            (actual - expected).magnitude() < 1e-5,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn closed_meshes_pair_every_half_edge() {
+        let mesh = tetrahedron();
+        for (index, half_edge) in mesh.half_edges.iter().enumerate() {
+            let twin = half_edge.twin.expect("a closed mesh has no boundary");
+            assert_eq!(mesh.half_edges[twin].twin, Some(index));
+            assert_eq!(mesh.half_edges[twin].origin, mesh.destination(index));
+            assert_eq!(mesh.destination(twin), half_edge.origin);
+        }
+    }
+
+    #[test]
+    fn subdividing_keeps_the_surface_closed() {
+        let mut mesh = tetrahedron();
+        for level in 1..=3 {
+            mesh = mesh.loop_subdivide();
+            let faces = mesh.half_edges.len() / 3;
+            assert_eq!(faces, 4 * 4usize.pow(level));
+            // V - E + F = 2, with each edge shared by two faces
+            let edges = faces * 3 / 2;
+            assert_eq!(mesh.positions.len() + faces, edges + 2);
+            assert!(mesh
+                .half_edges
+                .iter()
+                .all(|half_edge| half_edge.twin.is_some()));
+        }
+    }
+
+    #[test]
+    fn loop_weights() {
+        let mesh = tetrahedron();
+        let subdivided = mesh.loop_subdivide();
+        let p = &mesh.positions;
+
+        // Valence 3 relaxes by 3/16 of each neighbour
+        let ring = p[1] + p[2] + p[3];
+        assert_close(
+            subdivided.positions[0],
+            p[0] * (1.0 - 9.0 / 16.0) + ring * (3.0 / 16.0),
+        );
+
+        // The point on the edge from 0 to 1 lies between faces 0 1 2 and 0 3 1
+        let edge_point = (p[0] + p[1]) * 0.375 + (p[2] + p[3]) * 0.125;
+        assert!(subdivided.positions[4..]
+            .iter()
+            .any(|&point| (point - edge_point).magnitude() < 1e-5));
+    }
+
+    #[test]
+    fn boundaries_follow_their_own_curve() {
+        let positions = vec![Vec3::zeros(), Vec3::x(), Vec3::y()];
+        let corners = [
+            (0, Vec2::new(0.0, 0.0)),
+            (1, Vec2::new(1.0, 0.0)),
+            (2, Vec2::new(0.0, 1.0)),
+        ];
+        let mesh = HalfEdgeMesh::from_triangles(positions, &corners);
+        assert!(mesh
+            .half_edges
+            .iter()
+            .all(|half_edge| half_edge.twin.is_none()));
+
+        let subdivided = mesh.loop_subdivide();
+        assert_eq!(subdivided.half_edges.len(), 12);
+        // Corners move 1/8 of the way toward each boundary neighbour
+        assert_close(subdivided.positions[0], Vec3::new(0.125, 0.125, 0.0));
+        // Boundary edges split at their midpoints
+        let midpoints = [Vec3::new(0.5, 0.0, 0.0), Vec3::new(0.5, 0.5, 0.0)];
+        for midpoint in midpoints {
+            assert!(subdivided.positions[3..]
+                .iter()
+                .any(|&point| (point - midpoint).magnitude() < 1e-5));
+        }
+        // Each new corner's texcoord is halfway along its edge, as the face saw it
+        for half_edge in &subdivided.half_edges {
+            let position = subdivided.positions[half_edge.origin];
+            if half_edge.origin >= 3 {
+                assert!((half_edge.texcoord - position.xy()).magnitude() < 1e-5);
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::locale::Locale;
+
+// Every control the window answers to, by section. Each shows the text
+// under `help.<name>.keys` beside what it does from `help.<name>`, so a
+// language can name the keys its own way.
+const SECTIONS: [(&str, &[&str]); 7] = [
+    (
+        "help.camera",
+        &[
+            "orbit",
+            "move",
+            "zoom",
+            "save_camera",
+            "recall_camera",
+            "focus",
+            "focus_order",
+            "barycenter_camera",
+            "telescope",
+            "field_of_view",
+            "clip_planes",
+            "ship_hud",
+        ],
+    ),
+    (
+        "help.planet",
+        &[
+            "shaders",
+            "shader_menu",
+            "generate",
+            "exact_sphere",
+            "impostor",
+            "debug_view",
+            "fog",
+        ],
+    ),
+    (
+        "help.bodies",
+        &[
+            "select",
+            "select_order",
+            "asteroid",
+            "comet",
+            "belt",
+            "trails",
+            "spacecraft",
+            "physics",
+            "strike",
+            "n_body",
+            "labels",
+            "flash",
+            "measure",
+            "barycenters",
+            "ecliptic",
+        ],
+    ),
+    (
+        "help.views",
+        &[
+            "split",
+            "compare",
+            "inset",
+            "minimap",
+            "anaglyph",
+            "side_by_side",
+            "depth",
+            "profiler",
+            "retro",
+            "shading",
+        ],
+    ),
+    ("help.time", &["timeline", "timeline_step", "transit"]),
+    (
+        "help.image",
+        &[
+            "effects",
+            "motion_blur",
+            "occlusion",
+            "dither",
+            "tone_mapping",
+            "exposure",
+            "exposure_preset",
+            "starlight",
+            "nebula",
+            "resolution",
+            "smoothing",
+            "doppler",
+            "doppler_speeds",
+        ],
+    ),
+    (
+        "help.window",
+        &[
+            "fullscreen",
+            "gallery",
+            "screenshot",
+            "accumulation",
+            "help",
+            "quit",
+        ],
+    ),
+];
+// Sections in the left column; the rest go in the right one
+const LEFT_SECTIONS: usize = 3;
+
+const MARGIN: usize = 16;
+const PADDING: usize = 8;
+const LINE: usize = GLYPH_HEIGHT + 3;
+// Room for the longest keys in either language before what they do
+const KEYS_WIDTH: usize = 96;
+const BACKGROUND: u32 = 0x101018;
+const BORDER: u32 = 0x606078;
+const HEADING: u32 = 0xFFD700;
+const KEYS: u32 = 0x80C0FF;
+
+// Whether this build answers to the control, since physics can be left out
+fn available(name: &str) -> bool {
+    !matches!(name, "physics" | "strike" | "n_body") || cfg!(feature = "physics")
+}
+
+// One line per control in two columns over the resolved frame, for learning
+// them without reading the source. The scene keeps running behind it.
+pub fn draw(framebuffer: &mut Framebuffer, locale: &Locale) {
+    let (left, right) = SECTIONS.split_at(LEFT_SECTIONS);
+    let rows = column_rows(left).max(column_rows(right));
+    let width = framebuffer.width.saturating_sub(MARGIN * 2);
+    let height = (PADDING * 2 + LINE * (rows + 2)).min(framebuffer.height.saturating_sub(2));
+    let panel = Rect {
+        x: (framebuffer.width - width) / 2,
+        y: (framebuffer.height - height) / 2,
+        width,
+        height,
+    };
+    framebuffer.reset_scissor();
+    framebuffer.set_current_color(BORDER);
+    framebuffer.fill_rect(Rect {
+        x: panel.x - 1,
+        y: panel.y - 1,
+        width: panel.width + 2,
+        height: panel.height + 2,
+    });
+    framebuffer.set_current_color(BACKGROUND);
+    framebuffer.fill_rect(panel);
+
+    let top = panel.y + PADDING;
+    framebuffer.set_current_color(0xFFFFFF);
+    framebuffer.draw_text(
+        (panel.x + PADDING) as i32,
+        top as i32,
+        locale.get("help.title"),
+        1,
+    );
+    let close = locale.get("help.close");
+    let close_x = (panel.x + panel.width).saturating_sub(PADDING + text_width(close, 1));
+    framebuffer.draw_text(close_x as i32, top as i32, close, 1);
+
+    let column_width = (panel.width - PADDING) / 2;
+    for (index, sections) in [left, right].into_iter().enumerate() {
+        let x = panel.x + PADDING + index * column_width;
+        draw_column(framebuffer, locale, sections, x, top + LINE * 2);
+    }
+}
+
+fn draw_column(
+    framebuffer: &mut Framebuffer,
+    locale: &Locale,
+    sections: &[(&str, &[&str])],
+    x: usize,
+    mut y: usize,
+) {
+    for &(heading, names) in sections {
+        framebuffer.set_current_color(HEADING);
+        framebuffer.draw_text(x as i32, y as i32, locale.get(heading), 1);
+        y += LINE;
+        for &name in names.iter().filter(|&&name| available(name)) {
+            let action = format!("help.{}", name);
+            let keys = format!("{}.keys", action);
+            framebuffer.set_current_color(KEYS);
+            framebuffer.draw_text(x as i32, y as i32, locale.get(&keys), 1);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text((x + KEYS_WIDTH) as i32, y as i32, locale.get(&action), 1);
+            y += LINE;
+        }
+        y += LINE;
+    }
+}
+
+// Lines a column takes, a heading and a gap after each section
+fn column_rows(sections: &[(&str, &[&str])]) -> usize {
+    sections
+        .iter()
+        .map(|(_, names)| names.iter().filter(|&&name| available(name)).count() + 2)
+        .sum()
+}
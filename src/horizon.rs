@@ -0,0 +1,177 @@
+use crate::vertex::Vertex;
+use nalgebra_glm::{Mat4, Vec3};
+use std::borrow::Cow;
+
+// Patches along each side of the cube the mesh's triangles are sorted onto
+// by direction, so there are six times this squared
+const GRID: usize = 4;
+
+// A run of the mesh's triangles that all point about the same way from its
+// center, with a cone from the center that holds every vertex
+struct Patch {
+    start: usize,
+    end: usize,
+    axis: Vec3,
+    // Widest angle between the axis and a vertex, in radians
+    spread: f32,
+    // Farthest any vertex is from the center
+    max_radius: f32,
+}
+
+// A closed mesh around its origin split into patches, so the ones hidden
+// behind the body's own horizon can be left out before any of their
+// vertices are transformed. From far away that's the back half; from just
+// over the surface it's nearly all of it. Nothing in the mesh dips below
+// `occluder` from the center, so that sphere is solid and anything it hides
+// from the eye is hidden by the mesh too, however the surface is displaced.
+pub struct Patches {
+    vertices: Vec<Vertex>,
+    patches: Vec<Patch>,
+    occluder: f32,
+}
+
+impl Patches {
+    pub fn build(vertex_array: &[Vertex]) -> Self {
+        let mut cells: Vec<Vec<&[Vertex]>> = vec![Vec::new(); 6 * GRID * GRID];
+        let mut occluder = f32::INFINITY;
+        for triangle in vertex_array.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| triangle[corner].position);
+            let centroid = (a + b + c) / 3.0;
+            cells[cell(&centroid)].push(triangle);
+
+            // A flat triangle comes nearest the center somewhere inside, no
+            // closer than its plane does
+            let normal = (b - a).cross(&(c - a));
+            if normal.magnitude() > f32::EPSILON {
+                occluder = occluder.min(normal.normalize().dot(&a).abs());
+            }
+        }
+
+        let mut vertices = Vec::with_capacity(vertex_array.len());
+        let mut patches = Vec::new();
+        for cell in cells.into_iter().filter(|cell| !cell.is_empty()) {
+            let start = vertices.len();
+            vertices.extend(cell.iter().flat_map(|triangle| triangle.iter().cloned()));
+            let patch = &vertices[start..];
+            let sum: Vec3 = patch.iter().map(|vertex| vertex.position).sum();
+            let axis = if sum.magnitude() > f32::EPSILON {
+                sum.normalize()
+            } else {
+                Vec3::y()
+            };
+            let spread = patch
+                .iter()
+                .map(|vertex| angle(&axis, &vertex.position))
+                .fold(0.0, f32::max);
+            let max_radius = patch
+                .iter()
+                .map(|vertex| vertex.position.magnitude())
+                .fold(0.0, f32::max);
+            patches.push(Patch {
+                start,
+                end: vertices.len(),
+                axis,
+                spread,
+                max_radius,
+            });
+        }
+
+        Patches {
+            vertices,
+            patches,
+            occluder: if occluder.is_finite() { occluder } else { 0.0 },
+        }
+    }
+
+    // The triangles that could show from `eye` with the mesh placed by
+    // `model_matrix`
+    pub fn visible(&self, model_matrix: &Mat4, eye: Vec3) -> Cow<'_, [Vertex]> {
+        let Some(inverse) = model_matrix.try_inverse() else {
+            return Cow::Borrowed(&self.vertices);
+        };
+        let eye = (inverse * eye.push(1.0)).xyz();
+        let shown: Vec<&Patch> = self
+            .patches
+            .iter()
+            .filter(|patch| {
+                !behind_horizon(
+                    self.occluder,
+                    &eye,
+                    &patch.axis,
+                    patch.spread,
+                    patch.max_radius,
+                )
+            })
+            .collect();
+        if shown.len() == self.patches.len() {
+            return Cow::Borrowed(&self.vertices);
+        }
+        Cow::Owned(
+            shown
+                .iter()
+                .flat_map(|patch| self.vertices[patch.start..patch.end].iter().cloned())
+                .collect(),
+        )
+    }
+}
+
+// Whether everything within `spread` of `axis` out to `max_radius` from the
+// center is hidden from `eye` by a solid sphere of radius `occluder` there.
+// Seen from distance d, a point at radius r is behind it once it's more
+// than acos(occluder / d) + acos(occluder / r) around from the eye, the
+// angles to the eye's horizon and its own, so the cone is hidden once its
+// near edge is past that.
+pub fn behind_horizon(
+    occluder: f32,
+    eye: &Vec3,
+    axis: &Vec3,
+    spread: f32,
+    max_radius: f32,
+) -> bool {
+    let distance = eye.magnitude();
+    if distance <= occluder || occluder <= 0.0 {
+        return false;
+    }
+    let eye_horizon = (occluder / distance).acos();
+    let own_horizon = (occluder / max_radius).min(1.0).acos();
+    angle(axis, eye) - spread > eye_horizon + own_horizon
+}
+
+// Which patch of the cube a direction falls in
+fn cell(direction: &Vec3) -> usize {
+    let magnitude = direction.abs();
+    let (face, u, v, major) = if magnitude.x >= magnitude.y && magnitude.x >= magnitude.z {
+        (
+            usize::from(direction.x < 0.0),
+            direction.y,
+            direction.z,
+            magnitude.x,
+        )
+    } else if magnitude.y >= magnitude.z {
+        (
+            2 + usize::from(direction.y < 0.0),
+            direction.x,
+            direction.z,
+            magnitude.y,
+        )
+    } else {
+        (
+            4 + usize::from(direction.z < 0.0),
+            direction.x,
+            direction.y,
+            magnitude.z,
+        )
+    };
+    let major = major.max(f32::EPSILON);
+    let index =
+        |coordinate: f32| (((coordinate / major + 1.0) / 2.0 * GRID as f32) as usize).min(GRID - 1);
+    (face * GRID + index(u)) * GRID + index(v)
+}
+
+fn angle(a: &Vec3, b: &Vec3) -> f32 {
+    let lengths = a.magnitude() * b.magnitude();
+    if lengths <= f32::EPSILON {
+        return 0.0;
+    }
+    (a.dot(b) / lengths).clamp(-1.0, 1.0).acos()
+}
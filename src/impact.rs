@@ -0,0 +1,250 @@
+use crate::framebuffer::Framebuffer;
+use crate::particles::{Blend, ColorRamp, ParticleStyle, Particles};
+use crate::picking::{intersect_sphere, Ray};
+use crate::{Uniforms, GRAVITY};
+use nalgebra_glm::{Mat4, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const FLASH_FRAMES: u32 = 90;
+const GLOW_FRAMES: u32 = 600;
+const SCORCH_FRAMES: u32 = 1800;
+const DEBRIS_PER_IMPACT: usize = 48;
+// How far out a strike starts, from the planet's center, and how fast it
+// comes in, in world units and world units a frame
+const STRIKE_DISTANCE: f32 = 6.0;
+const STRIKE_SPEED: f32 = 0.06;
+// Sparks a striking rock sheds each frame on its way in
+const SPARKS_PER_FRAME: usize = 3;
+
+// Single pixels cooling from orange to dull red
+const DEBRIS: ParticleStyle = ParticleStyle {
+    lifetime: (40, 120),
+    color: ColorRamp {
+        start: Vec3::new(3.3, 1.25, 0.3),
+        end: Vec3::new(0.3, 0.05, 0.0),
+        falloff: 1.0,
+    },
+    blend: Blend::Translucent,
+    size: 0.0,
+};
+
+// Glowing specks left behind a striking rock, short-lived so they draw out
+// a streak
+const SPARKS: ParticleStyle = ParticleStyle {
+    lifetime: (8, 24),
+    color: ColorRamp {
+        start: Vec3::new(4.0, 2.6, 1.2),
+        end: Vec3::new(0.4, 0.08, 0.0),
+        falloff: 0.7,
+    },
+    blend: Blend::Additive,
+    size: 0.0,
+};
+
+// A rock knocked out of the belt, falling freely until it hits the planet
+#[derive(Clone)]
+pub struct Impactor {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub rotation: Vec3,
+    pub scale: f32,
+    // Bounding radius at this scale, used for the collision test
+    pub radius: f32,
+    pub seed: u32,
+    spin: Vec3,
+    // Sent in by `strike`, so it burns up a trail of sparks
+    streaking: bool,
+}
+
+// Where a rock struck, as a unit direction in the planet's model space so the
+// mark turns with the surface
+#[derive(Clone, Copy)]
+pub struct ImpactMark {
+    pub direction: Vec3,
+    // Angular radius of the scorch in radians; the flash spreads half again wider
+    pub radius: f32,
+    age: u32,
+}
+
+impl ImpactMark {
+    // White-hot at the moment of impact, gone after FLASH_FRAMES
+    pub fn flash(&self) -> f32 {
+        let t = 1.0 - self.age as f32 / FLASH_FRAMES as f32;
+        t.max(0.0).powi(3)
+    }
+
+    // The molten floor of the crater, left glowing once the flash is gone
+    // and cooling over GLOW_FRAMES
+    pub fn glow(&self) -> f32 {
+        let t = 1.0 - self.age as f32 / GLOW_FRAMES as f32;
+        t.max(0.0).powi(2)
+    }
+
+    // Dark mark that stays after the flash and slowly weathers away
+    pub fn scorch(&self) -> f32 {
+        1.0 - self.age as f32 / SCORCH_FRAMES as f32
+    }
+}
+
+// Everything physics mode adds to the scene: rocks in flight, the marks they
+// leave and the debris thrown up where they land
+#[derive(Clone)]
+pub struct Impacts {
+    pub impactors: Vec<Impactor>,
+    pub marks: Vec<ImpactMark>,
+    debris: Particles,
+    sparks: Particles,
+    rng: StdRng,
+}
+
+impl Impacts {
+    pub fn new(seed: u64) -> Self {
+        Impacts {
+            impactors: Vec::new(),
+            marks: Vec::new(),
+            debris: Particles::new(DEBRIS, seed + 1),
+            sparks: Particles::new(SPARKS, seed + 2),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn launch(
+        &mut self,
+        position: Vec3,
+        velocity: Vec3,
+        rotation: Vec3,
+        scale: f32,
+        radius: f32,
+        seed: u32,
+    ) {
+        let spin = Vec3::new(
+            self.rng.gen_range(-0.05..0.05),
+            self.rng.gen_range(-0.05..0.05),
+            self.rng.gen_range(-0.05..0.05),
+        );
+        self.impactors.push(Impactor {
+            position,
+            velocity,
+            rotation,
+            scale,
+            radius,
+            seed,
+            spin,
+            streaking: false,
+        });
+    }
+
+    // Sends a rock of `scale`, `radius` across, streaking in at the planet
+    // from somewhere well out past the moons, aimed at a spot on the side it
+    // comes from. It falls like any other, and sheds sparks the whole way.
+    pub fn strike(&mut self, planet_center: Vec3, planet_radius: f32, scale: f32, radius: f32) {
+        let mut direction = || {
+            Vec3::new(
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+            )
+            .try_normalize(1e-3)
+            .unwrap_or(Vec3::y())
+        };
+        let from = direction();
+        let aim = planet_center + (from + direction() * 0.5).normalize() * planet_radius * 0.8;
+        let position = planet_center + from * STRIKE_DISTANCE;
+        let velocity = (aim - position).normalize() * STRIKE_SPEED;
+        let rotation = direction();
+        let seed = self.rng.gen();
+        self.launch(position, velocity, rotation, scale, radius, seed);
+        if let Some(rock) = self.impactors.last_mut() {
+            rock.streaking = true;
+        }
+    }
+
+    // Advances one frame. A rock whose step crosses the planet's surface is
+    // removed and leaves a mark and a spray of debris at the contact point.
+    pub fn update(&mut self, planet_center: Vec3, planet_radius: f32, planet_model: &Mat4) {
+        let to_model = planet_model.try_inverse().unwrap_or(Mat4::identity());
+        let gravity = |position: Vec3| {
+            let offset = planet_center - position;
+            offset * (GRAVITY / offset.magnitude().powi(3))
+        };
+
+        let mut hits = Vec::new();
+        self.impactors.retain_mut(|rock| {
+            rock.velocity += gravity(rock.position);
+            let step = rock.velocity.magnitude();
+            let ray = Ray {
+                origin: rock.position,
+                direction: rock.velocity / step,
+            };
+            let hit = intersect_sphere(&ray, planet_center, planet_radius + rock.radius)
+                .filter(|&t| t <= step);
+
+            if rock.streaking {
+                for _ in 0..SPARKS_PER_FRAME {
+                    let spread = Vec3::new(
+                        self.rng.gen_range(-1.0..1.0),
+                        self.rng.gen_range(-1.0..1.0),
+                        self.rng.gen_range(-1.0..1.0),
+                    );
+                    let along = self.rng.gen_range(0.0..1.0);
+                    self.sparks.spawn(
+                        rock.position + rock.velocity * along,
+                        rock.velocity * 0.1 + spread * 0.002,
+                    );
+                }
+            }
+            rock.position += rock.velocity;
+            rock.rotation += rock.spin;
+            match hit {
+                Some(t) => {
+                    let normal = (ray.origin + ray.direction * t - planet_center).normalize();
+                    hits.push((normal, rock.radius));
+                    false
+                }
+                None => true,
+            }
+        });
+
+        for (normal, rock_radius) in hits {
+            let direction = (to_model * normal.push(0.0)).xyz().normalize();
+            self.marks.push(ImpactMark {
+                direction,
+                radius: rock_radius * 1.5 / planet_radius,
+                age: 0,
+            });
+
+            // Thrown out in a cone around the surface normal, fast enough to
+            // arc well clear before falling back
+            let contact = planet_center + normal * planet_radius;
+            for _ in 0..DEBRIS_PER_IMPACT {
+                let spread = Vec3::new(
+                    self.rng.gen_range(-1.0..1.0),
+                    self.rng.gen_range(-1.0..1.0),
+                    self.rng.gen_range(-1.0..1.0),
+                );
+                let speed = self.rng.gen_range(0.015..0.05);
+                self.debris
+                    .spawn(contact, (normal + spread * 0.7).normalize() * speed);
+            }
+        }
+
+        for mark in &mut self.marks {
+            mark.age += 1;
+        }
+        self.marks.retain(|mark| mark.age < SCORCH_FRAMES);
+
+        self.debris.update(gravity, |position| {
+            (position - planet_center).magnitude() > planet_radius
+        });
+        self.sparks.update(
+            |_| Vec3::zeros(),
+            |position| (position - planet_center).magnitude() > planet_radius,
+        );
+    }
+
+    pub fn draw_debris(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        self.debris.draw(framebuffer, uniforms);
+        self.sparks.draw(framebuffer, uniforms);
+    }
+}
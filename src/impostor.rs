@@ -0,0 +1,306 @@
+use crate::budget;
+use crate::color::Color;
+use crate::fragment::Fragment;
+use crate::framebuffer::{Framebuffer, RowBand};
+use crate::instance::Instance;
+use crate::material::Material;
+use crate::picking::{intersect_sphere, Ray};
+use crate::procedural::sphere_tex_coords;
+use crate::profiler::{self, Stage, Stopwatch};
+use crate::shaders::{fragment_shader, ShaderType};
+use crate::silhouette::projected_circle;
+use crate::{depth_planes, DepthMode, Uniforms};
+use nalgebra_glm::{mat4_to_mat3, Mat3, Mat4, Vec3, Vec4};
+use std::sync::atomic::Ordering;
+
+// Pixels added around the projected disk, since a sphere off to the side of
+// the view projects to an ellipse a little wider than the circle
+const MARGIN: f32 = 2.0;
+// Smallest slope between a pixel's ray and the surface the derivatives are
+// taken at, so at the silhouette they come out large instead of infinite
+const MIN_SLOPE: f32 = 1e-3;
+// Radius on screen, in pixels, below which a body is drawn by `draw_disk`
+// instead of its mesh
+pub const DISK_PIXELS: f32 = 3.0;
+// Radians off the points facing toward and away from the sun at which
+// `draw_disk` takes its other samples
+const SPREAD: f32 = 0.9;
+
+// Draws the sphere of `radius` about the origin of `model_matrix` without a
+// mesh: every pixel of its projected disk casts a ray that's intersected
+// with the sphere exactly, and the hit is shaded as if the rasterizer had
+// interpolated it there. The silhouette is round at any size and there are
+// no vertices to transform, only the pixels it covers.
+pub fn draw_sphere(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    model_matrix: &Mat4,
+    radius: f32,
+    shader: &ShaderType,
+    tint: Option<Mat3>,
+    depth_mode: DepthMode,
+) {
+    let Some(inverse_model) = model_matrix.try_inverse() else {
+        return;
+    };
+    let eye = (inverse_model * uniforms.eye.push(1.0)).xyz();
+    if eye.magnitude() <= radius {
+        return;
+    }
+    let transform = uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+    let Some(unproject) = (transform * model_matrix).try_inverse() else {
+        return;
+    };
+    let (near_depth, far_depth) = depth_planes(&uniforms.viewport_matrix, uniforms.reversed_z);
+    // Rays through pixels in the sphere's own space, from the near plane
+    let ray = |x: f32, y: f32| {
+        let near = unproject * Vec4::new(x, y, near_depth, 1.0);
+        let far = unproject * Vec4::new(x, y, far_depth, 1.0);
+        let (near, far) = (near.xyz() / near.w, far.xyz() / far.w);
+        Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        }
+    };
+
+    // With the center behind the eye the disk can't be bounded this way,
+    // but a sphere the eye is outside of can still reach into the view, so
+    // every pixel is tried
+    let scissor = framebuffer.scissor();
+    let center = model_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0);
+    let world_radius = (model_matrix * Vec4::new(radius, 0.0, 0.0, 0.0)).magnitude();
+    let (min_x, min_y, max_x, max_y) = match projected_circle(
+        center,
+        world_radius,
+        &uniforms.view_matrix,
+        &uniforms.projection_matrix,
+        &uniforms.viewport_matrix,
+    ) {
+        Some((center, pixels)) => {
+            let reach = pixels + MARGIN;
+            (
+                ((center.x - reach).floor().max(0.0) as usize).max(scissor.min_x),
+                ((center.y - reach).floor().max(0.0) as usize).max(scissor.min_y),
+                ((center.x + reach).ceil().max(0.0) as usize).min(scissor.max_x),
+                ((center.y + reach).ceil().max(0.0) as usize).min(scissor.max_y),
+            )
+        }
+        None => (scissor.min_x, scissor.min_y, scissor.max_x, scissor.max_y),
+    };
+    if min_x >= max_x || min_y >= max_y {
+        return;
+    }
+
+    let normal_matrix = mat4_to_mat3(model_matrix)
+        .transpose()
+        .try_inverse()
+        .unwrap_or(Mat3::identity());
+    let material = Material::for_shader(shader);
+    let split_x = framebuffer.width / 2;
+    let workers = uniforms.threads.max(1);
+
+    let shade_band = |band: &mut RowBand| {
+        let mut shaded = 0;
+        // Casting the rays and writing the pixels counts as rasterizing
+        let (mut band_time, mut shading) = (Stopwatch::new(), Stopwatch::new());
+        band_time.time(|| {
+            let rows = min_y.max(band.scissor.min_y)..max_y.min(band.scissor.max_y);
+            for y in rows {
+                for x in min_x..max_x {
+                    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                    let pixel = ray(px, py);
+                    let Some(distance) = intersect_sphere(&pixel, Vec3::zeros(), radius) else {
+                        continue;
+                    };
+                    let position = pixel.origin + pixel.direction * distance;
+                    let screen = transform * model_matrix * position.push(1.0);
+                    let depth = screen.z / screen.w;
+                    if depth > far_depth {
+                        continue;
+                    }
+
+                    // Where the neighbouring pixels' rays cross the plane
+                    // touching the sphere here, as a triangle's derivatives
+                    // would have come out across it
+                    let normal = position / radius;
+                    let across = |neighbour: Ray| {
+                        let slope = (-normal.dot(&neighbour.direction)).max(MIN_SLOPE);
+                        let along = normal.dot(&(neighbour.origin - position)) / slope;
+                        neighbour.origin + neighbour.direction * along - position
+                    };
+
+                    let fragment = Fragment {
+                        world_position: (model_matrix * position.push(1.0)).xyz(),
+                        tex_coords: sphere_tex_coords(&normal),
+                        material,
+                        dpdx: across(ray(px + 1.0, py)),
+                        dpdy: across(ray(px, py + 1.0)),
+                        ..Fragment::new(
+                            Color::black(),
+                            (normal_matrix * normal).normalize(),
+                            position,
+                        )
+                    };
+                    let mut radiance =
+                        shading.time(|| fragment_shader(&fragment, uniforms, shader));
+                    if let Some(tint) = &tint {
+                        radiance = tint * radiance;
+                    }
+                    shaded += 1;
+
+                    if depth_mode.painter(x, split_x) {
+                        band.set_radiance(x, y, radiance);
+                    } else {
+                        band.point(x, y, depth, radiance);
+                    }
+                }
+            }
+        });
+        profiler::add(Stage::Fragment, shading.elapsed());
+        let rasterizing = band_time.elapsed().saturating_sub(shading.elapsed());
+        profiler::add(Stage::Raster, rasterizing);
+        budget::FRAGMENTS.fetch_add(shaded, Ordering::Relaxed);
+    };
+
+    let mut bands = framebuffer.row_bands(workers);
+    if let [band] = bands.as_mut_slice() {
+        shade_band(band);
+    } else {
+        let shade_band = &shade_band;
+        std::thread::scope(|scope| {
+            for mut band in bands {
+                scope.spawn(move || shade_band(&mut band));
+            }
+        });
+    }
+}
+
+// Draws the sphere of `radius` about the origin of `instance`'s model matrix
+// as a disk, for a body too small on screen for its surface to show. The
+// shader only runs a few times around the points facing toward and away
+// from the sun, with the pixel made as wide as the body so its detail
+// averages out, and each pixel of the disk blends between the two sides by
+// how squarely its normal faces the sun. Depth is the sphere's own, so it hides and is hidden as the mesh
+// would be.
+pub fn draw_disk(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    instance: &Instance,
+    radius: f32,
+    shader: &ShaderType,
+    depth_mode: DepthMode,
+) {
+    let model_matrix = &instance.model_matrix;
+    let center = model_matrix * Vec4::new(0.0, 0.0, 0.0, 1.0);
+    let world_radius = (model_matrix * Vec4::new(radius, 0.0, 0.0, 0.0)).magnitude();
+    let Some((disk_center, pixels)) = projected_circle(
+        center,
+        world_radius,
+        &uniforms.view_matrix,
+        &uniforms.projection_matrix,
+        &uniforms.viewport_matrix,
+    ) else {
+        return;
+    };
+    let Some(inverse_model) = model_matrix.try_inverse() else {
+        return;
+    };
+    let normal_matrix = mat4_to_mat3(model_matrix)
+        .transpose()
+        .try_inverse()
+        .unwrap_or(Mat3::identity());
+    let material = instance
+        .material
+        .unwrap_or_else(|| Material::for_shader(shader));
+    let shade = |world_normal: Vec3| {
+        let normal = (inverse_model * world_normal.push(0.0)).xyz().normalize();
+        let position = normal * radius;
+        let (tangent, bitangent) = tangents(normal);
+        let fragment = Fragment {
+            world_position: center.xyz() + world_normal * world_radius,
+            tex_coords: sphere_tex_coords(&normal),
+            instance_seed: instance.seed,
+            material,
+            dpdx: tangent * radius,
+            dpdy: bitangent * radius,
+            ..Fragment::new(
+                Color::black(),
+                (normal_matrix * normal).normalize(),
+                position,
+            )
+        };
+        let radiance = fragment_shader(&fragment, uniforms, shader);
+        instance.tint.map_or(radiance, |tint| tint * radiance)
+    };
+    // The middle of five samples around `pole`, channel by channel, so a
+    // city light or a bright crater under one of them doesn't color the
+    // whole side
+    let side = |pole: Vec3| {
+        let (tangent, bitangent) = tangents(pole);
+        let (sin, cos) = SPREAD.sin_cos();
+        let samples = [Vec3::zeros(), tangent, -tangent, bitangent, -bitangent]
+            .map(|offset| shade((pole * cos + offset * sin).normalize()));
+        Vec3::from_fn(|channel, _| {
+            let mut values = samples.map(|sample| sample[channel]);
+            values.sort_by(f32::total_cmp);
+            values[2]
+        })
+    };
+    let sun = uniforms.light_dir.normalize();
+    let (day, night) = (side(sun), side(-sun));
+    budget::FRAGMENTS.fetch_add(10, Ordering::Relaxed);
+
+    // The camera's axes in the world, with screen x and y turned to run the
+    // way the viewport lays them out
+    let view = &uniforms.view_matrix;
+    let axis = |row: usize| Vec3::new(view[(row, 0)], view[(row, 1)], view[(row, 2)]);
+    let right = axis(0) * uniforms.viewport_matrix[(0, 0)].signum();
+    let up = axis(1) * uniforms.viewport_matrix[(1, 1)].signum();
+    let toward_eye = axis(2);
+    let transform = uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+    let (_, far_depth) = depth_planes(&uniforms.viewport_matrix, uniforms.reversed_z);
+    let split_x = framebuffer.width / 2;
+
+    let scissor = framebuffer.scissor();
+    let reach = pixels + 1.0;
+    let min_x = ((disk_center.x - reach).floor().max(0.0) as usize).max(scissor.min_x);
+    let min_y = ((disk_center.y - reach).floor().max(0.0) as usize).max(scissor.min_y);
+    let max_x = ((disk_center.x + reach).ceil().max(0.0) as usize).min(scissor.max_x);
+    let max_y = ((disk_center.y + reach).ceil().max(0.0) as usize).min(scissor.max_y);
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let across = (x as f32 + 0.5 - disk_center.x) / pixels;
+            let down = (y as f32 + 0.5 - disk_center.y) / pixels;
+            let squared = across * across + down * down;
+            if squared > 1.0 {
+                continue;
+            }
+            // Seen from this far the disk is near enough to what an
+            // orthographic camera would show
+            let normal = right * across + up * down + toward_eye * (1.0 - squared).sqrt();
+            let surface = center.xyz() + normal * world_radius;
+            let screen = transform * surface.push(1.0);
+            let depth = screen.z / screen.w;
+            if depth > far_depth {
+                continue;
+            }
+            let radiance = night.lerp(&day, normal.dot(&sun).max(0.0));
+            if depth_mode.painter(x, split_x) {
+                framebuffer.set_radiance(x, y, radiance);
+            } else {
+                framebuffer.point(x, y, depth, radiance);
+            }
+        }
+    }
+}
+
+// Two unit vectors square to `normal` and each other
+fn tangents(normal: Vec3) -> (Vec3, Vec3) {
+    let other = if normal.y.abs() < 0.9 {
+        Vec3::y()
+    } else {
+        Vec3::x()
+    };
+    let tangent = normal.cross(&other).normalize();
+    (tangent, normal.cross(&tangent))
+}
@@ -0,0 +1,291 @@
+#[cfg(feature = "recording")]
+use crate::error::{Error, Result};
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+#[cfg(feature = "recording")]
+use std::fs::{self, File};
+#[cfg(feature = "recording")]
+use std::io::{self, BufWriter, Write};
+#[cfg(feature = "recording")]
+use std::time::Instant;
+
+// Every key the viewer reacts to. Recordings only keep these, by name.
+const KEYS: [Key; 67] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Left,
+    Key::Right,
+    Key::Up,
+    Key::Down,
+    Key::Minus,
+    Key::Equal,
+    Key::LeftBracket,
+    Key::RightBracket,
+    Key::Comma,
+    Key::Period,
+    Key::Escape,
+    Key::Enter,
+    Key::Backspace,
+    Key::Tab,
+    Key::LeftShift,
+    Key::RightShift,
+    Key::LeftCtrl,
+    Key::RightCtrl,
+    Key::LeftAlt,
+    Key::RightAlt,
+];
+
+// Keyboard and mouse state for one frame, either polled from the window or
+// rebuilt from a recording
+#[derive(Clone, Default)]
+pub struct InputFrame {
+    keys_down: Vec<Key>,
+    // Went down since the previous frame, without key repeat
+    keys_pressed: Vec<Key>,
+    pub mouse_pos: Option<(f32, f32)>,
+    pub mouse_down: bool,
+}
+
+impl InputFrame {
+    pub fn poll(window: &Window) -> Self {
+        let tracked = |keys: Vec<Key>| -> Vec<Key> {
+            keys.into_iter().filter(|key| KEYS.contains(key)).collect()
+        };
+        InputFrame {
+            keys_down: tracked(window.get_keys()),
+            keys_pressed: tracked(window.get_keys_pressed(KeyRepeat::No)),
+            mouse_pos: window.get_mouse_pos(MouseMode::Discard),
+            mouse_down: window.get_mouse_down(MouseButton::Left),
+        }
+    }
+
+    // Keys alone, from a frontend without a mouse
+    pub fn from_keys(keys_down: Vec<Key>, keys_pressed: Vec<Key>) -> Self {
+        InputFrame {
+            keys_down,
+            keys_pressed,
+            ..InputFrame::default()
+        }
+    }
+
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    // Whether any key went down or the button is held this frame
+    pub fn is_anything_pressed(&self) -> bool {
+        !self.keys_pressed.is_empty() || self.mouse_down
+    }
+}
+
+// Writes the changes in input from frame to frame, one event per line:
+// `frame milliseconds event`, where the event is `down KEY`, `up KEY`, `tap KEY`
+// (pressed and released within one frame), `mouse X Y`, `mouse none` or
+// `button down|up`. The first line holds the session's random seed.
+#[cfg(feature = "recording")]
+pub struct InputRecorder {
+    // For the errors writing to it
+    path: String,
+    writer: BufWriter<File>,
+    start: Instant,
+    previous: InputFrame,
+}
+
+#[cfg(feature = "recording")]
+impl InputRecorder {
+    pub fn create(path: &str, seed: u64) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path).map_err(Error::io(path))?);
+        writeln!(writer, "seed {}", seed).map_err(Error::io(path))?;
+        Ok(InputRecorder {
+            path: path.to_string(),
+            writer,
+            start: Instant::now(),
+            previous: InputFrame::default(),
+        })
+    }
+
+    pub fn record(&mut self, frame: u32, input: &InputFrame) -> Result<()> {
+        let mut events = Vec::new();
+        for key in &input.keys_down {
+            if !self.previous.is_key_down(*key) {
+                events.push(format!("down {:?}", key));
+            }
+        }
+        for key in &input.keys_pressed {
+            if !input.is_key_down(*key) {
+                events.push(format!("tap {:?}", key));
+            }
+        }
+        for key in &self.previous.keys_down {
+            if !input.is_key_down(*key) {
+                events.push(format!("up {:?}", key));
+            }
+        }
+        if input.mouse_pos != self.previous.mouse_pos {
+            events.push(match input.mouse_pos {
+                Some((x, y)) => format!("mouse {} {}", x, y),
+                None => "mouse none".to_string(),
+            });
+        }
+        if input.mouse_down != self.previous.mouse_down {
+            let state = if input.mouse_down { "down" } else { "up" };
+            events.push(format!("button {}", state));
+        }
+
+        let elapsed = self.start.elapsed().as_millis();
+        for event in events {
+            writeln!(self.writer, "{} {} {}", frame, elapsed, event)
+                .map_err(Error::io(&self.path))?;
+        }
+        self.previous = input.clone();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "recording")]
+enum Event {
+    Down(Key),
+    Up(Key),
+    Tap(Key),
+    Mouse(Option<(f32, f32)>),
+    Button(bool),
+}
+
+// Replays a recording frame by frame. Frames are matched by number rather
+// than by the recorded times, so the replay follows the same simulation steps
+// however fast it runs.
+#[cfg(feature = "recording")]
+pub struct InputPlayback {
+    pub seed: u64,
+    events: Vec<(u32, Event)>,
+    next: usize,
+    state: InputFrame,
+}
+
+#[cfg(feature = "recording")]
+impl InputPlayback {
+    pub fn load(path: &str) -> Result<Self> {
+        let contents = fs::read_to_string(path).map_err(Error::io(path))?;
+        let mut lines = contents.lines();
+        let seed = lines
+            .next()
+            .and_then(|line| line.strip_prefix("seed "))
+            .and_then(|seed| seed.trim().parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing seed line"))
+            .map_err(Error::io(path))?;
+
+        let mut events = Vec::new();
+        for (number, line) in lines.enumerate() {
+            match parse_event(line) {
+                Some(event) => events.push(event),
+                None => eprintln!("{}:{}: skipping '{}'", path, number + 2, line),
+            }
+        }
+
+        Ok(InputPlayback {
+            seed,
+            events,
+            next: 0,
+            state: InputFrame::default(),
+        })
+    }
+
+    // Input for `frame`, or None once the recording is used up
+    pub fn next_frame(&mut self, frame: u32) -> Option<InputFrame> {
+        if self.next >= self.events.len() {
+            return None;
+        }
+
+        self.state.keys_pressed.clear();
+        while let Some((event_frame, event)) = self.events.get(self.next) {
+            if *event_frame > frame {
+                break;
+            }
+            match *event {
+                Event::Down(key) => {
+                    self.state.keys_down.push(key);
+                    self.state.keys_pressed.push(key);
+                }
+                Event::Up(key) => self.state.keys_down.retain(|&down| down != key),
+                Event::Tap(key) => self.state.keys_pressed.push(key),
+                Event::Mouse(position) => self.state.mouse_pos = position,
+                Event::Button(down) => self.state.mouse_down = down,
+            }
+            self.next += 1;
+        }
+
+        Some(self.state.clone())
+    }
+}
+
+#[cfg(feature = "recording")]
+fn parse_event(line: &str) -> Option<(u32, Event)> {
+    let mut fields = line.split_whitespace();
+    let frame = fields.next()?.parse().ok()?;
+    let _milliseconds = fields.next()?;
+    let key = |name: Option<&str>| {
+        KEYS.into_iter()
+            .find(|key| Some(format!("{:?}", key).as_str()) == name)
+    };
+
+    let event = match fields.next()? {
+        "down" => Event::Down(key(fields.next())?),
+        "up" => Event::Up(key(fields.next())?),
+        "tap" => Event::Tap(key(fields.next())?),
+        "mouse" => match fields.next()? {
+            "none" => Event::Mouse(None),
+            x => Event::Mouse(Some((x.parse().ok()?, fields.next()?.parse().ok()?))),
+        },
+        "button" => Event::Button(fields.next()? == "down"),
+        _ => return None,
+    };
+    Some((frame, event))
+}
@@ -0,0 +1,28 @@
+use crate::color::Color;
+use crate::material::Material;
+use nalgebra_glm::{Mat3, Mat4};
+
+// One placement of a shared mesh. `color` replaces the mesh's vertex colors
+// and `material` the shader's default surface when set, and `seed` is handed
+// to every fragment of this copy so shaders can vary their noise per instance.
+// `tint` mixes the color channels of whatever the shader comes up with.
+#[derive(Clone, Copy)]
+pub struct Instance {
+    pub model_matrix: Mat4,
+    pub color: Option<Color>,
+    pub seed: u32,
+    pub material: Option<Material>,
+    pub tint: Option<Mat3>,
+}
+
+impl Instance {
+    pub fn new(model_matrix: Mat4) -> Self {
+        Instance {
+            model_matrix,
+            color: None,
+            seed: 0,
+            material: None,
+            tint: None,
+        }
+    }
+}
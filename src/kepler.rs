@@ -0,0 +1,93 @@
+use nalgebra_glm::{quat_angle_axis, Quat, Vec3};
+use std::f32::consts::TAU;
+
+// A body's path around the center it orbits, as classical orbital elements.
+// Angles are in radians and `period` in frames. The reference plane is XZ
+// with Y as its normal, so an inclination of zero is a flat orbit running
+// from +X toward +Z and anything past 90 degrees is retrograde.
+#[derive(Clone, Copy)]
+pub struct KeplerOrbit {
+    pub semi_major_axis: f32,
+    // 0 for a circle, up to but not including 1
+    pub eccentricity: f32,
+    pub inclination: f32,
+    // Where the orbit climbs through the reference plane, measured from +X
+    pub ascending_node: f32,
+    // Angle from the ascending node to the closest approach
+    pub argument_of_periapsis: f32,
+    // Mean anomaly at frame 0, so bodies sharing an orbit can be spread out
+    pub phase: f32,
+    pub period: f32,
+}
+
+impl KeplerOrbit {
+    // Offset from the orbited center at `time`
+    pub fn position(&self, time: u32) -> Vec3 {
+        let e = self.eccentricity.clamp(0.0, 0.99);
+        let mean_anomaly = (self.phase + TAU * time as f32 / self.period).rem_euclid(TAU);
+        let eccentric_anomaly = solve_kepler(mean_anomaly, e);
+
+        let true_anomaly = 2.0
+            * ((1.0 + e).sqrt() * (eccentric_anomaly / 2.0).sin())
+                .atan2((1.0 - e).sqrt() * (eccentric_anomaly / 2.0).cos());
+        let distance = self.semi_major_axis * (1.0 - e * eccentric_anomaly.cos());
+
+        // Rotate the point in the orbital plane out to the reference frame
+        let (sin_u, cos_u) = (self.argument_of_periapsis + true_anomaly).sin_cos();
+        let (sin_node, cos_node) = self.ascending_node.sin_cos();
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        Vec3::new(
+            distance * (cos_node * cos_u - sin_node * sin_u * cos_i),
+            distance * sin_u * sin_i,
+            distance * (sin_node * cos_u + cos_node * sin_u * cos_i),
+        )
+    }
+
+    // Unit normal of the orbit's plane, +Y for a flat prograde orbit
+    pub fn normal(&self) -> Vec3 {
+        let (sin_node, cos_node) = self.ascending_node.sin_cos();
+        let (sin_i, cos_i) = self.inclination.sin_cos();
+        Vec3::new(sin_node * sin_i, cos_i, -cos_node * sin_i)
+    }
+}
+
+// Eccentric anomaly E for a mean anomaly M, from M = E - e sin E by Newton's
+// method. Starting from pi on very elongated orbits keeps it from overshooting
+// near periapsis.
+fn solve_kepler(mean_anomaly: f32, eccentricity: f32) -> f32 {
+    let mut anomaly = if eccentricity > 0.8 {
+        std::f32::consts::PI
+    } else {
+        mean_anomaly
+    };
+    for _ in 0..16 {
+        let error = anomaly - eccentricity * anomaly.sin() - mean_anomaly;
+        anomaly -= error / (1.0 - eccentricity * anomaly.cos());
+        if error.abs() < 1e-6 {
+            break;
+        }
+    }
+    anomaly
+}
+
+// Turning about the body's own axis, `rate` in radians per frame with positive
+// rates in the same sense as a prograde orbit. The axis leans `axial_tilt`
+// away from the orbit's normal, toward -X.
+#[derive(Clone, Copy)]
+pub struct Spin {
+    pub rate: f32,
+    pub axial_tilt: f32,
+}
+
+impl Spin {
+    // Turned about Y first and then tilted about Z
+    pub fn orientation(&self, time: u32) -> Quat {
+        let turned = -(self.rate * time as f32).rem_euclid(TAU);
+        quat_angle_axis(self.axial_tilt, &Vec3::z()) * quat_angle_axis(turned, &Vec3::y())
+    }
+
+    // The north pole's direction
+    pub fn axis(&self) -> Vec3 {
+        Vec3::new(-self.axial_tilt.sin(), self.axial_tilt.cos(), 0.0)
+    }
+}
@@ -0,0 +1,266 @@
+use crate::color::Color;
+use crate::vertex::Vertex;
+use nalgebra_glm::{rotation, translation, Mat4, Vec2, Vec3, Vec4};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::{PI, TAU};
+
+const HULL: Color = Color::from_hex(0xBEBEC3);
+const TRIM: Color = Color::from_hex(0x5A5A64);
+const SOLAR_PANEL: Color = Color::from_hex(0x1E3278);
+const GOLD_FOIL: Color = Color::from_hex(0xC8A03C);
+
+fn colored(position: Vec3, normal: Vec3, tex_coords: Vec2, color: Color) -> Vertex {
+    let mut vertex = Vertex::new(position, normal, tex_coords);
+    vertex.color = color;
+    vertex
+}
+
+// Axis-aligned box centered on the origin with flat-shaded faces
+pub fn cuboid(size: Vec3, color: Color) -> Vec<Vertex> {
+    let half = size * 0.5;
+    let mut vertices = Vec::with_capacity(36);
+
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for sign in [-1.0, 1.0] {
+            let mut normal = Vec3::zeros();
+            normal[axis] = sign;
+
+            let corner = |su: f32, sv: f32| {
+                let mut position = normal * half[axis];
+                position[u] = su * half[u];
+                position[v] = sv * half[v];
+                colored(
+                    position,
+                    normal,
+                    Vec2::new((su + 1.0) * 0.5, (sv + 1.0) * 0.5),
+                    color,
+                )
+            };
+            let (a, b, c, d) = (
+                corner(-1.0, -1.0),
+                corner(1.0, -1.0),
+                corner(1.0, 1.0),
+                corner(-1.0, 1.0),
+            );
+            vertices.extend([a.clone(), b, c.clone(), a, c, d]);
+        }
+    }
+
+    vertices
+}
+
+// Capped cylinder along the Y axis, centered on the origin
+pub fn cylinder(radius: f32, length: f32, segments: usize, color: Color) -> Vec<Vertex> {
+    let half = length * 0.5;
+    let mut vertices = Vec::with_capacity(segments * 12);
+
+    for i in 0..segments {
+        let (u0, u1) = (i as f32 / segments as f32, (i + 1) as f32 / segments as f32);
+        let (a0, a1) = (u0 * TAU, u1 * TAU);
+        let n0 = Vec3::new(a0.cos(), 0.0, a0.sin());
+        let n1 = Vec3::new(a1.cos(), 0.0, a1.sin());
+
+        let bottom0 = colored(
+            n0 * radius - Vec3::y() * half,
+            n0,
+            Vec2::new(u0, 1.0),
+            color,
+        );
+        let bottom1 = colored(
+            n1 * radius - Vec3::y() * half,
+            n1,
+            Vec2::new(u1, 1.0),
+            color,
+        );
+        let top0 = colored(
+            n0 * radius + Vec3::y() * half,
+            n0,
+            Vec2::new(u0, 0.0),
+            color,
+        );
+        let top1 = colored(
+            n1 * radius + Vec3::y() * half,
+            n1,
+            Vec2::new(u1, 0.0),
+            color,
+        );
+        vertices.extend([
+            bottom0.clone(),
+            top0.clone(),
+            top1.clone(),
+            bottom0,
+            top1,
+            bottom1,
+        ]);
+
+        for sign in [-1.0, 1.0] {
+            let normal = Vec3::y() * sign;
+            let cap = |n: Vec3| {
+                colored(
+                    n * radius + normal * half,
+                    normal,
+                    Vec2::new(0.5 + n.x * 0.5, 0.5 + n.z * 0.5),
+                    color,
+                )
+            };
+            let center = colored(normal * half, normal, Vec2::new(0.5, 0.5), color);
+            vertices.extend([center, cap(n0), cap(n1)]);
+        }
+    }
+
+    vertices
+}
+
+// Moves a part into place. Only rigid transforms are expected, so normals go
+// through the same matrix with w = 0.
+fn place(part: Vec<Vertex>, transform: &Mat4) -> Vec<Vertex> {
+    part.into_iter()
+        .map(|mut vertex| {
+            let p = vertex.position;
+            let n = vertex.normal;
+            vertex.position = (transform * Vec4::new(p.x, p.y, p.z, 1.0)).xyz();
+            vertex.normal = (transform * Vec4::new(n.x, n.y, n.z, 0.0))
+                .xyz()
+                .normalize();
+            vertex.clip_position = vertex.position.push(1.0);
+            vertex.screen_position = vertex.position;
+            vertex.transformed_normal = vertex.normal;
+            vertex.world_position = vertex.position;
+            vertex
+        })
+        .collect()
+}
+
+// Builds a satellite or a space station out of boxes, cylinders and solar
+// panels. The seed picks which one and all of its proportions. The result
+// fits roughly inside a unit sphere.
+pub fn spacecraft(seed: u64) -> Vec<Vertex> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    if rng.gen_bool(0.4) {
+        satellite(&mut rng)
+    } else {
+        station(&mut rng)
+    }
+}
+
+fn satellite(rng: &mut StdRng) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+
+    let bus = Vec3::new(
+        rng.gen_range(0.2..0.35),
+        rng.gen_range(0.25..0.4),
+        rng.gen_range(0.2..0.35),
+    );
+    vertices.extend(cuboid(bus, GOLD_FOIL));
+
+    // Two wings on booms, each split into a few panels
+    let panel_count = rng.gen_range(2..5);
+    let panel_size = Vec3::new(0.18, rng.gen_range(0.2..0.3), 0.01);
+    let boom_length = 0.08;
+    for side in [-1.0, 1.0] {
+        let boom_x = side * (bus.x * 0.5 + boom_length * 0.5);
+        vertices.extend(place(
+            cuboid(Vec3::new(boom_length, 0.02, 0.02), TRIM),
+            &translation(&Vec3::new(boom_x, 0.0, 0.0)),
+        ));
+        for i in 0..panel_count {
+            let offset = bus.x * 0.5 + boom_length + (i as f32 + 0.5) * (panel_size.x + 0.01);
+            vertices.extend(place(
+                cuboid(panel_size, SOLAR_PANEL),
+                &translation(&Vec3::new(side * offset, 0.0, 0.0)),
+            ));
+        }
+    }
+
+    // Antenna mast with a flat dish on top
+    let mast = rng.gen_range(0.1..0.2);
+    vertices.extend(place(
+        cylinder(0.01, mast, 6, TRIM),
+        &translation(&Vec3::new(0.0, bus.y * 0.5 + mast * 0.5, 0.0)),
+    ));
+    vertices.extend(place(
+        cylinder(rng.gen_range(0.06..0.12), 0.015, 16, HULL),
+        &translation(&Vec3::new(0.0, bus.y * 0.5 + mast, 0.0)),
+    ));
+
+    vertices
+}
+
+fn station(rng: &mut StdRng) -> Vec<Vertex> {
+    let mut vertices = Vec::new();
+
+    // Central spine of stacked modules along Y
+    let hub_radius = rng.gen_range(0.08..0.12);
+    let hub_length = rng.gen_range(0.3..0.5);
+    vertices.extend(cylinder(hub_radius, hub_length, 16, HULL));
+
+    let mut top = hub_length * 0.5;
+    let mut bottom = -top;
+    for _ in 0..rng.gen_range(1..4) {
+        let upward = rng.gen_bool(0.5);
+        let length = rng.gen_range(0.1..0.2);
+        let module = if rng.gen_bool(0.5) {
+            cylinder(hub_radius * rng.gen_range(0.5..0.9), length, 12, HULL)
+        } else {
+            let width = hub_radius * rng.gen_range(1.0..1.6);
+            cuboid(Vec3::new(width, length, width), TRIM)
+        };
+        let y = if upward {
+            top += length;
+            top - length * 0.5
+        } else {
+            bottom -= length;
+            bottom + length * 0.5
+        };
+        vertices.extend(place(module, &translation(&Vec3::new(0.0, y, 0.0))));
+    }
+
+    // Trusses radiating from the hub, each ending in a solar array
+    let arm_count = rng.gen_range(2..5);
+    let arm_length = rng.gen_range(0.3..0.45);
+    let array_size = Vec3::new(rng.gen_range(0.2..0.3), 0.01, rng.gen_range(0.15..0.25));
+    for i in 0..arm_count {
+        let angle = i as f32 / arm_count as f32 * TAU;
+        let turn = rotation(angle, &Vec3::y());
+        let arm_x = hub_radius + arm_length * 0.5;
+        vertices.extend(place(
+            cuboid(Vec3::new(arm_length, 0.025, 0.025), TRIM),
+            &(turn * translation(&Vec3::new(arm_x, 0.0, 0.0))),
+        ));
+        let array_x = hub_radius + arm_length + array_size.x * 0.5;
+        vertices.extend(place(
+            cuboid(array_size, SOLAR_PANEL),
+            &(turn * translation(&Vec3::new(array_x, 0.0, 0.0))),
+        ));
+    }
+
+    // Optional habitat ring on spokes, made of short box segments
+    if rng.gen_bool(0.5) {
+        let ring_radius = rng.gen_range(0.45..0.6);
+        let ring_y = rng.gen_range(-0.1..0.1);
+        let segments = 24;
+        let segment_length = TAU * ring_radius / segments as f32 * 1.05;
+        for i in 0..segments {
+            let angle = i as f32 / segments as f32 * TAU;
+            vertices.extend(place(
+                cuboid(Vec3::new(0.05, 0.05, segment_length), HULL),
+                &(translation(&Vec3::new(0.0, ring_y, 0.0))
+                    * rotation(angle, &Vec3::y())
+                    * translation(&Vec3::new(ring_radius, 0.0, 0.0))),
+            ));
+        }
+        for i in 0..4 {
+            let angle = i as f32 / 4.0 * TAU + PI / 4.0;
+            vertices.extend(place(
+                cuboid(Vec3::new(ring_radius - hub_radius, 0.015, 0.015), TRIM),
+                &(translation(&Vec3::new(0.0, ring_y, 0.0))
+                    * rotation(angle, &Vec3::y())
+                    * translation(&Vec3::new((ring_radius + hub_radius) * 0.5, 0.0, 0.0))),
+            ));
+        }
+    }
+
+    vertices
+}
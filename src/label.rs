@@ -0,0 +1,68 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{DepthTest, Framebuffer};
+use crate::silhouette::projected_circle;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+
+// World-space height of a label's text, so labels shrink as bodies recede
+const LABEL_HEIGHT: f32 = 0.12;
+const MAX_SCALE: usize = 4;
+
+// Draws `text` centered just above the projected outline of a body. The
+// label is skipped when the body is behind the camera, its center is off
+// screen, or something nearer has already been drawn over its center.
+pub fn draw_label(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    eye: Vec3,
+    text: &str,
+    center: Vec3,
+    radius: f32,
+    color: u32,
+) {
+    let Some((screen, pixel_radius)) = projected_circle(
+        Vec4::new(center.x, center.y, center.z, 1.0),
+        radius,
+        &uniforms.view_matrix,
+        &uniforms.projection_matrix,
+        &uniforms.viewport_matrix,
+    ) else {
+        return;
+    };
+
+    if screen.x < 0.0
+        || screen.y < 0.0
+        || screen.x >= framebuffer.width as f32
+        || screen.y >= framebuffer.height as f32
+    {
+        return;
+    }
+
+    // Depth of the sphere point facing the camera, compared against whatever
+    // ended up in the z-buffer under the body's center
+    let toward_eye = (eye - center).normalize();
+    let front = center + toward_eye * radius;
+    let clip = uniforms.projection_matrix
+        * uniforms.view_matrix
+        * Vec4::new(front.x, front.y, front.z, 1.0);
+    let front_depth = (uniforms.viewport_matrix * Vec4::new(0.0, 0.0, clip.z / clip.w, 1.0)).z;
+    let (x, y) = (screen.x as usize, screen.y as usize);
+    if !framebuffer.passes_depth(x, y, front_depth - 1e-5, DepthTest::LessEqual) {
+        return;
+    }
+
+    let distance = (center - eye).magnitude();
+    let pixel_height = LABEL_HEIGHT / distance
+        * uniforms.projection_matrix[(1, 1)]
+        * uniforms.viewport_matrix[(1, 1)].abs();
+    let scale = ((pixel_height / GLYPH_HEIGHT as f32).round() as usize).clamp(1, MAX_SCALE);
+
+    let x = (screen.x - text_width(text, scale) as f32 / 2.0).round() as i32;
+    let y = (screen.y - pixel_radius - (GLYPH_HEIGHT * scale) as f32 - 4.0).round() as i32;
+
+    // One-pixel drop shadow keeps the text readable over bright surfaces
+    framebuffer.set_current_color(0x000000);
+    framebuffer.draw_text(x + 1, y + 1, text, scale);
+    framebuffer.set_current_color(color);
+    framebuffer.draw_text(x, y, text, scale);
+}
@@ -0,0 +1,101 @@
+use crate::sky::{map_direction, sample_map};
+use nalgebra_glm::Vec3;
+use std::ops::{Add, Mul, Sub};
+
+pub const MAP_WIDTH: usize = 512;
+pub const MAP_HEIGHT: usize = 256;
+
+// What a layer can hold: anything that blends between texels
+pub trait Texel:
+    Copy + Default + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self>
+{
+}
+
+impl<T> Texel for T where
+    T: Copy + Default + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>
+{
+}
+
+// A latitude-longitude map of one value over a body's surface
+pub struct LayerMap<T = f32> {
+    values: Vec<T>,
+}
+
+impl<T: Texel> LayerMap<T> {
+    pub fn sample(&self, direction: &Vec3) -> T {
+        sample_map(&self.values, MAP_WIDTH, direction)
+    }
+}
+
+// A layer of a shader that's too slow to evaluate for every fragment of every
+// frame but changes slowly enough not to need to, like a planet's clouds.
+// It's baked into a map that fragments sample instead, and re-baked every
+// `interval` frames. The rows are spread over those frames so no one frame
+// pays for the whole map, and the new map replaces the old one only once
+// it's finished. Every row of one bake is evaluated with the inputs it
+// started from, so the slices baked on different frames line up.
+pub struct BakedLayer<S, T = f32> {
+    interval: u32,
+    front: Option<LayerMap<T>>,
+    back: Vec<T>,
+    next_row: usize,
+    inputs: Option<S>,
+}
+
+impl<S: Copy, T: Texel> BakedLayer<S, T> {
+    pub fn new(interval: u32) -> Self {
+        BakedLayer {
+            interval: interval.max(1),
+            front: None,
+            back: vec![T::default(); MAP_WIDTH * MAP_HEIGHT],
+            next_row: 0,
+            inputs: None,
+        }
+    }
+
+    // Bakes this frame's share of rows. `current` is what the layer depends
+    // on right now, kept as the inputs of the next bake when one starts.
+    pub fn update(&mut self, current: S, layer: impl Fn(&S, &Vec3) -> T) {
+        let inputs = self.inputs(current);
+        let rows = MAP_HEIGHT.div_ceil(self.interval as usize);
+        let end = (self.next_row + rows).min(MAP_HEIGHT);
+        for y in self.next_row..end {
+            let v = (y as f32 + 0.5) / MAP_HEIGHT as f32;
+            for x in 0..MAP_WIDTH {
+                let u = (x as f32 + 0.5) / MAP_WIDTH as f32;
+                self.back[y * MAP_WIDTH + x] = layer(&inputs, &map_direction(u, v));
+            }
+        }
+        self.next_row = end;
+
+        if self.next_row == MAP_HEIGHT {
+            let values = match self.front.take() {
+                Some(old) => std::mem::replace(&mut self.back, old.values),
+                None => {
+                    std::mem::replace(&mut self.back, vec![T::default(); MAP_WIDTH * MAP_HEIGHT])
+                }
+            };
+            self.front = Some(LayerMap { values });
+            self.next_row = 0;
+            self.inputs = None;
+        }
+    }
+
+    // What the bake in progress is evaluated with, or `current` for one
+    // starting now, so callers can set up for it before `update`
+    pub fn inputs(&mut self, current: S) -> S {
+        *self.inputs.get_or_insert(current)
+    }
+
+    // Drops the map and any bake in progress
+    pub fn clear(&mut self) {
+        self.front = None;
+        self.next_row = 0;
+        self.inputs = None;
+    }
+
+    // The last finished bake, if there's been one
+    pub fn map(&self) -> Option<&LayerMap<T>> {
+        self.front.as_ref()
+    }
+}
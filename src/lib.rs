@@ -0,0 +1,701 @@
+// The renderer the viewer is built on, for drawing the planets without its
+// window: a headless tool, a test or another frontend loads a mesh with
+// `obj::Obj`, fills in `Uniforms` for a camera and a light, and draws it
+// into a `framebuffer::Framebuffer` with `render` or `render_instanced` and
+// one of `shaders::ShaderType`. `main.rs` is the interactive viewer on top.
+
+// Types are made with `new`, as they were when all of this was the binary's;
+// a `Default` that only calls it would be one more way to do the same
+#![allow(clippy::new_without_default)]
+
+use fastnoise_lite::FastNoiseLite;
+use nalgebra_glm::{
+    look_at, mat4_to_mat3, perspective, perspective_zo, rotate_vec3, Mat3, Mat4, Vec3,
+};
+use std::borrow::Cow;
+use std::f32::consts::{PI, TAU};
+use std::sync::atomic::Ordering;
+
+pub mod accumulation;
+pub mod atmosphere;
+pub mod barycenter;
+pub mod bench;
+pub mod blue_noise;
+pub mod bookmarks;
+pub mod budget;
+pub mod camera;
+pub mod clipping;
+pub mod color;
+pub mod comet;
+pub mod cubemap;
+pub mod decimate;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+pub mod dither;
+pub mod doppler;
+pub mod ecliptic;
+pub mod erosion;
+pub mod error;
+pub mod export;
+pub mod exposure;
+pub mod ffi;
+pub mod fog;
+pub mod font;
+pub mod fragment;
+#[cfg(feature = "frame-server")]
+pub mod frame_server;
+pub mod framebuffer;
+pub mod frontend;
+pub mod gallery;
+pub mod generator;
+pub mod golden;
+pub mod halfedge;
+pub mod help;
+pub mod horizon;
+#[cfg(feature = "physics")]
+pub mod impact;
+pub mod impostor;
+pub mod input;
+pub mod instance;
+pub mod kepler;
+pub mod kitbash;
+pub mod label;
+pub mod layer;
+pub mod light;
+pub mod lighttime;
+pub mod locale;
+pub mod lod;
+pub mod material;
+pub mod measure;
+pub mod menu;
+pub mod minimap;
+pub mod motion_blur;
+#[cfg(feature = "physics")]
+pub mod n_body;
+pub mod noise;
+pub mod obj;
+pub mod occlusion;
+pub mod options;
+pub mod outline;
+pub mod overdraw;
+pub mod pacing;
+#[cfg(feature = "particles")]
+pub mod particles;
+pub mod picking;
+pub mod png;
+pub mod postprocess;
+pub mod present;
+pub mod procedural;
+pub mod profiler;
+pub mod projection;
+#[cfg(feature = "particles")]
+pub mod prominence;
+pub mod quadtree;
+pub mod resolution;
+pub mod satellite;
+pub mod scene_file;
+pub mod season;
+pub mod sh;
+pub mod shaders;
+pub mod shadow;
+pub mod ship_hud;
+pub mod silhouette;
+pub mod simulation;
+pub mod sky;
+pub mod spatial;
+pub mod sprite;
+pub mod stereo;
+pub mod surface;
+pub mod swapchain;
+pub mod sweep;
+pub mod tectonics;
+#[cfg(feature = "recording")]
+pub mod telemetry;
+pub mod telescope;
+pub mod terminal;
+pub mod texture;
+pub mod timeline;
+pub mod tonemap;
+pub mod trails;
+pub mod transform;
+pub mod transit;
+pub mod triangle;
+pub mod vertex;
+pub mod viewer;
+pub mod weather;
+
+use clipping::{clip_triangle, to_screen};
+use cubemap::Cubemap;
+use fragment::FragmentBatch;
+use framebuffer::{Framebuffer, RowBand};
+use generator::GeneratedPlanet;
+#[cfg(feature = "physics")]
+use impact::ImpactMark;
+use instance::Instance;
+use kepler::{KeplerOrbit, Spin};
+use layer::LayerMap;
+use light::{Companion, Light, Star};
+use material::Material;
+use profiler::{Stage, Stopwatch};
+use projection::Projection;
+use satellite::{Satellite, SatelliteMesh};
+use season::{Orbit, Season};
+use sh::ShIrradiance;
+use shaders::{
+    flatten, fragment_shader, light_vertex, vertex_shader, AuroraParams, DebugView, GasGiantParams,
+    OceanParams, ShaderType, ShadingModel,
+};
+use shadow::ShadowMap;
+use tectonics::Continents;
+use triangle::triangle;
+use vertex::Vertex;
+use weather::WeatherState;
+
+pub struct Uniforms<'a> {
+    pub model_matrix: Mat4,
+    pub view_matrix: Mat4,
+    pub projection_matrix: Mat4,
+    pub viewport_matrix: Mat4,
+    pub time: u32,
+    pub noise: &'a FastNoiseLite,
+    pub aurora: AuroraParams,
+    pub gas_giant: GasGiantParams,
+    pub ocean: OceanParams,
+    // Where the lighting is worked out: once a face, at the vertices or for
+    // every fragment
+    pub shading: ShadingModel,
+    pub light_dir: Vec3,
+    // The sun's kind, for the color and strength of the light it gives
+    pub star: Star,
+    // Lighting the scene along with the sun
+    pub lights: &'a [Light],
+    // World space to the sun's NDC, and the depths the sun sees through it.
+    // No map means no shadows.
+    pub light_space_matrix: Mat4,
+    pub shadow_map: Option<&'a ShadowMap>,
+    // The other star of a binary, or None for a lone sun
+    pub companion: Option<Companion<'a>>,
+    // Octaves of noise the shaders may add past their own, for a close
+    // fly-by to show more than flat triangles
+    pub extra_octaves: u32,
+    pub ambient: ShIrradiance,
+    // What shiny surfaces like the terran oceans reflect, or None for no
+    // reflections
+    pub environment: Option<&'a Cubemap>,
+    // Only one particle in this many is drawn, to keep within the frame
+    // budget
+    pub particle_stride: usize,
+    pub weather: WeatherState,
+    // The terran planet's clouds baked ahead of time, or None to work them
+    // out for every fragment
+    pub clouds: Option<&'a LayerMap>,
+    // The planet shaded ahead of time by the shader named with it, or None
+    // to shade it pixel by pixel
+    pub surface: Option<(ShaderType, &'a LayerMap<Vec3>)>,
+    // Camera position in world space, for view-dependent shading
+    pub eye: Vec3,
+    pub season: Season,
+    pub continents: &'a Continents,
+    // Fresh and lingering asteroid strikes on the planet, in its model space
+    #[cfg(feature = "physics")]
+    pub impacts: &'a [ImpactMark],
+    // Recipe for ShaderType::Generated
+    pub planet: &'a GeneratedPlanet,
+    // Every surface shows its normals, texture coordinates or overdraw
+    // instead of being shaded
+    pub debug_view: Option<DebugView>,
+    // How many strips of rows are shaded in parallel, from --threads
+    pub threads: usize,
+    // Whether depth is laid out reversed, from --reverse-z, as the matrices
+    // were built with; see `create_perspective_matrix`
+    pub reversed_z: bool,
+}
+
+// Pull toward the planet's center, world units³ per frame². A rock let go at
+// the belt reaches the surface in about three seconds.
+pub const GRAVITY: f32 = 0.001;
+
+// Toward the sun at the start of the year. It sits low over the plane the
+// moons orbit in, so once an orbit the moon crosses the sunlight and its
+// shadow sweeps over the planet.
+const SUN_DIRECTION: Vec3 = Vec3::new(0.8, 0.15, 0.58);
+
+// Toward the sun at `time`. The planet goes around it once a year, so seen
+// from the planet the sun circles the y axis from SUN_DIRECTION, and every
+// side of every body gets its turn in daylight.
+pub fn sun_direction(orbit: &Orbit, time: u32) -> Vec3 {
+    let angle = orbit.phase(time) * TAU;
+    rotate_vec3(&SUN_DIRECTION.normalize(), angle, &Vec3::y())
+}
+
+// A faint blue light from opposite the sun, as if off a companion star, so
+// the night sides show their relief
+const FILL_LIGHT: Vec3 = Vec3::new(0.02, 0.035, 0.08);
+
+pub fn fill_light(light_dir: Vec3) -> Light {
+    Light::directional(-light_dir, FILL_LIGHT)
+}
+
+// Orbits of the bodies around the planet. Periods are in frames; the station
+// flies retrograde, against the moon and the asteroid.
+pub const MOON_ORBIT: KeplerOrbit = KeplerOrbit {
+    semi_major_axis: 2.0,
+    eccentricity: 0.12,
+    inclination: 5.1_f32.to_radians(),
+    ascending_node: 0.0,
+    argument_of_periapsis: 0.0,
+    phase: 0.0,
+    period: 1260.0,
+};
+
+pub const ASTEROID_ORBIT: KeplerOrbit = KeplerOrbit {
+    semi_major_axis: 3.0,
+    eccentricity: 0.25,
+    inclination: 6.0_f32.to_radians(),
+    ascending_node: 40.0_f32.to_radians(),
+    argument_of_periapsis: 70.0_f32.to_radians(),
+    phase: PI,
+    period: 2100.0,
+};
+
+pub const STATION_ORBIT: KeplerOrbit = KeplerOrbit {
+    semi_major_axis: 1.4,
+    eccentricity: 0.02,
+    inclination: 172.0_f32.to_radians(),
+    ascending_node: 0.0,
+    argument_of_periapsis: 0.0,
+    phase: 0.0,
+    period: 1570.0,
+};
+
+// Long and steep, diving in close to the planet once per period
+pub const COMET_ORBIT: KeplerOrbit = KeplerOrbit {
+    semi_major_axis: 5.0,
+    eccentricity: 0.75,
+    inclination: 12.0_f32.to_radians(),
+    ascending_node: 200.0_f32.to_radians(),
+    argument_of_periapsis: 45.0_f32.to_radians(),
+    phase: 0.0,
+    period: 3000.0,
+};
+
+// How the planet and the moon turn on their axes. The moon is tidally locked,
+// spinning once per orbit to keep the same face toward the planet.
+pub const PLANET_SPIN: Spin = Spin {
+    rate: 0.002,
+    axial_tilt: 23.4_f32.to_radians(),
+};
+
+pub const MOON_SPIN: Spin = Spin {
+    rate: TAU / MOON_ORBIT.period,
+    axial_tilt: 6.7_f32.to_radians(),
+};
+
+// Moon systems, picked by the planet's shader; planets not listed here have
+// no satellites
+pub static ROCKY_SATELLITES: [Satellite; 1] = [Satellite {
+    name: Cow::Borrowed("Moon"),
+    mesh: SatelliteMesh::Moon,
+    shader: ShaderType::Moon,
+    noise: None,
+    scale: 0.15,
+    mass: 0.0123,
+    orbit: MOON_ORBIT,
+    spin: MOON_SPIN,
+    satellites: Cow::Borrowed(&[]),
+}];
+
+pub static GAS_GIANT_SATELLITES: [Satellite; 2] = [
+    Satellite {
+        name: Cow::Borrowed("Inner moon"),
+        mesh: SatelliteMesh::Moon,
+        shader: ShaderType::Moon,
+        noise: None,
+        scale: 0.08,
+        mass: 0.002,
+        orbit: KeplerOrbit {
+            semi_major_axis: 1.8,
+            eccentricity: 0.01,
+            inclination: 1.0_f32.to_radians(),
+            ascending_node: 0.0,
+            argument_of_periapsis: 0.0,
+            phase: 0.0,
+            period: 800.0,
+        },
+        spin: Spin {
+            rate: TAU / 800.0,
+            axial_tilt: 0.0,
+        },
+        satellites: Cow::Borrowed(&[]),
+    },
+    Satellite {
+        name: Cow::Borrowed("Outer moon"),
+        mesh: SatelliteMesh::Moon,
+        shader: ShaderType::Moon,
+        noise: None,
+        scale: 0.14,
+        mass: 0.08,
+        orbit: KeplerOrbit {
+            semi_major_axis: 2.4,
+            eccentricity: 0.05,
+            inclination: 3.0_f32.to_radians(),
+            ascending_node: 30.0_f32.to_radians(),
+            argument_of_periapsis: 0.0,
+            phase: 2.0,
+            period: 1800.0,
+        },
+        spin: Spin {
+            rate: TAU / 1800.0,
+            axial_tilt: 2.0_f32.to_radians(),
+        },
+        // A captured rock circling the outer moon
+        satellites: Cow::Borrowed(&[Satellite {
+            name: Cow::Borrowed("Moonlet"),
+            mesh: SatelliteMesh::Asteroid,
+            shader: ShaderType::Moon,
+            noise: None,
+            scale: 0.04,
+            mass: 0.0001,
+            orbit: KeplerOrbit {
+                semi_major_axis: 0.35,
+                eccentricity: 0.1,
+                inclination: 15.0_f32.to_radians(),
+                ascending_node: 0.0,
+                argument_of_periapsis: 0.0,
+                phase: 0.0,
+                period: 300.0,
+            },
+            spin: Spin {
+                rate: 0.03,
+                axial_tilt: 40.0_f32.to_radians(),
+            },
+            satellites: Cow::Borrowed(&[]),
+        }]),
+    },
+];
+
+pub fn satellites_of(shader: ShaderType) -> &'static [Satellite] {
+    match shader {
+        ShaderType::RockyPlanet => &ROCKY_SATELLITES,
+        ShaderType::GasGiant | ShaderType::ColdGasGiant => &GAS_GIANT_SATELLITES,
+        _ => &[],
+    }
+}
+
+pub fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
+    look_at(&eye, &center, &up)
+}
+
+// Vertical, in radians, that the views start out with
+pub const FIELD_OF_VIEW: f32 = 45.0 * PI / 180.0;
+
+// Reversed Z takes the near plane to a depth of 1 and the far one to 0,
+// instead of -1 and 1. Floats are finest close to 0, which is where the
+// depths of far-off things then end up, rather than a hair under 1, so
+// distant moons and their orbit lines stop fighting over pixels.
+pub fn create_perspective_matrix(
+    projection: &Projection,
+    window_width: f32,
+    window_height: f32,
+    reversed_z: bool,
+) -> Mat4 {
+    let aspect_ratio = window_width / window_height;
+    let Projection { fov, near, far } = *projection;
+
+    if reversed_z {
+        perspective_zo(aspect_ratio, fov, far, near)
+    } else {
+        perspective(aspect_ratio, fov, near, far)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+
+    pub fn full(width: f32, height: f32) -> Self {
+        Viewport::new(0.0, 0.0, width, height)
+    }
+
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+}
+
+// Maps NDC to a sub-rectangle of the framebuffer starting at (x, y), and NDC z
+// from [-1, 1] into [min_depth, max_depth]. Reversed, NDC z from the far
+// plane's 0 to the near plane's 1 goes to -min_depth down to -max_depth:
+// negated, which loses nothing, so nearer is smaller either way and the
+// depth buffer's clear value and tests hold for both, and scaled without
+// anything added before the depths of far-off things are stored.
+pub fn create_viewport_matrix(viewport: &Viewport, reversed_z: bool) -> Mat4 {
+    let half_width = viewport.width / 2.0;
+    let half_height = viewport.height / 2.0;
+    let (depth_scale, depth_offset) = if reversed_z {
+        (viewport.min_depth - viewport.max_depth, -viewport.min_depth)
+    } else {
+        let half_depth = (viewport.max_depth - viewport.min_depth) / 2.0;
+        (half_depth, viewport.min_depth + half_depth)
+    };
+
+    Mat4::new(
+        half_width,
+        0.0,
+        0.0,
+        viewport.x + half_width,
+        0.0,
+        -half_height,
+        0.0,
+        viewport.y + half_height,
+        0.0,
+        0.0,
+        depth_scale,
+        depth_offset,
+        0.0,
+        0.0,
+        0.0,
+        1.0,
+    )
+}
+
+// The depths `viewport_matrix` stores for the near and far planes
+pub fn depth_planes(viewport_matrix: &Mat4, reversed_z: bool) -> (f32, f32) {
+    let (near, far) = if reversed_z {
+        (1.0, 0.0)
+    } else {
+        (-1.0, 1.0)
+    };
+    let depth = |ndc: f32| viewport_matrix[(2, 2)] * ndc + viewport_matrix[(2, 3)];
+    (depth(near), depth(far))
+}
+
+// How visibility is resolved; Split renders z-buffered on the left half and
+// painter's algorithm on the right half for side-by-side comparison
+#[derive(Clone, Copy, PartialEq)]
+pub enum DepthMode {
+    ZBuffer,
+    Painter,
+    Split,
+}
+
+impl DepthMode {
+    // Whether the pixel in column `x` is drawn over rather than depth tested
+    fn painter(self, x: usize, split_x: usize) -> bool {
+        match self {
+            DepthMode::ZBuffer => false,
+            DepthMode::Painter => true,
+            DepthMode::Split => x >= split_x,
+        }
+    }
+}
+
+pub fn render(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    vertex_array: &[Vertex],
+    current_shader: &ShaderType,
+    depth_mode: DepthMode,
+) {
+    render_instanced(
+        framebuffer,
+        uniforms,
+        vertex_array,
+        &[Instance::new(uniforms.model_matrix)],
+        current_shader,
+        depth_mode,
+    );
+}
+
+// Names the body and triangle a non-finite value came from
+#[cfg(feature = "diagnostics")]
+fn source(shader: &ShaderType, (instance, triangle): (usize, usize)) -> String {
+    format!("{:?} instance {} triangle {}", shader, instance, triangle)
+}
+
+// Draws one copy of `vertex_array` per instance. `uniforms.model_matrix` is
+// ignored in favor of each instance's own transform.
+pub fn render_instanced(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    vertex_array: &[Vertex],
+    instances: &[Instance],
+    current_shader: &ShaderType,
+    depth_mode: DepthMode,
+) {
+    let view_projection = uniforms.projection_matrix * uniforms.view_matrix;
+    let (mut vertex_time, mut assembly_time) = (Stopwatch::new(), Stopwatch::new());
+
+    // Vertex Shader, Primitive Assembly and Clipping. The matrices only change per
+    // instance, so they are built once here instead of once per vertex.
+    let mut triangles = Vec::with_capacity(vertex_array.len() / 3 * instances.len());
+    for (instance_index, instance) in instances.iter().enumerate() {
+        let transform = view_projection * instance.model_matrix;
+        let material = instance
+            .material
+            .unwrap_or_else(|| Material::for_shader(current_shader));
+        let normal_matrix = mat4_to_mat3(&instance.model_matrix)
+            .transpose()
+            .try_inverse()
+            .unwrap_or(Mat3::identity());
+
+        let transformed_vertices: Vec<Vertex> = vertex_time.time(|| {
+            let mut transformed_vertices: Vec<Vertex> = vertex_array
+                .iter()
+                .map(|vertex| {
+                    let mut transformed =
+                        vertex_shader(vertex, &transform, &instance.model_matrix, &normal_matrix);
+                    if let Some(color) = instance.color {
+                        transformed.color = color;
+                    }
+                    // Gouraud shading lights the corners here and the
+                    // fragments only blend what they got
+                    if uniforms.shading == ShadingModel::Gouraud {
+                        transformed.radiance = light_vertex(
+                            &transformed,
+                            uniforms,
+                            current_shader,
+                            instance.seed,
+                            material,
+                        );
+                    }
+                    transformed
+                })
+                .collect();
+            if uniforms.shading == ShadingModel::Flat {
+                transformed_vertices.chunks_exact_mut(3).for_each(flatten);
+            }
+            transformed_vertices
+        });
+
+        assembly_time.time(|| {
+            for (triangle_index, tri) in transformed_vertices.chunks_exact(3).enumerate() {
+                let origin = (instance_index, triangle_index);
+                #[cfg(feature = "diagnostics")]
+                if let Some(vertex) = tri
+                    .iter()
+                    .find(|vertex| !vertex.clip_position.iter().all(|c| c.is_finite()))
+                {
+                    diagnostics::report(source(current_shader, origin), || {
+                        format!("vertex transformed to {:?}", vertex.clip_position)
+                    });
+                }
+                // Divided by w and mapped through the viewport only once what's
+                // outside the view, behind the eye included, is cut away
+                clip_triangle(tri, uniforms.reversed_z, |mut clipped| {
+                    if to_screen(&mut clipped, &uniforms.viewport_matrix) {
+                        triangles.push((clipped, instance.seed, material, origin));
+                    }
+                });
+            }
+        });
+    }
+
+    // Painter's algorithm: draw back to front and let later triangles overwrite
+    if depth_mode != DepthMode::ZBuffer {
+        let average_depth =
+            |tri: &[Vertex; 3]| tri.iter().map(|v| v.screen_position.z).sum::<f32>() / 3.0;
+        assembly_time.time(|| {
+            triangles.sort_by(|a, b| average_depth(&b.0).total_cmp(&average_depth(&a.0)));
+        });
+    }
+    profiler::add(Stage::Vertex, vertex_time.elapsed());
+    profiler::add(Stage::Assembly, assembly_time.elapsed());
+    budget::TRIANGLES.fetch_add(triangles.len(), Ordering::Relaxed);
+
+    // Rasterization and Fragment Processing. Each worker owns a strip of
+    // rows and only rasterizes the part of every triangle that falls in it,
+    // so no two ever write the same pixel. Order within a strip is kept,
+    // which the painter's algorithm relies on.
+    let split_x = framebuffer.width / 2;
+    let workers = uniforms.threads.max(1);
+    let shade_band = |band: &mut RowBand| {
+        let mut shaded = 0;
+        // Everything the band does but the shading counts as rasterizing
+        let (mut band_time, mut shading) = (Stopwatch::new(), Stopwatch::new());
+        // Fragments are shaded a batch at a time, and the batch is stored
+        // once they all are
+        let mut batch = FragmentBatch::new();
+        let scissor = band.scissor;
+        band_time.time(|| {
+            for (tri, seed, material, origin) in &triangles {
+                let tint = instances[origin.0].tint;
+                triangle(&tri[0], &tri[1], &tri[2], &scissor, &mut batch, |batch| {
+                    let len = batch.len;
+                    match uniforms.shading {
+                        ShadingModel::Gouraud => batch.shaded = batch.radiance,
+                        _ => shading.time(|| {
+                            for index in 0..len {
+                                let fragment = batch.fragment(index, *seed, *material);
+                                let radiance = fragment_shader(&fragment, uniforms, current_shader);
+                                batch.shaded.set(index, radiance);
+                            }
+                        }),
+                    }
+                    if let Some(tint) = &tint {
+                        batch.shaded.transform(tint, len);
+                    }
+                    shaded += len;
+
+                    for index in 0..len {
+                        let (x, y) = (batch.x[index], batch.y[index]);
+                        let depth = batch.depth[index];
+                        let radiance = batch.shaded.get(index);
+                        #[cfg(feature = "diagnostics")]
+                        if !depth.is_finite() || !diagnostics::finite(&radiance) {
+                            diagnostics::report(source(current_shader, *origin), || {
+                                format!(
+                                    "depth {} and color {:?} at ({}, {})",
+                                    depth, radiance, x, y
+                                )
+                            });
+                            // A NaN depth fails every test and would leave a hole, so
+                            // write a non-finite color over it for resolve to mark
+                            band.set_radiance(x, y, Vec3::repeat(f32::NAN));
+                            continue;
+                        }
+
+                        if depth_mode.painter(x, split_x) {
+                            band.set_radiance(x, y, radiance);
+                        } else {
+                            band.point(x, y, depth, radiance);
+                        }
+                    }
+                });
+            }
+        });
+        profiler::add(Stage::Fragment, shading.elapsed());
+        let rasterizing = band_time.elapsed().saturating_sub(shading.elapsed());
+        profiler::add(Stage::Raster, rasterizing);
+        budget::FRAGMENTS.fetch_add(shaded, Ordering::Relaxed);
+    };
+
+    let mut bands = framebuffer.row_bands(workers);
+    if let [band] = bands.as_mut_slice() {
+        shade_band(band);
+    } else {
+        let shade_band = &shade_band;
+        std::thread::scope(|scope| {
+            for mut band in bands {
+                scope.spawn(move || shade_band(&mut band));
+            }
+        });
+    }
+}
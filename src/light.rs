@@ -0,0 +1,172 @@
+use crate::shadow::ShadowMap;
+use nalgebra_glm::{rotate_vec3, Mat4, Vec3};
+use std::f32::consts::TAU;
+
+// Frames the two stars of a binary take to go around each other, and the
+// widest they're seen apart from the planet, in radians
+const BINARY_PERIOD: f32 = 900.0;
+const BINARY_SEPARATION: f32 = 0.12;
+
+// Where a light shines from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    // So far away it comes from `direction` everywhere, like the sun
+    Directional(Vec3),
+    // From `position`, fading out with the distance until it's gone at
+    // `range`
+    Point { position: Vec3, range: f32 },
+}
+
+// A light other than the sun. It casts no shadows, and `color` is what it
+// gives a surface facing it, at unit distance for a point light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub source: Source,
+    pub color: Vec3,
+}
+
+impl Light {
+    pub fn directional(direction: Vec3, color: Vec3) -> Self {
+        Light {
+            source: Source::Directional(direction.normalize()),
+            color,
+        }
+    }
+
+    pub fn point(position: Vec3, range: f32, color: Vec3) -> Self {
+        Light {
+            source: Source::Point { position, range },
+            color,
+        }
+    }
+
+    // Toward the light from `position`, and how much of it gets there, or
+    // None when it's out of reach
+    pub fn arriving(&self, position: &Vec3) -> Option<(Vec3, Vec3)> {
+        match self.source {
+            Source::Directional(direction) => Some((direction, self.color)),
+            Source::Point {
+                position: origin,
+                range,
+            } => {
+                let offset = origin - position;
+                let distance = offset.magnitude();
+                if distance >= range || distance <= f32::EPSILON {
+                    return None;
+                }
+                Some((offset / distance, self.color * attenuation(distance, range)))
+            }
+        }
+    }
+}
+
+// Inverse square, eased to nothing at `range` so a light can be left out
+// past it without a visible edge. The 1 keeps it finite at the light itself.
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let window = (1.0 - (distance / range).powi(4)).max(0.0);
+    window * window / (1.0 + distance * distance)
+}
+
+// The star the system goes around, which sets the sunlight's color and how
+// strong it is. The yellow dwarf is the plain white sun the scene has always
+// been lit by; a red dwarf bathes its planets in a dimmer, warmer light.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Star {
+    RedDwarf,
+    OrangeDwarf,
+    YellowDwarf,
+    BlueWhite,
+}
+
+const STARS: [Star; 4] = [
+    Star::RedDwarf,
+    Star::OrangeDwarf,
+    Star::YellowDwarf,
+    Star::BlueWhite,
+];
+
+impl Star {
+    pub fn parse(name: &str) -> Option<Self> {
+        STARS.into_iter().find(|star| star.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Star::RedDwarf => "red-dwarf",
+            Star::OrangeDwarf => "orange-dwarf",
+            Star::YellowDwarf => "yellow-dwarf",
+            Star::BlueWhite => "blue-white",
+        }
+    }
+
+    // Its light's color, with the brightest channel at 1, roughly that of a
+    // blackbody at the star's temperature
+    pub fn color(&self) -> Vec3 {
+        match self {
+            Star::RedDwarf => Vec3::new(1.0, 0.58, 0.32),
+            Star::OrangeDwarf => Vec3::new(1.0, 0.8, 0.6),
+            Star::YellowDwarf => Vec3::new(1.0, 1.0, 1.0),
+            Star::BlueWhite => Vec3::new(0.78, 0.86, 1.0),
+        }
+    }
+
+    // How strongly it lights the planets, the yellow dwarf's being 1
+    pub fn intensity(&self) -> f32 {
+        match self {
+            Star::RedDwarf => 0.8,
+            Star::OrangeDwarf => 0.9,
+            Star::YellowDwarf => 1.0,
+            Star::BlueWhite => 1.3,
+        }
+    }
+
+    // What a surface facing it gets
+    pub fn sunlight(&self) -> Vec3 {
+        self.color() * self.intensity()
+    }
+
+    // In suns, for where a binary balances
+    pub fn mass(&self) -> f32 {
+        match self {
+            Star::RedDwarf => 0.3,
+            Star::OrangeDwarf => 0.7,
+            Star::YellowDwarf => 1.0,
+            Star::BlueWhite => 2.0,
+        }
+    }
+}
+
+// Two stars going around their barycenter, with the planet going around the
+// pair. Their orbit is in the plane of the planet's, so from the planet it's
+// seen edge on: the stars swing from one side of the barycenter to the other
+// and back, each by as much as the other outweighs it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Binary {
+    pub primary: Star,
+    pub companion: Star,
+}
+
+impl Binary {
+    // Toward the primary and toward the companion at `time`, seen from the
+    // planet with the barycenter toward `barycenter`
+    pub fn directions(&self, barycenter: Vec3, time: u32) -> (Vec3, Vec3) {
+        let total = self.primary.mass() + self.companion.mass();
+        let swing = BINARY_SEPARATION * (time as f32 / BINARY_PERIOD * TAU).cos();
+        let toward = |angle: f32| rotate_vec3(&barycenter, angle, &Vec3::y());
+        (
+            toward(-swing * self.companion.mass() / total),
+            toward(swing * self.primary.mass() / total),
+        )
+    }
+}
+
+// The companion as it lights one frame, along with the primary and in the
+// same way: from `direction`, with shadows of its own in `shadow_map` seen
+// through `light_space_matrix`
+#[derive(Clone, Copy)]
+pub struct Companion<'a> {
+    pub direction: Vec3,
+    pub star: Star,
+    pub light_space_matrix: Mat4,
+    pub shadow_map: Option<&'a ShadowMap>,
+}
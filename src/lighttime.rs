@@ -0,0 +1,168 @@
+use crate::framebuffer::{DepthState, Framebuffer};
+use crate::silhouette::projected_circle;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+use std::borrow::Cow;
+
+// Frames a flash stays lit once its light has reached the eye
+const GLOW_FRAMES: u32 = 40;
+// Pixels out from the body's center the glow reaches at its brightest,
+// at least, and beyond its edge as a share of its radius on screen
+const GLOW_RADIUS: f32 = 14.0;
+const GLOW_SPREAD: f32 = 1.6;
+const GLOW: Vec3 = Vec3::new(6.0, 5.5, 4.0);
+// Past everything in the system, where a wavefront is let go
+const MAX_REACH: f32 = 30.0;
+const WAVEFRONT: Vec3 = Vec3::new(0.5, 0.45, 0.25);
+
+// A burst of light given off by `body` on frame `emitted`. It stays where the
+// body was then, since that's where its light comes from.
+pub struct Flash {
+    pub body: Cow<'static, str>,
+    pub origin: Vec3,
+    pub radius: f32,
+    pub emitted: u32,
+    // The frame its light reached the eye
+    pub seen: Option<u32>,
+}
+
+// Flashes spreading out at `speed` world units per frame, so what happens at
+// a body shows up from where the camera is only as long after as light
+// takes to get there. Each one's wavefront is drawn as it grows.
+pub struct LightTravel {
+    pub speed: f32,
+    pub flashes: Vec<Flash>,
+}
+
+impl LightTravel {
+    pub fn new(speed: f32) -> Self {
+        LightTravel {
+            speed,
+            flashes: Vec::new(),
+        }
+    }
+
+    pub fn emit(&mut self, body: Cow<'static, str>, origin: Vec3, radius: f32, time: u32) {
+        self.flashes.push(Flash {
+            body,
+            origin,
+            radius,
+            emitted: time,
+            seen: None,
+        });
+    }
+
+    // How far the light of `flash` has got by `time`
+    pub fn reach(&self, flash: &Flash, time: u32) -> f32 {
+        time.saturating_sub(flash.emitted) as f32 * self.speed
+    }
+
+    // Frames until the light of `flash` gets to `eye`, 0 once it has
+    pub fn arrives_in(&self, flash: &Flash, eye: Vec3, time: u32) -> u32 {
+        let left = (flash.origin - eye).magnitude() - self.reach(flash, time);
+        (left / self.speed).ceil().max(0.0) as u32
+    }
+
+    // Notes which flashes have been seen from `eye` by `time`, and lets go of
+    // the ones that have faded and left the system behind, or that the
+    // timeline has gone back to before
+    pub fn update(&mut self, eye: Vec3, time: u32) {
+        let speed = self.speed;
+        self.flashes.retain_mut(|flash| {
+            if time < flash.emitted {
+                return false;
+            }
+            let reach = (time - flash.emitted) as f32 * speed;
+            if flash.seen.is_none() && reach >= (flash.origin - eye).magnitude() {
+                flash.seen = Some(time);
+            }
+            let glowing = flash.seen.is_none_or(|seen| time - seen < GLOW_FRAMES);
+            glowing || reach < MAX_REACH
+        });
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms, time: u32) {
+        let previous = framebuffer.depth_state();
+        for flash in &self.flashes {
+            framebuffer.set_depth_state(DepthState::OVERLAY);
+            draw_wavefront(framebuffer, uniforms, flash.origin, self.reach(flash, time));
+            if let Some(seen) = flash.seen {
+                framebuffer.set_depth_state(DepthState::TRANSLUCENT);
+                let fade = 1.0 - (time - seen) as f32 / GLOW_FRAMES as f32;
+                draw_glow(framebuffer, uniforms, flash, fade);
+            }
+        }
+        framebuffer.set_depth_state(previous);
+    }
+}
+
+// The outline of the sphere the light has filled, fainter as it spreads.
+// Once it's past the eye there's nothing left to see of it.
+fn draw_wavefront(framebuffer: &mut Framebuffer, uniforms: &Uniforms, origin: Vec3, reach: f32) {
+    if reach <= 0.0 || reach >= MAX_REACH {
+        return;
+    }
+    let Some((screen, radius)) = projected_circle(
+        Vec4::new(origin.x, origin.y, origin.z, 1.0),
+        reach,
+        &uniforms.view_matrix,
+        &uniforms.projection_matrix,
+        &uniforms.viewport_matrix,
+    ) else {
+        return;
+    };
+    let radiance = WAVEFRONT * (1.0 - reach / MAX_REACH);
+    let steps = (radius * std::f32::consts::TAU).ceil().max(8.0) as usize;
+    for step in 0..steps {
+        let angle = step as f32 / steps as f32 * std::f32::consts::TAU;
+        let (x, y) = (
+            screen.x + angle.cos() * radius,
+            screen.y + angle.sin() * radius,
+        );
+        if x >= 0.0 && y >= 0.0 {
+            framebuffer.add_radiance(x as usize, y as usize, 0.0, radiance);
+        }
+    }
+}
+
+// Light spilling out around the body, hidden by whatever's in front of the
+// side of it facing the eye, and `fade` of its brightest
+fn draw_glow(framebuffer: &mut Framebuffer, uniforms: &Uniforms, flash: &Flash, fade: f32) {
+    let to_screen = |point: Vec3| {
+        let clip = uniforms.projection_matrix
+            * uniforms.view_matrix
+            * Vec4::new(point.x, point.y, point.z, 1.0);
+        (clip.w > 0.0).then(|| (uniforms.viewport_matrix * (clip / clip.w)).xyz())
+    };
+    let Some(toward_eye) = (uniforms.eye - flash.origin).try_normalize(1e-6) else {
+        return;
+    };
+    let (Some(center), Some(front)) = (
+        to_screen(flash.origin),
+        to_screen(flash.origin + toward_eye * flash.radius),
+    ) else {
+        return;
+    };
+
+    let body = projected_circle(
+        Vec4::new(flash.origin.x, flash.origin.y, flash.origin.z, 1.0),
+        flash.radius,
+        &uniforms.view_matrix,
+        &uniforms.projection_matrix,
+        &uniforms.viewport_matrix,
+    )
+    .map_or(0.0, |(_, radius)| radius);
+    let radius = (body * GLOW_SPREAD).max(GLOW_RADIUS) * fade.sqrt();
+    let reach = radius.ceil() as i32;
+    for dy in -reach..=reach {
+        for dx in -reach..=reach {
+            let falloff = 1.0 - (dx as f32).hypot(dy as f32) / radius;
+            let (x, y) = (center.x as i32 + dx, center.y as i32 + dy);
+            if falloff <= 0.0 || x < 0 || y < 0 {
+                continue;
+            }
+            let radiance = GLOW * falloff * falloff * fade;
+            framebuffer.add_radiance(x as usize, y as usize, front.z - 1e-5, radiance);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use crate::error::{Error, Result};
+use crate::shaders::ShaderType;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+
+// Languages with a file in LOCALE_DIR, for --lang
+pub const LANGUAGES: [&str; 2] = ["en", "es"];
+
+const LOCALE_DIR: &str = "assets/locales";
+// Built in, so the viewer still has its words when it's run from somewhere
+// LOCALE_DIR isn't
+const ENGLISH: &str = include_str!("../assets/locales/en.txt");
+
+// Everything the window and the console say to the user, looked up by key
+// from a file of `key = text` lines, one file per language; `#` starts a
+// comment line. English is built in and the chosen language is read over
+// it, so a key a translation hasn't caught up with comes out in English
+// rather than not at all. Each `{}` in a text is filled in by `format`, in
+// order.
+pub struct Locale {
+    texts: HashMap<String, String>,
+}
+
+impl Locale {
+    pub fn english() -> Self {
+        let mut texts = HashMap::new();
+        read_texts(&mut texts, "en.txt", ENGLISH);
+        Locale { texts }
+    }
+
+    pub fn load(language: &str) -> Result<Self> {
+        let mut locale = Locale::english();
+        if language != "en" {
+            let path = format!("{}/{}.txt", LOCALE_DIR, language);
+            let contents = fs::read_to_string(&path).map_err(Error::io(&path))?;
+            read_texts(&mut locale.texts, &path, &contents);
+        }
+        Ok(locale)
+    }
+
+    // The text for `key`, or the key itself when no file has it so the gap
+    // shows on screen
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.texts.get(key).map_or(key, String::as_str)
+    }
+
+    pub fn format(&self, key: &str, args: &[&dyn Display]) -> String {
+        let mut pieces = self.get(key).split("{}");
+        let mut text = pieces.next().unwrap_or_default().to_string();
+        for (index, piece) in pieces.enumerate() {
+            if let Some(arg) = args.get(index) {
+                text += &arg.to_string();
+            }
+            text += piece;
+        }
+        text
+    }
+
+    // Bodies are known in the code by their English names, e.g. "Inner moon"
+    // under `body.inner_moon`. Ones from a scene file with no key of their
+    // own keep the name they were given.
+    pub fn body_name(&self, name: &str) -> String {
+        let key = format!("body.{}", snake_case(name));
+        self.texts
+            .get(&key)
+            .map_or(name, String::as_str)
+            .to_string()
+    }
+
+    // Under `shader.` and the variant's name, e.g. `shader.gas_giant`
+    pub fn shader_name(&self, shader: ShaderType) -> String {
+        self.get(&format!("shader.{}", snake_case(&format!("{:?}", shader))))
+            .to_string()
+    }
+}
+
+fn read_texts(texts: &mut HashMap<String, String>, path: &str, contents: &str) {
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((key, text)) => {
+                texts.insert(key.trim().to_string(), text.trim().to_string());
+            }
+            None => eprintln!("{}:{}: skipping '{}'", path, number + 1, line),
+        }
+    }
+}
+
+// "Inner moon" and "InnerMoon" both become "inner_moon"
+pub fn snake_case(name: &str) -> String {
+    let mut key = String::new();
+    for c in name.chars() {
+        if c == ' ' {
+            key.push('_');
+        } else if c.is_uppercase() {
+            if !key.is_empty() && !key.ends_with('_') {
+                key.push('_');
+            }
+            key.extend(c.to_lowercase());
+        } else {
+            key.push(c);
+        }
+    }
+    key
+}
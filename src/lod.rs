@@ -0,0 +1,47 @@
+use crate::obj::Obj;
+use crate::vertex::Vertex;
+
+// Decimated copies of a model, each level with half the triangles of the
+// previous one. Level 0 is the model as loaded.
+pub struct LodChain {
+    levels: Vec<Vec<Vertex>>,
+    full_detail_radius: f32,
+}
+
+impl LodChain {
+    pub fn build(obj: &Obj, level_count: usize, min_triangles: usize) -> Self {
+        let mut levels = vec![obj.get_vertex_array()];
+        let mut triangles = obj.triangle_count();
+
+        for _ in 1..level_count {
+            triangles /= 2;
+            if triangles < min_triangles {
+                break;
+            }
+            let mut reduced = obj.clone();
+            reduced.decimate(triangles);
+            levels.push(reduced.get_vertex_array());
+        }
+
+        LodChain {
+            levels,
+            full_detail_radius: 150.0,
+        }
+    }
+
+    // Picks a level from the body's projected radius in pixels: covered area
+    // shrinks with radius squared, so every halving of area drops one level
+    pub fn select(&self, projected_radius: f32) -> &[Vertex] {
+        &self.levels[self.level(projected_radius)]
+    }
+
+    // Which of `levels` `select` picks
+    pub fn level(&self, projected_radius: f32) -> usize {
+        let ratio = (self.full_detail_radius / projected_radius.max(1.0)).powi(2);
+        (ratio.log2().max(0.0) as usize).min(self.levels.len() - 1)
+    }
+
+    pub fn levels(&self) -> &[Vec<Vertex>] {
+        &self.levels
+    }
+}
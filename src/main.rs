@@ -1,338 +1,114 @@
+// The interactive viewer: argument parsing, the batch modes, and a window
+// or the terminal onto the library's Viewer
 
-use minifb::{Key, Window, WindowOptions};
-use nalgebra_glm::{look_at, perspective, Mat4, Vec3};
-use std::f32::consts::PI;
-use std::time::Duration;
+use lab4_gpc::frontend::{Frontend, WindowFrontend};
+use lab4_gpc::locale::Locale;
+use lab4_gpc::options::Options;
+use lab4_gpc::profiler::{self, Stage, Stopwatch};
+use lab4_gpc::terminal::TerminalFrontend;
+use lab4_gpc::viewer::Viewer;
+use lab4_gpc::{bench, export, golden, sweep};
 
-mod camera;
-mod color;
-mod fragment;
-mod framebuffer;
-mod obj;
-mod shaders;
-mod triangle;
-mod vertex;
-
-use camera::Camera;
-use fastnoise_lite::{FastNoiseLite, NoiseType};
-use framebuffer::Framebuffer;
-use obj::Obj;
-use shaders::{fragment_shader, vertex_shader, ShaderType};
-use triangle::triangle;
-use vertex::Vertex;
-
-pub struct Uniforms {
-    model_matrix: Mat4,
-    view_matrix: Mat4,
-    projection_matrix: Mat4,
-    viewport_matrix: Mat4,
-    time: u32,
-    noise: FastNoiseLite,
-}
-
-fn create_noise() -> FastNoiseLite {
-    create_cloud_noise()
-}
-
-fn create_cloud_noise() -> FastNoiseLite {
-    let mut noise = FastNoiseLite::with_seed(1337);
-    noise.set_noise_type(Some(NoiseType::OpenSimplex2));
-    noise
-}
-
-fn create_model_matrix(translation: Vec3, scale: f32, rotation: Vec3) -> Mat4 {
-    let (sin_x, cos_x) = rotation.x.sin_cos();
-    let (sin_y, cos_y) = rotation.y.sin_cos();
-    let (sin_z, cos_z) = rotation.z.sin_cos();
-
-    let rotation_matrix_x = Mat4::new(
-        1.0, 0.0, 0.0, 0.0, 0.0, cos_x, -sin_x, 0.0, 0.0, sin_x, cos_x, 0.0, 0.0, 0.0, 0.0, 1.0,
-    );
-
-    let rotation_matrix_y = Mat4::new(
-        cos_y, 0.0, sin_y, 0.0, 0.0, 1.0, 0.0, 0.0, -sin_y, 0.0, cos_y, 0.0, 0.0, 0.0, 0.0, 1.0,
-    );
-
-    let rotation_matrix_z = Mat4::new(
-        cos_z, -sin_z, 0.0, 0.0, sin_z, cos_z, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
-    );
-
-    let rotation_matrix = rotation_matrix_z * rotation_matrix_y * rotation_matrix_x;
-
-    let transform_matrix = Mat4::new(
-        scale,
-        0.0,
-        0.0,
-        translation.x,
-        0.0,
-        scale,
-        0.0,
-        translation.y,
-        0.0,
-        0.0,
-        scale,
-        translation.z,
-        0.0,
-        0.0,
-        0.0,
-        1.0,
-    );
-
-    transform_matrix * rotation_matrix
-}
-
-fn create_view_matrix(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
-    look_at(&eye, &center, &up)
-}
-
-fn create_perspective_matrix(window_width: f32, window_height: f32) -> Mat4 {
-    let fov = 45.0 * PI / 180.0;
-    let aspect_ratio = window_width / window_height;
-    let near = 0.1;
-    let far = 1000.0;
-
-    perspective(fov, aspect_ratio, near, far)
-}
-
-fn create_viewport_matrix(width: f32, height: f32) -> Mat4 {
-    Mat4::new(
-        width / 2.0,
-        0.0,
-        0.0,
-        width / 2.0,
-        0.0,
-        -height / 2.0,
-        0.0,
-        height / 2.0,
-        0.0,
-        0.0,
-        1.0,
-        0.0,
-        0.0,
-        0.0,
-        0.0,
-        1.0,
-    )
-}
-
-fn render(
-    framebuffer: &mut Framebuffer,
-    uniforms: &Uniforms,
-    vertex_array: &[Vertex],
-    current_shader: &ShaderType,
-) {
-    // Vertex Shader
-    let mut transformed_vertices = Vec::with_capacity(vertex_array.len());
-    for vertex in vertex_array {
-        let transformed = vertex_shader(vertex, uniforms);
-        transformed_vertices.push(transformed);
-    }
-
-    // Primitive Assembly
-    let mut triangles = Vec::new();
-    for i in (0..transformed_vertices.len()).step_by(3) {
-        if i + 2 < transformed_vertices.len() {
-            triangles.push([
-                transformed_vertices[i].clone(),
-                transformed_vertices[i + 1].clone(),
-                transformed_vertices[i + 2].clone(),
-            ]);
+fn main() {
+    let options = Options::from_args();
+    // Assets that couldn't be loaded and what was used in their place
+    let mut asset_warnings = Vec::new();
+    let locale = Locale::load(&options.language).unwrap_or_else(|err| {
+        asset_warnings.push(err);
+        Locale::english()
+    });
+    if !options.sweep.is_empty() {
+        match sweep::render_contact_sheet(
+            &options.sweep,
+            &options.sweep_path,
+            options.threads,
+            options.reversed_z,
+        ) {
+            Ok(()) => println!(
+                "{}",
+                locale.format("status.sweep_saved", &[&options.sweep_path])
+            ),
+            Err(err) => {
+                eprintln!("Parameter sweep failed: {}", err);
+                std::process::exit(1);
+            }
         }
+        return;
     }
-
-    // Rasterization
-    let mut fragments = Vec::new();
-    for tri in &triangles {
-        fragments.extend(triangle(&tri[0], &tri[1], &tri[2]));
-    }
-
-    // Fragment Processing
-    for fragment in fragments {
-        let x = fragment.position.x as usize;
-        let y = fragment.position.y as usize;
-
-        if x < framebuffer.width && y < framebuffer.height {
-            let shaded_color = fragment_shader(&fragment, &uniforms, current_shader);
-            let color = shaded_color.to_hex();
-            framebuffer.set_current_color(color);
-            framebuffer.point(x, y, fragment.depth);
+    if let Some(frames) = options.bench_frames {
+        let size = options.render_size.unwrap_or((800, 600));
+        if let Err(err) = bench::run(frames, size, options.threads, options.reversed_z, &locale) {
+            eprintln!("Benchmark failed: {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if options.golden || options.golden_update {
+        match golden::run(options.golden_update, &locale) {
+            Ok(true) => {}
+            Ok(false) => std::process::exit(1),
+            Err(err) => {
+                eprintln!("Golden image check failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if let Some(model) = options.export {
+        match export::save(model, &options.export_path) {
+            Ok(triangles) => println!(
+                "{}",
+                locale.format("status.exported", &[&triangles, &options.export_path])
+            ),
+            Err(err) => {
+                eprintln!("Mesh export failed: {}", err);
+                std::process::exit(1);
+            }
         }
+        return;
     }
-}
-
-fn main() {
     let window_width = 800;
     let window_height = 600;
-    let framebuffer_width = 800;
-    let framebuffer_height = 600;
-    let frame_delay = Duration::from_millis(16);
-
-    let mut framebuffer = Framebuffer::new(framebuffer_width, framebuffer_height);
-    let mut window = Window::new(
-        "Celestial Bodies",
-        window_width,
-        window_height,
-        WindowOptions::default(),
-    )
-    .unwrap();
-
-    window.set_position(500, 500);
-    window.update();
-
-    framebuffer.set_background_color(0x000000);
-
-    // model position
-    let translation = Vec3::new(0.0, 0.0, 0.0);
-    let rotation = Vec3::new(0.0, 0.0, 0.0);
-    let scale = 1.0f32;
-
-    // camera parameters
-    let mut camera = Camera::new(
-        Vec3::new(0.0, 0.0, 5.0),
-        Vec3::new(0.0, 0.0, 0.0),
-        Vec3::new(0.0, 1.0, 0.0),
-    );
-
-    let obj = Obj::load("assets/models/sphere.obj").expect("Failed to load obj");
-    let vertex_arrays = obj.get_vertex_array();
-    let moon_obj = Obj::load("assets/models/moon.obj").expect("Failed to load moon obj");
-    let moon_vertex_array = moon_obj.get_vertex_array();
-
-    let mut current_shader = ShaderType::RockyPlanet;
-    let mut time = 0;
-
-    while window.is_open() {
-        if window.is_key_down(Key::Escape) {
-            break;
-        }
-
-        if window.is_key_down(Key::Key1) {
-            current_shader = ShaderType::GasGiant;
-        }
-        if window.is_key_down(Key::Key2) {
-            current_shader = ShaderType::ColdGasGiant;
-        }
-        if window.is_key_down(Key::Key3) {
-            current_shader = ShaderType::Solar;
-        }
-        if window.is_key_down(Key::Key4) {
-            current_shader = ShaderType::RockyPlanet;
+    let mut frontend: Box<dyn Frontend> = if options.terminal {
+        match TerminalFrontend::open() {
+            Ok(terminal) => Box::new(terminal),
+            Err(err) => {
+                eprintln!("--terminal: {}", err);
+                std::process::exit(2);
+            }
         }
-        if window.is_key_down(Key::Key5) {
-            current_shader = ShaderType::RockyPlanetVariant;
+    } else {
+        Box::new(WindowFrontend::open(
+            locale.get("window.title"),
+            (window_width, window_height),
+            options.fullscreen_size,
+        ))
+    };
+
+    // Frames are rendered at the frontend's size unless --render-size says
+    // otherwise, which a terminal has few enough pixels for. F11 or resizing
+    // the terminal changes what they're scaled to from then on.
+    let mut viewer = match Viewer::new(options, locale, frontend.size(), asset_warnings) {
+        Ok(viewer) => viewer,
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
         }
-        if window.is_key_down(Key::Key6) {
-            current_shader = ShaderType::AlienPlanet;
+    };
+    while frontend.is_open() && viewer.is_running() {
+        let input = frontend.poll();
+        let frame = viewer.frame(&input);
+        let mut showing = Stopwatch::new();
+        showing.time(|| {
+            frontend.show(frame);
+        });
+        profiler::add(Stage::Present, showing.elapsed());
+        for message in viewer.take_messages() {
+            frontend.status(&message);
         }
-        if window.is_key_down(Key::Key7) {
-            current_shader = ShaderType::GlacialTextured;
-        }
-
-        time += 1;
-
-        handle_input(&window, &mut camera);
-
-        framebuffer.clear();
-
-        let noise = create_noise();
-        let model_matrix = create_model_matrix(translation, scale, rotation);
-        let view_matrix = create_view_matrix(camera.eye, camera.center, camera.up);
-        let projection_matrix =
-            create_perspective_matrix(window_width as f32, window_height as f32);
-        let viewport_matrix =
-            create_viewport_matrix(framebuffer_width as f32, framebuffer_height as f32);
-        let uniforms = Uniforms {
-            model_matrix,
-            view_matrix,
-            projection_matrix,
-            viewport_matrix,
-            time,
-            noise,
-        };
-
-        framebuffer.set_current_color(0xFFDDDD);
-        render(&mut framebuffer, &uniforms, &vertex_arrays, &current_shader);
-
-        if current_shader == ShaderType::RockyPlanet {
-            let orbit_radius = 2.0; 
-            let orbit_speed = 0.005; 
-            let moon_x = orbit_radius * (time as f32 * orbit_speed).cos();
-            let moon_z = orbit_radius * (time as f32 * orbit_speed).sin();
-
-            let moon_translation = Vec3::new(moon_x, 0.0, moon_z);
-            let moon_scale = 0.15; 
-            let moon_model_matrix =
-                create_model_matrix(moon_translation, moon_scale, Vec3::new(0.0, 0.0, 0.0));
-
-            let moon_noise = create_noise(); 
-
-            let moon_uniforms = Uniforms {
-                model_matrix: moon_model_matrix,
-                view_matrix: uniforms.view_matrix,
-                projection_matrix: uniforms.projection_matrix,
-                viewport_matrix: uniforms.viewport_matrix,
-                time: uniforms.time,
-                noise: moon_noise, 
-            };
-            render(
-                &mut framebuffer,
-                &moon_uniforms,
-                &moon_vertex_array,
-                &ShaderType::Moon,
-                
-            );
+        if viewer.take_fullscreen_toggle() {
+            frontend.toggle_fullscreen();
         }
-
-        window
-            .update_with_buffer(&framebuffer.buffer, framebuffer_width, framebuffer_height)
-            .unwrap();
-
-        std::thread::sleep(frame_delay);
-    }
-}
-
-fn handle_input(window: &Window, camera: &mut Camera) {
-    let movement_speed = 1.0;
-    let rotation_speed = PI / 50.0;
-    let zoom_speed = 0.1;
-
-    //  camera orbit controls
-    if window.is_key_down(Key::Left) {
-        camera.orbit(rotation_speed, 0.0);
-    }
-    if window.is_key_down(Key::Right) {
-        camera.orbit(-rotation_speed, 0.0);
-    }
-    if window.is_key_down(Key::W) {
-        camera.orbit(0.0, -rotation_speed);
-    }
-    if window.is_key_down(Key::S) {
-        camera.orbit(0.0, rotation_speed);
-    }
-
-    // Camera movement controls
-    let mut movement = Vec3::new(0.0, 0.0, 0.0);
-    if window.is_key_down(Key::A) {
-        movement.x -= movement_speed;
-    }
-    if window.is_key_down(Key::D) {
-        movement.x += movement_speed;
-    }
-    if window.is_key_down(Key::Q) {
-        movement.y += movement_speed;
-    }
-    if window.is_key_down(Key::E) {
-        movement.y -= movement_speed;
-    }
-    if movement.magnitude() > 0.0 {
-        camera.move_center(movement);
-    }
-
-    // Camera zoom controls
-    if window.is_key_down(Key::Up) {
-        camera.zoom(zoom_speed);
-    }
-    if window.is_key_down(Key::Down) {
-        camera.zoom(-zoom_speed);
+        viewer.set_window_size(frontend.size());
     }
 }
@@ -0,0 +1,92 @@
+use crate::shaders::ShaderType;
+
+// Phong parameters of a body's surface. `ambient` is a constant fill light
+// added to the sky's irradiance, `diffuse` and `specular` scale the sun's
+// contribution, and `emissive` is light the surface gives off on its own, as a
+// multiple of its color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub specular: f32,
+    pub shininess: f32,
+    pub emissive: f32,
+}
+
+impl Material {
+    // Dusty regolith with no highlight at all
+    pub const MATTE: Material = Material {
+        ambient: 0.05,
+        diffuse: 1.0,
+        specular: 0.0,
+        shininess: 1.0,
+        emissive: 0.0,
+    };
+
+    pub const ROCK: Material = Material {
+        specular: 0.05,
+        shininess: 8.0,
+        ..Material::MATTE
+    };
+
+    // Broad, faint sheen off the upper atmosphere
+    pub const GAS: Material = Material {
+        specular: 0.15,
+        shininess: 10.0,
+        ..Material::MATTE
+    };
+
+    pub const ICE: Material = Material {
+        specular: 0.6,
+        shininess: 48.0,
+        ..Material::MATTE
+    };
+
+    // The terran shader masks this down to the open water
+    pub const OCEAN: Material = Material {
+        specular: 1.6,
+        shininess: 40.0,
+        ..Material::MATTE
+    };
+
+    pub const METAL: Material = Material {
+        ambient: 0.0,
+        diffuse: 0.85,
+        specular: 0.8,
+        shininess: 20.0,
+        emissive: 0.0,
+    };
+
+    // Lights itself and ignores the sun
+    pub const STAR: Material = Material {
+        ambient: 0.0,
+        diffuse: 0.0,
+        specular: 0.0,
+        shininess: 1.0,
+        emissive: 1.6,
+    };
+
+    // What a body drawn with `shader` is made of unless its instance says
+    // otherwise
+    pub fn for_shader(shader: &ShaderType) -> Material {
+        match shader {
+            ShaderType::GasGiant | ShaderType::ColdGasGiant => Material::GAS,
+            ShaderType::Solar => Material::STAR,
+            ShaderType::RockyPlanet
+            | ShaderType::RockyPlanetVariant
+            | ShaderType::AlienPlanet
+            | ShaderType::Lava
+            | ShaderType::Generated => Material::ROCK,
+            ShaderType::GlacialTextured => Material::ICE,
+            ShaderType::Moon | ShaderType::Outline => Material::MATTE,
+            ShaderType::Terran => Material::OCEAN,
+            ShaderType::Station => Material::METAL,
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material::ROCK
+    }
+}
@@ -0,0 +1,107 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{DepthTest, Framebuffer};
+use crate::locale::Locale;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+use std::borrow::Cow;
+
+// How long a world unit is in the units distances are also read out in
+pub struct Unit {
+    pub length: f32,
+    pub name: String,
+}
+
+impl Unit {
+    // A length and what it's counted in, like 12742km or 0.0000852au
+    pub fn parse(text: &str) -> Option<Unit> {
+        let split = text.rfind(|c: char| c.is_ascii_digit() || c == '.')? + 1;
+        let (length, name) = text.split_at(split);
+        let length: f32 = length.parse().ok()?;
+        (length > 0.0 && length.is_finite() && !name.is_empty()).then(|| Unit {
+            length,
+            name: name.to_string(),
+        })
+    }
+}
+
+// The bodies clicked on while measuring, by name, so the line follows them
+// as they orbit. A third click starts a new pair.
+#[derive(Default)]
+pub struct Measurement {
+    pub bodies: Vec<Cow<'static, str>>,
+}
+
+impl Measurement {
+    pub fn pick(&mut self, name: Cow<'static, str>) {
+        if self.bodies.len() == 2 {
+            self.bodies.clear();
+        }
+        if !self.bodies.contains(&name) {
+            self.bodies.push(name);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+    }
+}
+
+// A line from `from` to `to` in the current color, hidden wherever something
+// solid is in front of it, with how far apart they are at its middle in world
+// units and in `unit`
+pub fn draw(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    from: Vec3,
+    to: Vec3,
+    unit: &Unit,
+    locale: &Locale,
+) {
+    let transform = uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+    let project = |point: Vec3| {
+        let screen = transform * Vec4::new(point.x, point.y, point.z, 1.0);
+        (screen.w > 0.0).then(|| screen.xyz() / screen.w)
+    };
+    let (Some(start), Some(end)) = (project(from), project(to)) else {
+        return;
+    };
+
+    // A sample per pixel along the line, each projected on its own so depth
+    // follows the perspective
+    let length = (end.x - start.x).abs().max((end.y - start.y).abs());
+    let steps = (length.ceil() as usize).max(1);
+    for step in 0..=steps {
+        let Some(point) = project(from.lerp(&to, step as f32 / steps as f32)) else {
+            continue;
+        };
+        if point.x < 0.0 || point.y < 0.0 {
+            continue;
+        }
+        let (x, y) = (point.x as usize, point.y as usize);
+        if framebuffer.passes_depth(x, y, point.z, DepthTest::Less) {
+            framebuffer.set_pixel(x, y);
+        }
+    }
+
+    let distance = (to - from).magnitude();
+    let real = figure(distance * unit.length);
+    let text = locale.format(
+        "hud.distance",
+        &[&format!("{:.3}", distance), &real, &unit.name],
+    );
+    let middle = (start + end) / 2.0;
+    let left = middle.x - text_width(&text, 1) as f32 / 2.0;
+    let top = middle.y - GLYPH_HEIGHT as f32 - 4.0;
+    framebuffer.draw_text(left.round() as i32, top.round() as i32, &text, 1);
+}
+
+// Three or so significant digits, without a trail of zeros on big numbers
+pub fn figure(value: f32) -> String {
+    if value >= 100.0 {
+        format!("{:.0}", value)
+    } else if value >= 1.0 {
+        format!("{:.2}", value)
+    } else {
+        format!("{:.4}", value)
+    }
+}
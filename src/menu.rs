@@ -0,0 +1,195 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::input::InputFrame;
+use crate::locale::Locale;
+use crate::shaders::ShaderType;
+use minifb::Key;
+
+// Every shader the planet can be drawn with, in the order of the digit keys.
+// The outline is left out, being only the selection's rim color.
+pub const SHADERS: [ShaderType; 12] = [
+    ShaderType::GasGiant,
+    ShaderType::ColdGasGiant,
+    ShaderType::Solar,
+    ShaderType::RockyPlanet,
+    ShaderType::RockyPlanetVariant,
+    ShaderType::AlienPlanet,
+    ShaderType::GlacialTextured,
+    ShaderType::Terran,
+    ShaderType::Lava,
+    ShaderType::Moon,
+    ShaderType::Station,
+    ShaderType::Generated,
+];
+
+// Pixels inside the panel's edge, and one row of text with room around it
+const PADDING: usize = 6;
+const LINE: usize = GLYPH_HEIGHT + 5;
+// Wide enough for the longest name in either language
+const WIDTH: usize = 180;
+const BACKGROUND: u32 = 0x101018;
+const BORDER: u32 = 0x606078;
+const HIGHLIGHT: u32 = 0x303050;
+const CURRENT: u32 = 0xFFD700;
+
+// A list of the shaders over the middle of the screen, to pick from with
+// the arrow keys or the mouse instead of remembering which digit is which.
+// While it's open it has the mouse and the keys that move the camera to
+// itself; the scene keeps running behind it.
+pub struct ShaderMenu {
+    open: bool,
+    highlighted: usize,
+    // Picked and yet to be switched to, which happens with the digit keys at
+    // the start of the next frame
+    chosen: Option<ShaderType>,
+}
+
+impl ShaderMenu {
+    pub fn new() -> Self {
+        ShaderMenu {
+            open: false,
+            highlighted: 0,
+            chosen: None,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // Opens on `current`, or closes
+    pub fn toggle(&mut self, current: ShaderType) {
+        self.open = !self.open;
+        self.highlighted = SHADERS
+            .iter()
+            .position(|&shader| shader == current)
+            .unwrap_or(0);
+    }
+
+    pub fn handle_keys(&mut self, input: &InputFrame) {
+        let count = SHADERS.len();
+        if input.is_key_pressed(Key::Up) {
+            self.highlighted = (self.highlighted + count - 1) % count;
+        }
+        if input.is_key_pressed(Key::Down) {
+            self.highlighted = (self.highlighted + 1) % count;
+        }
+        if input.is_key_pressed(Key::Enter) {
+            self.choose();
+        }
+        if input.is_key_pressed(Key::Backspace) {
+            self.open = false;
+        }
+    }
+
+    // Highlights the row under the cursor and picks it on a press. Returns
+    // whether the cursor is over the panel, so the press isn't also taken
+    // as a click on the scene.
+    pub fn handle_mouse(
+        &mut self,
+        framebuffer: &Framebuffer,
+        mouse: Option<(f32, f32)>,
+        pressed: bool,
+    ) -> bool {
+        let Some((x, y)) = mouse else {
+            return false;
+        };
+        let panel = panel(framebuffer);
+        let inside = |value: f32, start: usize, length: usize| {
+            value >= start as f32 && value < (start + length) as f32
+        };
+        if !inside(x, panel.x, panel.width) || !inside(y, panel.y, panel.height) {
+            return false;
+        }
+        let row = (y as usize)
+            .checked_sub(first_row(&panel))
+            .map(|y| y / LINE);
+        if let Some(row) = row.filter(|&row| row < SHADERS.len()) {
+            self.highlighted = row;
+            if pressed {
+                self.choose();
+            }
+        }
+        true
+    }
+
+    // The shader picked since the last call, if any
+    pub fn take_choice(&mut self) -> Option<ShaderType> {
+        self.chosen.take()
+    }
+
+    // Drawn over the resolved frame, with `current` marked
+    pub fn draw(&self, framebuffer: &mut Framebuffer, locale: &Locale, current: ShaderType) {
+        let panel = panel(framebuffer);
+        framebuffer.reset_scissor();
+        framebuffer.set_current_color(BORDER);
+        framebuffer.fill_rect(Rect {
+            x: panel.x - 1,
+            y: panel.y - 1,
+            width: panel.width + 2,
+            height: panel.height + 2,
+        });
+        framebuffer.set_current_color(BACKGROUND);
+        framebuffer.fill_rect(panel);
+
+        let left = (panel.x + PADDING) as i32;
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.draw_text(
+            left,
+            (panel.y + PADDING) as i32,
+            locale.get("menu.title"),
+            1,
+        );
+
+        let top = first_row(&panel);
+        for (index, &shader) in SHADERS.iter().enumerate() {
+            let y = top + index * LINE;
+            if index == self.highlighted {
+                framebuffer.set_current_color(HIGHLIGHT);
+                framebuffer.fill_rect(Rect {
+                    x: panel.x,
+                    y,
+                    width: panel.width,
+                    height: LINE,
+                });
+            }
+            let color = if shader == current { CURRENT } else { 0xFFFFFF };
+            // Numbered with the digit key that also switches to it
+            let name = locale.shader_name(shader);
+            let label = match index {
+                0..=8 => format!("{}  {}", index + 1, name),
+                _ => format!("   {}", name),
+            };
+            framebuffer.set_current_color(color);
+            framebuffer.draw_text(left, (y + (LINE - GLYPH_HEIGHT) / 2) as i32, &label, 1);
+        }
+
+        let help = locale.get("menu.help");
+        let help_x = framebuffer.width.saturating_sub(text_width(help, 1)) / 2;
+        let help_y = panel.y + panel.height + PADDING;
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.draw_text(help_x as i32, help_y as i32, help, 1);
+    }
+
+    fn choose(&mut self) {
+        self.chosen = Some(SHADERS[self.highlighted]);
+        self.open = false;
+    }
+}
+
+// Centered on the screen, with a title line over the rows
+fn panel(framebuffer: &Framebuffer) -> Rect {
+    let width = WIDTH.min(framebuffer.width.saturating_sub(2));
+    let height =
+        (PADDING * 2 + LINE * (SHADERS.len() + 1)).min(framebuffer.height.saturating_sub(2));
+    Rect {
+        x: (framebuffer.width - width) / 2,
+        y: (framebuffer.height - height) / 2,
+        width,
+        height,
+    }
+}
+
+fn first_row(panel: &Rect) -> usize {
+    panel.y + PADDING + LINE
+}
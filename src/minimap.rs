@@ -0,0 +1,176 @@
+use crate::camera::Camera;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::kepler::KeplerOrbit;
+use crate::locale::Locale;
+use crate::picking::PickTarget;
+use crate::sprite::{icons, DOT, GLOW};
+use nalgebra_glm::{rotate_vec2, Vec2, Vec3};
+
+const SIZE: usize = 160;
+const MARGIN: usize = 8;
+// Left under the map so the timeline and its frame count still show
+const BOTTOM: usize = 40;
+// Points each orbit is drawn through, evenly in time, so enough of them land
+// in the quick swing past the closest approach
+const ORBIT_SEGMENTS: usize = 256;
+// Pixels out from the eye the sides of the view are drawn to
+const FRUSTUM_LENGTH: f32 = 36.0;
+
+const BACKGROUND: u32 = 0x101018;
+const BORDER: u32 = 0x606078;
+const ORBIT: u32 = 0x405070;
+const BODY: u32 = 0xC0C8D0;
+const SELECTED: u32 = 0xFFD700;
+const SUN: u32 = 0xFFC040;
+const CAMERA: u32 = 0x80C0FF;
+
+// The system seen from straight above, +X to the right and +Z down, in the
+// bottom right corner: a ring for each of `orbits`, drawn around the parent
+// at its center, a dot for each of `bodies`, the selected one brighter, and
+// the camera with the two sides of its view. The sun is only a direction, so
+// it sits on the edge of the map the way `sun` points. The map is scaled so
+// the widest orbit fits, and anything past it is cut off at the edge, but
+// the camera is kept on the edge so its view can still be followed.
+#[allow(clippy::too_many_arguments)]
+pub fn draw(
+    framebuffer: &mut Framebuffer,
+    locale: &Locale,
+    bodies: &[PickTarget],
+    orbits: &[(Vec3, KeplerOrbit)],
+    selected: Option<&str>,
+    sun: Vec3,
+    camera: &Camera,
+    fov: f32,
+) {
+    let Some(origin) = bodies.first().map(|body| body.center) else {
+        return;
+    };
+    let size = SIZE.min(framebuffer.width.saturating_sub(MARGIN * 2));
+    if size < 2 * MARGIN || framebuffer.height < size + BOTTOM + MARGIN {
+        return;
+    }
+    let panel = Rect {
+        x: framebuffer.width - size - MARGIN,
+        y: framebuffer.height - size - BOTTOM,
+        width: size,
+        height: size,
+    };
+    framebuffer.reset_scissor();
+    framebuffer.set_current_color(BORDER);
+    framebuffer.fill_rect(Rect {
+        x: panel.x - 1,
+        y: panel.y - 1,
+        width: panel.width + 2,
+        height: panel.height + 2,
+    });
+    framebuffer.set_current_color(BACKGROUND);
+    framebuffer.fill_rect(panel);
+    framebuffer.set_scissor(panel.x, panel.y, panel.width, panel.height);
+
+    let center = Vec2::new(
+        panel.x as f32 + panel.width as f32 / 2.0,
+        panel.y as f32 + panel.height as f32 / 2.0,
+    );
+    let half = size as f32 / 2.0 - 6.0;
+    let reach = orbits
+        .iter()
+        .map(|(parent, orbit)| {
+            flat(parent - origin).norm() + orbit.semi_major_axis * (1.0 + orbit.eccentricity)
+        })
+        .chain(bodies.iter().map(|body| flat(body.center - origin).norm()))
+        .fold(f32::EPSILON, f32::max);
+    let scale = half / reach;
+    let to_map = |point: Vec3| center + flat(point - origin) * scale;
+
+    framebuffer.set_current_color(ORBIT);
+    for (parent, orbit) in orbits {
+        let points: Vec<Vec2> = (0..=ORBIT_SEGMENTS)
+            .map(|step| {
+                let time = (step as f32 / ORBIT_SEGMENTS as f32 * orbit.period) as u32;
+                to_map(parent + orbit.position(time))
+            })
+            .collect();
+        for pair in points.windows(2) {
+            draw_line(framebuffer, pair[0], pair[1]);
+        }
+    }
+
+    let sun_direction = flat(sun);
+    if sun_direction.norm() > 1e-4 {
+        let sun_at = center + sun_direction.normalize() * half;
+        framebuffer.set_current_color(SUN);
+        draw_icon(framebuffer, GLOW, sun_at, 7.0);
+    }
+
+    for body in bodies {
+        let chosen = Some(&*body.name) == selected;
+        framebuffer.set_current_color(if chosen { SELECTED } else { BODY });
+        let radius = (body.radius * scale).clamp(1.0, 6.0);
+        let radius = if chosen { radius + 1.0 } else { radius };
+        draw_dot(framebuffer, to_map(body.center), radius);
+    }
+
+    let mut eye = to_map(camera.eye) - center;
+    if eye.norm() > half {
+        eye = eye.normalize() * half;
+    }
+    let eye = center + eye;
+    let forward = flat(camera.center - camera.eye);
+    if forward.norm() > 1e-4 {
+        let aspect = framebuffer.width as f32 / framebuffer.height.max(1) as f32;
+        let spread = ((fov / 2.0).tan() * aspect).atan();
+        let forward = forward.normalize() * FRUSTUM_LENGTH;
+        framebuffer.set_current_color(CAMERA);
+        for side in [-spread, spread] {
+            draw_line(framebuffer, eye, eye + rotate_vec2(&forward, side));
+        }
+    }
+    framebuffer.set_current_color(CAMERA);
+    draw_dot(framebuffer, eye, 2.0);
+
+    framebuffer.set_current_color(0xFFFFFF);
+    framebuffer.draw_text(
+        (panel.x + 4) as i32,
+        (panel.y + 4) as i32,
+        locale.get("hud.minimap"),
+        1,
+    );
+    framebuffer.reset_scissor();
+}
+
+// Where a point falls seen from above, X across and Z down
+fn flat(point: Vec3) -> Vec2 {
+    Vec2::new(point.x, point.z)
+}
+
+fn plot(framebuffer: &mut Framebuffer, point: Vec2) {
+    if point.x >= 0.0 && point.y >= 0.0 {
+        framebuffer.set_pixel(point.x as usize, point.y as usize);
+    }
+}
+
+fn draw_line(framebuffer: &mut Framebuffer, from: Vec2, to: Vec2) {
+    let steps = (to - from).abs().max().ceil().max(1.0) as usize;
+    for step in 0..=steps {
+        plot(framebuffer, from.lerp(&to, step as f32 / steps as f32));
+    }
+}
+
+fn draw_dot(framebuffer: &mut Framebuffer, center: Vec2, radius: f32) {
+    draw_icon(framebuffer, DOT, center, radius);
+}
+
+// One of the shared icons in the current color, centered on `center` and
+// `radius` pixels out to its edge
+fn draw_icon(framebuffer: &mut Framebuffer, icon: Rect, center: Vec2, radius: f32) {
+    let size = radius * 2.0 + 1.0;
+    let corner = center - Vec2::repeat(size / 2.0);
+    framebuffer.draw_sprite(
+        icons(),
+        icon,
+        corner.x.round() as i32,
+        corner.y.round() as i32,
+        size / icon.width as f32,
+        None,
+    );
+}
@@ -0,0 +1,107 @@
+use crate::framebuffer::Framebuffer;
+use crate::Uniforms;
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+
+// Points each pixel is averaged over, along the way it moved
+const SAMPLES: usize = 8;
+// Share of the frame the shutter is open for. All of it would smear a pixel
+// over all the way it moved, which reads as too soft.
+const SHUTTER: f32 = 0.5;
+// Longest a smear gets, in pixels. Farther than CUT_PIXELS is taken for a
+// jump, like a rewind or a new focus, and not smeared at all.
+const MAX_PIXELS: f32 = 24.0;
+const CUT_PIXELS: f32 = 200.0;
+
+// Smears each view along how far its pixels moved on screen since the frame
+// before, so a camera warp or a sped-up playback looks like motion instead of
+// a string of still frames at the renderer's rate. Each pixel is taken back
+// through the depth buffer to where it is in the world and on through last
+// frame's camera to where it was on screen then; the sky goes by direction
+// alone. Only the camera's motion is followed, so bodies moving by on their
+// own stay sharp.
+pub struct MotionBlur {
+    pub enabled: bool,
+    // World to screen for each view last frame
+    previous: Vec<Mat4>,
+    scratch: Vec<Vec3>,
+}
+
+impl MotionBlur {
+    pub fn new() -> Self {
+        MotionBlur {
+            enabled: false,
+            previous: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.previous.clear();
+    }
+
+    // Blurs the view `index` of the frame, just drawn with `uniforms` inside
+    // the framebuffer's scissor. Its first frame, or the first after the
+    // views changed, only keeps the camera for the next.
+    pub fn apply(&mut self, framebuffer: &mut Framebuffer, uniforms: &Uniforms, index: usize) {
+        if !self.enabled {
+            return;
+        }
+        let current = uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+        if index >= self.previous.len() {
+            self.previous.resize(index + 1, current);
+        }
+        let previous = std::mem::replace(&mut self.previous[index], current);
+        let Some(inverse) = current.try_inverse() else {
+            return;
+        };
+        if previous == current {
+            return;
+        }
+
+        let scissor = framebuffer.scissor();
+        self.scratch.clone_from(&framebuffer.hdr);
+        // Any depth in front of the eye works for finding the sky's direction
+        let mid_depth = uniforms.viewport_matrix[(2, 3)];
+        for y in scissor.min_y..scissor.max_y {
+            for x in scissor.min_x..scissor.max_x {
+                let index = y * framebuffer.width + x;
+                let here = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let depth = framebuffer.zbuffer[index];
+                let point = if depth.is_finite() {
+                    let world = inverse * Vec4::new(here.x, here.y, depth, 1.0);
+                    (world / world.w).xyz().push(1.0)
+                } else {
+                    let world = inverse * Vec4::new(here.x, here.y, mid_depth, 1.0);
+                    ((world / world.w).xyz() - uniforms.eye).push(0.0)
+                };
+                let then = previous * point;
+                if then.w <= f32::EPSILON {
+                    continue;
+                }
+                let motion = (then.xy() / then.w - here) * SHUTTER;
+                let length = motion.magnitude();
+                if length < 0.5 || length * 2.0 > CUT_PIXELS {
+                    continue;
+                }
+                let motion = motion * (MAX_PIXELS / length).min(1.0);
+
+                let mut sum = Vec3::zeros();
+                let mut count = 0.0;
+                for step in 0..SAMPLES {
+                    let at = here + motion * (step as f32 / (SAMPLES - 1) as f32);
+                    let (sx, sy) = (at.x.floor() as i64, at.y.floor() as i64);
+                    let inside = sx >= scissor.min_x as i64
+                        && sx < scissor.max_x as i64
+                        && sy >= scissor.min_y as i64
+                        && sy < scissor.max_y as i64;
+                    if inside {
+                        sum += self.scratch[sy as usize * framebuffer.width + sx as usize];
+                        count += 1.0;
+                    }
+                }
+                framebuffer.hdr[index] = sum / count;
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::kepler::KeplerOrbit;
+use crate::satellite::{MoonSystem, Satellite};
+use crate::GRAVITY;
+use nalgebra_glm::Vec3;
+use std::f32::consts::TAU;
+
+// Steps of the integrator per frame, so a close pass between two bodies is
+// followed through instead of jumped over
+const SUBSTEPS: u32 = 8;
+// Distance in world units below which the pull stops growing, so bodies that
+// meet pass through each other rather than fling each other off at any speed
+const SOFTENING: f32 = 0.05;
+
+// A point mass the integrator moves
+#[derive(Clone)]
+struct Body {
+    // In planet masses
+    mass: f32,
+    position: Vec3,
+    velocity: Vec3,
+    // The body whose frame this one's is attached under, or None for the
+    // planet
+    parent: Option<usize>,
+}
+
+// The planet and a moon system moving under each other's gravity instead of
+// along the orbits they were given, for setting up systems no orbit can
+// describe and watching where they go. Every body pulls on every other one,
+// GRAVITY per planet mass.
+#[derive(Clone)]
+pub struct NBody {
+    pub moons: MoonSystem,
+    // The planet first, then each satellite in the order `satellite::attach`
+    // adds them
+    bodies: Vec<Body>,
+}
+
+impl NBody {
+    // Takes over from the orbits at `time`. Each satellite starts where its
+    // orbit has it, heading along the orbit as fast as GRAVITY would carry a
+    // body around that ellipse, which takes most of the built-in ones round
+    // sooner than their periods. Then the same velocity is taken off them
+    // all for the whole system's momentum to be zero, so it doesn't drift.
+    pub fn start(center: Vec3, moons: MoonSystem, time: u32) -> Self {
+        let mut bodies = vec![Body {
+            mass: 1.0,
+            position: center,
+            velocity: Vec3::zeros(),
+            parent: None,
+        }];
+        add(&mut bodies, &moons, 0, time);
+        let momentum: Vec3 = bodies.iter().map(|body| body.velocity * body.mass).sum();
+        let mass: f32 = bodies.iter().map(|body| body.mass).sum();
+        for body in &mut bodies {
+            body.velocity -= momentum / mass;
+        }
+        NBody { moons, bodies }
+    }
+
+    // Advances one frame with semi-implicit Euler: each body's velocity takes
+    // the pull where everything is, then its position the new velocity
+    pub fn step(&mut self) {
+        let dt = 1.0 / SUBSTEPS as f32;
+        for _ in 0..SUBSTEPS {
+            let pulls: Vec<Vec3> = (0..self.bodies.len()).map(|i| self.pull(i)).collect();
+            for (body, pull) in self.bodies.iter_mut().zip(pulls) {
+                body.velocity += pull * dt;
+                body.position += body.velocity * dt;
+            }
+        }
+    }
+
+    // Acceleration on the body at `index` from all the others
+    fn pull(&self, index: usize) -> Vec3 {
+        let position = self.bodies[index].position;
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter(|&(other, _)| other != index)
+            .map(|(_, body)| {
+                let offset = body.position - position;
+                let distance_squared = offset.magnitude_squared() + SOFTENING * SOFTENING;
+                offset * (GRAVITY * body.mass / (distance_squared * distance_squared.sqrt()))
+            })
+            .sum()
+    }
+
+    // Where each satellite is from the body it was given to orbit, in the
+    // order `satellite::attach` adds them, for placing their frames. Seen
+    // from the planet, as everything else around it is drawn, however much
+    // it's being pulled about.
+    pub fn offsets(&self) -> Vec<Vec3> {
+        self.bodies[1..]
+            .iter()
+            .map(|body| body.position - self.bodies[body.parent.unwrap_or(0)].position)
+            .collect()
+    }
+}
+
+// Adds `satellites` and their own moons around the body at `parent`, each
+// moving relative to it as fast as the vis-viva equation has it at that
+// point of its orbit
+fn add(bodies: &mut Vec<Body>, satellites: &[Satellite], parent: usize, time: u32) {
+    for satellite in satellites {
+        let orbit = &satellite.orbit;
+        let offset = orbit.position(time);
+        // Along the chord from a frame before to a frame after, which for a
+        // circle is the tangent and for an ellipse close to it
+        let before = KeplerOrbit {
+            phase: orbit.phase - TAU / orbit.period,
+            ..*orbit
+        };
+        let heading = (orbit.position(time + 1) - before.position(time))
+            .try_normalize(1e-9)
+            .unwrap_or_else(Vec3::zeros);
+        let gravity = GRAVITY * (bodies[parent].mass + satellite.mass);
+        let distance = offset.magnitude().max(SOFTENING);
+        let speed = (gravity * (2.0 / distance - 1.0 / orbit.semi_major_axis))
+            .max(0.0)
+            .sqrt();
+        let index = bodies.len();
+        bodies.push(Body {
+            mass: satellite.mass,
+            position: bodies[parent].position + offset,
+            velocity: bodies[parent].velocity + heading * speed,
+            parent: Some(parent),
+        });
+        add(bodies, &satellite.satellites, index, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kepler::Spin;
+    use crate::satellite::SatelliteMesh;
+    use crate::shaders::ShaderType;
+    use std::borrow::Cow;
+
+    const fn moon(mass: f32, orbit: KeplerOrbit, satellites: &'static [Satellite]) -> Satellite {
+        Satellite {
+            name: Cow::Borrowed("moon"),
+            mesh: SatelliteMesh::Moon,
+            shader: ShaderType::Moon,
+            noise: None,
+            scale: 0.1,
+            mass,
+            orbit,
+            spin: Spin {
+                rate: 0.0,
+                axial_tilt: 0.0,
+            },
+            satellites: Cow::Borrowed(satellites),
+        }
+    }
+
+    const fn orbit(
+        semi_major_axis: f32,
+        eccentricity: f32,
+        inclination: f32,
+        period: f32,
+    ) -> KeplerOrbit {
+        KeplerOrbit {
+            semi_major_axis,
+            eccentricity,
+            inclination,
+            ascending_node: 0.7,
+            argument_of_periapsis: 0.3,
+            phase: 1.0,
+            period,
+        }
+    }
+
+    // A heavy moon with one of its own and a light one further out, on
+    // leaning, eccentric orbits
+    static SYSTEM: [Satellite; 2] = [
+        moon(0.05, orbit(1.5, 0.1, 0.2, 900.0), &SUBMOON),
+        moon(0.01, orbit(4.0, 0.2, 0.5, 2000.0), &[]),
+    ];
+    static SUBMOON: [Satellite; 1] = [moon(0.0001, orbit(0.3, 0.0, 0.0, 300.0), &[])];
+
+    static PAIR: [Satellite; 1] = [moon(0.2, orbit(1.0, 0.0, 0.4, 500.0), &[])];
+
+    fn momentum(n_body: &NBody) -> Vec3 {
+        n_body
+            .bodies
+            .iter()
+            .map(|body| body.velocity * body.mass)
+            .sum()
+    }
+
+    // Kinetic plus potential, with the potential the softened pull comes from
+    fn energy(n_body: &NBody) -> f32 {
+        let bodies = &n_body.bodies;
+        let kinetic: f32 = bodies
+            .iter()
+            .map(|body| 0.5 * body.mass * body.velocity.magnitude_squared())
+            .sum();
+        let mut potential = 0.0;
+        for (i, a) in bodies.iter().enumerate() {
+            for b in &bodies[i + 1..] {
+                let distance_squared =
+                    (a.position - b.position).magnitude_squared() + SOFTENING * SOFTENING;
+                potential -= GRAVITY * a.mass * b.mass / distance_squared.sqrt();
+            }
+        }
+        kinetic + potential
+    }
+
+    #[test]
+    fn starts_with_no_momentum() {
+        let n_body = NBody::start(Vec3::new(3.0, -1.0, 2.0), MoonSystem::BuiltIn(&SYSTEM), 77);
+        assert!(momentum(&n_body).magnitude() < 1e-7);
+    }
+
+    #[test]
+    fn starts_where_the_orbits_are() {
+        let time = 123;
+        let n_body = NBody::start(
+            Vec3::new(3.0, -1.0, 2.0),
+            MoonSystem::BuiltIn(&SYSTEM),
+            time,
+        );
+        let expected = [
+            SYSTEM[0].orbit.position(time),
+            SUBMOON[0].orbit.position(time),
+            SYSTEM[1].orbit.position(time),
+        ];
+        let offsets = n_body.offsets();
+        assert_eq!(offsets.len(), expected.len());
+        for (offset, expected) in offsets.iter().zip(expected) {
+            assert!(
+                (offset - expected).magnitude() < 1e-5,
+                "{offset:?} vs {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_its_energy_and_momentum() {
+        let mut n_body = NBody::start(Vec3::zeros(), MoonSystem::BuiltIn(&SYSTEM), 0);
+        let start = energy(&n_body);
+        for _ in 0..2000 {
+            n_body.step();
+        }
+        let drift = (energy(&n_body) - start).abs() / start.abs();
+        assert!(drift < 0.01, "energy drifted by {drift}");
+        assert!(momentum(&n_body).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn circular_orbit_closes() {
+        let mut n_body = NBody::start(Vec3::zeros(), MoonSystem::BuiltIn(&PAIR), 0);
+        let start = n_body.offsets()[0];
+        // The period gravity gives it, rather than the one the orbit was given
+        let period = TAU * (1.0 / (GRAVITY * 1.2)).sqrt();
+        let mut gap = f32::INFINITY;
+        for frame in 1..(period * 1.5) as u32 {
+            n_body.step();
+            let offset = n_body.offsets()[0];
+            assert!(
+                (offset.magnitude() - 1.0).abs() < 0.01,
+                "drifted to {}",
+                offset.magnitude()
+            );
+            if frame as f32 > period / 2.0 {
+                gap = gap.min((offset - start).magnitude());
+            }
+        }
+        assert!(gap < 0.01, "came back {gap} from where it started");
+    }
+}
@@ -0,0 +1,160 @@
+use crate::menu::SHADERS;
+use crate::shaders::ShaderType;
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use nalgebra_glm::Vec3;
+
+// How a body's noise generator is set up, built into one once when the body
+// is loaded. Above one octave FastNoiseLite itself sums that many octaves of
+// fBm into every sample, under the octaves the shaders add on top.
+#[derive(Clone, Copy, PartialEq)]
+pub struct NoiseSettings {
+    pub kind: NoiseType,
+    pub seed: i32,
+    pub frequency: f32,
+    pub octaves: u32,
+}
+
+impl NoiseSettings {
+    // What every body has unless it's given its own
+    pub const DEFAULT: NoiseSettings = NoiseSettings {
+        kind: NoiseType::OpenSimplex2,
+        seed: 1337,
+        frequency: 0.01,
+        octaves: 1,
+    };
+
+    // What a body drawn with `shader` is shaded with unless it's given its
+    // own. The moon's craters are the cells of cellular noise; the rest were
+    // tuned with the default simplex noise.
+    pub fn for_shader(shader: &ShaderType) -> NoiseSettings {
+        match shader {
+            ShaderType::Moon => NoiseSettings {
+                kind: NoiseType::Cellular,
+                ..NoiseSettings::DEFAULT
+            },
+            _ => NoiseSettings::DEFAULT,
+        }
+    }
+
+    pub fn build(&self) -> FastNoiseLite {
+        let mut noise = FastNoiseLite::with_seed(self.seed);
+        noise.set_noise_type(Some(self.kind));
+        noise.set_frequency(Some(self.frequency));
+        if self.octaves > 1 {
+            noise.set_fractal_type(Some(FractalType::FBm));
+            noise.set_fractal_octaves(Some(self.octaves as i32));
+        }
+        noise
+    }
+}
+
+// A generator for every shader, built once from `NoiseSettings::for_shader`
+pub struct NoiseRegistry {
+    generators: Vec<(ShaderType, FastNoiseLite)>,
+    // For the outline, which samples none
+    default: FastNoiseLite,
+}
+
+impl NoiseRegistry {
+    pub fn new() -> Self {
+        NoiseRegistry {
+            generators: SHADERS
+                .iter()
+                .map(|shader| (*shader, NoiseSettings::for_shader(shader).build()))
+                .collect(),
+            default: NoiseSettings::DEFAULT.build(),
+        }
+    }
+
+    pub fn get(&self, shader: ShaderType) -> &FastNoiseLite {
+        self.generators
+            .iter()
+            .find(|(registered, _)| *registered == shader)
+            .map_or(&self.default, |(_, noise)| noise)
+    }
+}
+
+// Multi-scale noise built from FastNoiseLite's single-octave samples. Each
+// octave doubles the frequency and halves the weight, and the octave count is
+// chosen per call so shaders can drop the ones a pixel can't resolve.
+
+// Fractal Brownian motion: plain octave sum, normalized to [-1, 1]
+pub fn fbm(noise: &FastNoiseLite, point: Vec3, octaves: u32) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut total_amplitude = 0.0;
+
+    for _ in 0..octaves {
+        let p = point * frequency;
+        value += noise.get_noise_3d(p.x, p.y, p.z) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    value / total_amplitude
+}
+
+// Sum of |noise|, in [0, 1]. The folds where each octave crosses zero give
+// billowy, creased shapes instead of fbm's soft blobs.
+pub fn turbulence(noise: &FastNoiseLite, point: Vec3, octaves: u32) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut total_amplitude = 0.0;
+
+    for _ in 0..octaves {
+        let p = point * frequency;
+        value += noise.get_noise_3d(p.x, p.y, p.z).abs() * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    value / total_amplitude
+}
+
+// Ridged multifractal, in [0, 1]: sharp crests where the noise crosses zero.
+// Each octave is weighted by the one before it, so detail piles up on the
+// ridges and the valleys between them stay smooth.
+pub fn ridged(noise: &FastNoiseLite, point: Vec3, octaves: u32) -> f32 {
+    let mut value = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut total_amplitude = 0.0;
+    let mut weight = 1.0;
+
+    for _ in 0..octaves {
+        let p = point * frequency;
+        let ridge = 1.0 - noise.get_noise_3d(p.x, p.y, p.z).abs();
+        let signal = ridge * ridge * weight;
+        weight = (signal * 2.0).clamp(0.0, 1.0);
+
+        value += signal * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+
+    value / total_amplitude
+}
+
+// Pushes `point` along an fbm vector field, `strength` noise units at most.
+// Sampling other noise at the result bends straight features into swirls.
+pub fn warp(noise: &FastNoiseLite, point: Vec3, strength: f32, octaves: u32) -> Vec3 {
+    let offset = Vec3::new(
+        fbm(noise, point + Vec3::new(17.0, 0.0, 0.0), octaves),
+        fbm(noise, point + Vec3::new(0.0, 53.0, 0.0), octaves),
+        fbm(noise, point + Vec3::new(0.0, 0.0, 91.0), octaves),
+    );
+    point + offset * strength
+}
+
+// Single sample on the unit sphere through `position`, `frequency` noise units
+// per radius. Reading (x, y) alone mirrors the pattern onto the far hemisphere;
+// the full 3D direction wraps around the body without a seam.
+pub fn sphere_noise(noise: &FastNoiseLite, position: &Vec3, frequency: f32) -> f32 {
+    let p = position.normalize() * frequency;
+    noise.get_noise_3d(p.x, p.y, p.z)
+}
@@ -1,11 +1,19 @@
+use crate::decimate::decimate;
+use crate::error::{self, Error};
+use crate::halfedge::HalfEdgeMesh;
 use crate::vertex::Vertex;
 use nalgebra_glm::{Vec2, Vec3};
-use tobj;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
 
+#[derive(Clone)]
 pub struct Obj {
     meshes: Vec<Mesh>,
 }
 
+#[derive(Clone)]
 struct Mesh {
     vertices: Vec<Vec3>,
     normals: Vec<Vec3>,
@@ -13,66 +21,705 @@ struct Mesh {
     indices: Vec<u32>,
 }
 
-impl Obj {
-    pub fn load(filename: &str) -> Result<Self, tobj::LoadError> {
-        let (models, _) = tobj::load_obj(
-            filename,
-            &tobj::LoadOptions {
-                single_index: true,
-                triangulate: true,
-                ..Default::default()
-            },
-        )?;
+impl Mesh {
+    // Welds corners by position so topology-aware passes see a closed surface
+    // across UV seams. Returns the welded positions and one (position, texcoord)
+    // pair per triangle corner.
+    fn welded(&self) -> (Vec<Vec3>, Vec<(usize, Vec2)>) {
+        let mut positions = Vec::new();
+        let mut lookup: HashMap<[u32; 3], usize> = HashMap::new();
+        let corners = self
+            .indices
+            .iter()
+            .map(|&index| {
+                let position = self.vertices[index as usize];
+                let key = [position.x, position.y, position.z].map(f32::to_bits);
+                let vertex = *lookup.entry(key).or_insert_with(|| {
+                    positions.push(position);
+                    positions.len() - 1
+                });
+                (vertex, self.texcoords[index as usize])
+            })
+            .collect();
+
+        (positions, corners)
+    }
+
+    // Inverse of `welded`: splits welded vertices again wherever the texcoords
+    // or normals of their corners differ
+    fn from_welded(positions: &[Vec3], corners: &[(usize, (Vec2, Vec3))]) -> Mesh {
+        let mut mesh = Mesh {
+            vertices: Vec::new(),
+            normals: Vec::new(),
+            texcoords: Vec::new(),
+            indices: Vec::new(),
+        };
+        let mut split: HashMap<(usize, [u32; 5]), u32> = HashMap::new();
+        for &(vertex, (texcoord, normal)) in corners {
+            let attributes = [texcoord.x, texcoord.y, normal.x, normal.y, normal.z];
+            let key = (vertex, attributes.map(f32::to_bits));
+            let index = *split.entry(key).or_insert_with(|| {
+                mesh.vertices.push(positions[vertex]);
+                mesh.normals.push(normal);
+                mesh.texcoords.push(texcoord);
+                (mesh.vertices.len() - 1) as u32
+            });
+            mesh.indices.push(index);
+        }
+
+        mesh
+    }
+
+    fn subdivided(&self, levels: u32) -> Mesh {
+        let (positions, corners) = self.welded();
+        let mut half_edge_mesh = HalfEdgeMesh::from_triangles(positions, &corners);
+        for _ in 0..levels {
+            half_edge_mesh = half_edge_mesh.loop_subdivide();
+        }
 
-        let meshes = models
+        let corners: Vec<(usize, Vec2)> = half_edge_mesh
+            .half_edges
+            .iter()
+            .map(|half_edge| (half_edge.origin, half_edge.texcoord))
+            .collect();
+        let normals = vertex_normals(&half_edge_mesh.positions, &corners);
+        let corners: Vec<(usize, (Vec2, Vec3))> = corners
+            .into_iter()
+            .map(|(vertex, texcoord)| (vertex, (texcoord, normals[vertex])))
+            .collect();
+        Mesh::from_welded(&half_edge_mesh.positions, &corners)
+    }
+
+    // Each corner keeps the normal it was read with, as it keeps its texcoord
+    fn decimated(&self, target_triangles: usize) -> Mesh {
+        let (positions, corners) = self.welded();
+        let corners = corners
             .into_iter()
-            .map(|model| {
-                let mesh = model.mesh;
-                Mesh {
-                    vertices: mesh
-                        .positions
-                        .chunks(3)
-                        .map(|v| Vec3::new(v[0], v[1], v[2]))
-                        .collect(),
-                    normals: mesh
-                        .normals
-                        .chunks(3)
-                        .map(|n| Vec3::new(n[0], n[1], n[2]))
-                        .collect(),
-                    texcoords: mesh
-                        .texcoords
-                        .chunks(2)
-                        .map(|t| Vec2::new(t[0], 1.0 - t[1]))
-                        .collect(),
-                    indices: mesh.indices,
+            .zip(&self.indices)
+            .map(|((vertex, texcoord), &index)| (vertex, (texcoord, self.normals[index as usize])))
+            .collect();
+        let (positions, mut corners) = decimate(positions, corners, target_triangles);
+        rejoin_normals(&mut corners);
+        Mesh::from_welded(&positions, &corners)
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "could not read obj file: {}", err),
+            ObjError::Parse { line, message } => write!(f, "obj line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ObjError::Io(err) => Some(err),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(err: io::Error) -> Self {
+        ObjError::Io(err)
+    }
+}
+
+// A face corner as (position, texcoord, normal) indices into the file-wide lists
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    mesh: Mesh,
+    lookup: HashMap<(Corner, u32), u32>,
+    // For vertices whose face had no normal: (position index, smoothing group)
+    generated: Vec<Option<(usize, u32)>>,
+}
+
+impl MeshBuilder {
+    fn new() -> Self {
+        MeshBuilder {
+            mesh: Mesh {
+                vertices: Vec::new(),
+                normals: Vec::new(),
+                texcoords: Vec::new(),
+                indices: Vec::new(),
+            },
+            lookup: HashMap::new(),
+            generated: Vec::new(),
+        }
+    }
+
+    fn push_corner(
+        &mut self,
+        corner: Corner,
+        smoothing_group: u32,
+        positions: &[Vec3],
+        texcoords: &[Vec2],
+        normals: &[Vec3],
+    ) {
+        let (v, vt, vn) = corner;
+
+        // Missing normals are generated later, so those corners are only shared
+        // within the same smoothing group, and never when smoothing is off
+        let key = (corner, if vn.is_some() { 0 } else { smoothing_group });
+        let shareable = vn.is_some() || smoothing_group != 0;
+
+        if shareable {
+            if let Some(&index) = self.lookup.get(&key) {
+                self.mesh.indices.push(index);
+                return;
+            }
+        }
+
+        let mesh = &mut self.mesh;
+        mesh.vertices.push(positions[v]);
+        mesh.texcoords
+            .push(vt.map(|i| texcoords[i]).unwrap_or(Vec2::new(0.0, 0.0)));
+        mesh.normals
+            .push(vn.map(|i| normals[i]).unwrap_or(Vec3::new(0.0, 1.0, 0.0)));
+        self.generated
+            .push(vn.is_none().then_some((v, smoothing_group)));
+
+        let index = (mesh.vertices.len() - 1) as u32;
+        if shareable {
+            self.lookup.insert(key, index);
+        }
+        mesh.indices.push(index);
+    }
+
+    // Area-weighted normals: the unnormalized cross product of each triangle
+    // is accumulated into every vertex sharing its position and smoothing group
+    fn finish(mut self) -> Mesh {
+        if self.generated.iter().all(Option::is_none) {
+            return self.mesh;
+        }
+
+        let mut smooth_sums: HashMap<(usize, u32), Vec3> = HashMap::new();
+        let mut flat_sums = vec![Vec3::zeros(); self.mesh.vertices.len()];
+
+        for tri in self.mesh.indices.chunks(3) {
+            let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let face_normal = (self.mesh.vertices[b] - self.mesh.vertices[a])
+                .cross(&(self.mesh.vertices[c] - self.mesh.vertices[a]));
+
+            for index in [a, b, c] {
+                match self.generated[index] {
+                    Some((_, 0)) => flat_sums[index] += face_normal,
+                    Some(key) => *smooth_sums.entry(key).or_insert(Vec3::zeros()) += face_normal,
+                    None => {}
                 }
+            }
+        }
+
+        for (index, generated) in self.generated.iter().enumerate() {
+            let sum = match generated {
+                Some((_, 0)) => flat_sums[index],
+                Some(key) => smooth_sums[key],
+                None => continue,
+            };
+            if sum.magnitude() > f32::EPSILON {
+                self.mesh.normals[index] = sum.normalize();
+            }
+        }
+
+        self.mesh
+    }
+}
+
+impl Obj {
+    pub fn load(filename: &str) -> error::Result<Self> {
+        fs::read_to_string(filename)
+            .map_err(ObjError::from)
+            .and_then(|source| Obj::parse(&source))
+            .map_err(|source| Error::Model {
+                path: filename.to_string(),
+                source,
             })
-            .collect();
+    }
+
+    // One mesh of a flat triangle list, for a model made in code rather than
+    // read from a file
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        let mesh = Mesh {
+            vertices: vertices.iter().map(|vertex| vertex.position).collect(),
+            normals: vertices.iter().map(|vertex| vertex.normal).collect(),
+            texcoords: vertices.iter().map(|vertex| vertex.tex_coords).collect(),
+            indices: (0..vertices.len() as u32).collect(),
+        };
+        Obj { meshes: vec![mesh] }
+    }
+
+    pub fn parse(source: &str) -> Result<Self, ObjError> {
+        let mut positions = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut normals = Vec::new();
+
+        let mut meshes = Vec::new();
+        let mut builder = MeshBuilder::new();
+        // Faces smooth together unless the file turns smoothing off with "s off"
+        let mut smoothing_group = 1;
+
+        for (line_index, raw_line) in source.lines().enumerate() {
+            let line = line_index + 1;
+            let mut tokens = raw_line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => {
+                    let v = parse_floats::<3>(tokens, line)?;
+                    positions.push(Vec3::new(v[0], v[1], v[2]));
+                }
+                Some("vt") => {
+                    let t = parse_floats::<2>(tokens, line)?;
+                    texcoords.push(Vec2::new(t[0], 1.0 - t[1]));
+                }
+                Some("vn") => {
+                    let n = parse_floats::<3>(tokens, line)?;
+                    normals.push(Vec3::new(n[0], n[1], n[2]));
+                }
+                Some("f") => {
+                    let corners = tokens
+                        .map(|token| {
+                            parse_corner(
+                                token,
+                                positions.len(),
+                                texcoords.len(),
+                                normals.len(),
+                                line,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    if corners.len() < 3 {
+                        return Err(ObjError::Parse {
+                            line,
+                            message: format!(
+                                "face needs at least 3 vertices, got {}",
+                                corners.len()
+                            ),
+                        });
+                    }
+
+                    // Fan triangulation for quads and n-gons
+                    for i in 1..corners.len() - 1 {
+                        for corner in [corners[0], corners[i], corners[i + 1]] {
+                            builder.push_corner(
+                                corner,
+                                smoothing_group,
+                                &positions,
+                                &texcoords,
+                                &normals,
+                            );
+                        }
+                    }
+                }
+                Some("s") => {
+                    smoothing_group = match tokens.next() {
+                        Some("off") | None => 0,
+                        Some(group) => group.parse().map_err(|_| ObjError::Parse {
+                            line,
+                            message: format!("invalid smoothing group '{}'", group),
+                        })?,
+                    };
+                }
+                Some("o") | Some("g") if !builder.mesh.indices.is_empty() => {
+                    meshes.push(builder.finish());
+                    builder = MeshBuilder::new();
+                }
+                _ => {}
+            }
+        }
+
+        if !builder.mesh.indices.is_empty() {
+            meshes.push(builder.finish());
+        }
 
         Ok(Obj { meshes })
     }
 
+    pub fn subdivide(&mut self, levels: u32) {
+        if levels == 0 {
+            return;
+        }
+        for mesh in &mut self.meshes {
+            *mesh = mesh.subdivided(levels);
+        }
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.meshes.iter().map(|mesh| mesh.indices.len() / 3).sum()
+    }
+
+    // Reduces every mesh proportionally so the whole model ends up with about
+    // `target_triangles` triangles
+    pub fn decimate(&mut self, target_triangles: usize) {
+        let total = self.triangle_count();
+        if target_triangles >= total {
+            return;
+        }
+        for mesh in &mut self.meshes {
+            let share = mesh.indices.len() / 3 * target_triangles / total;
+            *mesh = mesh.decimated(share.max(1));
+        }
+    }
+
+    pub fn bounding_radius(&self) -> f32 {
+        self.meshes
+            .iter()
+            .flat_map(|mesh| mesh.vertices.iter())
+            .map(|vertex| vertex.magnitude())
+            .fold(0.0, f32::max)
+    }
+
     pub fn get_vertex_array(&self) -> Vec<Vertex> {
         let mut vertices = Vec::new();
 
         for mesh in &self.meshes {
             for &index in &mesh.indices {
-                let position = mesh.vertices[index as usize];
-                let normal = mesh
-                    .normals
-                    .get(index as usize)
-                    .cloned()
-                    .unwrap_or(Vec3::new(0.0, 1.0, 0.0));
-                let tex_coords = mesh
-                    .texcoords
-                    .get(index as usize)
-                    .cloned()
-                    .unwrap_or(Vec2::new(0.0, 0.0));
-
-                vertices.push(Vertex::new(position, normal, tex_coords));
+                let index = index as usize;
+                vertices.push(Vertex::new(
+                    mesh.vertices[index],
+                    mesh.normals[index],
+                    mesh.texcoords[index],
+                ));
             }
         }
 
         vertices
     }
 }
+
+// Writes a flat triangle list as an OBJ file's text, sharing each corner that
+// repeats the same position, normal and texture coordinates. Vertex colors go
+// after the positions, as Blender reads them, unless every one is black.
+pub fn encode(vertices: &[Vertex]) -> String {
+    let colored = vertices.iter().any(|vertex| vertex.color.to_hex() != 0);
+    let mut text = String::new();
+    let mut faces = String::new();
+    let mut lookup: HashMap<[u32; 8], usize> = HashMap::new();
+    for (corner, vertex) in vertices.iter().enumerate() {
+        let (position, normal, tex_coords) = (vertex.position, vertex.normal, vertex.tex_coords);
+        let key = [
+            position.x,
+            position.y,
+            position.z,
+            normal.x,
+            normal.y,
+            normal.z,
+            tex_coords.x,
+            tex_coords.y,
+        ]
+        .map(f32::to_bits);
+        let count = lookup.len();
+        let index = *lookup.entry(key).or_insert_with(|| {
+            text += &format!("v {} {} {}", position.x, position.y, position.z);
+            if colored {
+                let color = vertex.color.to_vec3();
+                text += &format!(" {} {} {}", color.x, color.y, color.z);
+            }
+            // Flipped back from what `parse` turns them into
+            text += &format!("\nvt {} {}\n", tex_coords.x, 1.0 - tex_coords.y);
+            text += &format!("vn {} {} {}\n", normal.x, normal.y, normal.z);
+            count + 1
+        });
+        if corner % 3 == 0 {
+            faces += "f";
+        }
+        faces += &format!(" {0}/{0}/{0}", index);
+        if corner % 3 == 2 {
+            faces += "\n";
+        }
+    }
+    text + &faces
+}
+
+// Area-weighted normals over welded positions
+fn vertex_normals(positions: &[Vec3], corners: &[(usize, Vec2)]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::zeros(); positions.len()];
+    for tri in corners.chunks(3) {
+        let [a, b, c] = [tri[0].0, tri[1].0, tri[2].0];
+        let face_normal = (positions[b] - positions[a]).cross(&(positions[c] - positions[a]));
+        for vertex in [a, b, c] {
+            normals[vertex] += face_normal;
+        }
+    }
+
+    normals
+        .into_iter()
+        .map(|normal| {
+            if normal.magnitude() > f32::EPSILON {
+                normal.normalize()
+            } else {
+                Vec3::new(0.0, 1.0, 0.0)
+            }
+        })
+        .collect()
+}
+
+// Cosine of the widest angle between two normals of one vertex that still
+// counts as a smooth surface rather than a crease
+const SMOOTH_COSINE: f32 = 0.7;
+
+// A collapse hands the corners of the vertex it removes to the one it keeps,
+// normals and all, so where the surface was smooth the corners of a vertex
+// can end up a little apart. Each corner's normal becomes the average of the
+// ones at its vertex that are close to it, which closes those seams again and
+// leaves the creases.
+fn rejoin_normals(corners: &mut [(usize, (Vec2, Vec3))]) {
+    let mut at_vertex: HashMap<usize, Vec<Vec3>> = HashMap::new();
+    for &(vertex, (_, normal)) in corners.iter() {
+        at_vertex.entry(vertex).or_default().push(normal);
+    }
+    for (vertex, (_, normal)) in corners.iter_mut() {
+        let sum: Vec3 = at_vertex[vertex]
+            .iter()
+            .filter(|other| other.dot(normal) > SMOOTH_COSINE)
+            .sum();
+        if sum.magnitude() > f32::EPSILON {
+            *normal = sum.normalize();
+        }
+    }
+}
+
+fn parse_floats<'a, const N: usize>(
+    tokens: impl Iterator<Item = &'a str>,
+    line: usize,
+) -> Result<[f32; N], ObjError> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+
+    for token in tokens.take(N) {
+        values[count] = token.parse().map_err(|_| ObjError::Parse {
+            line,
+            message: format!("invalid number '{}'", token),
+        })?;
+        count += 1;
+    }
+
+    if count < N {
+        return Err(ObjError::Parse {
+            line,
+            message: format!("expected {} values, got {}", N, count),
+        });
+    }
+
+    Ok(values)
+}
+
+// Parses "v", "v/vt", "v//vn" or "v/vt/vn", resolving 1-based and negative indices
+fn parse_corner(
+    token: &str,
+    position_count: usize,
+    texcoord_count: usize,
+    normal_count: usize,
+    line: usize,
+) -> Result<Corner, ObjError> {
+    let mut parts = token.split('/');
+
+    let position = match parts.next() {
+        Some(part) if !part.is_empty() => resolve_index(part, position_count, line)?,
+        _ => {
+            return Err(ObjError::Parse {
+                line,
+                message: format!("face vertex '{}' has no position", token),
+            })
+        }
+    };
+
+    let texcoord = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, texcoord_count, line)?),
+        _ => None,
+    };
+
+    let normal = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, normal_count, line)?),
+        _ => None,
+    };
+
+    Ok((position, texcoord, normal))
+}
+
+fn resolve_index(token: &str, count: usize, line: usize) -> Result<usize, ObjError> {
+    let raw: i64 = token.parse().map_err(|_| ObjError::Parse {
+        line,
+        message: format!("invalid index '{}'", token),
+    })?;
+
+    let resolved = if raw > 0 { raw - 1 } else { count as i64 + raw };
+
+    if raw == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(ObjError::Parse {
+            line,
+            message: format!("index {} out of range ({} elements)", raw, count),
+        });
+    }
+
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A unit square in the XY plane, corners counterclockwise from the origin,
+    // and a point above its top edge
+    const POINTS: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0.5 1.5 0\n";
+    const CORNERS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.5, 1.5, 0.0],
+    ];
+
+    fn parse(source: &str) -> Obj {
+        Obj::parse(source).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn parse_error(source: &str) -> (usize, String) {
+        match Obj::parse(source) {
+            Err(ObjError::Parse { line, message }) => (line, message),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("parsed without an error"),
+        }
+    }
+
+    fn positions(obj: &Obj) -> Vec<[f32; 3]> {
+        obj.get_vertex_array()
+            .iter()
+            .map(|vertex| vertex.position.into())
+            .collect()
+    }
+
+    // The corners of POINTS at these 1-based indices
+    fn corners(indices: &[usize]) -> Vec<[f32; 3]> {
+        indices.iter().map(|&index| CORNERS[index - 1]).collect()
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_one_read() {
+        let obj = parse(&format!("{}f -5 -4 -1\n", POINTS));
+        assert_eq!(positions(&obj), corners(&[1, 2, 5]));
+
+        // Only what's been read so far counts
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf -3 -2 -1\nv 5 5 5\n");
+        assert_eq!(positions(&obj), corners(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn normals_without_texcoords() {
+        let obj = parse(&format!("{}vn 0 0 -1\nf 1//1 2//1 3//1\n", POINTS));
+        for vertex in obj.get_vertex_array() {
+            assert_eq!(vertex.normal, Vec3::new(0.0, 0.0, -1.0));
+            assert_eq!(vertex.tex_coords, Vec2::zeros());
+        }
+    }
+
+    #[test]
+    fn texcoords_are_flipped_to_count_rows_down() {
+        let obj = parse(&format!("{}vt 0.25 0.75\nf 1/1 2/1 3/1\n", POINTS));
+        assert_eq!(obj.get_vertex_array()[0].tex_coords, Vec2::new(0.25, 0.25));
+    }
+
+    #[test]
+    fn polygons_are_fanned_from_their_first_corner() {
+        let obj = parse(&format!("{}f 1 2 3 5 4\n", POINTS));
+        assert_eq!(obj.triangle_count(), 3);
+        assert_eq!(positions(&obj), corners(&[1, 2, 3, 1, 3, 5, 1, 5, 4]));
+    }
+
+    #[test]
+    fn smoothing_groups_share_corners_and_normals() {
+        // Two triangles folded along the edge from (1, 0, 0) to (0, 1, 0)
+        let fold = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 1\n";
+        let flat = Vec3::z();
+
+        let smooth = parse(&format!("{}f 1 2 3\nf 2 4 3\n", fold));
+        assert_eq!(smooth.meshes[0].vertices.len(), 4);
+        let shared = smooth.get_vertex_array()[1].normal;
+        assert!((shared.magnitude() - 1.0).abs() < 1e-5);
+        assert!(shared.dot(&flat) < 0.99, "{:?}", shared);
+
+        for source in [
+            format!("{}s off\nf 1 2 3\nf 2 4 3\n", fold),
+            format!("{}s 1\nf 1 2 3\ns 2\nf 2 4 3\n", fold),
+        ] {
+            let split = parse(&source);
+            assert_eq!(split.meshes[0].vertices.len(), 6, "{}", source);
+            for vertex in &split.get_vertex_array()[..3] {
+                assert!((vertex.normal - flat).magnitude() < 1e-5, "{}", source);
+            }
+        }
+    }
+
+    #[test]
+    fn objects_and_groups_start_new_meshes() {
+        let obj = parse(&format!(
+            "o first\n{}f 1 2 3\ng second\nf 1 3 4\no third\nf 3 5 4\n",
+            POINTS
+        ));
+        assert_eq!(obj.meshes.len(), 3);
+        assert_eq!(positions(&obj), corners(&[1, 2, 3, 1, 3, 4, 3, 5, 4]));
+
+        // A name before any faces doesn't leave an empty mesh behind
+        let obj = parse(&format!("g a\ng b\n{}f 1 2 3\n", POINTS));
+        assert_eq!(obj.meshes.len(), 1);
+    }
+
+    #[test]
+    fn indices_out_of_range_name_their_line() {
+        for (face, index) in [("f 1 2 6", "6"), ("f 0 1 2", "0"), ("f -6 1 2", "-6")] {
+            let (line, message) = parse_error(&format!("{}{}\n", POINTS, face));
+            assert_eq!(line, 6, "{}", face);
+            assert!(
+                message.contains(index) && message.contains("out of range"),
+                "{}: {}",
+                face,
+                message
+            );
+        }
+        let (line, message) = parse_error(&format!("{}vt 0 0\nf 1/2 2/1 3/1\n", POINTS));
+        assert_eq!(line, 7);
+        assert!(message.contains("out of range"), "{}", message);
+    }
+
+    #[test]
+    fn decimating_keeps_the_files_normals() {
+        // A flat 6 by 6 grid whose normals lean away from the one its winding
+        // would give it, as a file's own normals may
+        let mut source = String::from("vn 0 0.6 0.8\n");
+        for y in 0..=6 {
+            for x in 0..=6 {
+                source += &format!("v {} {} 0\n", x, y);
+            }
+        }
+        for y in 0..6 {
+            for x in 0..6 {
+                let corner = y * 7 + x + 1;
+                let [a, b, c, d] = [corner, corner + 1, corner + 8, corner + 7];
+                source += &format!("f {}//1 {}//1 {}//1 {}//1\n", a, b, c, d);
+            }
+        }
+        let mut obj = parse(&source);
+        obj.decimate(16);
+        assert!(obj.triangle_count() <= 16);
+        for vertex in obj.get_vertex_array() {
+            assert!((vertex.normal - Vec3::new(0.0, 0.6, 0.8)).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn faces_need_three_corners() {
+        let (line, message) = parse_error(&format!("{}f 1 2\n", POINTS));
+        assert_eq!(line, 6);
+        assert!(message.contains("at least 3"), "{}", message);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::blue_noise::blue_noise;
+use crate::fog::view_distance;
+use crate::framebuffer::{Framebuffer, Scissor};
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+use std::f32::consts::TAU;
+
+// Depth samples taken around each pixel
+const SAMPLES: usize = 8;
+// Farthest out a sample goes, in pixels, so a body filling the view doesn't
+// cost more than a small one
+const MAX_REACH: f32 = 24.0;
+// Cosine a neighbor has to rise above the surface by to count, so a flat or
+// gently curved surface doesn't shade itself
+const BIAS: f32 = 0.05;
+
+// Screen-space ambient occlusion, worked out from the depth buffer once a
+// view's bodies are in: each pixel looks at the depths a few pixels around
+// it, and what's there rising above the surface within `radius` darkens it,
+// so crevices in the terrain, the folds of the rocks and where a belt rock
+// passes close to something else come out darker. The normal it measures
+// against is taken from the depths of the neighbors, since the framebuffer
+// keeps no normals. It darkens the whole radiance rather than just the
+// ambient light, which isn't kept apart either.
+pub struct AmbientOcclusion {
+    pub enabled: bool,
+    // How far off, in world units, something can be and still occlude
+    pub radius: f32,
+    // How much what rises around a pixel darkens it; the darkening is
+    // clamped so the pixel never goes past black
+    pub strength: f32,
+    // Where the surface is at each pixel inside the scissor, worked out once
+    // for all the samples that land on it, and how unoccluded it is before
+    // the blur
+    positions: Vec<Option<Vec3>>,
+    unoccluded: Vec<f32>,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion {
+            enabled: false,
+            radius: 0.2,
+            strength: 2.5,
+            positions: Vec::new(),
+            unoccluded: Vec::new(),
+        }
+    }
+}
+
+impl AmbientOcclusion {
+    pub fn apply(&mut self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        if !self.enabled {
+            return;
+        }
+        let scissor = framebuffer.scissor();
+        let columns = scissor.max_x.saturating_sub(scissor.min_x);
+        let rows = scissor.max_y.saturating_sub(scissor.min_y);
+        let local = |x: usize, y: usize| (y - scissor.min_y) * columns + x - scissor.min_x;
+        self.positions.clear();
+        for y in scissor.min_y..scissor.max_y {
+            for x in scissor.min_x..scissor.max_x {
+                self.positions
+                    .push(view_position(framebuffer, uniforms, x, y));
+            }
+        }
+        self.unoccluded.clear();
+        self.unoccluded.resize(columns * rows, 1.0);
+
+        let positions = &self.positions;
+        let position = |x: usize, y: usize| positions[local(x, y)];
+        // Pixels across one world unit at one unit from the eye
+        let focal = uniforms.projection_matrix[(0, 0)] * uniforms.viewport_matrix[(0, 0)];
+        let noise = blue_noise();
+        // A spiral out from the pixel, turned differently at each one so the
+        // banding becomes fine noise for the blur
+        let spiral: [(f32, f32); SAMPLES] = std::array::from_fn(|sample| {
+            let (sin, cos) = (sample as f32 * 2.4).sin_cos();
+            let t = (sample as f32 + 0.5) / SAMPLES as f32;
+            (cos * t, sin * t)
+        });
+        // Only every other pixel each way, a quarter of them, is sampled; the
+        // blur fills in the rest
+        for y in (scissor.min_y..scissor.max_y).step_by(2) {
+            for x in (scissor.min_x..scissor.max_x).step_by(2) {
+                let Some(center) = position(x, y) else {
+                    continue;
+                };
+                let Some(normal) = depth_normal(x, y, center, &position, &scissor) else {
+                    continue;
+                };
+                let reach = (self.radius * focal / -center.z).min(MAX_REACH);
+                if reach < 1.0 {
+                    continue;
+                }
+
+                let (sin, cos) = (noise.sample(x, y) * TAU).sin_cos();
+                let mut occlusion = 0.0;
+                for &(along, across) in &spiral {
+                    let sample_x = x as f32 + (along * cos - across * sin) * reach;
+                    let sample_y = y as f32 + (along * sin + across * cos) * reach;
+                    if sample_x < scissor.min_x as f32
+                        || sample_y < scissor.min_y as f32
+                        || sample_x >= scissor.max_x as f32
+                        || sample_y >= scissor.max_y as f32
+                    {
+                        continue;
+                    }
+                    let Some(other) = position(sample_x as usize, sample_y as usize) else {
+                        continue;
+                    };
+                    let offset = other - center;
+                    let distance_squared = offset.norm_squared();
+                    let radius_squared = self.radius * self.radius;
+                    if distance_squared < 1e-8 || distance_squared > radius_squared {
+                        continue;
+                    }
+                    let rise = normal.dot(&offset) / distance_squared.sqrt() - BIAS;
+                    occlusion += rise.max(0.0) * (1.0 - distance_squared / radius_squared);
+                }
+                let unoccluded = 1.0 - self.strength * occlusion / SAMPLES as f32;
+                self.unoccluded[local(x, y)] = unoccluded.clamp(0.0, 1.0);
+            }
+        }
+
+        // Averages the sampled pixels in the 4x4 block around each one,
+        // smoothing out the noise, but leaves out those at another depth so
+        // the darkening doesn't bleed out past a body's edge
+        let sampled = |start: usize, end: usize, minimum: usize| {
+            let first = start.saturating_sub(1).max(minimum);
+            (first + (first - minimum) % 2..(start + 3).min(end)).step_by(2)
+        };
+        for y in scissor.min_y..scissor.max_y {
+            for x in scissor.min_x..scissor.max_x {
+                let Some(center) = position(x, y) else {
+                    continue;
+                };
+                let (mut sum, mut count) = (0.0, 0.0);
+                for ny in sampled(y, scissor.max_y, scissor.min_y) {
+                    for nx in sampled(x, scissor.max_x, scissor.min_x) {
+                        let close = position(nx, ny)
+                            .is_some_and(|other| (other.z - center.z).abs() < self.radius);
+                        if close {
+                            sum += self.unoccluded[local(nx, ny)];
+                            count += 1.0;
+                        }
+                    }
+                }
+                if count > 0.0 {
+                    framebuffer.hdr[y * framebuffer.width + x] *= sum / count;
+                }
+            }
+        }
+    }
+}
+
+// Where the surface seen through the middle of pixel (x, y) is, relative to
+// the eye and along the view's axes, or None where nothing was drawn
+fn view_position(
+    framebuffer: &Framebuffer,
+    uniforms: &Uniforms,
+    x: usize,
+    y: usize,
+) -> Option<Vec3> {
+    let depth = framebuffer.zbuffer[y * framebuffer.width + x];
+    if !depth.is_finite() {
+        return None;
+    }
+    let viewport = &uniforms.viewport_matrix;
+    let projection = &uniforms.projection_matrix;
+    let distance = view_distance(depth, uniforms);
+    let ndc_x = (x as f32 + 0.5 - viewport[(0, 3)]) / viewport[(0, 0)];
+    let ndc_y = (y as f32 + 0.5 - viewport[(1, 3)]) / viewport[(1, 1)];
+    Some(Vec3::new(
+        ndc_x * distance / projection[(0, 0)],
+        ndc_y * distance / projection[(1, 1)],
+        -distance,
+    ))
+}
+
+// The surface's normal at `center` from the neighbors across and down, on
+// whichever side is nearer in depth so an edge doesn't tip it over, turned
+// toward the eye
+fn depth_normal(
+    x: usize,
+    y: usize,
+    center: Vec3,
+    position: &impl Fn(usize, usize) -> Option<Vec3>,
+    scissor: &Scissor,
+) -> Option<Vec3> {
+    let nearer = |before: Option<Vec3>, after: Option<Vec3>| match (before, after) {
+        (Some(before), Some(after)) => {
+            if (center.z - before.z).abs() < (after.z - center.z).abs() {
+                Some(center - before)
+            } else {
+                Some(after - center)
+            }
+        }
+        (Some(before), None) => Some(center - before),
+        (None, Some(after)) => Some(after - center),
+        (None, None) => None,
+    };
+    let left = (x > scissor.min_x).then(|| position(x - 1, y)).flatten();
+    let right = (x + 1 < scissor.max_x)
+        .then(|| position(x + 1, y))
+        .flatten();
+    let up = (y > scissor.min_y).then(|| position(x, y - 1)).flatten();
+    let down = (y + 1 < scissor.max_y)
+        .then(|| position(x, y + 1))
+        .flatten();
+    let across = nearer(left, right)?;
+    let along = nearer(up, down)?;
+    let normal = across.cross(&along).try_normalize(1e-12)?;
+    Some(if normal.dot(&center) > 0.0 {
+        -normal
+    } else {
+        normal
+    })
+}
@@ -0,0 +1,481 @@
+use crate::export::Model;
+use crate::exposure::ExposurePreset;
+use crate::light::Star;
+use crate::locale::LANGUAGES;
+use crate::measure::Unit;
+use crate::sweep::Axis;
+#[cfg(feature = "recording")]
+use crate::telemetry::{Field, ALL_FIELDS};
+use crate::texture::Filter;
+use std::env;
+
+// Brightest `--starlight` and Ctrl with = go
+pub const MAX_STARLIGHT: f32 = 4.0;
+
+// Command line options, e.g. `cargo run -- --subdivide 2`, `--decimate 500`
+// or `--seed 1234` to start on that random planet. `--render-size 640x360`
+// renders at 640x360 whatever the window's size, scaled up to fit it with
+// black bars where the shapes differ. F11 switches to a borderless window
+// over the whole screen, taken to be `--fullscreen-size 2560x1440` (1920x1080
+// by default, as minifb can't tell how big it is). `--terminal` draws in the
+// terminal instead of a window, in 24-bit color two pixels to a character,
+// rendering at as many pixels as it has unless `--render-size` says
+// otherwise, so the viewer can be run over SSH. `--threads 4` shades on
+// four threads, each owning a strip of rows. `--fps 30` caps the frame rate
+// (60 by default, 0 for uncapped). `--exposure inner`, `outer` or `deep`
+// starts with automatic exposure for that part of the system, adapting over
+// `--adaptation` seconds (0.5). `--clouds-every 8` re-bakes the terran
+// planet's clouds every eight frames (4 by default, 1 to work them out for
+// every pixel every frame). `--surface-every 8` shades the whole planet into
+// a map over its surface every eight frames and draws it from there,
+// whatever its size on screen (1 by default, shading every pixel every
+// frame). `--shadow-map 2048` draws the shadows the bodies cast on each
+// other at 2048x2048 (1024 by default, 0 for none). `--reverse-z` lays
+// depth out from 1 at the near plane to 0 at the far one, which keeps far-off
+// bodies and orbit lines from flickering through each other.
+// `--haze-color 141c2c` and `--haze-density 0.2`
+// set the distance fog toggled with H. `--inset-filter nearest` samples the
+// north pole inset without blending texels when it's scaled (bilinear by
+// default). `--skybox dir` draws the six faces px.png, nx.png, py.png,
+// ny.png, pz.png and nz.png in `dir` behind the bodies instead of the nebula,
+// which stays if they can't be read.
+// `--triangle-budget 200000` and `--fragment-budget 500000` cap what a frame
+// draws, trading detail, then small distant bodies, then particles for
+// staying under them (no cap by default). `--unit 0.0000852au` reads the
+// distances measured with U out in AU as well as in world units, one of which
+// is taken to be the planet's diameter, 12742km, by default. In stereo, the
+// eyes are `--eye-separation 0.05` apart and cross `--convergence 0.8` of the
+// way to what the camera looks at (0.03 and 1 by default, both as shares of
+// the distance to it). `--cross-eyed` puts the left eye on the right in
+// side-by-side stereo, for crossing one's eyes at the screen. The light of a
+// flash set off with Shift+L spreads at `--light-speed 0.05` world units a
+// frame (0.02 by default). The Doppler tint on Shift+Y takes bodies' speeds
+// toward and away from the camera `--doppler 0.5` times over against that
+// light speed (0.02 by default). The camera eases into and out of turns and
+// zooms keeping `--camera-damping 0.9` of its speed each frame (0.85 by
+// default, 0 for the fixed steps of the held keys). The orbit trails on
+// Shift+B reach `--trail-length 600` frames back (360 by default). `--star
+// red-dwarf` lights the system with a red dwarf's warmer, dimmer light, or
+// `orange-dwarf` or `blue-white` (yellow-dwarf, plain white, by default).
+// `--companion red-dwarf` pairs it with a second star of that kind, the two
+// going around each other as the planet goes around both, each lighting the
+// bodies and casting shadows of its own.
+// The light of the sky or the skybox on the night sides is `--starlight 2`
+// times as bright (1 by default, 0 for none), and Ctrl with - and = turns it
+// down and up.
+// `--scene assets/moons.txt`
+// takes the moon systems from that file, described in scene_file.rs, and
+// picks them up again each time it's saved.
+// `--lang es` shows the window's text
+// and the console messages in Spanish rather than English. `--sweep band-frequency=20:60:5`, given
+// once or twice, renders a grid of images over one or two shader parameters
+// into `--sweep-out` (sweep.png) instead of opening the window. `--bench 100`
+// renders a few fixed scenes 100 frames each without a window and prints how
+// long each stage of the pipeline took. `--golden` renders every shader to a
+// small image of the same frame and checks it against the one kept in
+// assets/golden (assets/golden/fixed with the `fixed` feature), exiting with
+// 1 if any differs, and `--golden-update` keeps the new renders there
+// instead. `--export asteroid:7` saves a generated mesh (sphere, asteroid or
+// spacecraft, with an optional seed) to `--export-out` (model.obj) and exits.
+// `--telemetry log.csv` logs the bodies every `--telemetry-every` frames,
+// limited to a comma-separated `--telemetry-fields` list (position,
+// velocity, energy, timing). `--record demo.txt` saves the session's input and `--play
+// demo.txt` replays it. A rock streaks in and strikes the planet on each
+// frame given with `--strike-at 600`, once or more, as it does on Shift+G.
+// `--serve 8080` streams the frames to http://localhost:8080/. Options for a
+// cargo feature that was left out of the build are ignored with a warning.
+pub struct Options {
+    pub subdivision_level: u32,
+    pub target_triangles: Option<usize>,
+    pub planet_seed: Option<u64>,
+    pub render_size: Option<(usize, usize)>,
+    pub fullscreen_size: (usize, usize),
+    pub terminal: bool,
+    pub threads: usize,
+    pub target_fps: u32,
+    pub exposure_preset: Option<ExposurePreset>,
+    pub adaptation_seconds: f32,
+    pub clouds_every: u32,
+    pub surface_every: u32,
+    pub shadow_map_size: usize,
+    pub reversed_z: bool,
+    pub haze_color: u32,
+    pub haze_density: f32,
+    pub inset_filter: Filter,
+    pub skybox: Option<String>,
+    pub triangle_budget: Option<usize>,
+    pub fragment_budget: Option<usize>,
+    pub unit: Unit,
+    pub eye_separation: f32,
+    pub convergence: f32,
+    pub cross_eyed: bool,
+    pub light_speed: f32,
+    pub doppler: f32,
+    pub camera_damping: f32,
+    pub trail_length: usize,
+    pub starlight: f32,
+    pub star: Star,
+    pub companion: Option<Star>,
+    pub scene_path: Option<String>,
+    pub language: String,
+    pub sweep: Vec<Axis>,
+    pub sweep_path: String,
+    pub bench_frames: Option<u32>,
+    pub golden: bool,
+    pub golden_update: bool,
+    pub export: Option<Model>,
+    pub export_path: String,
+    #[cfg(feature = "recording")]
+    pub telemetry_path: Option<String>,
+    #[cfg(feature = "recording")]
+    pub telemetry_every: u32,
+    #[cfg(feature = "recording")]
+    pub telemetry_fields: Vec<Field>,
+    #[cfg(feature = "recording")]
+    pub record_path: Option<String>,
+    #[cfg(feature = "recording")]
+    pub play_path: Option<String>,
+    #[cfg(feature = "frame-server")]
+    pub serve_port: Option<u16>,
+    #[cfg(feature = "physics")]
+    pub strikes: Vec<u32>,
+}
+
+// Everything as it is with no arguments given
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            subdivision_level: 0,
+            target_triangles: None,
+            planet_seed: None,
+            render_size: None,
+            fullscreen_size: (1920, 1080),
+            terminal: false,
+            threads: 1,
+            target_fps: 60,
+            exposure_preset: None,
+            adaptation_seconds: 0.5,
+            clouds_every: 4,
+            surface_every: 1,
+            shadow_map_size: 1024,
+            reversed_z: false,
+            haze_color: 0x141C2C,
+            haze_density: 0.2,
+            inset_filter: Filter::Bilinear,
+            skybox: None,
+            triangle_budget: None,
+            fragment_budget: None,
+            unit: Unit {
+                length: 12742.0,
+                name: "km".to_string(),
+            },
+            eye_separation: 0.03,
+            convergence: 1.0,
+            cross_eyed: false,
+            light_speed: 0.02,
+            doppler: 0.02,
+            camera_damping: 0.85,
+            trail_length: 360,
+            starlight: 1.0,
+            star: Star::YellowDwarf,
+            companion: None,
+            scene_path: None,
+            language: "en".to_string(),
+            sweep: Vec::new(),
+            sweep_path: "sweep.png".to_string(),
+            bench_frames: None,
+            golden: false,
+            golden_update: false,
+            export: None,
+            export_path: "model.obj".to_string(),
+            #[cfg(feature = "recording")]
+            telemetry_path: None,
+            #[cfg(feature = "recording")]
+            telemetry_every: 1,
+            #[cfg(feature = "recording")]
+            telemetry_fields: ALL_FIELDS.to_vec(),
+            #[cfg(feature = "recording")]
+            record_path: None,
+            #[cfg(feature = "recording")]
+            play_path: None,
+            #[cfg(feature = "frame-server")]
+            serve_port: None,
+            #[cfg(feature = "physics")]
+            strikes: Vec::new(),
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args() -> Self {
+        let mut options = Options::default();
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--subdivide" => {
+                    options.subdivision_level = parse_value(&arg, args.next());
+                }
+                "--decimate" => {
+                    options.target_triangles = Some(parse_value(&arg, args.next()));
+                }
+                "--seed" => {
+                    options.planet_seed = Some(parse_value(&arg, args.next()));
+                }
+                "--render-size" => {
+                    options.render_size = Some(parse_size(&arg, args.next()));
+                }
+                "--fullscreen-size" => {
+                    options.fullscreen_size = parse_size(&arg, args.next());
+                }
+                "--terminal" => {
+                    options.terminal = true;
+                }
+                "--threads" => {
+                    options.threads = parse_value(&arg, args.next());
+                }
+                "--fps" => {
+                    options.target_fps = parse_value(&arg, args.next());
+                }
+                "--exposure" => {
+                    let name: String = parse_value(&arg, args.next());
+                    options.exposure_preset =
+                        Some(ExposurePreset::parse(&name).unwrap_or_else(|| {
+                            eprintln!("--exposure expects inner, outer or deep, got '{}'", name);
+                            std::process::exit(2);
+                        }));
+                }
+                "--adaptation" => {
+                    options.adaptation_seconds = parse_value(&arg, args.next());
+                }
+                "--clouds-every" => {
+                    options.clouds_every = parse_value(&arg, args.next());
+                }
+                "--surface-every" => {
+                    options.surface_every = parse_value(&arg, args.next());
+                }
+                "--shadow-map" => {
+                    options.shadow_map_size = parse_value(&arg, args.next());
+                }
+                "--reverse-z" => {
+                    options.reversed_z = true;
+                }
+                "--haze-color" => {
+                    let hex: String = parse_value(&arg, args.next());
+                    options.haze_color = u32::from_str_radix(hex.trim_start_matches('#'), 16)
+                        .unwrap_or_else(|_| {
+                            eprintln!(
+                                "--haze-color expects a hex color like 141c2c, got '{}'",
+                                hex
+                            );
+                            std::process::exit(2);
+                        });
+                }
+                "--haze-density" => {
+                    options.haze_density = parse_value(&arg, args.next());
+                }
+                "--inset-filter" => {
+                    let name: String = parse_value(&arg, args.next());
+                    options.inset_filter = Filter::parse(&name).unwrap_or_else(|| {
+                        eprintln!("--inset-filter expects nearest or bilinear, got '{}'", name);
+                        std::process::exit(2);
+                    });
+                }
+                "--skybox" => {
+                    options.skybox = Some(parse_value(&arg, args.next()));
+                }
+                "--triangle-budget" => {
+                    options.triangle_budget = Some(parse_value(&arg, args.next()));
+                }
+                "--fragment-budget" => {
+                    options.fragment_budget = Some(parse_value(&arg, args.next()));
+                }
+                "--unit" => {
+                    let text: String = parse_value(&arg, args.next());
+                    options.unit = Unit::parse(&text).unwrap_or_else(|| {
+                        eprintln!("--unit expects a length like 12742km, got '{}'", text);
+                        std::process::exit(2);
+                    });
+                }
+                "--eye-separation" => {
+                    options.eye_separation = parse_value(&arg, args.next());
+                }
+                "--convergence" => {
+                    options.convergence = parse_value(&arg, args.next());
+                }
+                "--cross-eyed" => {
+                    options.cross_eyed = true;
+                }
+                "--light-speed" => {
+                    options.light_speed = parse_value(&arg, args.next());
+                }
+                "--doppler" => {
+                    options.doppler = parse_value(&arg, args.next());
+                }
+                "--camera-damping" => {
+                    let damping: f32 = parse_value(&arg, args.next());
+                    if !(0.0..1.0).contains(&damping) {
+                        eprintln!(
+                            "--camera-damping expects at least 0 and under 1, got {}",
+                            damping
+                        );
+                        std::process::exit(2);
+                    }
+                    options.camera_damping = damping;
+                }
+                "--trail-length" => {
+                    let length: usize = parse_value(&arg, args.next());
+                    if length < 2 {
+                        eprintln!("--trail-length expects at least 2 frames, got {}", length);
+                        std::process::exit(2);
+                    }
+                    options.trail_length = length;
+                }
+                "--starlight" => {
+                    let starlight: f32 = parse_value(&arg, args.next());
+                    if !(0.0..=MAX_STARLIGHT).contains(&starlight) {
+                        eprintln!(
+                            "--starlight expects 0 to {}, got {}",
+                            MAX_STARLIGHT, starlight
+                        );
+                        std::process::exit(2);
+                    }
+                    options.starlight = starlight;
+                }
+                "--star" => {
+                    let name: String = parse_value(&arg, args.next());
+                    options.star = Star::parse(&name).unwrap_or_else(|| {
+                        eprintln!(
+                            "--star expects red-dwarf, orange-dwarf, yellow-dwarf or blue-white, got '{}'",
+                            name
+                        );
+                        std::process::exit(2);
+                    });
+                }
+                "--companion" => {
+                    let name: String = parse_value(&arg, args.next());
+                    options.companion = Some(Star::parse(&name).unwrap_or_else(|| {
+                        eprintln!(
+                            "--companion expects red-dwarf, orange-dwarf, yellow-dwarf or blue-white, got '{}'",
+                            name
+                        );
+                        std::process::exit(2);
+                    }));
+                }
+                "--scene" => {
+                    options.scene_path = Some(parse_value(&arg, args.next()));
+                }
+                "--lang" => {
+                    let language: String = parse_value(&arg, args.next());
+                    if !LANGUAGES.contains(&language.as_str()) {
+                        eprintln!(
+                            "--lang expects one of {}, got '{}'",
+                            LANGUAGES.join(", "),
+                            language
+                        );
+                        std::process::exit(2);
+                    }
+                    options.language = language;
+                }
+                "--sweep" => {
+                    let spec: String = parse_value(&arg, args.next());
+                    if options.sweep.len() == 2 {
+                        eprintln!("--sweep can be given at most twice");
+                        std::process::exit(2);
+                    }
+                    options.sweep.push(Axis::parse(&spec).unwrap_or_else(|err| {
+                        eprintln!("--sweep: {}", err);
+                        std::process::exit(2);
+                    }));
+                }
+                "--sweep-out" => {
+                    options.sweep_path = parse_value(&arg, args.next());
+                }
+                "--bench" => {
+                    options.bench_frames = Some(parse_value(&arg, args.next()));
+                }
+                "--golden" => {
+                    options.golden = true;
+                }
+                "--golden-update" => {
+                    options.golden_update = true;
+                }
+                "--export" => {
+                    let spec: String = parse_value(&arg, args.next());
+                    options.export = Some(Model::parse(&spec).unwrap_or_else(|err| {
+                        eprintln!("--export: {}", err);
+                        std::process::exit(2);
+                    }));
+                }
+                "--export-out" => {
+                    options.export_path = parse_value(&arg, args.next());
+                }
+                #[cfg(feature = "recording")]
+                "--telemetry" => {
+                    options.telemetry_path = Some(parse_value(&arg, args.next()));
+                }
+                #[cfg(feature = "recording")]
+                "--telemetry-every" => {
+                    options.telemetry_every = parse_value(&arg, args.next());
+                }
+                #[cfg(feature = "recording")]
+                "--record" => {
+                    options.record_path = Some(parse_value(&arg, args.next()));
+                }
+                #[cfg(feature = "recording")]
+                "--play" => {
+                    options.play_path = Some(parse_value(&arg, args.next()));
+                }
+                #[cfg(feature = "physics")]
+                "--strike-at" => {
+                    options.strikes.push(parse_value(&arg, args.next()));
+                }
+                #[cfg(feature = "frame-server")]
+                "--serve" => {
+                    options.serve_port = Some(parse_value(&arg, args.next()));
+                }
+                #[cfg(feature = "recording")]
+                "--telemetry-fields" => {
+                    let list: String = parse_value(&arg, args.next());
+                    options.telemetry_fields = list
+                        .split(',')
+                        .map(|name| {
+                            Field::parse(name.trim()).unwrap_or_else(|| {
+                                eprintln!("Unknown telemetry field '{}'", name);
+                                std::process::exit(2);
+                            })
+                        })
+                        .collect();
+                }
+                _ => eprintln!("Ignoring unknown argument '{}'", arg),
+            }
+        }
+
+        options
+    }
+}
+
+// A WIDTH x HEIGHT value like 640x360
+fn parse_size(flag: &str, value: Option<String>) -> (usize, usize) {
+    let size: String = parse_value(flag, value);
+    let parsed = size
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+    match parsed {
+        Some((width, height)) if width > 0 && height > 0 => (width, height),
+        _ => {
+            eprintln!("{} expects a size like 640x360, got '{}'", flag, size);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(value)) => value,
+        _ => {
+            eprintln!("{} expects a value", flag);
+            std::process::exit(2);
+        }
+    }
+}
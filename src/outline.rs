@@ -0,0 +1,104 @@
+use crate::color::Color;
+use crate::framebuffer::{DepthState, DepthTest, Framebuffer, StencilState, StencilTest};
+use crate::instance::Instance;
+use crate::picking::PickTarget;
+use crate::shaders::ShaderType;
+use crate::silhouette::projected_circle;
+use crate::vertex::Vertex;
+use crate::{render_instanced, DepthMode, Uniforms};
+use nalgebra_glm::{scaling, translation, Mat4, Vec3, Vec4};
+
+// Stencil value the selected body leaves wherever it's drawn
+const SELECTED: u8 = 1;
+
+// Pixels the outline sticks out past the body's silhouette, and the most it
+// can grow the body by when it's only a few pixels across
+const WIDTH: f32 = 3.0;
+const MAX_GROWTH: f32 = 0.5;
+
+// Outlines the selected body by remembering how it was drawn: while it's
+// drawn it marks the stencil, then a slightly larger copy is drawn in a flat
+// color only where the mark isn't, which leaves a rim around its silhouette.
+// The copy is depth tested, so bodies in front still cover the rim.
+pub struct Selection<'a> {
+    name: Option<&'a str>,
+    drawn: Option<(&'a [Vertex], Mat4)>,
+}
+
+impl<'a> Selection<'a> {
+    pub fn new(name: Option<&'a str>) -> Self {
+        Selection { name, drawn: None }
+    }
+
+    // Call before drawing the body called `name` with this mesh and
+    // transform. If it's the selection, the stencil is marked until `end`.
+    pub fn begin(
+        &mut self,
+        framebuffer: &mut Framebuffer,
+        name: &str,
+        vertex_array: &'a [Vertex],
+        model_matrix: Mat4,
+    ) {
+        if self.name == Some(name) {
+            self.drawn = Some((vertex_array, model_matrix));
+            framebuffer.set_stencil_state(StencilState {
+                test: StencilTest::Always,
+                write: Some(SELECTED),
+            });
+        }
+    }
+
+    pub fn end(&self, framebuffer: &mut Framebuffer) {
+        framebuffer.set_stencil_state(StencilState::DISABLED);
+    }
+
+    // Draws the rim once everything else is in. `target` is the selection's
+    // bounding sphere, which sets how far the copy is grown so the rim comes
+    // out the same width however near the body is.
+    pub fn draw_outline(
+        &self,
+        framebuffer: &mut Framebuffer,
+        uniforms: &Uniforms,
+        target: &PickTarget,
+        color: u32,
+    ) {
+        let Some((vertex_array, model_matrix)) = self.drawn else {
+            return;
+        };
+        let center = target.center;
+        let Some((_, pixels)) = projected_circle(
+            Vec4::new(center.x, center.y, center.z, 1.0),
+            target.radius,
+            &uniforms.view_matrix,
+            &uniforms.projection_matrix,
+            &uniforms.viewport_matrix,
+        ) else {
+            return;
+        };
+        let growth = 1.0 + (WIDTH / pixels.max(1.0)).min(MAX_GROWTH);
+        let grown = translation(&center) * scaling(&Vec3::repeat(growth)) * translation(&-center);
+
+        let depth_state = framebuffer.depth_state();
+        framebuffer.set_depth_state(DepthState {
+            test: DepthTest::LessEqual,
+            write: false,
+        });
+        framebuffer.set_stencil_state(StencilState {
+            test: StencilTest::NotEqual(SELECTED),
+            write: None,
+        });
+        render_instanced(
+            framebuffer,
+            uniforms,
+            vertex_array,
+            &[Instance {
+                color: Some(Color::from_hex(color)),
+                ..Instance::new(grown * model_matrix)
+            }],
+            &ShaderType::Outline,
+            DepthMode::ZBuffer,
+        );
+        framebuffer.set_stencil_state(StencilState::DISABLED);
+        framebuffer.set_depth_state(depth_state);
+    }
+}
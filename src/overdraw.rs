@@ -0,0 +1,40 @@
+use crate::font::{text_width, GLYPH_WIDTH};
+use crate::framebuffer::Framebuffer;
+use crate::locale::Locale;
+
+// Black where nothing was drawn, then blue for a pixel written once, green,
+// yellow and red, up to white for five times or more
+const HEAT: [u32; 6] = [0x000000, 0x2050FF, 0x20C040, 0xFFE020, 0xFF3020, 0xFFFFFF];
+
+// Paints over the frame how many of the rasterizer's samples were written to
+// each pixel, as counted while the framebuffer was counting overdraw, so
+// triangles drawn more times than they need to stand out. Goes on before
+// the HUD, which is drawn over it.
+pub fn show(framebuffer: &mut Framebuffer) {
+    framebuffer.reset_scissor();
+    for y in 0..framebuffer.height {
+        for x in 0..framebuffer.width {
+            let count = framebuffer.overdraw(x, y) as usize;
+            framebuffer.set_current_color(HEAT[count.min(HEAT.len() - 1)]);
+            framebuffer.set_pixel(x, y);
+        }
+    }
+}
+
+// The caption and the count each color stands for, at (x, y)
+pub fn draw_legend(framebuffer: &mut Framebuffer, locale: &Locale, x: usize, y: usize) {
+    let caption = locale.get("hud.overdraw");
+    framebuffer.set_current_color(0xFFFFFF);
+    framebuffer.draw_text(x as i32, y as i32, caption, 1);
+    let mut x = x + text_width(caption, 1) + GLYPH_WIDTH;
+    for (count, &color) in HEAT.iter().enumerate().skip(1) {
+        let label = if count == HEAT.len() - 1 {
+            format!("{}+", count)
+        } else {
+            count.to_string()
+        };
+        framebuffer.set_current_color(color);
+        framebuffer.draw_text(x as i32, y as i32, &label, 1);
+        x += text_width(&label, 1) + GLYPH_WIDTH;
+    }
+}
@@ -0,0 +1,37 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Keeps frames to a steady rate by sleeping off whatever is left of each
+// frame's budget once it's been drawn, instead of a fixed delay on top of
+// the time it took. Frames are due on a fixed schedule so short sleeps don't
+// add up into drift; one that runs long starts the schedule over rather than
+// rushing the next few to catch up.
+pub struct FramePacer {
+    // None runs uncapped
+    budget: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    // A target of 0 fps doesn't wait at all
+    pub fn new(target_fps: u32) -> Self {
+        FramePacer {
+            budget: (target_fps > 0).then(|| Duration::from_secs(1) / target_fps),
+            next_frame: Instant::now(),
+        }
+    }
+
+    // Call once per frame after presenting it
+    pub fn wait(&mut self) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        self.next_frame += budget;
+        let now = Instant::now();
+        if self.next_frame > now {
+            thread::sleep(self.next_frame - now);
+        } else {
+            self.next_frame = now;
+        }
+    }
+}
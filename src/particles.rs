@@ -0,0 +1,189 @@
+use crate::framebuffer::{DepthState, Framebuffer};
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// Color from birth to death: `start` fading into `end`, holding on to
+// `start` longer the higher `falloff` is
+#[derive(Clone, Copy)]
+pub struct ColorRamp {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub falloff: f32,
+}
+
+impl ColorRamp {
+    // `life` goes from 1 when a particle is born to 0 when it dies
+    pub fn at(&self, life: f32) -> Vec3 {
+        self.end + (self.start - self.end) * life.clamp(0.0, 1.0).powf(self.falloff)
+    }
+}
+
+// How a particle's color goes onto what's behind it
+#[derive(Clone, Copy, PartialEq)]
+pub enum Blend {
+    // Added onto what's there without writing depth, for glowing particles
+    // that shouldn't hide each other
+    Additive,
+    // Drawn like any other surface, hiding what's behind it
+    Opaque,
+    // Laid over what's behind it, fading out as the particle dies, once the
+    // framebuffer composites its translucent samples
+    #[cfg_attr(not(feature = "physics"), allow(dead_code))]
+    Translucent,
+}
+
+// Everything one kind of particle shares
+#[derive(Clone, Copy)]
+pub struct ParticleStyle {
+    // Frames, drawn uniformly from min..max for each particle
+    pub lifetime: (u32, u32),
+    pub color: ColorRamp,
+    pub blend: Blend,
+    // Particles are squares facing the camera, `size` world units across and
+    // never smaller than a pixel, so 0 draws points. Additive ones get a soft
+    // edge at a third of the brightness.
+    pub size: f32,
+}
+
+#[derive(Clone)]
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    age: u32,
+    lifetime: u32,
+}
+
+// A pool of particles of one style. Whoever owns it decides where they're
+// born and what pushes them around; the pool ages, moves and draws them.
+#[derive(Clone)]
+pub struct Particles {
+    pub style: ParticleStyle,
+    particles: Vec<Particle>,
+    // Fraction of a particle owed by `due` from earlier frames
+    owed: f32,
+    rng: StdRng,
+}
+
+impl Particles {
+    pub fn new(style: ParticleStyle, seed: u64) -> Self {
+        Particles {
+            style,
+            particles: Vec::new(),
+            owed: 0.0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // How many particles to spawn this frame for a steady `rate` per frame,
+    // so rates below one still come out right on average
+    pub fn due(&mut self, rate: f32) -> usize {
+        self.owed += rate.max(0.0);
+        let count = self.owed.floor();
+        self.owed -= count;
+        count as usize
+    }
+
+    pub fn spawn(&mut self, position: Vec3, velocity: Vec3) {
+        let (min, max) = self.style.lifetime;
+        self.particles.push(Particle {
+            position,
+            velocity,
+            age: 0,
+            lifetime: self.rng.gen_range(min..max.max(min + 1)),
+        });
+    }
+
+    // Advances one frame: each particle picks up `acceleration` at where it
+    // is, moves, and ages. Particles past their lifetime or that `alive`
+    // rejects are removed.
+    pub fn update(&mut self, acceleration: impl Fn(Vec3) -> Vec3, alive: impl Fn(Vec3) -> bool) {
+        self.particles.retain_mut(|particle| {
+            particle.velocity += acceleration(particle.position);
+            particle.position += particle.velocity;
+            particle.age += 1;
+            particle.age < particle.lifetime && alive(particle.position)
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        let previous = framebuffer.depth_state();
+        framebuffer.set_depth_state(if self.style.blend == Blend::Opaque {
+            DepthState::OPAQUE
+        } else {
+            DepthState::TRANSLUCENT
+        });
+        self.draw_particles(framebuffer, uniforms);
+        framebuffer.set_depth_state(previous);
+    }
+
+    fn draw_particles(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        let transform =
+            uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+        // Pixels per world unit at a distance of one
+        let focal = uniforms.projection_matrix[(1, 1)] * uniforms.viewport_matrix[(1, 1)].abs();
+        // Glowing particles left out are made up for by brightening the
+        // ones drawn, so a thinned tail gives off as much light
+        let stride = uniforms.particle_stride.max(1);
+        let gain = if self.style.blend == Blend::Additive {
+            stride as f32
+        } else {
+            1.0
+        };
+        for particle in self.particles.iter().step_by(stride) {
+            let p = particle.position;
+            let screen = transform * Vec4::new(p.x, p.y, p.z, 1.0);
+            if screen.w <= 0.0 {
+                continue;
+            }
+            let (x, y) = (screen.x / screen.w, screen.y / screen.w);
+            if x < 0.0 || y < 0.0 {
+                continue;
+            }
+
+            let life = 1.0 - particle.age as f32 / particle.lifetime as f32;
+            let radiance = self.style.color.at(life) * gain;
+            let depth = screen.z / screen.w;
+            let (x, y) = (x as usize, y as usize);
+            let half = (self.style.size * focal / screen.w / 2.0) as usize;
+            for py in y.saturating_sub(half)..=y + half {
+                for px in x.saturating_sub(half)..=x + half {
+                    self.splat(framebuffer, px, py, depth, radiance, life);
+                }
+            }
+            if self.style.blend == Blend::Additive {
+                let edge = radiance * 0.3;
+                let (left, top) = (x.wrapping_sub(half + 1), y.wrapping_sub(half + 1));
+                for offset in 0..=2 * half {
+                    let along_x = (x + offset).wrapping_sub(half);
+                    let along_y = (y + offset).wrapping_sub(half);
+                    self.splat(framebuffer, along_x, top, depth, edge, life);
+                    self.splat(framebuffer, along_x, y + half + 1, depth, edge, life);
+                    self.splat(framebuffer, left, along_y, depth, edge, life);
+                    self.splat(framebuffer, x + half + 1, along_y, depth, edge, life);
+                }
+            }
+        }
+    }
+
+    fn splat(
+        &self,
+        framebuffer: &mut Framebuffer,
+        x: usize,
+        y: usize,
+        depth: f32,
+        radiance: Vec3,
+        life: f32,
+    ) {
+        match self.style.blend {
+            Blend::Additive => framebuffer.add_radiance(x, y, depth, radiance),
+            Blend::Opaque => framebuffer.point(x, y, depth, radiance),
+            Blend::Translucent => framebuffer.queue_translucent(x, y, depth, radiance, life),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::depth_planes;
+use crate::shaders::ShaderType;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use std::borrow::Cow;
+
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+// Bounding sphere of something that can be clicked on
+pub struct PickTarget {
+    pub name: Cow<'static, str>,
+    pub center: Vec3,
+    pub radius: f32,
+    pub shader: ShaderType,
+}
+
+// Unprojects a framebuffer pixel at the near and far depth of the viewport
+// through the inverse of the full vertex pipeline
+pub fn screen_ray(
+    x: f32,
+    y: f32,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+    viewport_matrix: &Mat4,
+    reversed_z: bool,
+) -> Option<Ray> {
+    let inverse = (viewport_matrix * projection_matrix * view_matrix).try_inverse()?;
+
+    let (near_depth, far_depth) = depth_planes(viewport_matrix, reversed_z);
+
+    let near = inverse * Vec4::new(x, y, near_depth, 1.0);
+    let far = inverse * Vec4::new(x, y, far_depth, 1.0);
+    let near = near.xyz() / near.w;
+    let far = far.xyz() / far.w;
+
+    Some(Ray {
+        origin: near,
+        direction: (far - near).normalize(),
+    })
+}
+
+// Distance along the ray to the first hit, if any
+pub fn intersect_sphere(ray: &Ray, center: Vec3, radius: f32) -> Option<f32> {
+    let to_center = ray.origin - center;
+    let b = to_center.dot(&ray.direction);
+    let c = to_center.dot(&to_center) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrt = discriminant.sqrt();
+    [-b - sqrt, -b + sqrt].into_iter().find(|&t| t >= 0.0)
+}
+
+// Nearest target under the ray and the distance to it
+pub fn pick<'a>(ray: &Ray, targets: &'a [PickTarget]) -> Option<(&'a PickTarget, f32)> {
+    targets
+        .iter()
+        .filter_map(|target| {
+            intersect_sphere(ray, target.center, target.radius).map(|t| (target, t))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
@@ -0,0 +1,148 @@
+// Minimal PNG encoder for 0xRRGGBB pixels: one 8-bit RGB image, no filtering,
+// and the zlib stream made of stored (uncompressed) deflate blocks. The files
+// are larger than they could be, but encoding a frame is just a copy and two
+// checksums.
+
+const STORED_BLOCK: usize = 65535;
+
+pub fn encode(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+    // Every row starts with its filter type, 0 for none
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels.chunks_exact(width).take(height) {
+        raw.push(0);
+        for &pixel in row {
+            raw.extend([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8]);
+        }
+    }
+
+    let mut zlib = Vec::with_capacity(raw.len() + raw.len() / STORED_BLOCK * 5 + 11);
+    zlib.extend([0x78, 0x01]);
+    let blocks = raw.len().div_ceil(STORED_BLOCK).max(1);
+    for (index, block) in raw.chunks(STORED_BLOCK).enumerate() {
+        zlib.push((index + 1 == blocks) as u8);
+        let length = block.len() as u16;
+        zlib.extend(length.to_le_bytes());
+        zlib.extend((!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend(adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::with_capacity(13);
+    header.extend((width as u32).to_be_bytes());
+    header.extend((height as u32).to_be_bytes());
+    // Bit depth 8, color type 2 (RGB), default compression, filter and no
+    // interlacing
+    header.extend([8, 2, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+// Reads back what `encode` writes, as width, height and 0xRRGGBB pixels.
+// Anything else, like a PNG with compressed blocks or row filters, gives None.
+pub fn decode(png: &[u8]) -> Option<(usize, usize, Vec<u32>)> {
+    let mut rest = png.strip_prefix(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A])?;
+    let mut size = None;
+    let mut zlib = Vec::new();
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes(rest[..4].try_into().ok()?) as usize;
+        let kind = &rest[4..8];
+        let data = rest.get(8..8 + length)?;
+        match kind {
+            b"IHDR" if data.len() == 13 && data[8..] == [8, 2, 0, 0, 0] => {
+                let width = u32::from_be_bytes(data[..4].try_into().ok()?) as usize;
+                let height = u32::from_be_bytes(data[4..8].try_into().ok()?) as usize;
+                size = Some((width, height));
+            }
+            b"IHDR" => return None,
+            b"IDAT" => zlib.extend_from_slice(data),
+            _ => {}
+        }
+        rest = rest.get(12 + length..)?;
+    }
+    let (width, height) = size?;
+
+    // Stored deflate blocks after the two-byte zlib header, each a header
+    // byte and the length twice, the second time inverted
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    let mut stream = zlib.get(2..)?;
+    loop {
+        let (&block, after) = stream.split_first()?;
+        if block >> 1 != 0 {
+            return None;
+        }
+        let length = u16::from_le_bytes(after.get(..2)?.try_into().ok()?);
+        let inverted = u16::from_le_bytes(after.get(2..4)?.try_into().ok()?);
+        if length != !inverted {
+            return None;
+        }
+        raw.extend_from_slice(after.get(4..4 + length as usize)?);
+        stream = &after[4 + length as usize..];
+        if block & 1 == 1 {
+            break;
+        }
+    }
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for row in raw.chunks_exact(width * 3 + 1).take(height) {
+        if row[0] != 0 {
+            return None;
+        }
+        pixels.extend(
+            row[1..]
+                .chunks_exact(3)
+                .map(|rgb| (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32),
+        );
+    }
+    (pixels.len() == width * height).then_some((width, height, pixels))
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+// Byte-at-a-time CRC-32 lookup table, built at compile time
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &byte in data {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1_u32, 0_u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
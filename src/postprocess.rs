@@ -0,0 +1,122 @@
+use crate::blue_noise::blue_noise;
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    ChromaticAberration,
+    Vignette,
+    FilmGrain,
+}
+
+// Full-screen passes run in order over the finished frame, each with its own
+// on/off switch
+pub struct PostProcess {
+    passes: Vec<(Effect, bool)>,
+    scratch: Vec<u32>,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        PostProcess {
+            passes: vec![
+                (Effect::ChromaticAberration, false),
+                (Effect::Vignette, false),
+                (Effect::FilmGrain, false),
+            ],
+            scratch: Vec::new(),
+        }
+    }
+}
+
+impl PostProcess {
+    // Flips an effect and returns whether it is now enabled
+    pub fn toggle(&mut self, effect: Effect) -> bool {
+        match self.passes.iter_mut().find(|(e, _)| *e == effect) {
+            Some((_, enabled)) => {
+                *enabled = !*enabled;
+                *enabled
+            }
+            None => false,
+        }
+    }
+
+    pub fn apply(&mut self, framebuffer: &mut Framebuffer, time: u32) {
+        for &(effect, enabled) in &self.passes {
+            if !enabled {
+                continue;
+            }
+            match effect {
+                Effect::ChromaticAberration => {
+                    chromatic_aberration(framebuffer, &mut self.scratch, 0.008)
+                }
+                Effect::Vignette => vignette(framebuffer, 0.6),
+                Effect::FilmGrain => film_grain(framebuffer, time, 0.08),
+            }
+        }
+    }
+}
+
+// Splits red and blue apart radially, more toward the edges, like a cheap lens
+fn chromatic_aberration(framebuffer: &mut Framebuffer, scratch: &mut Vec<u32>, amount: f32) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    scratch.clear();
+    scratch.extend_from_slice(&framebuffer.buffer);
+
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let sample = |x: f32, y: f32| {
+        let x = (x.round().max(0.0) as usize).min(width - 1);
+        let y = (y.round().max(0.0) as usize).min(height - 1);
+        scratch[y * width + x]
+    };
+
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 - center_x, y as f32 - center_y);
+            let red = sample(x as f32 + dx * amount, y as f32 + dy * amount);
+            let blue = sample(x as f32 - dx * amount, y as f32 - dy * amount);
+            let index = y * width + x;
+            framebuffer.buffer[index] =
+                (red & 0xFF0000) | (scratch[index] & 0x00FF00) | (blue & 0x0000FF);
+        }
+    }
+}
+
+// Darkens toward the corners with a smooth falloff
+fn vignette(framebuffer: &mut Framebuffer, strength: f32) {
+    let (width, height) = (framebuffer.width, framebuffer.height);
+    let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+    let corner = (center_x * center_x + center_y * center_y).sqrt();
+
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - center_x, y as f32 + 0.5 - center_y);
+            let t = (((dx * dx + dy * dy).sqrt() / corner - 0.4) / 0.6).clamp(0.0, 1.0);
+            let falloff = 1.0 - strength * t * t * (3.0 - 2.0 * t);
+
+            let index = y * width + x;
+            framebuffer.buffer[index] =
+                (Color::from_hex(framebuffer.buffer[index]) * falloff).to_hex();
+        }
+    }
+}
+
+// Adds or removes the same amount from every channel, re-rolled each frame.
+// Blue noise keeps the grain even instead of clumping.
+fn film_grain(framebuffer: &mut Framebuffer, time: u32, amount: f32) {
+    let offset = (amount * 255.0) as i32;
+    if offset == 0 {
+        return;
+    }
+
+    let noise_tile = blue_noise();
+    let width = framebuffer.width;
+    for (index, pixel) in framebuffer.buffer.iter_mut().enumerate() {
+        let value = noise_tile.sample_animated(index % width, index / width, time);
+        let noise = (value * (2 * offset + 1) as f32) as i32 - offset;
+        let channel = |shift: u32| {
+            ((((*pixel >> shift) & 0xFF) as i32 + noise).clamp(0, 255) as u32) << shift
+        };
+        *pixel = channel(16) | channel(8) | channel(0);
+    }
+}
@@ -0,0 +1,219 @@
+use crate::framebuffer::{Framebuffer, Rect};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PresentMode {
+    Direct,
+    IntegerScale,
+}
+
+// Owns the window-sized buffer that the framebuffer gets scaled into before
+// being handed to minifb. Scaling keeps the framebuffer's aspect ratio,
+// filling whatever of the window it doesn't cover with the border color.
+pub struct Presenter {
+    pub width: usize,
+    pub height: usize,
+    // Direct stretching blends the four nearest framebuffer pixels instead of
+    // taking the one underneath
+    pub bilinear: bool,
+    buffer: Vec<u32>,
+    border_color: u32,
+}
+
+impl Presenter {
+    pub fn new(width: usize, height: usize) -> Self {
+        Presenter {
+            width,
+            height,
+            bilinear: false,
+            buffer: vec![0; width * height],
+            border_color: 0x000000,
+        }
+    }
+
+    // For a window that's now `width` by `height`
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.width = width;
+        self.height = height;
+        self.buffer = vec![self.border_color; width * height];
+    }
+
+    // Scales `framebuffer` into the window's buffer, unless it's already the
+    // window's size and can be shown as it is
+    pub fn scale(&mut self, framebuffer: &Framebuffer, mode: PresentMode) {
+        if framebuffer.width == self.width && framebuffer.height == self.height {
+            return;
+        }
+        match mode {
+            PresentMode::Direct if self.bilinear => self.stretch_bilinear(framebuffer),
+            PresentMode::Direct => self.stretch(framebuffer),
+            PresentMode::IntegerScale => self.integer_scale(framebuffer),
+        }
+    }
+
+    // What to show for `framebuffer` once it's been through `scale`
+    pub fn frame<'a>(&'a self, framebuffer: &'a Framebuffer) -> &'a [u32] {
+        if framebuffer.width == self.width && framebuffer.height == self.height {
+            &framebuffer.buffer
+        } else {
+            &self.buffer
+        }
+    }
+
+    // Maps a window pixel back onto the framebuffer, undoing the scaling done
+    // by `present`. Returns None over the borders.
+    pub fn window_to_framebuffer(
+        &self,
+        x: f32,
+        y: f32,
+        framebuffer: &Framebuffer,
+        mode: PresentMode,
+    ) -> Option<(f32, f32)> {
+        let (fb_width, fb_height) = (framebuffer.width as f32, framebuffer.height as f32);
+        let (width, height) = (self.width as f32, self.height as f32);
+
+        let (fb_x, fb_y) = if framebuffer.width == self.width && framebuffer.height == self.height {
+            (x, y)
+        } else {
+            match mode {
+                PresentMode::Direct => {
+                    let fitted = self.fitted(framebuffer);
+                    (
+                        (x - fitted.x as f32) * fb_width / fitted.width as f32,
+                        (y - fitted.y as f32) * fb_height / fitted.height as f32,
+                    )
+                }
+                PresentMode::IntegerScale => {
+                    let scale = (self.width / framebuffer.width)
+                        .min(self.height / framebuffer.height)
+                        .max(1) as f32;
+                    let offset_x = ((width - fb_width * scale) / 2.0).max(0.0).floor();
+                    let offset_y = ((height - fb_height * scale) / 2.0).max(0.0).floor();
+                    ((x - offset_x) / scale, (y - offset_y) / scale)
+                }
+            }
+        };
+
+        (fb_x >= 0.0 && fb_y >= 0.0 && fb_x < fb_width && fb_y < fb_height).then_some((fb_x, fb_y))
+    }
+
+    // The largest part of the window with the framebuffer's aspect ratio,
+    // centered, with bars left over along two sides when the ratios differ
+    fn fitted(&self, framebuffer: &Framebuffer) -> Rect {
+        let scale = (self.width as f32 / framebuffer.width as f32)
+            .min(self.height as f32 / framebuffer.height as f32);
+        let width = ((framebuffer.width as f32 * scale).round() as usize).clamp(1, self.width);
+        let height = ((framebuffer.height as f32 * scale).round() as usize).clamp(1, self.height);
+        Rect {
+            x: (self.width - width) / 2,
+            y: (self.height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    fn stretch(&mut self, framebuffer: &Framebuffer) {
+        let fitted = self.fitted(framebuffer);
+        self.buffer.fill(self.border_color);
+        for y in 0..fitted.height {
+            let src_y = y * framebuffer.height / fitted.height;
+            let dst_row = (y + fitted.y) * self.width + fitted.x;
+            for x in 0..fitted.width {
+                let src_x = x * framebuffer.width / fitted.width;
+                self.buffer[dst_row + x] = framebuffer.buffer[src_y * framebuffer.width + src_x];
+            }
+        }
+    }
+
+    // Samples at pixel centers, so the edges clamp rather than wrap
+    fn stretch_bilinear(&mut self, framebuffer: &Framebuffer) {
+        let fitted = self.fitted(framebuffer);
+        let (fb_width, fb_height) = (framebuffer.width, framebuffer.height);
+        let x_ratio = fb_width as f32 / fitted.width as f32;
+        let y_ratio = fb_height as f32 / fitted.height as f32;
+        self.buffer.fill(self.border_color);
+        for y in 0..fitted.height {
+            let src_y = ((y as f32 + 0.5) * y_ratio - 0.5).max(0.0);
+            let (y0, ty) = (src_y as usize, src_y.fract());
+            let y1 = (y0 + 1).min(fb_height - 1);
+            let dst_row = (y + fitted.y) * self.width + fitted.x;
+            for x in 0..fitted.width {
+                let src_x = ((x as f32 + 0.5) * x_ratio - 0.5).max(0.0);
+                let (x0, tx) = (src_x as usize, src_x.fract());
+                let x1 = (x0 + 1).min(fb_width - 1);
+                let pixel = |px: usize, py: usize| framebuffer.buffer[py * fb_width + px];
+                let mut blended = 0;
+                for shift in [16, 8, 0] {
+                    let channel = |color: u32| ((color >> shift) & 0xFF) as f32;
+                    let top = channel(pixel(x0, y0)) * (1.0 - tx) + channel(pixel(x1, y0)) * tx;
+                    let bottom = channel(pixel(x0, y1)) * (1.0 - tx) + channel(pixel(x1, y1)) * tx;
+                    let value = top * (1.0 - ty) + bottom * ty;
+                    blended |= (value.round() as u32) << shift;
+                }
+                self.buffer[dst_row + x] = blended;
+            }
+        }
+    }
+
+    // Nearest-neighbor upscale by the largest whole factor that fits, centered
+    // with borders so every source pixel maps to an identical square block
+    fn integer_scale(&mut self, framebuffer: &Framebuffer) {
+        let scale = (self.width / framebuffer.width)
+            .min(self.height / framebuffer.height)
+            .max(1);
+        let scaled_width = (framebuffer.width * scale).min(self.width);
+        let scaled_height = (framebuffer.height * scale).min(self.height);
+        let offset_x = (self.width - scaled_width) / 2;
+        let offset_y = (self.height - scaled_height) / 2;
+
+        self.buffer.fill(self.border_color);
+
+        for y in 0..scaled_height {
+            let src_row = (y / scale) * framebuffer.width;
+            let dst_row = (y + offset_y) * self.width + offset_x;
+            for x in 0..scaled_width {
+                self.buffer[dst_row + x] = framebuffer.buffer[src_row + x / scale];
+            }
+        }
+    }
+}
+
+// Scales finished frames for the window on a thread of its own, so the
+// viewer hands one over and goes on with the rest of the frame, its
+// bookkeeping and the pacer's wait, instead of doing the scaling itself.
+// minifb only lets the thread that opened the window update it, so the frame
+// comes back at the top of the next frame, for the viewer to hand to the
+// frontend once that one's drawn. The presenter and the frame go along with
+// each job and come back with it.
+pub struct PresentThread {
+    jobs: Sender<(Presenter, Framebuffer, PresentMode)>,
+    done: Receiver<(Presenter, Framebuffer)>,
+}
+
+impl PresentThread {
+    pub fn spawn() -> Self {
+        let (jobs, incoming) = mpsc::channel::<(Presenter, Framebuffer, PresentMode)>();
+        let (finished, done) = mpsc::channel();
+        thread::spawn(move || {
+            for (mut presenter, framebuffer, mode) in incoming {
+                presenter.scale(&framebuffer, mode);
+                if finished.send((presenter, framebuffer)).is_err() {
+                    break;
+                }
+            }
+        });
+        PresentThread { jobs, done }
+    }
+
+    pub fn submit(&self, presenter: Presenter, framebuffer: Framebuffer, mode: PresentMode) {
+        self.jobs
+            .send((presenter, framebuffer, mode))
+            .expect("present thread stopped");
+    }
+
+    // Waits for the frame last submitted to be scaled
+    pub fn finish(&self) -> (Presenter, Framebuffer) {
+        self.done.recv().expect("present thread stopped")
+    }
+}
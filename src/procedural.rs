@@ -0,0 +1,238 @@
+use crate::vertex::Vertex;
+use fastnoise_lite::{FastNoiseLite, NoiseType};
+use nalgebra_glm::{Vec2, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+// Latitude/longitude sphere centered at the origin, as a flat triangle list
+pub fn uv_sphere(radius: f32, segments: usize, rings: usize) -> Vec<Vertex> {
+    let point = |segment: usize, ring: usize| {
+        let u = segment as f32 / segments as f32;
+        let v = ring as f32 / rings as f32;
+        let theta = u * 2.0 * PI;
+        let phi = v * PI;
+
+        let normal = Vec3::new(phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin());
+        Vertex::new(normal * radius, normal, Vec2::new(u, v))
+    };
+
+    let mut vertices = Vec::with_capacity(segments * rings * 6);
+    for ring in 0..rings {
+        for segment in 0..segments {
+            let top_left = point(segment, ring);
+            let top_right = point(segment + 1, ring);
+            let bottom_left = point(segment, ring + 1);
+            let bottom_right = point(segment + 1, ring + 1);
+
+            if ring != 0 {
+                vertices.extend([top_left.clone(), bottom_left.clone(), top_right.clone()]);
+            }
+            if ring != rings - 1 {
+                vertices.extend([top_right, bottom_left, bottom_right]);
+            }
+        }
+    }
+
+    vertices
+}
+
+// Where `uv_sphere` puts the texture coordinates on the unit `direction`,
+// for surfaces of a sphere that aren't built by it
+pub fn sphere_tex_coords(direction: &Vec3) -> Vec2 {
+    Vec2::new(
+        direction.z.atan2(direction.x).rem_euclid(TAU) / TAU,
+        direction.y.clamp(-1.0, 1.0).acos() / PI,
+    )
+}
+
+// Unit icosahedron refined `subdivisions` times, as shared positions plus
+// triangle indices
+pub fn icosphere(subdivisions: u32) -> (Vec<Vec3>, Vec<[usize; 3]>) {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Vec3::new(x, y, z).normalize())
+    .collect();
+
+    let mut faces = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+        let mut midpoint = |a: usize, b: usize, positions: &mut Vec<Vec3>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(((positions[a] + positions[b]) * 0.5).normalize());
+                positions.len() - 1
+            })
+        };
+
+        faces = faces
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut positions);
+                let bc = midpoint(b, c, &mut positions);
+                let ca = midpoint(c, a, &mut positions);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    (positions, faces)
+}
+
+// Lumpy, cratered rock of roughly unit radius. The same seed always gives the
+// same shape.
+pub fn asteroid(seed: u64, subdivisions: u32) -> Vec<Vertex> {
+    let (directions, faces) = icosphere(subdivisions);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut noise = FastNoiseLite::with_seed(seed as i32);
+    noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+
+    let craters: Vec<(Vec3, f32, f32)> = (0..rng.gen_range(3..8))
+        .map(|_| {
+            let direction = Vec3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            )
+            .normalize();
+            (
+                direction,
+                rng.gen_range(0.2..0.55),
+                rng.gen_range(0.05..0.15),
+            )
+        })
+        .collect();
+    let stretch = Vec3::new(
+        rng.gen_range(0.8..1.3),
+        rng.gen_range(0.6..1.0),
+        rng.gen_range(0.8..1.2),
+    );
+
+    let positions: Vec<Vec3> = directions
+        .iter()
+        .map(|&direction| {
+            let mut radius = 1.0;
+
+            // Multi-octave lumps
+            let mut amplitude = 0.25;
+            let mut frequency = 60.0;
+            for _ in 0..4 {
+                let p = direction * frequency;
+                radius += noise.get_noise_3d(p.x, p.y, p.z) * amplitude;
+                amplitude *= 0.5;
+                frequency *= 2.0;
+            }
+
+            // Bowl-shaped dents with a slightly raised rim
+            for &(center, size, depth) in &craters {
+                let distance = direction.angle(&center) / size;
+                if distance < 1.0 {
+                    radius -= (1.0 - distance * distance) * depth;
+                }
+                radius += (-((distance - 1.0) / 0.2).powi(2)).exp() * depth * 0.3;
+            }
+
+            direction.component_mul(&stretch) * radius
+        })
+        .collect();
+
+    let mut normals = vec![Vec3::zeros(); positions.len()];
+    for &[a, b, c] in &faces {
+        let face_normal = (positions[b] - positions[a]).cross(&(positions[c] - positions[a]));
+        for index in [a, b, c] {
+            normals[index] += face_normal;
+        }
+    }
+
+    faces
+        .iter()
+        .flat_map(|face| face.iter())
+        .map(|&index| {
+            let direction = directions[index];
+            let tex_coords = Vec2::new(
+                0.5 + direction.z.atan2(direction.x) / (2.0 * PI),
+                0.5 - direction.y.asin() / PI,
+            );
+            Vertex::new(positions[index], normals[index].normalize(), tex_coords)
+        })
+        .collect()
+}
+
+// Placement of one rock in an asteroid belt, relative to the belt's center
+pub struct BeltRock {
+    pub position: Vec3,
+    pub scale: f32,
+    pub rotation: Vec3,
+    pub seed: u32,
+}
+
+// Scatters `count` rocks in a flat ring around the origin. Radii are biased
+// toward the middle of the ring so its edges thin out.
+pub fn asteroid_belt(
+    count: usize,
+    inner_radius: f32,
+    outer_radius: f32,
+    thickness: f32,
+    seed: u64,
+) -> Vec<BeltRock> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let middle = (inner_radius + outer_radius) * 0.5;
+    let half_width = (outer_radius - inner_radius) * 0.5;
+
+    (0..count)
+        .map(|_| {
+            let angle = rng.gen_range(0.0..2.0 * PI);
+            let spread: f32 = rng.gen_range(-1.0..1.0) + rng.gen_range(-1.0..1.0);
+            let radius = middle + spread * 0.5 * half_width;
+            let height = (rng.gen_range(-1.0..1.0) + rng.gen_range(-1.0..1.0)) * 0.25 * thickness;
+
+            BeltRock {
+                position: Vec3::new(radius * angle.cos(), height, radius * angle.sin()),
+                scale: rng.gen_range(0.015..0.05),
+                rotation: Vec3::new(
+                    rng.gen_range(0.0..2.0 * PI),
+                    rng.gen_range(0.0..2.0 * PI),
+                    rng.gen_range(0.0..2.0 * PI),
+                ),
+                seed: rng.gen(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,208 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::locale::Locale;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// The parts of the pipeline timed apart, in the order a triangle goes
+// through them
+#[derive(Clone, Copy)]
+pub enum Stage {
+    Vertex,
+    // Clipping, the divide by w, the viewport and the painter's sort
+    Assembly,
+    // Walking each triangle's pixels and writing what's shaded to them
+    Raster,
+    Fragment,
+    // Tone mapping, post-processing and the copy to the window
+    Present,
+}
+
+pub const STAGES: usize = 5;
+// Looked up in the locale
+pub const NAMES: [&str; STAGES] = [
+    "profiler.vertex",
+    "profiler.assembly",
+    "profiler.raster",
+    "profiler.fragment",
+    "profiler.present",
+];
+const COLORS: [u32; STAGES] = [0x60A0FF, 0x60E0C0, 0xFFD060, 0xFF8060, 0xC080FF];
+
+// Frames the averages are taken over
+const WINDOW: usize = 60;
+
+// Nanoseconds spent in each stage so far this frame, added to by every
+// render thread, so stages that run in parallel count the time on every
+// thread
+static SPENT: [AtomicU64; STAGES] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+// Nothing is timed while it's off, so it costs nothing then
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_enabled(on: bool) {
+    ENABLED.store(on, Ordering::Relaxed);
+    take();
+}
+
+// Milliseconds spent in each stage since the last call
+pub fn take() -> [f32; STAGES] {
+    SPENT.each_ref().map(|spent| {
+        let nanos = spent.swap(0, Ordering::Relaxed);
+        nanos as f32 / 1_000_000.0
+    })
+}
+
+pub fn add(stage: Stage, spent: Duration) {
+    if enabled() {
+        SPENT[stage as usize].fetch_add(spent.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+// Time added up over many short stretches, like every fragment a thread
+// shades, to be passed to `add` once at the end
+pub struct Stopwatch {
+    total: Option<Duration>,
+}
+
+impl Stopwatch {
+    pub fn new() -> Self {
+        Stopwatch {
+            total: enabled().then_some(Duration::ZERO),
+        }
+    }
+
+    pub fn time<T>(&mut self, work: impl FnOnce() -> T) -> T {
+        let Some(total) = self.total.as_mut() else {
+            return work();
+        };
+        let start = Instant::now();
+        let result = work();
+        *total += start.elapsed();
+        result
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.total.unwrap_or_default()
+    }
+}
+
+// What each stage of the pipeline took over the last WINDOW frames, in
+// milliseconds, shown in a corner so it's clear which one a slow frame is
+// waiting on. Times are CPU time summed over the render threads, so with
+// several threads they can add up to more than the frame took.
+pub struct Profiler {
+    history: Vec<[f32; STAGES]>,
+    frames: Vec<f32>,
+    next: usize,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            history: Vec::with_capacity(WINDOW),
+            frames: Vec::with_capacity(WINDOW),
+            next: 0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        enabled()
+    }
+
+    // Turning it back on starts the averages over
+    pub fn toggle(&mut self) {
+        set_enabled(!enabled());
+        self.history.clear();
+        self.frames.clear();
+        self.next = 0;
+    }
+
+    // Takes in what this frame spent in each stage, out of `frame_ms` for
+    // the whole frame
+    pub fn end_frame(&mut self, frame_ms: f32) {
+        if !enabled() {
+            return;
+        }
+        let spent = take();
+        if self.history.len() < WINDOW {
+            self.history.push(spent);
+            self.frames.push(frame_ms);
+        } else {
+            self.history[self.next] = spent;
+            self.frames[self.next] = frame_ms;
+        }
+        self.next = (self.next + 1) % WINDOW;
+    }
+
+    fn averages(&self) -> ([f32; STAGES], f32) {
+        let count = self.history.len().max(1) as f32;
+        let mut stages = [0.0; STAGES];
+        for frame in &self.history {
+            for (total, spent) in stages.iter_mut().zip(frame) {
+                *total += spent / count;
+            }
+        }
+        (stages, self.frames.iter().sum::<f32>() / count)
+    }
+
+    // Drawn over the resolved frame, in the top right corner
+    pub fn draw(&self, framebuffer: &mut Framebuffer, locale: &Locale) {
+        const PADDING: usize = 6;
+        const LINE: usize = GLYPH_HEIGHT + 5;
+        const WIDTH: usize = 260;
+        const BAR_X: usize = 175;
+
+        let (stages, frame_ms) = self.averages();
+        let busiest = stages.iter().copied().fold(f32::EPSILON, f32::max);
+        let width = WIDTH.min(framebuffer.width.saturating_sub(2));
+        let panel = Rect {
+            x: framebuffer.width - width - 1,
+            y: 1,
+            width,
+            height: (PADDING * 2 + LINE * (STAGES + 2)).min(framebuffer.height.saturating_sub(2)),
+        };
+        framebuffer.reset_scissor();
+        framebuffer.set_current_color(0x101018);
+        framebuffer.fill_rect(panel);
+
+        let left = panel.x + PADDING;
+        let mut y = panel.y + PADDING;
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.draw_text(left as i32, y as i32, locale.get("profiler.title"), 1);
+        y += LINE;
+
+        let value_right = panel.x + BAR_X - PADDING;
+        let bar_width = panel.width.saturating_sub(BAR_X + PADDING);
+        let rows = NAMES.iter().zip(COLORS).zip(stages);
+        for ((name, color), spent) in rows {
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(left as i32, y as i32, locale.get(name), 1);
+            let value = format!("{:.2}", spent);
+            let value_x = value_right.saturating_sub(text_width(&value, 1));
+            framebuffer.draw_text(value_x as i32, y as i32, &value, 1);
+            // Against the busiest stage, so the bottleneck fills its bar
+            framebuffer.set_current_color(color);
+            framebuffer.fill_rect(Rect {
+                x: panel.x + BAR_X,
+                y,
+                width: (bar_width as f32 * spent / busiest).round() as usize,
+                height: GLYPH_HEIGHT,
+            });
+            y += LINE;
+        }
+
+        let text = locale.format("profiler.frame", &[&format!("{:.2}", frame_ms)]);
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.draw_text(left as i32, y as i32, &text, 1);
+    }
+}
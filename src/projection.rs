@@ -0,0 +1,69 @@
+use crate::FIELD_OF_VIEW;
+
+const MIN_FIELD: f32 = 10.0;
+const MAX_FIELD: f32 = 150.0;
+// Closest the near plane comes to the eye, and farthest out the far plane
+// goes, in world units
+const MIN_NEAR: f32 = 0.001;
+const MAX_FAR: f32 = 100_000.0;
+// Share of the way to the nearest surface the near plane is kept to on a
+// close fly-by, and the most times farther the far plane is then let be, so
+// the depth buffer keeps its precision
+const NEAR_CLEARANCE: f32 = 0.5;
+const MAX_DEPTH_RATIO: f32 = 100_000.0;
+
+// The perspective the views are drawn with, changed while the window is open
+// to show off what it does: a wide field stretches the bodies toward the
+// edges of the view, and a near plane pushed out, or a far one pulled in,
+// cuts into them. The telescope keeps its own field but shares the planes.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Projection {
+    // Vertical, in radians
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection {
+            fov: FIELD_OF_VIEW,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+impl Projection {
+    // Widens the field by `degrees`, or narrows it for a negative number
+    pub fn widen(&mut self, degrees: f32) {
+        let field = (self.fov.to_degrees() + degrees).clamp(MIN_FIELD, MAX_FIELD);
+        self.fov = field.to_radians();
+    }
+
+    // Each plane is kept on its side of the other, at least twice as far as
+    // the near one
+    pub fn scale_near(&mut self, factor: f32) {
+        self.near = (self.near * factor).clamp(MIN_NEAR, self.far / 2.0);
+    }
+
+    pub fn scale_far(&mut self, factor: f32) {
+        self.far = (self.far * factor).clamp(self.near * 2.0, MAX_FAR);
+    }
+
+    // The planes for an eye `clearance` world units off the nearest surface.
+    // Once that's close enough for the near plane to cut into the ground,
+    // it's brought in ahead of the surface and the far one after it.
+    pub fn close_to(self, clearance: f32) -> Self {
+        let near = clearance * NEAR_CLEARANCE;
+        if near >= self.near {
+            return self;
+        }
+        let near = near.max(MIN_NEAR);
+        Projection {
+            near,
+            far: self.far.min(near * MAX_DEPTH_RATIO),
+            ..self
+        }
+    }
+}
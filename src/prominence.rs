@@ -0,0 +1,108 @@
+use crate::framebuffer::Framebuffer;
+use crate::particles::{Blend, ColorRamp, ParticleStyle, Particles};
+use crate::Uniforms;
+use nalgebra_glm::Vec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const ACTIVE_REGIONS: usize = 3;
+// How long a region keeps erupting before another one flares up elsewhere
+const REGION_FRAMES: u32 = 400;
+const EMIT_RATE: f32 = 2.5;
+// Launch speed and the pull back down, in star radii per frame and per
+// frame², so loops reach about a fifth of a radius over the surface
+const LAUNCH_SPEED: f32 = 0.01;
+const SURFACE_GRAVITY: f32 = 0.00025;
+
+// White-hot plasma cooling to deep red before it falls back in
+const PLASMA: ParticleStyle = ParticleStyle {
+    lifetime: (80, 120),
+    color: ColorRamp {
+        start: Vec3::new(1.2, 0.7, 0.25),
+        end: Vec3::new(0.5, 0.05, 0.0),
+        falloff: 0.7,
+    },
+    blend: Blend::Additive,
+    size: 0.005,
+};
+
+#[derive(Clone)]
+struct ActiveRegion {
+    // Unit direction from the star's center
+    site: Vec3,
+    // Along the surface, the way this region's loops lean
+    sweep: Vec3,
+    age: u32,
+}
+
+// Arcs of plasma thrown up from a few active regions on a star's surface and
+// pulled straight back down onto it
+#[derive(Clone)]
+pub struct Prominences {
+    regions: Vec<ActiveRegion>,
+    plasma: Particles,
+    rng: StdRng,
+}
+
+impl Prominences {
+    pub fn new(seed: u64) -> Self {
+        Prominences {
+            regions: Vec::new(),
+            plasma: Particles::new(PLASMA, seed + 1),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn update(&mut self, center: Vec3, radius: f32) {
+        self.plasma.update(
+            |position| (center - position).normalize() * (SURFACE_GRAVITY * radius),
+            |position| (position - center).magnitude() > radius,
+        );
+
+        self.regions.retain_mut(|region| {
+            region.age += 1;
+            region.age < REGION_FRAMES
+        });
+        while self.regions.len() < ACTIVE_REGIONS {
+            let site = self.random_direction();
+            let sweep = site.cross(&self.random_direction()).normalize();
+            // Staggered so the regions don't all die out on the same frame
+            let age = self.rng.gen_range(0..REGION_FRAMES / 2);
+            self.regions.push(ActiveRegion { site, sweep, age });
+        }
+
+        let count = self.plasma.due(EMIT_RATE * ACTIVE_REGIONS as f32);
+        for _ in 0..count {
+            let region = &self.regions[self.rng.gen_range(0..ACTIVE_REGIONS)];
+            let (site, sweep) = (region.site, region.sweep);
+            let foot = (site + self.random_direction() * 0.06).normalize();
+            let lean = self.rng.gen_range(0.6..1.2);
+            let speed = LAUNCH_SPEED * radius * self.rng.gen_range(0.7..1.0);
+            let velocity = (foot + sweep * lean).normalize() * speed;
+            self.plasma.spawn(center + foot * radius * 1.001, velocity);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.regions.clear();
+        self.plasma.clear();
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        self.plasma.draw(framebuffer, uniforms);
+    }
+
+    fn random_direction(&mut self) -> Vec3 {
+        loop {
+            let v = Vec3::new(
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+            );
+            let length = v.magnitude();
+            if length > 0.01 && length <= 1.0 {
+                return v / length;
+            }
+        }
+    }
+}
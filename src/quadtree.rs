@@ -0,0 +1,274 @@
+use crate::cubemap::face_direction;
+use crate::horizon::behind_horizon;
+use crate::procedural::sphere_tex_coords;
+use crate::tectonics::{Continents, SEA_LEVEL};
+use crate::vertex::Vertex;
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use std::f32::consts::SQRT_2;
+
+// Quads along each side of a patch, whatever its level
+const GRID: usize = 16;
+// Deepest a face is split, where a patch is a few meters of a real planet
+const MAX_LEVEL: u32 = 12;
+// A patch is split into four once the eye is closer than this many of its
+// widths, so every patch comes out about the same size on screen
+const SPLIT_DISTANCE: f32 = 1.5;
+// New patches made in a frame, so diving at the surface streams the detail
+// in over a few frames instead of stalling one. Until all four of a patch's
+// children are in, it's drawn itself.
+const BUILDS_PER_FRAME: usize = 8;
+// Frames a patch is kept after it was last needed, so turning back doesn't
+// build it again
+const KEEP_FRAMES: u32 = 300;
+// Highest land stands above the sea, as a share of the radius. Far more than
+// a real planet's, to be seen at all.
+const RELIEF: f32 = 0.02;
+// How far each patch's edges hang down, as a share of its width, to hide the
+// cracks where it meets a coarser neighbour
+const SKIRT: f32 = 0.1;
+
+// Eyes closer to the surface than this many radii get the quadtree's
+// terrain instead of the planet's fixed mesh
+pub const ALTITUDE: f32 = 1.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    face: usize,
+    level: u32,
+    x: u32,
+    y: u32,
+}
+
+impl Key {
+    fn children(self) -> [Key; 4] {
+        [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| Key {
+            face: self.face,
+            level: self.level + 1,
+            x: self.x * 2 + dx,
+            y: self.y * 2 + dy,
+        })
+    }
+}
+
+struct Patch {
+    vertices: Vec<Vertex>,
+    center: Vec3,
+    // Corner to corner
+    width: f32,
+    // Cone from the planet's center holding the whole patch, for the
+    // horizon test
+    axis: Vec3,
+    spread: f32,
+    max_radius: f32,
+    last_used: u32,
+}
+
+// The planet's surface as a cube projected onto the sphere, each face a
+// quadtree of patches that split as the eye nears them. Close up the ground
+// under the eye is as fine as the screen can show while the rest of the
+// planet stays coarse, and the Terran shader's land is raised off the sea by
+// the plate simulation's heights. Patches are built as they're first needed
+// and dropped once they've gone unused a while.
+pub struct QuadSphere {
+    radius: f32,
+    // Whether the patches that are in were raised by continents
+    displaced: bool,
+    patches: HashMap<Key, Patch>,
+    drawn: Vec<Key>,
+    mesh: Vec<Vertex>,
+    frame: u32,
+}
+
+impl QuadSphere {
+    pub fn new(radius: f32) -> Self {
+        QuadSphere {
+            radius,
+            displaced: false,
+            patches: HashMap::new(),
+            drawn: Vec::new(),
+            mesh: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    // Whether `eye`, in the planet's own space, is low enough to draw it
+    pub fn within_reach(&self, eye: &Vec3) -> bool {
+        eye.magnitude() < self.radius * (1.0 + ALTITUDE)
+    }
+
+    // Picks the patches to draw from `eye`, in the planet's own space,
+    // building what's missing. Land is raised by `continents` when given.
+    pub fn update(&mut self, eye: &Vec3, continents: Option<&Continents>) {
+        if continents.is_some() != self.displaced {
+            self.patches.clear();
+            self.displaced = continents.is_some();
+        }
+        self.frame += 1;
+        // Nothing is lower than the sea, and a flat triangle between the
+        // widest-spaced vertices of a face sags below the sphere by less
+        // than this
+        let step = (2.0 * SQRT_2 / GRID as f32).atan();
+        let occluder = self.radius * (step / 2.0).cos();
+
+        let mut builds = BUILDS_PER_FRAME;
+        let mut drawn = Vec::new();
+        for face in 0..6 {
+            let root = Key {
+                face,
+                level: 0,
+                x: 0,
+                y: 0,
+            };
+            if !self.patches.contains_key(&root) {
+                self.patches
+                    .insert(root, build(root, self.radius, continents));
+            }
+            self.visit(root, eye, occluder, continents, &mut builds, &mut drawn);
+        }
+
+        let frame = self.frame;
+        self.patches
+            .retain(|key, patch| key.level == 0 || frame - patch.last_used < KEEP_FRAMES);
+        if drawn != self.drawn {
+            self.mesh = drawn
+                .iter()
+                .flat_map(|key| self.patches[key].vertices.iter().cloned())
+                .collect();
+            self.drawn = drawn;
+        }
+    }
+
+    // The patches picked by the last update, as one triangle list
+    pub fn mesh(&self) -> &[Vertex] {
+        &self.mesh
+    }
+
+    fn visit(
+        &mut self,
+        key: Key,
+        eye: &Vec3,
+        occluder: f32,
+        continents: Option<&Continents>,
+        builds: &mut usize,
+        drawn: &mut Vec<Key>,
+    ) {
+        let Some(patch) = self.patches.get_mut(&key) else {
+            return;
+        };
+        patch.last_used = self.frame;
+        if behind_horizon(occluder, eye, &patch.axis, patch.spread, patch.max_radius) {
+            return;
+        }
+        let split = key.level < MAX_LEVEL
+            && (eye - patch.center).magnitude() < SPLIT_DISTANCE * patch.width;
+        if split {
+            let children = key.children();
+            for child in children {
+                if *builds > 0 && !self.patches.contains_key(&child) {
+                    self.patches
+                        .insert(child, build(child, self.radius, continents));
+                    *builds -= 1;
+                }
+            }
+            if children
+                .iter()
+                .all(|child| self.patches.contains_key(child))
+            {
+                for child in children {
+                    self.visit(child, eye, occluder, continents, builds, drawn);
+                }
+                return;
+            }
+        }
+        drawn.push(key);
+    }
+}
+
+// Height above the sphere at `direction`, as a share of the radius. The sea
+// stays flat, so only land is raised.
+fn height(continents: Option<&Continents>, direction: &Vec3) -> f32 {
+    continents.map_or(0.0, |continents| {
+        let land = (continents.elevation(direction) - SEA_LEVEL) / (1.0 - SEA_LEVEL);
+        land.max(0.0) * RELIEF
+    })
+}
+
+fn build(key: Key, radius: f32, continents: Option<&Continents>) -> Patch {
+    let size = 1.0 / (1u32 << key.level) as f32;
+    // Grid points one past each edge too, so normals along the edges come
+    // out the same as the neighbouring patch's
+    let side = GRID + 3;
+    let mut positions = Vec::with_capacity(side * side);
+    for j in 0..side {
+        for i in 0..side {
+            let u = (key.x as f32 + (i as f32 - 1.0) / GRID as f32) * size;
+            let v = (key.y as f32 + (j as f32 - 1.0) / GRID as f32) * size;
+            let direction = face_direction(key.face, u, v);
+            positions.push(direction * radius * (1.0 + height(continents, &direction)));
+        }
+    }
+    let at = |i: usize, j: usize| positions[(j + 1) * side + i + 1];
+    let grid: Vec<Vertex> = (0..=GRID)
+        .flat_map(|j| (0..=GRID).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let position = at(i, j);
+            let across = positions[(j + 1) * side + i + 2] - positions[(j + 1) * side + i];
+            let down = positions[(j + 2) * side + i + 1] - positions[j * side + i + 1];
+            let normal = across.cross(&down).normalize();
+            let normal = if normal.dot(&position) < 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            Vertex::new(position, normal, sphere_tex_coords(&position.normalize()))
+        })
+        .collect();
+    let vertex = |i: usize, j: usize| grid[j * (GRID + 1) + i].clone();
+
+    let mut vertices = Vec::with_capacity(GRID * GRID * 6 + GRID * 4 * 6);
+    for j in 0..GRID {
+        for i in 0..GRID {
+            vertices.extend([vertex(i, j), vertex(i + 1, j), vertex(i, j + 1)]);
+            vertices.extend([vertex(i + 1, j), vertex(i + 1, j + 1), vertex(i, j + 1)]);
+        }
+    }
+
+    let width = (at(0, 0) - at(GRID, GRID)).magnitude();
+    let lowered = |vertex: &Vertex| Vertex {
+        position: vertex.position - vertex.position.normalize() * width * SKIRT,
+        ..vertex.clone()
+    };
+    let edges: [Vec<(usize, usize)>; 4] = [
+        (0..=GRID).map(|i| (i, 0)).collect(),
+        (0..=GRID).map(|i| (i, GRID)).collect(),
+        (0..=GRID).map(|j| (0, j)).collect(),
+        (0..=GRID).map(|j| (GRID, j)).collect(),
+    ];
+    for edge in &edges {
+        for pair in edge.windows(2) {
+            let (a, b) = (vertex(pair[0].0, pair[0].1), vertex(pair[1].0, pair[1].1));
+            let (low_a, low_b) = (lowered(&a), lowered(&b));
+            vertices.extend([a, b.clone(), low_a.clone()]);
+            vertices.extend([b, low_b, low_a]);
+        }
+    }
+
+    let center = at(GRID / 2, GRID / 2);
+    let axis = center.normalize();
+    let (mut spread, mut max_radius) = (0.0f32, 0.0f32);
+    for vertex in &vertices {
+        let cosine = (axis.dot(&vertex.position) / vertex.position.magnitude()).clamp(-1.0, 1.0);
+        spread = spread.max(cosine.acos());
+        max_radius = max_radius.max(vertex.position.magnitude());
+    }
+    Patch {
+        vertices,
+        center,
+        width,
+        axis,
+        spread,
+        max_radius,
+        last_used: 0,
+    }
+}
@@ -0,0 +1,82 @@
+// Smallest fraction of the full size rendered, and how far each change moves
+// the scale
+const MIN_SCALE: f32 = 0.4;
+const STEP: f32 = 0.1;
+// Frames to wait after a change before measuring again
+const SETTLE_FRAMES: u32 = 20;
+// Weight of the newest frame in the running average
+const SMOOTHING: f32 = 0.1;
+// Drops below the budget by more than this fraction before scaling up, so it
+// doesn't flip between two sizes that land either side of the target
+const HEADROOM: f32 = 0.25;
+
+// Renders at a fraction of the full framebuffer size while frames take
+// longer than the target, and climbs back up once there's room to spare.
+// Frame times are smoothed so one slow frame doesn't change anything, and
+// after each change the scale holds for a while so the times measured at the
+// new size settle before it's judged again.
+pub struct DynamicResolution {
+    pub enabled: bool,
+    // Fraction of the full width and height being rendered
+    pub scale: f32,
+    budget_ms: f32,
+    average_ms: f32,
+    cooldown: u32,
+}
+
+impl DynamicResolution {
+    // A target of 0 fps (uncapped) aims for 60
+    pub fn new(target_fps: u32) -> Self {
+        let fps = if target_fps == 0 { 60 } else { target_fps };
+        let budget_ms = 1000.0 / fps as f32;
+        DynamicResolution {
+            enabled: false,
+            scale: 1.0,
+            budget_ms,
+            average_ms: budget_ms,
+            cooldown: SETTLE_FRAMES,
+        }
+    }
+
+    // Takes how long the last frame took to draw, not counting any wait for
+    // the next one, and returns the new scale when it changes
+    pub fn update(&mut self, frame_ms: f32) -> Option<f32> {
+        if !self.enabled {
+            return None;
+        }
+        self.average_ms += (frame_ms - self.average_ms) * SMOOTHING;
+        if self.cooldown > 0 {
+            self.cooldown -= 1;
+            return None;
+        }
+
+        let scale = if self.average_ms > self.budget_ms {
+            (self.scale - STEP).max(MIN_SCALE)
+        } else if self.average_ms < self.budget_ms * (1.0 - HEADROOM) {
+            (self.scale + STEP).min(1.0)
+        } else {
+            self.scale
+        };
+        if (scale - self.scale).abs() < f32::EPSILON {
+            return None;
+        }
+        // Shading cost goes with the pixel count, so expect the frame time to
+        // follow the area until it's measured at the new size
+        self.average_ms *= (scale / self.scale).powi(2);
+        self.scale = scale;
+        self.cooldown = SETTLE_FRAMES;
+        Some(scale)
+    }
+
+    // Back to full size, for when it's turned off
+    pub fn reset(&mut self) {
+        self.scale = 1.0;
+        self.average_ms = self.budget_ms;
+        self.cooldown = SETTLE_FRAMES;
+    }
+
+    pub fn size(&self, width: usize, height: usize) -> (usize, usize) {
+        let scaled = |length: usize| ((length as f32 * self.scale).round() as usize).max(1);
+        (scaled(width), scaled(height))
+    }
+}
@@ -0,0 +1,166 @@
+use crate::kepler::{KeplerOrbit, Spin};
+use crate::noise::{NoiseRegistry, NoiseSettings};
+use crate::shaders::ShaderType;
+use crate::transform::{NodeId, SceneGraph, Transform};
+use fastnoise_lite::FastNoiseLite;
+use nalgebra_glm::Vec3;
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::Arc;
+
+// Which of the loaded models a satellite is drawn with
+#[derive(Clone, Copy, PartialEq)]
+pub enum SatelliteMesh {
+    Moon,
+    Asteroid,
+}
+
+// A body orbiting another one. Its own satellites orbit it in turn, so a list
+// of these describes a whole moon system. The built-in ones borrow their
+// names and moons from statics, and the ones read from a scene file own
+// theirs.
+#[derive(Clone)]
+pub struct Satellite {
+    pub name: Cow<'static, str>,
+    pub mesh: SatelliteMesh,
+    pub shader: ShaderType,
+    // None to be shaded with the shader's own noise
+    pub noise: Option<NoiseSettings>,
+    pub scale: f32,
+    // In planet masses
+    pub mass: f32,
+    // Around the parent's center
+    pub orbit: KeplerOrbit,
+    pub spin: Spin,
+    pub satellites: Cow<'static, [Satellite]>,
+}
+
+// A moon system, one of the built-in ones or one read from a scene file,
+// which stays shared for as long as anything is drawing or moving it. It's
+// told apart from others by which one it is rather than what's in it, as
+// `BodyNoise` does, since each built-in system and each read of a scene file
+// is only made once.
+#[derive(Clone)]
+pub enum MoonSystem {
+    BuiltIn(&'static [Satellite]),
+    Read(Arc<[Satellite]>),
+}
+
+impl Deref for MoonSystem {
+    type Target = [Satellite];
+
+    fn deref(&self) -> &[Satellite] {
+        match self {
+            MoonSystem::BuiltIn(satellites) => satellites,
+            MoonSystem::Read(satellites) => satellites,
+        }
+    }
+}
+
+impl PartialEq for MoonSystem {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(&**self, &**other)
+    }
+}
+
+// The noise generators the planet and its satellites are shaded with, built
+// from their settings when the moon system is loaded instead of every frame.
+// Satellites without settings of their own share their shader's.
+pub struct BodyNoise {
+    moons: Option<MoonSystem>,
+    shaders: NoiseRegistry,
+    bodies: Vec<(Cow<'static, str>, FastNoiseLite)>,
+}
+
+impl BodyNoise {
+    pub fn new() -> Self {
+        BodyNoise {
+            moons: None,
+            shaders: NoiseRegistry::new(),
+            bodies: Vec::new(),
+        }
+    }
+
+    // Called once a frame with the moon system being drawn; the generators
+    // are only built again once it's another one
+    pub fn update(&mut self, moons: &MoonSystem) {
+        if self.moons.as_ref() == Some(moons) {
+            return;
+        }
+        fn build(satellites: &[Satellite], bodies: &mut Vec<(Cow<'static, str>, FastNoiseLite)>) {
+            for satellite in satellites {
+                if let Some(noise) = satellite.noise {
+                    bodies.push((satellite.name.clone(), noise.build()));
+                }
+                build(&satellite.satellites, bodies);
+            }
+        }
+        self.moons = Some(moons.clone());
+        self.bodies.clear();
+        build(moons, &mut self.bodies);
+    }
+
+    // What a body drawn with `shader` and no settings of its own is shaded
+    // with, the planet included
+    pub fn shader(&self, shader: ShaderType) -> &FastNoiseLite {
+        self.shaders.get(shader)
+    }
+
+    pub fn of(&self, satellite: &Satellite) -> &FastNoiseLite {
+        self.bodies
+            .iter()
+            .find(|(body, _)| *body == satellite.name)
+            .map_or_else(|| self.shader(satellite.shader), |(_, noise)| noise)
+    }
+}
+
+// Adds every satellite in the tree to `scene` under `parent`, the frame the
+// parent body orbits in, and returns each one's body node with parents before
+// their children. Each satellite gets a frame of its own that only follows
+// its orbit, so its spin doesn't carry its moons around with it.
+pub fn attach<'a>(
+    scene: &mut SceneGraph,
+    satellites: &'a [Satellite],
+    parent: NodeId,
+    time: u32,
+) -> Vec<(&'a Satellite, NodeId)> {
+    attach_with(scene, satellites, parent, time, |satellite| {
+        satellite.orbit.position(time)
+    })
+}
+
+// Like `attach`, but with each satellite's frame `offset` from its parent's
+// instead of where its orbit has it at `time`, asked for in the order the
+// bodies are returned
+pub fn attach_with<'a>(
+    scene: &mut SceneGraph,
+    satellites: &'a [Satellite],
+    parent: NodeId,
+    time: u32,
+    mut offset: impl FnMut(&'a Satellite) -> Vec3,
+) -> Vec<(&'a Satellite, NodeId)> {
+    let mut attached = Vec::new();
+    attach_into(scene, satellites, parent, time, &mut offset, &mut attached);
+    attached
+}
+
+fn attach_into<'a>(
+    scene: &mut SceneGraph,
+    satellites: &'a [Satellite],
+    parent: NodeId,
+    time: u32,
+    offset: &mut impl FnMut(&'a Satellite) -> Vec3,
+    attached: &mut Vec<(&'a Satellite, NodeId)>,
+) {
+    for satellite in satellites {
+        let frame = scene.add(Some(parent), Transform::at(offset(satellite)));
+        let body = Transform::new(
+            Vec3::zeros(),
+            satellite.scale,
+            satellite.spin.orientation(time),
+        );
+        attached.push((satellite, scene.add(Some(frame), body)));
+        attach_into(scene, &satellite.satellites, frame, time, offset, attached);
+    }
+}
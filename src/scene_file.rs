@@ -0,0 +1,384 @@
+use crate::error::{self, Error};
+use crate::kepler::{KeplerOrbit, Spin};
+use crate::locale::snake_case;
+use crate::menu::SHADERS;
+use crate::noise::NoiseSettings;
+use crate::satellite::{MoonSystem, Satellite, SatelliteMesh};
+use crate::shaders::ShaderType;
+use fastnoise_lite::NoiseType;
+use std::borrow::Cow;
+use std::f32::consts::TAU;
+use std::fs;
+use std::iter::Peekable;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+// Moon systems read from a text file instead of the built-in ones, and read
+// again whenever the file changes, so a layout can be tried out while the
+// window stays open. A `system` line names the planet shaders, e.g.
+// `system gas_giant cold_gas_giant`, and each line under it is a body with
+// its name, a colon and `key=value` fields:
+//
+//     system rocky_planet
+//       Moon: scale=0.15 mass=0.0123 distance=2 period=1260 eccentricity=0.12
+//         Moonlet: mesh=asteroid scale=0.04 distance=0.35 period=300 spin=200
+//
+// Two more spaces of indent than a body puts it in orbit around that body.
+// `scale`, `distance` (the semi-major axis) and `period` are required; the
+// rest are `mesh` (moon or asteroid), `shader` (moon by default), `mass` in
+// planet masses, `eccentricity`, the angles `inclination`, `node`,
+// `periapsis`, `phase` and `tilt` in degrees, `spin`, the frames per turn,
+// which leaves the body tidally locked when it's left out, and the noise the
+// body is shaded with: `noise` (simplex, perlin, cellular or value),
+// `noise_seed`, `noise_frequency` and `noise_octaves`. Planets with no
+// `system` have no satellites. `#` starts a comment line.
+pub struct SceneFile {
+    path: PathBuf,
+    // When the file was last written and how long it was, to tell a save
+    // apart from the one before it
+    stamp: Option<(SystemTime, u64)>,
+    polled: bool,
+    systems: Option<Systems>,
+}
+
+impl SceneFile {
+    // Nothing is read until the first `poll`
+    pub fn watch(path: impl Into<PathBuf>) -> Self {
+        SceneFile {
+            path: path.into(),
+            stamp: None,
+            polled: false,
+            systems: None,
+        }
+    }
+
+    // Reads the file on the first call and again once it's been saved since,
+    // returning how that went, or None when it hasn't changed. A file that
+    // doesn't parse leaves the systems it last read in place.
+    pub fn poll(&mut self) -> Option<error::Result<()>> {
+        let metadata = fs::metadata(&self.path);
+        let stamp = metadata
+            .as_ref()
+            .ok()
+            .and_then(|metadata| Some((metadata.modified().ok()?, metadata.len())));
+        if self.polled && stamp == self.stamp {
+            return None;
+        }
+        self.polled = true;
+        self.stamp = stamp;
+
+        let path = self.path.display().to_string();
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) => return Some(Err(Error::io(path)(err))),
+        };
+        match parse(&contents) {
+            Ok(systems) => self.systems = Some(systems),
+            Err((line, message)) => {
+                return Some(Err(Error::Scene {
+                    path,
+                    line,
+                    message,
+                }))
+            }
+        }
+        Some(Ok(()))
+    }
+
+    // The satellites the file gives `shader`, or None before it's been read
+    pub fn satellites(&self, shader: ShaderType) -> Option<MoonSystem> {
+        let systems = self.systems.as_ref()?;
+        let system = systems
+            .iter()
+            .find(|(shaders, _)| shaders.contains(&shader));
+        Some(system.map_or(MoonSystem::BuiltIn(&[]), |(_, satellites)| {
+            MoonSystem::Read(satellites.clone())
+        }))
+    }
+}
+
+// A body's line, with how far in it was indented and where it was
+struct Entry {
+    line: usize,
+    depth: usize,
+    satellite: Satellite,
+}
+
+type Systems = Vec<(Vec<ShaderType>, Arc<[Satellite]>)>;
+
+// Errors come with the line number they're on
+fn parse(contents: &str) -> Result<Systems, (usize, String)> {
+    let mut systems = Vec::new();
+    let mut shaders = None;
+    let mut entries = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let text = line.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        if let Some(names) = text.strip_prefix("system ") {
+            if let Some(shaders) = shaders.take() {
+                systems.push((shaders, nest(&mut entries.drain(..).peekable(), 1)?.into()));
+            }
+            let list = names
+                .split_whitespace()
+                .map(|name| shader_named(name).ok_or_else(|| unknown("shader", name)))
+                .collect::<Result<_, _>>()
+                .map_err(|err| (number, err))?;
+            shaders = Some(list);
+            continue;
+        }
+
+        if shaders.is_none() {
+            return Err((number, "a body before any `system` line".to_string()));
+        }
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent == 0 || indent % 2 != 0 {
+            return Err((
+                number,
+                "bodies are indented in steps of two spaces".to_string(),
+            ));
+        }
+        entries.push(Entry {
+            line: number,
+            depth: indent / 2,
+            satellite: parse_body(text).map_err(|err| (number, err))?,
+        });
+    }
+    if let Some(shaders) = shaders {
+        systems.push((shaders, nest(&mut entries.drain(..).peekable(), 1)?.into()));
+    }
+    Ok(systems)
+}
+
+// The bodies at `depth` until the indent goes back out past it, each with the
+// ones indented under it as its satellites
+fn nest(
+    entries: &mut Peekable<impl Iterator<Item = Entry>>,
+    depth: usize,
+) -> Result<Vec<Satellite>, (usize, String)> {
+    let mut satellites = Vec::new();
+    while let Some(entry) = entries.next_if(|entry| entry.depth >= depth) {
+        if entry.depth > depth {
+            return Err((entry.line, "indented past the body above".to_string()));
+        }
+        let mut satellite = entry.satellite;
+        satellite.satellites = Cow::Owned(nest(entries, depth + 1)?);
+        satellites.push(satellite);
+    }
+    Ok(satellites)
+}
+
+fn parse_body(text: &str) -> Result<Satellite, String> {
+    let (name, fields) = text
+        .split_once(':')
+        .ok_or_else(|| "expected a name and a colon".to_string())?;
+    let mut satellite = Satellite {
+        name: Cow::Owned(name.trim().to_string()),
+        mesh: SatelliteMesh::Moon,
+        shader: ShaderType::Moon,
+        noise: None,
+        scale: f32::NAN,
+        mass: 0.0,
+        orbit: KeplerOrbit {
+            semi_major_axis: f32::NAN,
+            eccentricity: 0.0,
+            inclination: 0.0,
+            ascending_node: 0.0,
+            argument_of_periapsis: 0.0,
+            phase: 0.0,
+            period: f32::NAN,
+        },
+        spin: Spin {
+            rate: f32::NAN,
+            axial_tilt: 0.0,
+        },
+        satellites: Cow::Borrowed(&[]),
+    };
+
+    // Put together once the shader is known, whose noise fills in the rest
+    let (mut kind, mut seed, mut frequency, mut octaves) = (None, None, None, None);
+    for field in fields.split_whitespace() {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value, found '{}'", field))?;
+        let number = || {
+            value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| format!("{} isn't a number: '{}'", key, value))
+        };
+        let orbit = &mut satellite.orbit;
+        match key {
+            "mesh" => {
+                satellite.mesh = match value {
+                    "moon" => SatelliteMesh::Moon,
+                    "asteroid" => SatelliteMesh::Asteroid,
+                    _ => return Err(unknown("mesh", value)),
+                }
+            }
+            "shader" => {
+                satellite.shader = shader_named(value).ok_or_else(|| unknown(key, value))?
+            }
+            "noise" => kind = Some(noise_named(value).ok_or_else(|| unknown(key, value))?),
+            "noise_seed" => {
+                seed = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("{} isn't a whole number: '{}'", key, value))?,
+                )
+            }
+            "noise_frequency" => frequency = Some(number()?),
+            "noise_octaves" => {
+                octaves = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&octaves| (1..=8).contains(&octaves))
+                        .ok_or_else(|| format!("{} is from 1 to 8: '{}'", key, value))?,
+                )
+            }
+            "scale" => satellite.scale = number()?,
+            "mass" => satellite.mass = number()?,
+            "distance" => orbit.semi_major_axis = number()?,
+            "eccentricity" => orbit.eccentricity = number()?,
+            "inclination" => orbit.inclination = number()?.to_radians(),
+            "node" => orbit.ascending_node = number()?.to_radians(),
+            "periapsis" => orbit.argument_of_periapsis = number()?.to_radians(),
+            "phase" => orbit.phase = number()?.to_radians(),
+            "period" => orbit.period = number()?,
+            "spin" => satellite.spin.rate = TAU / number()?,
+            "tilt" => satellite.spin.axial_tilt = number()?.to_radians(),
+            _ => return Err(format!("unknown field '{}'", key)),
+        }
+    }
+
+    if kind.is_some() || seed.is_some() || frequency.is_some() || octaves.is_some() {
+        let shader = NoiseSettings::for_shader(&satellite.shader);
+        satellite.noise = Some(NoiseSettings {
+            kind: kind.unwrap_or(shader.kind),
+            seed: seed.unwrap_or(shader.seed),
+            frequency: frequency.unwrap_or(shader.frequency),
+            octaves: octaves.unwrap_or(shader.octaves),
+        });
+    }
+
+    for (key, value) in [
+        ("scale", satellite.scale),
+        ("distance", satellite.orbit.semi_major_axis),
+        ("period", satellite.orbit.period),
+    ] {
+        if value.is_nan() || value <= 0.0 {
+            return Err(format!("needs a {} above 0", key));
+        }
+    }
+    if !(0.0..1.0).contains(&satellite.orbit.eccentricity) {
+        return Err("eccentricity is from 0 up to but not including 1".to_string());
+    }
+    if satellite.spin.rate.is_nan() {
+        satellite.spin.rate = TAU / satellite.orbit.period;
+    } else if satellite.spin.rate.is_infinite() {
+        return Err("spin needs some frames per turn".to_string());
+    }
+    Ok(satellite)
+}
+
+// Shaders go by the names of their locale keys, e.g. `gas_giant`
+fn shader_named(name: &str) -> Option<ShaderType> {
+    SHADERS
+        .into_iter()
+        .find(|&shader| snake_case(&format!("{:?}", shader)) == name)
+}
+
+fn noise_named(name: &str) -> Option<NoiseType> {
+    match name {
+        "simplex" => Some(NoiseType::OpenSimplex2),
+        "perlin" => Some(NoiseType::Perlin),
+        "cellular" => Some(NoiseType::Cellular),
+        "value" => Some(NoiseType::Value),
+        _ => None,
+    }
+}
+
+fn unknown(what: &str, name: &str) -> String {
+    format!("unknown {} '{}'", what, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_system() {
+        let systems = parse(
+            "# A comment\n\
+             system gas_giant cold_gas_giant\n\
+             \n\
+             \x20 Io: scale=0.1 distance=2 period=600 mass=0.02 inclination=90\n\
+             \x20 Europa: mesh=asteroid scale=0.05 distance=3 period=1200 spin=100\n",
+        )
+        .unwrap();
+        assert_eq!(systems.len(), 1);
+        let (shaders, satellites) = &systems[0];
+        assert_eq!(shaders, &[ShaderType::GasGiant, ShaderType::ColdGasGiant]);
+        assert_eq!(satellites.len(), 2);
+        let io = &satellites[0];
+        assert_eq!(io.name, "Io");
+        assert_eq!(io.mass, 0.02);
+        assert_eq!(io.orbit.semi_major_axis, 2.0);
+        assert!((io.orbit.inclination - TAU / 4.0).abs() < 1e-6);
+        // Left out, the spin keeps it tidally locked
+        assert_eq!(io.spin.rate, TAU / 600.0);
+        let europa = &satellites[1];
+        assert_eq!(europa.name, "Europa");
+        assert!(europa.mesh == SatelliteMesh::Asteroid);
+        assert_eq!(europa.spin.rate, TAU / 100.0);
+        assert!(europa.satellites.is_empty());
+    }
+
+    #[test]
+    fn nests_satellites_under_the_body_above() {
+        let systems = parse(
+            "system terran\n\
+             \x20 Moon: scale=0.15 distance=2 period=1260\n\
+             \x20   Moonlet: scale=0.04 distance=0.35 period=300\n\
+             \x20     Speck: scale=0.01 distance=0.05 period=40\n\
+             \x20 Second: scale=0.1 distance=4 period=2000\n",
+        )
+        .unwrap();
+        let satellites = &systems[0].1;
+        let names: Vec<&str> = satellites.iter().map(|s| &*s.name).collect();
+        assert_eq!(names, ["Moon", "Second"]);
+        let moonlet = &satellites[0].satellites[0];
+        assert_eq!(moonlet.name, "Moonlet");
+        assert_eq!(moonlet.satellites.len(), 1);
+        assert_eq!(moonlet.satellites[0].name, "Speck");
+        assert!(satellites[1].satellites.is_empty());
+    }
+
+    #[test]
+    fn errors_say_which_line() {
+        let error = |contents: &str| parse(contents).err().unwrap();
+        let (line, message) = error(
+            "system terran\n\
+             \x20 Moon: scale=0.15 distance=2 period=1260\n\
+             \n\
+             \x20 Broken: scale=0.1 distance=3\n",
+        );
+        assert_eq!(line, 4);
+        assert!(message.contains("period"), "{}", message);
+        assert_eq!(error("  Moon: scale=1 distance=2 period=3\n").0, 1);
+        assert_eq!(error("system terran\n   Odd: scale=1\n").0, 2);
+        assert_eq!(error("system no_such_planet\n").0, 1);
+        let (line, message) = error(
+            "system terran\n\
+             \x20 Moon: scale=0.15 distance=2 period=1260\n\
+             \x20     Lost: scale=0.1 distance=3 period=10\n",
+        );
+        assert_eq!(line, 3);
+        assert!(message.contains("indented past"), "{}", message);
+    }
+}
@@ -0,0 +1,52 @@
+use std::f32::consts::TAU;
+
+// The terran planet's trip around its star. `year_length` is in frames and
+// `axial_tilt` is the angle between the spin axis and the orbit's normal.
+#[derive(Clone, Copy)]
+pub struct Orbit {
+    pub year_length: f32,
+    pub axial_tilt: f32,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Orbit {
+            year_length: 3600.0,
+            axial_tilt: 23.4_f32.to_radians(),
+        }
+    }
+}
+
+impl Orbit {
+    // Fraction of the year elapsed, starting at the northern spring equinox
+    pub fn phase(&self, time: u32) -> f32 {
+        (time as f32 / self.year_length).fract()
+    }
+
+    pub fn season(&self, time: u32) -> Season {
+        let declination = (self.axial_tilt.sin() * (self.phase(time) * TAU).sin()).asin();
+        Season {
+            declination,
+            axial_tilt: self.axial_tilt,
+        }
+    }
+}
+
+// Where the sun sits over the year right now. `declination` is the latitude
+// it passes straight overhead, in radians.
+#[derive(Clone, Copy)]
+pub struct Season {
+    pub declination: f32,
+    pub axial_tilt: f32,
+}
+
+impl Season {
+    // How deep into summer a latitude is, from -1 at midwinter on a pole to 1
+    // at midsummer, and always 0 on the equator. `latitude` is its sine.
+    pub fn warmth(&self, latitude: f32) -> f32 {
+        if self.axial_tilt <= 0.0 {
+            return 0.0;
+        }
+        self.declination / self.axial_tilt * latitude
+    }
+}
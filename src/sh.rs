@@ -0,0 +1,71 @@
+use nalgebra_glm::Vec3;
+
+// Real spherical harmonics up to order 2, evaluated for a unit direction
+fn basis(direction: &Vec3) -> [f32; 9] {
+    let (x, y, z) = (direction.x, direction.y, direction.z);
+    [
+        0.282_095,
+        0.488_603 * y,
+        0.488_603 * z,
+        0.488_603 * x,
+        1.092_548 * x * y,
+        1.092_548 * y * z,
+        0.315_392 * (3.0 * z * z - 1.0),
+        1.092_548 * x * z,
+        0.546_274 * (x * x - y * y),
+    ]
+}
+
+// Distant lighting compressed to nine RGB coefficients, already convolved
+// with the cosine lobe: evaluating at a normal gives what a white Lambertian
+// surface facing that way reflects
+#[derive(Clone, Copy)]
+pub struct ShIrradiance {
+    coefficients: [Vec3; 9],
+}
+
+impl ShIrradiance {
+    // Projects (direction, radiance, solid angle) samples covering the sphere
+    pub fn project(samples: impl Iterator<Item = (Vec3, Vec3, f32)>) -> Self {
+        let mut coefficients = [Vec3::zeros(); 9];
+        for (direction, radiance, solid_angle) in samples {
+            for (coefficient, y) in coefficients.iter_mut().zip(basis(&direction)) {
+                *coefficient += radiance * (y * solid_angle);
+            }
+        }
+
+        // Ramamoorthi and Hanrahan's per-band factors for the clamped cosine,
+        // divided by π to go from irradiance to reflected radiance
+        let bands = [
+            1.0,
+            2.0 / 3.0,
+            2.0 / 3.0,
+            2.0 / 3.0,
+            0.25,
+            0.25,
+            0.25,
+            0.25,
+            0.25,
+        ];
+        for (coefficient, band) in coefficients.iter_mut().zip(bands) {
+            *coefficient *= band;
+        }
+
+        ShIrradiance { coefficients }
+    }
+
+    // The same light `factor` times as bright
+    pub fn scaled(&self, factor: f32) -> Self {
+        ShIrradiance {
+            coefficients: self.coefficients.map(|coefficient| coefficient * factor),
+        }
+    }
+
+    pub fn evaluate(&self, normal: &Vec3) -> Vec3 {
+        let mut result = Vec3::zeros();
+        for (coefficient, y) in self.coefficients.iter().zip(basis(normal)) {
+            result += coefficient * y;
+        }
+        result.map(|c| c.max(0.0))
+    }
+}
@@ -1,11 +1,19 @@
+use crate::atmosphere::Atmosphere;
 use crate::color::Color;
 use crate::fragment::Fragment;
+use crate::generator::PlanetKind;
+use crate::material::Material;
+use crate::noise::{fbm, ridged, sphere_noise, turbulence, warp};
+use crate::shadow::ShadowMap;
+use crate::tectonics::SEA_LEVEL;
 use crate::vertex::Vertex;
+use crate::weather::WeatherState;
 use crate::Uniforms;
-use nalgebra_glm::{mat4_to_mat3, Mat3, Vec3, Vec4};
-use rand::Rng;
+use fastnoise_lite::FastNoiseLite;
+use nalgebra_glm::{mat4_to_mat3, Mat3, Mat4, Vec3, Vec4};
+use std::f32::consts::TAU;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ShaderType {
     GasGiant,
     ColdGasGiant,
@@ -14,41 +22,211 @@ pub enum ShaderType {
     RockyPlanetVariant,
     AlienPlanet,
     GlacialTextured,
-    Moon
+    Moon,
+    Terran,
+    Lava,
+    Station,
+    Generated,
+    Outline,
 }
 
-pub fn vertex_shader(vertex: &Vertex, uniforms: &Uniforms) -> Vertex {
-    let position = Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
+// Polar aurora layer. `latitude` is the sine of the latitude where the oval
+// peaks, `band_width` its half-width in the same units
+#[derive(Clone, Copy)]
+pub struct AuroraParams {
+    pub intensity: f32,
+    pub latitude: f32,
+    pub band_width: f32,
+}
+
+impl Default for AuroraParams {
+    fn default() -> Self {
+        AuroraParams {
+            intensity: 1.0,
+            latitude: 0.85,
+            band_width: 0.12,
+        }
+    }
+}
+
+// Bandas y tormentas del gigante gaseoso. `storm_threshold` es el valor de
+// ruido a partir del cual aparece una tormenta
+#[derive(Clone, Copy)]
+pub struct GasGiantParams {
+    pub band_frequency: f32,
+    pub storm_threshold: f32,
+}
+
+impl Default for GasGiantParams {
+    fn default() -> Self {
+        GasGiantParams {
+            band_frequency: 40.0,
+            storm_threshold: 0.75,
+        }
+    }
+}
 
-    let transformed =
-        uniforms.projection_matrix * uniforms.view_matrix * uniforms.model_matrix * position;
+// Olas del océano de la Tierra. `wave_scale` es la escala del ruido del oleaje
+// y `wave_speed` cuánto avanza por cuadro, en unidades de ese ruido
+#[derive(Clone, Copy)]
+pub struct OceanParams {
+    pub wave_scale: f32,
+    pub wave_speed: f32,
+}
+
+impl Default for OceanParams {
+    fn default() -> Self {
+        OceanParams {
+            wave_scale: 4000.0,
+            wave_speed: 0.02,
+        }
+    }
+}
+
+// Dónde se calcula la luz: una vez por cara con su normal, en los vértices
+// interpolando el color que dan (Gouraud) o en cada fragmento con la normal
+// interpolada (Phong), que es lo normal
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ShadingModel {
+    Flat,
+    Gouraud,
+    Phong,
+}
+
+impl ShadingModel {
+    pub fn next(self) -> Self {
+        match self {
+            ShadingModel::Flat => ShadingModel::Gouraud,
+            ShadingModel::Gouraud => ShadingModel::Phong,
+            ShadingModel::Phong => ShadingModel::Flat,
+        }
+    }
+
+    // Se busca en el idioma como `shading.<nombre>`
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadingModel::Flat => "flat",
+            ShadingModel::Gouraud => "gouraud",
+            ShadingModel::Phong => "phong",
+        }
+    }
+}
 
-    let w = transformed.w;
-    let transformed_position =
-        Vec4::new(transformed.x / w, transformed.y / w, transformed.z / w, 1.0);
+// Lo que se ve en lugar de los sombreadores para buscar fallos: las normales
+// interpoladas como color, las coordenadas de textura o cuántos fragmentos
+// se escribieron en cada píxel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DebugView {
+    Normals,
+    TexCoords,
+    Overdraw,
+}
 
-    let screen_position = uniforms.viewport_matrix * transformed_position;
+impl DebugView {
+    // Ninguna, luego cada una en orden y otra vez ninguna
+    pub fn cycle(view: Option<DebugView>) -> Option<DebugView> {
+        match view {
+            None => Some(DebugView::Normals),
+            Some(DebugView::Normals) => Some(DebugView::TexCoords),
+            Some(DebugView::TexCoords) => Some(DebugView::Overdraw),
+            Some(DebugView::Overdraw) => None,
+        }
+    }
 
-    let model_mat3 = mat4_to_mat3(&uniforms.model_matrix);
-    let normal_matrix = model_mat3
-        .transpose()
-        .try_inverse()
-        .unwrap_or(Mat3::identity());
+    // Se busca en el idioma como `debug_view.<nombre>`
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugView::Normals => "normals",
+            DebugView::TexCoords => "uvs",
+            DebugView::Overdraw => "overdraw",
+        }
+    }
+}
 
-    let transformed_normal = normal_matrix * vertex.normal;
+// `transform` is viewport * projection * view * model. The viewport mapping is
+// affine, so applying it before the perspective divide gives the same result.
+pub fn vertex_shader(
+    vertex: &Vertex,
+    transform: &Mat4,
+    model_matrix: &Mat4,
+    normal_matrix: &Mat3,
+) -> Vertex {
+    let position = Vec4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0);
 
+    // Queda en espacio de recorte: la división por w y el viewport vienen
+    // después de recortar, en clipping::to_screen
     Vertex {
         position: vertex.position,
         normal: vertex.normal,
         tex_coords: vertex.tex_coords,
         color: vertex.color,
-        transformed_position: Vec3::new(screen_position.x, screen_position.y, screen_position.z),
-        transformed_normal: transformed_normal,
+        clip_position: transform * position,
+        screen_position: Vec3::zeros(),
+        transformed_normal: normal_matrix * vertex.normal,
+        world_position: (model_matrix * position).xyz(),
+        radiance: Vec3::zeros(),
+    }
+}
+
+// La luz de Gouraud: el vértice ya transformado se sombrea como si fuera un
+// fragmento, y lo que da se interpola por el triángulo en vez de la normal
+pub fn light_vertex(
+    vertex: &Vertex,
+    uniforms: &Uniforms,
+    shader_type: &ShaderType,
+    seed: u32,
+    material: Material,
+) -> Vec3 {
+    let normal = vertex.transformed_normal.normalize();
+    let fragment = Fragment {
+        world_position: vertex.world_position,
+        tex_coords: vertex.tex_coords,
+        instance_seed: seed,
+        material,
+        ..Fragment::new(vertex.color, normal, vertex.position)
+    };
+    fragment_shader(&fragment, uniforms, shader_type)
+}
+
+// Sombreado plano: los tres vértices toman la normal de la cara, del mismo
+// lado que las suyas, y todo el triángulo recibe la misma luz
+pub fn flatten(triangle: &mut [Vertex]) {
+    let [a, b, c] = [0, 1, 2].map(|i| triangle[i].world_position);
+    let face = (b - a).cross(&(c - a));
+    if face.magnitude() < f32::EPSILON {
+        return;
+    }
+    let smooth: Vec3 = triangle
+        .iter()
+        .map(|vertex| vertex.transformed_normal)
+        .sum();
+    let face = if face.dot(&smooth) < 0.0 { -face } else { face };
+    for vertex in triangle {
+        vertex.transformed_normal = face.normalize();
     }
 }
 
-pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms, shader_type: &ShaderType) -> Color {
-    match shader_type {
+// Radiancia HDR: 1.0 es blanco con exposición 1, y el brillo del sol o los
+// reflejos especulares pueden pasarse de ahí hasta el tone mapping
+pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms, shader_type: &ShaderType) -> Vec3 {
+    // La vista de depuración cambia todo menos el contorno de la selección
+    if *shader_type != ShaderType::Outline {
+        match uniforms.debug_view {
+            Some(DebugView::Normals) => return normal_debug_shader(fragment),
+            Some(DebugView::TexCoords) => return uv_debug_shader(fragment),
+            // Lo que se cuenta es cuántos fragmentos llegan, no su color
+            Some(DebugView::Overdraw) => return Vec3::zeros(),
+            None => {}
+        }
+    }
+    // Con el planeta ya sombreado en su mapa, basta con leerlo, salvo en el
+    // sombreado plano: el mapa tiene la luz de la esfera y no la de cada cara
+    let cached = uniforms
+        .surface
+        .filter(|(shader, _)| shader == shader_type)
+        .filter(|_| uniforms.shading != ShadingModel::Flat)
+        .map(|(_, map)| map.sample(&fragment.vertex_position.normalize()));
+    let radiance = cached.unwrap_or_else(|| match shader_type {
         ShaderType::GasGiant => gas_giant_shader(fragment, uniforms),
         ShaderType::ColdGasGiant => cold_gas_giant_shader(fragment, uniforms),
         ShaderType::Solar => solar_shader(fragment, uniforms),
@@ -56,34 +234,90 @@ pub fn fragment_shader(fragment: &Fragment, uniforms: &Uniforms, shader_type: &S
         ShaderType::RockyPlanetVariant => rocky_planet_variant_shader(fragment, uniforms),
         ShaderType::AlienPlanet => alien_planet_shader(fragment, uniforms),
         ShaderType::GlacialTextured => glacial_textured_shader(fragment, uniforms),
-        ShaderType::Moon => moon_shader(fragment, uniforms)
+        ShaderType::Moon => moon_shader(fragment, uniforms),
+        ShaderType::Terran => terran_shader(fragment, uniforms),
+        ShaderType::Lava => lava_shader(fragment, uniforms),
+        ShaderType::Station => station_shader(fragment, uniforms),
+        ShaderType::Generated => generated_shader(fragment, uniforms),
+        ShaderType::Outline => outline_shader(fragment),
+    });
+
+    // Los impactos solo caen sobre el planeta, no sobre la luna, la estación
+    // ni el contorno de la selección
+    #[cfg(feature = "physics")]
+    if !matches!(
+        shader_type,
+        ShaderType::Moon | ShaderType::Station | ShaderType::Outline
+    ) {
+        return impact_marks(radiance, fragment, uniforms);
+    }
+    radiance
+}
+
+// La normal interpolada en espacio de mundo, de [-1, 1] a [0, 1] en cada
+// canal: +x en rojo, +y en verde y +z en azul
+pub fn normal_debug_shader(fragment: &Fragment) -> Vec3 {
+    fragment.normal.add_scalar(1.0) * 0.5
+}
+
+// Coordenadas de textura a la vista: u en rojo, v en verde y un tablero de
+// 8x8 casillas encima para ver cómo se estiran y dónde está la costura
+pub fn uv_debug_shader(fragment: &Fragment) -> Vec3 {
+    let uv = fragment.tex_coords;
+    let cell = (uv.x * 8.0).floor() as i32 + (uv.y * 8.0).floor() as i32;
+    let checker = if cell.rem_euclid(2) == 0 { 1.0 } else { 0.5 };
+    Vec3::new(uv.x, uv.y, 0.2) * checker
+}
+
+// Quemadura oscura donde cayó cada roca y, encima, el destello del impacto,
+// un poco más ancho y que se apaga en pocos frames, y el fondo del cráter
+// todavía fundido, que brilla rojo mientras se enfría
+#[cfg(feature = "physics")]
+fn impact_marks(radiance: Vec3, fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let direction = fragment.vertex_position.normalize();
+    let mut color = radiance;
+    for mark in uniforms.impacts {
+        let angle = direction.dot(&mark.direction).clamp(-1.0, 1.0).acos();
+
+        let scorch = 1.0 - (angle / mark.radius).min(1.0);
+        color *= 1.0 - scorch.sqrt() * mark.scorch() * 0.75;
+        color += Vec3::new(1.6, 0.45, 0.08) * (scorch * scorch * mark.glow());
+
+        let flash = 1.0 - (angle / (mark.radius * 1.5)).min(1.0);
+        color += Vec3::new(4.0, 2.4, 1.2) * (flash * flash * mark.flash());
     }
+    color
 }
 
-pub fn moon_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn moon_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let position = fragment.vertex_position;
     let time = uniforms.time as f32 * 0.001;
+    // Cada instancia muestrea el ruido en otra zona para no repetir el patrón.
+    // Las semillas del cinturón llegan a u32::MAX, así que se acotan antes:
+    // con coordenadas de 1e9 el ruido pierde la precisión y se desborda.
+    let offset = (fragment.instance_seed % 1024) as f32 * 0.37;
+    let sample = position + Vec3::repeat(offset);
 
     let base_color = Color::new(180, 180, 180);  
     let crater_color = Color::new(100, 100, 100);
     let dust_color = Color::new(150, 150, 150);   
 
     let craters = uniforms.noise.get_noise_3d(
-        position.x * 150.0,
-        position.y * 150.0,
-        position.z * 150.0,
+        sample.x * 150.0,
+        sample.y * 150.0,
+        sample.z * 150.0,
     ).abs();
 
-    let dust = uniforms.noise.get_noise_3d(
-        position.x * 80.0 + time,
-        position.y * 80.0,
-        position.z * 80.0,
+    let dust = turbulence(
+        uniforms.noise,
+        sample * 80.0 + Vec3::new(time, 0.0, 0.0),
+        2,
     );
 
     let surface_details = uniforms.noise.get_noise_3d(
-        position.x * 200.0,
-        position.y * 200.0,
-        position.z * 200.0,
+        sample.x * 200.0,
+        sample.y * 200.0,
+        sample.z * 200.0,
     ).abs();
 
     let mut final_color = base_color;
@@ -92,150 +326,130 @@ pub fn moon_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         final_color = final_color.lerp(&crater_color, (craters - 0.7) * 2.0);
     }
 
-    final_color = final_color.lerp(&dust_color, dust.abs() * 0.2);
+    final_color = final_color.lerp(&dust_color, dust * 0.2);
 
     if surface_details > 0.8 {
         final_color = final_color.lerp(&crater_color, (surface_details - 0.8) * 0.5);
     }
 
-    let light_dir = Vec3::new(0.6, 0.8, 0.4).normalize();
-    let normal = position.normalize();
-    let lambertian = light_dir.dot(&normal).max(0.0);
-    let shading_factor = 0.75 + 0.25 * lambertian;
-
-    final_color = final_color * shading_factor;
-    final_color * fragment.intensity
+    phong(final_color.to_vec3(), fragment, uniforms)
 }
 
 
-pub fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let base_colors = [
-        Vec3::new(110.0 / 255.0, 0.0 / 255.0, 90.0 / 255.0),
-        Vec3::new(160.0 / 255.0, 20.0 / 255.0, 60.0 / 255.0),
-        Vec3::new(130.0 / 255.0, 10.0 / 255.0, 80.0 / 255.0),
-        Vec3::new(180.0 / 255.0, 40.0 / 255.0, 90.0 / 255.0),
-        Vec3::new(140.0 / 255.0, 10.0 / 255.0, 70.0 / 255.0),
-    ];
+        Color::new(110, 0, 90),
+        Color::new(160, 20, 60),
+        Color::new(130, 10, 80),
+        Color::new(180, 40, 90),
+        Color::new(140, 10, 70),
+    ]
+    .map(Color::to_vec3);
 
     let time = uniforms.time as f32 * 0.001;
-    let dynamic_y = fragment.vertex_position.y + time;
-
-    let distortion_scale = 10.0;
-    let distortion_value = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * distortion_scale,
-        dynamic_y * distortion_scale,
-    );
-
-    let distorted_y = dynamic_y + distortion_value * 0.1 + fragment.vertex_position.x * 0.05;
-
-    let band_frequency = 40.0;
-    let band_sine = (distorted_y * band_frequency).sin();
-    let band_variation = (fragment.vertex_position.y * 10.0).sin() * 0.3;
-    let band_index_float = (band_sine + band_variation + 1.0) / 2.0 * (base_colors.len() as f32);
-    let band_index = band_index_float as usize % base_colors.len();
-    let mut rng = rand::thread_rng();
-    let random_offset: f32 = rng.gen_range(-0.03..0.03);
-    let base_band_color =
-        base_colors[band_index] + Vec3::new(random_offset, random_offset, random_offset);
-
-    // Aumentar la saturación de algunas bandas de forma aleatoria
-    let saturation_boost: f32 = if rng.gen_bool(0.5) { 1.2 } else { 1.0 };
-    let boosted_band_color = base_band_color * saturation_boost;
-
-    // Se elige el siguiente color de banda para suavizar la transición
-    let next_band_index = (band_index + 1) % base_colors.len();
-    let next_band_color =
-        base_colors[next_band_index] + Vec3::new(random_offset, random_offset, random_offset);
-
-    // Interpolación suave entre colores adyacentes
-    let interpolation_factor = band_index_float.fract();
-    let interpolated_color = boosted_band_color.lerp(&next_band_color, interpolation_factor);
-
-    // capas de ruido de alta frecuencia para dar más textura a las bandas
-    let noise_scale_1 = 80.0;
-    let noise_value_1 = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * noise_scale_1,
-        fragment.vertex_position.y * noise_scale_1,
-    );
-
-    let noise_scale_2 = 40.0;
-    let noise_value_2 = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * noise_scale_2,
-        fragment.vertex_position.y * noise_scale_2,
-    );
-
-    let perturbed_color = interpolated_color * (0.95 + (noise_value_1 + noise_value_2) * 0.015);
+    let footprint = fragment.footprint();
+    // Las bandas leídas en `position`, que la gran tormenta desplaza
+    let bands = |position: Vec3| {
+        let dynamic_y = position.y + time;
+
+        let distortion_scale = 10.0;
+        let distortion_value = uniforms.noise.get_noise_3d(
+            position.x * distortion_scale,
+            dynamic_y * distortion_scale,
+            position.z * distortion_scale,
+        );
+
+        let distorted_y = dynamic_y + distortion_value * 0.1 + position.x * 0.05;
+
+        let band_frequency = uniforms.gas_giant.band_frequency;
+        let band_sine = filtered_sin(distorted_y * band_frequency, footprint * band_frequency);
+        let band_variation = filtered_sin(position.y * 10.0, footprint * 10.0) * 0.3;
+        // La variación puede sacar la suma de [-1, 1]; debajo de cero `fract` extrapolaría
+        let band_fraction = ((band_sine + band_variation + 1.0) / 2.0).max(0.0);
+        let band_index_float = band_fraction * (base_colors.len() as f32);
+        let band_index = band_index_float as usize % base_colors.len();
+        let (random_offset, saturation_boost) = band_grain(position, uniforms.time);
+        // Sin bajar de cero en los canales vacíos de la paleta
+        let base_band_color =
+            (base_colors[band_index] + Vec3::repeat(random_offset)).sup(&Vec3::zeros());
+
+        // Aumentar la saturación de algunas bandas de forma aleatoria
+        let boosted_band_color = base_band_color * saturation_boost;
+
+        // Se elige el siguiente color de banda para suavizar la transición
+        let next_band_index = (band_index + 1) % base_colors.len();
+        let next_band_color =
+            (base_colors[next_band_index] + Vec3::repeat(random_offset)).sup(&Vec3::zeros());
+
+        // Interpolación suave entre colores adyacentes
+        let interpolation_factor = band_index_float.fract();
+        let interpolated_color = boosted_band_color.lerp(&next_band_color, interpolation_factor);
+
+        // capas de ruido de alta frecuencia para dar más textura a las bandas.
+        // Lejos, el ruido fino se apaga en vez de centellear de un frame a otro
+        let max_octaves = 2 + uniforms.extra_octaves;
+        let octaves = filtered_octaves(fragment, uniforms.noise, 40.0, max_octaves);
+        let texture_noise = fbm(uniforms.noise, position * 40.0, octaves);
+        let texture_fade = noise_fade(fragment, uniforms.noise, 40.0);
+        let perturbed_color = interpolated_color * (0.95 + texture_noise * 0.03 * texture_fade);
+
+        let internal_shadow = (distorted_y * band_frequency * 0.1).sin().abs() * 0.15;
+        let shaded_color = perturbed_color * (1.0 - internal_shadow);
+
+        let shadow_noise_scale = 50.0;
+        let shadow_noise = sphere_noise(uniforms.noise, &position, shadow_noise_scale);
+        let shadow_variation =
+            1.0 - shadow_noise * 0.05 * noise_fade(fragment, uniforms.noise, shadow_noise_scale);
+        shaded_color * shadow_variation
+    };
 
-    let internal_shadow = (distorted_y * band_frequency * 0.1).sin().abs() * 0.15;
-    let shaded_color = perturbed_color * (1.0 - internal_shadow);
+    // Alrededor de la gran tormenta las bandas se enroscan hacia ella, y
+    // dentro del óvalo giran con ella y toman su color
+    let vortex = &uniforms.weather.vortex;
+    let (reach, around, within) = vortex.swirl(&fragment.vertex_position);
+    let mut final_shaded_color = bands(around);
+    if reach < 1.0 {
+        let inside = ((1.0 - reach) / 0.3).min(1.0);
+        let oval = bands(within).component_mul(&Vec3::new(1.6, 0.7, 0.5));
+        final_shaded_color = final_shaded_color.lerp(&oval, inside * inside * (3.0 - 2.0 * inside));
+    }
+    // Un collar oscuro marca el borde del óvalo
+    final_shaded_color *= 1.0 - 0.4 * (-((reach - 1.0) / 0.12).powi(2)).exp();
 
-    let shadow_noise_scale = 50.0;
-    let shadow_noise = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * shadow_noise_scale,
-        fragment.vertex_position.y * shadow_noise_scale,
-    );
-    let shadow_variation = 1.0 - shadow_noise * 0.05;
-    let final_shaded_color = shaded_color * shadow_variation;
     let spot_noise_scale = 25.0;
-    let spot_noise = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * spot_noise_scale,
-        fragment.vertex_position.y * spot_noise_scale,
-    );
-
-    let mut final_color;
+    let spot_noise = sphere_noise(uniforms.noise, &fragment.vertex_position, spot_noise_scale);
 
-    if spot_noise > 0.75 {
-        let mix_factor = (spot_noise - 0.75) / 0.25;
+    let storm_threshold = uniforms.gas_giant.storm_threshold;
+    let final_color = if spot_noise > storm_threshold {
+        let mix_factor = ((spot_noise - storm_threshold) / (1.0 - storm_threshold)).min(1.0);
         let storm_color = Vec3::new(0.95, 0.85, 0.65);
-        final_color = final_shaded_color.lerp(&storm_color, mix_factor);
+        final_shaded_color.lerp(&storm_color, mix_factor)
     } else {
-        final_color = final_shaded_color;
-    }
-
-    let normal = fragment.vertex_position.normalize();
-
-    let light_dir = Vec3::new(0.6, 0.8, 0.4).normalize();
-    let lambertian = light_dir.dot(&normal).max(0.0);
-    let shading_factor = 0.75 + 0.25 * lambertian;
-
-    final_color = final_color * shading_factor;
-
-    // dispersión atmosférica
-    let gradient_shading = 1.0 - (fragment.vertex_position.y.abs() * 0.15);
-    final_color = final_color * gradient_shading;
-
-    // reflejos especulares para simular brillos en la atmósfera
-    let view_dir = Vec3::new(0.0, 0.0, 1.0).normalize();
-    let reflect_dir = (2.0 * normal.dot(&light_dir) * normal - light_dir).normalize();
-    let specular_intensity = view_dir.dot(&reflect_dir).max(0.0).powf(10.0);
-
-    final_color = final_color + Vec3::new(1.0, 1.0, 1.0) * specular_intensity * 0.15;
-
-    final_color = final_color * fragment.intensity;
+        final_shaded_color
+    };
 
-    Color::new(
-        (final_color.x * 255.0) as u8,
-        (final_color.y * 255.0) as u8,
-        (final_color.z * 255.0) as u8,
-    )
+    let lit = phong(final_color, fragment, uniforms);
+    atmosphere(&Atmosphere::GAS_GIANT, lit, fragment, uniforms)
 }
 
-pub fn cold_gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn cold_gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let base_colors = [
-        Vec3::new(100.0 / 255.0, 150.0 / 255.0, 180.0 / 255.0),
-        Vec3::new(120.0 / 255.0, 180.0 / 255.0, 200.0 / 255.0),
-        Vec3::new(90.0 / 255.0, 140.0 / 255.0, 170.0 / 255.0),
-        Vec3::new(130.0 / 255.0, 190.0 / 255.0, 210.0 / 255.0),
-        Vec3::new(80.0 / 255.0, 120.0 / 255.0, 160.0 / 255.0),
-    ];
+        Color::new(100, 150, 180),
+        Color::new(120, 180, 200),
+        Color::new(90, 140, 170),
+        Color::new(130, 190, 210),
+        Color::new(80, 120, 160),
+    ]
+    .map(Color::to_vec3);
 
     let time = uniforms.time as f32 * 0.001;
     let dynamic_y = fragment.vertex_position.y + time;
 
     let distortion_scale = 10.0;
-    let distortion_value = uniforms.noise.get_noise_2d(
+    let distortion_value = uniforms.noise.get_noise_3d(
         fragment.vertex_position.x * distortion_scale,
         dynamic_y * distortion_scale,
+        fragment.vertex_position.z * distortion_scale,
     );
 
     let wind_tilt = fragment.vertex_position.x * 0.02;
@@ -243,180 +457,139 @@ pub fn cold_gas_giant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color
         dynamic_y + wind_tilt + distortion_value * 0.1 + fragment.vertex_position.x * 0.05;
 
     let band_frequency = 40.0;
-    let band_sine = (distorted_y * band_frequency).sin();
-    let band_variation = (fragment.vertex_position.y * 10.0).sin() * 0.3;
-    let band_index_float = (band_sine + band_variation + 1.0) / 2.0 * (base_colors.len() as f32);
+    let footprint = fragment.footprint();
+    let band_sine = filtered_sin(distorted_y * band_frequency, footprint * band_frequency);
+    let band_variation = filtered_sin(fragment.vertex_position.y * 10.0, footprint * 10.0) * 0.3;
+    // La variación puede sacar la suma de [-1, 1]; debajo de cero `fract` extrapolaría
+    let band_fraction = ((band_sine + band_variation + 1.0) / 2.0).max(0.0);
+    let band_index_float = band_fraction * (base_colors.len() as f32);
     let band_index = band_index_float as usize % base_colors.len();
-    let mut rng = rand::thread_rng();
-    let random_offset: f32 = rng.gen_range(-0.03..0.03);
+    let (random_offset, saturation_boost) = band_grain(fragment.vertex_position, uniforms.time);
+    // Sin bajar de cero en los canales vacíos de la paleta
     let base_band_color =
-        base_colors[band_index] + Vec3::new(random_offset, random_offset, random_offset);
+        (base_colors[band_index] + Vec3::repeat(random_offset)).sup(&Vec3::zeros());
 
-    let saturation_boost: f32 = if rng.gen_bool(0.5) { 1.2 } else { 1.0 };
     let boosted_band_color = base_band_color * saturation_boost;
 
     let next_band_index = (band_index + 1) % base_colors.len();
     let next_band_color =
-        base_colors[next_band_index] + Vec3::new(random_offset, random_offset, random_offset);
+        (base_colors[next_band_index] + Vec3::repeat(random_offset)).sup(&Vec3::zeros());
 
     let interpolation_factor = band_index_float.fract();
     let interpolated_color = boosted_band_color.lerp(&next_band_color, interpolation_factor);
 
-    let noise_scale_1 = 80.0;
-    let noise_value_1 = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * noise_scale_1,
-        fragment.vertex_position.y * noise_scale_1,
-    );
-
-    let noise_scale_2 = 40.0;
-    let noise_value_2 = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * noise_scale_2,
-        fragment.vertex_position.y * noise_scale_2,
-    );
-
-    let perturbed_color = interpolated_color * (0.95 + (noise_value_1 + noise_value_2) * 0.015);
+    // Lejos, el ruido fino se apaga en vez de centellear de un frame a otro
+    let max_octaves = 2 + uniforms.extra_octaves;
+    let octaves = filtered_octaves(fragment, uniforms.noise, 40.0, max_octaves);
+    let texture_noise = fbm(uniforms.noise, fragment.vertex_position * 40.0, octaves);
+    let texture_fade = noise_fade(fragment, uniforms.noise, 40.0);
+    let perturbed_color = interpolated_color * (0.95 + texture_noise * 0.03 * texture_fade);
 
     let internal_shadow = (distorted_y * band_frequency * 0.1).sin().abs() * 0.15;
     let shaded_color = perturbed_color * (1.0 - internal_shadow);
 
     let shadow_noise_scale = 50.0;
-    let shadow_noise = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * shadow_noise_scale,
-        fragment.vertex_position.y * shadow_noise_scale,
+    let shadow_noise = sphere_noise(
+        uniforms.noise,
+        &fragment.vertex_position,
+        shadow_noise_scale,
     );
-    let shadow_variation = 1.0 - shadow_noise * 0.05;
+    let shadow_variation =
+        1.0 - shadow_noise * 0.05 * noise_fade(fragment, uniforms.noise, shadow_noise_scale);
     let final_shaded_color = shaded_color * shadow_variation;
 
     let spot_noise_scale = 15.0;
-    let spot_noise = uniforms.noise.get_noise_2d(
-        fragment.vertex_position.x * spot_noise_scale,
-        fragment.vertex_position.y * spot_noise_scale,
-    );
+    let spot_noise = sphere_noise(uniforms.noise, &fragment.vertex_position, spot_noise_scale);
 
-    let mut final_color;
-
-    if spot_noise > 0.7 {
+    let final_color = if spot_noise > 0.7 {
         let mix_factor = (spot_noise - 0.7) / 0.3;
         let storm_color = Vec3::new(0.75, 0.85, 0.95);
-        final_color = final_shaded_color.lerp(&storm_color, mix_factor);
+        final_shaded_color.lerp(&storm_color, mix_factor)
     } else {
-        final_color = final_shaded_color;
-    }
-
-    let normal = fragment.vertex_position.normalize();
-
-    let light_dir = Vec3::new(0.6, 0.8, 0.4).normalize();
-    let lambertian = light_dir.dot(&normal).max(0.0);
-    let shading_factor = 0.75 + 0.25 * lambertian;
-    final_color = final_color * shading_factor;
-
-    let gradient_shading = 1.0 - (fragment.vertex_position.y.abs() * 0.15);
-    final_color = final_color * gradient_shading;
-
-    let view_dir = Vec3::new(0.0, 0.0, 1.0).normalize();
-    let reflect_dir = (2.0 * normal.dot(&light_dir) * normal - light_dir).normalize();
-    let specular_intensity = view_dir.dot(&reflect_dir).max(0.0).powf(10.0);
-    final_color = final_color + Vec3::new(1.0, 1.0, 1.0) * specular_intensity * 0.15;
-
-    final_color = final_color * fragment.intensity;
+        final_shaded_color
+    };
 
-    Color::new(
-        (final_color.x * 255.0) as u8,
-        (final_color.y * 255.0) as u8,
-        (final_color.z * 255.0) as u8,
-    )
+    let lit = phong(final_color, fragment, uniforms);
+    atmosphere(&Atmosphere::ICE_GIANT, lit, fragment, uniforms)
+        + aurora(fragment, uniforms).to_vec3()
 }
 
-pub fn solar_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
-    let bright_color = Color::new(255, 240, 70);
-    let mid_color = Color::new(255, 100, 0);
-    let dark_color = Color::new(70, 10, 0);
-
-    let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
-    );
-
-    let base_frequency = 0.04 + position.x * 0.01;
-    let pulsate_amplitude = 0.6 + position.y * 0.02;
-    let t = uniforms.time as f32 * 0.02;
-
-    let pulsate = (t * base_frequency).sin() * pulsate_amplitude;
-
-    let zoom = 1500.0;
-
-    // Obtener ruido en 3D para generar las manchas solares
-    let noise_value1 = uniforms.noise.get_noise_3d(
-        position.x * zoom,
-        position.y * zoom,
-        (position.z + pulsate) * zoom,
-    );
-    let noise_value2 = uniforms.noise.get_noise_3d(
-        (position.x + 300.0) * zoom,
-        (position.y + 300.0) * zoom,
-        (position.z + 300.0 + pulsate) * zoom,
-    );
-
-    let noise_value = (noise_value1 + noise_value2) * 0.5;
-
-    let fine_noise = uniforms.noise.get_noise_3d(
-        position.x * 500.0,
-        position.y * 500.0,
-        (position.z + pulsate) * 500.0,
-    );
-
-    let adjusted_noise = (noise_value + fine_noise * 0.6) * 1.8 - 0.4;
-
-    let high_freq_noise = uniforms.noise.get_noise_3d(
-        position.x * 2000.0,
-        position.y * 2000.0,
-        (position.z + pulsate) * 2000.0,
-    ) * 0.03;
-
-    let bands_pattern1 = (position.y * 6.0 + noise_value * 25.0 + t * 0.15).sin() * 0.2;
-    let bands_pattern2 = (position.y * 10.0 + noise_value * 50.0 + t * 0.08).sin() * 0.1;
-
-    let combined_bands = bands_pattern1 + bands_pattern2 + high_freq_noise;
-
-    let color = if adjusted_noise + combined_bands > 0.4 {
-        mid_color.lerp(&bright_color, adjusted_noise + combined_bands - 0.4)
-    } else {
-        dark_color.lerp(&mid_color, (adjusted_noise + combined_bands) * 2.5)
-    };
-
-    let pulse_effect = 1.0 + 0.15 * ((t * 1.5 + position.x * 0.05).sin());
-    let final_color = color * pulse_effect;
+// Granulación: celdas brillantes de convección separadas por canales oscuros,
+// que se deforman y se renuevan con el tiempo. El borde se oscurece y enrojece
+// como en una estrella real, y cada tanto se encienden arcos de protuberancia
+// cerca del limbo. Como fuente de luz emite por encima de 1.0 y el tone
+// mapping lo comprime en lugar de recortarlo.
+pub fn solar_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let hot_color = Vec3::new(1.0, 0.85, 0.45);
+    let granule_color = Vec3::new(0.9, 0.45, 0.08);
+    let lane_color = Vec3::new(0.3, 0.05, 0.0);
+
+    let direction = fragment.vertex_position.normalize();
+    let t = uniforms.time as f32 * 0.004;
+
+    // Las coordenadas se deforman con un campo de ruido que avanza lento, y
+    // los canales son las crestas del ruido ridged sobre ellas
+    let drift = Vec3::new(t, t * 0.7, -t * 0.5) * 100.0;
+    let warped = warp(uniforms.noise, direction * 250.0 + drift, 40.0, 2);
+    let lanes = ridged(uniforms.noise, warped - drift, 2).powi(3);
+    let granules = fbm(uniforms.noise, warped * 0.5 + drift, 2) * 0.5 + 0.5;
+
+    let color = granule_color
+        .lerp(&hot_color, granules)
+        .lerp(&lane_color, lanes);
+
+    // Oscurecimiento del limbo con la ley lineal (u = 0.6); el azul y el verde
+    // caen antes que el rojo
+    let mu = fragment
+        .normal
+        .dot(&fragment.view_dir(&uniforms.eye))
+        .max(0.0);
+    let limb = 1.0 - 0.6 * (1.0 - mu);
+    let reddening = Vec3::new(1.0, 0.75 + 0.25 * mu, 0.5 + 0.5 * mu);
+    let final_color =
+        color.component_mul(&reddening) * limb + prominence_arcs(direction, mu, uniforms.time);
+
+    phong(final_color, fragment, uniforms)
+}
 
-    final_color * fragment.intensity
+// Arcos que crecen, brillan y se apagan a lo largo de PROMINENCE_FRAMES, de a
+// tres escalonados. El sitio de cada uno sale de una espiral de Fibonacci,
+// así que todos los fragmentos coinciden sin guardar estado y los sitios
+// seguidos quedan lejos entre sí. Solo se ven cerca del borde.
+const PROMINENCE_FRAMES: u32 = 240;
+
+fn prominence_arcs(direction: Vec3, mu: f32, time: u32) -> Vec3 {
+    let mut glow = 0.0;
+    for slot in 0..3 {
+        let shifted = time + slot * PROMINENCE_FRAMES / 3;
+        let event = (shifted / PROMINENCE_FRAMES) * 3 + slot;
+        let phase = (shifted % PROMINENCE_FRAMES) as f32 / PROMINENCE_FRAMES as f32;
+
+        let z = 1.0 - 2.0 * (event as f32 * 0.618_034).fract();
+        let angle = event as f32 * 2.399_963;
+        let ring = (1.0 - z * z).sqrt();
+        let site = Vec3::new(ring * angle.cos(), z, ring * angle.sin());
+
+        // Un anillo alrededor del sitio que se abre mientras dura el arco
+        let distance = direction.dot(&site).clamp(-1.0, 1.0).acos();
+        let radius = 0.06 + 0.05 * phase;
+        let width = (1.0 - (distance - radius).abs() / 0.012).max(0.0);
+        glow += width * (phase * std::f32::consts::PI).sin();
+    }
+    Vec3::new(6.0, 2.4, 0.7) * glow * (1.0 - mu).powi(2)
 }
 
-pub fn rocky_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn rocky_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let bright_color = Color::new(230, 120, 70);
     let mid_color = Color::new(140, 70, 40);
     let dark_color = Color::new(30, 10, 5);
 
-    let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
-    );
+    let position = fragment.vertex_position;
 
     let zoom = 1200.0;
 
     // Obtener ruido para la superficie rocosa
-    let noise_value1 =
-        uniforms
-            .noise
-            .get_noise_3d(position.x * zoom, position.y * zoom, position.z * zoom);
-
-    let noise_value2 = uniforms.noise.get_noise_3d(
-        (position.x + 400.0) * zoom,
-        (position.y + 400.0) * zoom,
-        (position.z + 400.0) * zoom,
-    );
-
-    let noise_value = (noise_value1 + noise_value2) * 0.5;
+    let noise_value = fbm(uniforms.noise, position * zoom, 2);
 
     let crater_frequency = 1.5;
     let crater_amplitude = 2.0;
@@ -424,22 +597,11 @@ pub fn rocky_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         * (position.x * crater_frequency - position.y * crater_frequency).cos()
         * crater_amplitude;
 
-    let mut combined_value = (noise_value + crater_value).clamp(0.0, 1.0);
-
-    let fine_noise = uniforms.noise.get_noise_3d(
-        position.x * 1600.0,
-        position.y * 1600.0,
-        position.z * 1600.0,
-    ) * 0.3;
+    let combined_value = (noise_value + crater_value).clamp(0.0, 1.0);
 
-    combined_value = (combined_value + fine_noise).clamp(0.0, 1.0);
-
-    let fracture_noise = uniforms.noise.get_noise_3d(
-        position.x * 2000.0,
-        position.y * 2000.0,
-        position.z * 2000.0,
-    ) * 0.15;
-    combined_value = (combined_value + fracture_noise).clamp(0.0, 1.0);
+    // Grietas y fracturas finas
+    let fine_noise = fbm(uniforms.noise, position * 1600.0, 2) * 0.35;
+    let combined_value = (combined_value + fine_noise).clamp(0.0, 1.0);
 
     let color = if combined_value > 0.5 {
         mid_color.lerp(&bright_color, (combined_value - 0.5) * 1.5)
@@ -457,82 +619,76 @@ pub fn rocky_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
     let pulsate =
         (uniforms.time as f32 * pulsate_frequency + position.x * 0.02 + position.y * 0.02).sin()
             * pulsate_amplitude;
-    final_color = final_color * (1.0 + pulsate);
+    final_color *= 1.0 + pulsate;
+
+    // Sombras y brillos de la textura de la roca
+    let texture_noise = fbm(uniforms.noise, position * 2500.0, 3);
+    final_color *= 1.0 + texture_noise * 0.45;
+
+    let day_color = phong(final_color.to_vec3(), fragment, uniforms);
+    let night_color = day_color * 0.08 + city_lights(fragment, uniforms).to_vec3();
+
+    // Banda suave del terminador entre el lado iluminado y el nocturno
+    let normal = fragment.normal;
+    let sun_height = normal.dot(&uniforms.light_dir);
+    let daylight = ((sun_height + 0.1) / 0.25).clamp(0.0, 1.0);
+    let daylight = daylight * daylight * (3.0 - 2.0 * daylight);
+
+    night_color.lerp(&day_color, daylight)
+}
 
-    let shadow_texture_noise = uniforms.noise.get_noise_3d(
-        position.x * 2500.0,
-        position.y * 2500.0,
-        position.z * 2500.0,
-    ) * 0.3;
-    final_color = final_color * (1.0 + shadow_texture_noise);
+// Luces de ciudades: puntos cálidos agrupados en regiones "pobladas"
+fn city_lights(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let position = fragment.vertex_position;
 
-    let highlight_texture_noise = uniforms.noise.get_noise_3d(
-        position.x * 3000.0,
-        position.y * 3000.0,
-        position.z * 3000.0,
-    ) * 0.25;
-    final_color = final_color * (1.0 + highlight_texture_noise);
+    let region = uniforms
+        .noise
+        .get_noise_3d(position.x * 250.0, position.y * 250.0, position.z * 250.0);
+    if region < 0.15 {
+        return Color::black();
+    }
 
-    let depth_variation = uniforms.noise.get_noise_3d(
-        position.x * 3500.0,
-        position.y * 3500.0,
-        position.z * 3500.0,
-    ) * 0.1;
-    final_color = final_color * (1.0 + depth_variation);
+    let speckle = uniforms.noise.get_noise_3d(
+        position.x * 4000.0 + 71.0,
+        position.y * 4000.0,
+        position.z * 4000.0,
+    );
+    let density = ((region - 0.15) * 2.0).clamp(0.0, 1.0);
+    let light = ((speckle - (0.55 - density * 0.35)) * 4.0).clamp(0.0, 1.0);
 
-    final_color * fragment.intensity
+    Color::new(255, 190, 110) * light
 }
 
-pub fn rocky_planet_variant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+
+
+pub fn rocky_planet_variant_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let bright_color = Color::new(237, 201, 175);  
     let mid_color = Color::new(193, 154, 107);  
     let dark_color = Color::new(139, 108, 66);  
 
 
-    let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
-    );
+    let position = fragment.vertex_position;
 
     let zoom = 1000.0;
 
     // Obtener ruido para la superficie rocosa
-    let noise_value1 =
-        uniforms
-            .noise
-            .get_noise_3d(position.x * zoom, position.y * zoom, position.z * zoom);
-
-    let noise_value2 = uniforms.noise.get_noise_3d(
-        (position.x + 400.0) * zoom,
-        (position.y + 400.0) * zoom,
-        (position.z + 400.0) * zoom,
-    );
-
-    let noise_value = (noise_value1 + noise_value2) * 0.5;
+    let noise_value = fbm(uniforms.noise, position * zoom, 2);
 
-    let crater_frequency = 1.5;
-    let crater_amplitude = 2.0;
-    let crater_value = (position.x * crater_frequency + position.y * crater_frequency).sin()
-        * (position.x * crater_frequency - position.y * crater_frequency).cos()
-        * crater_amplitude;
+    // Cordilleras y cráteres. El ancho del píxel, en radios, decide qué
+    // cráteres se ven y la distancia con que se mide la pendiente del relieve.
+    let pixel = fragment.footprint() / position.magnitude().max(f32::EPSILON);
+    let max_octaves = 3 + uniforms.extra_octaves;
+    let octaves = filtered_octaves(fragment, uniforms.noise, MOUNTAIN_ZOOM, max_octaves);
+    let relief = RockyRelief::at(uniforms.noise, position, octaves, pixel);
 
-    let mut combined_value = (noise_value + crater_value).clamp(0.0, 1.0);
+    // Las cumbres y los bordes de los cráteres, de roca y eyecta recientes,
+    // salen más claros; el fondo de los cuencos, más oscuro
+    let combined_value =
+        (noise_value * 0.3 + 0.35 + relief.mountains * 0.5 + relief.craters * 0.6).clamp(0.0, 1.0);
 
-    let fine_noise = uniforms.noise.get_noise_3d(
-        position.x * 1600.0,
-        position.y * 1600.0,
-        position.z * 1600.0,
-    ) * 0.3;
-
-    combined_value = (combined_value + fine_noise).clamp(0.0, 1.0);
-
-    let fracture_noise = uniforms.noise.get_noise_3d(
-        position.x * 2000.0,
-        position.y * 2000.0,
-        position.z * 2000.0,
-    ) * 0.15;
-    combined_value = (combined_value + fracture_noise).clamp(0.0, 1.0);
+    // Grietas y fracturas finas
+    let fine_noise = fbm(uniforms.noise, position * 1600.0, 2) * 0.35;
+    let combined_value = (combined_value + fine_noise).clamp(0.0, 1.0);
 
     let color = if combined_value > 0.5 {
         mid_color.lerp(&bright_color, (combined_value - 0.5) * 1.5)
@@ -550,60 +706,217 @@ pub fn rocky_planet_variant_shader(fragment: &Fragment, uniforms: &Uniforms) ->
     let pulsate =
         (uniforms.time as f32 * pulsate_frequency + position.x * 0.02 + position.y * 0.02).sin()
             * pulsate_amplitude;
-    final_color = final_color * (1.0 + pulsate);
+    final_color *= 1.0 + pulsate;
+
+    // Sombras y brillos de la textura de la roca
+    let texture_noise = fbm(uniforms.noise, position * 2500.0, 3);
+    final_color *= 1.0 + texture_noise * 0.45;
+
+    // La luz cae sobre la pendiente del relieve y no sobre la esfera lisa
+    let normal = tilt_normal(
+        fragment.normal,
+        &position,
+        &relief.slope,
+        &uniforms.model_matrix,
+    );
+    let bumped = Fragment {
+        normal,
+        ..*fragment
+    };
+    phong(final_color.to_vec3(), &bumped, uniforms)
+}
 
-    let shadow_texture_noise = uniforms.noise.get_noise_3d(
-        position.x * 2500.0,
-        position.y * 2500.0,
-        position.z * 2500.0,
-    ) * 0.3;
-    final_color = final_color * (1.0 + shadow_texture_noise);
+// Escala del ruido de las cordilleras de la variante rocosa
+const MOUNTAIN_ZOOM: f32 = 300.0;
+// Altura de las cumbres más altas y profundidad de un cráter respecto de su
+// radio, en radios del planeta. Mucho más que en un planeta real, para que se
+// noten al sombrear.
+const MOUNTAIN_HEIGHT: f32 = 0.08;
+const CRATER_DEPTH: f32 = 0.35;
+// Cráteres por radio del planeta en cada escala: pocos grandes y muchos
+// chicos, todos con la misma forma
+const CRATER_SCALES: [f32; 3] = [3.0, 8.0, 22.0];
+
+// Relieve de la variante rocosa en un punto: cordilleras de ruido ridged y
+// campos de cráteres encima, más la pendiente de su altura sobre la esfera
+struct RockyRelief {
+    // En [0, 1], las crestas de las cordilleras
+    mountains: f32,
+    // Negativo en los cuencos y positivo en sus bordes
+    craters: f32,
+    // Cuánto sube la altura por radio recorrido, en el espacio del modelo y
+    // tangente a la esfera
+    slope: Vec3,
+}
 
-    let highlight_texture_noise = uniforms.noise.get_noise_3d(
-        position.x * 3000.0,
-        position.y * 3000.0,
-        position.z * 3000.0,
-    ) * 0.25;
-    final_color = final_color * (1.0 + highlight_texture_noise);
+impl RockyRelief {
+    fn at(noise: &FastNoiseLite, position: Vec3, octaves: u32, pixel: f32) -> Self {
+        let radius = position.magnitude().max(f32::EPSILON);
+        let direction = position / radius;
+        // La máscara cambia despacio, así que vale la misma en los tres puntos
+        let mask = mountain_mask(noise, position);
+        let mountains_at = |direction: Vec3| {
+            ridged(noise, direction * radius * MOUNTAIN_ZOOM, octaves).powi(3) * mask
+        };
+
+        // La pendiente de las cordilleras, por diferencias a un píxel en dos
+        // direcciones tangentes, lo que además suaviza lo que el píxel no
+        // alcanza a resolver
+        let step = pixel.max(1e-3);
+        let (across, along) = tangents(&direction);
+        let mountains = mountains_at(direction);
+        let rise = |tangent: Vec3| {
+            let ahead = mountains_at((direction + tangent * step).normalize());
+            (ahead - mountains) / step * MOUNTAIN_HEIGHT
+        };
+        let mut slope = across * rise(across) + along * rise(along);
+
+        // La de los cráteres sale exacta de su perfil
+        let mut craters_seen = 0.0;
+        for cells in CRATER_SCALES {
+            // Los que miden menos de unos píxeles solo centellearían
+            let fade = (1.0 / (cells * pixel * 4.0) - 1.0).clamp(0.0, 1.0);
+            if fade <= 0.0 {
+                continue;
+            }
+            let (profile, gradient) = craters(direction * cells);
+            craters_seen += profile * fade;
+            slope += gradient * (fade * CRATER_DEPTH);
+        }
+
+        RockyRelief {
+            mountains,
+            craters: craters_seen,
+            slope: slope - direction * slope.dot(&direction),
+        }
+    }
+}
 
-    let depth_variation = uniforms.noise.get_noise_3d(
-        position.x * 3500.0,
-        position.y * 3500.0,
-        position.z * 3500.0,
-    ) * 0.1;
-    final_color = final_color * (1.0 + depth_variation);
+// Dos direcciones tangentes a la esfera en `direction`, perpendiculares entre
+// sí
+fn tangents(direction: &Vec3) -> (Vec3, Vec3) {
+    let helper = if direction.y.abs() < 0.9 {
+        Vec3::y()
+    } else {
+        Vec3::x()
+    };
+    let across = direction.cross(&helper).normalize();
+    (across, direction.cross(&across))
+}
+
+// `normal`, en el espacio del mundo, inclinado según `slope`: cuánto sube el
+// relieve por radio recorrido sobre la esfera en `position`, ambos en el
+// espacio del modelo. `model_matrix` lleva la inclinación al del mundo.
+fn tilt_normal(normal: Vec3, position: &Vec3, slope: &Vec3, model_matrix: &Mat4) -> Vec3 {
+    let rotation = mat4_to_mat3(model_matrix);
+    let direction = position.normalize();
+    let tilted = rotation * (direction - slope).normalize();
+    let upright = rotation * direction;
+    let offset = tilted.normalize() - upright.normalize();
+    (normal + offset).normalize()
+}
+
+// Dónde hay cordilleras: las crestas del ruido ridged solo se levantan donde
+// lo deja este ruido más grande, para que formen cadenas separadas por llanuras
+fn mountain_mask(noise: &FastNoiseLite, position: Vec3) -> f32 {
+    let mask = fbm(
+        noise,
+        position * (MOUNTAIN_ZOOM * 0.3) + Vec3::repeat(41.0),
+        2,
+    );
+    ((mask + 0.1) * 3.0).clamp(0.0, 1.0)
+}
+
+// Campo de cráteres sobre una rejilla de celdas de lado 1: un hash decide si
+// cada celda tiene cráter, dónde y de qué tamaño. Cada uno es un cuenco con el
+// borde levantado, tan hondo como ancho es en celdas. Ninguno se sale más de
+// media celda de la suya, así que basta mirar las 8 celdas más cercanas a
+// `point` y no las 27 que lo rodean. Devuelve la altura en celdas y cuánto
+// sube por celda recorrida.
+fn craters(point: Vec3) -> (f32, Vec3) {
+    let cell = point.map(f32::floor);
+    let nearest = (point - cell).map(|offset| if offset < 0.5 { -1.0 } else { 0.0 });
+    let mut height = 0.0;
+    let mut gradient = Vec3::zeros();
+    for corner in 0..8 {
+        let step = Vec3::new(
+            (corner & 1) as f32,
+            (corner >> 1 & 1) as f32,
+            (corner >> 2) as f32,
+        );
+        let neighbour = cell + nearest + step;
+        let mut hash = cell_hash(neighbour);
+        let mut unit = || {
+            hash = hash.wrapping_mul(0x2C1B3C6D).rotate_left(13) ^ 0x297A2D39;
+            (hash >> 8) as f32 / (1 << 24) as f32
+        };
+        // Una de cada cuatro celdas queda sin cráter
+        if unit() > 0.75 {
+            continue;
+        }
+        let center = neighbour + Vec3::repeat(0.25) + Vec3::new(unit(), unit(), unit()) * 0.5;
+        // El borde acaba a 1.35 radios, justo dentro de la media celda
+        let radius = 0.12 + unit() * 0.25;
+        let offset = point - center;
+        let distance = offset.magnitude();
+        if distance >= radius * 1.35 || distance <= f32::EPSILON {
+            continue;
+        }
+        let (profile, rise) = crater_profile(distance / radius);
+        height += profile * radius;
+        gradient += offset * (rise / distance);
+    }
+    (height, gradient)
+}
 
-    final_color * fragment.intensity
+// Perfil de un cráter a `t` radios del centro, y cuánto sube por radio: fondo
+// en -1, sube en parábola hasta el borde y cae fuera de él hasta 1.35 radios
+fn crater_profile(t: f32) -> (f32, f32) {
+    let (bowl, bowl_rise) = if t < 1.0 {
+        (t * t - 1.0, 2.0 * t)
+    } else {
+        (0.0, 0.0)
+    };
+    let s = (t - 1.0) / 0.35;
+    let rim = (1.0 - s * s).max(0.0);
+    let rim_rise = if rim > 0.0 { -4.0 * s / 0.35 } else { 0.0 };
+    (bowl + rim * rim * 0.3, bowl_rise + rim * rim_rise * 0.3)
+}
+
+fn cell_hash(cell: Vec3) -> u32 {
+    let [x, y, z] = [cell.x, cell.y, cell.z].map(|value| value as i32 as u32);
+    let hash = x.wrapping_mul(0x8DA6B343) ^ y.wrapping_mul(0xD8163841) ^ z.wrapping_mul(0xCB1AB31F);
+    (hash ^ (hash >> 15)).wrapping_mul(0x2C1B3C6D)
+}
+
+// El grano de las bandas de los gigantes gaseosos: un desplazamiento en
+// [-0.03, 0.03) y un realce de saturación de 1.2 la mitad de las veces. Cambia
+// de un punto a otro y de un frame a otro como antes, pero sale de un hash de
+// la posición y el tiempo, así que el mismo frame se dibuja siempre igual
+fn band_grain(position: Vec3, time: u32) -> (f32, f32) {
+    let hash = cell_hash(position * 65536.0) ^ time.wrapping_mul(0x9E3779B9);
+    let hash = (hash ^ (hash >> 13)).wrapping_mul(0x85EBCA6B);
+    let offset = ((hash >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0) * 0.03;
+    let boost = if hash & 1 == 0 { 1.2 } else { 1.0 };
+    (offset, boost)
 }
 
-pub fn alien_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn alien_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let ocean_color = Color::new(25, 25, 112);
     let flora_color = Color::new(110, 62, 136);
     let alien_color = Color::new(13, 246, 243);
 
-    let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
-    );
+    let position = fragment.vertex_position;
     let zoom = 450.0;
 
     let time_factor = uniforms.time as f32 * 0.15;
 
-    let noise_value1 = uniforms.noise.get_noise_3d(
-        position.x * zoom + time_factor,
-        position.y * zoom + time_factor,
-        position.z * zoom + time_factor,
-    );
-
-    let noise_value2 = uniforms.noise.get_noise_3d(
-        (position.x + 300.0) * zoom + time_factor,
-        (position.y + 300.0) * zoom + time_factor,
-        (position.z + 300.0) * zoom + time_factor,
+    let noise_value = fbm(
+        uniforms.noise,
+        position * zoom + Vec3::repeat(time_factor),
+        2,
     );
 
-    let noise_value = (noise_value1 + noise_value2) * 0.5;
-
     let drift_noise = uniforms.noise.get_noise_3d(
         position.x * 0.05 + time_factor,
         position.y * 0.05 + time_factor,
@@ -620,53 +933,7 @@ pub fn alien_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
         ocean_color
     };
 
-    let texture_zoom1 = 700.0;
-    let texture_noise1 = uniforms.noise.get_noise_3d(
-        position.x * texture_zoom1,
-        position.y * texture_zoom1,
-        position.z * texture_zoom1,
-    ) * 0.3;
-
-    let texture_zoom2 = 1000.0;
-    let texture_noise2 = uniforms.noise.get_noise_3d(
-        position.x * texture_zoom2,
-        position.y * texture_zoom2,
-        position.z * texture_zoom2,
-    ) * 0.25;
-
-    let texture_zoom3 = 1500.0;
-    let texture_noise3 = uniforms.noise.get_noise_3d(
-        position.x * texture_zoom3,
-        position.y * texture_zoom3,
-        position.z * texture_zoom3,
-    ) * 0.2;
-
-    let texture_zoom4 = 2000.0;
-    let texture_noise4 = uniforms.noise.get_noise_3d(
-        position.x * texture_zoom4,
-        position.y * texture_zoom4,
-        position.z * texture_zoom4,
-    ) * 0.15;
-
-    let background_noise1 = uniforms.noise.get_noise_3d(
-        position.x * 2500.0,
-        position.y * 2500.0,
-        position.z * 2500.0,
-    ) * 0.15;
-
-    let background_noise2 = uniforms.noise.get_noise_3d(
-        position.x * 3500.0,
-        position.y * 3500.0,
-        position.z * 3500.0,
-    ) * 0.1;
-
-    let texture_combined = (texture_noise1
-        + texture_noise2
-        + texture_noise3
-        + texture_noise4
-        + background_noise1
-        + background_noise2)
-        .clamp(0.0, 1.0);
+    let texture_combined = (fbm(uniforms.noise, position * 700.0, 3) * 0.8).clamp(0.0, 1.0);
 
     let texturized_color = base_color * (1.0 + texture_combined);
 
@@ -680,17 +947,13 @@ pub fn alien_planet_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
 
     let final_color = illuminated_color.limit_min(50);
 
-    final_color * fragment.intensity
+    phong(final_color.to_vec3(), fragment, uniforms)
 }
 
-pub fn glacial_textured_shader(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+pub fn glacial_textured_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
     let ice_blue = Color::new(173, 216, 230);  
 
-    let position = Vec3::new(
-        fragment.vertex_position.x,
-        fragment.vertex_position.y,
-        fragment.depth,
-    );
+    let position = fragment.vertex_position;
 
     let zoom = 100.0;
 
@@ -702,25 +965,13 @@ pub fn glacial_textured_shader(fragment: &Fragment, uniforms: &Uniforms) -> Colo
         position.z * zoom,
     ) * 0.6;
 
-    let detail_noise1 = uniforms.noise.get_noise_3d(
-        position.x * 700.0,
-        position.y * 700.0,
-        position.z * 700.0,
-    ) * 0.5;
-
-    let detail_noise2 = uniforms.noise.get_noise_3d(
-        position.x * 1200.0 + time_factor,
-        position.y * 1200.0 + time_factor,
-        position.z * 1200.0 + time_factor,
-    ) * 0.4;
-
-    let fine_detail_noise = uniforms.noise.get_noise_3d(
-        position.x * 2500.0,
-        position.y * 2500.0,
-        position.z * 2500.0,
-    ) * 0.3;
+    let detail_noise = fbm(
+        uniforms.noise,
+        position * 700.0 + Vec3::repeat(time_factor),
+        3,
+    );
 
-    let combined_texture = (base_noise + detail_noise1 + detail_noise2 + fine_detail_noise).clamp(0.0, 1.0);
+    let combined_texture = (base_noise + detail_noise).clamp(0.0, 1.0);
 
     let texturized_color = ice_blue * (1.0 + combined_texture);
 
@@ -732,5 +983,924 @@ pub fn glacial_textured_shader(fragment: &Fragment, uniforms: &Uniforms) -> Colo
 
     let final_color = illuminated_color.limit_min(60);
 
-    final_color * fragment.intensity
-}
\ No newline at end of file
+    phong(final_color.to_vec3(), fragment, uniforms)
+        + environment_reflection(fragment, uniforms, 0.05, 0.15)
+        + aurora(fragment, uniforms).to_vec3()
+}
+
+// Iluminación compartida por todos los cuerpos: Phong con el sol y las demás
+// luces según el material del cuerpo, más la luz ambiental del cielo
+// horneada en armónicos esféricos. Así el lado nocturno toma el tono de la
+// nebulosa en vez de quedar negro.
+fn phong(albedo: Vec3, fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    phong_with(albedo, &fragment.material, fragment, uniforms)
+}
+
+// Igual que `phong`, pero con un material retocado por el shader
+fn phong_with(albedo: Vec3, material: &Material, fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let normal = fragment.normal;
+    let light_dir = uniforms.light_dir;
+    // Solo se consulta el mapa de sombras de una estrella donde su luz llega
+    // a la superficie
+    let lit = |light_dir: Vec3, light_space_matrix: &Mat4, shadow_map: Option<&ShadowMap>| {
+        let lambertian = light_dir.dot(&normal).max(0.0);
+        match shadow_map {
+            Some(shadow_map) if lambertian > 0.0 => {
+                let position = &fragment.world_position;
+                lambertian * shadow_map.visibility(light_space_matrix, position, &normal)
+            }
+            _ => lambertian,
+        }
+    };
+    let lambertian = lit(light_dir, &uniforms.light_space_matrix, uniforms.shadow_map);
+
+    let highlight = |light_dir: Vec3| {
+        if material.specular <= 0.0 {
+            return 0.0;
+        }
+        let view_dir = fragment.view_dir(&uniforms.eye);
+        let reflect_dir = (2.0 * normal.dot(&light_dir) * normal - light_dir).normalize();
+        view_dir.dot(&reflect_dir).max(0.0).powf(material.shininess) * material.specular
+    };
+    // El sol da la luz del color de su estrella y con su intensidad
+    let sunlight = uniforms.star.sunlight();
+    let mut diffuse = sunlight * lambertian;
+    let mut specular = if lambertian > 0.0 {
+        sunlight * highlight(light_dir)
+    } else {
+        Vec3::zeros()
+    };
+
+    // La compañera de una estrella doble ilumina igual que el sol, con sus
+    // propias sombras
+    if let Some(companion) = &uniforms.companion {
+        let (direction, matrix) = (companion.direction, &companion.light_space_matrix);
+        let lambertian = lit(direction, matrix, companion.shadow_map);
+        if lambertian > 0.0 {
+            let sunlight = companion.star.sunlight();
+            diffuse += sunlight * lambertian;
+            specular += sunlight * highlight(direction);
+        }
+    }
+
+    // Las demás luces, cada una con su color y sin sombras
+    for light in uniforms.lights {
+        let Some((direction, color)) = light.arriving(&fragment.world_position) else {
+            continue;
+        };
+        let lambertian = direction.dot(&normal).max(0.0);
+        if lambertian > 0.0 {
+            diffuse += color * lambertian;
+            specular += color * highlight(direction);
+        }
+    }
+
+    let sky = uniforms.ambient.evaluate(&normal);
+    let light = uniforms.star.color() * material.ambient + (diffuse + sky) * material.diffuse;
+    albedo.component_mul(&light) + specular + albedo * material.emissive
+}
+
+// `radiance`, lo que sale de la superficie, visto a través de la atmósfera:
+// azul de refilón hacia el borde del disco y rojizo cerca del terminador
+fn atmosphere(
+    atmosphere: &Atmosphere,
+    radiance: Vec3,
+    fragment: &Fragment,
+    uniforms: &Uniforms,
+) -> Vec3 {
+    let view_dir = fragment.view_dir(&uniforms.eye);
+    atmosphere.scatter(
+        radiance,
+        &fragment.normal,
+        &view_dir,
+        &uniforms.light_dir,
+        &uniforms.star.sunlight(),
+    )
+}
+
+// Lo que refleja la superficie del entorno, con el término de Fresnel de
+// Schlick: poco mirando de frente y casi todo de refilón, hacia el borde del
+// disco. `f0` es la reflectancia del material vista de frente y `roughness`,
+// en radianes, cuánto desenfocan el reflejo las irregularidades que la malla
+// no llega a mostrar.
+fn environment_reflection(
+    fragment: &Fragment,
+    uniforms: &Uniforms,
+    f0: f32,
+    roughness: f32,
+) -> Vec3 {
+    let Some(environment) = uniforms.environment else {
+        return Vec3::zeros();
+    };
+    let normal = fragment.normal;
+    let view_dir = fragment.view_dir(&uniforms.eye);
+    let cosine = normal.dot(&view_dir);
+    let fresnel = f0 + (1.0 - f0) * (1.0 - cosine.max(0.0)).powi(5);
+    let reflect_dir = 2.0 * cosine * normal - view_dir;
+    // En una esfera la normal gira el tamaño del píxel entre el radio de un
+    // píxel al siguiente, y el reflejo el doble. Promediar el cielo sobre ese
+    // ángulo evita que un skybox detallado parpadee al girar la cámara.
+    let radius = fragment.vertex_position.magnitude().max(f32::EPSILON);
+    let spread = 2.0 * fragment.footprint() / radius + roughness;
+    environment.sample_blurred(&reflect_dir, spread) * fresnel
+}
+
+// Octavas de fbm que el píxel puede resolver a esta escala. Las que oscilan en
+// menos de dos píxeles solo aportan aliasing, así que se descartan, igual que
+// al elegir un nivel de mipmap. Los shaders suben el máximo con
+// `extra_octaves` cuando la cámara pasa rozando la superficie.
+fn filtered_octaves(fragment: &Fragment, noise: &FastNoiseLite, zoom: f32, octaves: u32) -> u32 {
+    let pixel = noise_footprint(fragment, noise, zoom);
+    if pixel <= 0.0 {
+        return octaves;
+    }
+    let resolvable = (0.5 / pixel).log2().floor() as i32 + 1;
+    resolvable.clamp(1, octaves as i32) as u32
+}
+
+// Periodos de ruido (escalado por `zoom`) que caben en un píxel
+fn noise_footprint(fragment: &Fragment, noise: &FastNoiseLite, zoom: f32) -> f32 {
+    fragment.footprint() * zoom * noise.frequency
+}
+
+// Cuánto dejar de una capa de ruido de escala `zoom`: toda mientras el píxel
+// cubre menos de un cuarto de periodo y nada desde medio, donde ya solo
+// centellearía. El ruido promedia cero, así que lo que queda es el color medio.
+fn noise_fade(fragment: &Fragment, noise: &FastNoiseLite, zoom: f32) -> f32 {
+    (2.0 - 4.0 * noise_footprint(fragment, noise, zoom)).clamp(0.0, 1.0)
+}
+
+// Seno promediado sobre el ancho del píxel, medido en radianes de fase (un
+// filtro de caja). Si el píxel abarca un periodo completo solo queda el
+// promedio, cero, en vez de un patrón de Moiré.
+fn filtered_sin(phase: f32, width: f32) -> f32 {
+    let half = width * 0.5;
+    if half < 1e-4 {
+        phase.sin()
+    } else if width >= TAU {
+        0.0
+    } else {
+        phase.sin() * half.sin() / half
+    }
+}
+
+// Umbral con una rampa del ancho del píxel en lugar de un corte duro, para
+// bordes sin escalones ni parpadeo
+fn filtered_step(edge: f32, value: f32, width: f32) -> f32 {
+    if width <= 0.0 {
+        return if value >= edge { 1.0 } else { 0.0 };
+    }
+    ((value - edge) / width + 0.5).clamp(0.0, 1.0)
+}
+
+// Escala del ruido de las nubes de la Tierra
+const CLOUD_ZOOM: f32 = 450.0;
+// Pendiente de las olas por unidad de pendiente del ruido
+const WAVE_STEEPNESS: f32 = 0.05;
+
+// Pendiente del oleaje en `position`, en el espacio del modelo: dos trenes de
+// olas que el tiempo desplaza en direcciones distintas, para que el agua no
+// parezca un dibujo que se arrastra. Se deriva por diferencias finitas sobre
+// la esfera, y se apaga donde el píxel ya no resuelve las olas.
+fn ocean_slope(fragment: &Fragment, uniforms: &Uniforms, position: &Vec3) -> Vec3 {
+    let ocean = &uniforms.ocean;
+    let fade = noise_fade(fragment, uniforms.noise, ocean.wave_scale);
+    if fade <= 0.0 {
+        return Vec3::zeros();
+    }
+    let drift = uniforms.time as f32 * ocean.wave_speed / uniforms.noise.frequency;
+    let swell = Vec3::new(drift, 0.0, drift * 0.4);
+    let chop = Vec3::new(-drift * 0.7, drift * 0.5, 0.0);
+    let height = |point: Vec3| {
+        let point = point * ocean.wave_scale;
+        let sample = |point: Vec3| uniforms.noise.get_noise_3d(point.x, point.y, point.z);
+        sample(point + swell) + 0.5 * sample(point * 2.3 + chop)
+    };
+
+    let (across, along) = tangents(&position.normalize());
+    // Un periodo del ruido en el espacio del modelo; se deriva a un cuarto
+    let period = 1.0 / (ocean.wave_scale * uniforms.noise.frequency);
+    let step = period * 0.25;
+    let here = height(*position);
+    let rise = |tangent: Vec3| (height(position + tangent * step) - here) / step;
+    (across * rise(across) + along * rise(along)) * (WAVE_STEEPNESS * fade * period)
+}
+
+pub fn terran_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let deep_ocean = Color::new(10, 30, 90);
+    let shallow_ocean = Color::new(30, 90, 160);
+    let beach = Color::new(210, 195, 140);
+    let grass = Color::new(60, 130, 50);
+    let dry_grass = Color::new(140, 120, 60);
+    let rock = Color::new(110, 95, 80);
+    let snow = Color::new(240, 240, 245);
+    let sea_ice = Color::new(215, 225, 235);
+    let cloud_color = Color::new(255, 255, 255);
+
+    let position = fragment.vertex_position;
+
+    // Estación según la latitud: el hemisferio en invierno se seca y sus
+    // casquetes polares bajan, el de verano se pone verde y los retrae
+    let direction = position.normalize();
+    let latitude = direction.y;
+    let warmth = uniforms.season.warmth(latitude);
+    let grass = grass.lerp(&dry_grass, (-warmth * 1.5).clamp(0.0, 1.0));
+
+    // Los continentes vienen de la simulación de placas; el ruido solo añade
+    // colinas y recorta las costas
+    let zoom = 900.0;
+    let detail_amplitude = 0.15;
+    let detail = fbm(
+        uniforms.noise,
+        position * zoom,
+        filtered_octaves(fragment, uniforms.noise, zoom, 4 + uniforms.extra_octaves),
+    );
+    let elevation = uniforms.continents.elevation(&direction) + detail * detail_amplitude;
+
+    let sea_level = SEA_LEVEL;
+    // El relieve fino cambia unas dos veces su amplitud por periodo de ruido,
+    // así que la costa se funde a lo largo de lo que cubre el píxel
+    let coast_width = noise_footprint(fragment, uniforms.noise, zoom) * 2.0 * detail_amplitude;
+    let land_coverage = filtered_step(sea_level, elevation, coast_width);
+
+    let depth = ((sea_level - elevation) * 3.0).clamp(0.0, 1.0);
+    let ocean_color = shallow_ocean.lerp(&deep_ocean, depth);
+    let height = (elevation - sea_level) / (1.0 - sea_level);
+    let land_color = if height < 0.04 {
+        beach
+    } else if height < 0.25 {
+        beach.lerp(&grass, (height - 0.04) * 10.0)
+    } else if height < 0.45 {
+        grass.lerp(&rock, (height - 0.25) * 5.0)
+    } else {
+        rock.lerp(&snow, (height - 0.45) * 5.0)
+    };
+    // Los ríos que talló la erosión llevan agua de la costa tierra adentro
+    let river = uniforms.continents.river(&direction);
+    let land_color = land_color.lerp(&shallow_ocean, river * 0.85);
+    let surface_color = ocean_color.lerp(&land_color, land_coverage);
+
+    let ice_edge = 0.8 + 0.12 * warmth / latitude.abs().max(0.5);
+    let ice = filtered_step(
+        ice_edge,
+        latitude.abs() + elevation * 0.15,
+        fragment.footprint() * 2.0,
+    );
+    let ice_color = sea_ice.lerp(&snow, land_coverage);
+    let surface_color = surface_color.lerp(&ice_color, ice);
+
+    // Nubes del mapa horneado si lo hay; si no, se evalúan aquí
+    let cloud_coverage = match uniforms.clouds {
+        Some(map) => map.sample(&direction),
+        None => terran_clouds(
+            uniforms.noise,
+            &uniforms.weather,
+            &direction,
+            uniforms.time,
+            filtered_octaves(fragment, uniforms.noise, CLOUD_ZOOM, 4),
+        ),
+    };
+    let final_color = surface_color.lerp(&cloud_color, cloud_coverage);
+
+    // El agua tiene un brillo especular propio, la tierra, el hielo y las
+    // nubes no
+    let water = (1.0 - land_coverage) * (1.0 - ice) * (1.0 - cloud_coverage);
+    // Las olas mueven la normal del agua, y el reflejo del sol se rompe en
+    // destellos que corren sobre el mar. De lejos, donde ya no se resuelven,
+    // el brillo se ensancha en lugar de centellear.
+    let open_water = (1.0 - land_coverage) * (1.0 - ice);
+    let fade = noise_fade(fragment, uniforms.noise, uniforms.ocean.wave_scale);
+    let slope = if open_water > 0.0 {
+        ocean_slope(fragment, uniforms, &position) * open_water
+    } else {
+        Vec3::zeros()
+    };
+    let normal = tilt_normal(fragment.normal, &position, &slope, &uniforms.model_matrix);
+    let waves = Fragment {
+        normal,
+        ..*fragment
+    };
+    let material = Material {
+        specular: fragment.material.specular * water,
+        shininess: fragment.material.shininess * (0.5 + 0.5 * fade),
+        ..fragment.material
+    };
+    // Solo el agua abierta refleja el cielo
+    let lit = phong_with(final_color.to_vec3(), &material, &waves, uniforms)
+        + environment_reflection(&waves, uniforms, 0.02, 0.0) * water;
+    atmosphere(&Atmosphere::TERRAN, lit, fragment, uniforms)
+}
+
+// Nubes animadas sobre la superficie, en la dirección `direction` desde el
+// centro. El clima del planeta decide cuánto cielo está cubierto y enrosca el
+// ruido alrededor de cada tormenta. Se calculan sobre la esfera de radio 0.5
+// con la que se construyen las mallas del planeta, y no en la posición de
+// cada vértice, para que el mapa horneado, que solo sabe de direcciones, dé
+// las mismas nubes.
+pub fn terran_clouds(
+    noise: &FastNoiseLite,
+    weather: &WeatherState,
+    direction: &Vec3,
+    time: u32,
+    octaves: u32,
+) -> f32 {
+    let time = time as f32 * 0.05;
+    let (storm, swirled) = weather.storms(direction);
+    let cloud_position = swirled * 0.5;
+    let clouds = fbm(
+        noise,
+        cloud_position * CLOUD_ZOOM + Vec3::new(time, 0.0, -time * 0.5),
+        octaves,
+    );
+    let threshold = 0.45 - weather.coverage * 0.6;
+    ((clouds + storm * 0.6 - threshold) * 2.5).clamp(0.0, 0.85 + storm * 0.1)
+}
+
+pub fn lava_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let basalt_dark = Color::new(20, 15, 15);
+    let basalt_light = Color::new(60, 45, 40);
+    let lava_hot = Color::new(255, 220, 90);
+    let lava_cool = Color::new(200, 40, 0);
+
+    let position = fragment.vertex_position;
+    let time = uniforms.time as f32;
+
+    // Ruido "ridged": forma crestas finas donde el ruido cruza cero, que aquí
+    // son los canales de lava entre las placas
+    let zoom = 500.0;
+    let flow = time * 0.08;
+    let crack = ridged(
+        uniforms.noise,
+        position * zoom + Vec3::new(flow, 0.0, -flow),
+        2,
+    )
+    .powf(4.0);
+
+    let plate_noise = uniforms
+        .noise
+        .get_noise_3d(position.x * 900.0, position.y * 900.0, position.z * 900.0);
+    let plate_color = basalt_dark.lerp(&basalt_light, (plate_noise + 1.0) * 0.5);
+
+    let lit_plates = phong(plate_color.to_vec3(), fragment, uniforms);
+
+    // La lava emite luz propia: no depende de la iluminación y pulsa con el tiempo
+    let pulse = 0.8 + 0.2 * (time * 0.05 + plate_noise * 3.0).sin();
+    let lava_color = lava_cool.lerp(&lava_hot, crack);
+    let emissive = lava_color.to_vec3() * (crack * pulse * 1.4);
+
+    lit_plates.lerp(&emissive, crack.clamp(0.0, 1.0)) + emissive * 0.3
+}
+
+// Planeta aleatorio: paleta, tipo de ruido, bandas, tormentas y rugosidad salen
+// todos de la semilla de `uniforms.planet`
+pub fn generated_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    let planet = uniforms.planet;
+    let noise = &planet.noise;
+    let direction = fragment.vertex_position.normalize();
+
+    // La rugosidad suma octavas finas, hasta donde el píxel las resuelve
+    let max_octaves = 2 + (planet.roughness * 4.0) as u32 + uniforms.extra_octaves;
+    let octaves = filtered_octaves(fragment, noise, 1500.0, max_octaves);
+    let detail = fbm(noise, direction * 1500.0, octaves) * planet.roughness;
+
+    let (surface, material) = match planet.kind {
+        PlanetKind::Banded => {
+            let wobble = fbm(noise, direction * 150.0, 2) * 0.08;
+            let latitude = direction.y + wobble;
+            let frequency = planet.band_frequency;
+            let width = fragment.footprint() * frequency;
+            let bands = filtered_sin(latitude * frequency, width) * 0.35
+                + filtered_sin(latitude * frequency * 0.37, width * 0.37) * 0.15
+                + 0.5;
+            (bands + detail * 0.3, Material::GAS)
+        }
+        PlanetKind::Rocky => {
+            let elevation = fbm(noise, direction * 300.0, 3);
+            (elevation * 0.8 + 0.5 + detail * 0.4, fragment.material)
+        }
+    };
+    let mut color = planet.color_at(surface);
+
+    // Óvalos de tormenta (o de polvo en los rocosos), más cuanto mayor es la
+    // probabilidad de tormenta
+    let storm = sphere_noise(noise, &direction, 250.0);
+    let threshold = 1.0 - planet.storm_probability * 0.6;
+    if storm > threshold {
+        let mix = ((storm - threshold) / (1.0 - threshold)).clamp(0.0, 1.0);
+        color = color.lerp(&(planet.palette[3] * 1.2), mix);
+    }
+
+    phong_with(color, &material, fragment, uniforms)
+}
+
+// Superficies artificiales: el color viene de cada vértice y la normal es la
+// del modelo, no la de una esfera
+pub fn station_shader(fragment: &Fragment, uniforms: &Uniforms) -> Vec3 {
+    phong(fragment.color.to_vec3(), fragment, uniforms)
+}
+
+// Color plano del vértice, sin luz, para el contorno de la selección
+fn outline_shader(fragment: &Fragment) -> Vec3 {
+    fragment.color.to_vec3()
+}
+
+// Cortinas de aurora cerca de los polos, sumadas sobre el color del planeta
+fn aurora(fragment: &Fragment, uniforms: &Uniforms) -> Color {
+    let params = &uniforms.aurora;
+    if params.intensity <= 0.0 {
+        return Color::black();
+    }
+
+    let direction = fragment.vertex_position.normalize();
+    let latitude = direction.y.abs();
+    let band = 1.0 - ((latitude - params.latitude) / params.band_width).abs();
+    if band <= 0.0 {
+        return Color::black();
+    }
+    let band_mask = band * band * (3.0 - 2.0 * band);
+
+    let time = uniforms.time as f32 * 0.4;
+    let longitude = direction.z.atan2(direction.x);
+    // La longitud se muestrea como un círculo de radio 120 para que las cortinas
+    // no se corten donde atan2 salta de -π a π
+    let drift = longitude + time / 120.0;
+    let curtain_noise = uniforms.noise.get_noise_3d(
+        drift.cos() * 120.0,
+        drift.sin() * 120.0,
+        latitude * 200.0 + direction.y.signum() * 500.0,
+    );
+    let curtains = (1.0 - curtain_noise.abs()).powf(4.0);
+
+    let shimmer = 0.75 + 0.25 * (longitude * 40.0 + time * 0.3).sin();
+
+    // Verde en el borde inferior de la cortina, morado hacia arriba
+    let green = Color::new(60, 255, 140);
+    let purple = Color::new(170, 60, 255);
+    let height = ((latitude - params.latitude) / params.band_width * 0.5 + 0.5).clamp(0.0, 1.0);
+    let aurora_color = green.lerp(&purple, height);
+
+    aurora_color * (band_mask * curtains * shimmer * params.intensity * 0.8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dither::Dither;
+    use crate::framebuffer::Framebuffer;
+    use crate::generator::GeneratedPlanet;
+    use crate::golden;
+    use crate::instance::Instance;
+    use crate::light::{Light, Star};
+    use crate::menu::SHADERS;
+    use crate::noise::NoiseSettings;
+    use crate::obj::Obj;
+    use crate::season::Orbit;
+    use crate::sh::ShIrradiance;
+    use crate::tectonics::Continents;
+    use crate::tonemap::ToneMapping;
+    use crate::weather::Weather;
+    use crate::{create_viewport_matrix, render_instanced, DepthMode, Viewport};
+    use std::f32::consts::PI;
+    use std::sync::OnceLock;
+
+    const ALL_SHADERS: [ShaderType; 12] = [
+        ShaderType::GasGiant,
+        ShaderType::ColdGasGiant,
+        ShaderType::Solar,
+        ShaderType::RockyPlanet,
+        ShaderType::RockyPlanetVariant,
+        ShaderType::AlienPlanet,
+        ShaderType::GlacialTextured,
+        ShaderType::Moon,
+        ShaderType::Terran,
+        ShaderType::Lava,
+        ShaderType::Station,
+        ShaderType::Generated,
+    ];
+
+    // Lo que los Uniforms toman prestado. Generar los continentes es lo más
+    // lento de cada test, así que se comparte entre todos.
+    struct Fixture {
+        continents: Continents,
+        planet: GeneratedPlanet,
+        noise: FastNoiseLite,
+    }
+
+    impl Fixture {
+        fn shared() -> &'static Fixture {
+            static FIXTURE: OnceLock<Fixture> = OnceLock::new();
+            FIXTURE.get_or_init(|| Fixture {
+                continents: Continents::generate(5),
+                planet: GeneratedPlanet::from_seed(0),
+                noise: NoiseSettings::DEFAULT.build(),
+            })
+        }
+
+        // Un planeta de radio 1 en el origen, visto desde +z con el sol en
+        // +x y sin luz del cielo, así el lado nocturno solo recibe la luz
+        // ambiente y la emisión del material
+        fn uniforms(&self) -> Uniforms<'_> {
+            Uniforms {
+                model_matrix: Mat4::identity(),
+                view_matrix: Mat4::identity(),
+                projection_matrix: Mat4::identity(),
+                viewport_matrix: Mat4::identity(),
+                time: 0,
+                noise: &self.noise,
+                aurora: AuroraParams::default(),
+                gas_giant: GasGiantParams::default(),
+                ocean: OceanParams::default(),
+                shading: ShadingModel::Phong,
+                light_dir: Vec3::x(),
+                star: Star::YellowDwarf,
+                lights: &[],
+                light_space_matrix: Mat4::identity(),
+                shadow_map: None,
+                companion: None,
+                extra_octaves: 0,
+                ambient: ShIrradiance::project(std::iter::empty()),
+                environment: None,
+                particle_stride: 1,
+                weather: Weather::new(5).state(),
+                clouds: None,
+                surface: None,
+                eye: Vec3::new(0.0, 0.0, 3.0),
+                season: Orbit::default().season(0),
+                continents: &self.continents,
+                #[cfg(feature = "physics")]
+                impacts: &[],
+                planet: &self.planet,
+                debug_view: None,
+                threads: 1,
+                reversed_z: false,
+            }
+        }
+    }
+
+    // Fragmento sintético sobre la esfera unidad, con la normal dada y
+    // `intensity` como color de vértice. Las derivadas equivalen a un píxel
+    // de un planeta de unos 300 píxeles de ancho.
+    fn fragment(position: Vec3, normal: Vec3, intensity: f32) -> Fragment {
+        let gray = (intensity.clamp(0.0, 1.0) * 255.0) as u8;
+        let mut fragment =
+            Fragment::new(Color::new(gray, gray, gray), normal.normalize(), position);
+        fragment.world_position = position;
+        fragment.dpdx = Vec3::new(0.007, 0.0, 0.0);
+        fragment.dpdy = Vec3::new(0.0, 0.007, 0.0);
+        fragment
+    }
+
+    // Punto de la esfera con su normal hacia afuera
+    fn surface(direction: Vec3) -> Fragment {
+        let direction = direction.normalize();
+        fragment(direction, direction, 1.0)
+    }
+
+    // Direcciones repartidas en un casquete de `spread` radianes alrededor de
+    // `center`, siempre las mismas
+    fn cap(center: Vec3, spread: f32, count: usize) -> Vec<Vec3> {
+        let center = center.normalize();
+        let side = if center.y.abs() < 0.9 {
+            Vec3::y()
+        } else {
+            Vec3::x()
+        };
+        let u = center.cross(&side).normalize();
+        let v = center.cross(&u);
+        (0..count)
+            .map(|i| {
+                let angle = i as f32 * 2.399_963;
+                let radius = spread * ((i as f32 + 0.5) / count as f32).sqrt();
+                (center + (u * angle.cos() + v * angle.sin()) * radius.tan()).normalize()
+            })
+            .collect()
+    }
+
+    fn shade(uniforms: &Uniforms, shader: ShaderType, direction: Vec3) -> Vec3 {
+        fragment_shader(&surface(direction), uniforms, &shader)
+    }
+
+    fn luminance(color: Vec3) -> f32 {
+        color.dot(&Vec3::new(0.2126, 0.7152, 0.0722))
+    }
+
+    fn mean(values: impl Iterator<Item = f32>) -> f32 {
+        let values: Vec<f32> = values.collect();
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    fn mean_luminance(uniforms: &Uniforms, shader: ShaderType, directions: &[Vec3]) -> f32 {
+        mean(
+            directions
+                .iter()
+                .map(|&d| luminance(shade(uniforms, shader, d))),
+        )
+    }
+
+    #[test]
+    fn every_shader_is_finite_and_non_negative() {
+        let fixture = Fixture::shared();
+        let uniforms = fixture.uniforms();
+        for shader in ALL_SHADERS {
+            for direction in cap(Vec3::z(), PI / 2.0, 64) {
+                let color = shade(&uniforms, shader, direction);
+                assert!(
+                    color.iter().all(|c| c.is_finite() && *c >= 0.0),
+                    "{:?} gave {:?} at {:?}",
+                    shader,
+                    color,
+                    direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn night_side_is_darker_than_day_side() {
+        let fixture = Fixture::shared();
+        let uniforms = fixture.uniforms();
+        let day = cap(uniforms.light_dir, 0.5, 48);
+        let night = cap(-uniforms.light_dir, 0.5, 48);
+        for shader in ALL_SHADERS {
+            let lit = mean_luminance(&uniforms, shader, &day);
+            let dark = mean_luminance(&uniforms, shader, &night);
+            assert!(lit > dark * 1.5, "{:?}: day {} night {}", shader, lit, dark);
+        }
+    }
+
+    #[test]
+    fn a_red_dwarf_warms_the_day_side() {
+        let fixture = Fixture::shared();
+        let day = cap(Vec3::x(), 0.5, 48);
+        // Cuánto más rojo que azul sale el lado diurno bajo cada estrella
+        let warmth = |star: Star| {
+            let uniforms = Uniforms {
+                star,
+                ..fixture.uniforms()
+            };
+            let colors: Vec<Vec3> = day
+                .iter()
+                .map(|&d| shade(&uniforms, ShaderType::RockyPlanet, d))
+                .collect();
+            mean(colors.iter().map(|c| c.x)) / mean(colors.iter().map(|c| c.z))
+        };
+        let (red, yellow) = (warmth(Star::RedDwarf), warmth(Star::YellowDwarf));
+        assert!(red > yellow * 1.5, "roja {} amarilla {}", red, yellow);
+    }
+
+    #[test]
+    fn point_lights_fade_with_distance() {
+        let fixture = Fixture::shared();
+        // Del lado nocturno, donde el sol no llega y solo queda la luz puntual
+        let night = cap(-Vec3::x(), 0.5, 48);
+        let brightness = |distance: f32| {
+            let lights = [Light::point(-Vec3::x() * distance, 10.0, Vec3::repeat(2.0))];
+            let uniforms = Uniforms {
+                lights: &lights,
+                ..fixture.uniforms()
+            };
+            mean_luminance(&uniforms, ShaderType::Moon, &night)
+        };
+        // A 20 queda fuera de su alcance
+        let (near, far, unlit) = (brightness(2.0), brightness(4.0), brightness(20.0));
+        assert!(near > far * 1.5, "near {} far {}", near, far);
+        assert!(far > unlit, "far {} out of range {}", far, unlit);
+    }
+
+    #[test]
+    fn station_follows_its_vertex_color() {
+        let fixture = Fixture::shared();
+        let uniforms = fixture.uniforms();
+        let shade = |intensity| {
+            let fragment = fragment(Vec3::x(), Vec3::x(), intensity);
+            luminance(fragment_shader(&fragment, &uniforms, &ShaderType::Station))
+        };
+        assert!(shade(1.0) > shade(0.5));
+        assert!(shade(0.5) > shade(0.0));
+    }
+
+    #[test]
+    fn storms_appear_above_the_threshold() {
+        let fixture = Fixture::shared();
+        let mut uniforms = fixture.uniforms();
+        let directions = cap(uniforms.light_dir, 0.8, 64);
+        // El color de tormenta es amarillento y las bandas casi no tienen verde
+        let mut green = |threshold| {
+            uniforms.gas_giant.storm_threshold = threshold;
+            let green = directions
+                .iter()
+                .map(|&d| shade(&uniforms, ShaderType::GasGiant, d).y);
+            mean(green)
+        };
+        let calm = green(1.0);
+        let stormy = green(-1.0);
+        assert!(stormy > calm + 0.1, "calm {} stormy {}", calm, stormy);
+    }
+
+    #[test]
+    fn band_frequency_changes_the_bands() {
+        let fixture = Fixture::shared();
+        let mut uniforms = fixture.uniforms();
+        // Un meridiano del lado iluminado, donde se cruzan todas las bandas,
+        // entre el sol y la cámara para que la atmósfera no las tape
+        let meridian: Vec<Vec3> = (0..64)
+            .map(|i| Vec3::new(1.0, i as f32 / 32.0 - 1.0, 1.0).normalize())
+            .collect();
+        let mut crossings = |frequency| {
+            uniforms.gas_giant.band_frequency = frequency;
+            let shades: Vec<f32> = meridian
+                .iter()
+                .map(|&d| luminance(shade(&uniforms, ShaderType::GasGiant, d)))
+                .collect();
+            let average = mean(shades.iter().copied());
+            shades
+                .windows(2)
+                .filter(|pair| (pair[0] > average) != (pair[1] > average))
+                .count()
+        };
+        let (coarse, fine) = (crossings(10.0), crossings(80.0));
+        assert!(fine > coarse, "coarse {} fine {}", coarse, fine);
+    }
+
+    #[test]
+    fn aurora_only_lights_the_polar_band() {
+        let fixture = Fixture::shared();
+        let mut uniforms = fixture.uniforms();
+        let latitude = uniforms.aurora.latitude;
+        // Del lado nocturno, donde la aurora se nota sobre el planeta oscuro
+        let ring = |y: f32| -> Vec<Vec3> {
+            let radius = (1.0 - y * y).sqrt();
+            (0..48)
+                .map(|i| {
+                    let angle = PI / 2.0 + i as f32 / 48.0 * PI;
+                    Vec3::new(radius * angle.cos(), y, radius * angle.sin())
+                })
+                .collect()
+        };
+        let (polar, equator) = (ring(latitude), ring(0.0));
+
+        let mut brightness = |intensity, directions: &[Vec3]| {
+            uniforms.aurora.intensity = intensity;
+            mean_luminance(&uniforms, ShaderType::ColdGasGiant, directions)
+        };
+        let gain_at_pole = brightness(1.0, &polar) - brightness(0.0, &polar);
+        let gain_at_equator = brightness(1.0, &equator) - brightness(0.0, &equator);
+        assert!(gain_at_pole > 0.01, "pole gained {}", gain_at_pole);
+        // Solo el brillo aleatorio de las bandas cambia en el ecuador
+        assert!(gain_at_equator.abs() < gain_at_pole / 4.0);
+    }
+
+    #[test]
+    fn sun_darkens_toward_the_limb() {
+        let fixture = Fixture::shared();
+        let mut uniforms = fixture.uniforms();
+        uniforms.light_dir = Vec3::z();
+        let center = cap(Vec3::z(), 0.3, 64);
+        // A unos 75° del centro, antes de la franja de las protuberancias
+        let limb: Vec<Vec3> = (0..64)
+            .map(|i| {
+                let angle = i as f32 / 64.0 * 2.0 * PI;
+                Vec3::new(angle.cos() * 0.97, angle.sin() * 0.97, 0.26).normalize()
+            })
+            .collect();
+        let disc = mean_luminance(&uniforms, ShaderType::Solar, &center);
+        let edge = mean_luminance(&uniforms, ShaderType::Solar, &limb);
+        assert!(disc > edge * 1.2, "center {} limb {}", disc, edge);
+    }
+
+    #[test]
+    fn atmosphere_is_blue_at_the_limb_and_red_at_sunset() {
+        let air = Atmosphere::TERRAN;
+        let up = Vec3::z();
+        let white = Vec3::repeat(1.0);
+        // De frente y a pleno sol casi no se nota; de refilón el aire brilla
+        // y tira a azul
+        let noon = air.scatter(Vec3::zeros(), &up, &up, &up, &white);
+        let grazing = Vec3::new(0.99, 0.0, 0.14).normalize();
+        let limb = air.scatter(Vec3::zeros(), &up, &grazing, &up, &white);
+        assert!(limb.z > noon.z * 1.5, "noon {:?} limb {:?}", noon, limb);
+        assert!(limb.z > limb.x * 2.0, "limb {:?}", limb);
+        // Con el sol en el horizonte, una superficie blanca se ve rojiza
+        let low_sun = Vec3::new(0.0, 0.995, 0.1).normalize();
+        let sunset = air.scatter(Vec3::repeat(1.0), &up, &up, &low_sun, &white);
+        assert!(sunset.x > sunset.z * 1.5, "sunset {:?}", sunset);
+    }
+
+    #[test]
+    fn crater_slope_matches_its_height() {
+        // La pendiente exacta tiene que coincidir con la que se mide a pasos
+        // cortos, o el sombreado no correspondería con el relieve
+        let step = 1e-3;
+        let mut checked = 0;
+        for i in 0..400 {
+            let point = Vec3::new(
+                i as f32 * 0.137,
+                i as f32 * 0.071 + 0.3,
+                2.5 - i as f32 * 0.05,
+            );
+            let (height, gradient) = craters(point);
+            if height == 0.0 {
+                continue;
+            }
+            let numeric = Vec3::new(
+                craters(point + Vec3::x() * step).0 - height,
+                craters(point + Vec3::y() * step).0 - height,
+                craters(point + Vec3::z() * step).0 - height,
+            ) / step;
+            // Cerca de la cresta del borde el paso la cruza, así que se
+            // tolera un poco de diferencia
+            assert!(
+                (numeric - gradient).magnitude() < 0.05 + gradient.magnitude() * 0.05,
+                "en {:?}: exacta {:?}, medida {:?}",
+                point,
+                gradient,
+                numeric
+            );
+            checked += 1;
+        }
+        assert!(checked > 50, "solo {} puntos caen en un cráter", checked);
+    }
+
+    #[test]
+    fn normal_view_colors_each_axis() {
+        let fixture = Fixture::shared();
+        let uniforms = Uniforms {
+            debug_view: Some(DebugView::Normals),
+            ..fixture.uniforms()
+        };
+        for (axis, direction) in [Vec3::x(), Vec3::y(), Vec3::z()].into_iter().enumerate() {
+            let color = shade(&uniforms, ShaderType::Terran, direction);
+            let mut expected = Vec3::repeat(0.5);
+            expected[axis] = 1.0;
+            assert!(
+                (color - expected).magnitude() < 1e-5,
+                "{:?} se ve {:?}",
+                direction,
+                color
+            );
+            // Del otro lado, el mismo canal apagado
+            let color = shade(&uniforms, ShaderType::Terran, -direction);
+            assert!(
+                color[axis].abs() < 1e-5,
+                "{:?} se ve {:?}",
+                -direction,
+                color
+            );
+        }
+    }
+
+    #[test]
+    fn a_rock_with_the_largest_seed_still_renders() {
+        // Las rocas del cinturón toman semillas de todo el rango de u32, y
+        // los cráteres, el ruido celular de la luna
+        let fixture = Fixture::shared();
+        let noise = NoiseSettings::for_shader(&ShaderType::Moon).build();
+        let uniforms = Uniforms {
+            viewport_matrix: create_viewport_matrix(&Viewport::full(16.0, 16.0), false),
+            noise: &noise,
+            ..fixture.uniforms()
+        };
+        let obj = Obj::load("assets/models/sphere.obj").unwrap();
+        let rock = Instance {
+            seed: u32::MAX,
+            ..Instance::new(Mat4::new_scaling(0.8 / obj.bounding_radius()))
+        };
+        let mut framebuffer = Framebuffer::new(16, 16);
+        framebuffer.clear();
+        render_instanced(
+            &mut framebuffer,
+            &uniforms,
+            &obj.get_vertex_array(),
+            &[rock],
+            &ShaderType::Moon,
+            DepthMode::ZBuffer,
+        );
+        framebuffer.resolve(&ToneMapping::default(), Dither::Off);
+        let lit: Vec<u32> = framebuffer.buffer.into_iter().filter(|&p| p != 0).collect();
+        assert!(!lit.is_empty());
+        // El ruido sigue variando por la roca en vez de quedarse plano
+        assert!(lit.iter().any(|&p| p != lit[0]));
+    }
+
+    #[test]
+    fn every_shader_matches_its_golden_image() {
+        let scene = golden::Scene::load().unwrap();
+        for shader in SHADERS {
+            let path = golden::golden_path(shader);
+            let expected = golden::load(shader)
+                .unwrap()
+                .unwrap_or_else(|| panic!("falta {}", path.display()));
+            let difference = golden::compare(&expected, &scene.render(shader));
+            // `--golden` guarda la imagen de lo que cambió
+            assert_eq!(
+                difference.pixels,
+                0,
+                "{:?}: {} píxeles difieren de {}, hasta en {}",
+                shader,
+                difference.pixels,
+                path.display(),
+                difference.largest
+            );
+        }
+    }
+}
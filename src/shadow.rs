@@ -0,0 +1,140 @@
+use crate::vertex::Vertex;
+use nalgebra_glm::{look_at, ortho, Mat4, Vec3, Vec4};
+use raster::{Point, Scissor, Target};
+
+// Texels on either side of the one a fragment lands in that are averaged, so
+// shadow edges come out soft instead of stair-stepped
+const FILTER_RADIUS: i32 = 1;
+// How far a fragment is pushed off its surface, in texels along its normal,
+// before it's looked up. Without it the surface shadows itself in stripes
+// wherever it's at a slant to the sun.
+const NORMAL_OFFSET: f32 = 1.5;
+// Depth, in world units, a fragment can be behind the nearest caster and
+// still count as lit
+const DEPTH_BIAS: f32 = 0.01;
+
+// How deep everything is from the sun's side, drawn every frame before the
+// views: the bodies are rendered straight into a square depth buffer through
+// an orthographic projection looking along the sunlight, with no shading.
+// A fragment is then in shadow wherever something was drawn in front of it,
+// which is how the moon's shadow crosses the planet during an eclipse.
+pub struct ShadowMap {
+    size: usize,
+    depth: Vec<f32>,
+    // World units one texel covers, for the normal offset
+    texel: f32,
+}
+
+impl ShadowMap {
+    pub fn new(size: usize) -> Self {
+        ShadowMap {
+            size,
+            depth: vec![f32::INFINITY; size * size],
+            texel: 0.0,
+        }
+    }
+
+    // World space to the light's NDC for a sun in `light_dir` shining on
+    // everything within `reach` of `center`
+    pub fn light_space_matrix(light_dir: Vec3, center: Vec3, reach: f32) -> Mat4 {
+        let light_dir = light_dir.normalize();
+        let up = if light_dir.y.abs() > 0.99 {
+            Vec3::x()
+        } else {
+            Vec3::y()
+        };
+        let view = look_at(&(center + light_dir * reach * 2.0), &center, &up);
+        ortho(-reach, reach, -reach, reach, reach, reach * 3.0) * view
+    }
+
+    // Starts a frame's map seen through `light_space_matrix`
+    pub fn clear(&mut self, light_space_matrix: &Mat4) {
+        self.depth.fill(f32::INFINITY);
+        // The projection scales every world axis by one over the reach
+        self.texel = 2.0 / (light_space_matrix.column(0).xyz().magnitude() * self.size as f32);
+    }
+
+    // The depth-only render path: transforms and rasterizes the mesh,
+    // keeping the nearest depth in each texel
+    pub fn draw(
+        &mut self,
+        light_space_matrix: &Mat4,
+        vertex_array: &[Vertex],
+        model_matrix: &Mat4,
+    ) {
+        let transform = light_space_matrix * model_matrix;
+        for tri in vertex_array.chunks_exact(3) {
+            let [a, b, c] = [&tri[0], &tri[1], &tri[2]].map(|vertex| {
+                let p = vertex.position;
+                self.to_texels(&(transform * Vec4::new(p.x, p.y, p.z, 1.0)))
+            });
+            self.fill_triangle(a, b, c, |_| ());
+        }
+    }
+
+    // Fraction of the sunlight that reaches a surface point, 0 in full shadow.
+    // `normal` is the surface's unit normal in world space. Points outside
+    // the map are lit.
+    pub fn visibility(&self, light_space_matrix: &Mat4, position: &Vec3, normal: &Vec3) -> f32 {
+        let offset = position + normal * (self.texel * NORMAL_OFFSET);
+        let p =
+            self.to_texels(&(light_space_matrix * Vec4::new(offset.x, offset.y, offset.z, 1.0)));
+        // NDC depth spans two units over the light's depth range
+        let bias = DEPTH_BIAS * light_space_matrix[(2, 2)].abs() * 0.5;
+
+        let (cx, cy) = (p.x.floor() as i32, p.y.floor() as i32);
+        let mut lit = 0;
+        let mut taps = 0;
+        for dy in -FILTER_RADIUS..=FILTER_RADIUS {
+            for dx in -FILTER_RADIUS..=FILTER_RADIUS {
+                let (x, y) = (cx + dx, cy + dy);
+                taps += 1;
+                if x < 0 || y < 0 || x >= self.size as i32 || y >= self.size as i32 {
+                    lit += 1;
+                    continue;
+                }
+                if p.z - bias <= self.depth[y as usize * self.size + x as usize] {
+                    lit += 1;
+                }
+            }
+        }
+        lit as f32 / taps as f32
+    }
+
+    // Light NDC to texel coordinates, top row first, and a depth of 0 at the
+    // sun's end of the range and 1 at the far end
+    fn to_texels(&self, ndc: &Vec4) -> Point {
+        let size = self.size as f32;
+        Point::new(
+            (ndc.x * 0.5 + 0.5) * size,
+            (0.5 - ndc.y * 0.5) * size,
+            ndc.z * 0.5 + 0.5,
+        )
+    }
+}
+
+// Depth and nothing else, the nearest kept
+impl Target for ShadowMap {
+    type Color = ();
+
+    fn scissor(&self) -> Scissor {
+        Scissor {
+            min_x: 0,
+            min_y: 0,
+            max_x: self.size,
+            max_y: self.size,
+        }
+    }
+
+    fn passes(&self, x: usize, y: usize, depth: f32) -> bool {
+        depth < self.depth[y * self.size + x]
+    }
+
+    fn write(&mut self, x: usize, y: usize, depth: f32, _: ()) -> bool {
+        let passes = self.passes(x, y, depth);
+        if passes {
+            self.depth[y * self.size + x] = depth;
+        }
+        passes
+    }
+}
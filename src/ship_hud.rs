@@ -0,0 +1,212 @@
+use crate::font::{text_width, GLYPH_HEIGHT};
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::locale::Locale;
+use crate::measure::{figure, Unit};
+use crate::picking::PickTarget;
+use crate::silhouette::projected_circle;
+use crate::Uniforms;
+use nalgebra_glm::{Vec2, Vec3, Vec4};
+use std::borrow::Cow;
+use std::f32::consts::TAU;
+
+const HUD_COLOR: u32 = 0x80FFB0;
+const LEAD_COLOR: u32 = 0xFFB040;
+// Half the span of each arm of the crosshair, and the gap left in its middle
+const CROSSHAIR: usize = 8;
+const CROSSHAIR_GAP: usize = 3;
+// Smallest the brackets around the target close to, in pixels from its
+// center, so a far-off body still gets a box that can be seen
+const MIN_BRACKET: f32 = 8.0;
+const LEAD_RADIUS: f32 = 5.0;
+
+// The camera flown like a ship: a crosshair down its line of sight, how fast
+// it's going, how far off the target is, and where to head to meet the
+// target if it kept on as it's going and the camera flew straight at the
+// speed it has. Speeds are in world units a frame, since that's the step
+// everything in the scene moves by.
+pub struct ShipHud {
+    pub enabled: bool,
+    // Where the camera and the target were last frame
+    previous_eye: Option<Vec3>,
+    previous_target: Option<(Cow<'static, str>, Vec3)>,
+    speed: f32,
+    target_velocity: Option<Vec3>,
+}
+
+impl ShipHud {
+    pub fn new() -> Self {
+        ShipHud {
+            enabled: false,
+            previous_eye: None,
+            previous_target: None,
+            speed: 0.0,
+            target_velocity: None,
+        }
+    }
+
+    // Starts the measuring over when turned on, so the frames it was off
+    // don't count as one long step
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.previous_eye = None;
+        self.previous_target = None;
+        self.speed = 0.0;
+        self.target_velocity = None;
+    }
+
+    // Called once a frame with where the camera and the target are now. A
+    // new target has no velocity until the frame after.
+    pub fn update(&mut self, eye: Vec3, target: &PickTarget) {
+        if !self.enabled {
+            return;
+        }
+        self.speed = self
+            .previous_eye
+            .map_or(0.0, |previous| (eye - previous).magnitude());
+        self.target_velocity = match &self.previous_target {
+            Some((name, previous)) if *name == target.name => Some(target.center - previous),
+            _ => None,
+        };
+        self.previous_eye = Some(eye);
+        self.previous_target = Some((target.name.clone(), target.center));
+    }
+
+    // Draws over the view in `area`
+    pub fn draw(
+        &self,
+        framebuffer: &mut Framebuffer,
+        uniforms: &Uniforms,
+        area: Rect,
+        target: &PickTarget,
+        unit: &Unit,
+        locale: &Locale,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let eye = uniforms.eye;
+        let center = Vec2::new(
+            (area.x + area.width / 2) as f32,
+            (area.y + area.height / 2) as f32,
+        );
+        framebuffer.set_current_color(HUD_COLOR);
+        draw_crosshair(framebuffer, center);
+
+        let projected = projected_circle(
+            Vec4::new(target.center.x, target.center.y, target.center.z, 1.0),
+            target.radius,
+            &uniforms.view_matrix,
+            &uniforms.projection_matrix,
+            &uniforms.viewport_matrix,
+        );
+        if let Some((screen, radius)) = projected {
+            draw_brackets(framebuffer, screen, radius.max(MIN_BRACKET) + 3.0);
+        }
+
+        if let Some(lead) = self.lead_point(eye, target.center) {
+            let transform =
+                uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+            let screen = transform * Vec4::new(lead.x, lead.y, lead.z, 1.0);
+            if screen.w > 0.0 {
+                framebuffer.set_current_color(LEAD_COLOR);
+                draw_circle(framebuffer, screen.xy() / screen.w, LEAD_RADIUS);
+                plot(framebuffer, screen.xy() / screen.w);
+            }
+        }
+
+        // Under the crosshair, each line centered on it
+        let distance = ((target.center - eye).magnitude() - target.radius).max(0.0);
+        let lines = [
+            locale.format("hud.ship_speed", &[&format!("{:.4}", self.speed)]),
+            locale.format(
+                "hud.ship_target",
+                &[
+                    &locale.body_name(&target.name),
+                    &format!("{:.3}", distance),
+                    &figure(distance * unit.length),
+                    &unit.name,
+                ],
+            ),
+        ];
+        framebuffer.set_current_color(HUD_COLOR);
+        let mut y = center.y as i32 + CROSSHAIR as i32 + 8;
+        for text in &lines {
+            let x = center.x as i32 - text_width(text, 1) as i32 / 2;
+            framebuffer.draw_text(x, y, text, 1);
+            y += GLYPH_HEIGHT as i32 + 4;
+        }
+    }
+
+    // Where the camera, flying straight at its speed from `eye`, would meet
+    // the target going on at its velocity from `target`: the soonest time t
+    // that |target + velocity * t - eye| = speed * t. None when it's still,
+    // the target's velocity isn't known yet or the target is outrunning it.
+    fn lead_point(&self, eye: Vec3, target: Vec3) -> Option<Vec3> {
+        let velocity = self.target_velocity?;
+        if self.speed <= 1e-6 {
+            return None;
+        }
+        let offset = target - eye;
+        let a = velocity.norm_squared() - self.speed * self.speed;
+        let b = 2.0 * offset.dot(&velocity);
+        let c = offset.norm_squared();
+        let time = if a.abs() < 1e-9 {
+            -c / b
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let root = discriminant.sqrt();
+            // c > 0, so the roots' product c / a is negative when the camera
+            // is the faster, leaving one root ahead; otherwise both are ahead
+            // or both behind, and the sooner one ahead is wanted
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                .into_iter()
+                .filter(|&t| t > 0.0)
+                .fold(f32::INFINITY, f32::min)
+        };
+        (time.is_finite() && time > 0.0).then(|| target + velocity * time)
+    }
+}
+
+fn plot(framebuffer: &mut Framebuffer, point: Vec2) {
+    if point.x >= 0.0 && point.y >= 0.0 {
+        framebuffer.set_pixel(point.x as usize, point.y as usize);
+    }
+}
+
+fn draw_crosshair(framebuffer: &mut Framebuffer, center: Vec2) {
+    for step in CROSSHAIR_GAP..=CROSSHAIR {
+        let step = step as f32;
+        for offset in [
+            Vec2::new(step, 0.0),
+            Vec2::new(-step, 0.0),
+            Vec2::new(0.0, step),
+            Vec2::new(0.0, -step),
+        ] {
+            plot(framebuffer, center + offset);
+        }
+    }
+}
+
+// The four corners of a square `reach` pixels out from `center` each way
+fn draw_brackets(framebuffer: &mut Framebuffer, center: Vec2, reach: f32) {
+    let arm = (reach / 2.0).clamp(3.0, 12.0) as usize;
+    for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+        let corner = center + Vec2::new(x, y) * reach;
+        for step in 0..arm {
+            let step = step as f32;
+            plot(framebuffer, corner - Vec2::new(x * step, 0.0));
+            plot(framebuffer, corner - Vec2::new(0.0, y * step));
+        }
+    }
+}
+
+fn draw_circle(framebuffer: &mut Framebuffer, center: Vec2, radius: f32) {
+    let steps = (radius * TAU).ceil() as usize;
+    for step in 0..steps {
+        let (sin, cos) = (step as f32 / steps as f32 * TAU).sin_cos();
+        plot(framebuffer, center + Vec2::new(cos, sin) * radius);
+    }
+}
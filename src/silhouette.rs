@@ -0,0 +1,123 @@
+use crate::framebuffer::Framebuffer;
+use nalgebra_glm::{Mat4, Vec2, Vec3, Vec4};
+
+// Copy of the framebuffer around a sphere's projected circle, taken before the
+// sphere is drawn so its edge pixels can be blended against what was behind it
+pub struct SilhouetteRegion {
+    center: Vec2,
+    radius: f32,
+    min_x: usize,
+    min_y: usize,
+    max_x: usize,
+    max_y: usize,
+    colors: Vec<Vec3>,
+    depths: Vec<f32>,
+}
+
+// Screen-space center and pixel radius of a sphere. The true outline is a
+// slightly elliptical conic off-axis; a circle is close enough for edge AA.
+pub fn projected_circle(
+    center: Vec4,
+    radius: f32,
+    view_matrix: &Mat4,
+    projection_matrix: &Mat4,
+    viewport_matrix: &Mat4,
+) -> Option<(Vec2, f32)> {
+    let view_center = view_matrix * center;
+    let distance = view_center.xyz().magnitude();
+    if view_center.z >= 0.0 || distance <= radius {
+        return None;
+    }
+
+    let clip = projection_matrix * view_center;
+    let screen =
+        viewport_matrix * Vec4::new(clip.x / clip.w, clip.y / clip.w, clip.z / clip.w, 1.0);
+
+    let tan_angular_radius = radius / (distance * distance - radius * radius).sqrt();
+    let pixel_radius =
+        tan_angular_radius * projection_matrix[(1, 1)] * viewport_matrix[(1, 1)].abs();
+
+    Some((Vec2::new(screen.x, screen.y), pixel_radius))
+}
+
+impl SilhouetteRegion {
+    pub fn capture(framebuffer: &Framebuffer, center: Vec2, radius: f32) -> Option<Self> {
+        let margin = 2.0;
+        let scissor = framebuffer.scissor();
+        let min_x = ((center.x - radius - margin).floor().max(0.0) as usize).max(scissor.min_x);
+        let min_y = ((center.y - radius - margin).floor().max(0.0) as usize).max(scissor.min_y);
+        let max_x = ((center.x + radius + margin).ceil() as usize).min(scissor.max_x);
+        let max_y = ((center.y + radius + margin).ceil() as usize).min(scissor.max_y);
+        if min_x >= max_x || min_y >= max_y {
+            return None;
+        }
+
+        let mut colors = Vec::with_capacity((max_x - min_x) * (max_y - min_y));
+        let mut depths = Vec::with_capacity(colors.capacity());
+        for y in min_y..max_y {
+            let row = y * framebuffer.width;
+            colors.extend_from_slice(&framebuffer.hdr[row + min_x..row + max_x]);
+            depths.extend_from_slice(&framebuffer.zbuffer[row + min_x..row + max_x]);
+        }
+
+        Some(SilhouetteRegion {
+            center,
+            radius,
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            colors,
+            depths,
+        })
+    }
+
+    // Blends every pixel within a pixel of the circle by its analytic coverage.
+    // Pixels the mesh never reached (the polygon sits inside the true circle)
+    // borrow the surface color from their inward neighbour.
+    pub fn resolve(&self, framebuffer: &mut Framebuffer) {
+        let width = self.max_x - self.min_x;
+
+        for y in self.min_y..self.max_y {
+            for x in self.min_x..self.max_x {
+                let pixel = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                let offset = pixel - self.center;
+                let distance = offset.magnitude();
+                let coverage = (self.radius - distance + 0.5).clamp(0.0, 1.0);
+                if coverage <= 0.0 || coverage >= 1.0 {
+                    continue;
+                }
+
+                let local = (y - self.min_y) * width + (x - self.min_x);
+                let index = y * framebuffer.width + x;
+                let background = self.colors[local];
+                let drawn = framebuffer.zbuffer[index] != self.depths[local];
+
+                let surface = if drawn {
+                    framebuffer.hdr[index]
+                } else if self.depths[local].is_infinite() {
+                    let inward = pixel - offset / distance.max(1.0) * 1.5;
+                    match self.drawn_color(framebuffer, inward) {
+                        Some(color) => color,
+                        None => continue,
+                    }
+                } else {
+                    continue;
+                };
+
+                framebuffer.set_radiance(x, y, background.lerp(&surface, coverage));
+            }
+        }
+    }
+
+    fn drawn_color(&self, framebuffer: &Framebuffer, point: Vec2) -> Option<Vec3> {
+        let (x, y) = (point.x as usize, point.y as usize);
+        if x < self.min_x || x >= self.max_x || y < self.min_y || y >= self.max_y {
+            return None;
+        }
+
+        let local = (y - self.min_y) * (self.max_x - self.min_x) + (x - self.min_x);
+        let index = y * framebuffer.width + x;
+        (framebuffer.zbuffer[index] != self.depths[local]).then(|| framebuffer.hdr[index])
+    }
+}
@@ -0,0 +1,201 @@
+use crate::comet::Comet;
+#[cfg(feature = "physics")]
+use crate::impact::Impacts;
+#[cfg(feature = "physics")]
+use crate::n_body::NBody;
+use crate::procedural::BeltRock;
+#[cfg(feature = "particles")]
+use crate::prominence::Prominences;
+#[cfg(feature = "physics")]
+use crate::satellite::MoonSystem;
+use crate::season::Orbit;
+use crate::shaders::ShaderType;
+#[cfg(feature = "physics")]
+use crate::transform::Transform;
+use crate::weather::Weather;
+#[cfg(feature = "physics")]
+use crate::PLANET_SPIN;
+use crate::{sun_direction, COMET_ORBIT};
+use nalgebra_glm::Vec3;
+#[cfg(feature = "physics")]
+use rand::rngs::StdRng;
+#[cfg(feature = "physics")]
+use rand::{Rng, SeedableRng};
+
+// Everything in the system that changes from one frame to the next by more
+// than where its orbit puts it, which only takes the time. Stepping a clone
+// with the same controls brings it to the same place the original got to,
+// so a clone is a snapshot that can be run forward again to any later frame.
+#[derive(Clone)]
+pub struct Simulation {
+    pub time: u32,
+    pub weather: Weather,
+    pub comet: Comet,
+    #[cfg(feature = "particles")]
+    pub prominences: Prominences,
+    #[cfg(feature = "physics")]
+    pub impacts: Impacts,
+    // Belt rocks knocked loose so far, oldest first
+    #[cfg(feature = "physics")]
+    pub dropped: Vec<usize>,
+    // The moons as gravity has moved them, while they're not on their orbits
+    #[cfg(feature = "physics")]
+    pub n_body: Option<NBody>,
+    #[cfg(feature = "physics")]
+    rng: StdRng,
+}
+
+// The user's choices the simulation depends on, for one frame
+#[derive(Clone, PartialEq)]
+pub struct Controls {
+    pub shader: ShaderType,
+    pub sphere_antialiasing: bool,
+    pub show_comet: bool,
+    pub show_belt: bool,
+    #[cfg(feature = "physics")]
+    pub physics: bool,
+    // A rock is sent streaking in at the planet this frame
+    #[cfg(feature = "physics")]
+    pub strike: bool,
+    // The moons to move by gravity instead of along their orbits, or None
+    // to keep them on the orbits
+    #[cfg(feature = "physics")]
+    pub n_body: Option<MoonSystem>,
+}
+
+// What holds still while the simulation runs. The belt only comes loose in
+// physics mode.
+#[cfg_attr(not(feature = "physics"), allow(dead_code))]
+pub struct Setting<'a> {
+    pub center: Vec3,
+    pub scale: f32,
+    // The planet's year, over which the sun goes once around it
+    pub orbit: Orbit,
+    // The planet's radius as the mesh and as the exact sphere drawn with
+    // analytic antialiasing, whichever rocks land on
+    pub planet_radius: f32,
+    pub sphere_radius: f32,
+    pub belt_rocks: &'a [BeltRock],
+    pub belt_rock_radius: f32,
+}
+
+// Size of a rock sent in to strike the planet, as large as the belt's
+// largest
+#[cfg(feature = "physics")]
+const STRIKE_SCALE: f32 = 0.05;
+
+// Euler angles the belt has turned through by `time`, in the planet's frame
+pub fn belt_rotation(time: u32) -> Vec3 {
+    Vec3::new(0.0, time as f32 * 0.0008, 0.0)
+}
+
+impl Simulation {
+    // `seed` picks which belt rocks fall in physics mode
+    #[cfg_attr(not(feature = "physics"), allow(unused_variables))]
+    pub fn new(seed: u64) -> Self {
+        Simulation {
+            time: 0,
+            weather: Weather::new(5),
+            comet: Comet::new(COMET_ORBIT, 23),
+            #[cfg(feature = "particles")]
+            prominences: Prominences::new(29),
+            #[cfg(feature = "physics")]
+            impacts: Impacts::new(17),
+            #[cfg(feature = "physics")]
+            dropped: Vec::new(),
+            #[cfg(feature = "physics")]
+            n_body: None,
+            #[cfg(feature = "physics")]
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    // Advances one frame. Returns the belt rock that was knocked loose, if
+    // one was.
+    pub fn step(&mut self, controls: &Controls, setting: &Setting) -> Option<usize> {
+        self.time += 1;
+        let time = self.time;
+        self.weather.update();
+
+        // The tail starts over whenever the comet comes back
+        if controls.show_comet {
+            let light_dir = sun_direction(&setting.orbit, time);
+            self.comet.update(setting.center, time, light_dir);
+        } else {
+            self.comet.clear();
+        }
+
+        // What rocks land on and prominences rise from
+        #[cfg(feature = "particles")]
+        let planet_radius = if controls.sphere_antialiasing {
+            setting.sphere_radius
+        } else {
+            setting.planet_radius
+        } * setting.scale;
+        #[cfg(feature = "physics")]
+        let dropped = self.drop_rock(controls, setting);
+        #[cfg(not(feature = "physics"))]
+        let dropped = None;
+        #[cfg(feature = "physics")]
+        {
+            let planet_model = Transform::at(setting.center).matrix()
+                * Transform::new(Vec3::zeros(), setting.scale, PLANET_SPIN.orientation(time))
+                    .matrix();
+            if controls.strike {
+                let radius = setting.belt_rock_radius * STRIKE_SCALE;
+                self.impacts
+                    .strike(setting.center, planet_radius, STRIKE_SCALE, radius);
+            }
+            self.impacts
+                .update(setting.center, planet_radius, &planet_model);
+
+            // Moons taken off their orbits start from where they were on
+            // them, and start over when they're another system's
+            match &controls.n_body {
+                Some(moons) => match self.n_body.as_mut().filter(|n_body| n_body.moons == *moons) {
+                    Some(n_body) => n_body.step(),
+                    None => self.n_body = Some(NBody::start(setting.center, moons.clone(), time)),
+                },
+                None => self.n_body = None,
+            }
+        }
+
+        #[cfg(feature = "particles")]
+        if controls.shader == ShaderType::Solar {
+            self.prominences.update(setting.center, planet_radius);
+        } else {
+            self.prominences.clear();
+        }
+        dropped
+    }
+
+    // Every so often a random rock drops out of the belt, keeping the speed
+    // the belt's spin gave it plus a small push toward the planet
+    #[cfg(feature = "physics")]
+    fn drop_rock(&mut self, controls: &Controls, setting: &Setting) -> Option<usize> {
+        if !controls.physics || !controls.show_belt || !self.time.is_multiple_of(45) {
+            return None;
+        }
+        let index = self.rng.gen_range(0..setting.belt_rocks.len());
+        if self.dropped.contains(&index) {
+            return None;
+        }
+        self.dropped.push(index);
+
+        let rock = &setting.belt_rocks[index];
+        let belt_matrix = Transform::at(setting.center).matrix()
+            * Transform::from_euler(Vec3::zeros(), 1.0, belt_rotation(self.time)).matrix();
+        let position = (belt_matrix * rock.position.push(1.0)).xyz();
+        let orbital = Vec3::new(position.z, 0.0, -position.x) * 0.0008;
+        let inward = -position.normalize() * 0.004;
+        self.impacts.launch(
+            position,
+            orbital + inward,
+            rock.rotation,
+            rock.scale,
+            setting.belt_rock_radius * rock.scale,
+            rock.seed,
+        );
+        Some(index)
+    }
+}
@@ -0,0 +1,247 @@
+use crate::framebuffer::{Framebuffer, Scissor};
+use crate::noise::warp;
+use crate::sh::ShIrradiance;
+use crate::{depth_planes, Uniforms};
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use std::f32::consts::{PI, TAU};
+use std::ops::{Add, Mul, Sub};
+
+const MAP_WIDTH: usize = 512;
+const MAP_HEIGHT: usize = 256;
+// A nebula is faint next to the sun; its light is exaggerated so the tint it
+// gives the planets' night sides is visible. A skybox's is too.
+pub const AMBIENT_STRENGTH: f32 = 4.0;
+
+// How far the nebula's noise is pushed around before it's read, in noise
+// units, so the clouds curl into swirls instead of round blobs
+const WARP_STRENGTH: f32 = 0.5;
+
+// Look of one procedural nebula: a diffuse dust cloud and brighter filaments,
+// whose color drifts from `glow` to `core` across the sky
+pub struct Nebula {
+    pub seed: i32,
+    pub dust: [f32; 3],
+    pub glow: [f32; 3],
+    pub core: [f32; 3],
+}
+
+pub const NEBULAE: [Nebula; 3] = [
+    Nebula {
+        seed: 11,
+        dust: [0.10, 0.03, 0.12],
+        glow: [0.45, 0.12, 0.30],
+        core: [0.15, 0.20, 0.50],
+    },
+    Nebula {
+        seed: 29,
+        dust: [0.02, 0.07, 0.12],
+        glow: [0.10, 0.35, 0.40],
+        core: [0.35, 0.40, 0.12],
+    },
+    Nebula {
+        seed: 47,
+        dust: [0.12, 0.06, 0.02],
+        glow: [0.50, 0.28, 0.08],
+        core: [0.45, 0.10, 0.06],
+    },
+];
+
+// Background environment stored as a latitude-longitude map, with its diffuse
+// lighting baked into spherical harmonics when it is built
+pub struct Sky {
+    texels: Vec<Vec3>,
+    pub ambient: ShIrradiance,
+}
+
+// Unit direction through texel coordinates (u, v), with v = 0 at the north
+// pole
+pub fn map_direction(u: f32, v: f32) -> Vec3 {
+    let (phi, theta) = (u * TAU, v * PI);
+    Vec3::new(
+        theta.sin() * phi.cos(),
+        theta.cos(),
+        theta.sin() * phi.sin(),
+    )
+}
+
+// Bilinear lookup by direction into a map laid out like `map_direction`,
+// `width` texels to a row, wrapping around in longitude
+pub fn sample_map<T>(map: &[T], width: usize, direction: &Vec3) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let height = map.len() / width;
+    let u = direction.z.atan2(direction.x).rem_euclid(TAU) / TAU;
+    let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+    let x = u * width as f32 - 0.5;
+    let y = (v * height as f32 - 0.5).clamp(0.0, (height - 1) as f32);
+    let (fx, fy) = (x - x.floor(), y.fract());
+    let x0 = (x.floor() as i32).rem_euclid(width as i32) as usize;
+    let x1 = (x0 + 1) % width;
+    let y0 = y as usize;
+    let y1 = (y0 + 1).min(height - 1);
+
+    let texel = |x: usize, y: usize| map[y * width + x];
+    let top = texel(x0, y0) + (texel(x1, y0) - texel(x0, y0)) * fx;
+    let bottom = texel(x0, y1) + (texel(x1, y1) - texel(x0, y1)) * fx;
+    top + (bottom - top) * fy
+}
+
+impl Sky {
+    pub fn bake(nebula: &Nebula) -> Self {
+        let mut noise = FastNoiseLite::with_seed(nebula.seed);
+        noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        noise.set_fractal_type(Some(FractalType::FBm));
+        noise.set_fractal_octaves(Some(3));
+        noise.set_frequency(Some(1.2));
+
+        let dust = Vec3::from(nebula.dust);
+        let glow = Vec3::from(nebula.glow);
+        let core = Vec3::from(nebula.core);
+        let mut texels = Vec::with_capacity(MAP_WIDTH * MAP_HEIGHT);
+        for y in 0..MAP_HEIGHT {
+            for x in 0..MAP_WIDTH {
+                let u = (x as f32 + 0.5) / MAP_WIDTH as f32;
+                let v = (y as f32 + 0.5) / MAP_HEIGHT as f32;
+                let d = warp(&noise, map_direction(u, v), WARP_STRENGTH, 1);
+
+                let density = noise.get_noise_3d(d.x, d.y, d.z) * 0.5 + 0.5;
+                let filaments = 1.0
+                    - noise
+                        .get_noise_3d(d.x * 2.0 + 40.0, d.y * 2.0, d.z * 2.0)
+                        .abs();
+                let hue = (noise.get_noise_3d(d.x * 0.5, d.y * 0.5 - 70.0, d.z * 0.5) + 0.5)
+                    .clamp(0.0, 1.0);
+                let cloud = density * density * density;
+                let light = glow.lerp(&core, hue);
+                texels.push(dust * (cloud * 2.0) + light * (cloud * filaments.powi(8) * 3.0));
+            }
+        }
+
+        // Each texel covers (2π / width) * (π / height) * sin θ steradians
+        let texel_area = TAU / MAP_WIDTH as f32 * PI / MAP_HEIGHT as f32;
+        let ambient = ShIrradiance::project(texels.iter().enumerate().map(|(index, &radiance)| {
+            let u = ((index % MAP_WIDTH) as f32 + 0.5) / MAP_WIDTH as f32;
+            let v = ((index / MAP_WIDTH) as f32 + 0.5) / MAP_HEIGHT as f32;
+            (
+                map_direction(u, v),
+                radiance * AMBIENT_STRENGTH,
+                texel_area * (v * PI).sin(),
+            )
+        }));
+
+        Sky { texels, ambient }
+    }
+
+    // Bilinear lookup, wrapping around in longitude
+    pub fn radiance(&self, direction: &Vec3) -> Vec3 {
+        let u = direction.z.atan2(direction.x).rem_euclid(TAU) / TAU;
+        let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+        let x = u * MAP_WIDTH as f32 - 0.5;
+        let y = (v * MAP_HEIGHT as f32 - 0.5).clamp(0.0, (MAP_HEIGHT - 1) as f32);
+        let (fx, fy) = (x - x.floor(), y.fract());
+        let x0 = (x.floor() as i32).rem_euclid(MAP_WIDTH as i32) as usize;
+        let x1 = (x0 + 1) % MAP_WIDTH;
+        let y0 = y as usize;
+        let y1 = (y0 + 1).min(MAP_HEIGHT - 1);
+
+        let texel = |x: usize, y: usize| self.texels[y * MAP_WIDTH + x];
+        let top = texel(x0, y0).lerp(&texel(x1, y0), fx);
+        let bottom = texel(x0, y1).lerp(&texel(x1, y1), fx);
+        top.lerp(&bottom, fy)
+    }
+
+    // Fills the current scissor rectangle with the sky as seen by the camera
+    // in `uniforms`, or with what `backdrop` kept of it if that hasn't moved
+    pub fn draw(
+        &self,
+        framebuffer: &mut Framebuffer,
+        uniforms: &Uniforms,
+        backdrop: &mut Backdrop,
+    ) {
+        backdrop.draw(framebuffer, uniforms, |direction| self.radiance(direction));
+    }
+}
+
+// The background as last drawn into one view. Looking every pixel up in the
+// map costs about as much as shading a planet filling the screen, and while
+// the camera holds still each frame would work out the same pixels, so they
+// are copied over instead. Each view keeps its own, and a new sky needs a
+// new one.
+pub struct Backdrop {
+    // The camera and the rectangle it was drawn for
+    drawn: Option<(Mat4, Scissor)>,
+    texels: Vec<Vec3>,
+}
+
+impl Backdrop {
+    pub fn new() -> Self {
+        Backdrop {
+            drawn: None,
+            texels: Vec::new(),
+        }
+    }
+
+    // Like `draw_background`, looking the pixels up only when the camera or
+    // the scissor rectangle changed since the last call
+    pub fn draw(
+        &mut self,
+        framebuffer: &mut Framebuffer,
+        uniforms: &Uniforms,
+        radiance: impl Fn(&Vec3) -> Vec3,
+    ) {
+        let camera = uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+        let scissor = framebuffer.scissor();
+        let rows = scissor.min_y..scissor.max_y;
+        let columns = scissor.min_x..scissor.max_x;
+        if self.drawn == Some((camera, scissor)) {
+            let mut texels = self.texels.iter();
+            for y in rows {
+                for (x, texel) in columns.clone().zip(&mut texels) {
+                    framebuffer.set_radiance(x, y, *texel);
+                }
+            }
+            return;
+        }
+
+        draw_background(framebuffer, uniforms, radiance);
+        self.texels.clear();
+        for y in rows {
+            let row = y * framebuffer.width;
+            self.texels
+                .extend_from_slice(&framebuffer.hdr[row + columns.start..row + columns.end]);
+        }
+        self.drawn = Some((camera, scissor));
+    }
+}
+
+// Fills the current scissor rectangle with the radiance arriving along each
+// pixel's view ray, for an environment infinitely far away. Depth is left
+// untouched so everything draws over it.
+fn draw_background(
+    framebuffer: &mut Framebuffer,
+    uniforms: &Uniforms,
+    radiance: impl Fn(&Vec3) -> Vec3,
+) {
+    let Some(inverse) =
+        (uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix)
+            .try_inverse()
+    else {
+        return;
+    };
+    let (near_depth, far_depth) = depth_planes(&uniforms.viewport_matrix, uniforms.reversed_z);
+
+    let scissor = framebuffer.scissor();
+    for y in scissor.min_y..scissor.max_y {
+        for x in scissor.min_x..scissor.max_x {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            let near = inverse * Vec4::new(px, py, near_depth, 1.0);
+            let far = inverse * Vec4::new(px, py, far_depth, 1.0);
+            let direction = (far.xyz() / far.w - near.xyz() / near.w).normalize();
+            framebuffer.set_radiance(x, y, radiance(&direction));
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::picking::{intersect_sphere, Ray};
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+
+const LEAF_SIZE: usize = 8;
+
+// Clip volume as six planes (n, d) with n·p + d >= 0 on the inside, extracted
+// from a projection * view (* model) matrix. The planes live in whatever space
+// the matrix's input is in.
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let row = |i: usize| matrix.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes =
+            [w + x, w - x, w + y, w - y, w + z, w - z].map(|plane| plane / plane.xyz().magnitude());
+        Frustum { planes }
+    }
+
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(&center) + plane.w >= -radius)
+    }
+
+    // Conservative: only rejects boxes entirely behind one plane
+    fn intersects_box(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.xyz();
+            let farthest = Vec3::new(
+                if normal.x >= 0.0 { max.x } else { min.x },
+                if normal.y >= 0.0 { max.y } else { min.y },
+                if normal.z >= 0.0 { max.z } else { min.z },
+            );
+            normal.dot(&farthest) + plane.w >= 0.0
+        })
+    }
+}
+
+// A leaf owns items[first..first + count]; an inner node has count == 0 and
+// its children at `first` and `first + 1`
+#[derive(Clone)]
+struct Node {
+    min: Vec3,
+    max: Vec3,
+    first: usize,
+    count: usize,
+}
+
+// Bounding volume hierarchy over bounding spheres, so culling and picking
+// touch a few boxes instead of every instance
+#[derive(Clone)]
+pub struct SpatialIndex {
+    spheres: Vec<(Vec3, f32)>,
+    removed: Vec<bool>,
+    items: Vec<usize>,
+    nodes: Vec<Node>,
+}
+
+impl SpatialIndex {
+    pub fn build(spheres: Vec<(Vec3, f32)>) -> Self {
+        let mut index = SpatialIndex {
+            items: (0..spheres.len()).collect(),
+            removed: vec![false; spheres.len()],
+            spheres,
+            nodes: Vec::new(),
+        };
+        index.nodes.push(Node {
+            min: Vec3::zeros(),
+            max: Vec3::zeros(),
+            first: 0,
+            count: index.items.len(),
+        });
+        index.subdivide(0);
+        index
+    }
+
+    // Fits the node's box to its spheres, then splits at the median along the
+    // longest axis of their centers
+    fn subdivide(&mut self, node: usize) {
+        let (first, count) = (self.nodes[node].first, self.nodes[node].count);
+        let items = &mut self.items[first..first + count];
+
+        let mut min = Vec3::repeat(f32::INFINITY);
+        let mut max = Vec3::repeat(f32::NEG_INFINITY);
+        let mut center_min = min;
+        let mut center_max = max;
+        for &item in items.iter() {
+            let (center, radius) = self.spheres[item];
+            min = min.inf(&(center - Vec3::repeat(radius)));
+            max = max.sup(&(center + Vec3::repeat(radius)));
+            center_min = center_min.inf(&center);
+            center_max = center_max.sup(&center);
+        }
+        self.nodes[node].min = min;
+        self.nodes[node].max = max;
+
+        if count <= LEAF_SIZE {
+            return;
+        }
+
+        let extent = center_max - center_min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let spheres = &self.spheres;
+        items.sort_unstable_by(|&a, &b| spheres[a].0[axis].total_cmp(&spheres[b].0[axis]));
+
+        let half = count / 2;
+        let left = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            first,
+            count: half,
+        });
+        self.nodes.push(Node {
+            min,
+            max,
+            first: first + half,
+            count: count - half,
+        });
+        self.nodes[node].first = left;
+        self.nodes[node].count = 0;
+
+        self.subdivide(left);
+        self.subdivide(left + 1);
+    }
+
+    // Leaves `item` out of every later query. The boxes aren't refit, so they
+    // stay a conservative bound around whatever is left.
+    #[cfg(feature = "physics")]
+    pub fn remove(&mut self, item: usize) {
+        self.removed[item] = true;
+    }
+
+    #[cfg(feature = "physics")]
+    pub fn contains(&self, item: usize) -> bool {
+        !self.removed[item]
+    }
+
+    // Items whose sphere touches the frustum
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<usize> {
+        let mut visible = Vec::new();
+        if self.items.is_empty() {
+            return visible;
+        }
+
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !frustum.intersects_box(node.min, node.max) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend([node.first, node.first + 1]);
+                continue;
+            }
+            for &item in &self.items[node.first..node.first + node.count] {
+                if self.removed[item] {
+                    continue;
+                }
+                let (center, radius) = self.spheres[item];
+                if frustum.intersects_sphere(center, radius) {
+                    visible.push(item);
+                }
+            }
+        }
+
+        visible
+    }
+
+    // Nearest item hit by the ray and the distance to it
+    pub fn query_ray(&self, ray: &Ray) -> Option<(usize, f32)> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        let inverse_direction = ray.direction.map(|d| 1.0 / d);
+        let mut nearest: Option<(usize, f32)> = None;
+        let mut stack = vec![0];
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let limit = nearest.map_or(f32::INFINITY, |(_, t)| t);
+            match ray_box(ray, &inverse_direction, node.min, node.max) {
+                Some(t) if t <= limit => {}
+                _ => continue,
+            }
+            if node.count == 0 {
+                stack.extend([node.first, node.first + 1]);
+                continue;
+            }
+            for &item in &self.items[node.first..node.first + node.count] {
+                if self.removed[item] {
+                    continue;
+                }
+                let (center, radius) = self.spheres[item];
+                if let Some(t) = intersect_sphere(ray, center, radius) {
+                    if t < nearest.map_or(f32::INFINITY, |(_, best)| best) {
+                        nearest = Some((item, t));
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+// Slab test; distance to where the ray enters the box, or 0 if it starts inside
+fn ray_box(ray: &Ray, inverse_direction: &Vec3, min: Vec3, max: Vec3) -> Option<f32> {
+    let t1 = (min - ray.origin).component_mul(inverse_direction);
+    let t2 = (max - ray.origin).component_mul(inverse_direction);
+    let enter = t1.inf(&t2).max();
+    let exit = t1.sup(&t2).min();
+    (exit >= enter.max(0.0)).then_some(enter.max(0.0))
+}
@@ -0,0 +1,71 @@
+use crate::framebuffer::Rect;
+use std::sync::OnceLock;
+
+// Pixels across each of the icons, drawn shrunk or grown to size
+const ICON_SIZE: usize = 16;
+
+// A solid round dot with a soft edge, and a glow fading out from its middle
+pub const DOT: Rect = Rect {
+    x: 0,
+    y: 0,
+    width: ICON_SIZE,
+    height: ICON_SIZE,
+};
+pub const GLOW: Rect = Rect {
+    x: ICON_SIZE,
+    y: 0,
+    width: ICON_SIZE,
+    height: ICON_SIZE,
+};
+
+// Small images packed side by side into one, for `Framebuffer::draw_sprite`
+// to copy any of them out of by its rectangle. Texels are 0xAARRGGBB, with
+// straight rather than premultiplied alpha.
+pub struct SpriteAtlas {
+    width: usize,
+    texels: Vec<u32>,
+}
+
+// The icons the overlays share, white so they take the current color, drawn
+// on first use
+pub fn icons() -> &'static SpriteAtlas {
+    static ATLAS: OnceLock<SpriteAtlas> = OnceLock::new();
+    ATLAS.get_or_init(|| {
+        let mut atlas = SpriteAtlas::new(ICON_SIZE * 2, ICON_SIZE);
+        // Distances are from the middle, 1 at the edge of the icon
+        atlas.paint(DOT, 0xFFFFFF, |distance| {
+            ((1.0 - distance) * ICON_SIZE as f32 / 2.0).clamp(0.0, 1.0)
+        });
+        atlas.paint(GLOW, 0xFFFFFF, |distance| (1.0 - distance).max(0.0).powi(2));
+        atlas
+    })
+}
+
+impl SpriteAtlas {
+    // Transparent all over
+    pub fn new(width: usize, height: usize) -> Self {
+        SpriteAtlas {
+            width,
+            texels: vec![0; width * height],
+        }
+    }
+
+    pub fn texel(&self, x: usize, y: usize) -> u32 {
+        self.texels[y * self.width + x]
+    }
+
+    // Fills `region` with `color` at the opacity `alpha` gives for how far
+    // each texel's middle is from the region's, as a share of half its width
+    fn paint(&mut self, region: Rect, color: u32, alpha: impl Fn(f32) -> f32) {
+        let half = region.width as f32 / 2.0;
+        for y in 0..region.height {
+            for x in 0..region.width {
+                let dx = x as f32 + 0.5 - half;
+                let dy = y as f32 + 0.5 - region.height as f32 / 2.0;
+                let opacity = alpha(dx.hypot(dy) / half).clamp(0.0, 1.0);
+                let index = (region.y + y) * self.width + region.x + x;
+                self.texels[index] = ((opacity * 255.0).round() as u32) << 24 | color & 0xFFFFFF;
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use crate::framebuffer::Rect;
+use nalgebra_glm::Vec3;
+
+const LUMA: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+
+// How the two eyes are put on screen: red-cyan over each other for glasses,
+// or next to each other, the left eye on the left for displays that take
+// side-by-side images or on the right for crossing one's eyes at them
+#[derive(Clone, Copy, PartialEq)]
+pub enum StereoMode {
+    Anaglyph,
+    SideBySide,
+}
+
+// Where the eyes are for a camera. `separation` is the distance between
+// them and `convergence` how far off they cross, both as shares of the
+// distance to the point the camera looks at, so the depth looks the same
+// zoomed in as out. Crossing at that point puts it at the depth of the
+// screen, with nearer things standing out of it and farther ones behind.
+#[derive(Clone, Copy)]
+pub struct Stereo {
+    pub separation: f32,
+    pub convergence: f32,
+    pub cross_eyed: bool,
+}
+
+impl Stereo {
+    // The left and right eyes of a camera at `eye` looking at `center`, side
+    // by side along its right, and the point they both look at. Looking
+    // straight along `up` there's no telling which way is right, and both
+    // stay at `eye`.
+    pub fn eyes(&self, eye: Vec3, center: Vec3, up: Vec3) -> [(Vec3, Vec3); 2] {
+        let forward = center - eye;
+        let crossing = eye + forward * self.convergence;
+        let Some(right) = forward.cross(&up).try_normalize(1e-6) else {
+            return [(eye, crossing); 2];
+        };
+        let half = right * forward.magnitude() * self.separation / 2.0;
+        [(eye - half, crossing), (eye + half, crossing)]
+    }
+
+    // The halves of `area` the left and right eyes go in
+    pub fn halves(&self, area: Rect) -> [Rect; 2] {
+        let half = area.width / 2;
+        let left = Rect {
+            width: half,
+            ..area
+        };
+        let right = Rect {
+            x: area.x + half,
+            width: area.width - half,
+            ..area
+        };
+        if self.cross_eyed {
+            [right, left]
+        } else {
+            [left, right]
+        }
+    }
+}
+
+// Folds `left`, the radiance the left eye saw, into `right` for red-cyan
+// glasses: red from the left eye, green and blue from the right. Only the
+// left eye's brightness goes into red, so a red body doesn't show up in
+// one eye and go missing from the other.
+pub fn merge_anaglyph(left: &[Vec3], right: &mut [Vec3]) {
+    for (left, right) in left.iter().zip(right) {
+        right.x = left.dot(&LUMA);
+    }
+}
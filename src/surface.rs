@@ -0,0 +1,113 @@
+use crate::color::Color;
+use crate::fragment::Fragment;
+use crate::layer::{BakedLayer, LayerMap, MAP_HEIGHT, MAP_WIDTH};
+use crate::material::Material;
+use crate::shaders::{fragment_shader, ShaderType};
+use crate::Uniforms;
+use nalgebra_glm::{mat4_to_mat3, Mat3, Mat4, Vec3};
+use std::f32::consts::PI;
+
+// What one bake of the planet's shading is evaluated with. The light is
+// worked out for where the planet had turned to and the eye was when the
+// bake started, so it lags a little behind both until the next one.
+#[derive(Clone, Copy)]
+pub struct SurfaceInputs {
+    pub time: u32,
+    pub model_matrix: Mat4,
+    pub eye: Vec3,
+}
+
+// The planet shaded in texture space: its whole surface is shaded into a
+// latitude-longitude map a share of rows at a time, and drawing it only
+// looks the result up, so a frame costs the same however many pixels the
+// planet covers. Only worth it for surfaces that change slowly, since
+// anything faster than a bake, a flash or a passing shadow, is smeared over
+// the frames the bake takes.
+pub struct SurfaceCache {
+    layer: BakedLayer<SurfaceInputs, Vec3>,
+    // What the map, and any bake in progress, was shaded with
+    shader: Option<ShaderType>,
+}
+
+impl SurfaceCache {
+    // Re-baked every `interval` frames
+    pub fn new(interval: u32) -> Self {
+        SurfaceCache {
+            layer: BakedLayer::new(interval),
+            shader: None,
+        }
+    }
+
+    // Bakes this frame's share of the map for a planet of `radius` drawn
+    // with `shader`, starting over if that's not what it was baking.
+    // `uniforms` sets the scene up for the bake's inputs.
+    pub fn update<'a>(
+        &mut self,
+        shader: ShaderType,
+        current: SurfaceInputs,
+        radius: f32,
+        uniforms: impl FnOnce(&SurfaceInputs) -> Uniforms<'a>,
+    ) {
+        if self.shader != Some(shader) {
+            self.layer.clear();
+            self.shader = Some(shader);
+        }
+        let inputs = self.layer.inputs(current);
+        let uniforms = uniforms(&inputs);
+        let normal_matrix = mat4_to_mat3(&inputs.model_matrix)
+            .transpose()
+            .try_inverse()
+            .unwrap_or(Mat3::identity());
+        let material = Material::for_shader(&shader);
+        self.layer.update(current, |inputs, direction| {
+            let fragment = Fragment {
+                material,
+                ..surface_fragment(inputs, &normal_matrix, direction, radius)
+            };
+            fragment_shader(&fragment, &uniforms, &shader)
+        });
+    }
+
+    // The last finished map, if it was shaded with `shader`
+    pub fn map(&self, shader: ShaderType) -> Option<(ShaderType, &LayerMap<Vec3>)> {
+        let map = self.layer.map()?;
+        (self.shader == Some(shader)).then_some((shader, map))
+    }
+
+    pub fn clear(&mut self) {
+        self.layer.clear();
+        self.shader = None;
+    }
+}
+
+// The surface point in `direction` as the rasterizer would have handed it
+// to the shader, with derivatives a texel across
+fn surface_fragment(
+    inputs: &SurfaceInputs,
+    normal_matrix: &Mat3,
+    direction: &Vec3,
+    radius: f32,
+) -> Fragment {
+    let position = direction * radius;
+    let east = Vec3::y().cross(direction);
+    let east = if east.magnitude() > 1e-6 {
+        east.normalize()
+    } else {
+        Vec3::x()
+    };
+    let north = direction.cross(&east);
+    // Narrower toward the poles, where the columns of the map crowd together
+    let latitude_circle = direction.xz().magnitude().max(1.0 / MAP_WIDTH as f32);
+    let across = 2.0 * PI * radius * latitude_circle / MAP_WIDTH as f32;
+    let down = PI * radius / MAP_HEIGHT as f32;
+
+    let mut fragment = Fragment::new(
+        Color::black(),
+        (normal_matrix * direction).normalize(),
+        position,
+    );
+    fragment.world_position = (inputs.model_matrix * position.push(1.0)).xyz();
+    fragment.dpdx = east * across;
+    fragment.dpdy = north * down;
+    fragment
+}
@@ -0,0 +1,44 @@
+use crate::framebuffer::Framebuffer;
+use std::mem;
+
+// A pair of framebuffers: `back` is the one being drawn into and `front`
+// holds the last completed frame. Everything that shows or sends frames
+// somewhere reads `front`, so it never sees one half drawn. Only `swap`
+// should replace it, besides it being lent out with `take_front` to be
+// presented, and put back with `return_front` before the next swap.
+pub struct Swapchain {
+    pub back: Framebuffer,
+    pub front: Framebuffer,
+}
+
+impl Swapchain {
+    pub fn new(width: usize, height: usize) -> Self {
+        Swapchain {
+            back: Framebuffer::new(width, height),
+            front: Framebuffer::new(width, height),
+        }
+    }
+
+    // Hands the finished back buffer over to the front. The old front comes
+    // back to be drawn over, which is free since both keep their storage.
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.back, &mut self.front);
+        if self.back.width != self.front.width || self.back.height != self.front.height {
+            self.back = Framebuffer::new(self.front.width, self.front.height);
+        }
+    }
+
+    pub fn take_front(&mut self) -> Framebuffer {
+        mem::replace(&mut self.front, Framebuffer::new(0, 0))
+    }
+
+    pub fn return_front(&mut self, front: Framebuffer) {
+        self.front = front;
+    }
+
+    // Starts drawing at a new size. The front keeps showing the last frame at
+    // the old size until the next swap.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        self.back = Framebuffer::new(width, height);
+    }
+}
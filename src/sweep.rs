@@ -0,0 +1,245 @@
+use crate::cubemap::{Cubemap, REFLECTION_SIZE};
+use crate::dither::Dither;
+use crate::error::{self, Error};
+use crate::font::GLYPH_HEIGHT;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::generator::GeneratedPlanet;
+use crate::light::Star;
+use crate::noise::NoiseRegistry;
+use crate::obj::Obj;
+use crate::png;
+use crate::projection::Projection;
+use crate::season::Orbit;
+use crate::shaders::{AuroraParams, GasGiantParams, OceanParams, ShaderType, ShadingModel};
+use crate::sky::{Sky, NEBULAE};
+use crate::tectonics::Continents;
+use crate::tonemap::ToneMapping;
+use crate::transform::Transform;
+use crate::weather::Weather;
+use crate::{
+    create_perspective_matrix, create_view_matrix, create_viewport_matrix, fill_light, render,
+    sun_direction, DepthMode, Uniforms, Viewport, PLANET_SPIN,
+};
+use nalgebra_glm::{Mat4, Vec3};
+use std::fs;
+use std::io;
+
+// Pixels per side of each image on the sheet
+const CELL_SIZE: usize = 240;
+
+// A shader parameter a sweep can vary
+#[derive(Clone, Copy, PartialEq)]
+pub enum Param {
+    BandFrequency,
+    StormThreshold,
+    AuroraIntensity,
+    AuroraLatitude,
+}
+
+const PARAMS: [Param; 4] = [
+    Param::BandFrequency,
+    Param::StormThreshold,
+    Param::AuroraIntensity,
+    Param::AuroraLatitude,
+];
+
+impl Param {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Param::BandFrequency => "band-frequency",
+            Param::StormThreshold => "storm-threshold",
+            Param::AuroraIntensity => "aurora-intensity",
+            Param::AuroraLatitude => "aurora-latitude",
+        }
+    }
+
+    // The planet the parameter shows up on
+    fn shader(&self) -> ShaderType {
+        match self {
+            Param::BandFrequency | Param::StormThreshold => ShaderType::GasGiant,
+            Param::AuroraIntensity | Param::AuroraLatitude => ShaderType::ColdGasGiant,
+        }
+    }
+
+    fn apply(&self, value: f32, gas_giant: &mut GasGiantParams, aurora: &mut AuroraParams) {
+        match self {
+            Param::BandFrequency => gas_giant.band_frequency = value,
+            Param::StormThreshold => gas_giant.storm_threshold = value,
+            Param::AuroraIntensity => aurora.intensity = value,
+            Param::AuroraLatitude => aurora.latitude = value,
+        }
+    }
+}
+
+// One direction of the grid: `steps` values evenly spaced from `from` to `to`
+#[derive(Clone, Copy)]
+pub struct Axis {
+    pub param: Param,
+    pub from: f32,
+    pub to: f32,
+    pub steps: usize,
+}
+
+impl Axis {
+    // Parses `name=from:to:steps`, e.g. `band-frequency=20:60:5`
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (name, range) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("expected name=from:to:steps, got '{}'", spec))?;
+        let param = PARAMS
+            .into_iter()
+            .find(|param| param.name() == name)
+            .ok_or_else(|| format!("unknown parameter '{}'", name))?;
+        let parts: Vec<&str> = range.split(':').collect();
+        let [from, to, steps] = parts[..] else {
+            return Err(format!("expected from:to:steps, got '{}'", range));
+        };
+        let number = |text: &str| {
+            text.parse::<f32>()
+                .map_err(|_| format!("'{}' is not a number", text))
+        };
+        let steps = steps
+            .parse::<usize>()
+            .ok()
+            .filter(|&steps| steps > 0)
+            .ok_or_else(|| format!("'{}' is not a step count", steps))?;
+        Ok(Axis {
+            param,
+            from: number(from)?,
+            to: number(to)?,
+            steps,
+        })
+    }
+
+    fn value(&self, step: usize) -> f32 {
+        if self.steps == 1 {
+            return self.from;
+        }
+        self.from + (self.to - self.from) * step as f32 / (self.steps - 1) as f32
+    }
+}
+
+// Renders the planet once for every combination of the axes' values, the
+// first axis across and the second (if any) down, and saves them side by side
+// with their values written on each as a PNG contact sheet. Every image is the
+// same frame of the same planet seen from the same place, so only the swept
+// parameters differ between them. `threads` and `reversed_z` are as
+// --threads and --reverse-z have them for the window.
+pub fn render_contact_sheet(
+    axes: &[Axis],
+    path: &str,
+    threads: usize,
+    reversed_z: bool,
+) -> error::Result<()> {
+    let shader = axes[0].param.shader();
+    if axes.iter().any(|axis| axis.param.shader() != shader) {
+        return Err(Error::io(path)(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "swept parameters must belong to the same planet",
+        )));
+    }
+    let columns = axes[0];
+    let rows = axes.get(1).copied();
+    let row_count = rows.map_or(1, |axis| axis.steps);
+
+    let obj = Obj::load("assets/models/sphere.obj")?;
+    let vertex_array = obj.get_vertex_array();
+    let continents = Continents::generate(5);
+    let generated = GeneratedPlanet::from_seed(0);
+    let noises = NoiseRegistry::new();
+    let sky = Sky::bake(&NEBULAE[0]);
+    let reflections = Cubemap::from_sky(&sky, REFLECTION_SIZE);
+    let weather = Weather::new(5);
+    let orbit = Orbit {
+        axial_tilt: PLANET_SPIN.axial_tilt,
+        ..Orbit::default()
+    };
+    // Close enough for the planet to fill most of each image
+    let eye = Vec3::new(0.0, 0.0, obj.bounding_radius() * 3.0);
+    let time = 0;
+    let sun = sun_direction(&orbit, time);
+
+    let mut sheet = Framebuffer::new(columns.steps * CELL_SIZE, row_count * CELL_SIZE);
+    let mut cell = Framebuffer::new(CELL_SIZE, CELL_SIZE);
+    let viewport = Viewport::full(CELL_SIZE as f32, CELL_SIZE as f32);
+    for row in 0..row_count {
+        for column in 0..columns.steps {
+            let mut gas_giant = GasGiantParams::default();
+            let mut aurora = AuroraParams::default();
+            let mut values = vec![(columns.param, columns.value(column))];
+            if let Some(axis) = rows {
+                values.push((axis.param, axis.value(row)));
+            }
+            for &(param, value) in &values {
+                param.apply(value, &mut gas_giant, &mut aurora);
+            }
+
+            let uniforms = Uniforms {
+                model_matrix: Transform::new(Vec3::zeros(), 1.0, PLANET_SPIN.orientation(time))
+                    .matrix(),
+                view_matrix: create_view_matrix(eye, Vec3::zeros(), Vec3::y()),
+                projection_matrix: create_perspective_matrix(
+                    &Projection::default(),
+                    viewport.width,
+                    viewport.height,
+                    reversed_z,
+                ),
+                viewport_matrix: create_viewport_matrix(&viewport, reversed_z),
+                time,
+                noise: noises.get(shader),
+                aurora,
+                gas_giant,
+                ocean: OceanParams::default(),
+                shading: ShadingModel::Phong,
+                // Same sun as the viewer's
+                light_dir: sun,
+                star: Star::YellowDwarf,
+                lights: &[fill_light(sun)],
+                // A lone planet has nothing to cast a shadow on it
+                light_space_matrix: Mat4::identity(),
+                shadow_map: None,
+                companion: None,
+                extra_octaves: 0,
+                ambient: sky.ambient,
+                environment: Some(&reflections),
+                particle_stride: 1,
+                weather: weather.state(),
+                clouds: None,
+                surface: None,
+                eye,
+                season: orbit.season(time),
+                continents: &continents,
+                #[cfg(feature = "physics")]
+                impacts: &[],
+                planet: &generated,
+                debug_view: None,
+                threads,
+                reversed_z,
+            };
+            cell.clear();
+            render(
+                &mut cell,
+                &uniforms,
+                &vertex_array,
+                &shader,
+                DepthMode::ZBuffer,
+            );
+            cell.resolve(&ToneMapping::default(), Dither::Off);
+            for (line, (param, value)) in values.iter().enumerate() {
+                let y = 4 + line * (GLYPH_HEIGHT + 3);
+                let label = format!("{} {:.3}", param.name(), value);
+                cell.draw_text(4, y as i32, &label, 1);
+            }
+
+            let whole = Rect {
+                x: 0,
+                y: 0,
+                width: CELL_SIZE,
+                height: CELL_SIZE,
+            };
+            sheet.blit(&cell, whole, column * CELL_SIZE, row * CELL_SIZE, None);
+        }
+    }
+
+    fs::write(path, png::encode(sheet.width, sheet.height, &sheet.buffer)).map_err(Error::io(path))
+}
@@ -0,0 +1,193 @@
+use crate::erosion::erode;
+use crate::noise::warp;
+use crate::sky::{map_direction, sample_map};
+use fastnoise_lite::{FastNoiseLite, FractalType, NoiseType};
+use nalgebra_glm::Vec3;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::{PI, TAU};
+
+const MAP_WIDTH: usize = 512;
+const MAP_HEIGHT: usize = 256;
+const PLATES: usize = 14;
+// Angular half-width of the mountains, trenches and ridges along a boundary
+const BOUNDARY_WIDTH: f32 = 0.07;
+const AREA_SAMPLES: usize = 4096;
+// Drainage area, in map cells, where a stream first shows and where it reaches
+// full width
+const RIVER_START: f32 = 120.0;
+const RIVER_FULL: f32 = 4000.0;
+
+pub const SEA_LEVEL: f32 = 0.05;
+
+// A rigid piece of crust. It spins about `motion` (an Euler pole scaled by the
+// angular speed), so its velocity at a point `p` is `motion × p`.
+struct Plate {
+    center: Vec3,
+    continental: bool,
+    elevation: f32,
+    motion: Vec3,
+}
+
+fn random_direction(rng: &mut StdRng) -> Vec3 {
+    let y: f32 = rng.gen_range(-1.0..1.0);
+    let phi = rng.gen_range(0.0..TAU);
+    let r = (1.0 - y * y).sqrt();
+    Vec3::new(r * phi.cos(), y, r * phi.sin())
+}
+
+// Terrain height from a plate simulation, baked into a latitude-longitude map
+// when the planet is created. Heights run roughly from -1 (trenches) to 1
+// (mountain ranges), with the continental shelves just above 0. Rivers are
+// then carved into it, and how much water each cell drains is kept to draw
+// them.
+pub struct Continents {
+    elevation: Vec<f32>,
+    rivers: Vec<f32>,
+}
+
+impl Continents {
+    pub fn generate(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut plates: Vec<Plate> = (0..PLATES)
+            .map(|_| Plate {
+                center: random_direction(&mut rng),
+                continental: false,
+                elevation: rng.gen_range(-0.55..-0.35),
+                motion: random_direction(&mut rng) * rng.gen_range(0.2..0.6),
+            })
+            .collect();
+
+        // Hand continents out to plates in random order until they cover
+        // about a third of the surface, skipping any plate so large it would
+        // overshoot. Plate areas are estimated from evenly spread samples.
+        let mut area = [0; PLATES];
+        for sample in 0..AREA_SAMPLES {
+            let y = 1.0 - (sample as f32 + 0.5) / AREA_SAMPLES as f32 * 2.0;
+            let phi = sample as f32 * PI * (3.0 - 5.0_f32.sqrt());
+            let r = (1.0 - y * y).sqrt();
+            let point = Vec3::new(r * phi.cos(), y, r * phi.sin());
+            area[nearest_plates(&plates, &point).0] += 1;
+        }
+        let mut order: Vec<usize> = (0..PLATES).collect();
+        order.shuffle(&mut rng);
+        let (target, slack) = (AREA_SAMPLES * 3 / 10, AREA_SAMPLES / 10);
+        let mut covered = 0;
+        for index in order {
+            if covered >= target {
+                break;
+            }
+            if covered + area[index] > target + slack {
+                continue;
+            }
+            covered += area[index];
+            plates[index].continental = true;
+            plates[index].elevation = rng.gen_range(0.15..0.3);
+        }
+
+        // Warps the lookup so plate edges wander instead of being great-circle
+        // arcs, and adds the small hills the plates alone don't have
+        let mut noise = FastNoiseLite::with_seed(seed as i32);
+        noise.set_noise_type(Some(NoiseType::OpenSimplex2));
+        noise.set_fractal_type(Some(FractalType::FBm));
+        noise.set_fractal_octaves(Some(4));
+        noise.set_frequency(Some(1.5));
+
+        let mut elevation = Vec::with_capacity(MAP_WIDTH * MAP_HEIGHT);
+        for y in 0..MAP_HEIGHT {
+            for x in 0..MAP_WIDTH {
+                let u = (x as f32 + 0.5) / MAP_WIDTH as f32;
+                let v = (y as f32 + 0.5) / MAP_HEIGHT as f32;
+                let d = map_direction(u, v);
+
+                let warped = warp(&noise, d, 0.4, 1).normalize();
+                let detail = noise.get_noise_3d(d.x * 2.5, d.y * 2.5, d.z * 2.5);
+
+                elevation.push(plate_elevation(&plates, &warped) + detail * 0.25);
+            }
+        }
+
+        let flow = erode(&mut elevation, MAP_WIDTH, MAP_HEIGHT, SEA_LEVEL, 2);
+        let rivers = flow
+            .iter()
+            .map(|&area| {
+                ((area.max(1.0) / RIVER_START).ln() / (RIVER_FULL / RIVER_START).ln())
+                    .clamp(0.0, 1.0)
+            })
+            .collect();
+
+        Continents { elevation, rivers }
+    }
+
+    pub fn elevation(&self, direction: &Vec3) -> f32 {
+        sample_map(&self.elevation, MAP_WIDTH, direction)
+    }
+
+    // How much of a river runs here, from 0 (none) to 1 (a major one)
+    pub fn river(&self, direction: &Vec3) -> f32 {
+        sample_map(&self.rivers, MAP_WIDTH, direction)
+    }
+}
+
+// Indices of the plate closest to `direction` and the runner-up
+fn nearest_plates(plates: &[Plate], direction: &Vec3) -> (usize, usize) {
+    let (mut own, mut other) = (0, 1);
+    if direction.dot(&plates[other].center) > direction.dot(&plates[own].center) {
+        (own, other) = (other, own);
+    }
+    for (index, plate) in plates.iter().enumerate().skip(2) {
+        let closeness = direction.dot(&plate.center);
+        if closeness > direction.dot(&plates[own].center) {
+            other = own;
+            own = index;
+        } else if closeness > direction.dot(&plates[other].center) {
+            other = index;
+        }
+    }
+    (own, other)
+}
+
+// Height at `direction` from the plate it lies on and the nearest neighbour:
+// collisions raise ranges or dig trenches, plates pulling apart leave
+// mid-ocean ridges and rift valleys
+fn plate_elevation(plates: &[Plate], direction: &Vec3) -> f32 {
+    let (own, other) = nearest_plates(plates, direction);
+    let (a, b) = (&plates[own], &plates[other]);
+
+    // Distance to the boundary, measured from the plane halfway between the
+    // two plates' centers, and that plane's normal pointing into `a`
+    let normal = (a.center - b.center).normalize();
+    let distance = direction.dot(&normal);
+    let falloff = (-(distance / BOUNDARY_WIDTH).powi(2)).exp();
+
+    // Ease the base heights into each other near the boundary so coasts slope
+    // instead of stepping
+    let blend = (0.5 + distance / BOUNDARY_WIDTH * 0.5).clamp(0.0, 1.0);
+    let base = b.elevation + (a.elevation - b.elevation) * blend;
+
+    // Positive when `a` moves toward `b`
+    let relative = a.motion.cross(direction) - b.motion.cross(direction);
+    let convergence = -relative.dot(&normal);
+
+    let boundary = if convergence > 0.0 {
+        match (a.continental, b.continental) {
+            // Two continents crumple into a high range
+            (true, true) => convergence * 1.1,
+            // The ocean dives under, pushing up a volcanic range inland...
+            (true, false) => convergence * 0.8,
+            // ...and leaving a trench on its own side
+            (false, true) => -convergence * 0.6,
+            // Ocean against ocean: the older plate sinks under a string of
+            // islands
+            (false, false) if own < other => -convergence * 0.5,
+            (false, false) => convergence * 0.5,
+        }
+    } else if a.continental {
+        convergence * 0.3
+    } else {
+        -convergence * 0.25
+    };
+
+    base + boundary * falloff
+}
@@ -0,0 +1,218 @@
+use crate::error::{self, Error};
+use crate::GRAVITY;
+use nalgebra_glm::Vec3;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// What gets written for each body. Velocities are in world units per frame and
+// energies per unit mass, with the potential measured from the planet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Field {
+    Position,
+    Velocity,
+    Energy,
+    Timing,
+}
+
+pub const ALL_FIELDS: [Field; 4] = [
+    Field::Position,
+    Field::Velocity,
+    Field::Energy,
+    Field::Timing,
+];
+
+impl Field {
+    pub fn parse(name: &str) -> Option<Field> {
+        match name {
+            "position" => Some(Field::Position),
+            "velocity" => Some(Field::Velocity),
+            "energy" => Some(Field::Energy),
+            "timing" => Some(Field::Timing),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    // One row per body per sampled frame, with a header
+    Csv,
+    // One object per sampled frame and line, holding every body
+    JsonLines,
+}
+
+struct Sample {
+    name: String,
+    position: Vec3,
+    velocity: Vec3,
+    kinetic: f32,
+    potential: f32,
+}
+
+// Per-frame log of where every body is and how fast it moves, for plotting
+// offline. The format follows the file extension: `.json` or `.jsonl` for JSON
+// Lines, anything else for CSV.
+pub struct Telemetry {
+    // For the errors writing to it
+    path: String,
+    writer: BufWriter<File>,
+    format: Format,
+    every: u32,
+    fields: Vec<Field>,
+    previous: HashMap<String, Vec3>,
+}
+
+impl Telemetry {
+    pub fn create(path: &str, every: u32, fields: &[Field]) -> error::Result<Self> {
+        let format = if path.ends_with(".json") || path.ends_with(".jsonl") {
+            Format::JsonLines
+        } else {
+            Format::Csv
+        };
+        let mut telemetry = Telemetry {
+            path: path.to_string(),
+            writer: BufWriter::new(File::create(path).map_err(Error::io(path))?),
+            format,
+            every: every.max(1),
+            fields: fields.to_vec(),
+            previous: HashMap::new(),
+        };
+
+        if format == Format::Csv {
+            let mut header = String::from("frame,body");
+            for field in &telemetry.fields {
+                header += match field {
+                    Field::Position => ",x,y,z",
+                    Field::Velocity => ",vx,vy,vz",
+                    Field::Energy => ",kinetic,potential",
+                    Field::Timing => ",frame_ms",
+                };
+            }
+            writeln!(telemetry.writer, "{header}").map_err(Error::io(path))?;
+        }
+
+        Ok(telemetry)
+    }
+
+    // Call once per frame with every body's name and position; velocities come
+    // from the difference to the previous call, so frames that aren't written
+    // still have to be passed in. A body seen for the first time has none yet.
+    pub fn record(
+        &mut self,
+        frame: u32,
+        frame_ms: f32,
+        planet_center: Vec3,
+        bodies: &[(String, Vec3)],
+    ) -> error::Result<()> {
+        let samples: Vec<Sample> = bodies
+            .iter()
+            .map(|(name, position)| {
+                let velocity = self
+                    .previous
+                    .get(name)
+                    .map_or(Vec3::zeros(), |previous| position - previous);
+                let distance = (position - planet_center).magnitude();
+                Sample {
+                    name: name.clone(),
+                    position: *position,
+                    velocity,
+                    kinetic: 0.5 * velocity.magnitude_squared(),
+                    potential: if distance > 0.0 {
+                        -GRAVITY / distance
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        self.previous = bodies.iter().cloned().collect();
+
+        if !frame.is_multiple_of(self.every) {
+            return Ok(());
+        }
+        match self.format {
+            Format::Csv => self.write_csv(frame, frame_ms, &samples),
+            Format::JsonLines => self.write_json(frame, frame_ms, &samples),
+        }
+        .map_err(Error::io(&self.path))
+    }
+
+    fn write_csv(&mut self, frame: u32, frame_ms: f32, samples: &[Sample]) -> io::Result<()> {
+        for sample in samples {
+            let mut row = format!("{frame},{}", csv_field(&sample.name));
+            for field in &self.fields {
+                row += &match field {
+                    Field::Position => {
+                        let p = sample.position;
+                        format!(",{},{},{}", p.x, p.y, p.z)
+                    }
+                    Field::Velocity => {
+                        let v = sample.velocity;
+                        format!(",{},{},{}", v.x, v.y, v.z)
+                    }
+                    Field::Energy => format!(",{},{}", sample.kinetic, sample.potential),
+                    Field::Timing => format!(",{frame_ms}"),
+                };
+            }
+            writeln!(self.writer, "{row}")?;
+        }
+        Ok(())
+    }
+
+    fn write_json(&mut self, frame: u32, frame_ms: f32, samples: &[Sample]) -> io::Result<()> {
+        let mut line = format!("{{\"frame\":{frame}");
+        if self.fields.contains(&Field::Timing) {
+            line += &format!(",\"frame_ms\":{frame_ms}");
+        }
+
+        let bodies: Vec<String> = samples
+            .iter()
+            .map(|sample| {
+                let mut body = format!("{{\"name\":{}", json_string(&sample.name));
+                for field in &self.fields {
+                    body += &match field {
+                        Field::Position => format!(",\"position\":{}", json_vec(&sample.position)),
+                        Field::Velocity => format!(",\"velocity\":{}", json_vec(&sample.velocity)),
+                        Field::Energy => format!(
+                            ",\"kinetic\":{},\"potential\":{}",
+                            sample.kinetic, sample.potential
+                        ),
+                        Field::Timing => String::new(),
+                    };
+                }
+                body + "}"
+            })
+            .collect();
+        line += &format!(",\"bodies\":[{}]}}", bodies.join(","));
+
+        writeln!(self.writer, "{line}")
+    }
+}
+
+// Quoted only when the name would otherwise break the row
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted += "\\\"",
+            '\\' => quoted += "\\\\",
+            '\n' => quoted += "\\n",
+            c if (c as u32) < 0x20 => quoted += &format!("\\u{:04x}", c as u32),
+            c => quoted.push(c),
+        }
+    }
+    quoted + "\""
+}
+
+fn json_vec(v: &Vec3) -> String {
+    format!("[{},{},{}]", v.x, v.y, v.z)
+}
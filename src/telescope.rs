@@ -0,0 +1,117 @@
+use crate::font::GLYPH_HEIGHT;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::locale::Locale;
+use nalgebra_glm::Vec3;
+use std::f32::consts::PI;
+
+// Narrowest and widest the field opens to, in degrees
+const MIN_FIELD: f32 = 0.05;
+const MAX_FIELD: f32 = 30.0;
+// The field is this many times the target's angular diameter
+const FRAMING: f32 = 4.0;
+// Share of the way the aim and the field close on the target every frame.
+// Easing toward it rather than snapping holds the target still in the
+// reticle as it orbits, and keeps the camera's own moves from jerking it.
+const STEADYING: f32 = 0.15;
+const RETICLE_COLOR: u32 = 0x60C060;
+
+// Angle a sphere spans seen from `eye`, all of it from inside
+pub fn angular_diameter(eye: Vec3, center: Vec3, radius: f32) -> f32 {
+    let distance = (center - eye).magnitude();
+    if distance <= radius {
+        return 2.0 * PI;
+    }
+    2.0 * (radius / distance).asin()
+}
+
+// Degrees with the digits that matter at that size
+pub fn degrees(angle: f32) -> String {
+    let degrees = angle.to_degrees();
+    if degrees >= 10.0 {
+        format!("{:.1}", degrees)
+    } else if degrees >= 0.1 {
+        format!("{:.2}", degrees)
+    } else {
+        format!("{:.4}", degrees)
+    }
+}
+
+// A narrow, steadied view along `direction` from the camera's eye, framing
+// whatever it's trained on
+pub struct Telescope {
+    pub direction: Vec3,
+    // Vertical field of view, in radians
+    pub field: f32,
+}
+
+impl Telescope {
+    // Starts out looking where the camera does, as wide as it sees
+    pub fn new(direction: Vec3, field: f32) -> Self {
+        Telescope {
+            direction: direction.normalize(),
+            field,
+        }
+    }
+
+    // Eases toward the sphere at `center` seen from `eye`
+    pub fn track(&mut self, eye: Vec3, center: Vec3, radius: f32) {
+        let Some(toward) = (center - eye).try_normalize(1e-6) else {
+            return;
+        };
+        self.direction = self.direction.lerp(&toward, STEADYING).normalize();
+        let wanted = (angular_diameter(eye, center, radius) * FRAMING)
+            .clamp(MIN_FIELD.to_radians(), MAX_FIELD.to_radians());
+        // Eased in ratio rather than in degrees, so zooming from wide to a
+        // tiny field doesn't rush through the narrow end
+        self.field *= (wanted / self.field).powf(STEADYING);
+    }
+}
+
+// Crosshairs with a gap in the middle, a circle the target sits in and the
+// field of view under it, over the whole of `area`
+pub fn draw_reticle(framebuffer: &mut Framebuffer, area: Rect, field: f32, locale: &Locale) {
+    framebuffer.set_current_color(RETICLE_COLOR);
+    let (center_x, center_y) = (area.x + area.width / 2, area.y + area.height / 2);
+    // A little wider than the target, which spans 1 / FRAMING of the field
+    let radius = area.height as f32 / FRAMING / 2.0 * 1.5;
+    let steps = (radius * 2.0 * PI) as usize;
+    for step in 0..steps {
+        let angle = step as f32 / steps as f32 * 2.0 * PI;
+        let x = center_x as f32 + angle.cos() * radius;
+        let y = center_y as f32 + angle.sin() * radius;
+        if x >= 0.0 && y >= 0.0 {
+            framebuffer.set_pixel(x as usize, y as usize);
+        }
+    }
+
+    let gap = radius as usize + 6;
+    let horizontal = |x: usize, width: usize| Rect {
+        x,
+        y: center_y,
+        width,
+        height: 1,
+    };
+    let vertical = |y: usize, height: usize| Rect {
+        x: center_x,
+        y,
+        width: 1,
+        height,
+    };
+    let left = center_x.saturating_sub(gap);
+    framebuffer.fill_rect(horizontal(area.x, left.saturating_sub(area.x)));
+    framebuffer.fill_rect(horizontal(
+        center_x + gap,
+        (area.x + area.width).saturating_sub(center_x + gap),
+    ));
+    let top = center_y.saturating_sub(gap);
+    framebuffer.fill_rect(vertical(area.y, top.saturating_sub(area.y)));
+    framebuffer.fill_rect(vertical(
+        center_y + gap,
+        (area.y + area.height).saturating_sub(center_y + gap),
+    ));
+
+    // Beside the lower crosshair, just clear of the circle
+    let text = locale.format("hud.telescope", &[&degrees(field)]);
+    let text_y = center_y as f32 + radius + GLYPH_HEIGHT as f32;
+    framebuffer.draw_text(center_x as i32 + 6, text_y as i32, &text, 1);
+}
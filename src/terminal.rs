@@ -0,0 +1,485 @@
+use crate::frontend::Frontend;
+use crate::input::InputFrame;
+use minifb::Key;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// How often the terminal is asked its size again, to follow it being resized
+const RESIZE_CHECK: Duration = Duration::from_secs(1);
+// A terminal sends a key each time it's typed and nothing when it's let go,
+// so a key counts as held for this long after it last came in. That bridges
+// the key repeat once it gets going, but not the pause before it starts.
+const HOLD: Duration = Duration::from_millis(120);
+// How long a status message stays along the bottom row
+const STATUS_TIME: Duration = Duration::from_secs(4);
+
+const LETTERS: [Key; 26] = [
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+];
+const DIGITS: [Key; 10] = [
+    Key::Key0,
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+// What Shift turns the digits into on a US layout, from 1 up
+const SHIFTED_DIGITS: &[u8] = b"!@#$%^&*(";
+
+// A key and the modifiers that came with it
+type Keystroke = (Key, Vec<Key>);
+
+// Shows frames in the terminal it's started from, two pixels to a character
+// cell as the upper half block in one 24-bit color over a background in the
+// other, so the viewer can be run over SSH. The presenter scales each frame
+// to the terminal's size, which makes the pixels about square. Keys are read
+// from the terminal in raw mode, Shift, Ctrl and Alt as far as it tells them
+// apart; there's no mouse. Ctrl+C quits like Escape, since raw mode doesn't
+// turn it into a signal. Status messages can't be printed over the picture,
+// so the latest shows along the bottom row for a while, and all of them are
+// printed once the terminal is put back.
+pub struct TerminalFrontend {
+    size: (usize, usize),
+    checked: Instant,
+    // What `stty -g` said before raw mode, to put back at the end
+    saved: Option<String>,
+    keystrokes: Receiver<Vec<u8>>,
+    pending: Pending,
+    // Keys held and when each last came in
+    held: Vec<(Key, Instant)>,
+    output: Vec<u8>,
+    open: bool,
+    status: Option<(String, Instant)>,
+    messages: Vec<String>,
+}
+
+impl TerminalFrontend {
+    pub fn open() -> io::Result<Self> {
+        let saved = stty(&["-g"]).ok().map(|state| state.trim().to_string());
+        stty(&["raw", "-echo"])?;
+        // The alternate screen, so the shell's scrollback is there again
+        // after, and no cursor
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+
+        let (sender, keystrokes) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let mut stdin = io::stdin();
+            while let Ok(count @ 1..) = stdin.read(&mut buffer) {
+                if sender.send(buffer[..count].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(TerminalFrontend {
+            size: terminal_size(),
+            checked: Instant::now(),
+            saved,
+            keystrokes,
+            pending: Pending::default(),
+            held: Vec::new(),
+            output: Vec::new(),
+            open: true,
+            status: None,
+            messages: Vec::new(),
+        })
+    }
+}
+
+impl Drop for TerminalFrontend {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        let restored = match &self.saved {
+            Some(state) => stty(&[state]),
+            None => stty(&["sane"]),
+        };
+        if let Err(err) = restored {
+            eprintln!("Couldn't restore the terminal: {}", err);
+        }
+        for message in &self.messages {
+            println!("{}", message);
+        }
+    }
+}
+
+// Runs stty on the terminal itself, which stdin might not be
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(File::open("/dev/tty")?)
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("stty {} failed", args.join(" "))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// Pixels across and down: one per column and two per row
+fn terminal_size() -> (usize, usize) {
+    let asked = stty(&["size"]).ok().and_then(|size| {
+        let (rows, columns) = size.trim().split_once(' ')?;
+        Some((rows.parse().ok()?, columns.parse().ok()?))
+    });
+    let from_env = |name: &str, default: usize| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+    let (rows, columns): (usize, usize) =
+        asked.unwrap_or_else(|| (from_env("LINES", 24), from_env("COLUMNS", 80)));
+    (columns.max(1), rows.max(1) * 2)
+}
+
+impl Frontend for TerminalFrontend {
+    fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    fn show(&mut self, frame: &[u32]) {
+        let (width, height) = self.size;
+        let output = &mut self.output;
+        output.clear();
+        let mut colors = (None, None);
+        for row in 0..height / 2 {
+            // Straight to the row, so a line that wraps can't throw the rest off
+            let _ = write!(output, "\x1b[{};1H", row + 1);
+            for x in 0..width {
+                let top = frame[row * 2 * width + x];
+                let bottom = frame[(row * 2 + 1) * width + x];
+                if colors.0 != Some(top) {
+                    let [_, r, g, b] = top.to_be_bytes();
+                    let _ = write!(output, "\x1b[38;2;{};{};{}m", r, g, b);
+                }
+                if colors.1 != Some(bottom) {
+                    let [_, r, g, b] = bottom.to_be_bytes();
+                    let _ = write!(output, "\x1b[48;2;{};{};{}m", r, g, b);
+                }
+                colors = (Some(top), Some(bottom));
+                output.extend("▀".as_bytes());
+            }
+        }
+        output.extend(b"\x1b[0m");
+        if let Some((message, _)) = self
+            .status
+            .as_ref()
+            .filter(|(_, at)| at.elapsed() < STATUS_TIME)
+        {
+            let line: String = message.chars().take(width).collect();
+            let _ = write!(output, "\x1b[{};1H{}\x1b[K", (height / 2).max(1), line);
+        }
+        let mut stdout = io::stdout().lock();
+        // Gone with the connection, over SSH
+        if stdout
+            .write_all(output)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            self.open = false;
+        }
+
+        if self.checked.elapsed() >= RESIZE_CHECK {
+            self.checked = Instant::now();
+            self.size = terminal_size();
+        }
+    }
+
+    fn poll(&mut self) -> InputFrame {
+        let now = Instant::now();
+        let keys = self.pending.keys(self.keystrokes.try_iter());
+        let mut pressed = Vec::new();
+        for (key, modifiers) in keys {
+            for key in modifiers.into_iter().chain([key]) {
+                let was_held = self
+                    .held
+                    .iter()
+                    .any(|&(held, at)| held == key && now - at < HOLD);
+                if !was_held && !pressed.contains(&key) {
+                    pressed.push(key);
+                }
+                self.held.retain(|&(held, _)| held != key);
+                self.held.push((key, now));
+            }
+        }
+        self.held.retain(|&(_, at)| now - at < HOLD);
+        let down = self.held.iter().map(|&(key, _)| key).collect();
+        InputFrame::from_keys(down, pressed)
+    }
+
+    fn status(&mut self, message: &str) {
+        self.status = Some((message.to_string(), Instant::now()));
+        self.messages.push(message.to_string());
+    }
+}
+
+// Bytes of a key that haven't all come in yet, and whether they were
+// already waiting at the last poll
+#[derive(Default)]
+struct Pending {
+    bytes: Vec<u8>,
+    stale: bool,
+}
+
+impl Pending {
+    // The keys in what was waiting and the `chunks` read since
+    fn keys(&mut self, chunks: impl IntoIterator<Item = Vec<u8>>) -> Vec<Keystroke> {
+        let mut bytes = std::mem::take(&mut self.bytes);
+        for chunk in chunks {
+            bytes.extend(chunk);
+        }
+        let (mut keys, rest) = parse(&bytes);
+        // A lone Escape looks like the start of a longer key until nothing
+        // more comes after it
+        if !rest.is_empty() && self.stale {
+            keys.push((Key::Escape, Vec::new()));
+        } else {
+            self.bytes = rest.to_vec();
+        }
+        self.stale = !self.bytes.is_empty();
+        keys
+    }
+}
+
+// The keys in `bytes` and what's left
+// at the end of a key that hasn't all come in yet
+fn parse(bytes: &[u8]) -> (Vec<Keystroke>, &[u8]) {
+    let mut keys = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let rest = &bytes[index..];
+        if rest[0] != 0x1B {
+            if let Some(key) = plain_key(rest[0]) {
+                keys.push(key);
+            }
+            index += 1;
+            continue;
+        }
+        match escape(rest) {
+            Some((length, key)) => {
+                keys.extend(key);
+                index += length;
+            }
+            None => return (keys, rest),
+        }
+    }
+    (keys, &[])
+}
+
+// A key that comes as one byte
+fn plain_key(byte: u8) -> Option<Keystroke> {
+    let shifted = |key| Some((key, vec![Key::LeftShift]));
+    match byte {
+        b'a'..=b'z' => Some((LETTERS[(byte - b'a') as usize], Vec::new())),
+        b'A'..=b'Z' => shifted(LETTERS[(byte - b'A') as usize]),
+        b'0'..=b'9' => Some((DIGITS[(byte - b'0') as usize], Vec::new())),
+        // Ctrl+C would be a signal anywhere but raw mode
+        0x03 => Some((Key::Escape, Vec::new())),
+        b'\t' => Some((Key::Tab, Vec::new())),
+        b'\r' | b'\n' => Some((Key::Enter, Vec::new())),
+        0x08 | 0x7F => Some((Key::Backspace, Vec::new())),
+        0x01..=0x1A => Some((LETTERS[(byte - 1) as usize], vec![Key::LeftCtrl])),
+        b' ' => Some((Key::Space, Vec::new())),
+        b'-' => Some((Key::Minus, Vec::new())),
+        b'_' => shifted(Key::Minus),
+        b'=' => Some((Key::Equal, Vec::new())),
+        b'+' => shifted(Key::Equal),
+        b'[' => Some((Key::LeftBracket, Vec::new())),
+        b'{' => shifted(Key::LeftBracket),
+        b']' => Some((Key::RightBracket, Vec::new())),
+        b'}' => shifted(Key::RightBracket),
+        b',' => Some((Key::Comma, Vec::new())),
+        b'<' => shifted(Key::Comma),
+        b'.' => Some((Key::Period, Vec::new())),
+        b'>' => shifted(Key::Period),
+        _ => {
+            let digit = SHIFTED_DIGITS.iter().position(|&c| c == byte)?;
+            shifted(DIGITS[digit + 1])
+        }
+    }
+}
+
+// A key that starts with Escape, and how many bytes it took, or None if it
+// may go on past the end of `bytes`. Sequences it doesn't know are skipped.
+fn escape(bytes: &[u8]) -> Option<(usize, Option<Keystroke>)> {
+    match bytes.get(1) {
+        None => None,
+        // Ctrl+F1..F4 and the like on some terminals, plain F1..F4 on most
+        Some(b'O') => {
+            let key = match *bytes.get(2)? {
+                b'P' => Key::F1,
+                b'Q' => Key::F2,
+                b'R' => Key::F3,
+                b'S' => Key::F4,
+                _ => return Some((3, None)),
+            };
+            Some((3, Some((key, Vec::new()))))
+        }
+        Some(b'[') => {
+            // Numbers split by semicolons up to the byte that ends it
+            let end = bytes[2..]
+                .iter()
+                .position(|&byte| (0x40..0x7F).contains(&byte))?
+                + 2;
+            let text = std::str::from_utf8(&bytes[2..end]).unwrap_or("");
+            let mut numbers = text.split(';').map(|number| number.parse::<u32>().ok());
+            let first = numbers.next().flatten();
+            let shift = u32::from(bytes[end] == b'Z');
+            let modifier = numbers.next().flatten().unwrap_or(1).saturating_sub(1) | shift;
+            let key = match (bytes[end], first) {
+                (b'A', _) => Some(Key::Up),
+                (b'B', _) => Some(Key::Down),
+                (b'C', _) => Some(Key::Right),
+                (b'D', _) => Some(Key::Left),
+                (b'P', _) => Some(Key::F1),
+                (b'Q', _) => Some(Key::F2),
+                (b'R', _) => Some(Key::F3),
+                (b'S', _) => Some(Key::F4),
+                // Shift+Tab, which comes without the modifier number
+                (b'Z', _) => Some(Key::Tab),
+                (b'~', Some(15)) => Some(Key::F5),
+                (b'~', Some(17)) => Some(Key::F6),
+                (b'~', Some(18)) => Some(Key::F7),
+                (b'~', Some(19)) => Some(Key::F8),
+                (b'~', Some(20)) => Some(Key::F9),
+                (b'~', Some(21)) => Some(Key::F10),
+                (b'~', Some(23)) => Some(Key::F11),
+                (b'~', Some(24)) => Some(Key::F12),
+                _ => None,
+            };
+            let modifiers = [(1, Key::LeftShift), (2, Key::LeftAlt), (4, Key::LeftCtrl)]
+                .into_iter()
+                .filter(|&(bit, _)| modifier & bit != 0)
+                .map(|(_, key)| key)
+                .collect();
+            Some((end + 1, key.map(|key| (key, modifiers))))
+        }
+        // Escape and then a key is that key with Alt
+        Some(&byte) => {
+            let key = plain_key(byte).map(|(key, mut modifiers)| {
+                modifiers.push(Key::LeftAlt);
+                (key, modifiers)
+            });
+            Some((2, key))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(bytes: &[u8]) -> Vec<Keystroke> {
+        let (keys, rest) = parse(bytes);
+        assert!(rest.is_empty(), "{:?} left over", rest);
+        keys
+    }
+
+    #[test]
+    fn plain_bytes() {
+        assert_eq!(plain_key(b'w'), Some((Key::W, vec![])));
+        assert_eq!(plain_key(b'W'), Some((Key::W, vec![Key::LeftShift])));
+        assert_eq!(plain_key(b'7'), Some((Key::Key7, vec![])));
+        assert_eq!(plain_key(b'&'), Some((Key::Key7, vec![Key::LeftShift])));
+        assert_eq!(plain_key(b'+'), Some((Key::Equal, vec![Key::LeftShift])));
+        assert_eq!(plain_key(0x07), Some((Key::G, vec![Key::LeftCtrl])));
+        assert_eq!(plain_key(b'\t'), Some((Key::Tab, vec![])));
+        assert_eq!(plain_key(0x03), Some((Key::Escape, vec![])));
+        assert_eq!(plain_key(b'~'), None);
+    }
+
+    #[test]
+    fn csi_keys_and_modifiers() {
+        assert_eq!(keys(b"\x1b[A"), [(Key::Up, vec![])]);
+        assert_eq!(keys(b"\x1b[1;2D"), [(Key::Left, vec![Key::LeftShift])]);
+        assert_eq!(keys(b"\x1b[1;3C"), [(Key::Right, vec![Key::LeftAlt])]);
+        assert_eq!(
+            keys(b"\x1b[24;6~"),
+            [(Key::F12, vec![Key::LeftShift, Key::LeftCtrl])]
+        );
+        assert_eq!(keys(b"\x1b[15~"), [(Key::F5, vec![])]);
+        assert_eq!(keys(b"\x1b[Z"), [(Key::Tab, vec![Key::LeftShift])]);
+        // Known sequences for keys the viewer doesn't use are swallowed whole
+        assert_eq!(keys(b"\x1b[3~x"), [(Key::X, vec![])]);
+    }
+
+    #[test]
+    fn ss3_function_keys() {
+        assert_eq!(keys(b"\x1bOP"), [(Key::F1, vec![])]);
+        assert_eq!(keys(b"\x1bOS"), [(Key::F4, vec![])]);
+        assert_eq!(keys(b"\x1bOH"), []);
+    }
+
+    #[test]
+    fn escape_then_a_key_is_alt() {
+        assert_eq!(keys(b"\x1bh"), [(Key::H, vec![Key::LeftAlt])]);
+        assert_eq!(
+            keys(b"\x1bG"),
+            [(Key::G, vec![Key::LeftShift, Key::LeftAlt])]
+        );
+    }
+
+    #[test]
+    fn keys_split_across_reads() {
+        let (keys, rest) = parse(b"a\x1b[1;");
+        assert_eq!(keys, [(Key::A, vec![])]);
+        assert_eq!(rest, b"\x1b[1;");
+
+        let mut pending = Pending::default();
+        assert_eq!(pending.keys([b"\x1b[1".to_vec()]), []);
+        assert_eq!(
+            pending.keys([b";5A".to_vec()]),
+            [(Key::Up, vec![Key::LeftCtrl])]
+        );
+    }
+
+    #[test]
+    fn lone_escape_once_nothing_follows() {
+        let mut pending = Pending::default();
+        assert_eq!(pending.keys([b"\x1b".to_vec()]), []);
+        assert_eq!(pending.keys([]), [(Key::Escape, vec![])]);
+        assert_eq!(pending.keys([]), []);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::color::Color;
+use crate::framebuffer::Framebuffer;
+use nalgebra_glm::Vec3;
+use std::ops::Range;
+
+// How texels are picked for a sample point
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    // The one texel the point falls in, blocky up close
+    Nearest,
+    // The four texels around the point, blended by distance
+    Bilinear,
+}
+
+impl Filter {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "nearest" => Some(Filter::Nearest),
+            "bilinear" => Some(Filter::Bilinear),
+            _ => None,
+        }
+    }
+}
+
+// One image in a texture's mip chain
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<u32>,
+}
+
+impl Level {
+    fn texel(&self, x: usize, y: usize) -> Color {
+        Color::from_hex(self.texels[y.min(self.height - 1) * self.width + x.min(self.width - 1)])
+    }
+
+    // Clamped at the edges
+    fn sample(&self, u: f32, v: f32, filter: Filter) -> Color {
+        match filter {
+            Filter::Nearest => {
+                let x = (u * self.width as f32).max(0.0) as usize;
+                let y = (v * self.height as f32).max(0.0) as usize;
+                self.texel(x, y)
+            }
+            Filter::Bilinear => {
+                let x = (u * self.width as f32 - 0.5).max(0.0);
+                let y = (v * self.height as f32 - 0.5).max(0.0);
+                let (x0, y0) = (x as usize, y as usize);
+                let (fx, fy) = (x.fract(), y.fract());
+
+                let top = self.texel(x0, y0).lerp(&self.texel(x0 + 1, y0), fx);
+                let bottom = self.texel(x0, y0 + 1).lerp(&self.texel(x0 + 1, y0 + 1), fx);
+                top.lerp(&bottom, fy)
+            }
+        }
+    }
+
+    // Half the size each way, every texel the average of the two by two it
+    // covers. An odd last row or column is averaged into the one before.
+    fn halved(&self) -> Level {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let rows = covered(y, height, self.height);
+            for x in 0..width {
+                let columns = covered(x, width, self.width);
+                let mut sum = Vec3::zeros();
+                let mut count = 0;
+                for source_y in rows.clone() {
+                    for source_x in columns.clone() {
+                        sum += self.texel(source_x, source_y).to_vec3();
+                        count += 1;
+                    }
+                }
+                texels.push(Color::from_vec3(sum / count as f32).to_hex());
+            }
+        }
+        Level {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+// Rows or columns of a level that `index` of the next one down averages
+fn covered(index: usize, halved: usize, size: usize) -> Range<usize> {
+    let end = if index + 1 == halved {
+        size
+    } else {
+        2 * index + 2
+    };
+    2 * index..end
+}
+
+// Read-only image sampled with normalized coordinates, where (0, 0) is the
+// top-left corner. Usually the result of an earlier render pass.
+//
+// With mipmaps it also keeps copies at half, a quarter and so on down to a
+// single texel. A sample that covers many texels of the full image reads
+// from the copy where it covers about one instead, so an image drawn small
+// doesn't shimmer as it moves, and the two nearest copies are blended so
+// there's no visible step where one takes over from the next.
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub filter: Filter,
+    levels: Vec<Level>,
+}
+
+impl Texture {
+    pub fn from_framebuffer(framebuffer: &Framebuffer, filter: Filter) -> Self {
+        Texture {
+            width: framebuffer.width,
+            height: framebuffer.height,
+            filter,
+            levels: vec![Level {
+                width: framebuffer.width,
+                height: framebuffer.height,
+                texels: framebuffer.buffer.clone(),
+            }],
+        }
+    }
+
+    // Builds the mip chain, replacing any already built
+    pub fn generate_mipmaps(&mut self) {
+        self.levels.truncate(1);
+        while let Some(last) = self.levels.last() {
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.halved();
+            self.levels.push(next);
+        }
+    }
+
+    // A sample where one screen pixel spans `footprint` texels of the
+    // full-size image, which grows the farther away or more shrunk the
+    // texture is drawn. Without mipmaps it always reads the full-size image.
+    pub fn sample(&self, u: f32, v: f32, footprint: f32) -> Color {
+        let lod = footprint
+            .max(1.0)
+            .log2()
+            .min((self.levels.len() - 1) as f32);
+        let level = lod as usize;
+        let near = self.levels[level].sample(u, v, self.filter);
+        let Some(far) = self.levels.get(level + 1) else {
+            return near;
+        };
+        match self.filter {
+            // Sticks to the closest copy rather than blending two
+            Filter::Nearest if lod.fract() < 0.5 => near,
+            Filter::Nearest => far.sample(u, v, self.filter),
+            Filter::Bilinear => near.lerp(&far.sample(u, v, self.filter), lod.fract()),
+        }
+    }
+}
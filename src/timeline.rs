@@ -0,0 +1,225 @@
+use crate::font::GLYPH_HEIGHT;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::locale::Locale;
+use std::collections::VecDeque;
+
+// Frames between snapshots, so a jump re-simulates at most this many
+const SNAPSHOT_EVERY: u32 = 120;
+// The oldest snapshot is let go past this many, about ten minutes at 60 fps
+const MAX_SNAPSHOTS: usize = 300;
+const MARGIN: usize = 8;
+const BAR_HEIGHT: usize = 6;
+// Pixels above and below the bar that still grab it
+const GRAB: f32 = 4.0;
+
+// The session so far, for going back to an earlier moment: the controls of
+// every frame, kept as runs since they rarely change, and a snapshot of the
+// simulation every SNAPSHOT_EVERY frames. Any frame in between is rebuilt by
+// stepping on from the snapshot before it with the controls it had. Going
+// back doesn't forget what came after, since running on from there with the
+// same controls comes out the same; the rest is only dropped once the run
+// is given different controls than it had the first time.
+//
+// The scrubber is a bar along the bottom of the screen, from the oldest
+// frame kept to the newest. Dragging along it picks a frame to jump to when
+// the button is let go, and held keys can step along it too.
+pub struct Timeline<S, C> {
+    snapshots: VecDeque<(u32, S)>,
+    // The frame each run of unchanged controls starts on
+    controls: VecDeque<(u32, C)>,
+    end: u32,
+    open: bool,
+    // Frame under the cursor while the bar is being dragged
+    scrubbing: Option<u32>,
+}
+
+impl<S: Clone, C: Clone + PartialEq> Timeline<S, C> {
+    pub fn new() -> Self {
+        Timeline {
+            snapshots: VecDeque::new(),
+            controls: VecDeque::new(),
+            end: 0,
+            open: false,
+            scrubbing: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.scrubbing = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn is_scrubbing(&self) -> bool {
+        self.scrubbing.is_some()
+    }
+
+    // The frame `frames` on from `current`, or back when it's negative, kept
+    // to the ones that can still be gone back to. None while the bar isn't
+    // showing or when that's `current` itself.
+    pub fn step_from(&self, current: u32, frames: i64) -> Option<u32> {
+        if !self.open || self.snapshots.is_empty() {
+            return None;
+        }
+        let first = self.snapshots[0].0;
+        let frame = (current as i64 + frames).clamp(first as i64, self.end as i64) as u32;
+        (frame != current).then_some(frame)
+    }
+
+    // Keeps `state`, which one step with `controls` brought to `frame`
+    pub fn record(&mut self, frame: u32, controls: C, state: &S) {
+        if !self.snapshots.is_empty() && frame <= self.end {
+            if self.controls_at(frame).as_ref() == Some(&controls) {
+                return;
+            }
+            self.forget_after(frame.saturating_sub(1));
+        }
+
+        if self
+            .controls
+            .back()
+            .is_none_or(|(_, last)| *last != controls)
+        {
+            self.controls.push_back((frame, controls));
+        }
+        if self.snapshots.is_empty() || frame.is_multiple_of(SNAPSHOT_EVERY) {
+            self.snapshots.push_back((frame, state.clone()));
+        }
+        if self.snapshots.len() > MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+            let first = self.snapshots[0].0;
+            while self
+                .controls
+                .get(1)
+                .is_some_and(|&(start, _)| start <= first)
+            {
+                self.controls.pop_front();
+            }
+        }
+        self.end = frame;
+    }
+
+    // The controls `frame` was stepped with
+    pub fn controls_at(&self, frame: u32) -> Option<C> {
+        self.controls
+            .iter()
+            .rev()
+            .find(|&&(start, _)| start <= frame)
+            .map(|(_, controls)| controls.clone())
+    }
+
+    // The simulation as it was on `frame`, rebuilt with `step`, or None if
+    // the frame was never recorded or has been let go
+    pub fn rewind(&self, frame: u32, mut step: impl FnMut(&mut S, &C)) -> Option<S> {
+        if frame > self.end {
+            return None;
+        }
+        let (start, snapshot) = self
+            .snapshots
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= frame)?;
+        let mut state = snapshot.clone();
+        for next in start + 1..=frame {
+            step(&mut state, &self.controls_at(next)?);
+        }
+        Some(state)
+    }
+
+    // Follows a drag along the bar, returning the frame it picked once the
+    // button is let go. `pressed` is true on the frame the button went down.
+    pub fn scrub(
+        &mut self,
+        framebuffer: &Framebuffer,
+        mouse: Option<(f32, f32)>,
+        mouse_down: bool,
+        pressed: bool,
+    ) -> Option<u32> {
+        let bar = self.bar(framebuffer)?;
+        if pressed {
+            let on_bar = |(x, y): (f32, f32)| {
+                x >= bar.x as f32
+                    && x < (bar.x + bar.width) as f32
+                    && (y - (bar.y + bar.height / 2) as f32).abs() <= bar.height as f32 + GRAB
+            };
+            if mouse.is_some_and(on_bar) {
+                self.scrubbing = Some(self.end);
+            }
+        }
+        self.scrubbing?;
+        if !mouse_down {
+            return self.scrubbing.take();
+        }
+        if let Some((x, _)) = mouse {
+            let first = self.snapshots[0].0;
+            let along = ((x - bar.x as f32) / bar.width as f32).clamp(0.0, 1.0);
+            self.scrubbing = Some(first + (along * (self.end - first) as f32).round() as u32);
+        }
+        None
+    }
+
+    // `current` is the frame on screen
+    pub fn draw(&self, framebuffer: &mut Framebuffer, current: u32, locale: &Locale) {
+        let Some(bar) = self.bar(framebuffer) else {
+            return;
+        };
+        let first = self.snapshots[0].0;
+        let span = (self.end - first).max(1) as f32;
+        let x_of =
+            |frame: u32| bar.x + ((frame - first) as f32 / span * (bar.width - 1) as f32) as usize;
+
+        framebuffer.set_current_color(0x404040);
+        framebuffer.fill_rect(bar);
+        framebuffer.set_current_color(0x6080C0);
+        framebuffer.fill_rect(Rect {
+            width: x_of(current.clamp(first, self.end)) - bar.x,
+            ..bar
+        });
+        let marker = |x: usize| Rect {
+            x: x.saturating_sub(1),
+            y: bar.y.saturating_sub(2),
+            width: 3,
+            height: bar.height + 4,
+        };
+        framebuffer.set_current_color(0xFFFFFF);
+        framebuffer.fill_rect(marker(x_of(current.clamp(first, self.end))));
+        if let Some(frame) = self.scrubbing {
+            framebuffer.set_current_color(0xFFD700);
+            framebuffer.fill_rect(marker(x_of(frame)));
+        }
+
+        let shown = self.scrubbing.unwrap_or(current);
+        let text = locale.format("timeline.status", &[&shown, &self.end]);
+        framebuffer.set_current_color(0xFFFFFF);
+        let text_y = bar.y.saturating_sub(GLYPH_HEIGHT + 6);
+        framebuffer.draw_text(bar.x as i32, text_y as i32, &text, 1);
+    }
+
+    // Where the bar goes, if it's showing
+    fn bar(&self, framebuffer: &Framebuffer) -> Option<Rect> {
+        if !self.open || self.snapshots.is_empty() {
+            return None;
+        }
+        Some(Rect {
+            x: MARGIN,
+            y: framebuffer.height.saturating_sub(MARGIN + BAR_HEIGHT),
+            width: framebuffer.width.saturating_sub(2 * MARGIN).max(1),
+            height: BAR_HEIGHT,
+        })
+    }
+
+    fn forget_after(&mut self, frame: u32) {
+        self.snapshots.retain(|&(start, _)| start <= frame);
+        while self
+            .controls
+            .back()
+            .is_some_and(|&(start, _)| start > frame)
+        {
+            self.controls.pop_back();
+        }
+        self.end = frame;
+    }
+}
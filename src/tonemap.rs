@@ -0,0 +1,48 @@
+use nalgebra_glm::Vec3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneOperator {
+    // Plain clamp at 1.0, what casting to u8 used to do
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+// Maps HDR radiance, where 1.0 is display white at exposure 1, back into [0, 1]
+#[derive(Clone, Copy)]
+pub struct ToneMapping {
+    pub operator: ToneOperator,
+    pub exposure: f32,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping {
+            operator: ToneOperator::Aces,
+            exposure: 1.0,
+        }
+    }
+}
+
+impl ToneMapping {
+    pub fn apply(&self, radiance: Vec3) -> Vec3 {
+        let exposed = radiance * self.exposure;
+        match self.operator {
+            ToneOperator::Clamp => exposed.map(|c| c.clamp(0.0, 1.0)),
+            ToneOperator::Reinhard => exposed.map(|c| c.max(0.0) / (1.0 + c.max(0.0))),
+            // Narkowicz's curve fit of the ACES filmic response
+            ToneOperator::Aces => exposed.map(|c| {
+                let c = c.max(0.0);
+                ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }),
+        }
+    }
+
+    pub fn next_operator(&mut self) {
+        self.operator = match self.operator {
+            ToneOperator::Clamp => ToneOperator::Reinhard,
+            ToneOperator::Reinhard => ToneOperator::Aces,
+            ToneOperator::Aces => ToneOperator::Clamp,
+        };
+    }
+}
@@ -0,0 +1,120 @@
+use crate::framebuffer::{DepthState, Framebuffer};
+use crate::picking::PickTarget;
+use crate::Uniforms;
+use nalgebra_glm::{Vec3, Vec4};
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+// HDR radiance added at the newest end of each trail, taken in turn by the
+// bodies in the order they're listed for picking
+const COLORS: [Vec3; 6] = [
+    Vec3::new(0.5, 0.35, 0.12),
+    Vec3::new(0.12, 0.35, 0.5),
+    Vec3::new(0.4, 0.15, 0.45),
+    Vec3::new(0.15, 0.45, 0.2),
+    Vec3::new(0.5, 0.2, 0.15),
+    Vec3::new(0.35, 0.35, 0.35),
+];
+
+// Where each body has been over the last `length` frames, drawn as a line
+// that glows brightest where it is now and fades out toward the oldest
+// point, so the eccentric orbits and the loops the moons trace around a
+// moving planet can be seen whole. Only frames the simulation moves on are
+// kept, so a paused scene keeps its trails, and a jump on the timeline
+// starts them over.
+pub struct Trails {
+    pub enabled: bool,
+    pub length: usize,
+    trails: Vec<(Cow<'static, str>, VecDeque<Vec3>)>,
+    time: Option<u32>,
+}
+
+impl Trails {
+    pub fn new(length: usize) -> Self {
+        Trails {
+            enabled: false,
+            length,
+            trails: Vec::new(),
+            time: None,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.trails.clear();
+        self.time = None;
+    }
+
+    // Called once a frame with the bodies where they are at `time`. The
+    // trails of bodies no longer shown are dropped; a picked belt rock
+    // isn't given one.
+    pub fn update(&mut self, time: u32, targets: &[PickTarget]) {
+        if !self.enabled || self.time == Some(time) {
+            return;
+        }
+        if self.time.is_some_and(|previous| previous + 1 != time) {
+            self.trails.clear();
+        }
+        self.time = Some(time);
+
+        let bodies = targets.iter().filter(|target| target.name != "Belt rock");
+        self.trails
+            .retain(|(name, _)| bodies.clone().any(|target| target.name == *name));
+        for target in bodies {
+            let index = match self
+                .trails
+                .iter()
+                .position(|(name, _)| *name == target.name)
+            {
+                Some(index) => index,
+                None => {
+                    self.trails.push((target.name.clone(), VecDeque::new()));
+                    self.trails.len() - 1
+                }
+            };
+            let points = &mut self.trails[index].1;
+            points.push_back(target.center);
+            while points.len() > self.length {
+                points.pop_front();
+            }
+        }
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, uniforms: &Uniforms) {
+        if !self.enabled {
+            return;
+        }
+        let transform =
+            uniforms.viewport_matrix * uniforms.projection_matrix * uniforms.view_matrix;
+        let project = |point: Vec3| {
+            let screen = transform * Vec4::new(point.x, point.y, point.z, 1.0);
+            (screen.w > 0.0).then(|| screen.xyz() / screen.w)
+        };
+        let previous = framebuffer.depth_state();
+        framebuffer.set_depth_state(DepthState::TRANSLUCENT);
+        for (index, (_, points)) in self.trails.iter().enumerate() {
+            let color = COLORS[index % COLORS.len()];
+            let segments = points.len().saturating_sub(1);
+            for (age, (&from, &to)) in points.iter().zip(points.iter().skip(1)).enumerate() {
+                let fade = (age + 1) as f32 / segments as f32;
+                let (Some(start), Some(end)) = (project(from), project(to)) else {
+                    continue;
+                };
+                // A sample per pixel along the segment, leaving off its end,
+                // where the next one starts, so the joints aren't doubled
+                let length = (end.x - start.x).abs().max((end.y - start.y).abs());
+                let steps = (length.ceil() as usize).max(1);
+                for step in 0..steps {
+                    let Some(point) = project(from.lerp(&to, step as f32 / steps as f32)) else {
+                        continue;
+                    };
+                    if point.x >= 0.0 && point.y >= 0.0 {
+                        let (x, y) = (point.x as usize, point.y as usize);
+                        framebuffer.add_radiance(x, y, point.z, color * fade);
+                    }
+                }
+            }
+        }
+        framebuffer.set_depth_state(previous);
+    }
+}
@@ -0,0 +1,90 @@
+use nalgebra_glm::{quat_angle_axis, quat_to_mat4, scaling, translation, Mat4, Quat, Vec3};
+
+// Where a node sits relative to its parent: scaled first, then rotated, then
+// moved
+#[derive(Clone, Copy)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: f32,
+}
+
+impl Transform {
+    pub fn new(translation: Vec3, scale: f32, rotation: Quat) -> Self {
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    // Euler angles applied about X, then Y, then Z
+    pub fn from_euler(translation: Vec3, scale: f32, angles: Vec3) -> Self {
+        let rotation = quat_angle_axis(angles.z, &Vec3::z())
+            * quat_angle_axis(angles.y, &Vec3::y())
+            * quat_angle_axis(angles.x, &Vec3::x());
+        Transform::new(translation, scale, rotation)
+    }
+
+    // Moved but neither turned nor scaled, for the frames bodies orbit in
+    pub fn at(translation: Vec3) -> Self {
+        Transform::new(translation, 1.0, Quat::identity())
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        translation(&self.translation)
+            * quat_to_mat4(&self.rotation)
+            * scaling(&Vec3::repeat(self.scale))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct NodeId(usize);
+
+struct Node {
+    parent: Option<NodeId>,
+    local: Transform,
+}
+
+// Bodies placed relative to each other. A node can only be added under one
+// that already exists, so going through them in order always meets parents
+// before their children and one pass fills the world matrix cache.
+#[derive(Default)]
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    world: Vec<Mat4>,
+}
+
+impl SceneGraph {
+    pub fn add(&mut self, parent: Option<NodeId>, local: Transform) -> NodeId {
+        self.nodes.push(Node { parent, local });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.world.clear();
+    }
+
+    // Composes every node's world matrix from the root down
+    pub fn update(&mut self) {
+        self.world.clear();
+        for node in &self.nodes {
+            let local = node.local.matrix();
+            let world = match node.parent {
+                Some(NodeId(parent)) => self.world[parent] * local,
+                None => local,
+            };
+            self.world.push(world);
+        }
+    }
+
+    // As of the last `update`
+    pub fn world(&self, node: NodeId) -> Mat4 {
+        self.world[node.0]
+    }
+
+    pub fn world_position(&self, node: NodeId) -> Vec3 {
+        self.world[node.0].column(3).xyz()
+    }
+}
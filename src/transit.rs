@@ -0,0 +1,168 @@
+use crate::font::GLYPH_HEIGHT;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::locale::Locale;
+use crate::picking::PickTarget;
+use nalgebra_glm::Vec3;
+use std::collections::VecDeque;
+
+// How much dimmer the star's limb is than its middle, by the linear law
+const LIMB_DARKENING: f32 = 0.6;
+// Samples across the star's radius when adding up its light
+const SAMPLES: i32 = 48;
+// One pixel of graph per frame
+const GRAPH_WIDTH: usize = 240;
+const GRAPH_HEIGHT: usize = 80;
+const MARGIN: usize = 8;
+
+// Where to watch an orbit with `normal` around `center` from exactly edge
+// on, `distance` out, and the up that keeps the orbit level on screen
+pub fn edge_on(center: Vec3, normal: Vec3, distance: f32) -> (Vec3, Vec3) {
+    let side = normal
+        .cross(&Vec3::x())
+        .try_normalize(1e-3)
+        .unwrap_or_else(|| normal.cross(&Vec3::z()).normalize());
+    (center + side * distance, normal)
+}
+
+// The share of the light of `star` that gets to `eye` past the `bodies` in
+// front of it, 1 with nothing in the way. The star is summed over its disk
+// as seen from `eye`, brighter in the middle than at the limb, so a transit
+// dips gently at its edges and deepest as the body crosses the middle.
+pub fn brightness<'a>(
+    eye: Vec3,
+    star: &PickTarget,
+    bodies: impl Iterator<Item = &'a PickTarget>,
+) -> f32 {
+    let to_star = star.center - eye;
+    let distance = to_star.magnitude();
+    if distance <= star.radius {
+        return 1.0;
+    }
+    let forward = to_star / distance;
+    let right = forward
+        .cross(&Vec3::y())
+        .try_normalize(1e-6)
+        .unwrap_or_else(|| forward.cross(&Vec3::x()).normalize());
+    let up = right.cross(&forward);
+    // Each disk on the plane a unit in front of the eye, as its center and
+    // radius there
+    let disk = |center: Vec3, radius: f32| {
+        let offset = center - eye;
+        let along = offset.dot(&forward);
+        (
+            offset.dot(&right) / along,
+            offset.dot(&up) / along,
+            radius / along,
+        )
+    };
+    let (star_x, star_y, star_radius) = disk(star.center, star.radius);
+    let occluders: Vec<_> = bodies
+        .filter(|body| {
+            let along = (body.center - eye).dot(&forward);
+            along > 0.0 && along < distance
+        })
+        .map(|body| disk(body.center, body.radius))
+        .collect();
+
+    let (mut total, mut seen) = (0.0, 0.0);
+    for row in -SAMPLES..=SAMPLES {
+        for column in -SAMPLES..=SAMPLES {
+            let (u, v) = (column as f32 / SAMPLES as f32, row as f32 / SAMPLES as f32);
+            let squared = u * u + v * v;
+            if squared > 1.0 {
+                continue;
+            }
+            let intensity = 1.0 - LIMB_DARKENING * (1.0 - (1.0 - squared).sqrt());
+            total += intensity;
+            let (x, y) = (star_x + u * star_radius, star_y + v * star_radius);
+            let hidden = occluders
+                .iter()
+                .any(|&(cx, cy, radius)| (x - cx).hypot(y - cy) < radius);
+            if !hidden {
+                seen += intensity;
+            }
+        }
+    }
+    seen / total
+}
+
+// The latest frames' brightness, drawn as a graph in the top-right corner
+pub struct LightCurve {
+    samples: VecDeque<f32>,
+}
+
+impl LightCurve {
+    pub fn new() -> Self {
+        LightCurve {
+            samples: VecDeque::with_capacity(GRAPH_WIDTH),
+        }
+    }
+
+    pub fn push(&mut self, brightness: f32) {
+        if self.samples.len() == GRAPH_WIDTH {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(brightness);
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn draw(&self, framebuffer: &mut Framebuffer, locale: &Locale) {
+        let Some(&latest) = self.samples.back() else {
+            return;
+        };
+        let panel = Rect {
+            x: framebuffer.width.saturating_sub(GRAPH_WIDTH + MARGIN),
+            y: MARGIN + GLYPH_HEIGHT + 4,
+            width: GRAPH_WIDTH,
+            height: GRAPH_HEIGHT,
+        };
+        framebuffer.set_current_color(0x101820);
+        framebuffer.fill_rect(panel);
+
+        // From a little under the deepest dip to full brightness, so a
+        // shallow transit still fills the graph
+        let lowest = self.samples.iter().copied().fold(1.0, f32::min);
+        let floor = (lowest - (1.0 - lowest) * 0.2).min(0.995);
+        let y_of = |value: f32| {
+            let along = ((value - floor) / (1.0 - floor)).clamp(0.0, 1.0);
+            panel.y + ((1.0 - along) * (panel.height - 1) as f32).round() as usize
+        };
+
+        // Full brightness, then the curve with each step joined to the last
+        framebuffer.set_current_color(0x405060);
+        framebuffer.fill_rect(Rect {
+            y: y_of(1.0),
+            height: 1,
+            ..panel
+        });
+        framebuffer.set_current_color(0xFFD060);
+        let mut previous = None;
+        for (index, &value) in self.samples.iter().enumerate() {
+            let y = y_of(value);
+            let (top, bottom) = previous.map_or((y, y), |last: usize| (y.min(last), y.max(last)));
+            framebuffer.fill_rect(Rect {
+                x: panel.x + index,
+                y: top,
+                width: 1,
+                height: bottom - top + 1,
+            });
+            previous = Some(y);
+        }
+
+        let percent = |value: f32| format!("{:.2}", value * 100.0);
+        framebuffer.set_current_color(0xFFFFFF);
+        let title = locale.format("hud.light_curve", &[&percent(latest)]);
+        framebuffer.draw_text(panel.x as i32, MARGIN as i32, &title, 1);
+        framebuffer.set_current_color(0x8090A0);
+        let bottom = (panel.y + panel.height - GLYPH_HEIGHT - 2) as i32;
+        framebuffer.draw_text(
+            panel.x as i32 + 2,
+            bottom,
+            &format!("{}%", percent(floor)),
+            1,
+        );
+    }
+}
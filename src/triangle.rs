@@ -1,76 +1,125 @@
-use crate::color::Color;
-use crate::fragment::Fragment;
+use crate::fragment::FragmentBatch;
+use crate::framebuffer::Scissor;
 use crate::vertex::Vertex;
-use nalgebra_glm::{dot, Vec3};
-
-pub fn triangle(v1: &Vertex, v2: &Vertex, v3: &Vertex) -> Vec<Fragment> {
-    let mut fragments = Vec::new();
-    let (a, b, c) = (
-        v1.transformed_position,
-        v2.transformed_position,
-        v3.transformed_position,
-    );
-
-    let (min_x, min_y, max_x, max_y) = calculate_bounding_box(&a, &b, &c);
-
-    let light_dir = Vec3::new(0.0, 0.0, 1.0);
-
-    let triangle_area = edge_function(&a, &b, &c);
-
-    for y in min_y..=max_y {
-        for x in min_x..=max_x {
-            let point = Vec3::new(x as f32 + 0.5, y as f32 + 0.5, 0.0);
-
-            let (w1, w2, w3) = barycentric_coordinates(&point, &a, &b, &c, triangle_area);
-
-            if w1 >= 0.0 && w1 <= 1.0 && w2 >= 0.0 && w2 <= 1.0 && w3 >= 0.0 && w3 <= 1.0 {
-                let normal = v1.transformed_normal * w1
-                    + v2.transformed_normal * w2
-                    + v3.transformed_normal * w3;
-                let normal = normal.normalize();
-
-                let intensity = dot(&normal, &light_dir).max(0.0);
-
-                let base_color = Color::new(100, 100, 100);
-                let lit_color = base_color * intensity;
-
-                let depth = a.z * w1 + b.z * w2 + c.z * w3;
-
-                let vertex_position = v1.position * w1 + v2.position * w2 + v3.position * w3;
-
-                fragments.push(Fragment::new(
-                    x as f32,
-                    y as f32,
-                    lit_color,
-                    depth,
-                    normal,
-                    intensity,
-                    vertex_position,
-                ));
+use raster::{rasterize, Point};
+
+// Rasterizes the triangle into `batch`, handing it to `shade` each time it
+// fills up and once more at the end with whatever is left, so no fragment is
+// ever stored for longer than one batch
+pub fn triangle(
+    v1: &Vertex,
+    v2: &Vertex,
+    v3: &Vertex,
+    scissor: &Scissor,
+    batch: &mut FragmentBatch,
+    mut shade: impl FnMut(&mut FragmentBatch),
+) {
+    let [a, b, c] = [v1, v2, v3].map(|v| {
+        let p = v.screen_position;
+        Point::new(p.x, p.y, p.z)
+    });
+
+    // What's straight across the triangle in the world is straight across
+    // it on screen only after dividing by w, so the screen weights are
+    // scaled by each corner's 1/w and renormalized before attributes are
+    // interpolated with them. Depth was divided already and keeps the
+    // screen weights.
+    let inverse_w = [v1, v2, v3].map(|v| 1.0 / v.clip_position.w);
+    let perspective = |(w1, w2, w3): (f32, f32, f32)| {
+        let (p1, p2, p3) = (w1 * inverse_w[0], w2 * inverse_w[1], w3 * inverse_w[2]);
+        let sum = p1 + p2 + p3;
+        // Far enough outside the triangle to be past its horizon, where
+        // only the lanes for derivatives go
+        if sum.abs() < f32::EPSILON {
+            return (w1, w2, w3);
+        }
+        (p1 / sum, p2 / sum, p3 / sum)
+    };
+
+    // Interpolated over every lane of a touched quad, including ones outside
+    // the triangle, so covered pixels can take finite differences against
+    // their neighbours
+    let position_at =
+        |(w1, w2, w3): (f32, f32, f32)| v1.position * w1 + v2.position * w2 + v3.position * w3;
+
+    let mut flush = |batch: &mut FragmentBatch| {
+        interpolate(batch, [v1, v2, v3], [a.z, b.z, c.z]);
+        shade(batch);
+        batch.len = 0;
+    };
+
+    batch.len = 0;
+    rasterize(a, b, c, scissor, |quad| {
+        let weights = quad.weights.map(perspective);
+        let positions = weights.map(position_at);
+        let dpdx = positions[1] - positions[0];
+        let dpdy = positions[2] - positions[0];
+
+        for (lane, &(w1, w2, w3)) in weights.iter().enumerate() {
+            if !quad.covered[lane] {
+                continue;
+            }
+            let (x, y) = quad.lane_position(lane);
+            let (s1, s2, s3) = quad.weights[lane];
+            let index = batch.len;
+            batch.x[index] = x as usize;
+            batch.y[index] = y as usize;
+            batch.screen_weights[0][index] = s1;
+            batch.screen_weights[1][index] = s2;
+            batch.screen_weights[2][index] = s3;
+            batch.weights[0][index] = w1;
+            batch.weights[1][index] = w2;
+            batch.weights[2][index] = w3;
+            batch.vertex_position.set(index, positions[lane]);
+            batch.dpdx.set(index, dpdx);
+            batch.dpdy.set(index, dpdy);
+            batch.len += 1;
+            if batch.is_full() {
+                flush(batch);
             }
         }
+    });
+    if batch.len > 0 {
+        flush(batch);
     }
-
-    fragments
 }
 
-fn calculate_bounding_box(v1: &Vec3, v2: &Vec3, v3: &Vec3) -> (i32, i32, i32, i32) {
-    let min_x = v1.x.min(v2.x).min(v3.x).floor() as i32;
-    let min_y = v1.y.min(v2.y).min(v3.y).floor() as i32;
-    let max_x = v1.x.max(v2.x).max(v3.x).ceil() as i32;
-    let max_y = v1.y.max(v2.y).max(v3.y).ceil() as i32;
-
-    (min_x, min_y, max_x, max_y)
-}
+// Fills in every attribute of the batch's fragments from the corners'
+fn interpolate(batch: &mut FragmentBatch, [v1, v2, v3]: [&Vertex; 3], depths: [f32; 3]) {
+    let len = batch.len;
+    let weights = &batch.weights;
 
-fn barycentric_coordinates(p: &Vec3, a: &Vec3, b: &Vec3, c: &Vec3, area: f32) -> (f32, f32, f32) {
-    let w1 = edge_function(b, c, p) / area;
-    let w2 = edge_function(c, a, p) / area;
-    let w3 = edge_function(a, b, p) / area;
+    let [s1, s2, s3] = batch.screen_weights.each_ref().map(|s| &s[..len]);
+    let lanes = batch.depth[..len].iter_mut().zip(s1).zip(s2).zip(s3);
+    for (((depth, s1), s2), s3) in lanes {
+        *depth = depths[0] * s1 + depths[1] * s2 + depths[2] * s3;
+    }
 
-    (w1, w2, w3)
-}
+    let corners = |attribute: fn(&Vertex) -> _| [v1, v2, v3].map(attribute);
+    batch
+        .normal
+        .interpolate(corners(|v| v.transformed_normal), weights, len);
+    batch.normal.normalize(len);
+    batch
+        .world_position
+        .interpolate(corners(|v| v.world_position), weights, len);
+    batch
+        .radiance
+        .interpolate(corners(|v| v.radiance), weights, len);
+
+    let [w1, w2, w3] = weights.each_ref().map(|w| &w[..len]);
+    for (axis, column) in batch.tex_coords.iter_mut().enumerate() {
+        let [a, b, c] = [v1, v2, v3].map(|v| v.tex_coords[axis]);
+        let lanes = column[..len].iter_mut().zip(w1).zip(w2).zip(w3);
+        for (((value, w1), w2), w3) in lanes {
+            *value = a * w1 + b * w2 + c * w3;
+        }
+    }
 
-fn edge_function(a: &Vec3, b: &Vec3, c: &Vec3) -> f32 {
-    (c.x - a.x) * (b.y - a.y) - (c.y - a.y) * (b.x - a.x)
+    // Colors are bytes that saturate as they're added, which doesn't split
+    // into lanes, so they're blended one fragment at a time as before
+    let lanes = batch.color[..len].iter_mut().zip(w1).zip(w2).zip(w3);
+    for (((color, &w1), &w2), &w3) in lanes {
+        *color = v1.color * w1 + v2.color * w2 + v3.color * w3;
+    }
 }
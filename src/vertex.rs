@@ -1,5 +1,5 @@
 use crate::color::Color;
-use nalgebra_glm::{Vec2, Vec3};
+use nalgebra_glm::{Vec2, Vec3, Vec4};
 
 #[derive(Clone, Debug)]
 pub struct Vertex {
@@ -7,8 +7,15 @@ pub struct Vertex {
     pub normal: Vec3,
     pub tex_coords: Vec2,
     pub color: Color,
-    pub transformed_position: Vec3,
+    // Where the vertex shader put it, before the perspective divide
+    pub clip_position: Vec4,
+    // Framebuffer pixel and depth, once it's been clipped, divided and
+    // mapped through the viewport
+    pub screen_position: Vec3,
     pub transformed_normal: Vec3,
+    pub world_position: Vec3,
+    // What the vertex stage lit it to under Gouraud shading, black otherwise
+    pub radiance: Vec3,
 }
 
 impl Vertex {
@@ -18,8 +25,27 @@ impl Vertex {
             normal,
             tex_coords,
             color: Color::black(),
-            transformed_position: position,
+            clip_position: position.push(1.0),
+            screen_position: position,
             transformed_normal: normal,
+            world_position: position,
+            radiance: Vec3::zeros(),
+        }
+    }
+
+    // `t` of the way from `self` to `other` in everything it carries, for
+    // the corners clipping cuts into a triangle's edges
+    pub fn lerp(&self, other: &Vertex, t: f32) -> Vertex {
+        Vertex {
+            position: self.position.lerp(&other.position, t),
+            normal: self.normal.lerp(&other.normal, t),
+            tex_coords: self.tex_coords.lerp(&other.tex_coords, t),
+            color: self.color * (1.0 - t) + other.color * t,
+            clip_position: self.clip_position.lerp(&other.clip_position, t),
+            screen_position: self.screen_position.lerp(&other.screen_position, t),
+            transformed_normal: self.transformed_normal.lerp(&other.transformed_normal, t),
+            world_position: self.world_position.lerp(&other.world_position, t),
+            radiance: self.radiance.lerp(&other.radiance, t),
         }
     }
 }
@@ -31,8 +57,11 @@ impl Default for Vertex {
             normal: Vec3::new(0.0, 1.0, 0.0),
             tex_coords: Vec2::new(0.0, 0.0),
             color: Color::black(),
-            transformed_position: Vec3::new(0.0, 0.0, 0.0),
+            clip_position: Vec4::new(0.0, 0.0, 0.0, 1.0),
+            screen_position: Vec3::new(0.0, 0.0, 0.0),
             transformed_normal: Vec3::new(0.0, 1.0, 0.0),
+            world_position: Vec3::new(0.0, 0.0, 0.0),
+            radiance: Vec3::new(0.0, 0.0, 0.0),
         }
     }
 }
@@ -0,0 +1,3213 @@
+// The interactive viewer: the camera, the simulation and the HUD around the
+// renderer in lib.rs, fed a frame of input at a time by whichever frontend
+// shows what it draws
+
+use crate::accumulation::{jitter_projection, Accumulation};
+use crate::bookmarks::{Bookmarks, SLOTS};
+use crate::budget::FrameBudget;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::cubemap::{Cubemap, REFLECTION_SIZE};
+use crate::dither::Dither;
+use crate::doppler::Doppler;
+use crate::error::{Error, Result};
+use crate::exposure::{ExposurePreset, EyeAdaptation};
+use crate::fog::Fog;
+#[cfg(feature = "frame-server")]
+use crate::frame_server::FrameServer;
+use crate::framebuffer::{Framebuffer, Rect};
+use crate::gallery::Gallery;
+use crate::generator::GeneratedPlanet;
+use crate::horizon::Patches;
+use crate::impostor::DISK_PIXELS;
+use crate::input::InputFrame;
+#[cfg(feature = "recording")]
+use crate::input::{InputPlayback, InputRecorder};
+use crate::instance::Instance;
+use crate::kepler::KeplerOrbit;
+use crate::kitbash::spacecraft;
+use crate::label::draw_label;
+use crate::layer::BakedLayer;
+use crate::light::{Binary, Companion, Light};
+use crate::lighttime::LightTravel;
+use crate::locale::Locale;
+use crate::lod::LodChain;
+use crate::material::Material;
+use crate::measure::Measurement;
+use crate::menu::ShaderMenu;
+use crate::motion_blur::MotionBlur;
+#[cfg(feature = "physics")]
+use crate::n_body::NBody;
+use crate::obj::Obj;
+use crate::occlusion::AmbientOcclusion;
+use crate::options::{Options, MAX_STARLIGHT};
+use crate::outline::Selection;
+use crate::pacing::FramePacer;
+use crate::picking::{pick, screen_ray, PickTarget, Ray};
+use crate::postprocess::{Effect, PostProcess};
+use crate::present::{PresentMode, PresentThread, Presenter};
+use crate::procedural::{asteroid, asteroid_belt, uv_sphere, BeltRock};
+use crate::profiler::{Profiler, Stage, Stopwatch};
+use crate::projection::Projection;
+use crate::quadtree::QuadSphere;
+use crate::resolution::DynamicResolution;
+use crate::satellite::{BodyNoise, MoonSystem, Satellite, SatelliteMesh};
+use crate::scene_file::SceneFile;
+use crate::season::Orbit;
+use crate::sh::ShIrradiance;
+use crate::shaders::{
+    terran_clouds, AuroraParams, DebugView, GasGiantParams, OceanParams, ShaderType, ShadingModel,
+};
+use crate::shadow::ShadowMap;
+use crate::ship_hud::ShipHud;
+use crate::silhouette::{projected_circle, SilhouetteRegion};
+use crate::simulation::{belt_rotation, Controls, Setting, Simulation};
+use crate::sky::{Backdrop, Sky, NEBULAE};
+use crate::spatial::{Frustum, SpatialIndex};
+use crate::stereo::{Stereo, StereoMode};
+use crate::surface::{SurfaceCache, SurfaceInputs};
+use crate::swapchain::Swapchain;
+use crate::tectonics::Continents;
+#[cfg(feature = "recording")]
+use crate::telemetry::Telemetry;
+use crate::telescope::Telescope;
+use crate::texture::Texture;
+use crate::timeline::Timeline;
+use crate::tonemap::ToneMapping;
+use crate::trails::Trails;
+use crate::transform::{SceneGraph, Transform};
+use crate::transit::LightCurve;
+use crate::vertex::Vertex;
+use crate::weather::WeatherState;
+use crate::{
+    barycenter, ecliptic, gallery, help, impostor, measure, minimap, overdraw, profiler, satellite,
+    stereo, telescope, transit,
+};
+use crate::{
+    create_perspective_matrix, create_view_matrix, create_viewport_matrix, fill_light, render,
+    render_instanced, satellites_of, sun_direction, DepthMode, Uniforms, Viewport, ASTEROID_ORBIT,
+    COMET_ORBIT, PLANET_SPIN, STATION_ORBIT,
+};
+use minifb::Key;
+use nalgebra_glm::{Mat4, Vec3, Vec4};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+use std::f32::consts::PI;
+#[cfg(feature = "diagnostics")]
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+const DIGIT_KEYS: [Key; SLOTS] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+];
+
+// Toward the sun at `time`, and toward its companion when it's one of a
+// binary, whose barycenter then takes the place of the lone sun
+fn star_directions(orbit: &Orbit, binary: Option<Binary>, time: u32) -> (Vec3, Option<Vec3>) {
+    let toward = sun_direction(orbit, time);
+    match binary {
+        Some(binary) => {
+            let (primary, companion) = binary.directions(toward, time);
+            (primary, Some(companion))
+        }
+        None => (toward, None),
+    }
+}
+
+// What the planet gives off as a point light while it's drawn as a star,
+// and how far that reaches: past the asteroid but short of the comet's far
+// end
+const STAR_LIGHT: Vec3 = Vec3::new(2.5, 2.0, 1.4);
+const STAR_RANGE: f32 = 6.0;
+
+// How far from the planet shadows are worked out: past every moon, the
+// asteroid at its farthest and the belt
+const SHADOW_REACH: f32 = 4.0;
+
+// How far from a newly focused body the camera ends up, in its radii
+const FOCUS_DISTANCE: f32 = 4.0;
+
+// Most octaves of noise the shaders add past their own on a close fly-by
+const CLOSE_UP_OCTAVES: u32 = 3;
+
+// Octaves the shaders may add for an eye `clearance` off the surface of a
+// body of `radius`: one each time the distance halves inside a radius, and
+// only where the pixel then resolves them
+fn close_up_octaves(clearance: f32, radius: f32) -> u32 {
+    if clearance <= 0.0 {
+        return CLOSE_UP_OCTAVES;
+    }
+    ((radius / clearance).log2().max(0.0) as u32).min(CLOSE_UP_OCTAVES)
+}
+
+// How long a file that couldn't be read stays named on screen, in seconds
+const ASSET_WARNING_SECONDS: f32 = 10.0;
+
+// The model at `path`, or a plain sphere in its place when it can't be read,
+// with why added to `warnings` so the window can say so instead of the viewer
+// not starting at all
+fn load_model(path: &str, warnings: &mut Vec<Error>) -> Obj {
+    Obj::load(path).unwrap_or_else(|err| {
+        warnings.push(err);
+        Obj::from_vertices(&uv_sphere(1.0, 48, 24))
+    })
+}
+
+// The moon system from the --scene file, once it's been read, or else the
+// built-in one
+fn moons_of(scene_file: Option<&SceneFile>, shader: ShaderType) -> MoonSystem {
+    scene_file
+        .and_then(|file| file.satellites(shader))
+        .unwrap_or_else(|| MoonSystem::BuiltIn(satellites_of(shader)))
+}
+
+// The orbit of the body called `name` around the planet's center, or its
+// parent's for moons of moons, if it's one that keeps to an orbit among
+// `satellites`
+fn orbit_of(name: &str, satellites: &[Satellite]) -> Option<KeplerOrbit> {
+    fn find(satellites: &[Satellite], name: &str) -> Option<KeplerOrbit> {
+        satellites.iter().find_map(|satellite| {
+            (satellite.name == name)
+                .then_some(satellite.orbit)
+                .or_else(|| find(&satellite.satellites, name))
+        })
+    }
+    match name {
+        "Asteroid" => Some(ASTEROID_ORBIT),
+        "Station" => Some(STATION_ORBIT),
+        "Comet" => Some(COMET_ORBIT),
+        _ => find(satellites, name),
+    }
+}
+
+// A camera, the part of the framebuffer it draws into and what the planet
+// is shaded with there. Drawing is clipped to `scissor`, which is normally
+// the viewport but can be narrower so two views share one projection.
+#[derive(Clone, Copy)]
+struct View {
+    eye: Vec3,
+    center: Vec3,
+    up: Vec3,
+    projection: Projection,
+    viewport: Viewport,
+    scissor: Rect,
+    shader: ShaderType,
+}
+// What I keeps the framebuffer at, for crisp integer upscaling
+const RETRO_WIDTH: usize = 320;
+const RETRO_HEIGHT: usize = 240;
+
+// The modifiers held this frame, and what the keys asked of it beyond
+// flipping the viewer's toggles
+struct Keys {
+    ctrl: bool,
+    shift: bool,
+    alt: bool,
+    // Alt+Left or Alt+Right is going through the timeline
+    stepping: bool,
+    // Shift+L: a flash goes off at the selection
+    flash: bool,
+    // Shift+G or --strike-at: a rock streaks in at the planet
+    #[cfg(feature = "physics")]
+    strike: bool,
+}
+
+// Everything the viewer keeps from one frame to the next: the camera, the
+// simulation, what's shown and how, and the framebuffers it's drawn into.
+// A frontend hands it the input since the last frame and shows what comes
+// back, and says its status messages for it.
+pub struct Viewer {
+    options: Options,
+    locale: Locale,
+    running: bool,
+    fullscreen_toggled: bool,
+    messages: Vec<String>,
+    window_size: (usize, usize),
+    shown: Vec<u32>,
+    framebuffer_width: usize,
+    framebuffer_height: usize,
+    swapchain: Swapchain,
+    present_mode: PresentMode,
+    presents: PresentThread,
+    // Back from the present thread with the frame `frame_now` handed over,
+    // for the next frame to start from
+    held: Option<Presenter>,
+    translation: Vec3,
+    scale: f32,
+    camera: Camera,
+    bookmarks: Bookmarks,
+    scene_file: Option<SceneFile>,
+    gallery: Gallery,
+    shader_menu: ShaderMenu,
+    show_help: bool,
+    profiler: Profiler,
+    planet_radius: f32,
+    planet_lods: LodChain,
+    planet_patches: Vec<Patches>,
+    terrain: QuadSphere,
+    moon_radius: f32,
+    moon_lods: LodChain,
+    sphere_radius: f32,
+    sphere_vertex_array: Vec<Vertex>,
+    sphere_antialiasing: bool,
+    sphere_impostor: bool,
+    asteroid_vertex_array: Vec<Vertex>,
+    asteroid_radius: f32,
+    show_asteroid: bool,
+    show_comet: bool,
+    scene: SceneGraph,
+    spacecraft_vertex_array: Vec<Vertex>,
+    spacecraft_radius: f32,
+    show_spacecraft: bool,
+    belt_vertex_array: Vec<Vertex>,
+    belt_rock_radius: f32,
+    belt_rocks: Vec<BeltRock>,
+    belt_local_matrices: Vec<Mat4>,
+    belt_index: SpatialIndex,
+    show_belt: bool,
+    #[cfg(feature = "physics")]
+    whole_belt_index: SpatialIndex,
+    #[cfg(feature = "physics")]
+    physics: bool,
+    #[cfg(feature = "physics")]
+    n_body: bool,
+    selected_rock: Option<usize>,
+    show_labels: bool,
+    show_ecliptic: bool,
+    trails: Trails,
+    show_barycenters: bool,
+    measuring: bool,
+    measurement: Measurement,
+    debug_view: Option<DebugView>,
+    shading: ShadingModel,
+    stereo_mode: Option<StereoMode>,
+    stereo: Stereo,
+    left_eye: Vec<Vec3>,
+    telescope: Option<Telescope>,
+    light_travel: LightTravel,
+    show_doppler: bool,
+    doppler: Doppler,
+    transit: bool,
+    light_curve: LightCurve,
+    barycenter_camera: bool,
+    followed: Option<Cow<'static, str>>,
+    split_screen: bool,
+    compare_shader: Option<ShaderType>,
+    compare_divider: f32,
+    dragging_divider: bool,
+    inset_target: Framebuffer,
+    show_inset: bool,
+    show_minimap: bool,
+    projection: Projection,
+    post_process: PostProcess,
+    accumulation: Accumulation,
+    ship_hud: ShipHud,
+    dither: Dither,
+    tone_mapping: ToneMapping,
+    adaptation: EyeAdaptation,
+    fog: Fog,
+    occlusion: AmbientOcclusion,
+    motion_blur: MotionBlur,
+    nebula: usize,
+    sky: Sky,
+    skybox: Option<Cubemap>,
+    skybox_ambient: Option<ShIrradiance>,
+    #[cfg(feature = "recording")]
+    playback: Option<InputPlayback>,
+    asset_warnings: Vec<String>,
+    launched: Instant,
+    starlight: f32,
+    reflections: Cubemap,
+    backdrops: Vec<Backdrop>,
+    inset_backdrop: Backdrop,
+    clouds: Option<BakedLayer<(u32, WeatherState)>>,
+    surface: Option<SurfaceCache>,
+    body_noise: BodyNoise,
+    orbit: Orbit,
+    continents: Continents,
+    depth_mode: DepthMode,
+    aurora: AuroraParams,
+    gas_giant: GasGiantParams,
+    ocean: OceanParams,
+    shadow_map: Option<ShadowMap>,
+    binary: Option<Binary>,
+    companion_shadow_map: Option<ShadowMap>,
+    generated: GeneratedPlanet,
+    current_shader: ShaderType,
+    selected: Option<Cow<'static, str>>,
+    mouse_was_down: bool,
+    #[cfg(feature = "recording")]
+    telemetry: Option<Telemetry>,
+    rng: StdRng,
+    simulation: Simulation,
+    timeline: Timeline<Simulation, Controls>,
+    jump: Option<u32>,
+    #[cfg(feature = "recording")]
+    session_frame: u32,
+    #[cfg(feature = "recording")]
+    recorder: Option<InputRecorder>,
+    #[cfg(feature = "frame-server")]
+    server: Option<FrameServer>,
+    pacer: FramePacer,
+    resolution: DynamicResolution,
+    budget: FrameBudget,
+    frame_seconds: f32,
+}
+
+impl Viewer {
+    // Frames are scaled for a `window_size` window. `asset_warnings` are the
+    // files that couldn't be read before it was made, like the locale's,
+    // which it names on screen with its own for a while. Fails when a log or
+    // recording it was asked to write can't be created, or the frame server
+    // can't have its port.
+    pub fn new(
+        options: Options,
+        locale: Locale,
+        window_size: (usize, usize),
+        mut asset_warnings: Vec<Error>,
+    ) -> Result<Self> {
+        let (framebuffer_width, framebuffer_height) = options.render_size.unwrap_or(window_size);
+
+        let mut swapchain = Swapchain::new(framebuffer_width, framebuffer_height);
+        swapchain.back.set_background_color(0x000000);
+
+        let presenter = Presenter::new(window_size.0, window_size.1);
+        let present_mode = PresentMode::Direct;
+        // A frame is always with the present thread between one call to
+        // `frame` and the next, starting with the blank one handed over at the
+        // end of this, but for the one `frame_now` waited for
+        let presents = PresentThread::spawn();
+
+        // model position
+        let translation = Vec3::new(0.0, 0.0, 0.0);
+        let scale = 1.0f32;
+
+        // camera parameters
+        let mut camera = Camera::new(
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        camera.damping = options.camera_damping;
+        let bookmarks = Bookmarks::load("camera_bookmarks.txt");
+        let scene_file = options.scene_path.as_deref().map(SceneFile::watch);
+        let gallery = Gallery::new();
+        let shader_menu = ShaderMenu::new();
+        let show_help = false;
+        let profiler = Profiler::new();
+
+        let mut obj = load_model("assets/models/sphere.obj", &mut asset_warnings);
+        obj.subdivide(options.subdivision_level);
+        if let Some(target) = options.target_triangles {
+            obj.decimate(target);
+        }
+        let planet_radius = obj.bounding_radius();
+        let planet_lods = LodChain::build(&obj, 4, 64);
+        let planet_patches: Vec<Patches> = planet_lods
+            .levels()
+            .iter()
+            .map(|level| Patches::build(level))
+            .collect();
+        let terrain = QuadSphere::new(planet_radius);
+
+        let mut moon_obj = load_model("assets/models/moon.obj", &mut asset_warnings);
+        moon_obj.subdivide(options.subdivision_level);
+        if let Some(target) = options.target_triangles {
+            moon_obj.decimate(target);
+        }
+        let moon_radius = moon_obj.bounding_radius();
+        let moon_lods = LodChain::build(&moon_obj, 4, 64);
+        let sphere_radius = 0.5;
+        let sphere_vertex_array = uv_sphere(sphere_radius, 64, 32);
+        let sphere_antialiasing = false;
+        let sphere_impostor = false;
+        let asteroid_vertex_array = asteroid(7, 3);
+        let asteroid_radius = asteroid_vertex_array
+            .iter()
+            .map(|v| v.position.magnitude())
+            .fold(0.0, f32::max);
+        let show_asteroid = false;
+        let show_comet = false;
+        let scene = SceneGraph::default();
+        let spacecraft_vertex_array = spacecraft(9);
+        let spacecraft_radius = spacecraft_vertex_array
+            .iter()
+            .map(|v| v.position.magnitude())
+            .fold(0.0, f32::max);
+        let show_spacecraft = false;
+
+        // Belt rocks never move relative to each other, so the index is built once
+        // in belt space and the whole belt turns as one
+        let belt_vertex_array = asteroid(11, 1);
+        let belt_rock_radius = belt_vertex_array
+            .iter()
+            .map(|v| v.position.magnitude())
+            .fold(0.0, f32::max);
+        let belt_rocks = asteroid_belt(2000, 2.6, 3.4, 0.25, 42);
+        let belt_local_matrices: Vec<Mat4> = belt_rocks
+            .iter()
+            .map(|rock| Transform::from_euler(rock.position, rock.scale, rock.rotation).matrix())
+            .collect();
+        let belt_index = SpatialIndex::build(
+            belt_rocks
+                .iter()
+                .map(|rock| (rock.position, belt_rock_radius * rock.scale))
+                .collect(),
+        );
+        let show_belt = false;
+        // Physics mode knocks belt rocks loose to fall onto the planet, taking
+        // them out of the belt's index. Going back to before a rock fell puts it
+        // back, from a copy of the whole belt.
+        #[cfg(feature = "physics")]
+        let whole_belt_index = belt_index.clone();
+        #[cfg(feature = "physics")]
+        let physics = false;
+        // Moons moved by their gravity instead of along their orbits
+        #[cfg(feature = "physics")]
+        let n_body = false;
+        let selected_rock: Option<usize> = None;
+        let show_labels = true;
+        let show_ecliptic = false;
+        let trails = Trails::new(options.trail_length);
+        let show_barycenters = false;
+        // While measuring, clicks pick the two bodies to measure between rather
+        // than the selection
+        let measuring = false;
+        let measurement = Measurement::default();
+        let debug_view: Option<DebugView> = None;
+        let shading = ShadingModel::Phong;
+        let stereo_mode: Option<StereoMode> = None;
+        let stereo = Stereo {
+            separation: options.eye_separation,
+            convergence: options.convergence,
+            cross_eyed: options.cross_eyed,
+        };
+        // What the left eye saw while the right is drawn, for red-cyan stereo
+        let left_eye: Vec<Vec3> = Vec::new();
+        let telescope: Option<Telescope> = None;
+        let light_travel = LightTravel::new(options.light_speed);
+        // Tints bodies by how fast they're coming or going
+        let show_doppler = false;
+        let doppler = Doppler::new(options.doppler);
+        // Plots the planet's light as bodies pass in front of it
+        let transit = false;
+        let light_curve = LightCurve::new();
+        // Keeps the camera's pivot on the center of mass of the planet and its
+        // moons rather than wherever it was left
+        let barycenter_camera = false;
+        // The body the camera keeps at the center of the view, by name
+        let followed: Option<Cow<'static, str>> = None;
+        let split_screen = false;
+        // Shader pinned to the right of the comparison divider, which sits
+        // `compare_divider` of the way across
+        let compare_shader: Option<ShaderType> = None;
+        let compare_divider = 0.5;
+        let dragging_divider = false;
+        // Off-screen target for a picture-in-picture view over the planet's pole
+        let inset_target = Framebuffer::new(200, 150);
+        let show_inset = false;
+        let show_minimap = false;
+        let projection = Projection::default();
+        let post_process = PostProcess::default();
+        let accumulation = Accumulation::new();
+        let ship_hud = ShipHud::new();
+        let dither = Dither::Off;
+        let tone_mapping = ToneMapping::default();
+        let adaptation = EyeAdaptation {
+            preset: options.exposure_preset,
+            time_constant: options.adaptation_seconds,
+        };
+        let fog = Fog {
+            enabled: false,
+            color: Color::from_hex(options.haze_color).to_vec3(),
+            density: options.haze_density,
+        };
+        let occlusion = AmbientOcclusion::default();
+        let motion_blur = MotionBlur::new();
+        let nebula = 0;
+        let sky = Sky::bake(&NEBULAE[nebula]);
+        // A --skybox takes the nebula's place behind the bodies, in their
+        // reflections and in the light on their night sides
+        let skybox = options
+            .skybox
+            .as_deref()
+            .and_then(|dir| match Cubemap::load(dir) {
+                Ok(skybox) => Some(skybox),
+                Err(err) => {
+                    asset_warnings.push(err);
+                    None
+                }
+            });
+        let skybox_ambient = skybox.as_ref().map(Cubemap::irradiance);
+        // A recording that can't be read leaves the keyboard in charge
+        #[cfg(feature = "recording")]
+        let playback =
+            options
+                .play_path
+                .as_deref()
+                .and_then(|path| match InputPlayback::load(path) {
+                    Ok(playback) => Some(playback),
+                    Err(err) => {
+                        asset_warnings.push(err);
+                        None
+                    }
+                });
+        let asset_warnings: Vec<String> = asset_warnings
+            .iter()
+            .map(|err| locale.format("status.asset_failed", &[err]))
+            .collect();
+        let launched = Instant::now();
+        // How bright that light is, against how it was baked
+        let starlight = options.starlight;
+        let reflections = Cubemap::from_sky(&sky, REFLECTION_SIZE);
+        // The background each view drew last, and the inset's
+        let backdrops: Vec<Backdrop> = Vec::new();
+        let inset_backdrop = Backdrop::new();
+        // Re-baked every --clouds-every frames, or left to the shader at 1
+        let clouds = (options.clouds_every > 1).then(|| BakedLayer::new(options.clouds_every));
+        let surface = (options.surface_every > 1).then(|| SurfaceCache::new(options.surface_every));
+        let body_noise = BodyNoise::new();
+        // Seasons follow the same tilt the planet is drawn with
+        let orbit = Orbit {
+            axial_tilt: PLANET_SPIN.axial_tilt,
+            ..Orbit::default()
+        };
+        // Terran continents, baked once from the planet's plate layout
+        let continents = Continents::generate(5);
+        let depth_mode = DepthMode::ZBuffer;
+        let aurora = AuroraParams::default();
+        let gas_giant = GasGiantParams::default();
+        let ocean = OceanParams::default();
+        // Off with --shadow-map 0
+        let shadow_map =
+            (options.shadow_map_size > 0).then(|| ShadowMap::new(options.shadow_map_size));
+        // A --companion shines on the bodies from beside the sun, and casts
+        // shadows of its own
+        let binary = options.companion.map(|companion| Binary {
+            primary: options.star,
+            companion,
+        });
+        let companion_shadow_map = shadow_map
+            .as_ref()
+            .and(binary)
+            .map(|_| ShadowMap::new(options.shadow_map_size));
+
+        // Random planet mode: R rolls a new seed, --seed starts on a given one
+        let generated = GeneratedPlanet::from_seed(options.planet_seed.unwrap_or(0));
+        let current_shader = if options.planet_seed.is_some() {
+            ShaderType::Generated
+        } else {
+            ShaderType::RockyPlanet
+        };
+
+        let selected: Option<Cow<'static, str>> = None;
+        let mouse_was_down = false;
+
+        #[cfg(feature = "recording")]
+        let telemetry = options
+            .telemetry_path
+            .as_deref()
+            .map(|path| {
+                Telemetry::create(path, options.telemetry_every, &options.telemetry_fields)
+            })
+            .transpose()?;
+
+        // Playback feeds recorded input in place of the window's until it runs
+        // out. Random choices come from one seeded generator, and the seed is
+        // stored with a recording so replaying it makes the same choices.
+        #[cfg(feature = "recording")]
+        let session_seed = playback
+            .as_ref()
+            .map_or_else(|| rand::thread_rng().gen(), |playback| playback.seed);
+        #[cfg(not(feature = "recording"))]
+        let session_seed = rand::thread_rng().gen();
+        let rng = StdRng::seed_from_u64(session_seed);
+        // Everything that moves on its own, with the storms and cloud cover for
+        // the terran planet, and the record T scrubs through to go back to an
+        // earlier frame
+        let simulation = Simulation::new(session_seed.wrapping_add(1));
+        let timeline: Timeline<Simulation, Controls> = Timeline::new();
+        // Frame picked on the timeline, gone back to on the next frame
+        let jump: Option<u32> = None;
+        // Recordings count every frame the window shows, so they line up with
+        // the input even when the simulation jumps or waits
+        #[cfg(feature = "recording")]
+        let session_frame = 0;
+        #[cfg(feature = "recording")]
+        let recorder = options
+            .record_path
+            .as_deref()
+            .map(|path| InputRecorder::create(path, session_seed))
+            .transpose()?;
+
+        #[cfg(feature = "frame-server")]
+        let server = options
+            .serve_port
+            .map(|port| FrameServer::start(port).map_err(|source| Error::Server { port, source }))
+            .transpose()?;
+
+        let pacer = FramePacer::new(options.target_fps);
+        // Off until F7; only scales the full-size framebuffer, not retro mode
+        let resolution = DynamicResolution::new(options.target_fps);
+        let budget = FrameBudget::new(options.triangle_budget, options.fragment_budget);
+        // What one frame stands for when easing the exposure; uncapped counts as
+        // 60 fps
+        let frame_seconds = match options.target_fps {
+            0 => 1.0 / 60.0,
+            fps => 1.0 / fps as f32,
+        };
+        // For the frontend to say as soon as it's asked, with the files
+        // named on screen as well
+        #[cfg(feature = "frame-server")]
+        let serving = options
+            .serve_port
+            .map(|port| locale.format("status.serving", &[&port]));
+        #[cfg(not(feature = "frame-server"))]
+        let serving = None;
+        let messages = asset_warnings.iter().cloned().chain(serving).collect();
+        presents.submit(presenter, swapchain.take_front(), present_mode);
+        Ok(Viewer {
+            options,
+            locale,
+            running: true,
+            fullscreen_toggled: false,
+            messages,
+            window_size,
+            shown: vec![0; window_size.0 * window_size.1],
+            framebuffer_width,
+            framebuffer_height,
+            swapchain,
+            present_mode,
+            presents,
+            held: None,
+            translation,
+            scale,
+            camera,
+            bookmarks,
+            scene_file,
+            gallery,
+            shader_menu,
+            show_help,
+            profiler,
+            planet_radius,
+            planet_lods,
+            planet_patches,
+            terrain,
+            moon_radius,
+            moon_lods,
+            sphere_radius,
+            sphere_vertex_array,
+            sphere_antialiasing,
+            sphere_impostor,
+            asteroid_vertex_array,
+            asteroid_radius,
+            show_asteroid,
+            show_comet,
+            scene,
+            spacecraft_vertex_array,
+            spacecraft_radius,
+            show_spacecraft,
+            belt_vertex_array,
+            belt_rock_radius,
+            belt_rocks,
+            belt_local_matrices,
+            belt_index,
+            show_belt,
+            #[cfg(feature = "physics")]
+            whole_belt_index,
+            #[cfg(feature = "physics")]
+            physics,
+            #[cfg(feature = "physics")]
+            n_body,
+            selected_rock,
+            show_labels,
+            show_ecliptic,
+            trails,
+            show_barycenters,
+            measuring,
+            measurement,
+            debug_view,
+            shading,
+            stereo_mode,
+            stereo,
+            left_eye,
+            telescope,
+            light_travel,
+            show_doppler,
+            doppler,
+            transit,
+            light_curve,
+            barycenter_camera,
+            followed,
+            split_screen,
+            compare_shader,
+            compare_divider,
+            dragging_divider,
+            inset_target,
+            show_inset,
+            show_minimap,
+            projection,
+            post_process,
+            accumulation,
+            ship_hud,
+            dither,
+            tone_mapping,
+            adaptation,
+            fog,
+            occlusion,
+            motion_blur,
+            nebula,
+            sky,
+            skybox,
+            skybox_ambient,
+            #[cfg(feature = "recording")]
+            playback,
+            asset_warnings,
+            launched,
+            starlight,
+            reflections,
+            backdrops,
+            inset_backdrop,
+            clouds,
+            surface,
+            body_noise,
+            orbit,
+            continents,
+            depth_mode,
+            aurora,
+            gas_giant,
+            ocean,
+            shadow_map,
+            binary,
+            companion_shadow_map,
+            generated,
+            current_shader,
+            selected,
+            mouse_was_down,
+            #[cfg(feature = "recording")]
+            telemetry,
+            rng,
+            simulation,
+            timeline,
+            jump,
+            #[cfg(feature = "recording")]
+            session_frame,
+            #[cfg(feature = "recording")]
+            recorder,
+            #[cfg(feature = "frame-server")]
+            server,
+            pacer,
+            resolution,
+            budget,
+            frame_seconds,
+        })
+    }
+
+    // False once Escape has been pressed, on the keyboard or in a playback
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    // Whether F11 was pressed since the last time this was asked, for the
+    // frontend to go over the whole screen or back
+    pub fn take_fullscreen_toggle(&mut self) -> bool {
+        std::mem::take(&mut self.fullscreen_toggled)
+    }
+
+    // What's happened since the last time this was asked that the user
+    // should hear about, for the frontend's `status`
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.messages)
+    }
+
+    // For frames scaled to a window that's now `size`, from the next one on
+    pub fn set_window_size(&mut self, size: (usize, usize)) {
+        self.window_size = size;
+    }
+
+    // Takes the moon systems from the scene file at `path` from the next
+    // frame on, in place of --scene's, and watches it for saves the same way.
+    // One that can't be read or doesn't parse leaves the ones there were.
+    pub fn load_scene(&mut self, path: &str) -> Result<()> {
+        let mut scene_file = SceneFile::watch(path);
+        if let Some(Err(err)) = scene_file.poll() {
+            return Err(err);
+        }
+        self.scene_file = Some(scene_file);
+        Ok(())
+    }
+
+    // Takes in `live`, what the keys and the mouse did since the last frame,
+    // moves everything on a frame and draws it. What comes back is the frame
+    // drawn last time, scaled to the window while this one was being drawn:
+    // the window's size in pixels, 0xRRGGBB, row by row.
+    pub fn frame(&mut self, live: &InputFrame) -> &[u32] {
+        self.draw(live, true);
+        &self.shown
+    }
+
+    // Like `frame`, but what comes back is the frame just drawn, waited for
+    // instead of scaled alongside the next one, and it doesn't hold to
+    // --fps, for a caller that wants each frame as soon as it's done
+    pub fn frame_now(&mut self, live: &InputFrame) -> &[u32] {
+        self.draw(live, false);
+        self.held = Some(self.collect());
+        &self.shown
+    }
+
+    // Waits for the frame with the present thread and keeps it in `shown`,
+    // taking back the framebuffer it was scaled from
+    fn collect(&mut self) -> Presenter {
+        let (presenter, front) = self.presents.finish();
+        self.swapchain.return_front(front);
+        self.shown.clear();
+        self.shown
+            .extend_from_slice(presenter.frame(&self.swapchain.front));
+        #[cfg(feature = "frame-server")]
+        if let Some(server) = &self.server {
+            server.publish(&self.shown, presenter.width, presenter.height);
+        }
+        presenter
+    }
+
+    // Everything `frame` does but hand the frame back, with or without
+    // waiting out the rest of the frame's time at the end
+    fn draw(&mut self, live: &InputFrame, paced: bool) {
+        let frame_start = Instant::now();
+
+        // The frame drawn last time, kept to hand back at the end of this
+        // one, unless `frame_now` already did
+        let mut presenter = match self.held.take() {
+            Some(presenter) => presenter,
+            None => self.collect(),
+        };
+        if (presenter.width, presenter.height) != self.window_size {
+            presenter.resize(self.window_size.0, self.window_size.1);
+        }
+
+        // The scene graph is built afresh every frame, so a saved scene file
+        // takes over from the next one on without touching the camera or the
+        // simulation
+        match self.scene_file.as_mut().and_then(SceneFile::poll) {
+            Some(Ok(())) => self
+                .messages
+                .push(self.locale.get("status.scene_loaded").to_string()),
+            Some(Err(err)) => self
+                .messages
+                .push(self.locale.format("status.scene_failed", &[&err])),
+            None => {}
+        }
+
+        // What the keys are doing, whatever a playback makes of them
+        #[cfg(feature = "recording")]
+        let input = {
+            let recorded = self
+                .playback
+                .as_mut()
+                .and_then(|demo| demo.next_frame(self.session_frame));
+            let input = recorded.unwrap_or_else(|| {
+                if self.playback.take().is_some() {
+                    let finished = self.locale.get("status.playback_finished");
+                    self.messages.push(finished.to_string());
+                }
+                live.clone()
+            });
+            if let Some(recording) = self.recorder.as_mut() {
+                if let Err(err) = recording.record(self.session_frame, &input) {
+                    self.messages
+                        .push(format!("Input recording stopped: {}", err));
+                    self.recorder = None;
+                }
+            }
+            self.session_frame += 1;
+            input
+        };
+        #[cfg(not(feature = "recording"))]
+        let input = live.clone();
+
+        // Escape on the keyboard still quits in the middle of a playback.
+        // The frame goes back to the present thread all the same, for it to
+        // always have one between frames.
+        if input.is_key_down(Key::Escape) || live.is_key_down(Key::Escape) {
+            self.running = false;
+            let front = self.swapchain.take_front();
+            self.presents.submit(presenter, front, self.present_mode);
+            return;
+        }
+
+        // F11 has the frontend swap the window for a borderless one over the
+        // screen or back. The scene renders at the same size either way and
+        // is letterboxed into whichever window it's in.
+        if input.is_key_pressed(Key::F11) {
+            self.fullscreen_toggled = true;
+        }
+
+        // F10 opens the screenshot gallery, which has the keyboard and the
+        // screen to itself until it's closed. The scene waits where it was.
+        if input.is_key_pressed(Key::F10) {
+            self.gallery.toggle();
+        }
+        if self.gallery.is_open() {
+            self.gallery.handle_input(&input);
+            self.gallery.draw(&mut self.swapchain.back, &self.locale);
+            self.swapchain.swap();
+            let front = self.swapchain.take_front();
+            self.presents.submit(presenter, front, self.present_mode);
+            if paced {
+                self.pacer.wait();
+            }
+            return;
+        }
+
+        let keys = self.handle_keys(&input, &mut presenter);
+        let Keys {
+            ctrl,
+            shift,
+            alt,
+            stepping,
+            flash,
+            ..
+        } = keys;
+        let setting = Setting {
+            center: self.translation,
+            scale: self.scale,
+            orbit: self.orbit,
+            planet_radius: self.planet_radius,
+            sphere_radius: self.sphere_radius,
+            belt_rocks: &self.belt_rocks,
+            belt_rock_radius: self.belt_rock_radius,
+        };
+        // A frame picked on the timeline brings back everything as it was
+        // then, the toggles included; otherwise the simulation moves on one
+        // frame with the toggles as they are, unless it's being held still
+        // for the frames to be averaged
+        let rewound = self.jump.take().and_then(|frame| {
+            self.timeline.rewind(frame, |state, controls| {
+                state.step(controls, &setting);
+            })
+        });
+        if let Some(state) = rewound {
+            self.simulation = state;
+            if let Some(recorded) = self.timeline.controls_at(self.simulation.time) {
+                self.current_shader = recorded.shader;
+                self.sphere_antialiasing = recorded.sphere_antialiasing;
+                self.show_comet = recorded.show_comet;
+                self.show_belt = recorded.show_belt;
+                #[cfg(feature = "physics")]
+                {
+                    self.physics = recorded.physics;
+                    self.n_body = recorded.n_body.is_some();
+                }
+            }
+            #[cfg(feature = "physics")]
+            {
+                self.belt_index = self.whole_belt_index.clone();
+                for &rock in &self.simulation.dropped {
+                    self.belt_index.remove(rock);
+                }
+            }
+            if let Some(layer) = self.clouds.as_mut() {
+                layer.clear();
+            }
+            if let Some(cache) = self.surface.as_mut() {
+                cache.clear();
+            }
+        } else if !self.accumulation.enabled {
+            let controls = Controls {
+                shader: self.current_shader,
+                sphere_antialiasing: self.sphere_antialiasing,
+                show_comet: self.show_comet,
+                show_belt: self.show_belt,
+                #[cfg(feature = "physics")]
+                physics: self.physics,
+                #[cfg(feature = "physics")]
+                strike: keys.strike,
+                #[cfg(feature = "physics")]
+                n_body: self
+                    .n_body
+                    .then(|| moons_of(self.scene_file.as_ref(), self.current_shader)),
+            };
+            #[cfg(feature = "physics")]
+            if let Some(rock) = self.simulation.step(&controls, &setting) {
+                self.belt_index.remove(rock);
+            }
+            #[cfg(not(feature = "physics"))]
+            self.simulation.step(&controls, &setting);
+            self.timeline
+                .record(self.simulation.time, controls, &self.simulation);
+        }
+        #[cfg(feature = "physics")]
+        if self
+            .selected_rock
+            .is_some_and(|rock| !self.belt_index.contains(rock))
+        {
+            self.selected = None;
+            self.selected_rock = None;
+        }
+        let time = self.simulation.time;
+        let (light_dir, companion_dir) = star_directions(&self.orbit, self.binary, time);
+        // With the Solar shader the planet is a star, and lights up its
+        // moons and everything else around it too
+        let mut lights = vec![fill_light(light_dir)];
+        if self.current_shader == ShaderType::Solar {
+            lights.push(Light::point(self.translation, STAR_RANGE, STAR_LIGHT));
+        }
+
+        // Clouds are only baked while a terran planet is on screen, and start
+        // over when one comes back so stale ones don't show up in the
+        // meantime
+        if let Some(layer) = self.clouds.as_mut() {
+            if self.current_shader == ShaderType::Terran
+                || self.compare_shader == Some(ShaderType::Terran)
+            {
+                let inputs = (time, self.simulation.weather.state());
+                // The same noise the shader would work them out with
+                let cloud_noise = self.body_noise.shader(ShaderType::Terran);
+                layer.update(inputs, |(time, weather), direction| {
+                    terran_clouds(cloud_noise, weather, direction, *time, 4)
+                });
+            } else {
+                layer.clear();
+            }
+        }
+
+        if !self.shader_menu.is_open() && !stepping {
+            handle_input(&input, &mut self.camera);
+        }
+        self.camera.update();
+
+        let framebuffer = &mut self.swapchain.back;
+        framebuffer.clear();
+        framebuffer.count_overdraw(self.debug_view == Some(DebugView::Overdraw));
+
+        // Place every body for this frame before drawing, so all views and
+        // the close-up camera agree on where things are. Everything orbits in
+        // the planet's frame, which doesn't turn with the planet.
+        self.scene.clear();
+        let system = self.scene.add(None, Transform::at(self.translation));
+        let planet_node = self.scene.add(
+            Some(system),
+            Transform::new(Vec3::zeros(), self.scale, PLANET_SPIN.orientation(time)),
+        );
+        let moons = moons_of(self.scene_file.as_ref(), self.current_shader);
+        self.body_noise.update(&moons);
+        // Where gravity has taken the moons, if it's these it's been moving
+        #[cfg(feature = "physics")]
+        let n_body_offsets = self
+            .simulation
+            .n_body
+            .as_ref()
+            .filter(|n_body| n_body.moons == moons)
+            .map(NBody::offsets);
+        #[cfg(not(feature = "physics"))]
+        let n_body_offsets: Option<Vec<Vec3>> = None;
+        let satellites = match &n_body_offsets {
+            Some(offsets) => {
+                let mut offsets = offsets.iter().copied();
+                satellite::attach_with(&mut self.scene, &moons, system, time, |_| {
+                    offsets.next().unwrap_or_default()
+                })
+            }
+            None => satellite::attach(&mut self.scene, &moons, system, time),
+        };
+
+        let asteroid_scale = 0.12;
+        let tumble = time as f32 * 0.02;
+        let asteroid_node = self.scene.add(
+            Some(system),
+            Transform::from_euler(
+                ASTEROID_ORBIT.position(time),
+                asteroid_scale,
+                Vec3::new(tumble, tumble * 0.7, tumble * 0.3),
+            ),
+        );
+
+        let comet_scale = 0.05;
+        let comet_tumble = time as f32 * 0.01;
+        let comet_node = self.scene.add(
+            Some(system),
+            Transform::from_euler(
+                self.simulation.comet.orbit.position(time),
+                comet_scale,
+                Vec3::new(comet_tumble, comet_tumble * 0.4, 0.0),
+            ),
+        );
+
+        let spacecraft_scale = 0.2;
+        let spacecraft_offset = STATION_ORBIT.position(time);
+        // Keeps the same side of the station facing the planet
+        let spacecraft_heading = -spacecraft_offset.z.atan2(spacecraft_offset.x);
+        let spacecraft_node = self.scene.add(
+            Some(system),
+            Transform::from_euler(
+                spacecraft_offset,
+                spacecraft_scale,
+                Vec3::new(0.3, spacecraft_heading, 0.0),
+            ),
+        );
+
+        let belt_node = self.scene.add(
+            Some(system),
+            Transform::from_euler(Vec3::zeros(), 1.0, belt_rotation(time)),
+        );
+        self.scene.update();
+
+        let satellite_positions: Vec<Vec3> = satellites
+            .iter()
+            .map(|&(_, node)| self.scene.world_position(node))
+            .collect();
+        let systems = barycenter::systems(
+            "Planet".into(),
+            self.translation,
+            1.0,
+            &moons,
+            &satellite_positions,
+        );
+        if let Some(whole) = systems.last().filter(|_| self.barycenter_camera) {
+            self.camera.follow(whole.barycenter);
+        }
+
+        let planet_model_matrix = self.scene.world(planet_node);
+        let mut pick_targets = vec![PickTarget {
+            name: "Planet".into(),
+            center: self.translation,
+            radius: self.planet_radius * self.scale,
+            shader: self.current_shader,
+        }];
+
+        let satellite_radius = |satellite: &Satellite| {
+            let mesh_radius = match satellite.mesh {
+                SatelliteMesh::Moon => self.moon_radius,
+                SatelliteMesh::Asteroid => self.asteroid_radius,
+            };
+            mesh_radius * satellite.scale
+        };
+        for &(satellite, node) in &satellites {
+            pick_targets.push(PickTarget {
+                name: satellite.name.clone(),
+                center: self.scene.world_position(node),
+                radius: satellite_radius(satellite),
+                shader: satellite.shader,
+            });
+        }
+
+        let asteroid_model_matrix = self.scene.world(asteroid_node);
+        let asteroid_translation = self.scene.world_position(asteroid_node);
+        if self.show_asteroid {
+            pick_targets.push(PickTarget {
+                name: "Asteroid".into(),
+                center: asteroid_translation,
+                radius: self.asteroid_radius * asteroid_scale,
+                shader: ShaderType::Moon,
+            });
+        }
+
+        let comet_model_matrix = self.scene.world(comet_node);
+        let comet_translation = self.scene.world_position(comet_node);
+        if self.show_comet {
+            pick_targets.push(PickTarget {
+                name: "Comet".into(),
+                center: comet_translation,
+                radius: self.asteroid_radius * comet_scale,
+                shader: ShaderType::Moon,
+            });
+        }
+
+        let spacecraft_model_matrix = self.scene.world(spacecraft_node);
+        let spacecraft_translation = self.scene.world_position(spacecraft_node);
+        if self.show_spacecraft {
+            pick_targets.push(PickTarget {
+                name: "Station".into(),
+                center: spacecraft_translation,
+                radius: self.spacecraft_radius * spacecraft_scale,
+                shader: ShaderType::Station,
+            });
+        }
+
+        let belt_matrix = self.scene.world(belt_node);
+
+        if !self.show_belt {
+            self.selected_rock = None;
+        }
+        if let Some(rock) = self.selected_rock {
+            let center = belt_matrix * self.belt_rocks[rock].position.push(1.0);
+            pick_targets.push(PickTarget {
+                name: "Belt rock".into(),
+                center: center.xyz(),
+                radius: self.belt_rock_radius * self.belt_rocks[rock].scale,
+                shader: ShaderType::Moon,
+            });
+        }
+        // Tab moves the camera's focus on to the next body in the order
+        // they're listed for picking, and off after the last one, and
+        // Alt+Shift+1..9 focuses one by number. The camera flies over to it
+        // and from then on is carried along its orbit, still orbiting and
+        // zooming around it.
+        let following = self
+            .followed
+            .as_ref()
+            .and_then(|name| pick_targets.iter().position(|t| t.name == *name));
+        let mut refocus = None;
+        if !shift && input.is_key_pressed(Key::Tab) {
+            refocus = Some(following.map_or(0, |index| index + 1));
+        }
+        if alt && shift {
+            for (index, key) in DIGIT_KEYS.into_iter().enumerate() {
+                if input.is_key_pressed(key) && index < pick_targets.len() {
+                    refocus = Some(index);
+                }
+            }
+        }
+        match refocus.map(|index| pick_targets.get(index)) {
+            Some(Some(target)) => {
+                self.followed = Some(target.name.clone());
+                self.barycenter_camera = false;
+                let away = (self.camera.eye - self.camera.center).normalize();
+                let eye = target.center + away * target.radius * FOCUS_DISTANCE;
+                self.camera.fly_to(eye, target.center, self.camera.up);
+                let name = self.locale.body_name(&target.name);
+                self.messages
+                    .push(self.locale.format("status.focused", &[&name]));
+            }
+            Some(None) => {
+                self.followed = None;
+                self.messages
+                    .push(self.locale.get("status.focus_cleared").to_string());
+            }
+            None => match following {
+                Some(index) => self.camera.follow(pick_targets[index].center),
+                // Hidden since it was focused
+                None => self.followed = None,
+            },
+        }
+
+        // The eye can't be flown, zoomed or orbited into a body, nor a body
+        // into the eye
+        self.camera.set_obstacles(
+            pick_targets
+                .iter()
+                .map(|target| (target.center, target.radius)),
+        );
+
+        // The sun's view of the bodies, and the companion's, drawn once for
+        // every view to look up. Belt rocks and falling rocks are too small
+        // next to a texel to cast a shadow worth drawing.
+        let light_space_matrix =
+            ShadowMap::light_space_matrix(light_dir, self.translation, SHADOW_REACH);
+        let companion = self.binary.zip(companion_dir).map(|(binary, direction)| {
+            let light_space_matrix =
+                ShadowMap::light_space_matrix(direction, self.translation, SHADOW_REACH);
+            Companion {
+                direction,
+                star: binary.companion,
+                light_space_matrix,
+                shadow_map: None,
+            }
+        });
+        let cast_shadows = |shadow_map: &mut ShadowMap, light_space_matrix: &Mat4| {
+            let texels =
+                |radius: f32| radius * self.options.shadow_map_size as f32 / (2.0 * SHADOW_REACH);
+            shadow_map.clear(light_space_matrix);
+            let planet_vertex_array = if self.sphere_antialiasing {
+                &self.sphere_vertex_array
+            } else {
+                self.planet_lods
+                    .select(texels(self.planet_radius * self.scale))
+            };
+            shadow_map.draw(
+                light_space_matrix,
+                planet_vertex_array,
+                &planet_model_matrix,
+            );
+            for &(satellite, node) in &satellites {
+                let vertex_array = match satellite.mesh {
+                    SatelliteMesh::Moon => {
+                        self.moon_lods.select(texels(satellite_radius(satellite)))
+                    }
+                    SatelliteMesh::Asteroid => &self.asteroid_vertex_array,
+                };
+                shadow_map.draw(light_space_matrix, vertex_array, &self.scene.world(node));
+            }
+            if self.show_asteroid {
+                let model_matrix = &asteroid_model_matrix;
+                shadow_map.draw(
+                    light_space_matrix,
+                    &self.asteroid_vertex_array,
+                    model_matrix,
+                );
+            }
+            if self.show_comet {
+                let model_matrix = &comet_model_matrix;
+                shadow_map.draw(
+                    light_space_matrix,
+                    &self.asteroid_vertex_array,
+                    model_matrix,
+                );
+            }
+            if self.show_spacecraft {
+                let model_matrix = &spacecraft_model_matrix;
+                shadow_map.draw(
+                    light_space_matrix,
+                    &self.spacecraft_vertex_array,
+                    model_matrix,
+                );
+            }
+        };
+        if let Some(shadow_map) = self.shadow_map.as_mut() {
+            cast_shadows(shadow_map, &light_space_matrix);
+        }
+        if let (Some(shadow_map), Some(companion)) =
+            (self.companion_shadow_map.as_mut(), &companion)
+        {
+            cast_shadows(shadow_map, &companion.light_space_matrix);
+        }
+        let companion = companion.map(|companion| Companion {
+            shadow_map: self.companion_shadow_map.as_ref(),
+            ..companion
+        });
+
+        let (fb_width, fb_height) = (framebuffer.width as f32, framebuffer.height as f32);
+        let whole = Rect {
+            x: 0,
+            y: 0,
+            width: framebuffer.width,
+            height: framebuffer.height,
+        };
+        // The selection, or the planet with nothing selected, is what the
+        // telescope trains on, the split screen closes up on and a flash
+        // goes off at
+        let focus = pick_targets
+            .iter()
+            .find(|t| Some(&t.name) == self.selected.as_ref())
+            .unwrap_or(&pick_targets[0]);
+        if let Some(telescope) = self.telescope.as_mut() {
+            telescope.track(self.camera.eye, focus.center, focus.radius);
+        }
+        self.ship_hud.update(self.camera.eye, focus);
+        self.trails.update(time, &pick_targets);
+        if flash {
+            self.light_travel
+                .emit(focus.name.clone(), focus.center, focus.radius, time);
+        }
+        if self.transit {
+            let bodies = pick_targets[1..].iter().filter(|t| t.name != "Belt rock");
+            self.light_curve.push(transit::brightness(
+                self.camera.eye,
+                &pick_targets[0],
+                bodies,
+            ));
+        }
+        self.light_travel.update(self.camera.eye, time);
+        if self.show_doppler {
+            let bodies = pick_targets.iter().map(|t| (t.name.clone(), t.center));
+            self.doppler.update(self.camera.eye, bodies);
+        }
+        // A fly-by close to a body's surface brings the planes in, and lets
+        // the shaders add octaves of noise as they come to be resolved
+        let (clearance, nearest_radius) = pick_targets
+            .iter()
+            .map(|target| {
+                let distance = (target.center - self.camera.eye).magnitude();
+                (distance - target.radius, target.radius)
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .unwrap_or((f32::INFINITY, 0.0));
+        let close_projection = self.projection.close_to(clearance.max(0.0));
+        let extra_octaves = close_up_octaves(clearance, nearest_radius);
+        let views = if let Some(telescope) = &self.telescope {
+            let distance = (focus.center - self.camera.eye).magnitude();
+            vec![View {
+                eye: self.camera.eye,
+                center: self.camera.eye + telescope.direction * distance,
+                up: self.camera.up,
+                projection: Projection {
+                    fov: telescope.field,
+                    ..close_projection
+                },
+                viewport: Viewport::full(fb_width, fb_height),
+                scissor: whole,
+                shader: self.current_shader,
+            }]
+        } else if let Some(right_shader) = self.compare_shader {
+            let divider = (fb_width * self.compare_divider).round() as usize;
+            vec![
+                View {
+                    eye: self.camera.eye,
+                    center: self.camera.center,
+                    up: self.camera.up,
+                    projection: close_projection,
+                    viewport: Viewport::full(fb_width, fb_height),
+                    scissor: Rect {
+                        width: divider,
+                        ..whole
+                    },
+                    shader: self.current_shader,
+                },
+                View {
+                    eye: self.camera.eye,
+                    center: self.camera.center,
+                    up: self.camera.up,
+                    projection: close_projection,
+                    viewport: Viewport::full(fb_width, fb_height),
+                    scissor: Rect {
+                        x: divider,
+                        width: framebuffer.width - divider,
+                        ..whole
+                    },
+                    shader: right_shader,
+                },
+            ]
+        } else if self.split_screen {
+            // Close-up of the focus seen from the same side as the orbit
+            // camera
+            let closeup_eye =
+                focus.center + (self.camera.eye - focus.center).normalize() * focus.radius * 4.0;
+            let half = (fb_width / 2.0).floor();
+            vec![
+                View {
+                    eye: self.camera.eye,
+                    center: self.camera.center,
+                    up: self.camera.up,
+                    projection: close_projection,
+                    viewport: Viewport::new(0.0, 0.0, half, fb_height),
+                    scissor: Rect {
+                        width: half as usize,
+                        ..whole
+                    },
+                    shader: self.current_shader,
+                },
+                View {
+                    eye: closeup_eye,
+                    center: focus.center,
+                    up: self.camera.up,
+                    projection: close_projection,
+                    viewport: Viewport::new(half, 0.0, fb_width - half, fb_height),
+                    scissor: Rect {
+                        x: half as usize,
+                        width: framebuffer.width - half as usize,
+                        ..whole
+                    },
+                    shader: self.current_shader,
+                },
+            ]
+        } else {
+            vec![View {
+                eye: self.camera.eye,
+                center: self.camera.center,
+                up: self.camera.up,
+                projection: close_projection,
+                viewport: Viewport::full(fb_width, fb_height),
+                scissor: whole,
+                shader: self.current_shader,
+            }]
+        };
+        let views = match self.stereo_mode {
+            // Every view once per eye, all the left eyes first so their
+            // picture can be put aside in one go before the right eyes draw
+            // over it
+            Some(StereoMode::Anaglyph) => {
+                let stereo = &self.stereo;
+                let eye_views = |side: usize| {
+                    views.iter().map(move |view| {
+                        let (eye, center) = stereo.eyes(view.eye, view.center, view.up)[side];
+                        View {
+                            eye,
+                            center,
+                            ..*view
+                        }
+                    })
+                };
+                eye_views(0).chain(eye_views(1)).collect()
+            }
+            Some(StereoMode::SideBySide) => {
+                let view = views[0];
+                let eyes = self.stereo.eyes(view.eye, view.center, view.up);
+                self.stereo
+                    .halves(whole)
+                    .into_iter()
+                    .zip(eyes)
+                    .map(|(half, (eye, center))| View {
+                        eye,
+                        center,
+                        viewport: Viewport::new(
+                            half.x as f32,
+                            half.y as f32,
+                            half.width as f32,
+                            half.height as f32,
+                        ),
+                        scissor: half,
+                        ..view
+                    })
+                    .collect()
+            }
+            None => views,
+        };
+
+        let environment = self.skybox.as_ref().unwrap_or(&self.reflections);
+        let cuts = self.budget.cuts();
+        self.accumulation
+            .start_frame(&self.camera, framebuffer, input.is_anything_pressed());
+        let jitter = self.accumulation.jitter();
+        let scene_uniforms = |view: &View| Uniforms {
+            model_matrix: planet_model_matrix,
+            view_matrix: create_view_matrix(view.eye, view.center, view.up),
+            projection_matrix: jitter_projection(
+                create_perspective_matrix(
+                    &view.projection,
+                    view.viewport.width,
+                    view.viewport.height,
+                    self.options.reversed_z,
+                ),
+                jitter,
+                view.viewport.width,
+                view.viewport.height,
+            ),
+            viewport_matrix: create_viewport_matrix(&view.viewport, self.options.reversed_z),
+            time,
+            noise: self.body_noise.shader(view.shader),
+            aurora: self.aurora,
+            gas_giant: self.gas_giant,
+            ocean: self.ocean,
+            shading: self.shading,
+            light_dir,
+            star: self.options.star,
+            lights: &lights,
+            light_space_matrix,
+            shadow_map: self.shadow_map.as_ref(),
+            companion,
+            extra_octaves,
+            ambient: self
+                .skybox_ambient
+                .unwrap_or(self.sky.ambient)
+                .scaled(self.starlight),
+            environment: Some(environment),
+            particle_stride: cuts.particle_stride,
+            weather: self.simulation.weather.state(),
+            clouds: self.clouds.as_ref().and_then(BakedLayer::map),
+            surface: None,
+            eye: view.eye,
+            season: self.orbit.season(time),
+            continents: &self.continents,
+            #[cfg(feature = "physics")]
+            impacts: &self.simulation.impacts.marks,
+            planet: &self.generated,
+            debug_view: self.debug_view,
+            threads: self.options.threads,
+            reversed_z: self.options.reversed_z,
+        };
+        // This frame's share of the planet's shading, lit as the bake in
+        // progress started out. Impacts flash by too fast to bake and are
+        // marked over the map as it's drawn.
+        if let Some(cache) = self.surface.as_mut() {
+            let current = SurfaceInputs {
+                time,
+                model_matrix: planet_model_matrix,
+                eye: self.camera.eye,
+            };
+            let radius = if self.sphere_antialiasing {
+                self.sphere_radius
+            } else {
+                self.planet_radius
+            };
+            cache.update(self.current_shader, current, radius, |inputs| Uniforms {
+                model_matrix: inputs.model_matrix,
+                time: inputs.time,
+                light_dir: star_directions(&self.orbit, self.binary, inputs.time).0,
+                eye: inputs.eye,
+                #[cfg(feature = "physics")]
+                impacts: &[],
+                debug_view: None,
+                ..scene_uniforms(&views[0])
+            });
+        }
+        let view_uniforms = |view: &View| Uniforms {
+            surface: self
+                .surface
+                .as_ref()
+                .and_then(|cache| cache.map(view.shader)),
+            ..scene_uniforms(view)
+        };
+
+        let mouse_down = input.mouse_down;
+        let mouse = input.mouse_pos.and_then(|(x, y)| {
+            presenter.window_to_framebuffer(x, y, &self.swapchain.front, self.present_mode)
+        });
+
+        // The timeline's bar takes presses first, and the frame a drag along
+        // it ends on is gone back to next frame
+        let pressed = mouse_down && !self.mouse_was_down;
+        // The shader menu comes before anything under it
+        let over_menu = self.shader_menu.is_open()
+            && self.shader_menu.handle_mouse(framebuffer, mouse, pressed);
+        let pressed = pressed && !over_menu;
+        if let Some(frame) = self.timeline.scrub(framebuffer, mouse, mouse_down, pressed) {
+            self.jump = Some(frame);
+        }
+
+        // Pressing on the comparison divider grabs it instead of picking
+        if pressed {
+            self.dragging_divider = !self.timeline.is_scrubbing()
+                && self.compare_shader.is_some()
+                && mouse.is_some_and(|(x, _)| (x - fb_width * self.compare_divider).abs() < 4.0);
+        }
+        if !mouse_down {
+            self.dragging_divider = false;
+        }
+        if let Some((x, _)) = mouse.filter(|_| self.dragging_divider) {
+            self.compare_divider = (x / fb_width).clamp(0.05, 0.95);
+        }
+
+        // Click to select a body by casting a ray through the cursor, using
+        // the camera of whichever view it landed in
+        if pressed && !self.dragging_divider && !self.timeline.is_scrubbing() {
+            let ray = mouse.and_then(|(x, y)| {
+                let view = views.iter().find(|view| view.viewport.contains(x, y))?;
+                let uniforms = view_uniforms(view);
+                screen_ray(
+                    x,
+                    y,
+                    &uniforms.view_matrix,
+                    &uniforms.projection_matrix,
+                    &uniforms.viewport_matrix,
+                    uniforms.reversed_z,
+                )
+            });
+
+            let body_hit = ray.as_ref().and_then(|ray| pick(ray, &pick_targets));
+            // The belt only rotates, so distances along the ray carry over
+            // unchanged into belt space
+            let rock_hit = ray.as_ref().filter(|_| self.show_belt).and_then(|ray| {
+                let to_belt = belt_matrix.transpose();
+                self.belt_index.query_ray(&Ray {
+                    origin: (to_belt * ray.origin.push(1.0)).xyz(),
+                    direction: (to_belt * ray.direction.push(0.0)).xyz(),
+                })
+            });
+
+            match (body_hit, rock_hit) {
+                _ if self.measuring => match body_hit {
+                    Some((target, _)) => self.measurement.pick(target.name.clone()),
+                    None => self.measurement.clear(),
+                },
+                (Some((target, t)), rock) if rock.is_none_or(|(_, rock_t)| t < rock_t) => {
+                    self.selected = Some(target.name.clone());
+                    self.selected_rock = None;
+                    self.messages.push(selected_message(&self.locale, target));
+                }
+                (_, Some((rock, _))) => {
+                    self.selected = Some("Belt rock".into());
+                    self.selected_rock = Some(rock);
+                    self.messages
+                        .push(self.locale.format("status.selected_rock", &[&rock]));
+                }
+                _ => {
+                    self.selected = None;
+                    self.selected_rock = None;
+                    self.messages
+                        .push(self.locale.get("status.selection_cleared").to_string());
+                }
+            }
+        }
+        self.mouse_was_down = mouse_down;
+
+        // Alt+1..9 selects the bodies in the order they're listed for
+        // picking, the planet first
+        if alt {
+            for (index, key) in DIGIT_KEYS.into_iter().enumerate() {
+                let Some(target) = pick_targets.get(index) else {
+                    continue;
+                };
+                if input.is_key_pressed(key) && self.selected.as_ref() != Some(&target.name) {
+                    self.selected = Some(target.name.clone());
+                    self.selected_rock = None;
+                    self.messages.push(selected_message(&self.locale, target));
+                }
+            }
+        }
+
+        // Falling rocks are in world space rather than in the scene graph, but
+        // their matrices are still only built once for all the views
+        #[cfg(feature = "physics")]
+        let impactor_instances: Vec<Instance> = self
+            .simulation
+            .impacts
+            .impactors
+            .iter()
+            .map(|rock| Instance {
+                seed: rock.seed,
+                material: Some(Material::ROCK),
+                ..Instance::new(
+                    Transform::from_euler(rock.position, rock.scale, rock.rotation).matrix(),
+                )
+            })
+            .collect();
+
+        // Close to the surface the planet is drawn from the quadtree instead,
+        // which refines the ground near the eye, with mountains if it's
+        // Terran. It's picked once from the camera for all the views.
+        let planet_eye = planet_model_matrix
+            .try_inverse()
+            .map(|inverse| (inverse * self.camera.eye.push(1.0)).xyz());
+        let close_up = planet_eye.filter(|eye| self.terrain.within_reach(eye));
+        if let Some(eye) = close_up {
+            let continents =
+                (self.current_shader == ShaderType::Terran).then_some(&self.continents);
+            self.terrain.update(&eye, continents);
+        }
+
+        self.backdrops.resize_with(views.len(), Backdrop::new);
+        for (index, view) in views.iter().enumerate() {
+            if self.stereo_mode == Some(StereoMode::Anaglyph) && index == views.len() / 2 {
+                self.left_eye.clone_from(&framebuffer.hdr);
+                framebuffer.clear();
+            }
+            let scissor = view.scissor;
+            framebuffer.set_scissor(scissor.x, scissor.y, scissor.width, scissor.height);
+            let uniforms = view_uniforms(view);
+            let backdrop = &mut self.backdrops[index];
+            match &self.skybox {
+                Some(skybox) => skybox.draw(framebuffer, &uniforms, backdrop),
+                None => self.sky.draw(framebuffer, &uniforms, backdrop),
+            }
+            let mut selection = Selection::new(self.selected.as_deref());
+            let tint = |name: &str| self.doppler.tint(name, self.light_travel.speed);
+
+            // Radius on screen of a sphere, or infinity when the camera is in
+            // it or it's behind
+            let pixels = |center: Vec3, radius: f32| {
+                projected_circle(
+                    Vec4::new(center.x, center.y, center.z, 1.0),
+                    radius,
+                    &uniforms.view_matrix,
+                    &uniforms.projection_matrix,
+                    &uniforms.viewport_matrix,
+                )
+                .map_or(f32::INFINITY, |(_, radius)| radius)
+            };
+            // Small bodies the budget leaves out, never the selected one
+            let skipped = |name: &str| {
+                Some(name) != self.selected.as_deref()
+                    && pick_targets
+                        .iter()
+                        .find(|target| target.name == name)
+                        .is_some_and(|target| {
+                            pixels(target.center, target.radius) < cuts.min_pixels
+                        })
+            };
+
+            framebuffer.set_current_color(0xFFDDDD);
+            let planet_pixels = pixels(self.translation, self.planet_radius * self.scale);
+            // Too small for its surface to show, so not worth its mesh
+            if planet_pixels < DISK_PIXELS {
+                let vertex_array = self.planet_lods.select(planet_pixels * cuts.detail);
+                selection.begin(framebuffer, "Planet", vertex_array, planet_model_matrix);
+                impostor::draw_disk(
+                    framebuffer,
+                    &uniforms,
+                    &Instance {
+                        tint: tint("Planet"),
+                        ..Instance::new(planet_model_matrix)
+                    },
+                    self.planet_radius,
+                    &view.shader,
+                    self.depth_mode,
+                );
+                selection.end(framebuffer);
+            } else if self.sphere_antialiasing {
+                let silhouette = projected_circle(
+                    Vec4::new(
+                        self.translation.x,
+                        self.translation.y,
+                        self.translation.z,
+                        1.0,
+                    ),
+                    self.sphere_radius * self.scale,
+                    &uniforms.view_matrix,
+                    &uniforms.projection_matrix,
+                    &uniforms.viewport_matrix,
+                )
+                .and_then(|(center, radius)| {
+                    SilhouetteRegion::capture(framebuffer, center, radius)
+                });
+
+                selection.begin(
+                    framebuffer,
+                    "Planet",
+                    &self.sphere_vertex_array,
+                    planet_model_matrix,
+                );
+                if self.sphere_impostor {
+                    impostor::draw_sphere(
+                        framebuffer,
+                        &uniforms,
+                        &planet_model_matrix,
+                        self.sphere_radius,
+                        &view.shader,
+                        tint("Planet"),
+                        self.depth_mode,
+                    );
+                } else {
+                    render_instanced(
+                        framebuffer,
+                        &uniforms,
+                        &self.sphere_vertex_array,
+                        &[Instance {
+                            tint: tint("Planet"),
+                            ..Instance::new(planet_model_matrix)
+                        }],
+                        &view.shader,
+                        self.depth_mode,
+                    );
+                }
+
+                if let Some(region) = silhouette {
+                    region.resolve(framebuffer);
+                }
+                selection.end(framebuffer);
+            } else if close_up.is_some() {
+                selection.begin(
+                    framebuffer,
+                    "Planet",
+                    self.terrain.mesh(),
+                    planet_model_matrix,
+                );
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    self.terrain.mesh(),
+                    &[Instance {
+                        tint: tint("Planet"),
+                        ..Instance::new(planet_model_matrix)
+                    }],
+                    &view.shader,
+                    self.depth_mode,
+                );
+                selection.end(framebuffer);
+            } else {
+                let level = self.planet_lods.level(planet_pixels * cuts.detail);
+                let vertex_array = &self.planet_lods.levels()[level];
+                // Only what's above the planet's horizon, which from close
+                // to the surface is a small part of it
+                let visible =
+                    self.planet_patches[level].visible(&planet_model_matrix, uniforms.eye);
+                selection.begin(framebuffer, "Planet", vertex_array, planet_model_matrix);
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    &visible,
+                    &[Instance {
+                        tint: tint("Planet"),
+                        ..Instance::new(planet_model_matrix)
+                    }],
+                    &view.shader,
+                    self.depth_mode,
+                );
+                selection.end(framebuffer);
+            }
+
+            for (index, &(satellite, node)) in satellites.iter().enumerate() {
+                if skipped(&satellite.name) {
+                    continue;
+                }
+                let model_matrix = self.scene.world(node);
+                let center = self.scene.world_position(node);
+                let moon_pixels = pixels(center, satellite_radius(satellite));
+                // Shaded with the satellite's own noise
+                let uniforms = Uniforms {
+                    noise: self.body_noise.of(satellite),
+                    ..view_uniforms(view)
+                };
+                let vertex_array = match satellite.mesh {
+                    SatelliteMesh::Moon => self.moon_lods.select(moon_pixels * cuts.detail),
+                    SatelliteMesh::Asteroid => &self.asteroid_vertex_array,
+                };
+                // A different seed gives each moon its own craters
+                let instance = Instance {
+                    seed: index as u32 * 13,
+                    tint: tint(&satellite.name),
+                    ..Instance::new(model_matrix)
+                };
+
+                selection.begin(framebuffer, &satellite.name, vertex_array, model_matrix);
+                // Only round moons pass for a disk; an asteroid's outline
+                // would show the difference
+                if satellite.mesh == SatelliteMesh::Moon && moon_pixels < DISK_PIXELS {
+                    impostor::draw_disk(
+                        framebuffer,
+                        &uniforms,
+                        &instance,
+                        self.moon_radius,
+                        &satellite.shader,
+                        self.depth_mode,
+                    );
+                } else {
+                    render_instanced(
+                        framebuffer,
+                        &uniforms,
+                        vertex_array,
+                        &[instance],
+                        &satellite.shader,
+                        self.depth_mode,
+                    );
+                }
+                selection.end(framebuffer);
+            }
+
+            if self.show_asteroid && !skipped("Asteroid") {
+                selection.begin(
+                    framebuffer,
+                    "Asteroid",
+                    &self.asteroid_vertex_array,
+                    asteroid_model_matrix,
+                );
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    &self.asteroid_vertex_array,
+                    &[Instance {
+                        seed: 7,
+                        tint: tint("Asteroid"),
+                        ..Instance::new(asteroid_model_matrix)
+                    }],
+                    &ShaderType::Moon,
+                    self.depth_mode,
+                );
+                selection.end(framebuffer);
+            }
+
+            if self.show_belt {
+                let frustum = Frustum::from_matrix(
+                    &(uniforms.projection_matrix * uniforms.view_matrix * belt_matrix),
+                );
+                let visible = self.belt_index.query_frustum(&frustum);
+                let rock_instance = |rock: usize| Instance {
+                    seed: self.belt_rocks[rock].seed,
+                    // Bare rock with a faint sheen, unlike the moon's dust
+                    material: Some(Material::ROCK),
+                    ..Instance::new(belt_matrix * self.belt_local_matrices[rock])
+                };
+                let big_enough = |rock: usize| {
+                    let center = belt_matrix * self.belt_rocks[rock].position.push(1.0);
+                    pixels(
+                        center.xyz(),
+                        self.belt_rock_radius * self.belt_rocks[rock].scale,
+                    ) >= cuts.min_pixels
+                };
+                let instances: Vec<Instance> = visible
+                    .iter()
+                    .filter(|&&rock| Some(rock) != self.selected_rock && big_enough(rock))
+                    .map(|&rock| rock_instance(rock))
+                    .collect();
+
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    &self.belt_vertex_array,
+                    &instances,
+                    &ShaderType::Moon,
+                    self.depth_mode,
+                );
+
+                // The selected rock goes on its own so only it marks the
+                // stencil
+                if let Some(rock) = self.selected_rock.filter(|rock| visible.contains(rock)) {
+                    let instance = rock_instance(rock);
+                    selection.begin(
+                        framebuffer,
+                        "Belt rock",
+                        &self.belt_vertex_array,
+                        instance.model_matrix,
+                    );
+                    render_instanced(
+                        framebuffer,
+                        &uniforms,
+                        &self.belt_vertex_array,
+                        &[instance],
+                        &ShaderType::Moon,
+                        self.depth_mode,
+                    );
+                    selection.end(framebuffer);
+                }
+            }
+
+            #[cfg(feature = "physics")]
+            if !impactor_instances.is_empty() {
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    &self.belt_vertex_array,
+                    &impactor_instances,
+                    &ShaderType::Moon,
+                    self.depth_mode,
+                );
+            }
+            #[cfg(feature = "physics")]
+            self.simulation.impacts.draw_debris(framebuffer, &uniforms);
+
+            if self.show_spacecraft && !skipped("Station") {
+                selection.begin(
+                    framebuffer,
+                    "Station",
+                    &self.spacecraft_vertex_array,
+                    spacecraft_model_matrix,
+                );
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    &self.spacecraft_vertex_array,
+                    &[Instance {
+                        tint: tint("Station"),
+                        ..Instance::new(spacecraft_model_matrix)
+                    }],
+                    &ShaderType::Station,
+                    self.depth_mode,
+                );
+                selection.end(framebuffer);
+            }
+
+            if self.show_ecliptic {
+                let bodies: Vec<Vec3> = pick_targets
+                    .iter()
+                    .filter(|target| target.name != "Planet")
+                    .map(|target| target.center)
+                    .collect();
+                ecliptic::draw(framebuffer, &uniforms, self.translation, &bodies);
+            }
+            self.trails.draw(framebuffer, &uniforms);
+
+            // Glowing particles go last so they blend over everything they're
+            // in front of
+            #[cfg(feature = "particles")]
+            self.simulation.prominences.draw(framebuffer, &uniforms);
+            if self.show_comet && !skipped("Comet") {
+                selection.begin(
+                    framebuffer,
+                    "Comet",
+                    &self.asteroid_vertex_array,
+                    comet_model_matrix,
+                );
+                render_instanced(
+                    framebuffer,
+                    &uniforms,
+                    &self.asteroid_vertex_array,
+                    &[Instance {
+                        seed: 31,
+                        tint: tint("Comet"),
+                        ..Instance::new(comet_model_matrix)
+                    }],
+                    &ShaderType::Moon,
+                    self.depth_mode,
+                );
+                selection.end(framebuffer);
+            }
+            // The tail outlasts its nucleus being left out
+            if self.show_comet {
+                self.simulation.comet.draw_tail(framebuffer, &uniforms);
+            }
+
+            // Timed with the other full-screen passes, to be weighed against
+            // them on the profiler
+            let mut occluding = Stopwatch::new();
+            occluding.time(|| self.occlusion.apply(framebuffer, &uniforms));
+            profiler::add(Stage::Present, occluding.elapsed());
+            // Translucent samples held back while drawing, laid over the
+            // finished view back to front
+            framebuffer.composite_translucent();
+            self.fog
+                .apply(framebuffer, &uniforms, (view.eye - view.center).magnitude());
+            self.motion_blur.apply(framebuffer, &uniforms, index);
+
+            self.light_travel.draw(framebuffer, &uniforms, time);
+
+            if self.show_labels {
+                for target in &pick_targets {
+                    let color = if Some(&target.name) == self.selected.as_ref() {
+                        0xFFD700
+                    } else {
+                        0xFFFFFF
+                    };
+                    draw_label(
+                        framebuffer,
+                        &uniforms,
+                        view.eye,
+                        &self.locale.body_name(&target.name),
+                        target.center,
+                        target.radius,
+                        color,
+                    );
+                }
+            }
+
+            if self.show_barycenters {
+                framebuffer.set_current_color(0xFF9050);
+                for system in &systems {
+                    let primary = self.locale.body_name(&system.primary);
+                    let label = self.locale.format("hud.barycenter", &[&primary]);
+                    barycenter::draw_marker(framebuffer, &uniforms, system.barycenter, &label);
+                }
+            }
+
+            let measured: Vec<Vec3> = self
+                .measurement
+                .bodies
+                .iter()
+                .filter_map(|name| pick_targets.iter().find(|t| t.name == *name))
+                .map(|target| target.center)
+                .collect();
+            if let [from, to] = measured[..] {
+                framebuffer.set_current_color(0x80FFA0);
+                measure::draw(
+                    framebuffer,
+                    &uniforms,
+                    from,
+                    to,
+                    &self.options.unit,
+                    &self.locale,
+                );
+            }
+
+            if let Some(target) = pick_targets
+                .iter()
+                .find(|t| Some(&t.name) == self.selected.as_ref())
+            {
+                selection.draw_outline(framebuffer, &uniforms, target, 0xFFD700);
+            }
+        }
+        framebuffer.reset_scissor();
+        if self.stereo_mode == Some(StereoMode::Anaglyph) {
+            stereo::merge_anaglyph(&self.left_eye, &mut framebuffer.hdr);
+        }
+        if self.debug_view == Some(DebugView::Overdraw) {
+            overdraw::show(framebuffer);
+        }
+        self.accumulation.accumulate(framebuffer);
+
+        // Time moves a frame at a time here, like everything else in the
+        // scene, so adapting takes as many frames whatever the frame rate
+        // turns out to be
+        self.adaptation.update(
+            &mut self.tone_mapping.exposure,
+            framebuffer,
+            self.frame_seconds,
+        );
+
+        if let Some(telescope) = &self.telescope {
+            for view in &views {
+                telescope::draw_reticle(framebuffer, view.scissor, telescope.field, &self.locale);
+            }
+        }
+        for view in &views {
+            let uniforms = view_uniforms(view);
+            self.ship_hud.draw(
+                framebuffer,
+                &uniforms,
+                view.scissor,
+                focus,
+                &self.options.unit,
+                &self.locale,
+            );
+        }
+
+        // Shown so a planet worth keeping can be brought back with --seed
+        if self.current_shader == ShaderType::Generated {
+            framebuffer.set_current_color(0xFFFFFF);
+            let text = self.locale.format("hud.seed", &[&self.generated.seed]);
+            framebuffer.draw_text(8, 8, &text, 1);
+        }
+        // Each line under the ones before it that are showing
+        let mut hud_y = if self.current_shader == ShaderType::Generated {
+            20
+        } else {
+            8
+        };
+        if self.launched.elapsed().as_secs_f32() < ASSET_WARNING_SECONDS {
+            framebuffer.set_current_color(0xFF8040);
+            for warning in &self.asset_warnings {
+                framebuffer.draw_text(8, hud_y, warning, 1);
+                hud_y += 12;
+            }
+        }
+        if self.resolution.enabled && self.present_mode == PresentMode::Direct {
+            let percent = format!("{:.0}", self.resolution.scale * 100.0);
+            let text = self.locale.format("hud.resolution", &[&percent]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if let (step @ 1.., steps) = self.budget.step() {
+            let text = self.locale.format("hud.budget", &[&step, &steps]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        // Counted across the views since the last frame's HUD
+        #[cfg(feature = "diagnostics")]
+        {
+            let rejected = crate::clipping::REJECTED.swap(0, Ordering::Relaxed);
+            if rejected > 0 {
+                let text = self.locale.format("hud.rejected", &[&rejected]);
+                framebuffer.set_current_color(0xFF00FF);
+                framebuffer.draw_text(8, hud_y, &text, 1);
+                hud_y += 12;
+            }
+        }
+        match self.debug_view {
+            Some(DebugView::Overdraw) => {
+                overdraw::draw_legend(framebuffer, &self.locale, 8, hud_y as usize);
+                hud_y += 12;
+            }
+            Some(view) => {
+                let key = format!("debug_view.{}", view.name());
+                let text = self
+                    .locale
+                    .format("hud.debug_view", &[&self.locale.get(&key)]);
+                framebuffer.set_current_color(0xFFFFFF);
+                framebuffer.draw_text(8, hud_y, &text, 1);
+                hud_y += 12;
+            }
+            None => {}
+        }
+        if let Some(name) = &self.followed {
+            let text = self
+                .locale
+                .format("hud.focus", &[&self.locale.body_name(name)]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if self.projection != Projection::default() {
+            let text = self.locale.format(
+                "hud.projection",
+                &[
+                    &format!("{:.0}", self.projection.fov.to_degrees()),
+                    &format!("{:.3}", self.projection.near),
+                    &format!("{:.0}", self.projection.far),
+                ],
+            );
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if self.accumulation.enabled {
+            let text = self
+                .locale
+                .format("hud.accumulation", &[&self.accumulation.frames()]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if self.shading != ShadingModel::Phong {
+            let key = format!("shading.{}", self.shading.name());
+            let name = self.locale.get(&key);
+            let text = self.locale.format("hud.shading", &[&name]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if ctrl && (input.is_key_down(Key::Minus) || input.is_key_down(Key::Equal)) {
+            let text = self
+                .locale
+                .format("hud.starlight", &[&format!("{:.2}", self.starlight)]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if self.show_doppler {
+            let times = format!("{:.2}", self.doppler.exaggeration);
+            let text = self.locale.format("hud.doppler", &[&times]);
+            framebuffer.set_current_color(0xC0C0FF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        if self.measuring {
+            framebuffer.set_current_color(0x80FFA0);
+            framebuffer.draw_text(8, hud_y, self.locale.get("hud.measuring"), 1);
+            hud_y += 12;
+        }
+        if let Some(target) = pick_targets
+            .iter()
+            .find(|t| Some(&t.name) == self.selected.as_ref())
+        {
+            let across = telescope::angular_diameter(self.camera.eye, target.center, target.radius);
+            let name = self.locale.body_name(&target.name);
+            let text = self
+                .locale
+                .format("hud.angular_size", &[&name, &telescope::degrees(across)]);
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+            hud_y += 12;
+        }
+        // How long the latest flash's light has left to go, or took
+        if let Some(flash) = self.light_travel.flashes.last() {
+            let name = self.locale.body_name(&flash.body);
+            let text = match flash.seen {
+                Some(seen) => self
+                    .locale
+                    .format("hud.light_seen", &[&name, &(seen - flash.emitted)]),
+                None => {
+                    let frames = self.light_travel.arrives_in(flash, self.camera.eye, time);
+                    self.locale
+                        .format("hud.light_on_its_way", &[&name, &frames])
+                }
+            };
+            framebuffer.set_current_color(0xFFF0C0);
+            framebuffer.draw_text(8, hud_y, &text, 1);
+        }
+
+        if self.split_screen || self.depth_mode == DepthMode::Split {
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.fill_rect(Rect {
+                x: framebuffer.width / 2,
+                y: 0,
+                width: 1,
+                height: framebuffer.height,
+            });
+        }
+
+        if let Some(right_shader) = self.compare_shader {
+            let divider = (framebuffer.width as f32 * self.compare_divider).round() as usize;
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.fill_rect(Rect {
+                x: divider,
+                y: 0,
+                width: 1,
+                height: framebuffer.height,
+            });
+            let (right_x, label_y) = (divider as i32 + 8, framebuffer.height as i32 - 16);
+            framebuffer.draw_text(8, label_y, &self.locale.shader_name(self.current_shader), 1);
+            framebuffer.draw_text(right_x, label_y, &self.locale.shader_name(right_shader), 1);
+        }
+
+        self.timeline.draw(framebuffer, time, &self.locale);
+        if self.transit {
+            self.light_curve.draw(framebuffer, &self.locale);
+        }
+        if self.show_minimap {
+            // Moons gravity is moving don't keep to an orbit to draw
+            let orbiting = if n_body_offsets.is_some() {
+                &[]
+            } else {
+                &moons[..]
+            };
+            let orbits: Vec<(Vec3, KeplerOrbit)> = pick_targets
+                .iter()
+                .filter_map(|target| {
+                    let orbit = orbit_of(&target.name, orbiting)?;
+                    Some((target.center - orbit.position(time), orbit))
+                })
+                .collect();
+            minimap::draw(
+                framebuffer,
+                &self.locale,
+                &pick_targets,
+                &orbits,
+                self.selected.as_deref(),
+                light_dir,
+                &self.camera,
+                self.projection.fov,
+            );
+        }
+
+        if self.show_inset {
+            self.inset_target.clear();
+            let inset_viewport = Viewport::full(
+                self.inset_target.width as f32,
+                self.inset_target.height as f32,
+            );
+            let inset_eye = self.translation + PLANET_SPIN.axis() * 2.5;
+            let inset_uniforms = Uniforms {
+                model_matrix: planet_model_matrix,
+                view_matrix: create_view_matrix(
+                    inset_eye,
+                    self.translation,
+                    Vec3::new(0.0, 0.0, -1.0),
+                ),
+                projection_matrix: create_perspective_matrix(
+                    &Projection::default(),
+                    inset_viewport.width,
+                    inset_viewport.height,
+                    self.options.reversed_z,
+                ),
+                viewport_matrix: create_viewport_matrix(&inset_viewport, self.options.reversed_z),
+                time,
+                noise: self.body_noise.shader(self.current_shader),
+                aurora: self.aurora,
+                gas_giant: self.gas_giant,
+                ocean: self.ocean,
+                shading: self.shading,
+                light_dir,
+                star: self.options.star,
+                lights: &lights,
+                light_space_matrix,
+                shadow_map: self.shadow_map.as_ref(),
+                companion,
+                extra_octaves: 0,
+                ambient: self
+                    .skybox_ambient
+                    .unwrap_or(self.sky.ambient)
+                    .scaled(self.starlight),
+                environment: Some(environment),
+                particle_stride: cuts.particle_stride,
+                weather: self.simulation.weather.state(),
+                clouds: self.clouds.as_ref().and_then(BakedLayer::map),
+                surface: self
+                    .surface
+                    .as_ref()
+                    .and_then(|cache| cache.map(self.current_shader)),
+                eye: inset_eye,
+                season: self.orbit.season(time),
+                continents: &self.continents,
+                #[cfg(feature = "physics")]
+                impacts: &self.simulation.impacts.marks,
+                planet: &self.generated,
+                // Its framebuffer isn't counted, so it'd only be black
+                debug_view: self.debug_view.filter(|&view| view != DebugView::Overdraw),
+                threads: self.options.threads,
+                reversed_z: self.options.reversed_z,
+            };
+            match &self.skybox {
+                Some(skybox) => skybox.draw(
+                    &mut self.inset_target,
+                    &inset_uniforms,
+                    &mut self.inset_backdrop,
+                ),
+                None => self.sky.draw(
+                    &mut self.inset_target,
+                    &inset_uniforms,
+                    &mut self.inset_backdrop,
+                ),
+            }
+            let inset_pixels = projected_circle(
+                Vec4::new(
+                    self.translation.x,
+                    self.translation.y,
+                    self.translation.z,
+                    1.0,
+                ),
+                self.planet_radius * self.scale,
+                &inset_uniforms.view_matrix,
+                &inset_uniforms.projection_matrix,
+                &inset_uniforms.viewport_matrix,
+            )
+            .map_or(f32::INFINITY, |(_, radius)| radius);
+            let level = self.planet_lods.level(inset_pixels * cuts.detail);
+            render(
+                &mut self.inset_target,
+                &inset_uniforms,
+                &self.planet_patches[level].visible(&planet_model_matrix, inset_eye),
+                &self.current_shader,
+                self.depth_mode,
+            );
+
+            // Second pass: the inset goes into the corner over a white frame,
+            // copied straight across when it fits and sampled as a texture
+            // when it has to be scaled
+            self.inset_target.resolve(&self.tone_mapping, self.dither);
+            let inset_width = framebuffer.width / 4;
+            let inset_height = inset_width * self.inset_target.height / self.inset_target.width;
+            let inset_x = framebuffer.width - inset_width - 8;
+            let inset_y = 8;
+            let frame_rect = Rect {
+                x: inset_x - 1,
+                y: inset_y - 1,
+                width: inset_width + 2,
+                height: inset_height + 2,
+            };
+            framebuffer.set_current_color(0xFFFFFF);
+            framebuffer.fill_rect(frame_rect);
+            if inset_width == self.inset_target.width {
+                let whole = Rect {
+                    x: 0,
+                    y: 0,
+                    width: self.inset_target.width,
+                    height: self.inset_target.height,
+                };
+                framebuffer.blit(&self.inset_target, whole, inset_x, inset_y, None);
+            } else {
+                let mut texture =
+                    Texture::from_framebuffer(&self.inset_target, self.options.inset_filter);
+                texture.generate_mipmaps();
+                framebuffer.draw_texture(&texture, inset_x, inset_y, inset_width, inset_height);
+            }
+            let caption = self.locale.get("hud.north_pole");
+            framebuffer.draw_text(inset_x as i32 + 3, inset_y as i32 + 3, caption, 1);
+
+            // Each half of the split screen gets its own copy
+            if self.split_screen {
+                let left_x = framebuffer.width / 2 - frame_rect.width - 7;
+                framebuffer.copy_region(frame_rect, left_x, frame_rect.y, None);
+            }
+        }
+
+        let mut presenting = Stopwatch::new();
+        presenting.time(|| {
+            framebuffer.resolve(&self.tone_mapping, self.dither);
+            self.post_process.apply(framebuffer, time);
+        });
+
+        if !shift && input.is_key_pressed(Key::F12) {
+            match gallery::save_screenshot(framebuffer) {
+                Ok(path) => self
+                    .messages
+                    .push(self.locale.format("status.screenshot_saved", &[&path])),
+                Err(err) => self
+                    .messages
+                    .push(format!("Couldn't save screenshot: {}", err)),
+            }
+        }
+        // Over everything, and left out of screenshots
+        if self.shader_menu.is_open() {
+            self.shader_menu
+                .draw(framebuffer, &self.locale, self.current_shader);
+        }
+        if self.show_help {
+            help::draw(framebuffer, &self.locale);
+        }
+        if self.profiler.is_enabled() {
+            self.profiler.draw(framebuffer, &self.locale);
+        }
+
+        profiler::add(Stage::Present, presenting.elapsed());
+
+        // Scaled for the window while the rest of the frame goes on, and
+        // handed back at the top of the next
+        self.swapchain.swap();
+        self.presents
+            .submit(presenter, self.swapchain.take_front(), self.present_mode);
+
+        let frame_ms = frame_start.elapsed().as_secs_f32() * 1000.0;
+        if self.present_mode == PresentMode::Direct && self.resolution.update(frame_ms).is_some() {
+            let (width, height) = self
+                .resolution
+                .size(self.framebuffer_width, self.framebuffer_height);
+            self.swapchain.resize(width, height);
+        }
+        self.budget.update();
+        self.profiler.end_frame(frame_ms);
+
+        #[cfg(feature = "recording")]
+        if let Some(log) = self.telemetry.as_mut() {
+            let bodies: Vec<(String, Vec3)> = pick_targets
+                .iter()
+                .map(|target| (target.name.to_string(), target.center))
+                .collect();
+            #[cfg(feature = "physics")]
+            let bodies = [
+                bodies,
+                self.simulation
+                    .impacts
+                    .impactors
+                    .iter()
+                    .map(|rock| (format!("Impactor {}", rock.seed), rock.position))
+                    .collect(),
+            ]
+            .concat();
+            if let Err(err) = log.record(time, frame_ms, self.translation, &bodies) {
+                self.messages.push(format!("Telemetry stopped: {}", err));
+                self.telemetry = None;
+            }
+        }
+
+        if paced {
+            self.pacer.wait();
+        }
+    }
+
+    // Everything the keys switch, step or pick that doesn't wait on where
+    // the bodies are this frame; F8's smoothing goes to `presenter`
+    fn handle_keys(&mut self, input: &InputFrame, presenter: &mut Presenter) -> Keys {
+        // Ctrl+1..9 saves the camera to that slot and Shift+1..9 brings it
+        // back, and Alt+1..9 selects a body further down; with any of them
+        // held the digit doesn't switch shaders
+        let ctrl = input.is_key_down(Key::LeftCtrl) || input.is_key_down(Key::RightCtrl);
+        let shift = input.is_key_down(Key::LeftShift) || input.is_key_down(Key::RightShift);
+        let alt = input.is_key_down(Key::LeftAlt) || input.is_key_down(Key::RightAlt);
+        for (index, key) in DIGIT_KEYS.into_iter().enumerate() {
+            if !input.is_key_pressed(key) {
+                continue;
+            }
+            let slot = index + 1;
+            if ctrl {
+                match self.bookmarks.store(slot, &self.camera) {
+                    Ok(()) => self
+                        .messages
+                        .push(self.locale.format("status.camera_saved", &[&slot])),
+                    Err(err) => self
+                        .messages
+                        .push(format!("Couldn't save camera bookmarks: {}", err)),
+                }
+            } else if shift && !alt {
+                // A saved view is of where things were, so it stops following
+                self.followed = None;
+                if !self.bookmarks.recall(slot, &mut self.camera) {
+                    self.messages
+                        .push(self.locale.format("status.camera_empty", &[&slot]));
+                }
+            }
+        }
+
+        // Shift+Tab lists the shaders to pick from with the arrow keys or the
+        // mouse, for when which digit is which has been forgotten
+        if input.is_key_pressed(Key::Tab) && shift {
+            self.shader_menu.toggle(self.current_shader);
+        } else if self.shader_menu.is_open() {
+            self.shader_menu.handle_keys(input);
+        }
+        if let Some(shader) = self.shader_menu.take_choice() {
+            self.current_shader = shader;
+        }
+        if !ctrl && !shift && !alt {
+            if input.is_key_down(Key::Key1) {
+                self.current_shader = ShaderType::GasGiant;
+            }
+            if input.is_key_down(Key::Key2) {
+                self.current_shader = ShaderType::ColdGasGiant;
+            }
+            if input.is_key_down(Key::Key3) {
+                self.current_shader = ShaderType::Solar;
+            }
+            if input.is_key_down(Key::Key4) {
+                self.current_shader = ShaderType::RockyPlanet;
+            }
+            if input.is_key_down(Key::Key5) {
+                self.current_shader = ShaderType::RockyPlanetVariant;
+            }
+            if input.is_key_down(Key::Key6) {
+                self.current_shader = ShaderType::AlienPlanet;
+            }
+            if input.is_key_down(Key::Key7) {
+                self.current_shader = ShaderType::GlacialTextured;
+            }
+            if input.is_key_down(Key::Key8) {
+                self.current_shader = ShaderType::Terran;
+            }
+            if input.is_key_down(Key::Key9) {
+                self.current_shader = ShaderType::Lava;
+            }
+        }
+        if input.is_key_pressed(Key::R) {
+            self.generated = GeneratedPlanet::from_seed(self.rng.gen_range(0..1_000_000));
+            self.current_shader = ShaderType::Generated;
+            self.messages.push(
+                self.locale
+                    .format("status.generated", &[&self.generated.seed]),
+            );
+        }
+
+        // Toggle low-resolution rendering with crisp integer upscaling, and
+        // Shift+I steps through flat, Gouraud and per-fragment Phong shading
+        if input.is_key_pressed(Key::I) {
+            if shift {
+                self.shading = self.shading.next();
+            } else {
+                self.present_mode = match self.present_mode {
+                    PresentMode::Direct => {
+                        self.swapchain.resize(RETRO_WIDTH, RETRO_HEIGHT);
+                        PresentMode::IntegerScale
+                    }
+                    PresentMode::IntegerScale => {
+                        let (width, height) = self
+                            .resolution
+                            .size(self.framebuffer_width, self.framebuffer_height);
+                        self.swapchain.resize(width, height);
+                        PresentMode::Direct
+                    }
+                };
+                self.swapchain.back.set_background_color(0x000000);
+            }
+        }
+
+        // Swap the planet for an exact procedural sphere with analytic edge
+        // AA, and Shift+K for drawing that sphere by casting a ray per pixel
+        // instead of from its mesh
+        if input.is_key_pressed(Key::K) {
+            if shift {
+                self.sphere_impostor = !self.sphere_impostor;
+                self.sphere_antialiasing |= self.sphere_impostor;
+            } else {
+                self.sphere_antialiasing = !self.sphere_antialiasing;
+            }
+        }
+
+        if input.is_key_pressed(Key::J) {
+            self.show_asteroid = !self.show_asteroid;
+        }
+
+        if input.is_key_pressed(Key::C) {
+            self.show_comet = !self.show_comet;
+        }
+
+        // B shows the belt, Shift+B the trails the bodies leave behind them
+        if input.is_key_pressed(Key::B) {
+            if shift {
+                self.trails.toggle();
+            } else {
+                self.show_belt = !self.show_belt;
+            }
+        }
+
+        // H lists the controls, as F1 does, and Shift+H turns the fog on and
+        // off
+        if input.is_key_pressed(Key::H) {
+            if shift {
+                self.fog.enabled = !self.fog.enabled;
+            } else {
+                self.show_help = !self.show_help;
+            }
+        }
+
+        // G turns physics on and off, Shift+G sends a rock streaking in at
+        // the planet, as --strike-at does on the frames it's given, and
+        // Ctrl+G has the planet and moons pull each other around instead of
+        // keeping to their orbits
+        #[cfg(feature = "physics")]
+        let mut strike = self.options.strikes.contains(&(self.simulation.time + 1));
+        #[cfg(feature = "physics")]
+        if input.is_key_pressed(Key::G) {
+            if ctrl {
+                self.n_body = !self.n_body;
+            } else if shift {
+                strike = true;
+            } else {
+                self.physics = !self.physics;
+            }
+        }
+
+        // O shows the spacecraft, Shift+O darkens the crevices and contacts
+        // with ambient occlusion from the depth buffer
+        if input.is_key_pressed(Key::O) {
+            if shift {
+                self.occlusion.enabled = !self.occlusion.enabled;
+            } else {
+                self.show_spacecraft = !self.show_spacecraft;
+            }
+        }
+
+        // Orbit view on the left, close-up of the selected body on the right
+        if input.is_key_pressed(Key::V) {
+            self.split_screen = !self.split_screen;
+            self.compare_shader = None;
+            self.stereo_mode = self
+                .stereo_mode
+                .filter(|&mode| mode != StereoMode::SideBySide);
+        }
+
+        // Pins the current shader to the right of a divider, so another one
+        // can be picked for the left and compared against it from the same
+        // camera. Drag the divider to move it.
+        if input.is_key_pressed(Key::X) {
+            self.compare_shader = match self.compare_shader {
+                Some(_) => None,
+                None => Some(self.current_shader),
+            };
+            self.split_screen = false;
+            self.stereo_mode = self
+                .stereo_mode
+                .filter(|&mode| mode != StereoMode::SideBySide);
+        }
+
+        // N shows the north pole inset, Shift+N the map of the system
+        if input.is_key_pressed(Key::N) {
+            if shift {
+                self.show_minimap = !self.show_minimap;
+            } else {
+                self.show_inset = !self.show_inset;
+            }
+        }
+
+        // L shows the labels, Shift+L sets off a flash at the selection
+        // whose light takes its time getting to the camera
+        let flash = shift && input.is_key_pressed(Key::L);
+        if !shift && input.is_key_pressed(Key::L) {
+            self.show_labels = !self.show_labels;
+        }
+
+        // Y shows the ecliptic, Shift+Y the Doppler tint, which Shift with
+        // - and = then turns down and up in place of the exposure
+        if input.is_key_pressed(Key::Y) {
+            if shift {
+                self.show_doppler = !self.show_doppler;
+                self.doppler.clear();
+            } else {
+                self.show_ecliptic = !self.show_ecliptic;
+            }
+        }
+
+        // F trains the telescope on the focus, Shift+F flies the camera like
+        // a ship, with its heading and speed read out over the view
+        if input.is_key_pressed(Key::F) {
+            if shift {
+                self.ship_hud.toggle();
+            } else {
+                self.telescope = match self.telescope {
+                    Some(_) => None,
+                    None => Some(Telescope::new(
+                        self.camera.center - self.camera.eye,
+                        self.projection.fov,
+                    )),
+                };
+            }
+        }
+
+        // U measures between bodies, Shift+U steps through showing normals,
+        // texture coordinates and overdraw instead of the shading
+        if input.is_key_pressed(Key::U) {
+            if shift {
+                self.debug_view = DebugView::cycle(self.debug_view);
+            } else {
+                self.measuring = !self.measuring;
+                self.measurement.clear();
+            }
+        }
+
+        // Z for red-cyan stereo, Shift+Z for the eyes side by side, which
+        // takes the whole screen from split and compared views
+        if input.is_key_pressed(Key::Z) {
+            let mode = if shift {
+                self.split_screen = false;
+                self.compare_shader = None;
+                StereoMode::SideBySide
+            } else {
+                StereoMode::Anaglyph
+            };
+            self.stereo_mode = (self.stereo_mode != Some(mode)).then_some(mode);
+        }
+
+        // M marks the barycenters, Shift+M orbits the whole system's
+        if input.is_key_pressed(Key::M) {
+            if shift {
+                self.barycenter_camera = !self.barycenter_camera;
+                self.followed = None;
+            } else {
+                self.show_barycenters = !self.show_barycenters;
+            }
+        }
+
+        // T opens the timeline. Shift+T watches the selection's orbit edge
+        // on, or the first moon's or else the asteroid's, and graphs the
+        // planet's light dipping as bodies cross it.
+        if input.is_key_pressed(Key::T) {
+            if shift {
+                self.transit = !self.transit;
+                self.light_curve.clear();
+                if self.transit {
+                    let moons = moons_of(self.scene_file.as_ref(), self.current_shader);
+                    let orbit = self
+                        .selected
+                        .as_deref()
+                        .and_then(|name| orbit_of(name, &moons))
+                        .or_else(|| moons.first().map(|s| s.orbit))
+                        .unwrap_or(ASTEROID_ORBIT);
+                    let distance = orbit.semi_major_axis * 3.0;
+                    let (eye, up) = transit::edge_on(self.translation, orbit.normal(), distance);
+                    self.camera.fly_to(eye, self.translation, up);
+                    self.followed = None;
+                }
+            } else {
+                self.timeline.toggle();
+            }
+        }
+        // With it open, Alt+Left and Alt+Right held go back and on through
+        // the frames kept, ten at a time with Shift, in place of turning the
+        // camera
+        let stepping = alt && self.timeline.is_open();
+        if stepping {
+            let frames = if shift { 10 } else { 1 };
+            for (key, frames) in [(Key::Left, -frames), (Key::Right, frames)] {
+                if input.is_key_down(key) {
+                    self.jump = self
+                        .timeline
+                        .step_from(self.simulation.time, frames)
+                        .or(self.jump);
+                }
+            }
+        }
+
+        // Full-screen effects applied to the finished frame. Shift+F1 smears
+        // each view along the camera's motion instead, and F1 on its own is
+        // the help, like H.
+        if input.is_key_pressed(Key::F1) {
+            if shift {
+                self.motion_blur.toggle();
+            } else {
+                self.show_help = !self.show_help;
+            }
+        }
+        if input.is_key_pressed(Key::F2) {
+            if shift {
+                self.post_process.toggle(Effect::Vignette);
+            } else {
+                self.post_process.toggle(Effect::ChromaticAberration);
+            }
+        }
+        if input.is_key_pressed(Key::F3) {
+            self.post_process.toggle(Effect::FilmGrain);
+        }
+        // Shift+F12 holds everything still and averages the frames into a
+        // clean picture to take with F12
+        if shift && input.is_key_pressed(Key::F12) {
+            self.accumulation.toggle();
+        }
+
+        // Cycle dithering: off, 4x4 Bayer, 8x8 Bayer, blue noise
+        if input.is_key_pressed(Key::F4) {
+            self.dither = self.dither.next();
+        }
+
+        // Tone mapping operator and exposure for the HDR scene buffer
+        if input.is_key_pressed(Key::F5) {
+            self.tone_mapping.next_operator();
+        }
+        // F9 steps through the automatic exposure presets; adjusting the
+        // exposure by hand takes over from wherever it had got to
+        if input.is_key_pressed(Key::F9) {
+            self.adaptation.preset = ExposurePreset::cycle(self.adaptation.preset);
+            match self.adaptation.preset {
+                Some(preset) => {
+                    let key = format!("exposure.{}", preset.name());
+                    let name = self.locale.get(&key);
+                    self.messages
+                        .push(self.locale.format("status.exposure_auto", &[&name]));
+                }
+                None => self
+                    .messages
+                    .push(self.locale.get("status.exposure_manual").to_string()),
+            }
+        }
+        // Held to keep going: the field of view on [ and ], and the near
+        // plane on , and ., or with Shift the far one
+        if input.is_key_down(Key::LeftBracket) {
+            self.projection.widen(-0.5);
+        }
+        if input.is_key_down(Key::RightBracket) {
+            self.projection.widen(0.5);
+        }
+        for (key, factor) in [(Key::Comma, 1.0 / 1.03), (Key::Period, 1.03)] {
+            if input.is_key_down(key) {
+                if shift {
+                    self.projection.scale_far(factor);
+                } else {
+                    self.projection.scale_near(factor);
+                }
+            }
+        }
+        if self.show_doppler && shift {
+            if input.is_key_down(Key::Minus) {
+                self.doppler.exaggeration = (self.doppler.exaggeration / 1.02).max(0.01);
+            }
+            if input.is_key_down(Key::Equal) {
+                self.doppler.exaggeration = (self.doppler.exaggeration * 1.02).min(10.0);
+            }
+        } else if ctrl {
+            // Ctrl with them turns the starlight down and up, all the way off
+            if input.is_key_down(Key::Minus) {
+                self.starlight = (self.starlight - 0.02).max(0.0);
+            }
+            if input.is_key_down(Key::Equal) {
+                self.starlight = (self.starlight + 0.02).min(MAX_STARLIGHT);
+            }
+        } else {
+            if input.is_key_down(Key::Minus) || input.is_key_down(Key::Equal) {
+                self.adaptation.preset = None;
+            }
+            if input.is_key_down(Key::Minus) {
+                self.tone_mapping.exposure = (self.tone_mapping.exposure / 1.02).max(0.05);
+            }
+            if input.is_key_down(Key::Equal) {
+                self.tone_mapping.exposure = (self.tone_mapping.exposure * 1.02).min(20.0);
+            }
+        }
+
+        // Switch nebula; its lighting is re-baked so the planets pick up the tint
+        if input.is_key_pressed(Key::F6) {
+            self.nebula = (self.nebula + 1) % NEBULAE.len();
+            self.sky = Sky::bake(&NEBULAE[self.nebula]);
+            self.reflections = Cubemap::from_sky(&self.sky, REFLECTION_SIZE);
+            self.backdrops.clear();
+            self.inset_backdrop = Backdrop::new();
+        }
+
+        // Dynamic resolution, and smoothing when the smaller frames are
+        // stretched to the window
+        if input.is_key_pressed(Key::F7) {
+            self.resolution.enabled = !self.resolution.enabled;
+            if !self.resolution.enabled {
+                self.resolution.reset();
+                if self.present_mode == PresentMode::Direct {
+                    self.swapchain
+                        .resize(self.framebuffer_width, self.framebuffer_height);
+                }
+            }
+        }
+        if input.is_key_pressed(Key::F8) {
+            presenter.bilinear = !presenter.bilinear;
+        }
+
+        // P cycles the depth modes, Shift+P shows what each stage of the
+        // pipeline takes
+        if input.is_key_pressed(Key::P) {
+            if shift {
+                self.profiler.toggle();
+            } else {
+                self.depth_mode = match self.depth_mode {
+                    DepthMode::ZBuffer => DepthMode::Painter,
+                    DepthMode::Painter => DepthMode::Split,
+                    DepthMode::Split => DepthMode::ZBuffer,
+                };
+            }
+        }
+
+        Keys {
+            ctrl,
+            shift,
+            alt,
+            stepping,
+            flash,
+            #[cfg(feature = "physics")]
+            strike,
+        }
+    }
+}
+
+// What the console says when a body is picked, e.g. "Selected Planet (Terran
+// planet)"
+fn selected_message(locale: &Locale, target: &PickTarget) -> String {
+    let name = locale.body_name(&target.name);
+    let shader = locale.shader_name(target.shader);
+    locale.format("status.selected", &[&name, &shader])
+}
+
+fn handle_input(input: &InputFrame, camera: &mut Camera) {
+    let movement_speed = 1.0;
+    let rotation_speed = PI / 50.0;
+    let zoom_speed = 0.1;
+
+    //  camera orbit controls, eased in and out by the camera's damping
+    if input.is_key_down(Key::Left) {
+        camera.push(rotation_speed, 0.0, 0.0);
+    }
+    if input.is_key_down(Key::Right) {
+        camera.push(-rotation_speed, 0.0, 0.0);
+    }
+    if input.is_key_down(Key::W) {
+        camera.push(0.0, -rotation_speed, 0.0);
+    }
+    if input.is_key_down(Key::S) {
+        camera.push(0.0, rotation_speed, 0.0);
+    }
+
+    // Camera movement controls
+    let mut movement = Vec3::new(0.0, 0.0, 0.0);
+    if input.is_key_down(Key::A) {
+        movement.x -= movement_speed;
+    }
+    if input.is_key_down(Key::D) {
+        movement.x += movement_speed;
+    }
+    if input.is_key_down(Key::Q) {
+        movement.y += movement_speed;
+    }
+    if input.is_key_down(Key::E) {
+        movement.y -= movement_speed;
+    }
+    if movement.magnitude() > 0.0 {
+        camera.move_center(movement);
+    }
+
+    // Camera zoom controls
+    if input.is_key_down(Key::Up) {
+        camera.push(0.0, 0.0, zoom_speed);
+    }
+    if input.is_key_down(Key::Down) {
+        camera.push(0.0, 0.0, -zoom_speed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: (usize, usize) = (160, 120);
+
+    fn viewer() -> Viewer {
+        let options = Options {
+            render_size: Some((80, 60)),
+            ..Options::default()
+        };
+        Viewer::new(options, Locale::english(), WINDOW, Vec::new()).unwrap()
+    }
+
+    fn pressed(key: Key) -> InputFrame {
+        InputFrame::from_keys(vec![key], vec![key])
+    }
+
+    #[test]
+    fn hands_back_the_last_frame_scaled_to_the_window() {
+        let mut viewer = viewer();
+        // Nothing's been drawn before the first
+        let first = viewer.frame(&InputFrame::default());
+        assert_eq!(first.len(), WINDOW.0 * WINDOW.1);
+        assert!(first.iter().all(|&pixel| pixel == 0));
+        let second = viewer.frame(&InputFrame::default());
+        assert_eq!(second.len(), WINDOW.0 * WINDOW.1);
+        assert!(second.iter().any(|&pixel| pixel != 0));
+
+        viewer.set_window_size((40, 30));
+        viewer.frame(&InputFrame::default());
+        assert_eq!(viewer.frame(&InputFrame::default()).len(), 40 * 30);
+    }
+
+    #[test]
+    fn hands_back_the_frame_just_drawn_when_asked_to_wait() {
+        let mut viewer = viewer();
+        let drawn = viewer.frame_now(&InputFrame::default()).to_vec();
+        assert_eq!(drawn.len(), WINDOW.0 * WINDOW.1);
+        assert!(drawn.iter().any(|&pixel| pixel != 0));
+        // Already handed over, so it's still the last one drawn
+        assert_eq!(viewer.frame(&InputFrame::default()), drawn);
+
+        viewer.set_window_size((40, 30));
+        assert_eq!(viewer.frame_now(&InputFrame::default()).len(), 40 * 30);
+    }
+
+    #[test]
+    fn passes_on_what_the_keys_asked_for() {
+        let mut viewer = viewer();
+        assert!(viewer.take_messages().is_empty());
+        viewer.frame(&pressed(Key::R));
+        let messages = viewer.take_messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("Generated planet"), "{messages:?}");
+        assert!(viewer.take_messages().is_empty());
+
+        viewer.frame(&pressed(Key::F11));
+        assert!(viewer.take_fullscreen_toggle());
+        assert!(!viewer.take_fullscreen_toggle());
+
+        assert!(viewer.is_running());
+        viewer.frame(&pressed(Key::Escape));
+        assert!(!viewer.is_running());
+    }
+
+    #[cfg(feature = "recording")]
+    #[test]
+    fn says_which_log_it_could_not_create() {
+        let options = Options {
+            record_path: Some("no_such_dir/input.txt".to_string()),
+            ..Options::default()
+        };
+        let Err(err) = Viewer::new(options, Locale::english(), WINDOW, Vec::new()) else {
+            panic!("made a viewer with nowhere to record to");
+        };
+        assert!(err.to_string().starts_with("no_such_dir/input.txt: "), "{err}");
+    }
+}
@@ -0,0 +1,199 @@
+use nalgebra_glm::{rotate_vec3, Vec3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+pub const STORM_CELLS: usize = 6;
+
+// A cyclone drifting over the surface, in the planet's object space so it
+// turns with the planet
+#[derive(Clone, Copy)]
+pub struct StormCell {
+    latitude: f32,
+    longitude: f32,
+    // Angular radius in radians
+    pub radius: f32,
+    age: f32,
+    lifetime: f32,
+    // +1 counterclockwise (northern hemisphere), -1 clockwise
+    spin: f32,
+}
+
+impl StormCell {
+    fn spawn(rng: &mut StdRng) -> Self {
+        let hemisphere = if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+        StormCell {
+            latitude: hemisphere * rng.gen_range(0.1..0.95),
+            longitude: rng.gen_range(0.0..TAU),
+            radius: rng.gen_range(0.15..0.35),
+            age: 0.0,
+            lifetime: rng.gen_range(900.0..2400.0),
+            spin: hemisphere,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        surface_point(self.latitude, self.longitude)
+    }
+
+    // Grows in, peaks halfway through its life and fades out
+    pub fn strength(&self) -> f32 {
+        (self.age / self.lifetime * std::f32::consts::PI)
+            .sin()
+            .max(0.0)
+    }
+}
+
+fn surface_point(latitude: f32, longitude: f32) -> Vec3 {
+    let (sin_lat, cos_lat) = latitude.sin_cos();
+    let (sin_lon, cos_lon) = longitude.sin_cos();
+    Vec3::new(cos_lat * cos_lon, sin_lat, cos_lat * sin_lon)
+}
+
+// Radians a frame the great storm drifts along its latitude, once around the
+// planet in about 20000 frames
+const VORTEX_DRIFT: f32 = 0.0003;
+// Radians a frame its oval turns on itself
+const VORTEX_TURN: f32 = 0.006;
+// How far the winds around the oval are wound into it, in radians at its
+// center, and out to how many oval radii they are
+const VORTEX_TWIST: f32 = 2.5;
+const VORTEX_REACH: f32 = 2.5;
+// Width of the oval east to west over its height
+const VORTEX_STRETCH: f32 = 1.7;
+
+// A gas giant's great storm: one oval vortex, far bigger than the cells and
+// held at its latitude for as long as the planet lasts. It turns on itself,
+// winds the bands around it into it and drifts slowly around the planet.
+#[derive(Clone, Copy)]
+pub struct Vortex {
+    latitude: f32,
+    longitude: f32,
+    // Angular half-height of the oval in radians
+    radius: f32,
+    // How far the oval has turned since the start, in radians
+    angle: f32,
+}
+
+impl Vortex {
+    // How far `position` is from the storm's center in oval radii, and
+    // where to read the bands for it at any distance from the planet's
+    // center: twisted around the storm as it winds them in outside the oval,
+    // and turned with it inside
+    pub fn swirl(&self, position: &Vec3) -> (f32, Vec3, Vec3) {
+        let center = surface_point(self.latitude, self.longitude);
+        let direction = position.normalize();
+        if direction.dot(&center) <= 0.0 {
+            return (f32::INFINITY, *position, *position);
+        }
+        let east = Vec3::y().cross(&center).normalize();
+        let north = center.cross(&east);
+        let offset = direction - center;
+        let (x, y) = (offset.dot(&east) / VORTEX_STRETCH, offset.dot(&north));
+        // Distance from the center in oval radii
+        let reach = (x * x + y * y).sqrt() / self.radius;
+
+        let falloff = (1.0 - (reach / VORTEX_REACH).powi(2)).max(0.0);
+        let twist = VORTEX_TWIST * falloff * falloff;
+        let around = rotate_vec3(position, twist, &center);
+        let within = rotate_vec3(position, twist + self.angle, &center);
+        (reach, around, within)
+    }
+
+    fn update(&mut self) {
+        self.longitude = (self.longitude + VORTEX_DRIFT).rem_euclid(TAU);
+        self.angle = (self.angle + VORTEX_TURN).rem_euclid(TAU);
+    }
+}
+
+// Snapshot of the weather handed to the shaders each frame
+#[derive(Clone, Copy)]
+pub struct WeatherState {
+    // Fraction of the sky that is overcast away from storms, 0 to 1
+    pub coverage: f32,
+    pub cells: [StormCell; STORM_CELLS],
+    pub vortex: Vortex,
+}
+
+impl WeatherState {
+    // Storm density at a surface direction, and that direction twisted around
+    // every nearby storm so cloud noise sampled there spirals into the eye
+    pub fn storms(&self, direction: &Vec3) -> (f32, Vec3) {
+        let mut density = 0.0;
+        let mut swirled = *direction;
+        for cell in &self.cells {
+            let center = cell.center();
+            let distance = direction.dot(&center).clamp(-1.0, 1.0).acos();
+            let weight = (-(distance / cell.radius).powi(2)).exp() * cell.strength();
+            if weight < 1e-3 {
+                continue;
+            }
+            density += weight;
+            swirled = rotate_vec3(&swirled, cell.spin * weight * 4.0, &center);
+        }
+        (density.min(1.0), swirled)
+    }
+}
+
+// Per-planet weather simulation, stepped once per frame
+#[derive(Clone)]
+pub struct Weather {
+    state: WeatherState,
+    coverage_target: f32,
+    rng: StdRng,
+}
+
+impl Weather {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        // Start mid-life at random so the storms don't all form together
+        let cells = [(); STORM_CELLS].map(|_| {
+            let mut cell = StormCell::spawn(&mut rng);
+            cell.age = rng.gen_range(0.0..cell.lifetime);
+            cell
+        });
+        let coverage = rng.gen_range(0.35..0.65);
+        // South of the equator like Jupiter's, turning counterclockwise
+        let vortex = Vortex {
+            latitude: -rng.gen_range(0.3..0.45),
+            longitude: rng.gen_range(0.0..TAU),
+            radius: 0.2,
+            angle: 0.0,
+        };
+        Weather {
+            state: WeatherState {
+                coverage,
+                cells,
+                vortex,
+            },
+            coverage_target: coverage,
+            rng,
+        }
+    }
+
+    pub fn update(&mut self) {
+        for cell in &mut self.state.cells {
+            // Zonal winds: easterlies near the equator, westerlies at mid
+            // latitudes, plus a slow drift toward the pole
+            let wind = 0.004 * (3.0 * cell.latitude).cos();
+            cell.longitude = (cell.longitude + wind).rem_euclid(TAU);
+            cell.latitude =
+                (cell.latitude + 0.0002 * cell.latitude.signum()).clamp(-FRAC_PI_2, FRAC_PI_2);
+
+            cell.age += 1.0;
+            if cell.age >= cell.lifetime {
+                *cell = StormCell::spawn(&mut self.rng);
+                self.coverage_target = self.rng.gen_range(0.3..0.7);
+            }
+        }
+
+        self.state.vortex.update();
+
+        let coverage = &mut self.state.coverage;
+        *coverage += (self.coverage_target - *coverage) * 0.001;
+    }
+
+    pub fn state(&self) -> WeatherState {
+        self.state
+    }
+}